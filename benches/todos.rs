@@ -0,0 +1,270 @@
+//! Benchmarks for the hot paths of listing todos: decoding Scylla rows,
+//! serializing large responses and paging through the repository.
+//! Run with `cargo bench --features seed`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{DateTime, Utc};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use futures_util::stream::{self, TryStreamExt};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use scylla::frame::response::result::{CqlValue, Row};
+use scylla::frame::value::CqlTimestamp;
+use simple_api_actix_web::config::PaginationConfig;
+use simple_api_actix_web::decoding::RowDecoding;
+use simple_api_actix_web::fixtures;
+use simple_api_actix_web::model::Todo;
+use simple_api_actix_web::pagination::QueryOptions;
+use simple_api_actix_web::repository::{
+    decode_todos, decode_window, InMemoryTodoRepository, ListOptions, TodoRepository, TodoScope,
+    TodoSort, TodoVisibility,
+};
+use simple_api_actix_web::response::{Link, PageLinks, TodoListResponse, TodoRepresentation};
+use simple_api_actix_web::titles;
+use simple_api_actix_web::versioning::ApiVersion;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+/// Table sizes for reading a page out of the whole table.
+const TABLE_SIZES: [usize; 2] = [10_000, 100_000];
+
+/// Bytes allocated now, and the most since last reset.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, keeping count of the bytes allocated so the
+/// benchmarks can tell how much memory a listing takes.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(now, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// The most memory `f` had allocated at once, on top of what was already.
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    drop(f());
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+/// Every todo, `limit` at a time from `offset`, oldest first.
+fn list_options(offset: usize, limit: usize) -> ListOptions {
+    ListOptions {
+        offset,
+        limit,
+        include_archived: true,
+        scope: TodoScope::All,
+        visibility: TodoVisibility::All,
+        owner_id: None,
+        created_after: None,
+        created_before: None,
+        project_id: None,
+        status: None,
+        assignee_id: None,
+        completed: None,
+        due_after: None,
+        due_before: None,
+        sort: TodoSort::CreatedAt,
+    }
+}
+
+fn sample_todos(count: usize) -> Vec<Todo> {
+    fixtures::todos(&mut StdRng::seed_from_u64(42), count)
+}
+
+/// The row Scylla returns for `todo`, in `SELECT_TODOS` column order.
+fn to_row(todo: &Todo) -> Row {
+    let timestamp = |at: Option<DateTime<Utc>>| {
+        at.map(|at| CqlValue::Timestamp(CqlTimestamp(at.timestamp_millis())))
+    };
+    Row {
+        columns: vec![
+            todo.id.map(|id| CqlValue::Uuid(id.0)),
+            Some(CqlValue::Text(todo.title.clone())),
+            Some(CqlValue::Text(todo.content.clone())),
+            todo.completed.map(CqlValue::Boolean),
+            timestamp(todo.created_at),
+            timestamp(todo.updated_at),
+            todo.archived.map(CqlValue::Boolean),
+            timestamp(todo.due_at),
+            todo.recurrence
+                .as_ref()
+                .map(|recurrence| CqlValue::Text(serde_json::to_string(recurrence).unwrap())),
+            todo.series_id.map(|id| CqlValue::Uuid(id.0)),
+            todo.next_occurrence_id.map(|id| CqlValue::Uuid(id.0)),
+            timestamp(todo.remind_at),
+            timestamp(todo.reminder_sent_at),
+            todo.owner_id.clone().map(CqlValue::Text),
+            todo.workspace_id.clone().map(CqlValue::Text),
+            todo.project_id.clone().map(CqlValue::Text),
+            todo.position.map(CqlValue::BigInt),
+            todo.status
+                .map(|status| CqlValue::Text(status.as_str().to_string())),
+            todo.assignee_id.clone().map(CqlValue::Text),
+            Some(CqlValue::Text(titles::key(&todo.title))),
+            // `TTL(title)`; the sample todos never expire.
+            Some(CqlValue::Int(0)),
+        ],
+    }
+}
+
+fn row_decoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_todo_rows");
+    for size in SIZES {
+        let todos = sample_todos(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &todos, |b, todos| {
+            let decoded = decode_todos(todos.iter().map(to_row).collect(), RowDecoding::Strict);
+            assert_eq!(decoded.unwrap().len(), todos.len());
+            b.iter_batched(
+                // Rows are not Clone, so each batch converts the todos anew.
+                || todos.iter().map(to_row).collect(),
+                |rows| decode_todos(rows, RowDecoding::Strict).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn list_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize_todo_list");
+    for version in ApiVersion::ALL {
+        for size in SIZES {
+            let response = TodoListResponse {
+                status: "success".to_string(),
+                results: size,
+                links: PageLinks {
+                    self_link: Link::get("/api/todos?limit=100".to_string()),
+                    next: Some(Link::get("/api/todos?limit=100&page=2".to_string())),
+                    prev: None,
+                },
+                todos: sample_todos(size)
+                    .into_iter()
+                    .map(|todo| TodoRepresentation::new(version, todo))
+                    .collect(),
+                warnings: Vec::new(),
+            };
+            group.throughput(Throughput::Elements(size as u64));
+            group.bench_with_input(
+                BenchmarkId::new(version.as_str(), size),
+                &response,
+                |b, response| b.iter(|| serde_json::to_vec(response).unwrap()),
+            );
+        }
+    }
+    group.finish();
+}
+
+fn pagination(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let repository = InMemoryTodoRepository::new();
+    runtime
+        .block_on(repository.insert_many(&sample_todos(10_000)))
+        .unwrap();
+    let config = PaginationConfig {
+        default_limit: 10,
+        max_limit: 100,
+    };
+
+    let mut group = c.benchmark_group("list_page");
+    for page in [1, 10, 100] {
+        let query = format!("page={}&limit=100", page);
+        group.bench_with_input(BenchmarkId::from_parameter(page), &query, |b, query| {
+            b.to_async(&runtime).iter(|| async {
+                let opts = QueryOptions::parse(query, &config).unwrap();
+                repository
+                    .list(&ListOptions {
+                        include_archived: opts.include_archived,
+                        ..list_options(opts.offset, opts.limit)
+                    })
+                    .await
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// The first page of a Scylla table: decoding every row and then taking
+/// the page, against decoding rows only until the page is full. The peak
+/// memory of each is printed before they are timed.
+fn table_page(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let options = list_options(0, 100);
+    let decode_all = |rows: Vec<Row>| -> Vec<Todo> {
+        decode_todos(rows, RowDecoding::Strict)
+            .unwrap()
+            .into_iter()
+            .filter(|todo| options.matches(todo))
+            .skip(options.offset)
+            .take(options.limit)
+            .collect()
+    };
+    let decode_page = |rows: Vec<Row>| -> Vec<Todo> {
+        let rows = stream::iter(rows.into_iter().map(Ok::<_, Infallible>));
+        runtime
+            .block_on(decode_window(rows, &options, RowDecoding::Strict).try_collect())
+            .unwrap()
+    };
+
+    let mut group = c.benchmark_group("table_page");
+    for size in TABLE_SIZES {
+        let todos = sample_todos(size);
+        // Rows are not Clone, so every run converts the todos anew.
+        let rows = || todos.iter().map(to_row).collect::<Vec<Row>>();
+        // Timing a listing that fails, or finds nothing, would measure nothing.
+        let ids = |todos: Vec<Todo>| todos.into_iter().map(|todo| todo.id).collect::<Vec<_>>();
+        let expected = ids(decode_all(rows()));
+        assert_eq!(expected.len(), options.limit);
+        assert_eq!(ids(decode_page(rows())), expected);
+        let (all, page) = (rows(), rows());
+        println!(
+            "table_page/{}: peak allocation {} KiB decoding every row, {} KiB decoding the page",
+            size,
+            peak_allocation(|| decode_all(all)) / 1024,
+            peak_allocation(|| decode_page(page)) / 1024
+        );
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_function(BenchmarkId::new("decode_all", size), |b| {
+            b.iter_batched(rows, decode_all, BatchSize::LargeInput)
+        });
+        group.bench_function(BenchmarkId::new("decode_window", size), |b| {
+            b.iter_batched(rows, decode_page, BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    row_decoding,
+    list_serialization,
+    pagination,
+    table_page
+);
+criterion_main!(benches);
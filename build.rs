@@ -0,0 +1,9 @@
+use vergen::EmitBuilder;
+
+/// Sets `VERGEN_GIT_SHA` to the commit being built, for `build_info`.
+/// Outside a git checkout, as in some container builds, vergen warns and
+/// sets a placeholder rather than failing the build.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    EmitBuilder::builder().git_sha(true).emit()?;
+    Ok(())
+}
@@ -0,0 +1,6 @@
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS due_at TIMESTAMPTZ;
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS recurrence TEXT;
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS series_id TEXT;
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS next_occurrence_id TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_series_id_idx ON todos (series_id);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS todo_acl (
+    todo_id TEXT NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    shared_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (todo_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS todo_acl_user_id_idx ON todo_acl (user_id);
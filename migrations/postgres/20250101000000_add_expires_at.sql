@@ -0,0 +1,3 @@
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS todos_expires_at_idx ON todos (expires_at) WHERE expires_at IS NOT NULL;
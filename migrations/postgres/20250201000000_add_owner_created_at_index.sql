@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS todos_owner_id_idx;
+
+CREATE INDEX IF NOT EXISTS todos_owner_created_at_idx ON todos (owner_id, created_at, id);
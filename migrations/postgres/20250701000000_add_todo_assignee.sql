@@ -0,0 +1,3 @@
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS assignee_id TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_assignee_id_idx ON todos (assignee_id);
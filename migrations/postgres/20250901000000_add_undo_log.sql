@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS undo_log (
+    id TEXT PRIMARY KEY,
+    actor_id TEXT NOT NULL,
+    action TEXT NOT NULL,
+    todos TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS undo_log_actor_id_created_at_idx ON undo_log (actor_id, created_at);
@@ -0,0 +1,12 @@
+-- One row per todo, kept as a tombstone once the todo is deleted.
+CREATE TABLE IF NOT EXISTS todo_changes (
+    todo_id TEXT PRIMARY KEY,
+    token BIGINT NOT NULL,
+    workspace_id TEXT,
+    owner_id TEXT,
+    deleted BOOLEAN NOT NULL,
+    version TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS todo_changes_token_idx ON todo_changes (token);
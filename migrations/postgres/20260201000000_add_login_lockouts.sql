@@ -0,0 +1,12 @@
+-- Keys are `account:<email>` or `client:<address>`.
+CREATE TABLE IF NOT EXISTS login_failures (
+    lock_key TEXT NOT NULL,
+    failed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS login_failures_lock_key_idx ON login_failures (lock_key, failed_at);
+
+CREATE TABLE IF NOT EXISTS login_lockouts (
+    lock_key TEXT PRIMARY KEY,
+    locked_until TIMESTAMPTZ NOT NULL
+);
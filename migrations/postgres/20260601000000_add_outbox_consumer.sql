@@ -0,0 +1,6 @@
+-- Each entry is for one consumer; those written before were all for the
+-- external event sink.
+ALTER TABLE outbox ADD COLUMN consumer TEXT NOT NULL DEFAULT 'sink';
+
+DROP INDEX IF EXISTS outbox_pending_idx;
+CREATE INDEX IF NOT EXISTS outbox_pending_idx ON outbox (consumer, created_at) WHERE delivered_at IS NULL;
@@ -0,0 +1,8 @@
+-- Titles are unique up to Unicode composition, whitespace and case; the
+-- key is the title in that form. `todo-admin reindex` rewrites any key
+-- whose lowercasing differs from the application's.
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS title_key TEXT;
+
+UPDATE todos SET title_key = lower(btrim(regexp_replace(normalize(title, NFC), '\s+', ' ', 'g')));
+
+CREATE INDEX IF NOT EXISTS todos_title_key_idx ON todos (title_key);
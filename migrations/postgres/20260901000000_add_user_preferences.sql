@@ -0,0 +1,2 @@
+ALTER TABLE user_settings ADD COLUMN IF NOT EXISTS page_size INTEGER;
+ALTER TABLE user_settings ADD COLUMN IF NOT EXISTS default_sort TEXT;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS maintenance (
+    id TEXT PRIMARY KEY,
+    message TEXT NOT NULL,
+    retry_after_secs INTEGER NOT NULL,
+    allow_reads BOOLEAN NOT NULL,
+    started_by TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL
+);
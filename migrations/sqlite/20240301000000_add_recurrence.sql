@@ -0,0 +1,6 @@
+ALTER TABLE todos ADD COLUMN due_at TEXT;
+ALTER TABLE todos ADD COLUMN recurrence TEXT;
+ALTER TABLE todos ADD COLUMN series_id TEXT;
+ALTER TABLE todos ADD COLUMN next_occurrence_id TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_series_id_idx ON todos (series_id);
@@ -0,0 +1,4 @@
+ALTER TABLE todos ADD COLUMN remind_at TEXT;
+ALTER TABLE todos ADD COLUMN reminder_sent_at TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_remind_at_idx ON todos (remind_at);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS notification_settings (
+    email TEXT PRIMARY KEY NOT NULL,
+    reminders BOOLEAN NOT NULL DEFAULT FALSE,
+    digest BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY NOT NULL,
+    email TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+ALTER TABLE todos ADD COLUMN owner_id TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_owner_id_idx ON todos (owner_id);
@@ -0,0 +1,3 @@
+ALTER TABLE todos ADD COLUMN expires_at TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_expires_at_idx ON todos (expires_at);
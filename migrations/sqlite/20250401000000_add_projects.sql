@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS projects (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    workspace_id TEXT,
+    owner_id TEXT,
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS projects_workspace_id_created_at_idx ON projects (workspace_id, created_at);
+
+ALTER TABLE todos ADD COLUMN project_id TEXT;
+
+CREATE INDEX IF NOT EXISTS todos_project_id_idx ON todos (project_id);
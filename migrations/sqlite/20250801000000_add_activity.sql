@@ -0,0 +1,13 @@
+-- No foreign key to todos: a user's feed keeps entries for deleted todos.
+CREATE TABLE IF NOT EXISTS activity (
+    id TEXT PRIMARY KEY NOT NULL,
+    todo_id TEXT NOT NULL,
+    actor_id TEXT,
+    kind TEXT NOT NULL,
+    detail TEXT,
+    occurred_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS activity_todo_id_occurred_at_idx ON activity (todo_id, occurred_at);
+
+CREATE INDEX IF NOT EXISTS activity_actor_id_occurred_at_idx ON activity (actor_id, occurred_at);
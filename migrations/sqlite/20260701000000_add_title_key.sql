@@ -0,0 +1,8 @@
+-- Titles are unique up to Unicode composition, whitespace and case; the
+-- key is the title in that form. SQLite can only trim and lowercase ASCII,
+-- so run `todo-admin reindex` to finish the keys of existing todos.
+ALTER TABLE todos ADD COLUMN title_key TEXT;
+
+UPDATE todos SET title_key = lower(trim(title));
+
+CREATE INDEX IF NOT EXISTS todos_title_key_idx ON todos (title_key);
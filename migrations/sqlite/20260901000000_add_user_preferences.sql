@@ -0,0 +1,2 @@
+ALTER TABLE user_settings ADD COLUMN page_size INTEGER;
+ALTER TABLE user_settings ADD COLUMN default_sort TEXT;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN display_name TEXT;
+ALTER TABLE users ADD COLUMN avatar_url TEXT;
+
+CREATE INDEX IF NOT EXISTS user_identities_user_id_idx ON user_identities (user_id);
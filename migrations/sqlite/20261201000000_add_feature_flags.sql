@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS feature_flags (
+    name TEXT PRIMARY KEY NOT NULL,
+    enabled BOOLEAN NOT NULL
+);
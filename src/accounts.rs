@@ -0,0 +1,124 @@
+//! Erasing an account at its user's request. Their personal todos go, with
+//! their comments, shares and attachments, as do workspaces nobody else is
+//! a member of. Todos they created in shared workspaces belong to the
+//! team, so they stay; like comments and activity elsewhere, they keep
+//! only the user's ID, which no longer leads to an email address or name.
+
+use chrono::Utc;
+use futures_util::TryStreamExt;
+
+use crate::assignments;
+use crate::error::AppError;
+use crate::model::{AppState, Todo, TodoId, User, Workspace, WorkspaceRole};
+use crate::repository::{ListOptions, TodoFilter, TodoScope, TodoSort, TodoVisibility};
+use crate::todos;
+
+/// Every todo matching `scope`, `owner_id` and `assignee_id`, archived
+/// ones included.
+async fn find_todos(
+    data: &AppState,
+    scope: TodoScope,
+    owner_id: Option<&str>,
+    assignee_id: Option<&str>,
+) -> Result<Vec<Todo>, AppError> {
+    let options = ListOptions {
+        offset: 0,
+        // Everything; SQL backends bind the limit as an i64.
+        limit: i64::MAX as usize,
+        include_archived: true,
+        scope,
+        visibility: TodoVisibility::All,
+        owner_id: owner_id.map(str::to_string),
+        created_after: None,
+        created_before: None,
+        project_id: None,
+        status: None,
+        assignee_id: assignee_id.map(str::to_string),
+        completed: None,
+        due_after: None,
+        due_before: None,
+        sort: TodoSort::default(),
+    };
+    Ok(data.todos.stream(&options).await?.try_collect().await?)
+}
+
+/// Splits the user's workspaces into those only they belong to and those
+/// they share. Refuses with a 409 while they are the last owner of a
+/// shared one, which would be left without anyone to run it.
+async fn workspaces_of(
+    data: &AppState,
+    user: &User,
+) -> Result<(Vec<Workspace>, Vec<Workspace>), AppError> {
+    let (mut own, mut shared) = (Vec::new(), Vec::new());
+    for workspace in data.workspaces.list_for_user(&user.id).await? {
+        let members = data.workspaces.list_members(&workspace.id).await?;
+        if members.iter().all(|member| member.user_id == user.id) {
+            own.push(workspace);
+            continue;
+        }
+        let other_owner = members
+            .iter()
+            .any(|member| member.role == WorkspaceRole::Owner && member.user_id != user.id);
+        if !other_owner {
+            return Err(AppError::Conflict(format!(
+                "Hand over ownership of workspace '{}' before deleting the account",
+                workspace.name
+            )));
+        }
+        shared.push(workspace);
+    }
+    Ok((own, shared))
+}
+
+/// Erases `user` as described in the module docs. Returns how many todos
+/// were deleted.
+pub async fn erase(data: &AppState, user: &User) -> Result<usize, AppError> {
+    let (own, shared) = workspaces_of(data, user).await?;
+
+    let mut ids: Vec<TodoId> = find_todos(data, TodoScope::Personal, Some(&user.id), None)
+        .await?
+        .into_iter()
+        .filter_map(|todo| todo.id)
+        .collect();
+    for workspace in &own {
+        ids.extend(
+            data.todos
+                .find_ids(&TodoFilter {
+                    scope: TodoScope::Workspace(workspace.id.clone()),
+                    ..TodoFilter::default()
+                })
+                .await?,
+        );
+    }
+    if !ids.is_empty() {
+        todos::delete_all(data, &ids, Some(user.id.clone())).await?;
+    }
+    for workspace in &own {
+        data.workspaces.delete(&workspace.id).await?;
+    }
+    for workspace in &shared {
+        data.workspaces
+            .remove_member(&workspace.id, &user.id)
+            .await?;
+    }
+
+    let now = Utc::now();
+    for mut todo in find_todos(data, TodoScope::All, None, Some(&user.id)).await? {
+        let id = todo.id.expect("stored todos have an ID");
+        assignments::assign(data, &id, &mut todo, None, Some(&user.id), now).await?;
+    }
+
+    data.exports.delete(&user.id).await?;
+    data.refresh_tokens.end_all(&user.id).await?;
+    data.user_settings.delete(&user.id).await?;
+    data.notification_settings.delete(&user.email).await?;
+    data.users.delete(&user.id).await?;
+
+    log::info!(
+        "event=user_erased user_id={} todos_deleted={} workspaces_deleted={}",
+        user.id,
+        ids.len(),
+        own.len()
+    );
+    Ok(ids.len())
+}
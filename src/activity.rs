@@ -0,0 +1,90 @@
+//! The activity log behind the per-todo and per-user feeds. Entries are
+//! written after the change they describe has been stored and never fail
+//! it: those recorded from domain events are retried through the outbox,
+//! and a failure to record anything else is logged.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::events::{DomainEvent, EventHandler, HandlerError};
+use crate::model::{Activity, ActivityKind, AppState, TodoId};
+use crate::repository::{ActivityRepository, RepositoryError};
+
+/// Records that `actor_id` did `kind` to the todo `todo_id`. Creating,
+/// retitling and completing todos are recorded from their domain events
+/// by [`ActivityRecorder`]; this is for the rest.
+pub async fn record(
+    data: &AppState,
+    todo_id: &TodoId,
+    actor_id: Option<&str>,
+    kind: ActivityKind,
+    detail: Option<String>,
+) {
+    if let Err(e) = insert(data.activity.as_ref(), todo_id, actor_id, kind, detail).await {
+        log::warn!(
+            "event=activity_record_failed todo_id={} kind={} error=\"{}\"",
+            todo_id,
+            kind.as_str(),
+            e
+        );
+    }
+}
+
+async fn insert(
+    repository: &dyn ActivityRepository,
+    todo_id: &TodoId,
+    actor_id: Option<&str>,
+    kind: ActivityKind,
+    detail: Option<String>,
+) -> Result<(), RepositoryError> {
+    let activity = Activity {
+        id: Uuid::new_v4().to_string(),
+        todo_id: *todo_id,
+        actor_id: actor_id.map(str::to_string),
+        kind,
+        detail,
+        occurred_at: Utc::now(),
+    };
+    repository.insert(&activity).await
+}
+
+/// Writes the activity entries for todo lifecycle events.
+pub struct ActivityRecorder {
+    pub repository: Arc<dyn ActivityRepository>,
+}
+
+#[async_trait]
+impl EventHandler for ActivityRecorder {
+    fn name(&self) -> &'static str {
+        "activity"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), HandlerError> {
+        let (kind, actor_id, detail) = match event {
+            DomainEvent::TodoCreated { todo, actor_id } => {
+                (ActivityKind::Created, actor_id, Some(todo.title.clone()))
+            }
+            DomainEvent::TodoUpdated {
+                title: Some(title),
+                actor_id,
+                ..
+            } => (ActivityKind::TitleEdited, actor_id, Some(title.clone())),
+            DomainEvent::TodoCompleted { actor_id, .. } => {
+                (ActivityKind::Completed, actor_id, None)
+            }
+            DomainEvent::TodoUpdated { .. } | DomainEvent::TodoDeleted { .. } => return Ok(()),
+        };
+        insert(
+            self.repository.as_ref(),
+            &event.todo_id(),
+            actor_id.as_deref(),
+            kind,
+            detail,
+        )
+        .await
+        .map_err(|e| HandlerError(e.to_string()))
+    }
+}
@@ -0,0 +1,482 @@
+use std::sync::Arc;
+
+use crate::concurrency::ConcurrencyLimiter;
+use crate::config::{Config, StorageBackend};
+use crate::decoding::RowDecoding;
+use crate::diagnostics::Diagnostics;
+use crate::events::EventBus;
+use crate::features::FeatureFlags;
+use crate::jobs::JobQueue;
+use crate::leader::{self, LeaderElector};
+use crate::maintenance::MaintenanceMode;
+use crate::markdown::RenderCache;
+use crate::metrics::QueryMetrics;
+use crate::model::AppState;
+use crate::repository::{
+    ChangeRepository, CoalescingTodoRepository, EncryptedTodoRepository,
+    InMemoryActivityRepository, InMemoryAttachmentRepository, InMemoryChangeRepository,
+    InMemoryCommentRepository, InMemoryFeatureFlagRepository, InMemoryJobRepository,
+    InMemoryLeaseRepository, InMemoryLoginAttemptRepository, InMemoryMaintenanceRepository,
+    InMemoryNotificationSettingsRepository, InMemoryOutboxRepository,
+    InMemoryPasswordResetRepository, InMemoryProjectRepository, InMemoryRefreshTokenRepository,
+    InMemorySchemaRepository, InMemorySessionRepository, InMemoryTemplateRepository,
+    InMemoryTodoAclRepository, InMemoryTodoRepository, InMemoryUndoRepository,
+    InMemoryUserRepository, InMemoryUserSettingsRepository, InMemoryWebhookRepository,
+    InMemoryWorkspaceRepository, Repositories, Resilience, ResilientRepository,
+    ScyllaTodoRepository, SyncedTodoRepository, TodoRepository,
+};
+use crate::sanitize::Sanitizer;
+use crate::scheduler::Scheduler;
+use crate::scheduling::RecurrenceScheduler;
+use crate::secrets::{self, Secrets};
+use crate::undo::UndoLog;
+use crate::{
+    activity, auth, blobs, bridge, db, digest, encryption, expiry, exports, lockout, migrations,
+    notifier, oauth, outbox, password_reset, reminders, retention, signing, stats, totp, webhooks,
+};
+
+pub async fn create_repositories(
+    config: &Config,
+    query_metrics: Arc<QueryMetrics>,
+    secrets: &Secrets,
+) -> std::io::Result<Repositories> {
+    match config.storage.backend {
+        StorageBackend::Scylla => {
+            // Connect to Scylla
+            let session = db::connect_with_retry(&config.database, secrets)
+                .await
+                .map_err(|e| {
+                    std::io::Error::other(format!("Failed to connect to Scylla: {}", e))
+                })?;
+            log::info!("event=database_connected backend=scylla");
+            let session = Arc::new(session);
+            query_metrics.watch_cluster(session.clone());
+
+            if let Some(replication) = &config.database.bootstrap {
+                migrations::create_keyspace(&session, replication)
+                    .await
+                    .map_err(|e| {
+                        std::io::Error::other(format!("Failed to create the keyspace: {}", e))
+                    })?;
+            }
+            if config.database.migrate_on_start {
+                let applied = migrations::run(&session).await.map_err(|e| {
+                    std::io::Error::other(format!("Failed to run migrations: {}", e))
+                })?;
+                if !applied.is_empty() {
+                    log::info!("event=migrations_applied versions={:?}", applied);
+                }
+            }
+
+            let repository = ScyllaTodoRepository::new(
+                session,
+                config.database.consistency,
+                RowDecoding::new(config.database.strict_decoding),
+            );
+            let resilience = Arc::new(Resilience::new(&config.database, query_metrics));
+            let changes: Arc<dyn ChangeRepository> = guarded(repository.changes(), &resilience);
+            Ok(Repositories {
+                webhooks: guarded(repository.webhooks(), &resilience),
+                notification_settings: guarded(repository.notification_settings(), &resilience),
+                user_settings: guarded(repository.user_settings(), &resilience),
+                jobs: guarded(repository.jobs(), &resilience),
+                leases: guarded(repository.leases(), &resilience),
+                schema: guarded(repository.schema(), &resilience),
+                feature_flags: guarded(repository.feature_flags(), &resilience),
+                maintenance: guarded(repository.maintenance(), &resilience),
+                users: guarded(repository.users(), &resilience),
+                workspaces: guarded(repository.workspaces(), &resilience),
+                acl: guarded(repository.acl(), &resilience),
+                comments: guarded(repository.comments(), &resilience),
+                activity: guarded(repository.activity(), &resilience),
+                undo: guarded(repository.undo(), &resilience),
+                changes: changes.clone(),
+                outbox: guarded(repository.outbox(), &resilience),
+                refresh_tokens: guarded(repository.refresh_tokens(), &resilience),
+                password_resets: guarded(repository.password_resets(), &resilience),
+                login_attempts: guarded(repository.login_attempts(), &resilience),
+                sessions: guarded(repository.sessions(), &resilience),
+                attachments: guarded(repository.attachments(), &resilience),
+                templates: guarded(repository.templates(), &resilience),
+                projects: guarded(repository.projects(), &resilience),
+                todos: Arc::new(SyncedTodoRepository::new(
+                    ResilientRepository::new(repository, resilience),
+                    changes,
+                )),
+            })
+        }
+        #[cfg(feature = "postgres")]
+        StorageBackend::Postgres => {
+            let repository = crate::repository::PostgresTodoRepository::connect(&config.postgres)
+                .await
+                .map_err(|e| {
+                    std::io::Error::other(format!("Failed to connect to Postgres: {}", e))
+                })?;
+            log::info!("event=database_connected backend=postgres");
+
+            let resilience = Arc::new(Resilience::new(&config.database, query_metrics));
+            let changes: Arc<dyn ChangeRepository> = guarded(repository.changes(), &resilience);
+            Ok(Repositories {
+                webhooks: guarded(repository.webhooks(), &resilience),
+                notification_settings: guarded(repository.notification_settings(), &resilience),
+                user_settings: guarded(repository.user_settings(), &resilience),
+                jobs: guarded(repository.jobs(), &resilience),
+                leases: guarded(repository.leases(), &resilience),
+                schema: guarded(repository.schema(), &resilience),
+                feature_flags: guarded(repository.feature_flags(), &resilience),
+                maintenance: guarded(repository.maintenance(), &resilience),
+                users: guarded(repository.users(), &resilience),
+                workspaces: guarded(repository.workspaces(), &resilience),
+                acl: guarded(repository.acl(), &resilience),
+                comments: guarded(repository.comments(), &resilience),
+                activity: guarded(repository.activity(), &resilience),
+                undo: guarded(repository.undo(), &resilience),
+                changes: changes.clone(),
+                outbox: guarded(repository.outbox(), &resilience),
+                refresh_tokens: guarded(repository.refresh_tokens(), &resilience),
+                password_resets: guarded(repository.password_resets(), &resilience),
+                login_attempts: guarded(repository.login_attempts(), &resilience),
+                sessions: guarded(repository.sessions(), &resilience),
+                attachments: guarded(repository.attachments(), &resilience),
+                templates: guarded(repository.templates(), &resilience),
+                projects: guarded(repository.projects(), &resilience),
+                todos: Arc::new(SyncedTodoRepository::new(
+                    ResilientRepository::new(repository, resilience),
+                    changes,
+                )),
+            })
+        }
+        #[cfg(not(feature = "postgres"))]
+        StorageBackend::Postgres => Err(std::io::Error::other(
+            "Postgres storage requires building with the `postgres` feature",
+        )),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => {
+            let repository = crate::repository::SqliteTodoRepository::connect(&config.sqlite)
+                .await
+                .map_err(|e| {
+                    std::io::Error::other(format!("Failed to open SQLite database: {}", e))
+                })?;
+            log::info!(
+                "event=database_connected backend=sqlite url={}",
+                config.sqlite.url
+            );
+
+            let resilience = Arc::new(Resilience::new(&config.database, query_metrics));
+            let changes: Arc<dyn ChangeRepository> = guarded(repository.changes(), &resilience);
+            Ok(Repositories {
+                webhooks: guarded(repository.webhooks(), &resilience),
+                notification_settings: guarded(repository.notification_settings(), &resilience),
+                user_settings: guarded(repository.user_settings(), &resilience),
+                jobs: guarded(repository.jobs(), &resilience),
+                leases: guarded(repository.leases(), &resilience),
+                schema: guarded(repository.schema(), &resilience),
+                feature_flags: guarded(repository.feature_flags(), &resilience),
+                maintenance: guarded(repository.maintenance(), &resilience),
+                users: guarded(repository.users(), &resilience),
+                workspaces: guarded(repository.workspaces(), &resilience),
+                acl: guarded(repository.acl(), &resilience),
+                comments: guarded(repository.comments(), &resilience),
+                activity: guarded(repository.activity(), &resilience),
+                undo: guarded(repository.undo(), &resilience),
+                changes: changes.clone(),
+                outbox: guarded(repository.outbox(), &resilience),
+                refresh_tokens: guarded(repository.refresh_tokens(), &resilience),
+                password_resets: guarded(repository.password_resets(), &resilience),
+                login_attempts: guarded(repository.login_attempts(), &resilience),
+                sessions: guarded(repository.sessions(), &resilience),
+                attachments: guarded(repository.attachments(), &resilience),
+                templates: guarded(repository.templates(), &resilience),
+                projects: guarded(repository.projects(), &resilience),
+                todos: Arc::new(SyncedTodoRepository::new(
+                    ResilientRepository::new(repository, resilience),
+                    changes,
+                )),
+            })
+        }
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err(std::io::Error::other(
+            "SQLite storage requires building with the `sqlite` feature",
+        )),
+        StorageBackend::Memory => {
+            log::warn!("event=memory_storage message=\"data will not be persisted\"");
+            let changes: Arc<dyn ChangeRepository> = Arc::new(InMemoryChangeRepository::new());
+            let outbox = Arc::new(InMemoryOutboxRepository::new());
+            Ok(Repositories {
+                changes: changes.clone(),
+                todos: Arc::new(SyncedTodoRepository::new(
+                    InMemoryTodoRepository::with_outbox(outbox.clone()),
+                    changes,
+                )),
+                outbox,
+                webhooks: Arc::new(InMemoryWebhookRepository::new()),
+                notification_settings: Arc::new(InMemoryNotificationSettingsRepository::new()),
+                user_settings: Arc::new(InMemoryUserSettingsRepository::new()),
+                jobs: Arc::new(InMemoryJobRepository::new()),
+                leases: Arc::new(InMemoryLeaseRepository::new()),
+                schema: Arc::new(InMemorySchemaRepository::new()),
+                feature_flags: Arc::new(InMemoryFeatureFlagRepository::new()),
+                maintenance: Arc::new(InMemoryMaintenanceRepository::new()),
+                users: Arc::new(InMemoryUserRepository::new()),
+                workspaces: Arc::new(InMemoryWorkspaceRepository::new()),
+                acl: Arc::new(InMemoryTodoAclRepository::new()),
+                comments: Arc::new(InMemoryCommentRepository::new()),
+                activity: Arc::new(InMemoryActivityRepository::new()),
+                undo: Arc::new(InMemoryUndoRepository::new()),
+                refresh_tokens: Arc::new(InMemoryRefreshTokenRepository::new()),
+                password_resets: Arc::new(InMemoryPasswordResetRepository::new()),
+                login_attempts: Arc::new(InMemoryLoginAttemptRepository::new()),
+                sessions: Arc::new(InMemorySessionRepository::new()),
+                attachments: Arc::new(InMemoryAttachmentRepository::new()),
+                templates: Arc::new(InMemoryTemplateRepository::new()),
+                projects: Arc::new(InMemoryProjectRepository::new()),
+            })
+        }
+    }
+}
+
+/// `repository` behind its database's timeout and circuit breaker.
+fn guarded<R>(repository: R, resilience: &Arc<Resilience>) -> Arc<ResilientRepository<R>> {
+    Arc::new(ResilientRepository::new(repository, resilience.clone()))
+}
+
+/// Connects the configured storage, starts the background job queue and
+/// assembles the state shared by every request handler. Used by the server
+/// binary and by the integration tests, so both run the same wiring.
+pub async fn build_state(config: &Config) -> std::io::Result<AppState> {
+    let secrets = Secrets::load(&config.secrets)
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to load secrets: {}", e)))?;
+    let mut config = config.clone();
+    secrets.apply(&mut config);
+
+    let query_metrics = Arc::new(QueryMetrics::new());
+    let repositories = create_repositories(&config, query_metrics.clone(), &secrets).await?;
+    let (state, queue) = assemble_state(&config, repositories, query_metrics, &secrets).await?;
+
+    let resumed = queue
+        .start()
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to start job queue: {}", e)))?;
+    if resumed > 0 {
+        log::info!("event=jobs_resumed count={}", resumed);
+    }
+    state.stats.refresh().await;
+    state.features.refresh().await;
+    state.features.start();
+    state.maintenance.refresh().await;
+    state.maintenance.start();
+
+    Ok(state)
+}
+
+/// Builds the handler state around already created repositories. Background
+/// jobs are registered on the returned queue but it is not started, so unit
+/// tests can run handlers against mock repositories without any job touching
+/// them.
+pub async fn assemble_state(
+    config: &Config,
+    repositories: Repositories,
+    query_metrics: Arc<QueryMetrics>,
+    secrets: &Secrets,
+) -> std::io::Result<(AppState, JobQueue)> {
+    let Repositories {
+        todos,
+        users,
+        workspaces,
+        acl,
+        comments,
+        activity,
+        undo,
+        changes,
+        outbox,
+        refresh_tokens,
+        password_resets,
+        login_attempts,
+        sessions,
+        attachments,
+        templates,
+        projects,
+        webhooks,
+        notification_settings,
+        user_settings,
+        jobs,
+        leases,
+        schema,
+        feature_flags,
+        maintenance,
+    } = repositories;
+    let queue = JobQueue::new(jobs, &config.jobs);
+    queue.set_leader(LeaderElector::new(
+        leases,
+        leader::WORKERS,
+        config.jobs.leader_lease,
+    ));
+    let scheduler = Scheduler::new(queue.clone());
+
+    let cipher = encryption::ContentCipher::new(&config.encryption).map_err(|e| {
+        std::io::Error::other(format!("Failed to set up content encryption: {}", e))
+    })?;
+    let encryption =
+        encryption::ContentEncryption::new(queue.clone(), todos.clone(), cipher.clone());
+    let todos: Arc<dyn TodoRepository> = Arc::new(CoalescingTodoRepository::new(
+        Arc::new(EncryptedTodoRepository::new(
+            todos,
+            cipher,
+            RowDecoding::new(config.database.strict_decoding),
+        )),
+        query_metrics.clone(),
+    ));
+
+    let recurrence = RecurrenceScheduler::new(
+        queue.clone(),
+        todos.clone(),
+        config.scheduler.sweep_interval,
+    );
+
+    let stats = stats::TodoStats::new(
+        queue.clone(),
+        todos.clone(),
+        config.scheduler.stats_refresh_interval,
+    );
+
+    let blobs = blobs::from_config(&config.attachments)
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to set up blob storage: {}", e)))?;
+
+    let notifier = notifier::from_config(&config.notifier, notification_settings.clone(), secrets)
+        .map_err(|e| std::io::Error::other(format!("Failed to set up notifier: {}", e)))?;
+    queue.register(Arc::new(reminders::ReminderScanJob {
+        repository: todos.clone(),
+        user_settings: user_settings.clone(),
+        notifier: notifier.clone(),
+    }));
+    queue.every(
+        reminders::ReminderScanJob::KIND,
+        config.scheduler.reminder_poll_interval,
+    );
+    queue.register(Arc::new(digest::DigestJob {
+        repository: todos.clone(),
+        notifier: notifier.clone(),
+    }));
+    scheduler.add(
+        digest::DigestJob::KIND,
+        config.scheduler.digest_schedule.clone(),
+    );
+    queue.register(Arc::new(expiry::ExpirySweepJob {
+        todos: todos.clone(),
+        acl: acl.clone(),
+        comments: comments.clone(),
+        attachments: attachments.clone(),
+        blobs: blobs.clone(),
+    }));
+    queue.every(
+        expiry::ExpirySweepJob::KIND,
+        config.scheduler.expiry_sweep_interval,
+    );
+    queue.register(Arc::new(stats::CountReconcileJob {
+        repository: todos.clone(),
+    }));
+    scheduler.add(
+        stats::CountReconcileJob::KIND,
+        config.scheduler.count_reconcile_schedule.clone(),
+    );
+
+    let dispatcher =
+        webhooks::WebhookDispatcher::new(queue.clone(), webhooks.clone(), &config.webhooks)
+            .map_err(|e| std::io::Error::other(format!("Failed to set up webhooks: {}", e)))?;
+    let mut consumers = vec![
+        outbox::Consumer::Handler(Arc::new(dispatcher)),
+        outbox::Consumer::Handler(Arc::new(activity::ActivityRecorder {
+            repository: activity.clone(),
+        })),
+    ];
+    let sink = outbox::from_config(&config.event_sink)
+        .map_err(|e| std::io::Error::other(format!("Failed to set up the event sink: {}", e)))?;
+    consumers.extend(sink.map(outbox::Consumer::Sink));
+    let relay = outbox::OutboxRelay::new(queue.clone(), outbox, consumers, &config.event_sink);
+    let events = EventBus::new();
+    let transport = bridge::from_config(&config.event_bridge)
+        .map_err(|e| std::io::Error::other(format!("Failed to set up the event bridge: {}", e)))?;
+    if let Some(transport) = transport {
+        bridge::start(&events, transport);
+    }
+    let oauth = oauth::OAuthClient::new(&config.auth.oauth)
+        .map_err(|e| std::io::Error::other(format!("Failed to set up OAuth sign-in: {}", e)))?;
+    let totp = totp::TotpService::new(users.clone(), &config.auth).map_err(|e| {
+        std::io::Error::other(format!("Failed to set up two-factor authentication: {}", e))
+    })?;
+
+    let exports = exports::AccountExports::new(
+        queue.clone(),
+        exports::ExportJob {
+            users: users.clone(),
+            user_settings: user_settings.clone(),
+            notification_settings: notification_settings.clone(),
+            workspaces: workspaces.clone(),
+            todos: todos.clone(),
+            comments: comments.clone(),
+            attachments: attachments.clone(),
+            activity: activity.clone(),
+            blobs: blobs.clone(),
+        },
+    );
+
+    let retention = retention::Retention::new(
+        &queue,
+        &scheduler,
+        &config.retention,
+        changes.clone(),
+        activity.clone(),
+    );
+
+    let tokens = auth::TokenService::new(&config.auth);
+    let rotated = tokens.clone();
+    secrets.watch(secrets::JWT_SECRET, move |secret| rotated.rotate(secret));
+
+    let state = AppState::new(
+        todos,
+        users,
+        workspaces,
+        acl,
+        comments,
+        activity,
+        attachments,
+        templates,
+        projects,
+        blobs,
+        blobs::UploadPolicy::new(&config.attachments),
+        config.server.pagination,
+        webhooks,
+        notification_settings,
+        user_settings,
+        recurrence,
+        events,
+        relay,
+        notifier,
+        stats,
+        tokens,
+        query_metrics,
+        ConcurrencyLimiter::new(&config.concurrency),
+        config.workflow.clone(),
+        config.suggestions,
+        RenderCache::new(config.markdown_cache_size),
+        Sanitizer::new(&config.sanitize),
+        UndoLog::new(undo, config.undo_window),
+        changes,
+        auth::RefreshTokenService::new(refresh_tokens, sessions, &config.auth),
+        oauth,
+        password_reset::PasswordResetService::new(password_resets, &config.auth.password_reset),
+        lockout::LoginGuard::new(login_attempts, &config.auth.lockout),
+        totp,
+        encryption,
+        exports,
+        retention,
+        scheduler,
+        signing::SignatureVerifier::new(&config.auth.signing),
+        Diagnostics::new(config, schema),
+        FeatureFlags::new(&config.features, feature_flags),
+        MaintenanceMode::new(&config.maintenance, maintenance),
+    );
+    Ok((state, queue))
+}
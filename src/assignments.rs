@@ -0,0 +1,105 @@
+//! Assigning todos to members of their workspace. The new assignee is
+//! notified unless they assigned the todo to themselves.
+
+use chrono::{DateTime, Utc};
+
+use crate::activity;
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{ActivityKind, AppState, Todo, TodoId, User};
+use crate::notifier::Notification;
+use crate::outbox;
+use crate::repository::{TodoPatch, TodoWrite};
+
+/// The user `assignee_id` names, if they may be assigned `todo`: only
+/// members of the todo's workspace can, so personal todos cannot be
+/// assigned at all.
+pub async fn check_assignee(
+    data: &AppState,
+    todo: &Todo,
+    assignee_id: &str,
+) -> Result<User, AppError> {
+    let Some(workspace_id) = todo.workspace_id.as_deref() else {
+        return Err(AppError::BadRequest(
+            "Only todos in a workspace can be assigned".to_string(),
+        ));
+    };
+    let not_member = || {
+        AppError::BadRequest(format!(
+            "User with ID: {} is not a member of the todo's workspace",
+            assignee_id
+        ))
+    };
+    data.workspaces
+        .find_member(workspace_id, assignee_id)
+        .await?
+        .ok_or_else(not_member)?;
+    data.users
+        .find_by_id(assignee_id)
+        .await?
+        .ok_or_else(not_member)
+}
+
+/// Assigns `todo` to `assignee_id`, or unassigns it with `None`, on behalf
+/// of `assigned_by`, and returns whether anything changed.
+pub async fn assign(
+    data: &AppState,
+    id: &TodoId,
+    todo: &mut Todo,
+    assignee_id: Option<String>,
+    assigned_by: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<bool, AppError> {
+    let assignee = match assignee_id.as_deref() {
+        Some(assignee_id) => Some(check_assignee(data, todo, assignee_id).await?),
+        None => None,
+    };
+    if todo.assignee_id == assignee_id {
+        return Ok(false);
+    }
+
+    let patch = TodoPatch {
+        assignee_id: Some(assignee_id),
+        updated_at: now,
+        expires_at: todo.expires_at,
+        ..TodoPatch::default()
+    };
+    patch.apply(todo);
+    let event = DomainEvent::TodoUpdated {
+        id: *id,
+        todo: Some(todo.clone()),
+        title: None,
+        actor_id: assigned_by.map(str::to_string),
+    };
+    outbox::commit(data, TodoWrite::Patch(*id, patch), vec![event]).await?;
+    activity::record(
+        data,
+        id,
+        assigned_by,
+        ActivityKind::Assigned,
+        todo.assignee_id.clone(),
+    )
+    .await;
+
+    log::info!(
+        "event=todo_assigned todo_id={} assignee_id={}",
+        id,
+        todo.assignee_id.as_deref().unwrap_or("none")
+    );
+
+    // The assignment stands even if the assignee cannot be told about it.
+    if let Some(assignee) = assignee.filter(|assignee| Some(assignee.id.as_str()) != assigned_by) {
+        if let Err(e) = data
+            .notifier
+            .notify(&Notification::assigned(todo, &assignee.email))
+            .await
+        {
+            log::warn!(
+                "event=assignment_notification_failed todo_id={} error=\"{}\"",
+                id,
+                e
+            );
+        }
+    }
+    Ok(true)
+}
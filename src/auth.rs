@@ -0,0 +1,605 @@
+use std::future::{ready, Ready};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::Payload;
+use actix_web::{http::header, web, FromRequest, HttpMessage, HttpRequest};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData, Validation};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::{AuthConfig, CookieAuthConfig};
+use crate::csrf;
+use crate::error::AppError;
+use crate::model::{AppState, RefreshToken, Role, User, UserSession};
+use crate::repository::{RefreshTokenRepository, SessionRepository};
+use crate::response::AuthData;
+use crate::signing::ServiceClient;
+
+/// Shortest password accepted at registration.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// `amr` values (RFC 8176) for how a user authenticated.
+pub const AMR_PASSWORD: &str = "pwd";
+pub const AMR_OTP: &str = "otp";
+/// Signed in through an external identity provider.
+pub const AMR_FEDERATED: &str = "fed";
+
+/// Cookies carrying the tokens in cookie sessions.
+pub const ACCESS_COOKIE: &str = "access_token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+/// How long the second login step can be completed after the first.
+const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+const CHALLENGE_PURPOSE: &str = "mfa";
+
+/// How long the link confirming a new email address works.
+pub const EMAIL_CHANGE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const EMAIL_CHANGE_PURPOSE: &str = "email_change";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    email: String,
+    role: Role,
+    /// Absent from tokens issued before it was added.
+    #[serde(default)]
+    amr: Vec<String>,
+    /// The session the token was issued in; absent like `amr`.
+    #[serde(default)]
+    sid: Option<String>,
+    iat: i64,
+    exp: i64,
+}
+
+/// Claims of the token proving a login's first step. Having none of the
+/// access token's `email` and `role`, it does not pass for one.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChallengeClaims {
+    sub: String,
+    purpose: String,
+    amr: Vec<String>,
+    iat: i64,
+    exp: i64,
+}
+
+/// Claims of the token emailed to a new address to confirm it. It names
+/// the address it replaces, so it stops working once the email changes.
+#[derive(Debug, Serialize, Deserialize)]
+struct EmailChangeClaims {
+    sub: String,
+    purpose: String,
+    email: String,
+    previous: String,
+    iat: i64,
+    exp: i64,
+}
+
+/// A change of email address its new owner confirmed.
+#[derive(Debug, Clone)]
+pub struct EmailChange {
+    pub user_id: String,
+    /// The address the change was asked for from.
+    pub previous: String,
+    pub email: String,
+}
+
+struct SigningKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// The key replaced by the last rotation, still accepted until the
+    /// tokens it signed have expired.
+    previous: Option<(DecodingKey, Instant)>,
+}
+
+/// Issues and verifies the HS256 bearer tokens handed out at login.
+#[derive(Clone)]
+pub struct TokenService {
+    keys: Arc<RwLock<SigningKeys>>,
+    ttl: Duration,
+    admin_emails: Arc<Vec<String>>,
+    cookies: CookieAuthConfig,
+}
+
+impl TokenService {
+    pub fn new(config: &AuthConfig) -> Self {
+        let secret = match &config.jwt_secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                log::warn!("event=auth_ephemeral_secret message=\"AUTH_JWT_SECRET is not set; tokens will not survive a restart\"");
+                rand::thread_rng().gen::<[u8; 32]>().to_vec()
+            }
+        };
+
+        TokenService {
+            keys: Arc::new(RwLock::new(SigningKeys {
+                encoding: EncodingKey::from_secret(&secret),
+                decoding: DecodingKey::from_secret(&secret),
+                previous: None,
+            })),
+            ttl: config.token_ttl,
+            admin_emails: Arc::new(config.admin_emails.clone()),
+            cookies: config.cookies,
+        }
+    }
+
+    /// Signs new tokens with `secret` from now on. Tokens signed with the
+    /// old secret stay valid for as long as they were issued for.
+    pub fn rotate(&self, secret: &str) {
+        let mut keys = self.keys.write().unwrap();
+        let previous = std::mem::replace(
+            &mut keys.decoding,
+            DecodingKey::from_secret(secret.as_bytes()),
+        );
+        keys.encoding = EncodingKey::from_secret(secret.as_bytes());
+        keys.previous = Some((previous, Instant::now() + self.ttl.max(CHALLENGE_TTL)));
+        log::info!("event=jwt_secret_rotated");
+    }
+
+    /// The role for the owner of `email`, once they have shown they control
+    /// it: through a provider that verified it, or by redeeming a password
+    /// reset sent there. Open registration proves neither, so accounts it
+    /// creates are always [`Role::User`].
+    pub fn role_for_verified(&self, email: &str) -> Role {
+        if self.admin_emails.iter().any(|admin| admin == email) {
+            Role::Admin
+        } else {
+            Role::User
+        }
+    }
+
+    /// Returns a signed token for `user` in the session `refresh` belongs
+    /// to, and when it expires.
+    pub fn issue(
+        &self,
+        user: &User,
+        refresh: &RefreshToken,
+    ) -> Result<(String, DateTime<Utc>), AppError> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::from_std(self.ttl).unwrap_or_default();
+        let claims = Claims {
+            sub: user.id.clone(),
+            email: user.email.clone(),
+            role: user.role,
+            amr: refresh.amr.clone(),
+            sid: Some(refresh.family_id.clone()),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        Ok((self.sign(&claims)?, expires_at))
+    }
+
+    pub fn cookie_config(&self) -> CookieAuthConfig {
+        self.cookies
+    }
+
+    /// Cookies handing the tokens in `auth` to a browser, with a fresh
+    /// CSRF token; none unless cookie sessions are on.
+    pub fn session_cookies(&self, auth: &AuthData) -> Vec<Cookie<'static>> {
+        if !self.cookies.enabled {
+            return Vec::new();
+        }
+        let cookie = |name, value: &str, expires_at: DateTime<Utc>| {
+            let max_age = (expires_at - Utc::now()).num_seconds().max(0);
+            Cookie::build(name, value.to_string())
+                .path("/")
+                .http_only(true)
+                .secure(self.cookies.secure)
+                // Links from other sites still arrive signed in; what they
+                // may change is up to the CSRF check.
+                .same_site(SameSite::Lax)
+                .max_age(actix_web::cookie::time::Duration::seconds(max_age))
+                .finish()
+        };
+        vec![
+            cookie(ACCESS_COOKIE, &auth.token, auth.expires_at),
+            cookie(REFRESH_COOKIE, &auth.refresh_token, auth.refresh_expires_at),
+            csrf::cookie(&csrf::new_token(), self.cookies),
+        ]
+    }
+
+    /// Expires the cookies set by [`TokenService::session_cookies`].
+    pub fn clear_session_cookies(&self) -> Vec<Cookie<'static>> {
+        if !self.cookies.enabled {
+            return Vec::new();
+        }
+        [ACCESS_COOKIE, REFRESH_COOKIE, csrf::CSRF_COOKIE]
+            .into_iter()
+            .map(|name| {
+                let mut cookie = Cookie::build(name, "").path("/").finish();
+                cookie.make_removal();
+                cookie
+            })
+            .collect()
+    }
+
+    /// Returns a token for completing the login of `user`, who passed the
+    /// `amr` methods so far, with their second factor; and when it expires.
+    pub fn issue_challenge(
+        &self,
+        user: &User,
+        amr: &[String],
+    ) -> Result<(String, DateTime<Utc>), AppError> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::from_std(CHALLENGE_TTL).unwrap_or_default();
+        let claims = ChallengeClaims {
+            sub: user.id.clone(),
+            purpose: CHALLENGE_PURPOSE.to_string(),
+            amr: amr.to_vec(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        Ok((self.sign(&claims)?, expires_at))
+    }
+
+    /// The user a challenge token was issued for and the methods they had
+    /// passed.
+    pub fn verify_challenge(&self, token: &str) -> Result<(String, Vec<String>), AppError> {
+        let invalid = || AppError::Unauthorized("Invalid or expired login".to_string());
+        let data = self
+            .decode::<ChallengeClaims>(token)
+            .map_err(|_| invalid())?;
+        if data.claims.purpose != CHALLENGE_PURPOSE {
+            return Err(invalid());
+        }
+        Ok((data.claims.sub, data.claims.amr))
+    }
+
+    /// Returns a token that moves `user` to `email` once it comes back from
+    /// that address.
+    pub fn issue_email_change(&self, user: &User, email: &str) -> Result<String, AppError> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::from_std(EMAIL_CHANGE_TTL).unwrap_or_default();
+        self.sign(&EmailChangeClaims {
+            sub: user.id.clone(),
+            purpose: EMAIL_CHANGE_PURPOSE.to_string(),
+            email: email.to_string(),
+            previous: user.email.clone(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        })
+    }
+
+    pub fn verify_email_change(&self, token: &str) -> Result<EmailChange, AppError> {
+        let invalid = || AppError::BadRequest("Invalid or expired confirmation token".to_string());
+        let data = self
+            .decode::<EmailChangeClaims>(token)
+            .map_err(|_| invalid())?;
+        if data.claims.purpose != EMAIL_CHANGE_PURPOSE {
+            return Err(invalid());
+        }
+        Ok(EmailChange {
+            user_id: data.claims.sub,
+            previous: data.claims.previous,
+            email: data.claims.email,
+        })
+    }
+
+    fn sign<T: Serialize>(&self, claims: &T) -> Result<String, AppError> {
+        jsonwebtoken::encode(
+            &Header::default(),
+            claims,
+            &self.keys.read().unwrap().encoding,
+        )
+        .map_err(|e| AppError::Internal(format!("Failed to sign token: {}", e)))
+    }
+
+    fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let keys = self.keys.read().unwrap();
+        let result = jsonwebtoken::decode::<T>(token, &keys.decoding, &Validation::default());
+        match &keys.previous {
+            Some((previous, until)) if result.is_err() && Instant::now() < *until => {
+                jsonwebtoken::decode::<T>(token, previous, &Validation::default())
+            }
+            _ => result,
+        }
+    }
+
+    fn verify(&self, token: &str) -> Result<AuthUser, AppError> {
+        let data = self
+            .decode::<Claims>(token)
+            .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?;
+
+        Ok(AuthUser {
+            id: data.claims.sub,
+            role: data.claims.role,
+            session_id: data.claims.sid,
+        })
+    }
+}
+
+/// Who a login or refresh came from, as noted on its session.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip_address: String,
+    pub user_agent: Option<String>,
+}
+
+/// Issues refresh tokens and trades them for new ones. A token can be
+/// used once: presenting a spent one again means it leaked, so every token
+/// descended from the same login is revoked and the user has to log in
+/// again.
+#[derive(Clone)]
+pub struct RefreshTokenService {
+    repository: Arc<dyn RefreshTokenRepository>,
+    sessions: Arc<dyn SessionRepository>,
+    ttl: Duration,
+}
+
+impl RefreshTokenService {
+    pub fn new(
+        repository: Arc<dyn RefreshTokenRepository>,
+        sessions: Arc<dyn SessionRepository>,
+        config: &AuthConfig,
+    ) -> Self {
+        RefreshTokenService {
+            repository,
+            sessions,
+            ttl: config.refresh_token_ttl,
+        }
+    }
+
+    fn lifetime(&self) -> chrono::Duration {
+        chrono::Duration::from_std(self.ttl).unwrap_or_default()
+    }
+
+    /// Starts a new token family and session for a login from `client`,
+    /// in which the user passed the `amr` methods. Returns the first token
+    /// as stored, and its value.
+    pub async fn issue(
+        &self,
+        user_id: &str,
+        amr: &[String],
+        client: &ClientInfo,
+    ) -> Result<(RefreshToken, String), AppError> {
+        let (stored, token) = self
+            .issue_in(user_id, Uuid::new_v4().to_string(), amr.to_vec())
+            .await?;
+        self.sessions
+            .save(&UserSession {
+                id: stored.family_id.clone(),
+                user_id: stored.user_id.clone(),
+                user_agent: client.user_agent.clone(),
+                ip_address: client.ip_address.clone(),
+                created_at: stored.created_at,
+                last_seen_at: stored.created_at,
+                expires_at: stored.expires_at,
+            })
+            .await?;
+        Ok((stored, token))
+    }
+
+    async fn issue_in(
+        &self,
+        user_id: &str,
+        family_id: String,
+        amr: Vec<String>,
+    ) -> Result<(RefreshToken, String), AppError> {
+        let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let now = Utc::now();
+        let stored = RefreshToken {
+            token_hash: hash_token(&token),
+            family_id,
+            user_id: user_id.to_string(),
+            created_at: now,
+            expires_at: now + self.lifetime(),
+            used_at: None,
+            amr,
+        };
+        self.repository.insert(&stored).await?;
+        Ok((stored, token))
+    }
+
+    /// Spends `token` for a new one in the same family, noting `client` on
+    /// the session. Returns the new token as stored, and its value.
+    pub async fn rotate(
+        &self,
+        token: &str,
+        client: &ClientInfo,
+    ) -> Result<(RefreshToken, String), AppError> {
+        let invalid = || AppError::Unauthorized("Invalid or expired refresh token".to_string());
+        let now = Utc::now();
+
+        let stored = self
+            .repository
+            .find(&hash_token(token))
+            .await?
+            .ok_or_else(invalid)?;
+        if stored.expires_at <= now || self.repository.is_revoked(&stored.family_id).await? {
+            return Err(invalid());
+        }
+        let spent =
+            stored.used_at.is_some() || !self.repository.mark_used(&stored.token_hash, now).await?;
+        if spent {
+            log::warn!(
+                "event=refresh_token_reused user_id={} family_id={}",
+                stored.user_id,
+                stored.family_id
+            );
+            self.end_family(&stored.user_id, &stored.family_id).await?;
+            return Err(invalid());
+        }
+
+        let (rotated, token) = self
+            .issue_in(&stored.user_id, stored.family_id, stored.amr)
+            .await?;
+        // Families started before sessions were tracked have none yet.
+        let created_at = self
+            .sessions
+            .find(&rotated.user_id, &rotated.family_id)
+            .await?
+            .map_or(rotated.created_at, |session| session.created_at);
+        self.sessions
+            .save(&UserSession {
+                id: rotated.family_id.clone(),
+                user_id: rotated.user_id.clone(),
+                user_agent: client.user_agent.clone(),
+                ip_address: client.ip_address.clone(),
+                created_at,
+                last_seen_at: rotated.created_at,
+                expires_at: rotated.expires_at,
+            })
+            .await?;
+        Ok((rotated, token))
+    }
+
+    /// Revokes a family and removes its session.
+    async fn end_family(&self, user_id: &str, family_id: &str) -> Result<(), AppError> {
+        self.repository
+            .revoke_family(family_id, Utc::now() + self.lifetime())
+            .await?;
+        self.sessions.delete(user_id, family_id).await?;
+        Ok(())
+    }
+
+    /// The user's sessions that have not expired, last seen first.
+    pub async fn sessions(&self, user_id: &str) -> Result<Vec<UserSession>, AppError> {
+        Ok(self.sessions.find_by_user(user_id, Utc::now()).await?)
+    }
+
+    /// Ends one of the user's sessions: its refresh token stops working.
+    /// Access tokens already issued in it stay valid until they expire.
+    pub async fn revoke_session(&self, user_id: &str, id: &str) -> Result<(), AppError> {
+        if self.sessions.find(user_id, id).await?.is_none() {
+            return Err(AppError::NotFound(format!(
+                "Session with ID: {} not found",
+                id
+            )));
+        }
+        self.end_family(user_id, id).await?;
+        log::info!(
+            "event=refresh_token_family_revoked user_id={} family_id={}",
+            user_id,
+            id
+        );
+        Ok(())
+    }
+
+    /// Ends every session of the user, as when their account goes.
+    pub async fn end_all(&self, user_id: &str) -> Result<(), AppError> {
+        for session in self.sessions(user_id).await? {
+            self.end_family(user_id, &session.id).await?;
+        }
+        Ok(())
+    }
+
+    /// Revokes the family of `token`, ending the login it came from.
+    /// Unknown tokens are ignored, so logging out twice is harmless.
+    pub async fn revoke(&self, token: &str) -> Result<(), AppError> {
+        if let Some(stored) = self.repository.find(&hash_token(token)).await? {
+            self.end_family(&stored.user_id, &stored.family_id).await?;
+            log::info!(
+                "event=refresh_token_family_revoked user_id={} family_id={}",
+                stored.user_id,
+                stored.family_id
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Refresh and password reset tokens are random, so a fast unsalted hash
+/// is enough to keep the stored values useless to whoever reads them.
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Hashes `password` with Argon2 into a PHC string. CPU-bound, so call it
+/// off the async executor.
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
+
+/// Checks `password` against a stored PHC string. CPU-bound, like [`hash_password`].
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// The caller identified by the request's bearer token. Use
+/// `Option<AuthUser>` on routes that also accept anonymous callers.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: String,
+    pub role: Role,
+    /// The session the token was issued in, if it says.
+    pub session_id: Option<String>,
+}
+
+impl FromRequest for AuthUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authenticate(req))
+    }
+}
+
+/// The caller identified by the request's bearer token or, in cookie
+/// sessions, by its access token cookie. Services whose signed request
+/// was verified act as a user with ID `service:<client ID>`.
+pub fn authenticate(req: &HttpRequest) -> Result<AuthUser, AppError> {
+    if let Some(client) = req.extensions().get::<ServiceClient>() {
+        return Ok(AuthUser {
+            id: format!("service:{}", client.id),
+            role: Role::User,
+            session_id: None,
+        });
+    }
+    let state = req
+        .app_data::<web::Data<AppState>>()
+        .ok_or_else(|| AppError::Internal("Application state is missing".to_string()))?;
+
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let cookie = match bearer {
+        None if state.tokens.cookies.enabled => req.cookie(ACCESS_COOKIE),
+        _ => None,
+    };
+    let token = bearer
+        .or(cookie.as_ref().map(Cookie::value))
+        .ok_or_else(|| AppError::Unauthorized("Missing bearer token".to_string()))?;
+
+    state.tokens.verify(token.trim())
+}
+
+/// An authenticated caller with the admin role.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthUser);
+
+impl FromRequest for AdminUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authenticate(req).and_then(|user| match user.role {
+            Role::Admin => Ok(AdminUser(user)),
+            Role::User => Err(AppError::Forbidden(
+                "This endpoint requires the admin role".to_string(),
+            )),
+        }))
+    }
+}
@@ -0,0 +1,216 @@
+//! `todo-admin`, database maintenance for operators. It reads the same
+//! environment as the server and works through the repository and
+//! migration modules, so it needs no `cqlsh` or `psql` scripts:
+//!
+//! ```text
+//! todo-admin migrate
+//! todo-admin verify-schema
+//! todo-admin rebuild-counters
+//! todo-admin reindex
+//! todo-admin purge-deleted --older-than-days 90
+//! ```
+
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use simple_api_actix_web::app::create_repositories;
+use simple_api_actix_web::config::{Config, StorageBackend};
+use simple_api_actix_web::metrics::QueryMetrics;
+use simple_api_actix_web::migrations::{self, SchemaStatus};
+use simple_api_actix_web::secrets::Secrets;
+use simple_api_actix_web::{db, logging};
+
+#[derive(Parser)]
+#[command(name = "todo-admin", version, about = "Maintain the todo database")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply pending migrations, first creating the Scylla keyspace when
+    /// `SCYLLA_BOOTSTRAP` is set.
+    Migrate,
+    /// Compare the applied migrations with those of this build, failing
+    /// unless they match. Applies none.
+    VerifySchema,
+    /// Recompute the per-owner todo counters from the todos.
+    RebuildCounters,
+    /// Rebuild the indexes todos are looked up by.
+    Reindex,
+    /// Delete the sync tombstones of todos deleted this many days ago or
+    /// earlier.
+    PurgeDeleted {
+        #[arg(long)]
+        older_than_days: u32,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut config = Config::from_env();
+    logging::init(&config.logging);
+
+    match run(&cli.command, &mut config).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("todo-admin: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs `command`, returning whether it found everything in order.
+async fn run(command: &Command, config: &mut Config) -> std::io::Result<bool> {
+    let secrets = Secrets::load(&config.secrets)
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to load secrets: {}", e)))?;
+    secrets.apply(config);
+
+    match command {
+        Command::Migrate => {
+            let status = schema_status(config, &secrets, true).await?;
+            if status.pending.is_empty() {
+                println!("No pending migrations");
+            }
+            for (version, name) in &status.pending {
+                println!("Applied {} {}", version, name);
+            }
+            Ok(true)
+        }
+        Command::VerifySchema => {
+            let status = schema_status(config, &secrets, false).await?;
+            for (version, name) in &status.pending {
+                println!("Pending: {} {}", version, name);
+            }
+            for version in &status.modified {
+                println!("Modified since it was applied: {}", version);
+            }
+            for version in &status.unknown {
+                println!("Applied but unknown to this build: {}", version);
+            }
+            if status.is_current() {
+                println!("Schema is up to date");
+            }
+            Ok(status.is_current())
+        }
+        Command::RebuildCounters => {
+            let repositories = connect(config, &secrets).await?;
+            let corrected = repositories
+                .todos
+                .reconcile_counts()
+                .await
+                .map_err(std::io::Error::other)?;
+            println!("Corrected {} owners' counts", corrected);
+            Ok(true)
+        }
+        Command::Reindex => {
+            let repositories = connect(config, &secrets).await?;
+            let corrected = repositories
+                .todos
+                .rebuild_indexes()
+                .await
+                .map_err(std::io::Error::other)?;
+            println!("Rebuilt indexes, correcting {} entries", corrected);
+            Ok(true)
+        }
+        Command::PurgeDeleted { older_than_days } => {
+            let repositories = connect(config, &secrets).await?;
+            let before = Utc::now() - chrono::Duration::days((*older_than_days).into());
+            let purged = repositories
+                .changes
+                .purge_deleted(before)
+                .await
+                .map_err(std::io::Error::other)?;
+            println!(
+                "Purged {} tombstones of todos deleted before {}",
+                purged, before
+            );
+            Ok(true)
+        }
+    }
+}
+
+async fn connect(
+    config: &Config,
+    secrets: &Secrets,
+) -> std::io::Result<simple_api_actix_web::repository::Repositories> {
+    if config.storage.backend == StorageBackend::Memory {
+        return Err(std::io::Error::other(
+            "memory storage has nothing to maintain; set STORAGE_BACKEND",
+        ));
+    }
+    create_repositories(config, Arc::new(QueryMetrics::new()), secrets).await
+}
+
+/// The configured database's schema status, taken before applying the
+/// pending migrations when `migrate` is set.
+async fn schema_status(
+    config: &Config,
+    secrets: &Secrets,
+    migrate: bool,
+) -> std::io::Result<SchemaStatus> {
+    let status = match config.storage.backend {
+        StorageBackend::Scylla => {
+            let session = db::connect_with_retry(&config.database, secrets)
+                .await
+                .map_err(std::io::Error::other)?;
+            let status = migrations::status(&session)
+                .await
+                .map_err(std::io::Error::other)?;
+            if migrate {
+                if let Some(replication) = &config.database.bootstrap {
+                    migrations::create_keyspace(&session, replication)
+                        .await
+                        .map_err(std::io::Error::other)?;
+                }
+                migrations::run(&session)
+                    .await
+                    .map_err(std::io::Error::other)?;
+            }
+            status
+        }
+        #[cfg(feature = "postgres")]
+        StorageBackend::Postgres => {
+            let repository =
+                simple_api_actix_web::repository::PostgresTodoRepository::open(&config.postgres)
+                    .await
+                    .map_err(std::io::Error::other)?;
+            let status = repository
+                .schema_status()
+                .await
+                .map_err(std::io::Error::other)?;
+            if migrate {
+                repository.migrate().await.map_err(std::io::Error::other)?;
+            }
+            status
+        }
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => {
+            let repository =
+                simple_api_actix_web::repository::SqliteTodoRepository::open(&config.sqlite)
+                    .await
+                    .map_err(std::io::Error::other)?;
+            let status = repository
+                .schema_status()
+                .await
+                .map_err(std::io::Error::other)?;
+            if migrate {
+                repository.migrate().await.map_err(std::io::Error::other)?;
+            }
+            status
+        }
+        backend => {
+            return Err(std::io::Error::other(format!(
+                "{:?} storage has no schema to migrate, or this build lacks its feature",
+                backend
+            )))
+        }
+    };
+    Ok(status)
+}
@@ -0,0 +1,255 @@
+//! `todo`, a command-line client for the HTTP API, for scripting and for
+//! smoke-testing a deployment:
+//!
+//! ```text
+//! todo add "Buy milk" --content "Two litres"
+//! todo list --completed
+//! todo done 0f8fad5b-d9cb-469f-a165-70867728950e
+//! todo export > todos.ndjson
+//! ```
+//!
+//! The server and bearer token come from `--url` and `--token`, or from
+//! `TODO_API_URL` and `TODO_API_TOKEN`. It talks to the `/api/v1` routes,
+//! so its output does not change as newer versions are added.
+
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::{Method, RequestBuilder, Response};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use simple_api_actix_web::model::Todo;
+
+#[derive(Parser)]
+#[command(name = "todo", version, about = "Manage todos through the HTTP API")]
+struct Cli {
+    /// Base URL of the server.
+    #[arg(
+        long,
+        env = "TODO_API_URL",
+        default_value = "http://localhost:8000",
+        global = true
+    )]
+    url: String,
+    /// Bearer token to send with every request.
+    #[arg(long, env = "TODO_API_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+    /// How to print todos.
+    #[arg(long, value_enum, default_value_t = Output::Table, global = true)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a todo.
+    Add {
+        title: String,
+        #[arg(long, default_value = "")]
+        content: String,
+        /// When the todo is due, as an RFC 3339 timestamp.
+        #[arg(long)]
+        due: Option<DateTime<Utc>>,
+    },
+    /// List todos, a page at a time.
+    List {
+        /// Only completed todos.
+        #[arg(long)]
+        completed: bool,
+        /// Only your own todos; needs a token.
+        #[arg(long)]
+        mine: bool,
+        #[arg(long)]
+        include_archived: bool,
+        #[arg(long, default_value_t = 1)]
+        page: usize,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Mark a todo completed.
+    Done { id: String },
+    /// Write every todo, archived ones included, as newline-delimited JSON.
+    Export,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    #[error("the server answered {status}: {message}")]
+    Api {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+#[derive(Deserialize)]
+struct TodoData {
+    todo: Todo,
+}
+
+#[derive(Deserialize)]
+struct SingleTodo {
+    data: TodoData,
+}
+
+#[derive(Deserialize)]
+struct TodoList {
+    todos: Vec<Todo>,
+}
+
+struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    fn new(cli: &Cli) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: format!("{}/api/v1", cli.url.trim_end_matches('/')),
+            token: cli.token.clone(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends `request`, turning an error status into [`CliError::Api`] with
+    /// the message from the body.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, CliError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|body| body["message"].as_str().map(str::to_string))
+            .unwrap_or(body);
+        Err(CliError::Api { status, message })
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("todo: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: &Cli) -> Result<(), CliError> {
+    let client = Client::new(cli);
+    match &cli.command {
+        Command::Add {
+            title,
+            content,
+            due,
+        } => {
+            let request = client.request(Method::POST, "/todos").json(&json!({
+                "title": title,
+                "content": content,
+                "dueAt": due,
+            }));
+            let created: SingleTodo = client.send(request).await?.json().await?;
+            print_todos(cli.output, &[created.data.todo])
+        }
+        Command::List {
+            completed,
+            mine,
+            include_archived,
+            page,
+            limit,
+        } => {
+            let mut query = vec![("page", page.to_string())];
+            if let Some(limit) = limit {
+                query.push(("limit", limit.to_string()));
+            }
+            if *completed {
+                query.push(("status", "done".to_string()));
+            }
+            if *mine {
+                query.push(("mine", "true".to_string()));
+            }
+            if *include_archived {
+                query.push(("include_archived", "true".to_string()));
+            }
+            let request = client.request(Method::GET, "/todos").query(&query);
+            let list: TodoList = client.send(request).await?.json().await?;
+            print_todos(cli.output, &list.todos)
+        }
+        Command::Done { id } => {
+            let request = client
+                .request(Method::PATCH, &format!("/todos/{}", id))
+                .json(&json!({ "completed": true }));
+            let updated: SingleTodo = client.send(request).await?.json().await?;
+            print_todos(cli.output, &[updated.data.todo])
+        }
+        Command::Export => {
+            let request = client
+                .request(Method::GET, "/todos")
+                .query(&[("stream", "true"), ("include_archived", "true")]);
+            let mut response = client.send(request).await?;
+            let mut stdout = io::stdout().lock();
+            while let Some(chunk) = response.chunk().await? {
+                stdout.write_all(&chunk)?;
+            }
+            Ok(stdout.flush()?)
+        }
+    }
+}
+
+fn print_todos(output: Output, todos: &[Todo]) -> Result<(), CliError> {
+    let mut stdout = io::stdout().lock();
+    match output {
+        Output::Json => {
+            serde_json::to_writer_pretty(&mut stdout, todos).map_err(io::Error::from)?;
+            writeln!(stdout)?;
+        }
+        Output::Table => {
+            writeln!(stdout, "{:<36}  {:<4}  {:<10}  TITLE", "ID", "DONE", "DUE")?;
+            for todo in todos {
+                writeln!(
+                    stdout,
+                    "{:<36}  {:<4}  {:<10}  {}",
+                    todo.id.map(|id| id.to_string()).unwrap_or_default(),
+                    if todo.completed.unwrap_or(false) {
+                        "yes"
+                    } else {
+                        "no"
+                    },
+                    todo.due_at
+                        .map(|due_at| due_at.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default(),
+                    todo.title,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use futures_util::TryStreamExt;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+
+use super::{BlobError, BlobInfo, BlobStore, BlobStream, BlobWriter};
+
+/// Keeps blobs as files in one directory on local disk. Only suitable for a
+/// single instance, or several sharing a network file system.
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: PathBuf) -> Result<Self, BlobError> {
+        std::fs::create_dir_all(&root)?;
+        Ok(LocalBlobStore { root })
+    }
+
+    /// Keys map straight to file names, so anything that could escape the
+    /// root directory is rejected.
+    fn path(&self, key: &str) -> Result<PathBuf, BlobError> {
+        let valid = !key.is_empty()
+            && !key.starts_with('.')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(BlobError::Storage(format!("invalid blob key: {:?}", key)));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn create(&self, key: &str) -> Result<Box<dyn BlobWriter>, BlobError> {
+        let path = self.path(key)?;
+        let partial = path.with_extension("part");
+        let file = File::create(&partial).await?;
+
+        Ok(Box::new(LocalBlobWriter {
+            file,
+            partial,
+            path,
+        }))
+    }
+
+    async fn open(&self, key: &str) -> Result<BlobStream, BlobError> {
+        let file = File::open(self.path(key)?).await?;
+        Ok(Box::pin(ReaderStream::new(file).map_err(BlobError::from)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), BlobError> {
+        match fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<BlobInfo>, BlobError> {
+        match fs::metadata(self.path(key)?).await {
+            Ok(metadata) => Ok(Some(BlobInfo {
+                size: metadata.len(),
+                content_type: None,
+            })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Writes to `<key>.part` and renames it into place when finished, so
+/// readers never see a partial file.
+struct LocalBlobWriter {
+    file: File,
+    partial: PathBuf,
+    path: PathBuf,
+}
+
+#[async_trait]
+impl BlobWriter for LocalBlobWriter {
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), BlobError> {
+        self.file.write_all(chunk).await?;
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<(), BlobError> {
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        fs::rename(&self.partial, &self.path).await?;
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) {
+        drop(self.file);
+        if let Err(e) = fs::remove_file(&self.partial).await {
+            log::warn!(
+                "event=blob_abort_failed path={} error=\"{}\"",
+                self.partial.display(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+mod local;
+#[cfg(feature = "s3")]
+mod s3;
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
+
+use crate::config::{AttachmentConfig, BlobBackend};
+use crate::error::AppError;
+
+pub use self::local::LocalBlobStore;
+#[cfg(feature = "s3")]
+pub use self::s3::S3BlobStore;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlobError {
+    #[error("Blob not found")]
+    NotFound,
+    #[error("Blob storage error: {0}")]
+    Storage(String),
+}
+
+impl From<std::io::Error> for BlobError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => BlobError::NotFound,
+            _ => BlobError::Storage(e.to_string()),
+        }
+    }
+}
+
+/// A blob's contents, read in chunks.
+pub type BlobStream = Pin<Box<dyn Stream<Item = Result<Bytes, BlobError>> + Send>>;
+
+/// What the store knows about a blob without reading it.
+#[derive(Debug, Clone)]
+pub struct BlobInfo {
+    pub size: u64,
+    /// Only known to stores that keep it alongside the contents.
+    pub content_type: Option<String>,
+}
+
+/// A time-limited request a client can send straight to the store. Every
+/// header listed is part of the signature and must be sent unchanged.
+#[derive(Debug, Clone)]
+pub struct PresignedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Storage for attachment contents, addressed by opaque keys chosen by the
+/// caller. Metadata lives in the repositories; this only holds the bytes.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Starts writing the blob stored under `key`. It becomes readable once
+    /// the writer is finished.
+    async fn create(&self, key: &str) -> Result<Box<dyn BlobWriter>, BlobError>;
+
+    async fn open(&self, key: &str) -> Result<BlobStream, BlobError>;
+
+    /// Deletes a blob; deleting a missing blob is not an error.
+    async fn delete(&self, key: &str) -> Result<(), BlobError>;
+
+    async fn stat(&self, key: &str) -> Result<Option<BlobInfo>, BlobError>;
+
+    /// A request that uploads exactly `size` bytes of `content_type` under
+    /// `key`, bypassing this process. `None` when the store cannot hand
+    /// out direct URLs.
+    async fn presign_upload(
+        &self,
+        _key: &str,
+        _content_type: &str,
+        _size: u64,
+    ) -> Result<Option<PresignedRequest>, BlobError> {
+        Ok(None)
+    }
+
+    /// A request that downloads `key` as `file_name`, bypassing this
+    /// process. `None` when the store cannot hand out direct URLs.
+    async fn presign_download(
+        &self,
+        _key: &str,
+        _file_name: &str,
+        _content_type: &str,
+    ) -> Result<Option<PresignedRequest>, BlobError> {
+        Ok(None)
+    }
+}
+
+/// A blob being written chunk by chunk, so uploads never have to be held in
+/// memory. Dropping a writer without finishing it may leave partial data
+/// behind; call [`BlobWriter::abort`] instead.
+#[async_trait]
+pub trait BlobWriter: Send {
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), BlobError>;
+
+    async fn finish(self: Box<Self>) -> Result<(), BlobError>;
+
+    /// Discards everything written so far. Best effort.
+    async fn abort(self: Box<Self>);
+}
+
+/// Builds the blob store selected by `BLOB_STORAGE_BACKEND`.
+pub async fn from_config(config: &AttachmentConfig) -> Result<Arc<dyn BlobStore>, BlobError> {
+    match config.backend {
+        BlobBackend::Local => Ok(Arc::new(LocalBlobStore::new(config.local_dir.clone())?)),
+        #[cfg(feature = "s3")]
+        BlobBackend::S3 => Ok(Arc::new(S3BlobStore::new(&config.s3).await)),
+        #[cfg(not(feature = "s3"))]
+        BlobBackend::S3 => Err(BlobError::Storage(
+            "S3 storage requires building with the `s3` feature".to_string(),
+        )),
+    }
+}
+
+/// Size and content-type limits applied to uploads.
+#[derive(Debug, Clone)]
+pub struct UploadPolicy {
+    pub max_size: u64,
+    allowed_types: Vec<String>,
+}
+
+impl UploadPolicy {
+    pub fn new(config: &AttachmentConfig) -> Self {
+        UploadPolicy {
+            max_size: config.max_size,
+            allowed_types: config.allowed_types.clone(),
+        }
+    }
+
+    /// Checks an upload announced up front, before any bytes are sent.
+    pub fn check(&self, content_type: &str, size: u64) -> Result<(), AppError> {
+        if !self.allows(content_type) {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Attachments of type '{}' are not allowed",
+                content_type
+            )));
+        }
+        if size > self.max_size {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Attachments must be at most {} bytes",
+                self.max_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether `content_type` (without parameters) may be uploaded. An entry
+    /// such as `image/*` allows every subtype.
+    pub fn allows(&self, content_type: &str) -> bool {
+        let content_type = content_type.to_ascii_lowercase();
+        self.allowed_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(prefix) => content_type
+                    .split_once('/')
+                    .is_some_and(|(top, _)| top == prefix),
+                None => *allowed == content_type,
+            })
+    }
+}
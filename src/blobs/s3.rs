@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use aws_sdk_s3::Client;
+use chrono::Utc;
+use futures_util::TryStreamExt;
+use tokio_util::io::ReaderStream;
+
+use super::{BlobError, BlobInfo, BlobStore, BlobStream, BlobWriter, PresignedRequest};
+use crate::config::S3Config;
+
+/// Size of the parts a multipart upload is split into. S3 requires at least
+/// 5 MiB for every part but the last.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Keeps blobs as objects in an S3 bucket, or any store speaking the same
+/// API (MinIO, R2, ...). Shared by every instance of the service.
+pub struct S3BlobStore {
+    client: Client,
+    bucket: String,
+    presign_ttl: Duration,
+}
+
+impl S3BlobStore {
+    pub async fn new(config: &S3Config) -> Self {
+        let mut loader = aws_config::from_env();
+        if let Some(region) = &config.region {
+            loader = loader.region(aws_config::Region::new(region.clone()));
+        }
+        if let Some(endpoint) = &config.endpoint {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let shared = loader.load().await;
+        let s3_config = aws_sdk_s3::config::Builder::from(&shared)
+            .force_path_style(config.force_path_style)
+            .build();
+
+        S3BlobStore {
+            client: Client::from_conf(s3_config),
+            bucket: config.bucket.clone(),
+            presign_ttl: config.presign_ttl,
+        }
+    }
+
+    fn presigning(&self) -> Result<PresigningConfig, BlobError> {
+        PresigningConfig::expires_in(self.presign_ttl).map_err(storage_error)
+    }
+
+    fn presigned(&self, request: aws_sdk_s3::presigning::PresignedRequest) -> PresignedRequest {
+        PresignedRequest {
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+            headers: request
+                .headers()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            expires_at: Utc::now()
+                + chrono::Duration::from_std(self.presign_ttl).unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn create(&self, key: &str) -> Result<Box<dyn BlobWriter>, BlobError> {
+        Ok(Box::new(S3BlobWriter {
+            client: self.client.clone(),
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            buffer: Vec::new(),
+            upload_id: None,
+            parts: Vec::new(),
+        }))
+    }
+
+    async fn open(&self, key: &str) -> Result<BlobStream, BlobError> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| match e.as_service_error() {
+                Some(service) if service.is_no_such_key() => BlobError::NotFound,
+                _ => storage_error(e),
+            })?;
+
+        Ok(Box::pin(
+            ReaderStream::new(output.body.into_async_read()).map_err(BlobError::from),
+        ))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), BlobError> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<BlobInfo>, BlobError> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(BlobInfo {
+                size: output.content_length().unwrap_or_default().max(0) as u64,
+                content_type: output.content_type().map(str::to_string),
+            })),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(storage_error(e)),
+        }
+    }
+
+    async fn presign_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+        size: u64,
+    ) -> Result<Option<PresignedRequest>, BlobError> {
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .content_length(size as i64)
+            .presigned(self.presigning()?)
+            .await
+            .map_err(storage_error)?;
+        Ok(Some(self.presigned(request)))
+    }
+
+    async fn presign_download(
+        &self,
+        key: &str,
+        file_name: &str,
+        content_type: &str,
+    ) -> Result<Option<PresignedRequest>, BlobError> {
+        let file_name = file_name.replace(['"', '\\'], "_");
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .response_content_type(content_type)
+            .response_content_disposition(format!("attachment; filename=\"{}\"", file_name))
+            .presigned(self.presigning()?)
+            .await
+            .map_err(storage_error)?;
+        Ok(Some(self.presigned(request)))
+    }
+}
+
+/// Buffers small blobs into a single `PutObject`; anything larger than one
+/// part switches to a multipart upload so memory use stays bounded.
+struct S3BlobWriter {
+    client: Client,
+    bucket: String,
+    key: String,
+    buffer: Vec<u8>,
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+}
+
+impl S3BlobWriter {
+    async fn upload_part(&mut self) -> Result<(), BlobError> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let output = self
+                    .client
+                    .create_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(&self.key)
+                    .send()
+                    .await
+                    .map_err(storage_error)?;
+                let upload_id = output
+                    .upload_id()
+                    .ok_or_else(|| BlobError::Storage("missing multipart upload ID".to_string()))?
+                    .to_string();
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+
+        let part_number = self.parts.len() as i32 + 1;
+        let body = std::mem::take(&mut self.buffer);
+        let output = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(storage_error)?;
+        self.parts.push(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .set_e_tag(output.e_tag().map(str::to_string))
+                .build(),
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlobWriter for S3BlobWriter {
+    async fn write(&mut self, chunk: &[u8]) -> Result<(), BlobError> {
+        self.buffer.extend_from_slice(chunk);
+        if self.buffer.len() >= PART_SIZE {
+            self.upload_part().await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<(), BlobError> {
+        if self.upload_id.is_none() {
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .body(ByteStream::from(std::mem::take(&mut self.buffer)))
+                .send()
+                .await
+                .map_err(storage_error)?;
+            return Ok(());
+        }
+
+        if !self.buffer.is_empty() {
+            self.upload_part().await?;
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .set_upload_id(self.upload_id.clone())
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(std::mem::take(&mut self.parts)))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(storage_error)?;
+        Ok(())
+    }
+
+    async fn abort(self: Box<Self>) {
+        let Some(upload_id) = self.upload_id else {
+            return;
+        };
+        if let Err(e) = self
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .send()
+            .await
+        {
+            log::warn!(
+                "event=blob_abort_failed key={} error=\"{}\"",
+                self.key,
+                DisplayErrorContext(e)
+            );
+        }
+    }
+}
+
+fn storage_error<E: std::error::Error>(e: E) -> BlobError {
+    BlobError::Storage(DisplayErrorContext(e).to_string())
+}
@@ -0,0 +1,130 @@
+//! Domain events across instances. The [`EventBus`] of an instance only
+//! has the events of changes made through it, so with several instances
+//! behind a load balancer, a client following live changes would miss
+//! those made through the others. The bridge publishes every event of the
+//! local bus to a broker all instances subscribe to, and delivers the
+//! events of the other instances to the local subscribers. Like the bus it
+//! is best effort: events sent while the broker is unreachable are lost,
+//! and clients that must not miss any catch up through sync.
+
+#[cfg(feature = "nats")]
+mod nats;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::config::{EventBridgeBackend, EventBridgeConfig};
+use crate::events::EventBus;
+use crate::outbox::EventMessage;
+
+#[cfg(feature = "nats")]
+pub use self::nats::NatsTransport;
+
+/// Wait before subscribing again after the subscription ended.
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct BridgeError(pub String);
+
+/// A broker carrying messages between instances.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Sends `message` to every subscribed instance, this one included.
+    async fn publish(&self, message: &[u8]) -> Result<(), BridgeError>;
+
+    /// Hands every message published from now on to `deliver`, until the
+    /// subscription ends, and returns why it did.
+    async fn subscribe(&self, deliver: &(dyn Fn(Vec<u8>) + Send + Sync)) -> BridgeError;
+}
+
+/// What goes over the broker: an event and the instance it happened on,
+/// which skips it when the broker hands it back.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    origin: String,
+    message: EventMessage,
+}
+
+/// Builds the transport selected by `EVENT_BRIDGE`, or `None` when events
+/// stay on their instance.
+pub fn from_config(config: &EventBridgeConfig) -> Result<Option<Arc<dyn Transport>>, BridgeError> {
+    match config.backend {
+        EventBridgeBackend::None => Ok(None),
+        #[cfg(feature = "nats")]
+        EventBridgeBackend::Nats => Ok(Some(Arc::new(NatsTransport::new(&config.nats)))),
+        #[cfg(not(feature = "nats"))]
+        EventBridgeBackend::Nats => Err(BridgeError(
+            "The NATS event bridge requires building with the `nats` feature".to_string(),
+        )),
+    }
+}
+
+/// Connects `bus` to the other instances on `transport`.
+pub fn start(bus: &EventBus, transport: Arc<dyn Transport>) {
+    let origin = Uuid::new_v4().to_string();
+
+    let mut events = bus.subscribe_local();
+    let publisher = transport.clone();
+    let sender = origin.clone();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    log::warn!(
+                        "event=bridge_events_missed transport={} count={}",
+                        publisher.name(),
+                        missed
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let envelope = Envelope {
+                origin: sender.clone(),
+                message: EventMessage::new(&event, Utc::now()),
+            };
+            let message = serde_json::to_vec(&envelope).expect("envelopes serialize");
+            if let Err(e) = publisher.publish(&message).await {
+                log::warn!(
+                    "event=bridge_publish_failed transport={} kind={} todo_id={} error=\"{}\"",
+                    publisher.name(),
+                    event.name(),
+                    event.todo_id(),
+                    e
+                );
+            }
+        }
+    });
+
+    let bus = bus.clone();
+    tokio::spawn(async move {
+        let deliver = move |message: Vec<u8>| match serde_json::from_slice::<Envelope>(&message) {
+            Ok(envelope) if envelope.origin == origin => {}
+            Ok(envelope) => match envelope.message.into_event() {
+                Ok(event) => bus.deliver(event),
+                Err(e) => log::warn!("event=bridge_message_invalid error=\"{}\"", e),
+            },
+            Err(e) => log::warn!("event=bridge_message_invalid error=\"{}\"", e),
+        };
+        loop {
+            let e = transport.subscribe(&deliver).await;
+            log::warn!(
+                "event=bridge_subscription_ended transport={} error=\"{}\"",
+                transport.name(),
+                e
+            );
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    });
+}
@@ -0,0 +1,122 @@
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::{BridgeError, Transport};
+use crate::config::NatsBridgeConfig;
+use crate::outbox::nats::connect;
+
+/// How long a publish may take before the connection is given up on.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Carries messages on one NATS subject, over one connection to publish
+/// and another to receive. As with the event sink, a publish only counts
+/// once a `PONG` shows the server processed it.
+pub struct NatsTransport {
+    address: String,
+    subject: String,
+    /// Opened on first publish, and again after any error.
+    connection: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl NatsTransport {
+    pub fn new(config: &NatsBridgeConfig) -> Self {
+        NatsTransport {
+            address: config.address.clone(),
+            subject: config.subject.clone(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    async fn send(&self, stream: &mut BufStream<TcpStream>, message: &[u8]) -> io::Result<()> {
+        let command = format!("PUB {} {}\r\n", self.subject, message.len());
+        stream.write_all(command.as_bytes()).await?;
+        stream.write_all(message).await?;
+        stream.write_all(b"\r\nPING\r\n").await?;
+        stream.flush().await?;
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            match line.trim_end() {
+                "PONG" => return Ok(()),
+                "PING" => {
+                    stream.write_all(b"PONG\r\n").await?;
+                    stream.flush().await?;
+                }
+                error if error.starts_with("-ERR") => return Err(io::Error::other(error)),
+                _ => {}
+            }
+        }
+    }
+
+    async fn receive(&self, deliver: &(dyn Fn(Vec<u8>) + Send + Sync)) -> io::Result<()> {
+        let mut stream = connect(&self.address).await?;
+        let command = format!("SUB {} 1\r\n", self.subject);
+        stream.write_all(command.as_bytes()).await?;
+        stream.flush().await?;
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let line = line.trim_end();
+            if line == "PING" {
+                stream.write_all(b"PONG\r\n").await?;
+                stream.flush().await?;
+            } else if line.starts_with("MSG ") {
+                // `MSG <subject> <sid> [reply-to] <bytes>`, then the payload.
+                let size: usize = line
+                    .rsplit(' ')
+                    .next()
+                    .and_then(|size| size.parse().ok())
+                    .ok_or_else(|| io::Error::other(format!("malformed MSG: {:?}", line)))?;
+                let mut payload = vec![0; size + 2];
+                stream.read_exact(&mut payload).await?;
+                payload.truncate(size);
+                deliver(payload);
+            } else if line.starts_with("-ERR") {
+                return Err(io::Error::other(line.to_string()));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for NatsTransport {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn publish(&self, message: &[u8]) -> Result<(), BridgeError> {
+        let mut connection = self.connection.lock().await;
+        let result = tokio::time::timeout(PUBLISH_TIMEOUT, async {
+            let stream = match &mut *connection {
+                Some(stream) => stream,
+                slot => slot.insert(connect(&self.address).await?),
+            };
+            self.send(stream, message).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()));
+
+        result.map_err(|e| {
+            *connection = None;
+            BridgeError(format!("{}: {}", self.address, e))
+        })
+    }
+
+    async fn subscribe(&self, deliver: &(dyn Fn(Vec<u8>) + Send + Sync)) -> BridgeError {
+        match self.receive(deliver).await {
+            Ok(()) => BridgeError("subscription closed".to_string()),
+            Err(e) => BridgeError(format!("{}: {}", self.address, e)),
+        }
+    }
+}
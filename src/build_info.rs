@@ -0,0 +1,61 @@
+//! What this binary was built from, to tell apart the versions deployed.
+//! Every response names it in [`VERSION_HEADER`], so a client report or a
+//! captured response shows the build that served it.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+
+/// Response header carrying [`version`].
+pub const VERSION_HEADER: &str = "X-Api-Version";
+
+/// The crate's version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The short SHA of the commit built, or `None` when it was built outside
+/// a git checkout.
+pub fn commit() -> Option<&'static str> {
+    Some(env!("VERGEN_GIT_SHA")).filter(|sha| *sha != "VERGEN_IDEMPOTENT_OUTPUT")
+}
+
+/// [`VERSION`] with the commit as build metadata, e.g. `0.1.0+1a2b3c4`.
+pub fn version() -> String {
+    match commit() {
+        Some(commit) => format!("{}+{}", VERSION, commit),
+        None => VERSION.to_string(),
+    }
+}
+
+/// The optional cargo features built in.
+pub fn features() -> Vec<&'static str> {
+    [
+        ("postgres", cfg!(feature = "postgres")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("s3", cfg!(feature = "s3")),
+        ("kafka", cfg!(feature = "kafka")),
+        ("nats", cfg!(feature = "nats")),
+        ("scylla-tls", cfg!(feature = "scylla-tls")),
+        ("ui", cfg!(feature = "ui")),
+        ("seed", cfg!(feature = "seed")),
+        ("chaos", cfg!(feature = "chaos")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+/// Middleware setting [`VERSION_HEADER`] on responses. Requests another
+/// middleware rejects get theirs from [`AppError`](crate::error::AppError),
+/// as they only become responses after passing through here.
+pub async fn add_version_header(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&version()) {
+        res.headers_mut()
+            .insert(HeaderName::from_static("x-api-version"), value);
+    }
+    Ok(res)
+}
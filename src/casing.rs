@@ -0,0 +1,102 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::Next;
+use serde_json::Value;
+
+use crate::config::FieldCase;
+use crate::error::AppError;
+
+/// Request header choosing the key style of JSON responses: `camel`
+/// (the default) or `snake`.
+pub const FIELD_CASE_HEADER: &str = "X-Field-Case";
+
+/// Middleware rewriting the keys of JSON responses to snake_case for
+/// clients that ask for it. The models serialize as camelCase, so camel
+/// responses pass through untouched. The server-wide default is read from
+/// the `FieldCase` app data.
+pub async fn apply_field_case(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let case = match req.headers().get(FIELD_CASE_HEADER) {
+        None => req
+            .app_data::<FieldCase>()
+            .copied()
+            .unwrap_or(FieldCase::Camel),
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| {
+                AppError::BadRequest(format!("{} must be 'camel' or 'snake'", FIELD_CASE_HEADER))
+            })?,
+    };
+
+    let mut res = next.call(req).await?;
+    res.headers_mut()
+        .append(header::VARY, HeaderValue::from_static(FIELD_CASE_HEADER));
+
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if case == FieldCase::Camel || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        AppError::Internal(e.to_string())
+    })?;
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut value) => {
+            to_snake_keys(&mut value);
+            serde_json::to_vec(&value).map_err(|e| AppError::Internal(e.to_string()))?
+        }
+        Err(_) => bytes.to_vec(),
+    };
+
+    Ok(ServiceResponse::new(
+        req,
+        res.set_body(body).map_into_boxed_body(),
+    ))
+}
+
+fn to_snake_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let entries = std::mem::take(map);
+            for (key, mut value) in entries {
+                to_snake_keys(&mut value);
+                map.insert(snake_case(&key), value);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(to_snake_keys),
+        _ => {}
+    }
+}
+
+/// `nextOccurrenceId` becomes `next_occurrence_id`. Keys that are not
+/// camelCase identifiers, such as dates or header names, are kept as-is.
+fn snake_case(key: &str) -> String {
+    let is_identifier = key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.chars().all(|c| c.is_ascii_alphanumeric());
+    if !is_identifier {
+        return key.to_string();
+    }
+
+    let mut snake = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
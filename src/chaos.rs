@@ -0,0 +1,201 @@
+//! Fault injection, to check how clients' retries and circuit breakers
+//! cope with this service misbehaving. Rules set through
+//! `PUT /api/dev/chaos` delay, fail or drop a share of the requests to a
+//! route. A dropped request is handled, so its changes are made, but its
+//! response is held back until the client gives up. Rules are kept by each
+//! instance and lost on restart. Only built with the `chaos` feature,
+//! which must never be enabled in production.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+/// Longest delay a rule may add.
+const MAX_LATENCY_MS: u64 = 60_000;
+
+/// How long the response of a dropped request is held back; longer than
+/// any client should wait.
+const DROP_HOLD: Duration = Duration::from_secs(300);
+
+/// The route of the rules themselves, never faulted so they can be cleared.
+const CHAOS_ROUTE: &str = "/dev/chaos";
+
+/// What to do to a share of the requests to a route. Shares are percents;
+/// a request is delayed independently of being failed or dropped.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultRule {
+    /// The end of the route templates it applies to, e.g. `/todos/{id}`,
+    /// or `*` for every route.
+    pub route: String,
+    /// `None` for every method.
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub latency_percent: f64,
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub error_percent: f64,
+    /// 429, 500, 502 or 503.
+    #[serde(default = "default_error_status")]
+    pub error_status: u16,
+    #[serde(default)]
+    pub drop_percent: f64,
+}
+
+fn default_error_status() -> u16 {
+    503
+}
+
+impl FaultRule {
+    fn validate(&self) -> Result<(), String> {
+        if self.route.is_empty() {
+            return Err("route must not be empty".to_string());
+        }
+        if let Some(method) = &self.method {
+            Method::from_bytes(method.as_bytes())
+                .map_err(|_| format!("{} is not an HTTP method", method))?;
+        }
+        for (name, percent) in [
+            ("latencyPercent", self.latency_percent),
+            ("errorPercent", self.error_percent),
+            ("dropPercent", self.drop_percent),
+        ] {
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("{} must be between 0 and 100", name));
+            }
+        }
+        if self.error_percent + self.drop_percent > 100.0 {
+            return Err("errorPercent and dropPercent must add up to at most 100".to_string());
+        }
+        if self.latency_ms > MAX_LATENCY_MS {
+            return Err(format!("latencyMs must be at most {}", MAX_LATENCY_MS));
+        }
+        if !matches!(self.error_status, 429 | 500 | 502 | 503) {
+            return Err("errorStatus must be 429, 500, 502 or 503".to_string());
+        }
+        Ok(())
+    }
+
+    fn applies_to(&self, method: &Method, route: &str) -> bool {
+        (self.route == "*" || route.ends_with(&self.route))
+            && self
+                .method
+                .as_ref()
+                .is_none_or(|m| m.eq_ignore_ascii_case(method.as_str()))
+    }
+
+    fn error(&self) -> AppError {
+        let message = "Fault injected for resilience testing".to_string();
+        match self.error_status {
+            429 => AppError::TooManyRequests(message, Duration::from_secs(1)),
+            500 => AppError::Internal(message),
+            502 => AppError::BadGateway(message),
+            _ => AppError::ServiceUnavailable(message),
+        }
+    }
+}
+
+/// The fault rules of this instance.
+#[derive(Default)]
+pub struct FaultInjector {
+    rules: RwLock<Vec<FaultRule>>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rules(&self) -> Vec<FaultRule> {
+        self.rules.read().unwrap().clone()
+    }
+
+    /// Replaces the rules; the first that applies to a request is used.
+    pub fn set(&self, rules: Vec<FaultRule>) -> Result<(), AppError> {
+        for (index, rule) in rules.iter().enumerate() {
+            rule.validate()
+                .map_err(|e| AppError::BadRequest(format!("Rule {}: {}", index, e)))?;
+        }
+        *self.rules.write().unwrap() = rules;
+        Ok(())
+    }
+
+    fn rule_for(&self, method: &Method, route: &str) -> Option<FaultRule> {
+        if route.ends_with(CHAOS_ROUTE) {
+            return None;
+        }
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .find(|rule| rule.applies_to(method, route))
+            .cloned()
+    }
+}
+
+/// Whether a roll falls within `percent`.
+fn roll(percent: f64) -> bool {
+    rand::random::<f64>() * 100.0 < percent
+}
+
+/// Middleware applying the [`FaultInjector`] in the app data, if any.
+pub async fn inject_faults(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let rule = match (
+        req.app_data::<web::Data<FaultInjector>>(),
+        req.match_pattern(),
+    ) {
+        (Some(injector), Some(route)) => injector
+            .rule_for(req.method(), &route)
+            .map(|rule| (rule, route)),
+        _ => None,
+    };
+    let Some((rule, route)) = rule else {
+        return next.call(req).await;
+    };
+
+    if roll(rule.latency_percent) {
+        log::info!(
+            "event=fault_injected kind=latency route={} latency_ms={}",
+            route,
+            rule.latency_ms
+        );
+        tokio::time::sleep(Duration::from_millis(rule.latency_ms)).await;
+    }
+    let outcome = rand::random::<f64>() * 100.0;
+    if outcome < rule.error_percent {
+        log::info!(
+            "event=fault_injected kind=error route={} status={}",
+            route,
+            rule.error_status
+        );
+        return Err(rule.error().into());
+    }
+    if outcome < rule.error_percent + rule.drop_percent {
+        let res = next.call(req).await?;
+        log::info!(
+            "event=fault_injected kind=drop route={} status={}",
+            route,
+            res.status().as_u16()
+        );
+        drop(res);
+        tokio::time::sleep(DROP_HOLD).await;
+        return Err(AppError::ServiceUnavailable(
+            "Response dropped for resilience testing".to_string(),
+        )
+        .into());
+    }
+    next.call(req).await
+}
@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::CircuitBreakerConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed {
+        consecutive_failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single probe request is in flight; everyone else keeps failing fast.
+    HalfOpen {
+        since: Instant,
+        probe: u64,
+    },
+}
+
+/// Leave to make one call, handed back with its outcome.
+#[must_use]
+#[derive(Debug, Clone, Copy)]
+pub struct Permit {
+    /// Set for the probe let through while half-open; only its outcome
+    /// decides whether the circuit closes.
+    probe: Option<u64>,
+}
+
+/// Classic three-state circuit breaker. After `failure_threshold` consecutive
+/// failures the circuit opens and calls fail fast for `open_duration`, after
+/// which one probe is let through to decide whether to close it again.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<State>,
+    probes: AtomicU64,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            failure_threshold: config.failure_threshold.max(1),
+            open_duration: config.open_duration,
+            state: Mutex::new(State::Closed {
+                consecutive_failures: 0,
+            }),
+            probes: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns `None` when the call should be rejected without touching the
+    /// database.
+    pub fn try_acquire(&self) -> Option<Permit> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => Some(Permit { probe: None }),
+            State::Open { until } if now >= until => {
+                log::info!("event=circuit_half_open");
+                Some(self.probe(&mut state, now))
+            }
+            State::Open { .. } => None,
+            // A probe that never reported back (e.g. a dropped request) must
+            // not wedge the breaker, so allow a new one after the cooldown.
+            State::HalfOpen { since, .. } if now.duration_since(since) >= self.open_duration => {
+                Some(self.probe(&mut state, now))
+            }
+            State::HalfOpen { .. } => None,
+        }
+    }
+
+    fn probe(&self, state: &mut State, now: Instant) -> Permit {
+        let probe = self.probes.fetch_add(1, Ordering::Relaxed);
+        *state = State::HalfOpen { since: now, probe };
+        Permit { probe: Some(probe) }
+    }
+
+    /// Whether `permit` is the probe the half-open circuit is waiting on.
+    /// Calls let through before the circuit opened, or earlier probes that
+    /// were given up on, can still finish late and must not decide.
+    fn is_probe(state: State, permit: Permit) -> bool {
+        matches!(state, State::HalfOpen { probe, .. } if permit.probe == Some(probe))
+    }
+
+    pub fn on_success(&self, permit: Permit) {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => {
+                *state = State::Closed {
+                    consecutive_failures: 0,
+                };
+            }
+            State::HalfOpen { .. } if Self::is_probe(*state, permit) => {
+                log::info!("event=circuit_closed");
+                *state = State::Closed {
+                    consecutive_failures: 0,
+                };
+            }
+            State::HalfOpen { .. } | State::Open { .. } => {}
+        }
+    }
+
+    pub fn on_failure(&self, permit: Permit) {
+        let mut state = self.state.lock().unwrap();
+        let open = State::Open {
+            until: Instant::now() + self.open_duration,
+        };
+        match *state {
+            State::Closed {
+                consecutive_failures,
+            } => {
+                let consecutive_failures = consecutive_failures + 1;
+                if consecutive_failures >= self.failure_threshold {
+                    log::warn!(
+                        "event=circuit_opened consecutive_failures={} open_ms={}",
+                        consecutive_failures,
+                        self.open_duration.as_millis()
+                    );
+                    *state = open;
+                } else {
+                    *state = State::Closed {
+                        consecutive_failures,
+                    };
+                }
+            }
+            State::HalfOpen { .. } if Self::is_probe(*state, permit) => {
+                log::warn!(
+                    "event=circuit_reopened open_ms={}",
+                    self.open_duration.as_millis()
+                );
+                *state = open;
+            }
+            State::HalfOpen { .. } | State::Open { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::io::Write;
+
+use actix_web::body::{self, BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::web;
+use flate2::write::GzEncoder;
+
+use crate::config::CompressionConfig;
+use crate::error::AppError;
+
+/// Content types worth compressing; anything else (images, archives,
+/// attachments) is usually compressed already.
+const COMPRESSIBLE_TYPES: [&str; 5] = [
+    "application/json",
+    "application/x-ndjson",
+    "application/xml",
+    "text/",
+    "image/svg+xml",
+];
+
+/// A `Content-Encoding` the server can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The encoding to use for an `Accept-Encoding` value: the one with the
+    /// highest quality, brotli on a tie. `None` when neither is accepted.
+    pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+        let mut best: Option<(Encoding, f32)> = None;
+        for (encoding, quality) in [Encoding::Brotli, Encoding::Gzip]
+            .into_iter()
+            .map(|encoding| (encoding, quality(accept_encoding, encoding.as_str())))
+        {
+            if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+                best = Some((encoding, quality));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+
+    pub fn encode(&self, bytes: &[u8], config: &CompressionConfig) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: config.brotli_quality as i32,
+                    ..Default::default()
+                };
+                let mut compressed = Vec::new();
+                brotli::BrotliCompress(&mut &bytes[..], &mut compressed, &params)?;
+                Ok(compressed)
+            }
+            Encoding::Gzip => {
+                let mut encoder =
+                    GzEncoder::new(Vec::new(), flate2::Compression::new(config.gzip_level));
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// The quality `accept_encoding` gives `coding`, counting `*` for codings
+/// not listed by name; 0 when it is not accepted at all.
+fn quality(accept_encoding: &str, coding: &str) -> f32 {
+    let mut wildcard = None;
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .map_or(Some(1.0), |q| q.parse::<f32>().ok())
+            .unwrap_or(0.0);
+        if name.eq_ignore_ascii_case(coding) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    wildcard.unwrap_or(0.0)
+}
+
+/// Middleware compressing response bodies for clients that send a
+/// matching `Accept-Encoding`, as configured by the `CompressionConfig` app
+/// data. Streamed bodies, bodies under the minimum size and content that
+/// is already compressed are passed through.
+pub async fn compress_responses(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(config) = req
+        .app_data::<CompressionConfig>()
+        .copied()
+        .filter(|config| config.enabled)
+    else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let encoding = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Encoding::negotiate);
+    let is_head = req.method() == Method::HEAD;
+
+    let mut res = next.call(req).await?;
+    res.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+    let Some(encoding) = encoding else {
+        return Ok(res.map_into_boxed_body());
+    };
+    if is_head || !should_compress(&res, config.min_size) {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        AppError::Internal(e.to_string())
+    })?;
+    // Large exports take a while to compress, so keep it off the executor.
+    let compressed = web::block(move || encoding.encode(&bytes, &config))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::Internal(format!("Failed to compress response: {}", e)))?;
+
+    res.headers_mut().insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    res.headers_mut().remove(header::CONTENT_LENGTH);
+    Ok(ServiceResponse::new(
+        req,
+        res.set_body(compressed).map_into_boxed_body(),
+    ))
+}
+
+fn should_compress<B: MessageBody>(res: &ServiceResponse<B>, min_size: usize) -> bool {
+    let is_compressible = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            COMPRESSIBLE_TYPES
+                .iter()
+                .any(|compressible| value.starts_with(compressible))
+        });
+    let is_large = match res.response().body().size() {
+        BodySize::Sized(size) => size >= min_size as u64,
+        BodySize::None | BodySize::Stream => false,
+    };
+
+    is_compressible
+        && is_large
+        && res.status() != StatusCode::NO_CONTENT
+        && !res.headers().contains_key(header::CONTENT_ENCODING)
+}
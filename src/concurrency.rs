@@ -0,0 +1,268 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::ConcurrencyConfig;
+use crate::error::AppError;
+use crate::model::AppState;
+
+/// Kinds of requests that get their own concurrency limit, on top of the
+/// global one every request is subject to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    Lists,
+    Bulk,
+    Uploads,
+}
+
+impl RouteGroup {
+    pub const ALL: [RouteGroup; 3] = [RouteGroup::Lists, RouteGroup::Bulk, RouteGroup::Uploads];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Lists => "lists",
+            RouteGroup::Bulk => "bulk",
+            RouteGroup::Uploads => "uploads",
+        }
+    }
+
+    /// The group of a request, from its method and route template (e.g.
+    /// `/api/v1/todos/{id}/comments`). Single-item routes belong to none.
+    pub fn of(method: &Method, route: &str) -> Option<RouteGroup> {
+        const LISTS: [&str; 19] = [
+            "/todos",
+            "/todos/stats",
+            "/todos/shared-with-me",
+            "/todos/by-title/{title}",
+            "/todos/{id}/occurrences",
+            "/todos/{id}/comments",
+            "/todos/{id}/attachments",
+            "/todos/{id}/activity",
+            "/activity",
+            "/sync",
+            "/projects",
+            "/projects/{id}/todos",
+            "/reminders",
+            "/templates",
+            "/webhooks",
+            "/webhooks/dead-letters",
+            "/workspaces",
+            "/workspaces/{id}/members",
+            "/admin/stats",
+        ];
+        const BULK: [(Method, &str); 8] = [
+            (Method::DELETE, "/todos"),
+            (Method::POST, "/todos/batch-get"),
+            (Method::PATCH, "/todos/complete"),
+            (Method::PATCH, "/todos/incomplete"),
+            (Method::PATCH, "/todos/reorder"),
+            (Method::POST, "/undo"),
+            (Method::POST, "/sync"),
+            (Method::POST, "/dev/seed"),
+        ];
+        const UPLOADS: [&str; 2] = [
+            "/todos/{id}/attachments",
+            "/todos/{id}/attachments/{attachment_id}/complete",
+        ];
+
+        if BULK
+            .iter()
+            .any(|(bulk_method, suffix)| bulk_method == method && route.ends_with(suffix))
+        {
+            return Some(RouteGroup::Bulk);
+        }
+        if *method == Method::POST && UPLOADS.iter().any(|suffix| route.ends_with(suffix)) {
+            return Some(RouteGroup::Uploads);
+        }
+        if (*method == Method::GET || *method == Method::HEAD)
+            && LISTS.iter().any(|suffix| route.ends_with(suffix))
+        {
+            return Some(RouteGroup::Lists);
+        }
+        None
+    }
+}
+
+/// Point-in-time counters of one limit.
+#[derive(Debug, Clone)]
+pub struct LimitSnapshot {
+    pub name: &'static str,
+    /// `None` when the limit is disabled.
+    pub limit: Option<usize>,
+    pub in_flight: usize,
+    /// Highest `in_flight` seen since startup.
+    pub peak: usize,
+    pub admitted: u64,
+    /// Requests turned away after waiting the full queue timeout.
+    pub rejected: u64,
+}
+
+struct Limit {
+    name: &'static str,
+    limit: Option<usize>,
+    semaphore: Arc<Semaphore>,
+    in_flight: Arc<AtomicUsize>,
+    peak: AtomicUsize,
+    admitted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Held for as long as a request runs; frees its slots when dropped.
+pub struct Permit {
+    _permits: Vec<OwnedSemaphorePermit>,
+    counters: Vec<Arc<AtomicUsize>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        for in_flight in &self.counters {
+            in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Limit {
+    fn new(name: &'static str, limit: usize) -> Self {
+        let limit = (limit > 0).then_some(limit);
+        Limit {
+            name,
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit.unwrap_or(Semaphore::MAX_PERMITS))),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            peak: AtomicUsize::new(0),
+            admitted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    async fn acquire(&self, deadline: tokio::time::Instant) -> Option<OwnedSemaphorePermit> {
+        let permit = tokio::time::timeout_at(deadline, self.semaphore.clone().acquire_owned())
+            .await
+            .ok()
+            .and_then(Result::ok);
+        match &permit {
+            Some(_) => {
+                let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+                self.peak.fetch_max(in_flight, Ordering::Relaxed);
+                self.admitted.fetch_add(1, Ordering::Relaxed);
+            }
+            None => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        permit
+    }
+
+    fn snapshot(&self) -> LimitSnapshot {
+        LimitSnapshot {
+            name: self.name,
+            limit: self.limit,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            admitted: self.admitted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Bounds how many requests run at once, overall and per [`RouteGroup`].
+/// Requests over a limit queue for up to the configured timeout and are
+/// then rejected, rather than piling up on the database pool.
+pub struct ConcurrencyLimiter {
+    global: Limit,
+    lists: Limit,
+    bulk: Limit,
+    uploads: Limit,
+    queue_timeout: Duration,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        ConcurrencyLimiter {
+            global: Limit::new("global", config.global),
+            lists: Limit::new(RouteGroup::Lists.as_str(), config.lists),
+            bulk: Limit::new(RouteGroup::Bulk.as_str(), config.bulk),
+            uploads: Limit::new(RouteGroup::Uploads.as_str(), config.uploads),
+            queue_timeout: config.queue_timeout,
+        }
+    }
+
+    fn group(&self, group: RouteGroup) -> &Limit {
+        match group {
+            RouteGroup::Lists => &self.lists,
+            RouteGroup::Bulk => &self.bulk,
+            RouteGroup::Uploads => &self.uploads,
+        }
+    }
+
+    /// Takes a slot in the request's group and then in the global limit.
+    /// The group goes first so requests queued behind a busy group do not
+    /// hold global slots other requests could use.
+    pub async fn acquire(&self, group: Option<RouteGroup>) -> Result<Permit, AppError> {
+        let deadline = tokio::time::Instant::now() + self.queue_timeout;
+        let mut permits = Vec::with_capacity(2);
+        let mut counters = Vec::with_capacity(2);
+
+        let limits = group
+            .map(|group| self.group(group))
+            .into_iter()
+            .chain([&self.global]);
+        for limit in limits {
+            let Some(permit) = limit.acquire(deadline).await else {
+                log::warn!(
+                    "event=request_rejected limit={} timeout_ms={}",
+                    limit.name,
+                    self.queue_timeout.as_millis()
+                );
+                // Dropping the permits taken so far releases them.
+                drop(Permit {
+                    _permits: permits,
+                    counters,
+                });
+                return Err(AppError::ServiceUnavailable(
+                    "Server is busy, try again shortly".to_string(),
+                ));
+            };
+            permits.push(permit);
+            counters.push(limit.in_flight.clone());
+        }
+
+        Ok(Permit {
+            _permits: permits,
+            counters,
+        })
+    }
+
+    /// The global limit first, then each group.
+    pub fn snapshot(&self) -> Vec<LimitSnapshot> {
+        std::iter::once(&self.global)
+            .chain(RouteGroup::ALL.iter().map(|group| self.group(*group)))
+            .map(Limit::snapshot)
+            .collect()
+    }
+}
+
+/// Middleware holding a slot of the `AppState` limiter while the request
+/// is handled. Streamed response bodies are not covered once the handler
+/// has returned.
+pub async fn limit_concurrency(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return next.call(req).await;
+    };
+    let group = req
+        .match_pattern()
+        .and_then(|route| RouteGroup::of(req.method(), &route));
+
+    let _permit = state.concurrency.acquire(group).await?;
+    next.call(req).await
+}
@@ -0,0 +1,54 @@
+use std::future::Future;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+
+use crate::error::AppError;
+
+/// Request header asking for reads that see the client's earlier writes:
+/// `true` to read at the stronger `SCYLLA_READ_YOUR_WRITES_CONSISTENCY`.
+pub const READ_YOUR_WRITES_HEADER: &str = "X-Read-Your-Writes";
+
+tokio::task_local! {
+    static READ_YOUR_WRITES: bool;
+}
+
+/// Middleware running requests that send [`READ_YOUR_WRITES_HEADER`] with
+/// their Scylla reads at the read-your-writes consistency. Writes already
+/// respond with the todo as written, so this is for reads that follow them,
+/// which at the default `LOCAL_ONE` may reach a replica the write has not.
+/// Streamed response bodies are read after the request completes, at the
+/// ordinary read consistency.
+pub async fn read_your_writes(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let enabled = match req.headers().get(READ_YOUR_WRITES_HEADER) {
+        None => false,
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "{} must be 'true' or 'false'",
+                    READ_YOUR_WRITES_HEADER
+                ))
+            })?,
+    };
+
+    READ_YOUR_WRITES.scope(enabled, next.call(req)).await
+}
+
+/// Runs `future` reading its own writes or not, whichever task polls it.
+pub async fn with_reads_own_writes<F: Future>(enabled: bool, future: F) -> F::Output {
+    READ_YOUR_WRITES.scope(enabled, future).await
+}
+
+/// Whether the current request asked to read its own writes.
+pub fn reads_own_writes() -> bool {
+    READ_YOUR_WRITES
+        .try_with(|enabled| *enabled)
+        .unwrap_or(false)
+}
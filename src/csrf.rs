@@ -0,0 +1,89 @@
+//! Double-submit CSRF protection for cookie sessions. A page on another
+//! site can make the browser send a request with the session cookies
+//! attached, but cannot read them; so a mutating request authenticated by
+//! cookie must repeat the CSRF cookie's value in the `X-CSRF-Token` header.
+
+use actix_web::body::MessageBody;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use rand::Rng;
+
+use crate::auth::{ACCESS_COOKIE, REFRESH_COOKIE};
+use crate::config::CookieAuthConfig;
+use crate::error::AppError;
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+pub fn new_token() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// The cookie holding `token`. Unlike the session cookies, scripts can
+/// read it, which is how the page's own code copies it into the header.
+pub fn cookie(token: &str, config: CookieAuthConfig) -> Cookie<'static> {
+    Cookie::build(CSRF_COOKIE, token.to_string())
+        .path("/")
+        .secure(config.secure)
+        .same_site(SameSite::Strict)
+        .finish()
+}
+
+/// Rejects mutating requests authenticated by a session cookie unless
+/// their `X-CSRF-Token` header matches the CSRF cookie. Requests with an
+/// `Authorization` header, and all requests when cookie sessions are off,
+/// pass untouched: browsers never add that header on their own.
+pub async fn protect(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let enabled = req
+        .app_data::<CookieAuthConfig>()
+        .is_some_and(|config| config.enabled);
+    let uses_cookies = !req.headers().contains_key(header::AUTHORIZATION)
+        && (req.cookie(ACCESS_COOKIE).is_some() || req.cookie(REFRESH_COOKIE).is_some());
+    if !enabled || is_safe(req.method()) || !uses_cookies {
+        return next.call(req).await;
+    }
+
+    let expected = req.cookie(CSRF_COOKIE);
+    let given = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match (expected, given) {
+        (Some(expected), Some(given)) if matches(expected.value(), given) => next.call(req).await,
+        (expected, given) => {
+            log::warn!(
+                "event=csrf_rejected method={} path={} cookie={} header={}",
+                req.method(),
+                req.path(),
+                expected.is_some(),
+                given.is_some()
+            );
+            Err(AppError::Forbidden(format!(
+                "Missing or invalid {} header; get a token from /api/auth/csrf",
+                CSRF_HEADER
+            ))
+            .into())
+        }
+    }
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Compares in constant time, so response times do not tell how much of
+/// a guessed token was right.
+fn matches(expected: &str, given: &str) -> bool {
+    !expected.is_empty()
+        && expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+#[cfg(feature = "scylla-tls")]
+use openssl::error::ErrorStack;
+#[cfg(feature = "scylla-tls")]
+use openssl::ssl::{SslContext, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use rand::Rng;
+use scylla::authentication::{AuthError, AuthenticatorProvider, AuthenticatorSession};
+use scylla::load_balancing::DefaultPolicy;
+use scylla::speculative_execution::{SimpleSpeculativeExecutionPolicy, SpeculativeExecutionPolicy};
+use scylla::transport::errors::NewSessionError;
+use scylla::{ExecutionProfile, Session, SessionBuilder};
+
+use crate::config::{DatabaseConfig, RetryConfig, ScyllaTlsConfig};
+use crate::secrets::{self, Secrets};
+
+/// Connects to Scylla, retrying with exponential backoff and full jitter until
+/// the configured window elapses. Container orchestrators routinely start the
+/// API before the database is accepting connections. Logs in with the
+/// Scylla credentials in `secrets`, if there are any, over TLS when
+/// `config.tls` is set.
+pub async fn connect_with_retry(
+    config: &DatabaseConfig,
+    secrets: &Secrets,
+) -> Result<Session, NewSessionError> {
+    let retry = &config.connect_retry;
+    let started = Instant::now();
+    let mut attempt: u32 = 1;
+
+    let mut builder = SessionBuilder::new()
+        .known_nodes(&config.contact_points)
+        .default_execution_profile_handle(execution_profile(config).into_handle());
+    if secrets.get(secrets::SCYLLA_USERNAME).is_some() {
+        builder = builder.authenticator_provider(Arc::new(SecretsAuthenticator {
+            secrets: secrets.clone(),
+        }));
+    }
+    if let Some(tls) = &config.tls {
+        builder = with_tls(builder, tls)?;
+    }
+
+    loop {
+        match builder.build().await {
+            Ok(session) => {
+                log::info!(
+                    "event=scylla_connected contact_points={} attempt={} elapsed_ms={}",
+                    config.contact_points.join(","),
+                    attempt,
+                    started.elapsed().as_millis()
+                );
+                return Ok(session);
+            }
+            Err(e) => {
+                let elapsed = started.elapsed();
+                if elapsed >= retry.max_elapsed {
+                    log::error!(
+                        "event=scylla_connect_failed contact_points={} attempt={} elapsed_ms={} error=\"{}\"",
+                        config.contact_points.join(","),
+                        attempt,
+                        elapsed.as_millis(),
+                        e
+                    );
+                    return Err(e);
+                }
+
+                let delay = backoff_delay(retry, attempt).min(retry.max_elapsed - elapsed);
+                log::warn!(
+                    "event=scylla_connect_retry contact_points={} attempt={} elapsed_ms={} retry_in_ms={} error=\"{}\"",
+                    config.contact_points.join(","),
+                    attempt,
+                    elapsed.as_millis(),
+                    delay.as_millis(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "scylla-tls")]
+fn with_tls(
+    builder: SessionBuilder,
+    tls: &ScyllaTlsConfig,
+) -> Result<SessionBuilder, NewSessionError> {
+    let context = ssl_context(tls).map_err(|e| {
+        NewSessionError::IoError(Arc::new(std::io::Error::other(format!(
+            "Invalid Scylla TLS configuration: {}",
+            e
+        ))))
+    })?;
+    Ok(builder.ssl_context(Some(context)))
+}
+
+#[cfg(not(feature = "scylla-tls"))]
+fn with_tls(
+    _builder: SessionBuilder,
+    _tls: &ScyllaTlsConfig,
+) -> Result<SessionBuilder, NewSessionError> {
+    Err(NewSessionError::IoError(Arc::new(std::io::Error::other(
+        "SCYLLA_TLS is set but this build lacks the scylla-tls feature",
+    ))))
+}
+
+/// Trusts `tls.ca_cert`, or the system roots without one, and presents the
+/// client certificate when both it and its key are configured.
+#[cfg(feature = "scylla-tls")]
+fn ssl_context(tls: &ScyllaTlsConfig) -> Result<SslContext, ErrorStack> {
+    let mut context = SslContextBuilder::new(SslMethod::tls())?;
+    match &tls.ca_cert {
+        Some(ca_cert) => context.set_ca_file(ca_cert)?,
+        None => context.set_default_verify_paths()?,
+    }
+    if let (Some(cert), Some(key)) = (&tls.client_cert, &tls.client_key) {
+        context.set_certificate_chain_file(cert)?;
+        context.set_private_key_file(key, SslFiletype::PEM)?;
+        context.check_private_key()?;
+    }
+    context.set_verify(if tls.verify_peer {
+        SslVerifyMode::PEER
+    } else {
+        SslVerifyMode::NONE
+    });
+    Ok(context.build())
+}
+
+/// The load balancing and speculative execution every statement runs with.
+/// Consistency is left to the statements, which the repository sets.
+fn execution_profile(config: &DatabaseConfig) -> ExecutionProfile {
+    let balancing = &config.load_balancing;
+    let mut policy = DefaultPolicy::builder()
+        .token_aware(balancing.token_aware)
+        .permit_dc_failover(balancing.permit_dc_failover);
+    if let Some(datacenter) = &balancing.local_datacenter {
+        policy = policy.prefer_datacenter(datacenter.clone());
+    }
+    let speculative_execution = config.speculative_execution.map(|speculative| {
+        Arc::new(SimpleSpeculativeExecutionPolicy {
+            max_retry_count: speculative.max_retry_count,
+            retry_interval: speculative.retry_interval,
+        }) as Arc<dyn SpeculativeExecutionPolicy>
+    });
+
+    ExecutionProfile::builder()
+        .load_balancing_policy(policy.build())
+        .speculative_execution_policy(speculative_execution)
+        .build()
+}
+
+/// Full-jitter backoff: a random delay between zero and the capped exponential step.
+fn backoff_delay(retry: &RetryConfig, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let ceiling = retry
+        .initial_backoff
+        .saturating_mul(1 << exponent)
+        .min(retry.max_backoff);
+    let millis = ceiling.as_millis() as u64;
+    Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+}
+
+/// Logs in with the current Scylla credentials each time a connection is
+/// opened, so connections opened after a rotation use the new password
+/// while established ones carry on.
+struct SecretsAuthenticator {
+    secrets: Secrets,
+}
+
+#[async_trait]
+impl AuthenticatorProvider for SecretsAuthenticator {
+    async fn start_authentication_session(
+        &self,
+        _authenticator_name: &str,
+    ) -> Result<(Option<Vec<u8>>, Box<dyn AuthenticatorSession>), AuthError> {
+        let username = self
+            .secrets
+            .get(secrets::SCYLLA_USERNAME)
+            .unwrap_or_default();
+        let password = self
+            .secrets
+            .get(secrets::SCYLLA_PASSWORD)
+            .unwrap_or_default();
+        // SASL PLAIN: an empty authorization identity, then the credentials.
+        let mut response = vec![0];
+        response.extend(username.as_bytes());
+        response.push(0);
+        response.extend(password.as_bytes());
+        Ok((Some(response), Box::new(PlainTextSession)))
+    }
+}
+
+struct PlainTextSession;
+
+#[async_trait]
+impl AuthenticatorSession for PlainTextSession {
+    async fn evaluate_challenge(
+        &mut self,
+        _token: Option<&[u8]>,
+    ) -> Result<Option<Vec<u8>>, AuthError> {
+        Err("Unexpected authentication challenge from Scylla".to_string())
+    }
+
+    async fn success(&mut self, _token: Option<&[u8]>) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
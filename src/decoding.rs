@@ -0,0 +1,66 @@
+//! Stored todos that cannot be read back, such as rows written with a
+//! malformed timestamp by a tool that bypassed the API, or content sealed
+//! with an encryption key that is no longer configured. By default a read
+//! of many todos leaves them out rather than failing on one bad row; each
+//! is logged, and a listing reports it in its `warnings`.
+//! `SCYLLA_STRICT_DECODING` fails the read instead. A lookup of the one
+//! todo always fails, since leaving it out would answer 404 for a todo
+//! that exists.
+
+use std::future::Future;
+use std::sync::Mutex;
+
+use crate::repository::RepositoryError;
+
+tokio::task_local! {
+    static SKIPPED: Mutex<Vec<String>>;
+}
+
+/// What a read does with a todo it cannot decode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowDecoding {
+    /// Leaves it out, logging it and reporting it to the request.
+    #[default]
+    Lenient,
+    /// Fails.
+    Strict,
+}
+
+impl RowDecoding {
+    pub fn new(strict: bool) -> Self {
+        if strict {
+            RowDecoding::Strict
+        } else {
+            RowDecoding::Lenient
+        }
+    }
+
+    /// The todo a row `decoded` to, or `None` for one left out.
+    pub fn check<T>(
+        self,
+        decoded: Result<T, RepositoryError>,
+    ) -> Result<Option<T>, RepositoryError> {
+        match decoded {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self == RowDecoding::Strict => Err(e),
+            Err(e) => {
+                log::warn!("event=todo_row_skipped error=\"{}\"", e);
+                let _ = SKIPPED.try_with(|skipped| skipped.lock().unwrap().push(e.to_string()));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Runs `read`, returning with its output why any todos it read were left
+/// out. Rows decoded after `read` completes, as those of a streamed
+/// response body, are only logged.
+pub async fn collect<F: Future>(read: F) -> (F::Output, Vec<String>) {
+    SKIPPED
+        .scope(Mutex::new(Vec::new()), async move {
+            let output = read.await;
+            let skipped = SKIPPED.with(|skipped| std::mem::take(&mut *skipped.lock().unwrap()));
+            (output, skipped)
+        })
+        .await
+}
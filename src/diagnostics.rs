@@ -0,0 +1,160 @@
+//! What is needed to triage a misbehaving instance: the build it runs, how
+//! it is configured and the state of its database, as served by
+//! `GET /api/debug/info`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::config::Config;
+use crate::migrations::SchemaStatus;
+use crate::repository::{RepositoryError, SchemaRepository};
+
+/// Facts about this instance kept from its startup.
+pub struct Diagnostics {
+    started_at: DateTime<Utc>,
+    started: Instant,
+    config: BTreeMap<&'static str, String>,
+    schema: Arc<dyn SchemaRepository>,
+}
+
+impl Diagnostics {
+    pub fn new(config: &Config, schema: Arc<dyn SchemaRepository>) -> Self {
+        Diagnostics {
+            started_at: Utc::now(),
+            started: Instant::now(),
+            config: snapshot(config),
+            schema,
+        }
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The settings that most often explain how an instance behaves, by the
+    /// variable that sets them. Secrets are left out, and passwords in URLs
+    /// redacted.
+    pub fn config(&self) -> &BTreeMap<&'static str, String> {
+        &self.config
+    }
+
+    /// The database's migrations, read now.
+    pub async fn schema(&self) -> Result<SchemaStatus, RepositoryError> {
+        self.schema.status().await
+    }
+}
+
+fn snapshot(config: &Config) -> BTreeMap<&'static str, String> {
+    let database = &config.database;
+    let mut settings = BTreeMap::from([
+        ("SERVER_HOST", config.server.host.clone()),
+        ("SERVER_PORT", config.server.port.to_string()),
+        ("STORAGE_BACKEND", format!("{:?}", config.storage.backend)),
+        ("LOG_FORMAT", format!("{:?}", config.logging.format)),
+        (
+            "COMPRESSION_ENABLED",
+            config.compression.enabled.to_string(),
+        ),
+        ("SCYLLA_CONTACT_POINTS", database.contact_points.join(",")),
+        (
+            "SCYLLA_READ_CONSISTENCY",
+            format!("{:?}", database.consistency.read),
+        ),
+        (
+            "SCYLLA_WRITE_CONSISTENCY",
+            format!("{:?}", database.consistency.write),
+        ),
+        (
+            "SCYLLA_QUERY_TIMEOUT_MS",
+            database.query_timeout.as_millis().to_string(),
+        ),
+        (
+            "SCYLLA_STRICT_DECODING",
+            database.strict_decoding.to_string(),
+        ),
+        ("SCYLLA_TLS", database.tls.is_some().to_string()),
+        ("EVENT_SINK", format!("{:?}", config.event_sink.backend)),
+        ("EVENT_BRIDGE", format!("{:?}", config.event_bridge.backend)),
+        (
+            "BLOB_STORAGE_BACKEND",
+            format!("{:?}", config.attachments.backend),
+        ),
+        ("SECRETS_BACKEND", format!("{:?}", config.secrets.backend)),
+        ("JOBS_CONCURRENCY", config.jobs.concurrency.to_string()),
+        (
+            "JOBS_LEADER_LEASE_SECS",
+            config.jobs.leader_lease.as_secs().to_string(),
+        ),
+        ("UNDO_WINDOW_SECS", config.undo_window.as_secs().to_string()),
+    ]);
+    if let Some(datacenter) = &database.load_balancing.local_datacenter {
+        settings.insert("SCYLLA_LOCAL_DATACENTER", datacenter.clone());
+    }
+    if let Some(key_id) = &config.encryption.active_key {
+        settings.insert("CONTENT_ENCRYPTION_KEY_ID", key_id.clone());
+    }
+    #[cfg(feature = "postgres")]
+    settings.insert("DATABASE_URL", redact_url(&config.postgres.url));
+    #[cfg(feature = "sqlite")]
+    settings.insert("SQLITE_URL", redact_url(&config.sqlite.url));
+    settings
+}
+
+/// Query parameters that carry a password in the database URLs sqlx
+/// accepts.
+const PASSWORD_PARAMETERS: [&str; 2] = ["password", "sslpassword"];
+
+/// `url` with the password of its user info and the values of its password
+/// query parameters, if any, replaced by `***`.
+pub fn redact_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    // The host follows the last `@` of the user info, which may itself
+    // hold a `?`, and ends where the path or the query begins.
+    let user_info_end = rest[..rest.find('/').unwrap_or(rest.len())]
+        .rfind('@')
+        .map_or(0, |at| at + 1);
+    let authority_end = rest[user_info_end..]
+        .find(['/', '?'])
+        .map_or(rest.len(), |end| user_info_end + end);
+    let (authority, rest) = rest.split_at(authority_end);
+    let (path, query) = match rest.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (rest, None),
+    };
+
+    let authority = match authority.rsplit_once('@') {
+        Some((user_info, host)) => match user_info.split_once(':') {
+            Some((user, _)) => format!("{}:***@{}", user, host),
+            None => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+    let mut redacted = format!("{}://{}{}", scheme, authority, path);
+    if let Some(query) = query {
+        let parameters: Vec<String> = query
+            .split('&')
+            .map(|parameter| match parameter.split_once('=') {
+                Some((name, _))
+                    if PASSWORD_PARAMETERS
+                        .iter()
+                        .any(|password| name.eq_ignore_ascii_case(password)) =>
+                {
+                    format!("{}=***", name)
+                }
+                _ => parameter.to_string(),
+            })
+            .collect();
+        redacted.push('?');
+        redacted.push_str(&parameters.join("&"));
+    }
+    redacted
+}
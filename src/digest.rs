@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::jobs::{Job, JobError};
+use crate::notifier::{Notification, Notifier};
+use crate::repository::{ListOptions, TodoRepository, TodoScope, TodoSort, TodoVisibility};
+
+/// Most todos listed in one digest.
+const DIGEST_MAX_TODOS: usize = 500;
+
+/// Job that sends the open-todo digest; scheduled once a day.
+pub struct DigestJob {
+    pub repository: Arc<dyn TodoRepository>,
+    pub notifier: Arc<dyn Notifier>,
+}
+
+impl DigestJob {
+    pub const KIND: &'static str = "digest.send";
+}
+
+#[async_trait]
+impl Job for DigestJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let count = send_digest(self.repository.as_ref(), self.notifier.as_ref()).await?;
+        log::info!("event=digest_sent open_todos={}", count);
+        Ok(())
+    }
+}
+
+/// Sends one digest of open, unarchived todos. Nothing is sent when there are
+/// none. Returns how many todos were included.
+pub async fn send_digest(
+    repository: &dyn TodoRepository,
+    notifier: &dyn Notifier,
+) -> Result<usize, JobError> {
+    let open: Vec<_> = repository
+        .list(&ListOptions {
+            offset: 0,
+            limit: DIGEST_MAX_TODOS,
+            include_archived: false,
+            scope: TodoScope::All,
+            visibility: TodoVisibility::All,
+            owner_id: None,
+            created_after: None,
+            created_before: None,
+            project_id: None,
+            status: None,
+            assignee_id: None,
+            completed: None,
+            due_after: None,
+            due_before: None,
+            sort: TodoSort::CreatedAt,
+        })
+        .await?
+        .into_iter()
+        .filter(|todo| !todo.completed.unwrap_or(false))
+        .collect();
+
+    if open.is_empty() {
+        return Ok(0);
+    }
+
+    notifier
+        .notify(&Notification::digest(&open))
+        .await
+        .map_err(|e| JobError(e.to_string()))?;
+
+    Ok(open.len())
+}
@@ -0,0 +1,273 @@
+//! Application-level encryption of todo content, so the database (and its
+//! backups) only ever hold ciphertext for it. Stored values are tagged
+//! with the ID of the key that sealed them, so keys can be rotated: new
+//! content uses the active key, older keys stay configured for reading,
+//! and a maintenance job re-seals everything under the active key.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use futures_util::TryStreamExt;
+use rand::Rng;
+
+use crate::config::EncryptionConfig;
+use crate::error::AppError;
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::repository::{
+    ListOptions, RepositoryError, TodoPatch, TodoRepository, TodoScope, TodoSort, TodoVisibility,
+};
+
+/// Marks a sealed value: `enc:v1:<key id>:<base64 of nonce and ciphertext>`.
+const PREFIX: &str = "enc:v1:";
+/// Marks plain content stored while encryption is off that would otherwise
+/// look sealed: `enc:raw:<content>`.
+const RAW_PREFIX: &str = "enc:raw:";
+/// What every tagged value starts with.
+const TAG: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+/// Seals and opens todo content with the configured keys. Without keys it
+/// passes content through untouched.
+#[derive(Clone, Default)]
+pub struct ContentCipher {
+    keys: Arc<HashMap<String, Aes256Gcm>>,
+    active: Option<String>,
+}
+
+impl ContentCipher {
+    pub fn new(config: &EncryptionConfig) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for (id, key) in &config.keys {
+            if id.is_empty()
+                || !id
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+            {
+                return Err(format!(
+                    "content encryption key ID '{}' may only use letters, digits, '-' and '_'",
+                    id
+                ));
+            }
+            let key: [u8; 32] = hex::decode(key.trim())
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(|| format!("content encryption key '{}' must be 64 hex digits", id))?;
+            if keys
+                .insert(id.clone(), Aes256Gcm::new(&key.into()))
+                .is_some()
+            {
+                return Err(format!("content encryption key '{}' is listed twice", id));
+            }
+        }
+
+        let active = match &config.active_key {
+            Some(id) if !keys.contains_key(id) => {
+                return Err(format!(
+                    "CONTENT_ENCRYPTION_KEY_ID '{}' is not one of CONTENT_ENCRYPTION_KEYS",
+                    id
+                ))
+            }
+            Some(id) => Some(id.clone()),
+            None => config.keys.first().map(|(id, _)| id.clone()),
+        };
+
+        Ok(ContentCipher {
+            keys: Arc::new(keys),
+            active,
+        })
+    }
+
+    /// ID of the key new content is sealed with, if encryption is on.
+    pub fn active_key(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// `content` as it should be stored.
+    pub fn seal(&self, content: &str) -> Result<String, RepositoryError> {
+        let Some(id) = &self.active else {
+            if content.starts_with(TAG) {
+                return Ok(format!("{}{}", RAW_PREFIX, content));
+            }
+            return Ok(content.to_string());
+        };
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.keys[id]
+                .encrypt(Nonce::from_slice(&nonce), content.as_bytes())
+                .map_err(|_| RepositoryError::Database("Failed to encrypt content".to_string()))?,
+        );
+        Ok(format!("{}{}:{}", PREFIX, id, STANDARD.encode(sealed)))
+    }
+
+    /// Stored content as it was written. Content stored before encryption
+    /// was turned on is returned as is.
+    pub fn open(&self, stored: &str) -> Result<String, RepositoryError> {
+        if let Some(content) = stored.strip_prefix(RAW_PREFIX) {
+            return Ok(content.to_string());
+        }
+        let Some(rest) = stored.strip_prefix(PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let unreadable = |reason: &str| {
+            RepositoryError::Decode(format!("Stored content is unreadable: {}", reason))
+        };
+        let (id, sealed) = rest
+            .split_once(':')
+            .ok_or_else(|| unreadable("no key ID"))?;
+        let cipher = self
+            .keys
+            .get(id)
+            .ok_or_else(|| unreadable(&format!("key '{}' is not configured", id)))?;
+        let sealed = STANDARD
+            .decode(sealed)
+            .map_err(|_| unreadable("bad encoding"))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(unreadable("too short"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let content = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| unreadable(&format!("key '{}' does not open it", id)))?;
+        String::from_utf8(content).map_err(|_| unreadable("not UTF-8"))
+    }
+
+    /// Whether `stored` is already sealed the way [`ContentCipher::seal`]
+    /// would store it now.
+    pub fn is_current(&self, stored: &str) -> bool {
+        match &self.active {
+            Some(id) => stored
+                .strip_prefix(PREFIX)
+                .and_then(|rest| rest.split_once(':'))
+                .is_some_and(|(key, _)| key == id),
+            None => !stored.starts_with(PREFIX),
+        }
+    }
+}
+
+/// Job that re-seals every todo's content with the active key: after a
+/// key rotation, so the old key can be retired, or after encryption is
+/// turned on, for content stored before.
+struct ReencryptJob {
+    /// The repository below the encryption, which sees stored values.
+    repository: Arc<dyn TodoRepository>,
+    cipher: ContentCipher,
+}
+
+impl ReencryptJob {
+    const KIND: &'static str = "content.reencrypt";
+}
+
+#[async_trait]
+impl Job for ReencryptJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    /// Reads the todos a page at a time. Each one sealed with an old key
+    /// is read again just before it is written, so an edit made since its
+    /// page was read is re-sealed rather than overwritten.
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let mut todos = self
+            .repository
+            .stream(&ListOptions {
+                offset: 0,
+                // Every todo; SQL backends bind the limit as an i64.
+                limit: i64::MAX as usize,
+                include_archived: true,
+                scope: TodoScope::All,
+                visibility: TodoVisibility::All,
+                owner_id: None,
+                created_after: None,
+                created_before: None,
+                project_id: None,
+                status: None,
+                assignee_id: None,
+                completed: None,
+                due_after: None,
+                due_before: None,
+                sort: TodoSort::CreatedAt,
+            })
+            .await?;
+
+        let mut reencrypted = 0;
+        while let Some(todo) = todos.try_next().await? {
+            let Some(id) = todo.id else {
+                continue;
+            };
+            if self.cipher.is_current(&todo.content) {
+                continue;
+            }
+            let Some(todo) = self
+                .repository
+                .find_by_id(&id)
+                .await?
+                .filter(|todo| !self.cipher.is_current(&todo.content))
+            else {
+                continue;
+            };
+            let content = self.cipher.seal(&self.cipher.open(&todo.content)?)?;
+            self.repository
+                .update_fields(
+                    &id,
+                    &TodoPatch {
+                        content: Some(content),
+                        updated_at: todo.updated_at.unwrap_or_else(Utc::now),
+                        expires_at: todo.expires_at,
+                        ..TodoPatch::default()
+                    },
+                )
+                .await?;
+            reencrypted += 1;
+        }
+
+        log::info!(
+            "event=content_reencrypted key_id={} todos={}",
+            self.cipher.active_key().unwrap_or("none"),
+            reencrypted
+        );
+        Ok(())
+    }
+}
+
+/// Runs the re-encryption job on request.
+#[derive(Clone)]
+pub struct ContentEncryption {
+    jobs: JobQueue,
+    cipher: ContentCipher,
+}
+
+impl ContentEncryption {
+    /// Registers the re-encryption job. `repository` must be the one below
+    /// the encryption, so the job sees which key sealed each todo.
+    pub fn new(jobs: JobQueue, repository: Arc<dyn TodoRepository>, cipher: ContentCipher) -> Self {
+        jobs.register(Arc::new(ReencryptJob {
+            repository,
+            cipher: cipher.clone(),
+        }));
+        ContentEncryption { jobs, cipher }
+    }
+
+    pub fn active_key(&self) -> Option<&str> {
+        self.cipher.active_key()
+    }
+
+    /// Queues a re-encryption of all stored content. Returns the job ID.
+    pub async fn reencrypt(&self) -> Result<String, AppError> {
+        if self.cipher.active_key().is_none() {
+            return Err(AppError::BadRequest(
+                "Content encryption is not configured".to_string(),
+            ));
+        }
+        Ok(self
+            .jobs
+            .enqueue(ReencryptJob::KIND, serde_json::Value::Null)
+            .await?)
+    }
+}
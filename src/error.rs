@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use chrono::{DateTime, Utc};
+
+use crate::blobs::BlobError;
+use crate::build_info;
+use crate::i18n;
+use crate::notifier::NotifierError;
+use crate::repository::RepositoryError;
+use crate::response::{GenericResponse, LockedResponse};
+
+/// Errors returned by handlers, rendered as the usual `GenericResponse` body
+/// in the language the request prefers; see [`i18n`].
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    UnprocessableEntity(String),
+    /// With when the lockout ends.
+    #[error("{0}")]
+    Locked(String, DateTime<Utc>),
+    /// With how long until the request would be accepted.
+    #[error("{0}")]
+    TooManyRequests(String, Duration),
+    #[error("{0}")]
+    BadGateway(String),
+    #[error("{0}")]
+    ServiceUnavailable(String),
+    /// With how long clients are told to wait before trying again.
+    #[error("{0}")]
+    Maintenance(String, Duration),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl From<RepositoryError> for AppError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::Timeout | RepositoryError::Unavailable => {
+                AppError::ServiceUnavailable(e.to_string())
+            }
+            RepositoryError::Database(_) | RepositoryError::Decode(_) => {
+                AppError::Internal(e.to_string())
+            }
+        }
+    }
+}
+
+impl From<NotifierError> for AppError {
+    fn from(e: NotifierError) -> Self {
+        AppError::BadGateway(e.to_string())
+    }
+}
+
+impl From<BlobError> for AppError {
+    fn from(e: BlobError) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Locked(..) => StatusCode::LOCKED,
+            AppError::TooManyRequests(..) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            AppError::ServiceUnavailable(_) | AppError::Maintenance(..) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = if self.status_code().is_client_error() {
+            "fail"
+        } else {
+            "error"
+        };
+        let (message, language) = i18n::localize(self.to_string());
+        let mut response = HttpResponse::build(self.status_code());
+        response.insert_header((header::CONTENT_LANGUAGE, language));
+        response.insert_header((build_info::VERSION_HEADER, build_info::version()));
+        // RFC 9110 requires a challenge on every 401.
+        if let AppError::Unauthorized(_) = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        if let AppError::ServiceUnavailable(_) = self {
+            response.insert_header((header::RETRY_AFTER, "1"));
+        }
+        if let AppError::TooManyRequests(_, retry_after) | AppError::Maintenance(_, retry_after) =
+            self
+        {
+            // Rounded up, so a client waiting that long is let through.
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        if let AppError::Locked(_, unlock_at) = self {
+            let secs = (*unlock_at - Utc::now()).num_seconds().max(1);
+            response.insert_header((header::RETRY_AFTER, secs.to_string()));
+            return response.json(LockedResponse {
+                status: status.to_string(),
+                message,
+                unlock_at: *unlock_at,
+            });
+        }
+        response.json(GenericResponse {
+            status: status.to_string(),
+            message,
+        })
+    }
+}
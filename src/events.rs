@@ -0,0 +1,182 @@
+//! Domain events. Code that changes a todo describes what happened once,
+//! and webhooks, the activity log and anything else that reacts to changes
+//! handle it, rather than each being called from every place a todo
+//! changes. Handlers that must not miss an event get it through the outbox
+//! (see [`crate::outbox`]); the in-process bus is for those that only care
+//! while they are listening.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::model::{Todo, TodoId};
+
+/// Events a subscriber that falls this far behind misses the oldest of.
+const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum DomainEvent {
+    TodoCreated {
+        todo: Todo,
+        actor_id: Option<String>,
+    },
+    /// Any change to a stored todo. `todo` is left out by batch updates
+    /// that do not load it; `title` is set when the title was edited.
+    TodoUpdated {
+        id: TodoId,
+        todo: Option<Todo>,
+        title: Option<String>,
+        actor_id: Option<String>,
+    },
+    TodoDeleted {
+        id: TodoId,
+        actor_id: Option<String>,
+    },
+    /// A todo was marked done; follows the `TodoUpdated` for the change.
+    TodoCompleted {
+        id: TodoId,
+        actor_id: Option<String>,
+    },
+}
+
+impl DomainEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::TodoCreated { .. } => "todo_created",
+            DomainEvent::TodoUpdated { .. } => "todo_updated",
+            DomainEvent::TodoDeleted { .. } => "todo_deleted",
+            DomainEvent::TodoCompleted { .. } => "todo_completed",
+        }
+    }
+
+    pub fn todo_id(&self) -> TodoId {
+        match self {
+            DomainEvent::TodoCreated { todo, .. } => todo.id.expect("stored todos have an ID"),
+            DomainEvent::TodoUpdated { id, .. }
+            | DomainEvent::TodoDeleted { id, .. }
+            | DomainEvent::TodoCompleted { id, .. } => *id,
+        }
+    }
+
+    pub fn actor_id(&self) -> Option<&str> {
+        match self {
+            DomainEvent::TodoCreated { actor_id, .. }
+            | DomainEvent::TodoUpdated { actor_id, .. }
+            | DomainEvent::TodoDeleted { actor_id, .. }
+            | DomainEvent::TodoCompleted { actor_id, .. } => actor_id.as_deref(),
+        }
+    }
+
+    /// The new title, for updates that edited it.
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            DomainEvent::TodoUpdated { title, .. } => title.as_deref(),
+            DomainEvent::TodoCreated { .. }
+            | DomainEvent::TodoDeleted { .. }
+            | DomainEvent::TodoCompleted { .. } => None,
+        }
+    }
+
+    /// The todo as it was left by the event, when the publisher had it.
+    pub fn todo(&self) -> Option<&Todo> {
+        match self {
+            DomainEvent::TodoCreated { todo, .. } => Some(todo),
+            DomainEvent::TodoUpdated { todo, .. } => todo.as_ref(),
+            DomainEvent::TodoDeleted { .. } | DomainEvent::TodoCompleted { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct HandlerError(pub String);
+
+/// Reacts to domain events, which arrive in the order they were published.
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// An error means the event was not handled. The outbox hands it over
+    /// again later; the bus only logs it.
+    async fn handle(&self, event: &DomainEvent) -> Result<(), HandlerError>;
+}
+
+/// Broadcasts domain events to every subscriber.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DomainEvent>,
+    /// Only the events published on this instance, for the bridge to
+    /// other instances (see [`crate::bridge`]).
+    local: broadcast::Sender<DomainEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let (local, _) = broadcast::channel(CAPACITY);
+        EventBus { sender, local }
+    }
+
+    /// Hands `event` to the subscribers without waiting for them. With no
+    /// subscribers it goes nowhere.
+    pub fn publish(&self, event: DomainEvent) {
+        log::debug!(
+            "event=domain_event_published kind={} todo_id={}",
+            event.name(),
+            event.todo_id()
+        );
+        let _ = self.local.send(event.clone());
+        let _ = self.sender.send(event);
+    }
+
+    /// Hands `event`, published on another instance, to the subscribers.
+    pub fn deliver(&self, event: DomainEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Every event published or delivered from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Every event published on this instance from now on.
+    pub fn subscribe_local(&self) -> broadcast::Receiver<DomainEvent> {
+        self.local.subscribe()
+    }
+
+    /// Runs `handler` on every event published from now on, on a task of
+    /// its own so a slow handler holds up no other.
+    pub fn spawn(&self, handler: Arc<dyn EventHandler>) {
+        let mut events = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = handler.handle(&event).await {
+                            log::warn!(
+                                "event=domain_event_handler_failed handler={} kind={} todo_id={} error=\"{}\"",
+                                handler.name(),
+                                event.name(),
+                                event.todo_id(),
+                                e
+                            );
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => log::warn!(
+                        "event=domain_events_missed handler={} count={}",
+                        handler.name(),
+                        missed
+                    ),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+
+use crate::blobs::BlobStore;
+use crate::jobs::{Job, JobError};
+use crate::repository::{
+    AttachmentRepository, CommentRepository, RepositoryError, TodoAclRepository, TodoRepository,
+};
+
+/// Job that deletes expired todos with their shares, comments and
+/// attachments; scheduled every expiry sweep interval. Scylla drops expired
+/// rows through their TTL instead, so there the sweep finds nothing.
+pub struct ExpirySweepJob {
+    pub todos: Arc<dyn TodoRepository>,
+    pub acl: Arc<dyn TodoAclRepository>,
+    pub comments: Arc<dyn CommentRepository>,
+    pub attachments: Arc<dyn AttachmentRepository>,
+    pub blobs: Arc<dyn BlobStore>,
+}
+
+impl ExpirySweepJob {
+    pub const KIND: &'static str = "todos.expire";
+
+    /// Returns how many todos were deleted.
+    pub async fn sweep(&self) -> Result<usize, RepositoryError> {
+        let ids = self.todos.delete_expired(Utc::now()).await?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        self.acl.delete_for_todos(&ids).await?;
+        self.comments.delete_for_todos(&ids).await?;
+        for attachment in self.attachments.delete_for_todos(&ids).await? {
+            if let Err(e) = self.blobs.delete(&attachment.id).await {
+                log::warn!(
+                    "event=blob_delete_failed attachment_id={} error=\"{}\"",
+                    attachment.id,
+                    e
+                );
+            }
+        }
+        Ok(ids.len())
+    }
+}
+
+#[async_trait]
+impl Job for ExpirySweepJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let deleted = self.sweep().await?;
+        if deleted > 0 {
+            log::info!("event=todos_expired count={}", deleted);
+        }
+        Ok(())
+    }
+
+    /// The next scheduled sweep is the retry.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+}
@@ -0,0 +1,219 @@
+//! Exports of everything the service keeps about a user, for them to
+//! download: their account, settings, workspace memberships, the todos
+//! they own with those todos' comments and attachment metadata, and the
+//! activity they caused. Exports are built by a background job and stored
+//! as one JSON document per user, which the next export replaces and
+//! deleting the account removes.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::blobs::{BlobError, BlobInfo, BlobStore, BlobStream};
+use crate::error::AppError;
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::model::{
+    Activity, Attachment, Comment, JobRecord, NotificationSettings, Todo, User, UserSettings,
+    WorkspaceMember,
+};
+use crate::repository::{
+    ActivityRepository, AttachmentRepository, CommentRepository, ListOptions,
+    NotificationSettingsRepository, TodoRepository, TodoScope, TodoSort, TodoVisibility,
+    UserRepository, UserSettingsRepository, WorkspaceRepository,
+};
+
+/// Content type of a finished export.
+pub const CONTENT_TYPE: &str = "application/json";
+
+/// File name a downloaded export is saved as.
+pub const FILE_NAME: &str = "account-export.json";
+
+/// Everything read at once; SQL backends bind the limit as an i64.
+const ALL: usize = i64::MAX as usize;
+
+/// Where the export of `user_id` is stored.
+fn key(user_id: &str) -> String {
+    format!("export-{}.json", user_id)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportPayload {
+    user_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountExport {
+    exported_at: DateTime<Utc>,
+    user: User,
+    settings: Option<UserSettings>,
+    notification_settings: Option<NotificationSettings>,
+    workspaces: Vec<WorkspaceMember>,
+    todos: Vec<Todo>,
+    comments: Vec<Comment>,
+    attachments: Vec<Attachment>,
+    activity: Vec<Activity>,
+}
+
+/// Gathers a user's data and stores it as their export.
+pub struct ExportJob {
+    pub users: Arc<dyn UserRepository>,
+    pub user_settings: Arc<dyn UserSettingsRepository>,
+    pub notification_settings: Arc<dyn NotificationSettingsRepository>,
+    pub workspaces: Arc<dyn WorkspaceRepository>,
+    pub todos: Arc<dyn TodoRepository>,
+    pub comments: Arc<dyn CommentRepository>,
+    pub attachments: Arc<dyn AttachmentRepository>,
+    pub activity: Arc<dyn ActivityRepository>,
+    pub blobs: Arc<dyn BlobStore>,
+}
+
+impl ExportJob {
+    pub const KIND: &'static str = "account.export";
+
+    async fn collect(&self, user: User) -> Result<AccountExport, JobError> {
+        let mut workspaces = Vec::new();
+        for workspace in self.workspaces.list_for_user(&user.id).await? {
+            workspaces.extend(self.workspaces.find_member(&workspace.id, &user.id).await?);
+        }
+
+        let todos: Vec<Todo> = self
+            .todos
+            .stream(&ListOptions {
+                offset: 0,
+                limit: ALL,
+                include_archived: true,
+                scope: TodoScope::All,
+                visibility: TodoVisibility::All,
+                owner_id: Some(user.id.clone()),
+                created_after: None,
+                created_before: None,
+                project_id: None,
+                status: None,
+                assignee_id: None,
+                completed: None,
+                due_after: None,
+                due_before: None,
+                sort: TodoSort::CreatedAt,
+            })
+            .await?
+            .try_collect()
+            .await?;
+        let (mut comments, mut attachments) = (Vec::new(), Vec::new());
+        for id in todos.iter().filter_map(|todo| todo.id) {
+            comments.extend(self.comments.list(&id, 0, ALL).await?);
+            attachments.extend(self.attachments.list(&id).await?);
+        }
+
+        Ok(AccountExport {
+            exported_at: Utc::now(),
+            settings: self.user_settings.find(&user.id).await?,
+            notification_settings: self.notification_settings.find(&user.email).await?,
+            activity: self.activity.list_for_actor(&user.id, &[], 0, ALL).await?,
+            user,
+            workspaces,
+            todos,
+            comments,
+            attachments,
+        })
+    }
+}
+
+#[async_trait]
+impl Job for ExportJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, payload: &serde_json::Value) -> Result<(), JobError> {
+        let payload: ExportPayload =
+            serde_json::from_value(payload.clone()).map_err(|e| JobError(e.to_string()))?;
+
+        // Deleted since asking for the export.
+        let Some(user) = self.users.find_by_id(&payload.user_id).await? else {
+            return Ok(());
+        };
+        let export = self.collect(user).await?;
+        let body = serde_json::to_vec_pretty(&export).map_err(|e| JobError(e.to_string()))?;
+
+        let blob_error = |e: BlobError| JobError(e.to_string());
+        let mut writer = self
+            .blobs
+            .create(&key(&payload.user_id))
+            .await
+            .map_err(blob_error)?;
+        if let Err(e) = writer.write(&body).await {
+            writer.abort().await;
+            return Err(blob_error(e));
+        }
+        writer.finish().await.map_err(blob_error)?;
+
+        log::info!(
+            "event=account_exported user_id={} todos={} bytes={}",
+            payload.user_id,
+            export.todos.len(),
+            body.len()
+        );
+        Ok(())
+    }
+}
+
+/// Starts exports and hands out their progress and results.
+#[derive(Clone)]
+pub struct AccountExports {
+    jobs: JobQueue,
+    blobs: Arc<dyn BlobStore>,
+}
+
+impl AccountExports {
+    /// Registers `job` on the queue.
+    pub fn new(jobs: JobQueue, job: ExportJob) -> Self {
+        let blobs = job.blobs.clone();
+        jobs.register(Arc::new(job));
+        AccountExports { jobs, blobs }
+    }
+
+    /// Queues an export of `user_id`'s data. Returns the job ID.
+    pub async fn start(&self, user_id: &str) -> Result<String, AppError> {
+        let payload = serde_json::to_value(ExportPayload {
+            user_id: user_id.to_string(),
+        })
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+        Ok(self.jobs.enqueue(ExportJob::KIND, payload).await?)
+    }
+
+    /// The export job `id`, if it is one of `user_id`'s; others get the
+    /// same 404 as a missing job.
+    pub async fn status(&self, user_id: &str, id: &str) -> Result<JobRecord, AppError> {
+        self.jobs
+            .find(id)
+            .await?
+            .filter(|record| {
+                record.kind == ExportJob::KIND
+                    && serde_json::from_value::<ExportPayload>(record.payload.clone())
+                        .is_ok_and(|payload| payload.user_id == user_id)
+            })
+            .ok_or_else(|| AppError::NotFound(format!("Export with ID: {} not found", id)))
+    }
+
+    /// `user_id`'s latest finished export.
+    pub async fn open(&self, user_id: &str) -> Result<(BlobInfo, BlobStream), AppError> {
+        let not_ready = || AppError::NotFound("No export has finished yet".to_string());
+        let key = key(user_id);
+        let info = self.blobs.stat(&key).await?.ok_or_else(not_ready)?;
+        let contents = self.blobs.open(&key).await.map_err(|e| match e {
+            BlobError::NotFound => not_ready(),
+            e => e.into(),
+        })?;
+        Ok((info, contents))
+    }
+
+    /// Removes `user_id`'s export, if there is one.
+    pub async fn delete(&self, user_id: &str) -> Result<(), AppError> {
+        Ok(self.blobs.delete(&key(user_id)).await?)
+    }
+}
@@ -0,0 +1,177 @@
+//! Feature flags, to turn a feature on in one environment and off in
+//! another without a redeploy. Each flag starts at its default from
+//! `FEATURE_FLAGS`; a value stored through `PUT /api/admin/features/{name}`
+//! overrides it on every instance. Instances read the stored flags every
+//! `FEATURE_FLAGS_REFRESH_SECS`, so a change reaches the others within that
+//! time, and keep the last flags they read while the database cannot be
+//! reached. A flag set on neither is off, unless it has a built-in default
+//! here.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::config::FeaturesConfig;
+use crate::error::AppError;
+use crate::repository::{FeatureFlagRepository, RepositoryError};
+
+/// Serves the offline sync API, `/api/sync`. On unless turned off.
+pub const OFFLINE_SYNC: &str = "offline_sync";
+
+/// Flags on or off while neither `FEATURE_FLAGS` nor the database says.
+pub const BUILT_IN_DEFAULTS: [(&str, bool); 1] = [(OFFLINE_SYNC, true)];
+
+/// Longest flag name accepted.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// A flag and where its value comes from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    /// Its value from `FEATURE_FLAGS`, if it has one.
+    pub default: Option<bool>,
+    /// Its value in the database, if set there.
+    pub stored: Option<bool>,
+}
+
+#[derive(Clone)]
+pub struct FeatureFlags {
+    defaults: Arc<HashMap<String, bool>>,
+    repository: Arc<dyn FeatureFlagRepository>,
+    /// The stored flags as last read.
+    stored: Arc<RwLock<HashMap<String, bool>>>,
+    refresh_interval: Duration,
+}
+
+impl FeatureFlags {
+    pub fn new(config: &FeaturesConfig, repository: Arc<dyn FeatureFlagRepository>) -> Self {
+        FeatureFlags {
+            defaults: Arc::new(config.defaults.clone()),
+            repository,
+            stored: Arc::new(RwLock::new(HashMap::new())),
+            refresh_interval: config.refresh_interval,
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.stored
+            .read()
+            .unwrap()
+            .get(name)
+            .or_else(|| self.defaults.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Fails with 404 unless `name` is enabled, for a handler to behave as if
+    /// the route it serves did not exist while its feature is off.
+    pub fn require(&self, name: &str) -> Result<(), AppError> {
+        if self.is_enabled(name) {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!(
+                "The {} feature is not enabled",
+                name
+            )))
+        }
+    }
+
+    /// Every flag with a default or a stored value, by name.
+    pub fn list(&self) -> Vec<FeatureFlag> {
+        let stored = self.stored.read().unwrap();
+        let names: BTreeSet<&String> = self.defaults.keys().chain(stored.keys()).collect();
+        names
+            .into_iter()
+            .map(|name| self.describe(name, &stored))
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> FeatureFlag {
+        self.describe(name, &self.stored.read().unwrap())
+    }
+
+    /// Stores whether `name` is enabled. This instance follows at once,
+    /// the others on their next refresh.
+    pub async fn set(&self, name: &str, enabled: bool) -> Result<FeatureFlag, RepositoryError> {
+        self.repository.set(name, enabled).await?;
+        self.stored
+            .write()
+            .unwrap()
+            .insert(name.to_string(), enabled);
+        Ok(self.get(name))
+    }
+
+    /// Returns `name` to its default.
+    pub async fn clear(&self, name: &str) -> Result<FeatureFlag, RepositoryError> {
+        self.repository.clear(name).await?;
+        self.stored.write().unwrap().remove(name);
+        Ok(self.get(name))
+    }
+
+    /// Reads the stored flags again, keeping those last read if that fails.
+    pub async fn refresh(&self) {
+        let stored = match self.repository.list().await {
+            Ok(stored) => stored,
+            Err(e) => {
+                log::warn!("event=feature_flags_refresh_failed error=\"{}\"", e);
+                return;
+            }
+        };
+        let previous = std::mem::replace(&mut *self.stored.write().unwrap(), stored.clone());
+        for (name, enabled) in &stored {
+            if previous.get(name) != Some(enabled) {
+                log::info!(
+                    "event=feature_flag_changed name={} enabled={}",
+                    name,
+                    enabled
+                );
+            }
+        }
+        for name in previous.keys().filter(|name| !stored.contains_key(*name)) {
+            log::info!("event=feature_flag_cleared name={}", name);
+        }
+    }
+
+    /// Refreshes the flags every `FEATURE_FLAGS_REFRESH_SECS`.
+    pub fn start(&self) {
+        let flags = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(flags.refresh_interval).await;
+                flags.refresh().await;
+            }
+        });
+    }
+
+    fn describe(&self, name: &str, stored: &HashMap<String, bool>) -> FeatureFlag {
+        let default = self.defaults.get(name).copied();
+        let stored = stored.get(name).copied();
+        FeatureFlag {
+            name: name.to_string(),
+            enabled: stored.or(default).unwrap_or(false),
+            default,
+            stored,
+        }
+    }
+}
+
+/// Checks a flag name given to set: lowercase letters, digits, `_` and `-`.
+pub fn validate_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Feature flag names are 1 to {} lowercase letters, digits, '_' or '-'",
+            MAX_NAME_LENGTH
+        )))
+    }
+}
@@ -0,0 +1,67 @@
+//! Realistic fake data for seeding a database, benchmarks and tests.
+//! Requires the `seed` cargo feature.
+
+use chrono::{Duration, Utc};
+use fake::faker::company::en::Bs;
+use fake::faker::lorem::en::Paragraph;
+use fake::Fake;
+use rand::Rng;
+
+use crate::model::{Todo, TodoId};
+
+/// A single fake todo with a fresh ID, created some time in the last 90
+/// days. Titles end in part of the ID so they stay unique, as the API
+/// requires.
+pub fn todo<R: Rng + ?Sized>(rng: &mut R) -> Todo {
+    let id = TodoId::generate();
+    let topic: String = Bs().fake_with_rng(rng);
+    let title = format!("{} ({})", capitalize(&topic), &id.to_string()[..8]);
+    let content: String = Paragraph(1..3).fake_with_rng(rng);
+
+    let now = Utc::now();
+    let created_at = now - Duration::minutes(rng.gen_range(0..90 * 24 * 60));
+    let updated_at = created_at + (now - created_at) / rng.gen_range(1..=4);
+    // Roughly half have a due date, anywhere from a month ago to a month out.
+    let due_at = rng
+        .gen_bool(0.5)
+        .then(|| now + Duration::hours(rng.gen_range(-30 * 24..30 * 24)));
+
+    Todo {
+        id: Some(id),
+        title,
+        content,
+        completed: Some(rng.gen_bool(0.3)),
+        archived: Some(rng.gen_bool(0.05)),
+        due_at,
+        recurrence: None,
+        series_id: None,
+        next_occurrence_id: None,
+        remind_at: None,
+        reminder_sent_at: None,
+        owner_id: None,
+        workspace_id: None,
+        assignee_id: None,
+        project_id: None,
+        position: None,
+        status: None,
+        created_at: Some(created_at),
+        updated_at: Some(updated_at),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    }
+}
+
+/// `count` fake todos, see [`todo`].
+pub fn todos<R: Rng + ?Sized>(rng: &mut R, count: usize) -> Vec<Todo> {
+    (0..count).map(|_| todo(rng)).collect()
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
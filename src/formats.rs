@@ -0,0 +1,653 @@
+//! Alternative wire formats for the JSON API: XML for legacy clients and
+//! MessagePack for high-throughput ones. Handlers only ever see and
+//! produce JSON; the [`negotiate_format`] middleware translates request
+//! bodies by their `Content-Type` and responses by the `Accept` header.
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::web;
+use serde_json::{Map, Number, Value};
+
+use crate::error::AppError;
+
+/// How deeply nested a decoded document may be.
+const MAX_DEPTH: usize = 64;
+
+/// A representation of request and response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Xml,
+    MessagePack,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Json, Format::Xml, Format::MessagePack];
+
+    /// The `Content-Type` of responses in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Xml => "application/xml; charset=utf-8",
+            Format::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Every media type naming this format; the first is the canonical one.
+    fn media_types(&self) -> &'static [&'static str] {
+        match self {
+            Format::Json => &["application/json"],
+            Format::Xml => &["application/xml", "text/xml"],
+            Format::MessagePack => &[
+                "application/msgpack",
+                "application/x-msgpack",
+                "application/vnd.msgpack",
+            ],
+        }
+    }
+
+    /// The format of a `Content-Type` value, ignoring its parameters.
+    pub fn from_content_type(content_type: &str) -> Option<Format> {
+        let media_type = content_type.split(';').next()?.trim();
+        Format::ALL.into_iter().find(|format| {
+            format
+                .media_types()
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(media_type))
+        })
+    }
+
+    /// The format to respond with for an `Accept` value: the one with the
+    /// highest quality, JSON on a tie. Wildcards only count for JSON, so
+    /// clients that do not ask for another format by name keep getting it.
+    pub fn negotiate(accept: &str) -> Format {
+        let mut best = (Format::Json, quality(accept, Format::Json));
+        for format in [Format::Xml, Format::MessagePack] {
+            let quality = quality(accept, format);
+            if quality > best.1 {
+                best = (format, quality);
+            }
+        }
+        best.0
+    }
+
+    pub fn encode(&self, value: &Value) -> Vec<u8> {
+        match self {
+            Format::Json => serde_json::to_vec(value).unwrap_or_default(),
+            Format::Xml => xml::encode(value).into_bytes(),
+            Format::MessagePack => {
+                let mut bytes = Vec::new();
+                msgpack::encode(value, &mut bytes);
+                bytes
+            }
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, String> {
+        match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Format::Xml => {
+                let text = std::str::from_utf8(bytes).map_err(|_| "XML must be UTF-8")?;
+                xml::decode(text)
+            }
+            Format::MessagePack => msgpack::decode(bytes),
+        }
+    }
+}
+
+/// The quality `accept` gives `format`. JSON also takes that of `*/*` and
+/// `application/*` when it is not listed by name.
+fn quality(accept: &str, format: Format) -> f32 {
+    let mut named: Option<f32> = None;
+    let mut wildcard: Option<f32> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or_default();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if format
+            .media_types()
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(media_type))
+        {
+            named = Some(named.map_or(quality, |named| named.max(quality)));
+        } else if format == Format::Json && (media_type == "*/*" || media_type == "application/*") {
+            wildcard = Some(wildcard.map_or(quality, |wildcard| wildcard.max(quality)));
+        }
+    }
+    named.or(wildcard).unwrap_or(0.0)
+}
+
+/// Middleware letting clients send request bodies as XML or MessagePack
+/// and ask for responses in them through `Accept`. Bodies are converted to
+/// JSON before the handler runs; JSON responses are re-encoded on the way
+/// out, and anything else (CSV exports, NDJSON streams, attachments)
+/// passes through untouched.
+pub async fn negotiate_format(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let request_format = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Format::from_content_type);
+    if let Some(format) = request_format.filter(|format| *format != Format::Json) {
+        let bytes = req.extract::<web::Bytes>().await?;
+        let value = format.decode(&bytes).map_err(|e| {
+            AppError::BadRequest(format!("Invalid {} body: {}", format.media_types()[0], e))
+        })?;
+        let json = serde_json::to_vec(&value).map_err(|e| AppError::Internal(e.to_string()))?;
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        req.headers_mut()
+            .insert(header::CONTENT_LENGTH, json.len().into());
+        req.set_payload(Payload::from(json));
+    }
+
+    let format = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .map_or(Format::Json, Format::negotiate);
+
+    let mut res = next.call(req).await?;
+    res.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if format == Format::Json || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        AppError::Internal(e.to_string())
+    })?;
+    // Empty bodies (e.g. a 304) have nothing to re-encode.
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Ok(ServiceResponse::new(
+            req,
+            res.set_body(bytes).map_into_boxed_body(),
+        ));
+    };
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    Ok(ServiceResponse::new(
+        req,
+        res.set_body(format.encode(&value)).map_into_boxed_body(),
+    ))
+}
+
+/// XML in the style older REST clients expect: objects become elements
+/// named after their keys, arrays hold `<item>` elements, and non-string
+/// values carry a `type` (or `nil`) attribute so they decode back to the
+/// same JSON. Keys that are not XML names become `<entry key="...">`.
+mod xml {
+    use super::*;
+    use xmlparser::{ElementEnd, Token, Tokenizer};
+
+    const ROOT: &str = "response";
+    const ITEM: &str = "item";
+    const ENTRY: &str = "entry";
+
+    pub fn encode(value: &Value) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        element(&mut xml, ROOT, None, value);
+        xml
+    }
+
+    fn element(xml: &mut String, name: &str, key: Option<&str>, value: &Value) {
+        xml.push('<');
+        xml.push_str(name);
+        if let Some(key) = key {
+            xml.push_str(" key=\"");
+            escape(xml, key);
+            xml.push('"');
+        }
+        match value {
+            Value::Null => xml.push_str(r#" nil="true"/>"#),
+            Value::Bool(_) | Value::Number(_) | Value::String(_) => {
+                xml.push_str(match value {
+                    Value::Bool(_) => r#" type="boolean">"#,
+                    Value::Number(_) => r#" type="number">"#,
+                    _ => ">",
+                });
+                match value {
+                    Value::String(text) => escape(xml, text),
+                    other => xml.push_str(&other.to_string()),
+                }
+                close(xml, name);
+            }
+            Value::Array(items) => {
+                xml.push_str(r#" type="array">"#);
+                for item in items {
+                    element(xml, ITEM, None, item);
+                }
+                close(xml, name);
+            }
+            Value::Object(map) if map.is_empty() => xml.push_str(r#" type="object"/>"#),
+            Value::Object(map) => {
+                xml.push('>');
+                for (key, value) in map {
+                    if is_name(key) && key != ENTRY {
+                        element(xml, key, None, value);
+                    } else {
+                        element(xml, ENTRY, Some(key), value);
+                    }
+                }
+                close(xml, name);
+            }
+        }
+    }
+
+    fn close(xml: &mut String, name: &str) {
+        xml.push_str("</");
+        xml.push_str(name);
+        xml.push('>');
+    }
+
+    /// A conservative subset of XML names: ASCII letters or `_` first, then
+    /// letters, digits, `_`, `-` and `.`, and not starting with `xml`.
+    fn is_name(key: &str) -> bool {
+        key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            && !key.to_ascii_lowercase().starts_with("xml")
+    }
+
+    fn escape(xml: &mut String, text: &str) {
+        for c in text.chars() {
+            match c {
+                '&' => xml.push_str("&amp;"),
+                '<' => xml.push_str("&lt;"),
+                '>' => xml.push_str("&gt;"),
+                '"' => xml.push_str("&quot;"),
+                '\'' => xml.push_str("&apos;"),
+                c => xml.push(c),
+            }
+        }
+    }
+
+    fn unescape(text: &str) -> Result<String, String> {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('&') {
+            unescaped.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find(';')
+                .ok_or("unterminated entity reference")?;
+            let entity = &rest[start + 1..start + end];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("unknown entity '&{};'", entity))?,
+            };
+            unescaped.push(c);
+            rest = &rest[start + end + 1..];
+        }
+        unescaped.push_str(rest);
+        Ok(unescaped)
+    }
+
+    /// An element being read.
+    #[derive(Default)]
+    struct Frame {
+        name: String,
+        key: Option<String>,
+        kind: Option<String>,
+        nil: bool,
+        text: String,
+        children: Vec<(String, Value)>,
+    }
+
+    impl Frame {
+        fn finish(self) -> Result<(String, Value), String> {
+            let key = self.key.unwrap_or(self.name);
+            if self.nil {
+                return Ok((key, Value::Null));
+            }
+            let text = self.text.trim();
+            let value = match self.kind.as_deref() {
+                Some("array") => Value::Array(self.children.into_iter().map(|(_, v)| v).collect()),
+                Some("object") => Value::Object(self.children.into_iter().collect()),
+                None if !self.children.is_empty() => {
+                    Value::Object(self.children.into_iter().collect())
+                }
+                Some("boolean") => match text {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => return Err(format!("<{}> is not a boolean", key)),
+                },
+                Some("number" | "integer" | "float") => {
+                    text.parse::<Number>()
+                        .map(Value::Number)
+                        .map_err(|_| format!("<{}> is not a number", key))?
+                }
+                Some("string") | None => Value::String(self.text),
+                Some(other) => return Err(format!("<{}> has unknown type '{}'", key, other)),
+            };
+            Ok((key, value))
+        }
+    }
+
+    /// Reads a document written by [`encode`]. The root element may have
+    /// any name; elements without a `type` are strings unless they have
+    /// children, in which case they are objects.
+    pub fn decode(text: &str) -> Result<Value, String> {
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut root = None;
+        for token in Tokenizer::from(text) {
+            match token.map_err(|e| e.to_string())? {
+                Token::ElementStart { local, .. } => {
+                    if root.is_some() {
+                        return Err("content after the root element".to_string());
+                    }
+                    if stack.len() == MAX_DEPTH {
+                        return Err(format!("nested more than {} levels deep", MAX_DEPTH));
+                    }
+                    stack.push(Frame {
+                        name: local.as_str().to_string(),
+                        ..Frame::default()
+                    });
+                }
+                Token::Attribute { local, value, .. } => {
+                    let Some(frame) = stack.last_mut() else {
+                        continue;
+                    };
+                    let value = unescape(value.as_str())?;
+                    match local.as_str() {
+                        "key" => frame.key = Some(value),
+                        "type" => frame.kind = Some(value),
+                        "nil" => frame.nil = value == "true",
+                        _ => {}
+                    }
+                }
+                Token::ElementEnd { end, .. } => {
+                    if matches!(end, ElementEnd::Open) {
+                        continue;
+                    }
+                    let frame = stack.pop().ok_or("unexpected closing tag")?;
+                    // Array items and the root are unnamed; `key` only
+                    // matters inside objects.
+                    let (key, value) = frame.finish()?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push((key, value)),
+                        None => root = Some(value),
+                    }
+                }
+                Token::Text { text } => {
+                    if let Some(frame) = stack.last_mut() {
+                        frame.text.push_str(&unescape(text.as_str())?);
+                    } else if !text.as_str().trim().is_empty() {
+                        return Err("text outside the root element".to_string());
+                    }
+                }
+                Token::Cdata { text, .. } => {
+                    if let Some(frame) = stack.last_mut() {
+                        frame.text.push_str(text.as_str());
+                    }
+                }
+                Token::DtdStart { .. } | Token::EmptyDtd { .. } => {
+                    return Err("document type declarations are not allowed".to_string());
+                }
+                _ => {}
+            }
+        }
+        root.ok_or_else(|| "no root element".to_string())
+    }
+}
+
+/// MessagePack for the JSON data model: strings, numbers, booleans, nil,
+/// arrays and maps with string keys. Binary and extension types have no
+/// JSON counterpart and are rejected.
+mod msgpack {
+    use super::*;
+
+    pub fn encode(value: &Value, out: &mut Vec<u8>) {
+        match value {
+            Value::Null => out.push(0xc0),
+            Value::Bool(false) => out.push(0xc2),
+            Value::Bool(true) => out.push(0xc3),
+            Value::Number(number) => {
+                if let Some(n) = number.as_u64() {
+                    encode_unsigned(n, out);
+                } else if let Some(n) = number.as_i64() {
+                    encode_signed(n, out);
+                } else {
+                    out.push(0xcb);
+                    out.extend_from_slice(&number.as_f64().unwrap_or_default().to_be_bytes());
+                }
+            }
+            Value::String(text) => {
+                header(out, text.len(), Some(0xa0), 32, [0xd9, 0xda, 0xdb]);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Value::Array(items) => {
+                header(out, items.len(), Some(0x90), 16, [0, 0xdc, 0xdd]);
+                for item in items {
+                    encode(item, out);
+                }
+            }
+            Value::Object(map) => {
+                header(out, map.len(), Some(0x80), 16, [0, 0xde, 0xdf]);
+                for (key, value) in map {
+                    encode(&Value::String(key.clone()), out);
+                    encode(value, out);
+                }
+            }
+        }
+    }
+
+    /// Writes a length prefix: the `fix` form below `fix_limit`, then the
+    /// 8, 16 and 32-bit forms (a zero marker skips the 8-bit one).
+    fn header(out: &mut Vec<u8>, len: usize, fix: Option<u8>, fix_limit: usize, markers: [u8; 3]) {
+        match fix {
+            Some(fix) if len < fix_limit => out.push(fix | len as u8),
+            _ if markers[0] != 0 && len <= u8::MAX as usize => {
+                out.extend_from_slice(&[markers[0], len as u8]);
+            }
+            _ if len <= u16::MAX as usize => {
+                out.push(markers[1]);
+                out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                out.push(markers[2]);
+                out.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+        }
+    }
+
+    fn encode_unsigned(n: u64, out: &mut Vec<u8>) {
+        if n < 0x80 {
+            out.push(n as u8);
+        } else if n <= u8::MAX as u64 {
+            out.extend_from_slice(&[0xcc, n as u8]);
+        } else if n <= u16::MAX as u64 {
+            out.push(0xcd);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        } else if n <= u32::MAX as u64 {
+            out.push(0xce);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        } else {
+            out.push(0xcf);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+
+    fn encode_signed(n: i64, out: &mut Vec<u8>) {
+        if n >= -32 {
+            out.push(n as i8 as u8);
+        } else if n >= i8::MIN as i64 {
+            out.extend_from_slice(&[0xd0, n as i8 as u8]);
+        } else if n >= i16::MIN as i64 {
+            out.push(0xd1);
+            out.extend_from_slice(&(n as i16).to_be_bytes());
+        } else if n >= i32::MIN as i64 {
+            out.push(0xd2);
+            out.extend_from_slice(&(n as i32).to_be_bytes());
+        } else {
+            out.push(0xd3);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader { bytes, position: 0 };
+        let value = reader.value(0)?;
+        if reader.position != bytes.len() {
+            return Err("trailing bytes after the value".to_string());
+        }
+        Ok(value)
+    }
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        position: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+            let end = self
+                .position
+                .checked_add(len)
+                .filter(|end| *end <= self.bytes.len())
+                .ok_or("unexpected end of input")?;
+            let bytes = &self.bytes[self.position..end];
+            self.position = end;
+            Ok(bytes)
+        }
+
+        fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+            Ok(self.take(N)?.try_into().unwrap_or([0; N]))
+        }
+
+        fn u8(&mut self) -> Result<u8, String> {
+            Ok(self.array::<1>()?[0])
+        }
+
+        fn u16(&mut self) -> Result<usize, String> {
+            Ok(u16::from_be_bytes(self.array()?) as usize)
+        }
+
+        fn u32(&mut self) -> Result<usize, String> {
+            Ok(u32::from_be_bytes(self.array()?) as usize)
+        }
+
+        fn value(&mut self, depth: usize) -> Result<Value, String> {
+            if depth > MAX_DEPTH {
+                return Err(format!("nested more than {} levels deep", MAX_DEPTH));
+            }
+            let marker = self.u8()?;
+            let value = match marker {
+                0x00..=0x7f => Value::from(marker),
+                0x80..=0x8f => self.map((marker & 0x0f) as usize, depth)?,
+                0x90..=0x9f => self.items((marker & 0x0f) as usize, depth)?,
+                0xa0..=0xbf => self.string((marker & 0x1f) as usize)?,
+                0xc0 => Value::Null,
+                0xc2 => Value::Bool(false),
+                0xc3 => Value::Bool(true),
+                0xca => float(f32::from_be_bytes(self.array()?) as f64)?,
+                0xcb => float(f64::from_be_bytes(self.array()?))?,
+                0xcc => Value::from(self.u8()?),
+                0xcd => Value::from(u16::from_be_bytes(self.array()?)),
+                0xce => Value::from(u32::from_be_bytes(self.array()?)),
+                0xcf => Value::from(u64::from_be_bytes(self.array()?)),
+                0xd0 => Value::from(i8::from_be_bytes(self.array()?)),
+                0xd1 => Value::from(i16::from_be_bytes(self.array()?)),
+                0xd2 => Value::from(i32::from_be_bytes(self.array()?)),
+                0xd3 => Value::from(i64::from_be_bytes(self.array()?)),
+                0xd9 => {
+                    let len = self.u8()? as usize;
+                    self.string(len)?
+                }
+                0xda => {
+                    let len = self.u16()?;
+                    self.string(len)?
+                }
+                0xdb => {
+                    let len = self.u32()?;
+                    self.string(len)?
+                }
+                0xdc => {
+                    let len = self.u16()?;
+                    self.items(len, depth)?
+                }
+                0xdd => {
+                    let len = self.u32()?;
+                    self.items(len, depth)?
+                }
+                0xde => {
+                    let len = self.u16()?;
+                    self.map(len, depth)?
+                }
+                0xdf => {
+                    let len = self.u32()?;
+                    self.map(len, depth)?
+                }
+                0xe0..=0xff => Value::from(marker as i8),
+                0xc4..=0xc6 => return Err("binary values are not supported".to_string()),
+                _ => return Err(format!("unsupported type 0x{:02x}", marker)),
+            };
+            Ok(value)
+        }
+
+        fn string(&mut self, len: usize) -> Result<Value, String> {
+            let bytes = self.take(len)?;
+            std::str::from_utf8(bytes)
+                .map(|text| Value::String(text.to_string()))
+                .map_err(|_| "string is not valid UTF-8".to_string())
+        }
+
+        fn items(&mut self, len: usize, depth: usize) -> Result<Value, String> {
+            // Every item takes at least a byte, so this bounds the
+            // allocation by the input size.
+            let mut items = Vec::with_capacity(len.min(self.bytes.len() - self.position));
+            for _ in 0..len {
+                items.push(self.value(depth + 1)?);
+            }
+            Ok(Value::Array(items))
+        }
+
+        fn map(&mut self, len: usize, depth: usize) -> Result<Value, String> {
+            let mut map = Map::new();
+            for _ in 0..len {
+                let Value::String(key) = self.value(depth + 1)? else {
+                    return Err("map keys must be strings".to_string());
+                };
+                map.insert(key, self.value(depth + 1)?);
+            }
+            Ok(Value::Object(map))
+        }
+    }
+
+    fn float(n: f64) -> Result<Value, String> {
+        Number::from_f64(n)
+            .map(Value::Number)
+            .ok_or_else(|| "NaN and infinite numbers are not supported".to_string())
+    }
+}
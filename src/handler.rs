@@ -1,13 +1,142 @@
 use crate::{
-    model::{AppState, QueryOptions, Todo, UpdateTodoSchema},
-    response::{GenericResponse, SingleTodoResponse, TodoData, TodoListResponse},
+    accounts, activity, assignments,
+    auth::{self, AdminUser, AuthUser, ClientInfo},
+    blobs::BlobWriter,
+    build_info,
+    config::TrustedProxies,
+    csrf, decoding,
+    error::AppError,
+    events::DomainEvent,
+    exports, features,
+    json_patch::{self, PatchOperation},
+    maintenance::MaintenanceMode,
+    markdown::{Render, RenderQuery},
+    model::{
+        ActivityKind, ActivityListQuery, AddMemberSchema, AppState, AssignTodoSchema, Attachment,
+        BatchIdsSchema, BulkDeleteQuery, ClearLockoutQuery, Comment, CompleteUploadSchema,
+        ConfirmEmailSchema, CreateCommentSchema, CreateProjectSchema, CreateTemplateSchema,
+        CreateTodoSchema, CreateWebhookSchema, CreateWorkspaceSchema, DeadLetterQuery, DueFilter,
+        DuplicateTodoSchema, ForgotPasswordSchema, InstantiateTemplateSchema, JobRecord,
+        LoginSchema, NotificationSettings, OAuthCallbackQuery, OccurrencesQuery,
+        PresignUploadSchema, Project, ProjectListQuery, PushSyncSchema, RefreshToken,
+        RefreshTokenSchema, RegisterUserSchema, ReorderTodosSchema, ReplaceTodoQuery,
+        ReplaceTodoSchema, ResetPasswordSchema, Role, SetFeatureFlagSchema, SharePermission,
+        ShareTodoSchema, StartMaintenanceSchema, StatsQuery, SyncQuery, Template, Todo,
+        TodoCountQuery, TodoId, TodoListQuery, TodoShare, TodoStatus, TotpCodeSchema,
+        TotpLoginSchema, UndoAction, UpdateNotificationSettingsSchema, UpdateProfileSchema,
+        UpdateProjectSchema, UpdateTodoSchema, UpdateTodoStatusSchema, UpdateUserSettingsSchema,
+        UpdateWebhookSchema, UpdateWorkspaceSchema, User, UserSettings, Webhook, Workspace,
+        WorkspaceMember, WorkspaceRole,
+    },
+    notifier::Notification,
+    oauth::{self, OAuthProvider},
+    ordering::{self, Placement},
+    outbox,
+    pagination::QueryOptions,
+    preferences, projects,
+    repository::{
+        ListOptions, TodoFilter, TodoPatch, TodoScope, TodoSort, TodoVisibility, TodoWrite,
+    },
+    response::{
+        ActivityListResponse, AdminDatabaseStats, AdminStatsData, AdminStatsResponse,
+        AdminTodoStats, AdminUserStats, AttachmentData, AttachmentListResponse, AuthData,
+        AuthResponse, BackupCodesData, BackupCodesResponse, BatchGetResponse, BatchItemResult,
+        BatchResponse, BulkDeleteResponse, ClusterNode, ClusterStats, CommentData,
+        CommentListResponse, CompletionRate, ConcurrencyStats, CsrfTokenData, CsrfTokenResponse,
+        DailyCount, DeadLetterListResponse, DebugInfoData, DebugInfoResponse, ExportData,
+        ExportResponse, FeatureFlagData, FeatureFlagListResponse, GenericResponse, Link,
+        LookupStats, MaintenanceData, MaintenanceResponse, MfaChallengeData, MfaChallengeResponse,
+        MigrationLevel, NotificationOptIns, NotificationSettingsResponse, OccurrencesResponse,
+        OwnerTodoCount, PageLinks, PatchTodoResponse, PresignedDownloadResponse,
+        PresignedUploadData, PresignedUploadResponse, ProfileData, ProfileResponse, ProjectData,
+        ProjectListResponse, QueryLatency, ReencryptionData, ReencryptionResponse, Reminder,
+        ReminderListResponse, RetentionPreviewResponse, ScheduleListResponse, SessionListResponse,
+        SessionSummary, SharedTodo, SharedTodoListResponse, SingleAttachmentResponse,
+        SingleCommentResponse, SingleFeatureFlagResponse, SingleProjectResponse,
+        SingleTemplateResponse, SingleTodoResponse, SingleTodoShareResponse, SingleWebhookResponse,
+        SingleWorkspaceMemberResponse, SingleWorkspaceResponse, StatsData, StatsResponse,
+        StatsTotals, SuggestedTodoData, SuggestedTodoResponse, SyncPullResponse, SyncPushResponse,
+        SyncPushResult, SyncedTodoRepresentation, TemplateData, TemplateListResponse,
+        TemplateSummary, TodoCountData, TodoCountResponse, TodoData, TodoListResponse,
+        TodoRepresentation, TotpEnrollmentData, TotpEnrollmentResponse, UndoData, UndoResponse,
+        UserSettingsData, UserSettingsResponse, VersionData, VersionResponse, WebhookData,
+        WebhookListResponse, WorkspaceData, WorkspaceListResponse, WorkspaceMemberListResponse,
+    },
+    scheduling::{self, Recurrence},
+    sharing::{self, TodoAccess},
+    stats::MAX_STATS_DAYS,
+    suggestions, sync, templates, timezones, titles, todos, undo, urls,
+    versioning::{ApiMount, ApiVersion},
+    webhooks, workflow,
+    workspaces::{self, RequestScope},
+};
+#[cfg(feature = "chaos")]
+use crate::{
+    chaos::FaultInjector,
+    model::SetFaultRulesSchema,
+    response::{FaultRulesData, FaultRulesResponse},
+};
+#[cfg(feature = "seed")]
+use crate::{fixtures, model::SeedQuery, response::SeedResponse};
+use actix_multipart::{Field, Multipart, MultipartError};
+use actix_web::error::PathError;
+use actix_web::guard::GuardContext;
+use actix_web::http::header::Header as _;
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{
+    delete, get, middleware, patch, post, put, route, web, HttpRequest, HttpResponse,
+    HttpResponseBuilder, Responder, Scope,
 };
-use actix_web::{delete, get, patch, post, web, HttpResponse, Responder};
 use chrono::prelude::*;
-use scylla::IntoTypedRows;
-use scylla::frame::value::CqlTimestamp;
+use futures_util::{StreamExt, TryStreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+/// Upper bound on IDs accepted by the batch endpoints.
+const MAX_BATCH_IDS: usize = 100;
+
+/// Upper bound on IDs ordered by one `PATCH /todos/reorder`.
+const MAX_REORDER_IDS: usize = 500;
+
+/// Upper bound on projected occurrences returned by the occurrences endpoint.
+const MAX_UPCOMING_OCCURRENCES: usize = 50;
+
+/// Upper bound on dead letters returned in one listing.
+const MAX_DEAD_LETTERS: usize = 500;
+
+/// Upper bound on completion-rate windows requested at once.
+const MAX_STATS_WINDOWS: usize = 10;
+
+/// Longest lifetime a todo can be created with: Scylla's maximum TTL (20 years).
+const MAX_TODO_TTL_SECS: u64 = 630_720_000;
+
+/// Upper bound on the length of a comment, in characters.
+const MAX_COMMENT_LENGTH: usize = 10_000;
+
+/// Upper bound on the length of a stored attachment file name, in characters.
+const MAX_FILE_NAME_LENGTH: usize = 255;
+
+/// Longest display name a user can choose, in characters.
+const MAX_DISPLAY_NAME_LENGTH: usize = 100;
+
+/// Longest `User-Agent` kept on a session, in characters.
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+/// Todos generated by `POST /dev/seed` when no `count` is given, and the
+/// most it accepts at once.
+#[cfg(feature = "seed")]
+const DEFAULT_SEED_COUNT: usize = 100;
+#[cfg(feature = "seed")]
+const MAX_SEED_TODOS: usize = 10_000;
+
+/// Response header confirming that a `Prefer` preference was honoured (RFC 7240).
+const PREFERENCE_APPLIED: &str = "Preference-Applied";
+
+/// Media type of streamed todo listings: one JSON todo per line.
+const NDJSON: &str = "application/x-ndjson";
+
 #[get("/healthchecker")]
 async fn health_checker_handler() -> impl Responder {
     const MESSAGE: &str = "Build Simple CRUD API with Rust, Actix Web, and Scylla";
@@ -19,321 +148,6177 @@ async fn health_checker_handler() -> impl Responder {
     HttpResponse::Ok().json(response_json)
 }
 
-#[get("/todos")]
+/// The build serving the request, so clients can tell which one they hit.
+#[get("/version")]
+async fn version_handler() -> impl Responder {
+    HttpResponse::Ok().json(VersionResponse {
+        status: "success".to_string(),
+        data: VersionData {
+            version: build_info::VERSION.to_string(),
+            commit: build_info::commit().map(str::to_string),
+            build: build_info::version(),
+        },
+    })
+}
+
+#[route("/todos", method = "GET", method = "HEAD", name = "todos")]
 pub async fn todos_list_handler(
-    opts: web::Query<QueryOptions>,
-    data: web::Data<AppState>,
-) -> impl Responder {
-    let limit = opts.limit.unwrap_or(10);
-    
-    let query = "SELECT id, title, content, completed, created_at, updated_at FROM todo_db.todos";
-    
-    let rows = match data.db.query(query, &[]).await {
-        Ok(result) => result.rows,
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Database error: {}", e),
-            };
-            return HttpResponse::InternalServerError().json(error_response);
-        }
-    };
-
-    let mut todos: Vec<Todo> = Vec::new();
-    
-    if let Some(rows) = rows {
-        for row in rows.into_typed::<(String, String, String, bool, CqlTimestamp, CqlTimestamp)>() {
-            if let Ok((id, title, content, completed, created_at, updated_at)) = row {
-                todos.push(Todo {
-                    id: Some(id),
-                    title,
-                    content,
-                    completed: Some(completed),
-                    createdAt: Some(DateTime::from_timestamp_millis(created_at.0).unwrap()),
-                    updatedAt: Some(DateTime::from_timestamp_millis(updated_at.0).unwrap()),
-                });
-            }
+    version: ApiVersion,
+    req: HttpRequest,
+    opts: QueryOptions,
+    query: web::Query<TodoListQuery>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let owner_id = match (query.mine.unwrap_or(false), &user) {
+        (false, _) => None,
+        (true, Some(user)) => Some(user.id.clone()),
+        (true, None) => {
+            return Err(AppError::Unauthorized(
+                "mine=true requires a bearer token".to_string(),
+            ))
+        }
+    };
+    let assignee_id = match (query.assigned_to.as_deref(), &user) {
+        (Some("me"), Some(user)) => Some(user.id.clone()),
+        (Some("me"), None) => {
+            return Err(AppError::Unauthorized(
+                "assigned_to=me requires a bearer token".to_string(),
+            ))
+        }
+        (assigned_to, _) => assigned_to.map(str::to_string),
+    };
+    if let (Some(after), Some(before)) = (query.created_after, query.created_before) {
+        if after >= before {
+            return Err(AppError::BadRequest(
+                "created_after must be before created_before".to_string(),
+            ));
         }
     }
 
-    let offset = (opts.page.unwrap_or(1) - 1) * limit;
-    let paginated_todos: Vec<Todo> = todos.into_iter().skip(offset).take(limit).collect();
+    let (completed, due_after, due_before) = match query.due {
+        Some(due) => {
+            let (today, tomorrow) = timezones::day_bounds(Utc::now(), timezones::current());
+            match due {
+                DueFilter::Overdue => (Some(false), None, Some(today)),
+                DueFilter::Today => (None, Some(today), Some(tomorrow)),
+            }
+        }
+        None => (None, None, None),
+    };
+
+    let options = ListOptions {
+        offset: opts.offset,
+        limit: opts.limit,
+        include_archived: opts.include_archived,
+        scope: scope.0,
+        visibility: sharing::visibility(&data, user.as_ref()).await?,
+        owner_id,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        project_id: query.project_id.clone(),
+        status: query.status,
+        assignee_id,
+        completed,
+        due_after,
+        due_before,
+        sort: query
+            .sort_by
+            .or_else(|| preferences::of(&req).and_then(|settings| settings.default_sort))
+            .unwrap_or_default(),
+    };
+    let include_ttl = query.include_ttl.unwrap_or(false);
+
+    let accepts_ndjson = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains(NDJSON));
+    if query.stream.unwrap_or(false) || accepts_ndjson {
+        let options = ListOptions {
+            offset: 0,
+            // Everything; SQL backends bind the limit as an i64.
+            limit: i64::MAX as usize,
+            ..options
+        };
+        return stream_todos(version, &data, &options, include_ttl).await;
+    }
 
+    let (todos, warnings) = decoding::collect(data.todos.list(&options)).await;
+    let mut todos = todos?;
+    if include_ttl {
+        let now = Utc::now();
+        for todo in &mut todos {
+            fill_ttl(todo, now);
+        }
+    }
+    let mut todos = with_comment_counts(&data, todos).await?;
+    if query.render == Some(Render::Html) {
+        data.markdown.apply(&mut todos);
+    }
+    let modified = todos.iter().filter_map(|todo| todo.updated_at).max();
     let json_response = TodoListResponse {
         status: "success".to_string(),
-        results: paginated_todos.len(),
-        todos: paginated_todos,
+        results: todos.len(),
+        links: urls::page_links(&req, Some(&opts), todos.len()),
+        todos: todos
+            .into_iter()
+            .map(|todo| TodoRepresentation::new(version, todo))
+            .collect(),
+        warnings,
     };
-    
-    HttpResponse::Ok().json(json_response)
+
+    conditional(&req, modified, json_response)
 }
 
 #[post("/todos")]
 async fn create_todo_handler(
-    body: web::Json<Todo>,
-    data: web::Data<AppState>,
-) -> impl Responder {
-    // Debug: Log what we received
-    println!("Received title: {}", body.title);
-    println!("Received content: {}", body.content);
-    
-    let uuid_id = Uuid::new_v4().to_string();
+    version: ApiVersion,
+    req: HttpRequest,
+    body: web::Json<CreateTodoSchema>,
+    user: Option<AuthUser>,
+    scope: RequestScope,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let uuid_id = TodoId::generate();
     let datetime = Utc::now();
-    let timestamp = CqlTimestamp(datetime.timestamp_millis());
-
-    let title = body.title.clone();
-    let content = body.content.clone();
-
-    let check_query = "SELECT id FROM todo_db.todos WHERE title = ? ALLOW FILTERING";
-    match data.db.query(check_query, (&title,)).await {
-        Ok(result) => {
-            if let Some(rows) = result.rows {
-                if !rows.is_empty() {
-                    let error_response = GenericResponse {
-                        status: "fail".to_string(),
-                        message: format!("Todo with title: '{}' already exists", title),
-                    };
-                    return HttpResponse::Conflict().json(error_response);
-                }
-            }
+
+    let CreateTodoSchema {
+        todo: body,
+        expires_in_seconds,
+    } = body.into_inner();
+    let title = titles::normalize(&data.sanitizer.title(body.title.clone()));
+    let content = data.sanitizer.content(body.content.clone());
+
+    if let Some(recurrence) = &body.recurrence {
+        recurrence.validate().map_err(AppError::BadRequest)?;
+    }
+    let expires_at = match expires_in_seconds {
+        Some(seconds) if seconds == 0 || seconds > MAX_TODO_TTL_SECS => {
+            return Err(AppError::BadRequest(format!(
+                "expiresInSeconds must be between 1 and {}",
+                MAX_TODO_TTL_SECS
+            )));
         }
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Database error: {}", e),
-            };
-            return HttpResponse::InternalServerError().json(error_response);
-        }
-    }
-
-    let insert_query = "INSERT INTO todo_db.todos (id, title, content, completed, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)";
-    
-    println!("Inserting: id={}, title={}, content={}", uuid_id, title, content);
-    
-    match data.db.query(
-        insert_query,
-        (&uuid_id, &title, &content, false, timestamp, timestamp)
-    ).await {
-        Ok(_) => {
-            let todo = Todo {
-                id: Some(uuid_id.clone()),
-                title: title.clone(),
-                content: content.clone(),
-                completed: Some(false),
-                createdAt: Some(datetime),
-                updatedAt: Some(datetime),
-            };
+        Some(seconds) => Some(datetime + chrono::Duration::seconds(seconds as i64)),
+        None => None,
+    };
+    let workspace_id = scope.0.workspace_id().map(str::to_string);
+    if let Some(project_id) = &body.project_id {
+        let owner_id = user.as_ref().map(|user| user.id.as_str());
+        projects::check_assignable(&data, workspace_id.as_deref(), owner_id, project_id).await?;
+    }
 
-            println!("Successfully created todo with id: {}", uuid_id);
+    // New todos start in the backlog unless the body says otherwise.
+    let status = body.status.unwrap_or(TodoStatus::Backlog);
+    let todo = Todo {
+        id: Some(uuid_id),
+        title,
+        content,
+        completed: Some(status == TodoStatus::Done),
+        archived: Some(false),
+        due_at: body.due_at,
+        recurrence: body.recurrence.clone(),
+        // The first occurrence of a series is identified by its own ID.
+        series_id: body.recurrence.as_ref().map(|_| uuid_id),
+        next_occurrence_id: None,
+        remind_at: body.remind_at,
+        reminder_sent_at: None,
+        owner_id: user.map(|user| user.id),
+        workspace_id,
+        assignee_id: None,
+        project_id: body.project_id,
+        position: body.position,
+        status: Some(status),
+        created_at: Some(datetime),
+        updated_at: Some(datetime),
+        expires_at,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    };
 
-            let json_response = SingleTodoResponse {
-                status: "success".to_string(),
-                data: TodoData { todo },
-            };
+    todos::create(&data, &todo).await?;
 
-            HttpResponse::Ok().json(json_response)
-        }
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Failed to create todo: {}", e),
-            };
-            HttpResponse::InternalServerError().json(error_response)
+    let json_response = SingleTodoResponse {
+        status: "success".to_string(),
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+        links: urls::todo_links(&req, &uuid_id)?,
+    };
+
+    Ok(created(&req, urls::todo(&req, &uuid_id), json_response))
+}
+
+/// Writes todos as newline-delimited JSON while they are read, so the
+/// full list is never held in memory. `commentCount` is left out, as it
+/// would take a lookup per todo.
+async fn stream_todos(
+    version: ApiVersion,
+    data: &AppState,
+    options: &ListOptions,
+    include_ttl: bool,
+) -> Result<HttpResponse, AppError> {
+    let todos = data.todos.stream(options).await?;
+
+    let now = Utc::now();
+    // The body is written after the request's scope has ended.
+    let zone = timezones::current();
+    let lines = todos.map(move |todo| {
+        let mut todo = todo.inspect_err(|e| {
+            log::error!("event=todo_stream_failed error=\"{}\"", e);
+        })?;
+        if include_ttl {
+            fill_ttl(&mut todo, now);
         }
+        let mut line = timezones::with_zone(zone, || {
+            serde_json::to_vec(&TodoRepresentation::new(version, todo))
+        })
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+        line.push(b'\n');
+        Ok::<_, AppError>(web::Bytes::from(line))
+    });
+
+    Ok(HttpResponse::Ok().content_type(NDJSON).streaming(lines))
+}
+
+/// Sets `ttlSeconds`, the time left before an expiring todo is deleted.
+fn fill_ttl(todo: &mut Todo, now: DateTime<Utc>) {
+    todo.ttl_seconds = todo
+        .expires_at
+        .map(|expires_at| (expires_at - now).num_seconds().max(0));
+}
+
+/// Fills in `commentCount` on each todo.
+async fn with_comment_counts(data: &AppState, mut todos: Vec<Todo>) -> Result<Vec<Todo>, AppError> {
+    let ids: Vec<TodoId> = todos.iter().filter_map(|todo| todo.id).collect();
+    let counts = data.comments.count_by_todo(&ids).await?;
+    for todo in &mut todos {
+        let count = todo.id.as_ref().and_then(|id| counts.get(id)).copied();
+        todo.comment_count = Some(count.unwrap_or(0));
     }
+    Ok(todos)
 }
 
-#[get("/todos/{id}")]
-async fn get_todo_handler(
-    path: web::Path<String>,
+/// Todo counts, completion rates and daily trends from the latest
+/// statistics snapshot.
+#[get("/todos/stats")]
+async fn todo_stats_handler(
+    opts: web::Query<StatsQuery>,
     data: web::Data<AppState>,
-) -> impl Responder {
-    let id = path.into_inner();
-    
-    let query = "SELECT id, title, content, completed, created_at, updated_at FROM todo_db.todos WHERE id = ?";
-    
-    match data.db.query(query, (&id,)).await {
-        Ok(result) => {
-            if let Some(rows) = result.rows {
-                if let Some(row) = rows.into_typed::<(String, String, String, bool, CqlTimestamp, CqlTimestamp)>().next() {
-                    if let Ok((id, title, content, completed, created_at, updated_at)) = row {
-                        let todo = Todo {
-                            id: Some(id),
-                            title,
-                            content,
-                            completed: Some(completed),
-                            createdAt: Some(DateTime::from_timestamp_millis(created_at.0).unwrap()),
-                            updatedAt: Some(DateTime::from_timestamp_millis(updated_at.0).unwrap()),
-                        };
-
-                        let json_response = SingleTodoResponse {
-                            status: "success".to_string(),
-                            data: TodoData { todo },
-                        };
-                        
-                        return HttpResponse::Ok().json(json_response);
+) -> Result<HttpResponse, AppError> {
+    let windows = parse_stats_windows(opts.windows.as_deref().unwrap_or("7,30"))?;
+    let days = opts.days.unwrap_or(14);
+    if days == 0 || days > MAX_STATS_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "days must be between 1 and {}",
+            MAX_STATS_DAYS
+        )));
+    }
+
+    let Some(snapshot) = data.stats.snapshot() else {
+        data.stats.refresh().await;
+        return Err(AppError::ServiceUnavailable(
+            "Statistics are still being computed, try again shortly".to_string(),
+        ));
+    };
+
+    let json_response = StatsResponse {
+        status: "success".to_string(),
+        data: StatsData {
+            generated_at: snapshot.generated_at,
+            totals: StatsTotals {
+                open: snapshot.open,
+                completed: snapshot.completed,
+                overdue: snapshot.overdue,
+                archived: snapshot.archived,
+            },
+            completion_rates: windows
+                .into_iter()
+                .map(|days| {
+                    let window = snapshot.completion_window(days);
+                    CompletionRate {
+                        days: window.days,
+                        created: window.created,
+                        completed: window.completed,
+                        rate: window.rate,
                     }
-                }
-            }
-            
-            let error_response = GenericResponse {
-                status: "fail".to_string(),
-                message: format!("Todo with ID: {} not found", id),
+                })
+                .collect(),
+            trend: snapshot
+                .trend(days)
+                .into_iter()
+                .map(|day| DailyCount {
+                    date: day.date,
+                    created: day.created,
+                    completed: day.completed,
+                })
+                .collect(),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// How many todos the caller owns. Unfiltered, it is read from the
+/// per-owner counters instead of counting the todos themselves; with
+/// `completed` or `project`, the matching unarchived todos are streamed
+/// and counted, without holding them in memory.
+#[get("/todos/count")]
+async fn todo_count_handler(
+    query: web::Query<TodoCountQuery>,
+    scope: RequestScope,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    if query.tag.is_some() {
+        return Err(AppError::BadRequest(
+            "Todos have no tags to filter by".to_string(),
+        ));
+    }
+
+    let count = match (query.completed, query.project) {
+        (None, None) => data.todos.count_for_owner(Some(&user.id)).await?,
+        (completed, project_id) => {
+            let options = ListOptions {
+                offset: 0,
+                // Everything; SQL backends bind the limit as an i64.
+                limit: i64::MAX as usize,
+                include_archived: false,
+                scope: scope.0,
+                visibility: sharing::visibility(&data, Some(&user)).await?,
+                owner_id: Some(user.id),
+                created_after: None,
+                created_before: None,
+                project_id,
+                // Completed todos are exactly those done; open ones can
+                // have any other status, so they are filtered below.
+                status: completed
+                    .filter(|completed| *completed)
+                    .map(|_| TodoStatus::Done),
+                assignee_id: None,
+                completed: None,
+                due_after: None,
+                due_before: None,
+                sort: TodoSort::default(),
             };
-            HttpResponse::NotFound().json(error_response)
+            data.todos
+                .stream(&options)
+                .await?
+                .try_fold(0, |count, todo| async move {
+                    let matches = completed
+                        .is_none_or(|completed| todo.completed.unwrap_or(false) == completed);
+                    Ok(count + u64::from(matches))
+                })
+                .await?
         }
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Database error: {}", e),
-            };
-            HttpResponse::InternalServerError().json(error_response)
+    };
+
+    let json_response = TodoCountResponse {
+        status: "success".to_string(),
+        data: TodoCountData { count },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// The open todo most worth doing now, as [`suggestions`] scores them:
+/// among the caller's own todos with a bearer token, otherwise among those
+/// in the request's scope. No content when there is nothing to do.
+#[get("/todos/next")]
+async fn next_todo_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let options = ListOptions {
+        offset: 0,
+        // Everything; SQL backends bind the limit as an i64.
+        limit: i64::MAX as usize,
+        include_archived: false,
+        scope: scope.0,
+        visibility: sharing::visibility(&data, user.as_ref()).await?,
+        owner_id: user.map(|user| user.id),
+        created_after: None,
+        created_before: None,
+        project_id: None,
+        status: None,
+        assignee_id: None,
+        completed: None,
+        due_after: None,
+        due_before: None,
+        sort: TodoSort::default(),
+    };
+    let Some((todo, score)) = suggestions::next(&data, &options, &data.suggestions).await? else {
+        return Ok(HttpResponse::NoContent().finish());
+    };
+    let id = todo.id.expect("stored todos have an ID");
+
+    let json_response = SuggestedTodoResponse {
+        status: "success".to_string(),
+        data: SuggestedTodoData {
+            todo: TodoRepresentation::new(version, todo),
+            score,
+        },
+        links: urls::todo_links(&req, &id)?,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+fn parse_stats_windows(value: &str) -> Result<Vec<u64>, AppError> {
+    let windows = value
+        .split(',')
+        .map(|window| match window.trim().parse::<u64>() {
+            Ok(days) if (1..=MAX_STATS_DAYS).contains(&days) => Ok(days),
+            _ => Err(AppError::BadRequest(format!(
+                "Invalid window {:?}: expected a number of days between 1 and {}",
+                window.trim(),
+                MAX_STATS_DAYS
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if windows.len() > MAX_STATS_WINDOWS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} windows can be requested",
+            MAX_STATS_WINDOWS
+        )));
+    }
+    Ok(windows)
+}
+
+/// Todos the caller can see in their scope whose title is exactly the
+/// given one, oldest first. Served from a title index, not a scan.
+#[get("/todos/by-title/{title}")]
+async fn todos_by_title_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<String>,
+    render: web::Query<RenderQuery>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let title = path.into_inner();
+    let visibility = sharing::visibility(&data, user.as_ref()).await?;
+
+    let (todos, warnings) = decoding::collect(data.todos.find_by_title(&title)).await;
+    let mut todos: Vec<Todo> = todos?
+        .into_iter()
+        .filter(|todo| scope.0.contains(todo) && visibility.allows(todo))
+        .collect();
+    todos.sort_by_key(|todo| todo.created_at);
+    let mut todos = with_comment_counts(&data, todos).await?;
+    if render.render == Some(Render::Html) {
+        data.markdown.apply(&mut todos);
+    }
+
+    let json_response = TodoListResponse {
+        status: "success".to_string(),
+        results: todos.len(),
+        links: urls::page_links(&req, None, todos.len()),
+        todos: todos
+            .into_iter()
+            .map(|todo| TodoRepresentation::new(version, todo))
+            .collect(),
+        warnings,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Fetches up to `MAX_BATCH_IDS` todos at once, looking them up
+/// concurrently. Todos the caller could not read one by one are reported
+/// as missing, like IDs that do not exist.
+#[post("/todos/batch-get")]
+async fn batch_get_todos_handler(
+    version: ApiVersion,
+    body: web::Json<BatchIdsSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    if body.ids.is_empty() {
+        return Err(AppError::BadRequest("ids must not be empty".to_string()));
+    }
+    if body.ids.len() > MAX_BATCH_IDS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} ids can be fetched at once",
+            MAX_BATCH_IDS
+        )));
+    }
+
+    let mut ids = body.into_inner().ids;
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+
+    let lookups = ids
+        .iter()
+        .map(|id| sharing::authorize(&data, id, &scope.0, user.as_ref(), TodoAccess::Read));
+    let mut todos = Vec::with_capacity(ids.len());
+    let mut missing = Vec::new();
+    for (id, result) in ids
+        .iter()
+        .zip(futures_util::future::join_all(lookups).await)
+    {
+        match result {
+            Ok(todo) => todos.push(todo),
+            Err(AppError::NotFound(_)) => missing.push(*id),
+            Err(e) => return Err(e),
         }
     }
+    let todos = with_comment_counts(&data, todos).await?;
+
+    let json_response = BatchGetResponse {
+        status: "success".to_string(),
+        results: todos.len(),
+        todos: todos
+            .into_iter()
+            .map(|todo| TodoRepresentation::new(version, todo))
+            .collect(),
+        missing,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[route("/todos/{id}", method = "GET", method = "HEAD", name = "todo")]
+async fn get_todo_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    render: web::Query<RenderQuery>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let todo = sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+    let mut todos = with_comment_counts(&data, vec![todo]).await?;
+    if render.render == Some(Render::Html) {
+        data.markdown.apply(&mut todos);
+    }
+    let todo = todos.remove(0);
+    let modified = todo.updated_at;
+
+    let json_response = SingleTodoResponse {
+        status: "success".to_string(),
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+        links: urls::todo_links(&req, &id)?,
+    };
+
+    conditional(&req, modified, json_response)
 }
 
 #[patch("/todos/{id}")]
 async fn edit_todo_handler(
-    path: web::Path<String>,
+    version: ApiVersion,
+    path: web::Path<TodoId>,
     body: web::Json<UpdateTodoSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
     data: web::Data<AppState>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let id = path.into_inner();
-    
-    let select_query = "SELECT id, title, content, completed, created_at, updated_at FROM todo_db.todos WHERE id = ?";
-    
-    let existing_todo = match data.db.query(select_query, (&id,)).await {
-        Ok(result) => {
-            if let Some(rows) = result.rows {
-                if let Some(row) = rows.into_typed::<(String, String, String, bool, CqlTimestamp, CqlTimestamp)>().next() {
-                    if let Ok((id, title, content, completed, created_at, updated_at)) = row {
-                        Some(Todo {
-                            id: Some(id),
-                            title,
-                            content,
-                            completed: Some(completed),
-                            createdAt: Some(DateTime::from_timestamp_millis(created_at.0).unwrap()),
-                            updatedAt: Some(DateTime::from_timestamp_millis(updated_at.0).unwrap()),
-                        })
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        }
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Database error: {}", e),
-            };
-            return HttpResponse::InternalServerError().json(error_response);
-        }
-    };
 
-    if existing_todo.is_none() {
-        let error_response = GenericResponse {
-            status: "fail".to_string(),
-            message: format!("Todo with ID: {} not found", id),
-        };
-        return HttpResponse::NotFound().json(error_response);
+    let existing =
+        sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+    edit_todo(
+        version,
+        &data,
+        id,
+        existing,
+        body.into_inner(),
+        user.as_ref(),
+    )
+    .await
+}
+
+/// Edits the fields of `PATCH /todos/{id}` given as JSON Patch operations
+/// (RFC 6902) on the todo's editable fields, e.g.
+/// `[{"op": "replace", "path": "/title", "value": "Buy oat milk"}]`.
+/// Operations that do not apply, or touch any other path, fail with a 422.
+#[patch("/todos/{id}", guard = "is_json_patch")]
+async fn json_patch_todo_handler(
+    version: ApiVersion,
+    path: web::Path<TodoId>,
+    body: web::Bytes,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let document: serde_json::Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JSON Patch document: {}", e)))?;
+    let operations: Vec<PatchOperation> = serde_json::from_value(document).map_err(|e| {
+        AppError::UnprocessableEntity(format!("Invalid JSON Patch operation: {}", e))
+    })?;
+
+    let existing =
+        sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+
+    let mut fields = serde_json::json!({
+        "title": existing.title,
+        "content": existing.content,
+        "completed": existing.completed.unwrap_or(false),
+        "dueAt": existing.due_at,
+        "recurrence": existing.recurrence,
+        "remindAt": existing.remind_at,
+        "projectId": existing.project_id,
+        "position": existing.position,
+    });
+    let before = fields.clone();
+    json_patch::apply(&mut fields, &operations, &JSON_PATCH_FIELDS)
+        .map_err(AppError::UnprocessableEntity)?;
+    // An unset field means "unchanged" to the merge-style edit, so clearing
+    // one cannot be expressed.
+    for field in JSON_PATCH_FIELDS {
+        if !before[field].is_null() && fields[field].is_null() {
+            return Err(AppError::UnprocessableEntity(format!(
+                "/{} cannot be removed",
+                field
+            )));
+        }
     }
+    let body: UpdateTodoSchema = serde_json::from_value(fields)
+        .map_err(|e| AppError::UnprocessableEntity(format!("Patched todo is invalid: {}", e)))?;
 
-    let existing = existing_todo.unwrap();
-    let datetime = Utc::now();
-    let timestamp = CqlTimestamp(datetime.timestamp_millis());
-
-    let new_title = body.title.clone().unwrap_or(existing.title.clone());
-    let new_content = body.content.clone().unwrap_or(existing.content.clone());
-    let new_completed = body.completed.unwrap_or(existing.completed.unwrap_or(false));
-
-    let update_query = "UPDATE todo_db.todos SET title = ?, content = ?, completed = ?, updated_at = ? WHERE id = ?";
-    
-    match data.db.query(
-        update_query,
-        (&new_title, &new_content, new_completed, timestamp, &id)
-    ).await {
-        Ok(_) => {
-            let todo = Todo {
-                id: Some(id),
-                title: new_title,
-                content: new_content,
-                completed: Some(new_completed),
-                createdAt: existing.createdAt,
-                updatedAt: Some(datetime),
-            };
+    edit_todo(version, &data, id, existing, body, user.as_ref()).await
+}
 
-            let json_response = SingleTodoResponse {
-                status: "success".to_string(),
-                data: TodoData { todo },
-            };
+/// The fields JSON Patch operations may touch, as `UpdateTodoSchema` names
+/// them.
+const JSON_PATCH_FIELDS: [&str; 8] = [
+    "title",
+    "content",
+    "completed",
+    "dueAt",
+    "recurrence",
+    "remindAt",
+    "projectId",
+    "position",
+];
+
+fn is_json_patch(ctx: &GuardContext) -> bool {
+    ctx.header::<header::ContentType>()
+        .is_some_and(|content_type| content_type.essence_str() == json_patch::CONTENT_TYPE)
+}
+
+/// Writes the fields of `body` that differ from `existing` and responds
+/// with the edited todo.
+async fn edit_todo(
+    version: ApiVersion,
+    data: &AppState,
+    id: TodoId,
+    existing: Todo,
+    body: UpdateTodoSchema,
+    user: Option<&AuthUser>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(recurrence) = &body.recurrence {
+        recurrence.validate().map_err(AppError::BadRequest)?;
+    }
+
+    // Only fields that differ from the stored todo are written.
+    let mut patch = TodoPatch {
+        title: body
+            .title
+            .map(|title| titles::normalize(&data.sanitizer.title(title)))
+            .filter(|title| *title != existing.title),
+        content: body
+            .content
+            .map(|content| data.sanitizer.content(content))
+            .filter(|content| *content != existing.content),
+        completed: body
+            .completed
+            .filter(|completed| *completed != existing.completed.unwrap_or(false)),
+        due_at: body
+            .due_at
+            .filter(|due_at| Some(*due_at) != existing.due_at),
+        recurrence: body
+            .recurrence
+            .filter(|recurrence| Some(recurrence) != existing.recurrence.as_ref()),
+        remind_at: body
+            .remind_at
+            .filter(|remind_at| Some(*remind_at) != existing.remind_at),
+        project_id: body
+            .project_id
+            .filter(|project_id| Some(project_id) != existing.project_id.as_ref()),
+        position: body
+            .position
+            .filter(|position| Some(*position) != existing.position),
+        updated_at: Utc::now(),
+        expires_at: existing.expires_at,
+        ..TodoPatch::default()
+    };
+    if let Some(project_id) = &patch.project_id {
+        projects::check_assignable(
+            data,
+            existing.workspace_id.as_deref(),
+            existing.owner_id.as_deref(),
+            project_id,
+        )
+        .await?;
+    }
+    if patch.recurrence.is_some() && existing.series_id.is_none() {
+        patch.series_id = Some(id);
+    }
+    // Setting a new reminder time re-arms the reminder.
+    patch.rearm_reminder = patch.remind_at.is_some();
 
-            HttpResponse::Ok().json(json_response)
+    let modified = !patch.is_empty();
+    let before = (patch.completed == Some(true)).then(|| existing.clone());
+    let mut todo = existing;
+    if modified {
+        let previous_status = todo.status();
+        patch.apply(&mut todo);
+        data.workflow.check(previous_status, todo.status())?;
+        let actor_id = user.map(|user| user.id.as_str());
+        let completes = patch.completed == Some(true);
+        let mut events = vec![DomainEvent::TodoUpdated {
+            id,
+            todo: Some(todo.clone()),
+            title: patch.title.clone(),
+            actor_id: actor_id.map(str::to_string),
+        }];
+        if completes {
+            events.push(DomainEvent::TodoCompleted {
+                id,
+                actor_id: actor_id.map(str::to_string),
+            });
         }
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Failed to update todo: {}", e),
-            };
-            HttpResponse::InternalServerError().json(error_response)
+        outbox::commit(data, TodoWrite::Patch(id, patch), events).await?;
+        if completes {
+            data.undo
+                .record(actor_id, UndoAction::Complete, before.into_iter().collect())
+                .await;
+            if todo.recurrence.is_some() {
+                data.recurrence.wake().await;
+            }
         }
     }
+
+    let json_response = PatchTodoResponse {
+        status: "success".to_string(),
+        modified,
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
 }
 
-#[delete("/todos/{id}")]
-async fn delete_todo_handler(
-    path: web::Path<String>,
+/// Assigns a todo to a member of its workspace, or unassigns it. The
+/// assignee is notified unless they made the assignment themselves.
+#[post("/todos/{id}/assign")]
+async fn assign_todo_handler(
+    version: ApiVersion,
+    path: web::Path<TodoId>,
+    body: web::Json<AssignTodoSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
     data: web::Data<AppState>,
-) -> impl Responder {
+) -> Result<HttpResponse, AppError> {
     let id = path.into_inner();
-    
-    let check_query = "SELECT id FROM todo_db.todos WHERE id = ?";
-    match data.db.query(check_query, (&id,)).await {
-        Ok(result) => {
-            if let Some(rows) = result.rows {
-                if rows.is_empty() {
-                    let error_response = GenericResponse {
-                        status: "fail".to_string(),
-                        message: format!("Todo with ID: {} not found", id),
-                    };
-                    return HttpResponse::NotFound().json(error_response);
-                }
-            }
+
+    let mut todo =
+        sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+    let modified = assignments::assign(
+        &data,
+        &id,
+        &mut todo,
+        body.into_inner().assignee_id,
+        user.as_ref().map(|user| user.id.as_str()),
+        Utc::now(),
+    )
+    .await?;
+
+    let json_response = PatchTodoResponse {
+        status: "success".to_string(),
+        modified,
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Moves a todo to another status, if the configured workflow allows the
+/// move. `completed` follows: it is set exactly when the status is done.
+#[patch("/todos/{id}/status", name = "todo_status")]
+async fn set_todo_status_handler(
+    version: ApiVersion,
+    path: web::Path<TodoId>,
+    body: web::Json<UpdateTodoStatusSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let mut todo =
+        sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+    let modified = workflow::set_status(
+        &data,
+        &id,
+        &mut todo,
+        body.status,
+        user.as_ref().map(|user| user.id.as_str()),
+        Utc::now(),
+    )
+    .await?;
+
+    let json_response = PatchTodoResponse {
+        status: "success".to_string(),
+        modified,
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Replaces every field of a todo. With `?upsert=true` a missing todo is
+/// created under the given ID (201) instead of reported as not found.
+#[put("/todos/{id}")]
+#[allow(clippy::too_many_arguments)]
+async fn replace_todo_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    opts: web::Query<ReplaceTodoQuery>,
+    body: web::Json<ReplaceTodoSchema>,
+    user: Option<AuthUser>,
+    scope: RequestScope,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let body = body.into_inner();
+
+    let title = titles::normalize(&data.sanitizer.title(body.title));
+    if title.is_empty() {
+        return Err(AppError::BadRequest("title must not be empty".to_string()));
+    }
+    if let Some(recurrence) = &body.recurrence {
+        recurrence.validate().map_err(AppError::BadRequest)?;
+    }
+
+    let existing = match data.todos.find_by_id(&id).await? {
+        Some(_) => {
+            Some(sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?)
         }
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Database error: {}", e),
-            };
-            return HttpResponse::InternalServerError().json(error_response);
+        None if opts.upsert.unwrap_or(false) => None,
+        None => {
+            return Err(AppError::NotFound(format!(
+                "Todo with ID: {} not found",
+                id
+            )))
         }
-    }
+    };
 
-    let delete_query = "DELETE FROM todo_db.todos WHERE id = ?";
-    
-    match data.db.query(delete_query, (&id,)).await {
-        Ok(_) => HttpResponse::NoContent().finish(),
-        Err(e) => {
-            let error_response = GenericResponse {
-                status: "error".to_string(),
-                message: format!("Failed to delete todo: {}", e),
-            };
-            HttpResponse::InternalServerError().json(error_response)
+    let (workspace_id, owner_id) = match &existing {
+        Some(existing) => (existing.workspace_id.clone(), existing.owner_id.clone()),
+        None => (
+            scope.0.workspace_id().map(str::to_string),
+            user.as_ref().map(|user| user.id.clone()),
+        ),
+    };
+    if let Some(project_id) = &body.project_id {
+        if existing.as_ref().and_then(|todo| todo.project_id.as_ref()) != Some(project_id) {
+            projects::check_assignable(
+                &data,
+                workspace_id.as_deref(),
+                owner_id.as_deref(),
+                project_id,
+            )
+            .await?;
         }
     }
+
+    // Renaming a todo to a variant of its own title is not a clash.
+    if existing.as_ref().map(|todo| titles::key(&todo.title)) != Some(titles::key(&title))
+        && data.todos.exists_with_title(&title).await?
+    {
+        return Err(AppError::Conflict(format!(
+            "Todo with title: '{}' already exists",
+            title
+        )));
+    }
+
+    let is_new = existing.is_none();
+    let title_edited = existing
+        .as_ref()
+        .is_some_and(|existing| existing.title != title);
+    let was_completed = existing
+        .as_ref()
+        .is_some_and(|existing| existing.completed == Some(true));
+    let previous_status = existing.as_ref().map(Todo::status);
+    let actor_id = user.as_ref().map(|user| user.id.clone());
+    let datetime = Utc::now();
+    let todo = match existing {
+        Some(existing) => Todo {
+            id: Some(id),
+            title,
+            content: data.sanitizer.content(body.content),
+            completed: Some(body.completed),
+            archived: Some(body.archived),
+            due_at: body.due_at,
+            series_id: existing
+                .series_id
+                .or_else(|| body.recurrence.as_ref().map(|_| id)),
+            recurrence: body.recurrence,
+            next_occurrence_id: existing.next_occurrence_id,
+            // A changed reminder time re-arms the reminder.
+            reminder_sent_at: if body.remind_at == existing.remind_at {
+                existing.reminder_sent_at
+            } else {
+                None
+            },
+            remind_at: body.remind_at,
+            owner_id: existing.owner_id,
+            workspace_id,
+            assignee_id: existing.assignee_id,
+            project_id: body.project_id,
+            position: body.position,
+            // `completed` wins over a stored status it contradicts.
+            status: existing.status,
+            created_at: existing.created_at,
+            updated_at: Some(datetime),
+            expires_at: existing.expires_at,
+            comment_count: None,
+            ttl_seconds: None,
+            content_html: None,
+        },
+        None => Todo {
+            id: Some(id),
+            title,
+            content: data.sanitizer.content(body.content),
+            completed: Some(body.completed),
+            archived: Some(body.archived),
+            due_at: body.due_at,
+            series_id: body.recurrence.as_ref().map(|_| id),
+            recurrence: body.recurrence,
+            next_occurrence_id: None,
+            remind_at: body.remind_at,
+            reminder_sent_at: None,
+            owner_id: user.map(|user| user.id),
+            workspace_id,
+            assignee_id: None,
+            project_id: body.project_id,
+            position: body.position,
+            status: None,
+            created_at: Some(datetime),
+            updated_at: Some(datetime),
+            expires_at: None,
+            comment_count: None,
+            ttl_seconds: None,
+            content_html: None,
+        },
+    };
+    if let Some(previous_status) = previous_status {
+        data.workflow.check(previous_status, todo.status())?;
+    }
+
+    let (write, mut events) = if is_new {
+        let event = DomainEvent::TodoCreated {
+            todo: todo.clone(),
+            actor_id: actor_id.clone(),
+        };
+        (TodoWrite::Insert(todo.clone()), vec![event])
+    } else {
+        let event = DomainEvent::TodoUpdated {
+            id,
+            todo: Some(todo.clone()),
+            title: title_edited.then(|| todo.title.clone()),
+            actor_id: actor_id.clone(),
+        };
+        (TodoWrite::Update(todo.clone()), vec![event])
+    };
+    if todo.completed == Some(true) && !was_completed {
+        events.push(DomainEvent::TodoCompleted {
+            id,
+            actor_id: actor_id.clone(),
+        });
+    }
+    outbox::commit(&data, write, events).await?;
+
+    if todo.completed == Some(true) && todo.recurrence.is_some() {
+        data.recurrence.wake().await;
+    }
+
+    let json_response = SingleTodoResponse {
+        status: "success".to_string(),
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+        links: urls::todo_links(&req, &id)?,
+    };
+
+    if is_new {
+        Ok(created(&req, urls::todo(&req, &id), json_response))
+    } else {
+        Ok(HttpResponse::Ok().json(json_response))
+    }
 }
 
-pub fn config(conf: &mut web::ServiceConfig) {
-    let scope = web::scope("/api")
-        .service(health_checker_handler)
-        .service(todos_list_handler)
-        .service(create_todo_handler)
-        .service(get_todo_handler)
-        .service(edit_todo_handler)
-        .service(delete_todo_handler);
+/// Lists the materialized occurrences of a recurring todo's series and
+/// projects the next `upcoming` due dates after the latest one.
+#[get("/todos/{id}/occurrences")]
+async fn todo_occurrences_handler(
+    version: ApiVersion,
+    path: web::Path<TodoId>,
+    opts: web::Query<OccurrencesQuery>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let todo = sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+
+    let recurrence: Recurrence = todo
+        .recurrence
+        .clone()
+        .ok_or_else(|| AppError::BadRequest(format!("Todo with ID: {} does not recur", id)))?;
+
+    let series_id = todo.series_id.unwrap_or(id);
+    let mut occurrences = data.todos.list_series(&series_id).await?;
+    if occurrences.is_empty() {
+        occurrences.push(todo);
+    }
+    occurrences.sort_by_key(scheduling::anchor);
 
-    conf.service(scope);
-}
\ No newline at end of file
+    let count = opts.upcoming.unwrap_or(5).min(MAX_UPCOMING_OCCURRENCES);
+    let upcoming = occurrences
+        .last()
+        .and_then(scheduling::anchor)
+        .map(|latest| recurrence.upcoming(latest, count))
+        .unwrap_or_default();
+
+    let json_response = OccurrencesResponse {
+        status: "success".to_string(),
+        occurrences: occurrences
+            .into_iter()
+            .map(|todo| TodoRepresentation::new(version, todo))
+            .collect(),
+        upcoming,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Creates a copy of a todo the caller can read, owned by the caller in
+/// the request's scope. See [`todos::duplicate`] for what is copied.
+#[post("/todos/{id}/duplicate")]
+async fn duplicate_todo_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    body: web::Bytes,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let body: DuplicateTodoSchema = optional_json(&body)?.unwrap_or_default();
+
+    let source = sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+    let todo = todos::duplicate(
+        &source,
+        body.title_suffix
+            .as_deref()
+            .unwrap_or(todos::DEFAULT_COPY_SUFFIX),
+        body.keep_completion.unwrap_or(false),
+        user.map(|user| user.id),
+        scope.0.workspace_id().map(str::to_string),
+        Utc::now(),
+    );
+    let new_id = todo.id.expect("duplicates are given an ID");
+    todos::create(&data, &todo).await?;
+
+    let json_response = SingleTodoResponse {
+        status: "success".to_string(),
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+        links: urls::todo_links(&req, &new_id)?,
+    };
+
+    Ok(created(&req, urls::todo(&req, &new_id), json_response))
+}
+
+#[post("/todos/{id}/archive")]
+async fn archive_todo_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    set_archived(
+        &req,
+        path.into_inner(),
+        true,
+        version,
+        &scope.0,
+        user.as_ref(),
+        &data,
+    )
+    .await
+}
+
+#[post("/todos/{id}/unarchive")]
+async fn unarchive_todo_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    set_archived(
+        &req,
+        path.into_inner(),
+        false,
+        version,
+        &scope.0,
+        user.as_ref(),
+        &data,
+    )
+    .await
+}
+
+async fn set_archived(
+    req: &HttpRequest,
+    id: TodoId,
+    archived: bool,
+    version: ApiVersion,
+    scope: &TodoScope,
+    user: Option<&AuthUser>,
+    data: &AppState,
+) -> Result<HttpResponse, AppError> {
+    let mut todo = sharing::authorize(data, &id, scope, user, TodoAccess::Write).await?;
+
+    todo.archived = Some(archived);
+    todo.updated_at = Some(Utc::now());
+
+    let event = DomainEvent::TodoUpdated {
+        id,
+        todo: Some(todo.clone()),
+        title: None,
+        actor_id: user.map(|user| user.id.clone()),
+    };
+    outbox::commit(data, TodoWrite::Update(todo.clone()), vec![event]).await?;
+
+    let json_response = SingleTodoResponse {
+        status: "success".to_string(),
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+        links: urls::todo_links(req, &id)?,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[patch("/todos/complete")]
+async fn complete_todos_handler(
+    body: web::Json<BatchIdsSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    set_completed_batch(&body.ids, true, &scope.0, user.as_ref(), &data).await
+}
+
+#[patch("/todos/incomplete")]
+async fn incomplete_todos_handler(
+    body: web::Json<BatchIdsSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    set_completed_batch(&body.ids, false, &scope.0, user.as_ref(), &data).await
+}
+
+async fn set_completed_batch(
+    ids: &[TodoId],
+    completed: bool,
+    scope: &TodoScope,
+    user: Option<&AuthUser>,
+    data: &AppState,
+) -> Result<HttpResponse, AppError> {
+    if ids.is_empty() {
+        return Err(AppError::BadRequest("ids must not be empty".to_string()));
+    }
+    if ids.len() > MAX_BATCH_IDS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} ids can be updated at once",
+            MAX_BATCH_IDS
+        )));
+    }
+
+    // IDs the caller may not change are reported as not found, and todos
+    // the workflow does not let move to or from done as conflicts. The
+    // stored todos give each patch its expiry, and the undo log what to
+    // restore.
+    let mut before = sharing::authorize_all(data, ids, scope, user, TodoAccess::Write).await?;
+
+    let now = Utc::now();
+    let actor_id = user.map(|user| user.id.clone());
+    let mut updated = Vec::with_capacity(before.len());
+    let mut refused = Vec::new();
+    let mut changes = Vec::with_capacity(before.len());
+    for todo in &before {
+        let Some(id) = todo.id else {
+            continue;
+        };
+        let patch = TodoPatch {
+            completed: Some(completed),
+            updated_at: now,
+            expires_at: todo.expires_at,
+            ..TodoPatch::default()
+        };
+        let mut todo = todo.clone();
+        let previous_status = todo.status();
+        patch.apply(&mut todo);
+        if data.workflow.check(previous_status, todo.status()).is_err() {
+            refused.push(id);
+            continue;
+        }
+        let mut events = vec![DomainEvent::TodoUpdated {
+            id,
+            todo: Some(todo),
+            title: None,
+            actor_id: actor_id.clone(),
+        }];
+        if completed {
+            events.push(DomainEvent::TodoCompleted {
+                id,
+                actor_id: actor_id.clone(),
+            });
+        }
+        updated.push(id);
+        changes.push((TodoWrite::Patch(id, patch), events));
+    }
+    outbox::commit_all(data, changes).await?;
+
+    let action = if completed {
+        UndoAction::Complete
+    } else {
+        UndoAction::Incomplete
+    };
+    // Only signed-in callers can undo, so only their changes are logged.
+    if user.is_none() {
+        before.clear();
+    }
+    before.retain(|todo| todo.id.is_some_and(|id| updated.contains(&id)));
+    data.undo
+        .record(user.map(|user| user.id.as_str()), action, before)
+        .await;
+    if completed && !updated.is_empty() {
+        data.recurrence.wake().await;
+    }
+
+    let results = ids
+        .iter()
+        .map(|id| BatchItemResult {
+            id: *id,
+            status: if updated.contains(id) {
+                "updated".to_string()
+            } else if refused.contains(id) {
+                "conflict".to_string()
+            } else {
+                "not_found".to_string()
+            },
+        })
+        .collect();
+
+    let json_response = BatchResponse {
+        status: "success".to_string(),
+        results,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Persists a manual order. With `ids` the todos get spaced positions in
+/// that order; with `id` and `before` or `after` one todo is moved next to
+/// another. Responds with the todos whose position changed.
+#[patch("/todos/reorder")]
+async fn reorder_todos_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    body: web::Json<ReorderTodosSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let now = Utc::now();
+    let updated = match body.into_inner() {
+        ReorderTodosSchema::Ordered { ids } => {
+            if ids.is_empty() {
+                return Err(AppError::BadRequest("ids must not be empty".to_string()));
+            }
+            if ids.len() > MAX_REORDER_IDS {
+                return Err(AppError::BadRequest(format!(
+                    "At most {} ids can be ordered at once",
+                    MAX_REORDER_IDS
+                )));
+            }
+            let mut todos = Vec::with_capacity(ids.len());
+            for (index, id) in ids.iter().enumerate() {
+                if ids[..index].contains(id) {
+                    return Err(AppError::BadRequest(format!(
+                        "Todo with ID: {} is listed more than once",
+                        id
+                    )));
+                }
+                let todo =
+                    sharing::authorize(&data, id, &scope.0, user.as_ref(), TodoAccess::Write)
+                        .await?;
+                todos.push((todo, ordering::spaced(index)));
+            }
+            ordering::set_positions(&data, todos, now).await?
+        }
+        ReorderTodosSchema::Move { id, before, after } => {
+            let (anchor_id, placement) = match (before, after) {
+                (Some(before), None) => (before, Placement::Before),
+                (None, Some(after)) => (after, Placement::After),
+                _ => {
+                    return Err(AppError::BadRequest(
+                        "Exactly one of before and after must be given".to_string(),
+                    ))
+                }
+            };
+            if anchor_id == id {
+                return Err(AppError::BadRequest(
+                    "A todo cannot be moved next to itself".to_string(),
+                ));
+            }
+            let moved =
+                sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+            let anchor =
+                sharing::authorize(&data, &anchor_id, &scope.0, user.as_ref(), TodoAccess::Read)
+                    .await?;
+            ordering::move_todo(&data, moved, &anchor, placement, now).await?
+        }
+    };
+
+    let json_response = TodoListResponse {
+        status: "success".to_string(),
+        results: updated.len(),
+        // The list the todos were reordered within.
+        links: PageLinks {
+            self_link: Link::get(urls::route(&req, "todos", &[])?),
+            next: None,
+            prev: None,
+        },
+        todos: updated
+            .into_iter()
+            .map(|todo| TodoRepresentation::new(version, todo))
+            .collect(),
+        warnings: Vec::new(),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Shares a todo with another registered user. Only the todo's owner can
+/// share it; sharing again with the same user changes their permission.
+#[post("/todos/{id}/share")]
+async fn share_todo_handler(
+    path: web::Path<TodoId>,
+    body: web::Json<ShareTodoSchema>,
+    scope: RequestScope,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let todo = sharing::authorize(&data, &id, &scope.0, Some(&user), TodoAccess::Read).await?;
+    if todo.owner_id.as_deref() != Some(user.id.as_str()) {
+        return Err(AppError::Forbidden(
+            "Only the todo's owner can share it".to_string(),
+        ));
+    }
+
+    let email = body.email.trim().to_lowercase();
+    let collaborator = data
+        .users
+        .find_by_email(&email)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No account with email '{}'", email)))?;
+    if collaborator.id == user.id {
+        return Err(AppError::BadRequest(
+            "A todo cannot be shared with its owner".to_string(),
+        ));
+    }
+
+    let share = TodoShare {
+        todo_id: id,
+        user_id: collaborator.id,
+        permission: body.permission.unwrap_or(SharePermission::Read),
+        shared_at: Utc::now(),
+    };
+    data.acl.upsert(&share).await?;
+    log::info!(
+        "event=todo_shared todo_id={} user_id={} permission={}",
+        share.todo_id,
+        share.user_id,
+        share.permission.as_str()
+    );
+
+    let json_response = SingleTodoShareResponse {
+        status: "success".to_string(),
+        share,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Todos other users have shared with the caller, most recently shared first.
+#[get("/todos/shared-with-me")]
+async fn shared_with_me_handler(
+    version: ApiVersion,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let shares = data.acl.list_for_user(&user.id).await?;
+
+    let mut todos = Vec::with_capacity(shares.len());
+    for share in shares {
+        if let Some(todo) = data.todos.find_by_id(&share.todo_id).await? {
+            todos.push(SharedTodo {
+                todo: TodoRepresentation::new(version, todo),
+                permission: share.permission,
+            });
+        }
+    }
+
+    let json_response = SharedTodoListResponse {
+        status: "success".to_string(),
+        results: todos.len(),
+        todos,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[get("/todos/{id}/comments")]
+async fn todo_comments_list_handler(
+    path: web::Path<TodoId>,
+    opts: QueryOptions,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+
+    let comments = data.comments.list(&id, opts.offset, opts.limit).await?;
+
+    let json_response = CommentListResponse {
+        status: "success".to_string(),
+        results: comments.len(),
+        comments,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// The todo's activity, oldest first. `?type=` limits it to some kinds,
+/// e.g. `completed,commented`.
+#[get("/todos/{id}/activity")]
+async fn todo_activity_handler(
+    path: web::Path<TodoId>,
+    opts: QueryOptions,
+    query: web::Query<ActivityListQuery>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let kinds = query.kinds().map_err(AppError::BadRequest)?;
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+
+    let activity = data
+        .activity
+        .list_for_todo(&id, &kinds, opts.offset, opts.limit)
+        .await?;
+
+    let json_response = ActivityListResponse {
+        status: "success".to_string(),
+        results: activity.len(),
+        activity,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// What the caller has done, oldest first, filtered like the todo feed.
+#[get("/activity")]
+async fn user_activity_handler(
+    opts: QueryOptions,
+    query: web::Query<ActivityListQuery>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let kinds = query.kinds().map_err(AppError::BadRequest)?;
+
+    let activity = data
+        .activity
+        .list_for_actor(&user.id, &kinds, opts.offset, opts.limit)
+        .await?;
+
+    let json_response = ActivityListResponse {
+        status: "success".to_string(),
+        results: activity.len(),
+        activity,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Adds a comment. Anyone who can see the todo can comment on it.
+#[post("/todos/{id}/comments")]
+async fn create_comment_handler(
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    body: web::Json<CreateCommentSchema>,
+    scope: RequestScope,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, Some(&user), TodoAccess::Read).await?;
+
+    let text = body.body.trim();
+    if text.is_empty() {
+        return Err(AppError::BadRequest(
+            "Comment body must not be empty".to_string(),
+        ));
+    }
+    if text.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Comment body must be at most {} characters",
+            MAX_COMMENT_LENGTH
+        )));
+    }
+
+    let now = Utc::now();
+    let comment = Comment {
+        id: Uuid::new_v4().to_string(),
+        todo_id: id,
+        author_id: user.id,
+        body: text.to_string(),
+        created_at: now,
+        updated_at: now,
+    };
+    data.comments.insert(&comment).await?;
+    activity::record(
+        &data,
+        &id,
+        Some(&comment.author_id),
+        ActivityKind::Commented,
+        Some(comment.id.clone()),
+    )
+    .await;
+
+    let location = urls::comment(&req, &comment.todo_id, &comment.id);
+    let json_response = SingleCommentResponse {
+        status: "success".to_string(),
+        data: CommentData { comment },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+/// Deletes a comment. Only its author or the todo's owner can do this.
+#[delete("/todos/{id}/comments/{comment_id}")]
+async fn delete_comment_handler(
+    path: web::Path<(TodoId, String)>,
+    scope: RequestScope,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (id, comment_id) = path.into_inner();
+    let todo = sharing::authorize(&data, &id, &scope.0, Some(&user), TodoAccess::Read).await?;
+
+    let comment = data
+        .comments
+        .find_by_id(&id, &comment_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Comment with ID: {} not found", comment_id)))?;
+    if comment.author_id != user.id && todo.owner_id.as_deref() != Some(user.id.as_str()) {
+        return Err(AppError::Forbidden(
+            "Only the comment's author or the todo's owner can delete it".to_string(),
+        ));
+    }
+
+    data.comments.delete(&id, &comment_id).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/todos/{id}/attachments")]
+async fn attachments_list_handler(
+    path: web::Path<TodoId>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+
+    let attachments = data.attachments.list(&id).await?;
+    let json_response = AttachmentListResponse {
+        status: "success".to_string(),
+        results: attachments.len(),
+        attachments,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Uploads the `file` field of a multipart form as a new attachment. The
+/// contents are streamed to the blob store and the size limit is enforced
+/// while reading, so oversized uploads are cut off early.
+#[post("/todos/{id}/attachments")]
+async fn upload_attachment_handler(
+    req: HttpRequest,
+    path: web::Path<TodoId>,
+    mut payload: Multipart,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+
+    let mut field = loop {
+        match payload.try_next().await.map_err(multipart_error)? {
+            Some(field) if field.name() == Some("file") => break field,
+            Some(_) => continue,
+            None => {
+                return Err(AppError::BadRequest(
+                    "Expected a multipart form with a 'file' field".to_string(),
+                ))
+            }
+        }
+    };
+
+    let content_type = field
+        .content_type()
+        .map(|mime| mime.essence_str().to_string())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    if !data.uploads.allows(&content_type) {
+        return Err(AppError::UnsupportedMediaType(format!(
+            "Attachments of type '{}' are not allowed",
+            content_type
+        )));
+    }
+    let file_name = field
+        .content_disposition()
+        .and_then(|disposition| disposition.get_filename())
+        .map(sanitize_file_name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "attachment".to_string());
+
+    let attachment_id = Uuid::new_v4().to_string();
+    let mut writer = data.blobs.create(&attachment_id).await?;
+    let size = match copy_upload(&mut field, writer.as_mut(), data.uploads.max_size).await {
+        Ok(size) => size,
+        Err(e) => {
+            writer.abort().await;
+            return Err(e);
+        }
+    };
+    writer.finish().await?;
+
+    let attachment = Attachment {
+        id: attachment_id,
+        todo_id: id,
+        file_name,
+        content_type,
+        size,
+        uploaded_by: user.map(|user| user.id),
+        created_at: Utc::now(),
+    };
+    if let Err(e) = data.attachments.insert(&attachment).await {
+        todos::delete_blob(&data, &attachment.id).await;
+        return Err(e.into());
+    }
+    log::info!(
+        "event=attachment_uploaded todo_id={} attachment_id={} size={}",
+        attachment.todo_id,
+        attachment.id,
+        attachment.size
+    );
+
+    let location = urls::attachment(&req, &attachment.todo_id, &attachment.id);
+    let json_response = SingleAttachmentResponse {
+        status: "success".to_string(),
+        data: AttachmentData { attachment },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+/// Hands out a URL the client uploads the file to directly, for stores that
+/// support it. The attachment only exists once the upload is completed;
+/// objects that are uploaded but never completed are left to the bucket's
+/// lifecycle rules.
+#[post("/todos/{id}/attachments/presign")]
+async fn presign_upload_handler(
+    path: web::Path<TodoId>,
+    body: web::Json<PresignUploadSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+
+    let content_type = body.content_type.trim().to_ascii_lowercase();
+    data.uploads.check(&content_type, body.size)?;
+    if sanitize_file_name(&body.file_name).is_empty() {
+        return Err(AppError::BadRequest(
+            "fileName must not be empty".to_string(),
+        ));
+    }
+
+    let attachment_id = Uuid::new_v4().to_string();
+    let upload = data
+        .blobs
+        .presign_upload(&attachment_id, &content_type, body.size)
+        .await?
+        .ok_or_else(presigning_unsupported)?;
+
+    let json_response = PresignedUploadResponse {
+        status: "success".to_string(),
+        data: PresignedUploadData {
+            attachment_id,
+            upload: upload.into(),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Records an attachment uploaded through a presigned URL. Size and type
+/// are read back from the store, so limits hold even if the client lied.
+#[post("/todos/{id}/attachments/{attachment_id}/complete")]
+async fn complete_upload_handler(
+    req: HttpRequest,
+    path: web::Path<(TodoId, String)>,
+    body: web::Json<CompleteUploadSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (id, attachment_id) = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+
+    let not_found =
+        || AppError::NotFound(format!("No upload found for attachment {}", attachment_id));
+    if Uuid::parse_str(&attachment_id).is_err() {
+        return Err(not_found());
+    }
+    if data
+        .attachments
+        .find_by_id(&id, &attachment_id)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::Conflict(format!(
+            "Attachment with ID: {} is already complete",
+            attachment_id
+        )));
+    }
+    let info = data
+        .blobs
+        .stat(&attachment_id)
+        .await?
+        .ok_or_else(not_found)?;
+
+    let content_type = info
+        .content_type
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    if let Err(e) = data.uploads.check(&content_type, info.size) {
+        todos::delete_blob(&data, &attachment_id).await;
+        return Err(e);
+    }
+    let file_name = Some(sanitize_file_name(&body.file_name))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "attachment".to_string());
+
+    let attachment = Attachment {
+        id: attachment_id,
+        todo_id: id,
+        file_name,
+        content_type,
+        size: info.size,
+        uploaded_by: user.map(|user| user.id),
+        created_at: Utc::now(),
+    };
+    data.attachments.insert(&attachment).await?;
+    log::info!(
+        "event=attachment_uploaded todo_id={} attachment_id={} size={} direct=true",
+        attachment.todo_id,
+        attachment.id,
+        attachment.size
+    );
+
+    let location = urls::attachment(&req, &attachment.todo_id, &attachment.id);
+    let json_response = SingleAttachmentResponse {
+        status: "success".to_string(),
+        data: AttachmentData { attachment },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+/// A short-lived URL that downloads the attachment straight from the store.
+#[get("/todos/{id}/attachments/{attachment_id}/url")]
+async fn download_url_handler(
+    path: web::Path<(TodoId, String)>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (id, attachment_id) = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+
+    let attachment = find_attachment(&data, &id, &attachment_id).await?;
+    let download = data
+        .blobs
+        .presign_download(
+            &attachment.id,
+            &attachment.file_name,
+            &attachment.content_type,
+        )
+        .await?
+        .ok_or_else(presigning_unsupported)?;
+
+    let json_response = PresignedDownloadResponse {
+        status: "success".to_string(),
+        data: download.into(),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Streams an attachment's contents back with its original name and type.
+/// Stores that support presigned URLs get a redirect instead, so the bytes
+/// never pass through this process.
+#[get("/todos/{id}/attachments/{attachment_id}")]
+async fn download_attachment_handler(
+    path: web::Path<(TodoId, String)>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (id, attachment_id) = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Read).await?;
+
+    let attachment = find_attachment(&data, &id, &attachment_id).await?;
+    if let Some(download) = data
+        .blobs
+        .presign_download(
+            &attachment.id,
+            &attachment.file_name,
+            &attachment.content_type,
+        )
+        .await?
+    {
+        return Ok(HttpResponse::TemporaryRedirect()
+            .insert_header((header::LOCATION, download.url))
+            .finish());
+    }
+    let contents = data.blobs.open(&attachment.id).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(attachment.content_type.as_str())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(attachment.file_name)],
+        })
+        .no_chunking(attachment.size)
+        .streaming(contents))
+}
+
+#[delete("/todos/{id}/attachments/{attachment_id}")]
+async fn delete_attachment_handler(
+    path: web::Path<(TodoId, String)>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (id, attachment_id) = path.into_inner();
+    sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+
+    let attachment = find_attachment(&data, &id, &attachment_id).await?;
+    data.attachments.delete(&id, &attachment.id).await?;
+    todos::delete_blob(&data, &attachment.id).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn find_attachment(
+    data: &AppState,
+    todo_id: &TodoId,
+    id: &str,
+) -> Result<Attachment, AppError> {
+    data.attachments
+        .find_by_id(todo_id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment with ID: {} not found", id)))
+}
+
+/// Copies an upload into `writer`, failing once it exceeds `max_size` bytes.
+async fn copy_upload(
+    field: &mut Field,
+    writer: &mut dyn BlobWriter,
+    max_size: u64,
+) -> Result<u64, AppError> {
+    let mut size: u64 = 0;
+    while let Some(chunk) = field.try_next().await.map_err(multipart_error)? {
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Attachments must be at most {} bytes",
+                max_size
+            )));
+        }
+        writer.write(&chunk).await?;
+    }
+    Ok(size)
+}
+
+fn presigning_unsupported() -> AppError {
+    AppError::BadRequest("The configured blob store does not support direct URLs".to_string())
+}
+
+fn multipart_error(e: MultipartError) -> AppError {
+    AppError::BadRequest(format!("Invalid multipart upload: {}", e))
+}
+
+/// Keeps only the final path component of a client-supplied file name and
+/// drops control characters.
+fn sanitize_file_name(name: &str) -> String {
+    name.rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FILE_NAME_LENGTH)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// The projects of the request's workspace, or the caller's personal ones,
+/// oldest first.
+#[get("/projects")]
+async fn projects_list_handler(
+    query: web::Query<ProjectListQuery>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let workspace_id = scope.0.workspace_id();
+    let owner_id = projects::owner(workspace_id, user.as_ref())?;
+    let include_archived = query.include_archived.unwrap_or(false);
+    let projects: Vec<Project> = projects::list(&data, workspace_id, owner_id.as_deref())
+        .await?
+        .into_iter()
+        .filter(|project| include_archived || !project.archived)
+        .collect();
+
+    let json_response = ProjectListResponse {
+        status: "success".to_string(),
+        results: projects.len(),
+        projects,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Creates a project in the request's workspace, or a personal one.
+#[post("/projects")]
+async fn create_project_handler(
+    req: HttpRequest,
+    body: web::Json<CreateProjectSchema>,
+    user: Option<AuthUser>,
+    scope: RequestScope,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let workspace_id = scope.0.workspace_id();
+    projects::owner(workspace_id, user.as_ref())?;
+    let name = projects::validate_name(&body.name)?;
+
+    let now = Utc::now();
+    let project = Project {
+        id: Uuid::new_v4().to_string(),
+        name,
+        workspace_id: workspace_id.map(str::to_string),
+        owner_id: user.map(|user| user.id),
+        archived: false,
+        created_at: now,
+        updated_at: now,
+    };
+    data.projects.insert(&project).await?;
+    log::info!("event=project_created project_id={}", project.id);
+
+    let location = urls::project(&req, &project.id);
+    let json_response = SingleProjectResponse {
+        status: "success".to_string(),
+        data: ProjectData { project },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+#[get("/projects/{id}")]
+async fn get_project_handler(
+    path: web::Path<String>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let project = find_project(&data, &scope.0, user.as_ref(), &path.into_inner()).await?;
+
+    let json_response = SingleProjectResponse {
+        status: "success".to_string(),
+        data: ProjectData { project },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Renames a project or changes whether it is archived. Archiving it also
+/// archives its todos.
+#[patch("/projects/{id}")]
+async fn edit_project_handler(
+    path: web::Path<String>,
+    body: web::Json<UpdateProjectSchema>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let mut project = find_project(&data, &scope.0, user.as_ref(), &path.into_inner()).await?;
+
+    let now = Utc::now();
+    if let Some(name) = &body.name {
+        project.name = projects::validate_name(name)?;
+    }
+    let archiving = body.archived == Some(true) && !project.archived;
+    if let Some(archived) = body.archived {
+        project.archived = archived;
+    }
+    project.updated_at = now;
+    data.projects.update(&project).await?;
+
+    if archiving {
+        let archived = projects::archive_todos(&data, &project, now).await?;
+        log::info!(
+            "event=project_archived project_id={} todos_archived={}",
+            project.id,
+            archived
+        );
+    }
+
+    let json_response = SingleProjectResponse {
+        status: "success".to_string(),
+        data: ProjectData { project },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Deletes a project. Its todos are kept, outside any project.
+#[delete("/projects/{id}")]
+async fn delete_project_handler(
+    path: web::Path<String>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let project = find_project(&data, &scope.0, user.as_ref(), &path.into_inner()).await?;
+
+    let detached = projects::detach_todos(&data, &project, Utc::now()).await?;
+    data.projects
+        .delete(project.workspace_id.as_deref(), &project.id)
+        .await?;
+    log::info!(
+        "event=project_deleted project_id={} todos_detached={}",
+        project.id,
+        detached
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/projects/{id}/todos")]
+async fn project_todos_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<String>,
+    opts: QueryOptions,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let project = find_project(&data, &scope.0, user.as_ref(), &path.into_inner()).await?;
+
+    let options = ListOptions {
+        offset: opts.offset,
+        limit: opts.limit,
+        include_archived: opts.include_archived,
+        scope: scope.0,
+        visibility: sharing::visibility(&data, user.as_ref()).await?,
+        owner_id: None,
+        created_after: None,
+        created_before: None,
+        project_id: Some(project.id),
+        status: None,
+        assignee_id: None,
+        completed: None,
+        due_after: None,
+        due_before: None,
+        sort: TodoSort::CreatedAt,
+    };
+    let (todos, warnings) = decoding::collect(data.todos.list(&options)).await;
+    let todos = with_comment_counts(&data, todos?).await?;
+
+    let json_response = TodoListResponse {
+        status: "success".to_string(),
+        results: todos.len(),
+        links: urls::page_links(&req, Some(&opts), todos.len()),
+        todos: todos
+            .into_iter()
+            .map(|todo| TodoRepresentation::new(version, todo))
+            .collect(),
+        warnings,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// The project `id` in the request's workspace, or among the caller's
+/// personal projects outside one.
+async fn find_project(
+    data: &AppState,
+    scope: &TodoScope,
+    user: Option<&AuthUser>,
+    id: &str,
+) -> Result<Project, AppError> {
+    let owner_id = projects::owner(scope.workspace_id(), user)?;
+    projects::find(data, scope.workspace_id(), owner_id.as_deref(), id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Project with ID: {} not found", id)))
+}
+
+/// The caller's templates, oldest first.
+#[get("/templates")]
+async fn templates_list_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let templates = data.templates.list_for_owner(&user.id).await?;
+
+    let json_response = TemplateListResponse {
+        status: "success".to_string(),
+        results: templates.len(),
+        templates: templates.into_iter().map(TemplateSummary::new).collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[post("/templates")]
+async fn create_template_handler(
+    req: HttpRequest,
+    body: web::Json<CreateTemplateSchema>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    let name = body.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Template name must not be empty".to_string(),
+        ));
+    }
+    let title = data.sanitizer.title(body.title);
+    if title.trim().is_empty() {
+        return Err(AppError::BadRequest(
+            "Template title must not be empty".to_string(),
+        ));
+    }
+
+    let now = Utc::now();
+    let template = Template {
+        id: Uuid::new_v4().to_string(),
+        owner_id: user.id,
+        name: name.to_string(),
+        title,
+        content: data.sanitizer.content(body.content),
+        created_at: now,
+        updated_at: now,
+    };
+    data.templates.insert(&template).await?;
+
+    let location = urls::template(&req, &template.id);
+    let json_response = SingleTemplateResponse {
+        status: "success".to_string(),
+        data: TemplateData {
+            template: TemplateSummary::new(template),
+        },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+#[get("/templates/{id}")]
+async fn get_template_handler(
+    path: web::Path<String>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let template = find_template(&data, &user, &path.into_inner()).await?;
+
+    let json_response = SingleTemplateResponse {
+        status: "success".to_string(),
+        data: TemplateData {
+            template: TemplateSummary::new(template),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[delete("/templates/{id}")]
+async fn delete_template_handler(
+    path: web::Path<String>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let template = find_template(&data, &user, &path.into_inner()).await?;
+    data.templates.delete(&user.id, &template.id).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Creates a todo from one of the caller's templates, in the request's
+/// scope. Every `{{variable}}` the template uses needs a value.
+#[post("/templates/{id}/instantiate")]
+async fn instantiate_template_handler(
+    version: ApiVersion,
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Bytes,
+    scope: RequestScope,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body: InstantiateTemplateSchema = optional_json(&body)?.unwrap_or_default();
+    let template = find_template(&data, &user, &path.into_inner()).await?;
+
+    let todo = templates::instantiate(
+        &template,
+        &body,
+        user.id,
+        scope.0.workspace_id().map(str::to_string),
+        Utc::now(),
+    )?;
+    let id = todo.id.expect("instantiated todos are given an ID");
+    todos::create(&data, &todo).await?;
+
+    let json_response = SingleTodoResponse {
+        status: "success".to_string(),
+        data: TodoData {
+            todo: TodoRepresentation::new(version, todo),
+        },
+        links: urls::todo_links(&req, &id)?,
+    };
+
+    Ok(created(&req, urls::todo(&req, &id), json_response))
+}
+
+/// One of the caller's templates; other users' templates are not found.
+async fn find_template(data: &AppState, user: &AuthUser, id: &str) -> Result<Template, AppError> {
+    data.templates
+        .find_by_id(&user.id, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Template with ID: {} not found", id)))
+}
+
+#[get("/reminders")]
+async fn reminders_list_handler(
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let visibility = sharing::visibility(&data, user.as_ref()).await?;
+    let reminders: Vec<Reminder> = data
+        .todos
+        .pending_reminders(None)
+        .await?
+        .into_iter()
+        .filter(|todo| scope.0.contains(todo) && visibility.allows(todo))
+        .filter_map(|todo| {
+            Some(Reminder {
+                todo_id: todo.id?,
+                title: todo.title,
+                remind_at: todo.remind_at?,
+            })
+        })
+        .collect();
+
+    let json_response = ReminderListResponse {
+        status: "success".to_string(),
+        results: reminders.len(),
+        reminders,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Cancels a todo's pending reminder without touching the rest of the todo.
+#[delete("/reminders/{id}")]
+async fn cancel_reminder_handler(
+    path: web::Path<TodoId>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let mut todo =
+        sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Write).await?;
+
+    if todo.remind_at.is_none() || todo.reminder_sent_at.is_some() {
+        return Err(AppError::NotFound(format!(
+            "Todo with ID: {} has no pending reminder",
+            id
+        )));
+    }
+
+    todo.remind_at = None;
+    todo.updated_at = Some(Utc::now());
+    let event = DomainEvent::TodoUpdated {
+        id,
+        todo: Some(todo.clone()),
+        title: None,
+        actor_id: user.map(|user| user.id),
+    };
+    outbox::commit(&data, TodoWrite::Update(todo), vec![event]).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/todos/{id}")]
+async fn delete_todo_handler(
+    path: web::Path<TodoId>,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let todo = sharing::authorize(&data, &id, &scope.0, user.as_ref(), TodoAccess::Owner).await?;
+
+    let event = DomainEvent::TodoDeleted {
+        id,
+        actor_id: user.as_ref().map(|user| user.id.clone()),
+    };
+    outbox::commit(&data, TodoWrite::Delete(id), vec![event]).await?;
+    todos::delete_related(&data, std::slice::from_ref(&id)).await?;
+    data.undo
+        .record(
+            user.as_ref().map(|user| user.id.as_str()),
+            UndoAction::Delete,
+            vec![todo],
+        )
+        .await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Deletes either the todos listed in the JSON body or those matching the
+/// query filters. Refuses to run without one of them so a bare
+/// `DELETE /api/todos` can never wipe the table.
+#[delete("/todos")]
+async fn bulk_delete_todos_handler(
+    opts: web::Query<BulkDeleteQuery>,
+    body: web::Bytes,
+    scope: RequestScope,
+    user: Option<AuthUser>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body: Option<Vec<TodoId>> = optional_json(&body)?;
+    // Shares never grant deleting, so only the caller's own todos and
+    // those anyone may delete are picked.
+    let filter = TodoFilter {
+        completed: opts.completed,
+        created_before: opts.created_before,
+        scope: scope.0,
+        visibility: TodoVisibility::Caller {
+            user_id: user.as_ref().map(|user| user.id.clone()),
+            shared: Vec::new(),
+        },
+    };
+
+    let ids = match body {
+        Some(_) if !filter.is_empty() => {
+            return Err(AppError::BadRequest(
+                "Provide either a list of ids or filter parameters, not both".to_string(),
+            ));
+        }
+        Some(ids) => {
+            if ids.len() > MAX_BATCH_IDS {
+                return Err(AppError::BadRequest(format!(
+                    "At most {} ids can be deleted at once",
+                    MAX_BATCH_IDS
+                )));
+            }
+            ids
+        }
+        None if filter.is_empty() => {
+            return Err(AppError::BadRequest(
+                "Provide a list of ids or at least one filter parameter".to_string(),
+            ));
+        }
+        None => data.todos.find_ids(&filter).await?,
+    };
+    // Only the caller's own todos, and those anyone may delete, are
+    // deleted; the rest are left out as if they did not exist. A filter
+    // can pick any number, so they are looked up a batch at a time.
+    let mut deleted = Vec::new();
+    for batch in ids.chunks(MAX_BATCH_IDS) {
+        deleted.extend(
+            sharing::authorize_all(
+                &data,
+                batch,
+                &filter.scope,
+                user.as_ref(),
+                TodoAccess::Owner,
+            )
+            .await?,
+        );
+    }
+    let ids: Vec<TodoId> = deleted.iter().filter_map(|todo| todo.id).collect();
+
+    let dry_run = opts.dry_run.unwrap_or(false);
+    if !dry_run && !ids.is_empty() {
+        let actor_id = user.as_ref().map(|user| user.id.clone());
+        todos::delete_all(&data, &ids, actor_id).await?;
+        data.undo
+            .record(
+                user.as_ref().map(|user| user.id.as_str()),
+                UndoAction::Delete,
+                deleted,
+            )
+            .await;
+    }
+
+    let json_response = BulkDeleteResponse {
+        status: "success".to_string(),
+        dry_run,
+        deleted: ids.len(),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Reverses the caller's most recent delete, completion or bulk change of
+/// completion, if it was made within the undo window.
+#[post("/undo")]
+async fn undo_handler(
+    version: ApiVersion,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let Some((action, todos)) = undo::undo(&data, &user.id, Utc::now()).await? else {
+        return Err(AppError::NotFound("Nothing to undo".to_string()));
+    };
+
+    let json_response = UndoResponse {
+        status: "success".to_string(),
+        data: UndoData {
+            action,
+            todos: todos
+                .into_iter()
+                .map(|todo| TodoRepresentation::new(version, todo))
+                .collect(),
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Changes to the caller's todos after `since`, or all of them when it is
+/// left out. Continue from `nextToken`, at once while `hasMore` is set.
+/// Not found while the `offline_sync` feature is off.
+#[get("/sync")]
+async fn sync_pull_handler(
+    version: ApiVersion,
+    query: web::Query<SyncQuery>,
+    user: AuthUser,
+    scope: RequestScope,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    data.features.require(features::OFFLINE_SYNC)?;
+    let since = query.since.as_deref().map(sync::parse_token).transpose()?;
+    let limit = match query.limit {
+        Some(0) => return Err(AppError::BadRequest("limit must be at least 1".to_string())),
+        Some(limit) => limit.min(data.pagination.max_limit),
+        None => data.pagination.max_limit,
+    };
+
+    let pull = sync::pull(&data, &scope.0, &user, since, limit).await?;
+
+    let json_response = SyncPullResponse {
+        status: "success".to_string(),
+        results: pull.changes.len(),
+        changes: pull
+            .changes
+            .into_iter()
+            .map(|synced| SyncedTodoRepresentation::new(version, synced))
+            .collect(),
+        next_token: pull.next_token.to_string(),
+        has_more: pull.has_more,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Applies changes a client made offline. Each is applied, loses to a
+/// newer server change (`conflict`) or is `rejected`; the server's copy of
+/// the todo is returned either way. Not found while the `offline_sync`
+/// feature is off.
+#[post("/sync")]
+async fn sync_push_handler(
+    version: ApiVersion,
+    body: web::Json<PushSyncSchema>,
+    user: AuthUser,
+    scope: RequestScope,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    data.features.require(features::OFFLINE_SYNC)?;
+    let results = sync::push(&data, &scope.0, &user, &body.replica_id, &body.changes).await?;
+
+    let json_response = SyncPushResponse {
+        status: "success".to_string(),
+        results: results
+            .into_iter()
+            .map(|result| SyncPushResult {
+                id: result.current.id,
+                outcome: result.outcome,
+                reason: result.reason,
+                current: SyncedTodoRepresentation::new(version, result.current),
+            })
+            .collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Bulk-inserts `count` fake todos for load testing. Only built with the
+/// `seed` feature, which must never be enabled in production.
+#[cfg(feature = "seed")]
+#[post("/dev/seed")]
+async fn seed_todos_handler(
+    opts: web::Query<SeedQuery>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let count = opts.count.unwrap_or(DEFAULT_SEED_COUNT);
+    if count == 0 || count > MAX_SEED_TODOS {
+        return Err(AppError::BadRequest(format!(
+            "count must be between 1 and {}",
+            MAX_SEED_TODOS
+        )));
+    }
+
+    let todos = fixtures::todos(&mut rand::thread_rng(), count);
+    data.todos.insert_many(&todos).await?;
+    log::info!("event=todos_seeded count={}", count);
+
+    let json_response = SeedResponse {
+        status: "success".to_string(),
+        created: count,
+    };
+
+    Ok(HttpResponse::Created().json(json_response))
+}
+
+/// The fault rules of this instance; see [`crate::chaos`]. Only built with
+/// the `chaos` feature, which must never be enabled in production.
+#[cfg(feature = "chaos")]
+#[get("/dev/chaos")]
+async fn get_fault_rules_handler(
+    admin: AdminUser,
+    injector: web::Data<FaultInjector>,
+) -> Result<HttpResponse, AppError> {
+    log::info!("event=fault_rules_viewed user_id={}", admin.0.id);
+    Ok(fault_rules_response(&injector))
+}
+
+/// Replaces the fault rules of this instance.
+#[cfg(feature = "chaos")]
+#[put("/dev/chaos")]
+async fn set_fault_rules_handler(
+    admin: AdminUser,
+    body: web::Json<SetFaultRulesSchema>,
+    injector: web::Data<FaultInjector>,
+) -> Result<HttpResponse, AppError> {
+    let rules = body.into_inner().rules;
+    let count = rules.len();
+    injector.set(rules)?;
+    log::warn!(
+        "event=fault_rules_set user_id={} rules={}",
+        admin.0.id,
+        count
+    );
+
+    Ok(fault_rules_response(&injector))
+}
+
+/// Stops injecting faults on this instance.
+#[cfg(feature = "chaos")]
+#[delete("/dev/chaos")]
+async fn clear_fault_rules_handler(
+    admin: AdminUser,
+    injector: web::Data<FaultInjector>,
+) -> Result<HttpResponse, AppError> {
+    injector.set(Vec::new())?;
+    log::warn!("event=fault_rules_cleared user_id={}", admin.0.id);
+
+    Ok(fault_rules_response(&injector))
+}
+
+#[cfg(feature = "chaos")]
+fn fault_rules_response(injector: &FaultInjector) -> HttpResponse {
+    HttpResponse::Ok().json(FaultRulesResponse {
+        status: "success".to_string(),
+        data: FaultRulesData {
+            rules: injector.rules(),
+        },
+    })
+}
+
+async fn validate_webhook_url(url: &str) -> Result<(), AppError> {
+    webhooks::check_destination(url)
+        .await
+        .map_err(AppError::BadRequest)
+}
+
+fn validate_webhook_secret(secret: &str) -> Result<(), AppError> {
+    if secret.is_empty() {
+        return Err(AppError::BadRequest("secret must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+#[get("/webhooks")]
+async fn webhooks_list_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let webhooks = data.webhooks.list().await?;
+    log::info!("event=webhooks_viewed user_id={}", admin.0.id);
+
+    let json_response = WebhookListResponse {
+        status: "success".to_string(),
+        results: webhooks.len(),
+        webhooks,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[post("/webhooks")]
+async fn create_webhook_handler(
+    req: HttpRequest,
+    admin: AdminUser,
+    body: web::Json<CreateWebhookSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    validate_webhook_url(&body.url).await?;
+    validate_webhook_secret(&body.secret)?;
+
+    let datetime = Utc::now();
+    let webhook = Webhook {
+        id: Uuid::new_v4().to_string(),
+        url: body.url.clone(),
+        secret: body.secret.clone(),
+        events: body.events.clone().unwrap_or_default(),
+        active: true,
+        created_at: datetime,
+        updated_at: datetime,
+    };
+
+    data.webhooks.insert(&webhook).await?;
+    log::info!(
+        "event=webhook_created webhook_id={} user_id={}",
+        webhook.id,
+        admin.0.id
+    );
+
+    let location = urls::webhook(&req, &webhook.id);
+    let json_response = SingleWebhookResponse {
+        status: "success".to_string(),
+        data: WebhookData { webhook },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+/// Deliveries that failed every retry, newest first.
+#[get("/webhooks/dead-letters")]
+async fn dead_letters_list_handler(
+    admin: AdminUser,
+    opts: web::Query<DeadLetterQuery>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let limit = opts.limit.unwrap_or(50).min(MAX_DEAD_LETTERS);
+    let dead_letters = data.webhooks.list_dead_letters(limit).await?;
+    log::info!("event=dead_letters_viewed user_id={}", admin.0.id);
+
+    let json_response = DeadLetterListResponse {
+        status: "success".to_string(),
+        results: dead_letters.len(),
+        dead_letters,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[get("/webhooks/{id}")]
+async fn get_webhook_handler(
+    admin: AdminUser,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let webhook = data
+        .webhooks
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Webhook with ID: {} not found", id)))?;
+    log::info!(
+        "event=webhook_viewed webhook_id={} user_id={}",
+        webhook.id,
+        admin.0.id
+    );
+
+    let json_response = SingleWebhookResponse {
+        status: "success".to_string(),
+        data: WebhookData { webhook },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[patch("/webhooks/{id}")]
+async fn edit_webhook_handler(
+    admin: AdminUser,
+    path: web::Path<String>,
+    body: web::Json<UpdateWebhookSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    let mut webhook = data
+        .webhooks
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Webhook with ID: {} not found", id)))?;
+
+    if let Some(url) = &body.url {
+        validate_webhook_url(url).await?;
+        webhook.url = url.clone();
+    }
+    if let Some(secret) = &body.secret {
+        validate_webhook_secret(secret)?;
+        webhook.secret = secret.clone();
+    }
+    if let Some(events) = &body.events {
+        webhook.events = events.clone();
+    }
+    if let Some(active) = body.active {
+        webhook.active = active;
+    }
+    webhook.updated_at = Utc::now();
+
+    data.webhooks.update(&webhook).await?;
+    log::info!(
+        "event=webhook_updated webhook_id={} user_id={}",
+        webhook.id,
+        admin.0.id
+    );
+
+    let json_response = SingleWebhookResponse {
+        status: "success".to_string(),
+        data: WebhookData { webhook },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[delete("/webhooks/{id}")]
+async fn delete_webhook_handler(
+    admin: AdminUser,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+
+    if data.webhooks.find_by_id(&id).await?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Webhook with ID: {} not found",
+            id
+        )));
+    }
+
+    data.webhooks.delete(&id).await?;
+    log::info!(
+        "event=webhook_deleted webhook_id={} user_id={}",
+        id,
+        admin.0.id
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn validate_email(email: &str) -> Result<(), AppError> {
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() => Ok(()),
+        _ => Err(AppError::BadRequest(format!(
+            "'{}' is not a valid email address",
+            email
+        ))),
+    }
+}
+
+/// The address of the client making `req`, for per-client limits.
+/// `X-Forwarded-For` is only believed as far as it was written by the
+/// `TrustedProxies`: each proxy appends the address it was reached from,
+/// so it is read from the right up to the first hop that is not one of
+/// them. Anything left of that hop could have been sent by the client.
+fn client_address(req: &HttpRequest) -> String {
+    let Some(peer) = req.peer_addr().map(|addr| addr.ip()) else {
+        return "unknown".to_string();
+    };
+    let Some(proxies) = req.app_data::<TrustedProxies>() else {
+        return peer.to_string();
+    };
+
+    let forwarded: Vec<&str> = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    let mut client = peer;
+    for hop in forwarded.into_iter().rev() {
+        if !proxies.contains(client) {
+            break;
+        }
+        // A proxy would have written an address; stop at what it did not.
+        match hop.parse() {
+            Ok(hop) => client = hop,
+            Err(_) => break,
+        }
+    }
+    client.to_string()
+}
+
+/// The client making `req`, as noted on sessions.
+fn client_info(req: &HttpRequest) -> ClientInfo {
+    ClientInfo {
+        ip_address: client_address(req),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|agent| agent.chars().take(MAX_USER_AGENT_LENGTH).collect()),
+    }
+}
+
+fn validate_password(password: &str) -> Result<(), AppError> {
+    if password.chars().count() < auth::MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Password must be at least {} characters",
+            auth::MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+#[post("/auth/register")]
+async fn register_handler(
+    req: HttpRequest,
+    body: web::Json<RegisterUserSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_lowercase();
+    validate_email(&email)?;
+    validate_password(&body.password)?;
+
+    if data.users.find_by_email(&email).await?.is_some() {
+        return Err(AppError::Conflict(format!(
+            "An account with email '{}' already exists",
+            email
+        )));
+    }
+
+    let password_hash = web::block(move || auth::hash_password(&body.password))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    let now = Utc::now();
+    let user = User {
+        id: Uuid::new_v4().to_string(),
+        // Registering does not show the caller owns the address, so it
+        // never grants the admin role.
+        role: Role::User,
+        email,
+        password_hash,
+        display_name: None,
+        avatar_url: None,
+        created_at: now,
+        updated_at: now,
+    };
+    data.users.insert(&user).await?;
+    log::info!(
+        "event=user_registered user_id={} role={}",
+        user.id,
+        user.role.as_str()
+    );
+
+    let auth = sign_in(&data, &req, user, vec![auth::AMR_PASSWORD.to_string()]).await?;
+    Ok(auth_response(&data, HttpResponse::Created(), auth))
+}
+
+#[post("/auth/login")]
+async fn login_handler(
+    req: HttpRequest,
+    body: web::Json<LoginSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_lowercase();
+    let client = client_address(&req);
+    let invalid = || AppError::Unauthorized("Invalid email or password".to_string());
+    data.login_guard.check(&email, &client).await?;
+
+    // Unknown addresses count as failures too, so guessing at them gets
+    // the client locked out all the same.
+    let Some(user) = data.users.find_by_email(&email).await? else {
+        data.login_guard.record_failure(&email, &client).await?;
+        return Err(invalid());
+    };
+    let hash = user.password_hash.clone();
+    let valid = web::block(move || auth::verify_password(&body.password, &hash))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !valid {
+        data.login_guard.record_failure(&email, &client).await?;
+        return Err(invalid());
+    }
+    data.login_guard.record_success(&email).await?;
+
+    let amr = vec![auth::AMR_PASSWORD.to_string()];
+    if data.totp.is_enabled(&user.id).await? {
+        return Ok(HttpResponse::Ok().json(mfa_challenge(&data, &user, &amr)?));
+    }
+    let auth = sign_in(&data, &req, user, amr).await?;
+    Ok(auth_response(&data, HttpResponse::Ok(), auth))
+}
+
+/// Trades a refresh token for a new access token and refresh token. The
+/// old refresh token is spent; using it again revokes the whole login.
+#[post("/auth/refresh")]
+async fn refresh_handler(
+    req: HttpRequest,
+    body: web::Json<RefreshTokenSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let refresh_token = refresh_token(&data, &req, body.into_inner())?;
+    let (rotated, refresh_token) = data
+        .refresh_tokens
+        .rotate(&refresh_token, &client_info(&req))
+        .await?;
+    let user = data
+        .users
+        .find_by_id(&rotated.user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired refresh token".to_string()))?;
+
+    let auth = auth_data(&data, user, (rotated, refresh_token))?;
+    Ok(auth_response(&data, HttpResponse::Ok(), auth))
+}
+
+/// The caller's sessions, one per login that has not ended, with the
+/// device and address each was last used from.
+#[get("/auth/sessions")]
+async fn sessions_list_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let sessions: Vec<SessionSummary> = data
+        .refresh_tokens
+        .sessions(&user.id)
+        .await?
+        .into_iter()
+        .map(|session| SessionSummary {
+            current: user.session_id.as_deref() == Some(session.id.as_str()),
+            session,
+        })
+        .collect();
+
+    let json_response = SessionListResponse {
+        status: "success".to_string(),
+        results: sessions.len(),
+        sessions,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Ends one of the caller's sessions, e.g. on a lost device. Its refresh
+/// token stops working; access tokens issued in it lapse when they expire.
+#[delete("/auth/sessions/{id}")]
+async fn revoke_session_handler(
+    user: AuthUser,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    data.refresh_tokens
+        .revoke_session(&user.id, &path.into_inner())
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Revokes the refresh token and every token rotated from the same login.
+/// Access tokens already handed out stay valid until they expire, though
+/// in cookie sessions the browser is told to drop its cookies.
+#[post("/auth/logout")]
+async fn logout_handler(
+    req: HttpRequest,
+    body: web::Json<RefreshTokenSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let refresh_token = refresh_token(&data, &req, body.into_inner())?;
+    data.refresh_tokens.revoke(&refresh_token).await?;
+
+    let mut response = HttpResponse::NoContent();
+    for cookie in data.tokens.clear_session_cookies() {
+        response.cookie(cookie);
+    }
+    Ok(response.finish())
+}
+
+/// The refresh token in `body` or, in cookie sessions, in its cookie.
+fn refresh_token(
+    data: &AppState,
+    req: &HttpRequest,
+    body: RefreshTokenSchema,
+) -> Result<String, AppError> {
+    if let Some(token) = body.refresh_token {
+        return Ok(token);
+    }
+    data.tokens
+        .cookie_config()
+        .enabled
+        .then(|| req.cookie(auth::REFRESH_COOKIE))
+        .flatten()
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| AppError::BadRequest("refreshToken is required".to_string()))
+}
+
+/// A CSRF token for browsers in cookie sessions to send in the
+/// `X-CSRF-Token` header, set as the cookie it is checked against too.
+/// A token the browser already holds is handed back as is, so other open
+/// tabs keep working.
+#[get("/auth/csrf")]
+async fn csrf_token_handler(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    let csrf_token = req
+        .cookie(csrf::CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+        .unwrap_or_else(csrf::new_token);
+
+    HttpResponse::Ok()
+        .cookie(csrf::cookie(&csrf_token, data.tokens.cookie_config()))
+        .json(CsrfTokenResponse {
+            status: "success".to_string(),
+            data: CsrfTokenData { csrf_token },
+        })
+}
+
+/// Emails a password reset link to the account using `email`. The answer
+/// is the same whether or not there is one, so the endpoint cannot be
+/// used to find out which addresses have accounts.
+#[post("/auth/forgot-password")]
+async fn forgot_password_handler(
+    req: HttpRequest,
+    body: web::Json<ForgotPasswordSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let email = body.email.trim().to_lowercase();
+    validate_email(&email)?;
+    let client = client_address(&req);
+    data.password_resets.check_limit(&email, &client)?;
+
+    if let Some(user) = data.users.find_by_email(&email).await? {
+        let notification = data.password_resets.issue(&user).await?;
+        log::info!("event=password_reset_requested user_id={}", user.id);
+        // Sent in the background, so the response does not take longer
+        // when the account exists.
+        let notifier = data.notifier.clone();
+        tokio::spawn(async move {
+            if let Err(e) = notifier.notify(&notification).await {
+                log::warn!(
+                    "event=password_reset_notification_failed user_id={} error=\"{}\"",
+                    user.id,
+                    e
+                );
+            }
+        });
+    }
+
+    let json_response = GenericResponse {
+        status: "success".to_string(),
+        message: "If an account uses that address, a reset link is on its way".to_string(),
+    };
+
+    Ok(HttpResponse::Accepted().json(json_response))
+}
+
+/// Sets a new password with the token from a reset email. Each token
+/// works once.
+#[post("/auth/reset-password")]
+async fn reset_password_handler(
+    body: web::Json<ResetPasswordSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    validate_password(&body.password)?;
+
+    let user_id = data.password_resets.redeem(&body.token).await?;
+    let password_hash = web::block(move || auth::hash_password(&body.password))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+    data.users
+        .update_password(&user_id, &password_hash, Utc::now())
+        .await?;
+    log::info!("event=password_reset user_id={}", user_id);
+    // Logins made with the old password end with it.
+    data.refresh_tokens.end_all(&user_id).await?;
+    // Whoever reset the password owns the mailbox, so a lockout that kept
+    // them out of the account is lifted, and an admin address is now
+    // verified. A second factor set up before that may be someone else's,
+    // so it does not carry over to the admin account.
+    if let Some(user) = data.users.find_by_id(&user_id).await? {
+        data.login_guard.clear_account(&user.email).await?;
+        if user.role != Role::Admin && data.tokens.role_for_verified(&user.email) == Role::Admin {
+            data.totp.remove(&user.id).await?;
+            data.users
+                .update_role(&user.id, Role::Admin, Utc::now())
+                .await?;
+            log::info!("event=user_promoted user_id={} role=admin", user.id);
+        }
+    }
+
+    let json_response = GenericResponse {
+        status: "success".to_string(),
+        message: "Password has been reset".to_string(),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Moves an account to the address a confirmation token was emailed to.
+/// The token stops working once the account's email has changed since it
+/// was asked for, or the new address has been taken in the meantime.
+#[post("/auth/confirm-email")]
+async fn confirm_email_handler(
+    body: web::Json<ConfirmEmailSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let change = data.tokens.verify_email_change(&body.token)?;
+    let mut account = data
+        .users
+        .find_by_id(&change.user_id)
+        .await?
+        .filter(|account| account.email == change.previous)
+        .ok_or_else(|| AppError::BadRequest("Invalid or expired confirmation token".to_string()))?;
+    if data.users.find_by_email(&change.email).await?.is_some() {
+        return Err(AppError::Conflict(format!(
+            "An account with email '{}' already exists",
+            change.email
+        )));
+    }
+
+    account.email = change.email;
+    account.updated_at = Utc::now();
+    data.users.update_profile(&account).await?;
+    if let Some(settings) = data.notification_settings.find(&change.previous).await? {
+        data.notification_settings
+            .upsert(&NotificationSettings {
+                email: account.email.clone(),
+                ..settings
+            })
+            .await?;
+        data.notification_settings.delete(&change.previous).await?;
+    }
+    log::info!("event=user_email_changed user_id={}", account.id);
+
+    Ok(HttpResponse::Ok().json(profile_response(account)))
+}
+
+/// Second login step for accounts with two-factor authentication: the
+/// token from the first step, with a code from the authenticator app or
+/// a backup code. Wrong codes count towards a lockout like wrong passwords.
+#[post("/auth/login/totp")]
+async fn totp_login_handler(
+    req: HttpRequest,
+    body: web::Json<TotpLoginSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (user_id, mut amr) = data.tokens.verify_challenge(&body.mfa_token)?;
+    let user = data
+        .users
+        .find_by_id(&user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired login".to_string()))?;
+    let client = client_address(&req);
+    data.login_guard.check(&user.email, &client).await?;
+
+    if !data.totp.verify(&user.id, &body.code).await? {
+        data.login_guard
+            .record_failure(&user.email, &client)
+            .await?;
+        return Err(AppError::Unauthorized(
+            "Invalid two-factor code".to_string(),
+        ));
+    }
+    amr.push(auth::AMR_OTP.to_string());
+    log::info!("event=totp_login user_id={}", user.id);
+
+    let auth = sign_in(&data, &req, user, amr).await?;
+    Ok(auth_response(&data, HttpResponse::Ok(), auth))
+}
+
+/// Starts enrolling an authenticator app. `otpauthUri` is meant to be
+/// shown as a QR code; nothing changes at login until a code from the app
+/// is confirmed.
+#[post("/auth/totp/enroll")]
+async fn totp_enroll_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let account = data
+        .users
+        .find_by_id(&user.id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Unknown user".to_string()))?;
+    let enrollment = data.totp.enroll(&account).await?;
+
+    let json_response = TotpEnrollmentResponse {
+        status: "success".to_string(),
+        data: TotpEnrollmentData {
+            secret: enrollment.secret,
+            otpauth_uri: enrollment.uri,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Turns two-factor authentication on with a first code from the app.
+/// Returns the backup codes, which are not shown again.
+#[post("/auth/totp/confirm")]
+async fn totp_confirm_handler(
+    user: AuthUser,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let backup_codes = data.totp.confirm(&user.id, &body.code).await?;
+
+    let json_response = BackupCodesResponse {
+        status: "success".to_string(),
+        data: BackupCodesData { backup_codes },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Replaces the backup codes; the old ones stop working.
+#[post("/auth/totp/backup-codes")]
+async fn totp_backup_codes_handler(
+    user: AuthUser,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let backup_codes = data
+        .totp
+        .regenerate_backup_codes(&user.id, &body.code)
+        .await?;
+
+    let json_response = BackupCodesResponse {
+        status: "success".to_string(),
+        data: BackupCodesData { backup_codes },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Turns two-factor authentication off, given a current code.
+#[post("/auth/totp/disable")]
+async fn totp_disable_handler(
+    user: AuthUser,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    data.totp.disable(&user.id, &body.code).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Starts signing in with an external provider by redirecting to its
+/// consent page.
+#[get("/auth/oauth/{provider}/start")]
+async fn oauth_start_handler(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let provider = oauth_provider(&path)?;
+    let authorization = data.oauth.authorize(provider)?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, authorization.url))
+        .cookie(authorization.cookie)
+        .finish())
+}
+
+/// Where the provider sends the user back to. Links the account to a local
+/// user, creating one on first sign-in, and logs them in.
+#[get("/auth/oauth/{provider}/callback")]
+async fn oauth_callback_handler(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<OAuthCallbackQuery>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let provider = oauth_provider(&path)?;
+    let query = query.into_inner();
+    if let Some(error) = query.error {
+        return Err(AppError::Unauthorized(format!(
+            "Sign-in with {} was not completed: {}",
+            provider, error
+        )));
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest(
+            "code and state are required".to_string(),
+        ));
+    };
+
+    let verifier = data.oauth.verifier(provider, &req, &state)?;
+    let account = data.oauth.exchange(provider, &code, &verifier).await?;
+    let user = oauth::resolve_user(&data, &account).await?;
+    log::info!(
+        "event=oauth_login user_id={} provider={}",
+        user.id,
+        provider
+    );
+
+    let amr = vec![auth::AMR_FEDERATED.to_string()];
+    let mut response = HttpResponse::Ok();
+    response.cookie(data.oauth.clear_cookie(provider));
+    if data.totp.is_enabled(&user.id).await? {
+        return Ok(response.json(mfa_challenge(&data, &user, &amr)?));
+    }
+    let auth = sign_in(&data, &req, user, amr).await?;
+    Ok(auth_response(&data, response, auth))
+}
+
+fn oauth_provider(name: &str) -> Result<OAuthProvider, AppError> {
+    name.parse()
+        .map_err(|_| AppError::NotFound(format!("Unknown sign-in provider '{}'", name)))
+}
+
+/// Starts a session for `user`, who authenticated with the `amr` methods:
+/// a new refresh token family and an access token.
+async fn sign_in(
+    data: &AppState,
+    req: &HttpRequest,
+    user: User,
+    amr: Vec<String>,
+) -> Result<AuthData, AppError> {
+    let refresh = data
+        .refresh_tokens
+        .issue(&user.id, &amr, &client_info(req))
+        .await?;
+    auth_data(data, user, refresh)
+}
+
+/// `response` handing out the tokens in `auth`, also as cookies in cookie
+/// sessions.
+fn auth_response(
+    data: &AppState,
+    mut response: HttpResponseBuilder,
+    auth: AuthData,
+) -> HttpResponse {
+    for cookie in data.tokens.session_cookies(&auth) {
+        response.cookie(cookie);
+    }
+    response.json(AuthResponse {
+        status: "success".to_string(),
+        data: auth,
+    })
+}
+
+/// Stands in for the tokens while `user`, who passed the `amr` methods,
+/// still has to give their second factor.
+fn mfa_challenge(
+    data: &AppState,
+    user: &User,
+    amr: &[String],
+) -> Result<MfaChallengeResponse, AppError> {
+    let (mfa_token, expires_at) = data.tokens.issue_challenge(user, amr)?;
+    Ok(MfaChallengeResponse {
+        status: "success".to_string(),
+        data: MfaChallengeData {
+            mfa_required: true,
+            mfa_token,
+            expires_at,
+        },
+    })
+}
+
+/// An access token for `user` alongside the given refresh token, as
+/// stored and its value.
+fn auth_data(
+    data: &AppState,
+    user: User,
+    (refresh, refresh_token): (RefreshToken, String),
+) -> Result<AuthData, AppError> {
+    let (token, expires_at) = data.tokens.issue(&user, &refresh)?;
+    Ok(AuthData {
+        token,
+        expires_at,
+        refresh_token,
+        refresh_expires_at: refresh.expires_at,
+        user,
+    })
+}
+
+/// System-level counters for operators: todo and user totals and database
+/// latency per repository operation.
+#[get("/admin/stats")]
+async fn admin_stats_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    log::info!("event=admin_stats_viewed user_id={}", admin.0.id);
+
+    let Some(snapshot) = data.stats.snapshot() else {
+        data.stats.refresh().await;
+        return Err(AppError::ServiceUnavailable(
+            "Statistics are still being computed, try again shortly".to_string(),
+        ));
+    };
+
+    let mut unowned = 0;
+    let mut per_user: Vec<OwnerTodoCount> = Vec::new();
+    for (owner_id, todos) in data.todos.owner_counts().await? {
+        match owner_id {
+            Some(user_id) => per_user.push(OwnerTodoCount { user_id, todos }),
+            None => unowned = todos,
+        }
+    }
+    per_user.sort_by_key(|count| std::cmp::Reverse(count.todos));
+
+    let millis = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+    let queries = data
+        .query_metrics
+        .snapshot()
+        .into_iter()
+        .map(|operation| QueryLatency {
+            operation: operation.operation.to_string(),
+            calls: operation.calls,
+            errors: operation.errors,
+            p50_ms: millis(operation.p50),
+            p95_ms: millis(operation.p95),
+            p99_ms: millis(operation.p99),
+            max_ms: millis(operation.max),
+        })
+        .collect();
+    let lookups = data.query_metrics.lookups();
+    let concurrency = data
+        .concurrency
+        .snapshot()
+        .into_iter()
+        .map(|limit| ConcurrencyStats {
+            group: limit.name.to_string(),
+            limit: limit.limit,
+            in_flight: limit.in_flight,
+            peak: limit.peak,
+            admitted: limit.admitted,
+            rejected: limit.rejected,
+        })
+        .collect();
+
+    let json_response = AdminStatsResponse {
+        status: "success".to_string(),
+        data: AdminStatsData {
+            generated_at: snapshot.generated_at,
+            todos: AdminTodoStats {
+                total: snapshot.total(),
+                open: snapshot.open,
+                completed: snapshot.completed,
+                overdue: snapshot.overdue,
+                archived: snapshot.archived,
+                per_user,
+                unowned,
+            },
+            users: AdminUserStats {
+                total: data.users.count().await?,
+            },
+            database: AdminDatabaseStats {
+                queries,
+                in_flight: data.query_metrics.in_flight(),
+                lookups: LookupStats {
+                    queried: lookups.queried,
+                    shared: lookups.shared,
+                },
+                cluster: data.query_metrics.cluster().map(|cluster| ClusterStats {
+                    nodes: cluster.nodes,
+                    nodes_up: cluster.nodes_up,
+                    queries: cluster.queries,
+                    errors: cluster.errors,
+                    retries: cluster.retries,
+                }),
+            },
+            concurrency,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Lifts the lockout of an account, of a client address, or both, and
+/// forgets their failed logins.
+#[delete("/admin/lockouts")]
+async fn clear_lockout_handler(
+    admin: AdminUser,
+    query: web::Query<ClearLockoutQuery>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let query = query.into_inner();
+    if query.email.is_none() && query.client.is_none() {
+        return Err(AppError::BadRequest(
+            "Give the email or the client address to unlock".to_string(),
+        ));
+    }
+    if let Some(email) = query.email {
+        data.login_guard
+            .clear_account(&email.trim().to_lowercase())
+            .await?;
+    }
+    if let Some(client) = query.client {
+        data.login_guard.clear_client(client.trim()).await?;
+    }
+    log::info!("event=lockout_cleared user_id={}", admin.0.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Queues a re-encryption of all todo content with the active key, to
+/// run after a key rotation or after turning encryption on. The previous
+/// key can be removed once the job has finished.
+#[post("/admin/encryption/reencrypt")]
+async fn reencrypt_content_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let job_id = data.encryption.reencrypt().await?;
+    log::info!(
+        "event=content_reencryption_queued user_id={} job_id={}",
+        admin.0.id,
+        job_id
+    );
+
+    let json_response = ReencryptionResponse {
+        status: "success".to_string(),
+        data: ReencryptionData {
+            job_id,
+            key_id: data.encryption.active_key().unwrap_or_default().to_string(),
+        },
+    };
+
+    Ok(HttpResponse::Accepted().json(json_response))
+}
+
+/// What the next retention purge would remove, per workspace, without
+/// removing anything.
+#[get("/admin/retention/preview")]
+async fn retention_preview_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let report = data.retention.preview().await?;
+    log::info!(
+        "event=retention_previewed user_id={} deleted_todos={} activity={}",
+        admin.0.id,
+        report.deleted_todos,
+        report.activity
+    );
+
+    let json_response = RetentionPreviewResponse {
+        status: "success".to_string(),
+        data: report,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// The cron schedules of periodic jobs, with when each last ran and how
+/// that run went.
+#[get("/admin/schedules")]
+async fn list_schedules_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    log::info!("event=schedules_viewed user_id={}", admin.0.id);
+    let schedules = data.scheduler.statuses().await?;
+
+    let json_response = ScheduleListResponse {
+        status: "success".to_string(),
+        results: schedules.len(),
+        schedules,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// The feature flags with a default or a stored value, and where each
+/// one's value comes from.
+#[get("/admin/features")]
+async fn list_features_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    log::info!("event=features_viewed user_id={}", admin.0.id);
+    let flags = data.features.list();
+
+    let json_response = FeatureFlagListResponse {
+        status: "success".to_string(),
+        results: flags.len(),
+        flags,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Turns a feature flag on or off on every instance, overriding its default.
+#[put("/admin/features/{name}")]
+async fn set_feature_handler(
+    admin: AdminUser,
+    path: web::Path<String>,
+    body: web::Json<SetFeatureFlagSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let name = path.into_inner();
+    features::validate_name(&name)?;
+    let flag = data.features.set(&name, body.enabled).await?;
+    log::info!(
+        "event=feature_flag_set user_id={} name={} enabled={}",
+        admin.0.id,
+        name,
+        body.enabled
+    );
+
+    let json_response = SingleFeatureFlagResponse {
+        status: "success".to_string(),
+        data: FeatureFlagData { flag },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Returns a feature flag to its default from `FEATURE_FLAGS`.
+#[delete("/admin/features/{name}")]
+async fn clear_feature_handler(
+    admin: AdminUser,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let name = path.into_inner();
+    let flag = data.features.clear(&name).await?;
+    log::info!(
+        "event=feature_flag_cleared user_id={} name={}",
+        admin.0.id,
+        name
+    );
+
+    let json_response = SingleFeatureFlagResponse {
+        status: "success".to_string(),
+        data: FeatureFlagData { flag },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Longest message maintenance can be started with.
+const MAX_MAINTENANCE_MESSAGE_LENGTH: usize = 500;
+
+/// Longest clients can be told to wait during maintenance: a day.
+const MAX_MAINTENANCE_RETRY_AFTER_SECS: u32 = 24 * 60 * 60;
+
+/// The maintenance under way, if any.
+#[get("/admin/maintenance")]
+async fn get_maintenance_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    log::info!("event=maintenance_viewed user_id={}", admin.0.id);
+    Ok(maintenance_response(&data.maintenance))
+}
+
+/// Puts the API in maintenance on every instance: requests are refused
+/// with a 503, except reads the cache can answer while `allowReads` is
+/// left on.
+#[put("/admin/maintenance")]
+async fn start_maintenance_handler(
+    admin: AdminUser,
+    body: web::Json<StartMaintenanceSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let mut body = body.into_inner();
+    if let Some(message) = &body.message {
+        let message = message.trim();
+        if message.is_empty() || message.chars().count() > MAX_MAINTENANCE_MESSAGE_LENGTH {
+            return Err(AppError::BadRequest(format!(
+                "The maintenance message must be 1 to {} characters",
+                MAX_MAINTENANCE_MESSAGE_LENGTH
+            )));
+        }
+        body.message = Some(message.to_string());
+    }
+    if body
+        .retry_after_secs
+        .is_some_and(|secs| secs == 0 || secs > MAX_MAINTENANCE_RETRY_AFTER_SECS)
+    {
+        return Err(AppError::BadRequest(format!(
+            "retryAfterSecs must be between 1 and {}",
+            MAX_MAINTENANCE_RETRY_AFTER_SECS
+        )));
+    }
+
+    let maintenance = data.maintenance.enable(&admin.0.id, body).await?;
+    log::warn!(
+        "event=maintenance_started user_id={} allow_reads={} retry_after_secs={}",
+        admin.0.id,
+        maintenance.allow_reads,
+        maintenance.retry_after_secs
+    );
+
+    Ok(maintenance_response(&data.maintenance))
+}
+
+/// Ends maintenance on every instance.
+#[delete("/admin/maintenance")]
+async fn end_maintenance_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    data.maintenance.disable().await?;
+    log::warn!("event=maintenance_ended user_id={}", admin.0.id);
+
+    Ok(maintenance_response(&data.maintenance))
+}
+
+fn maintenance_response(maintenance: &MaintenanceMode) -> HttpResponse {
+    HttpResponse::Ok().json(MaintenanceResponse {
+        status: "success".to_string(),
+        data: MaintenanceData {
+            maintenance: maintenance.current(),
+        },
+    })
+}
+
+/// What is needed to triage this instance: its build, configuration,
+/// uptime, migration level and view of the Scylla cluster. Reports what it
+/// can when the database cannot be reached.
+#[get("/debug/info")]
+async fn debug_info_handler(
+    admin: AdminUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    log::info!("event=debug_info_viewed user_id={}", admin.0.id);
+    let diagnostics = &data.diagnostics;
+
+    let migrations = match diagnostics.schema().await {
+        Ok(schema) => Some(MigrationLevel {
+            latest: schema.latest,
+            pending: schema
+                .pending
+                .into_iter()
+                .map(|(version, _)| version)
+                .collect(),
+            unknown: schema.unknown,
+        }),
+        Err(e) => {
+            log::warn!("event=schema_status_failed error=\"{}\"", e);
+            None
+        }
+    };
+    let cluster = data.query_metrics.nodes().map(|nodes| {
+        nodes
+            .into_iter()
+            .map(|node| ClusterNode {
+                address: node.address,
+                datacenter: node.datacenter,
+                rack: node.rack,
+                up: node.up,
+            })
+            .collect()
+    });
+
+    let json_response = DebugInfoResponse {
+        status: "success".to_string(),
+        data: DebugInfoData {
+            version: build_info::VERSION.to_string(),
+            commit: build_info::commit().map(str::to_string),
+            features: build_info::features()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            started_at: diagnostics.started_at(),
+            uptime_seconds: diagnostics.uptime().as_secs(),
+            config: diagnostics
+                .config()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            migrations,
+            cluster,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Returns the opt-ins for the caller's address; an address without any is
+/// opted out of everything. Service clients, which have no address, get a 404.
+#[get("/notifications/settings")]
+async fn get_notification_settings_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let email = find_account(&data, &user.id).await?.email;
+
+    let settings = data
+        .notification_settings
+        .find(&email)
+        .await?
+        .unwrap_or_else(|| NotificationSettings {
+            email,
+            reminders: false,
+            digest: false,
+            updated_at: Utc::now(),
+        });
+
+    let json_response = NotificationSettingsResponse {
+        status: "success".to_string(),
+        settings,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[put("/notifications/settings")]
+async fn update_notification_settings_handler(
+    user: AuthUser,
+    body: web::Json<UpdateNotificationSettingsSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let email = find_account(&data, &user.id).await?.email;
+
+    let existing = data.notification_settings.find(&email).await?;
+    let settings = updated_notification_settings(email, existing.as_ref(), &body);
+
+    data.notification_settings.upsert(&settings).await?;
+
+    let json_response = NotificationSettingsResponse {
+        status: "success".to_string(),
+        settings,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// `update` applied to an address's opt-ins; those it leaves out keep their
+/// value, or are off when the address has none yet.
+fn updated_notification_settings(
+    email: String,
+    existing: Option<&NotificationSettings>,
+    update: &UpdateNotificationSettingsSchema,
+) -> NotificationSettings {
+    NotificationSettings {
+        reminders: update
+            .reminders
+            .or(existing.map(|settings| settings.reminders))
+            .unwrap_or(false),
+        digest: update
+            .digest
+            .or(existing.map(|settings| settings.digest))
+            .unwrap_or(false),
+        email,
+        updated_at: Utc::now(),
+    }
+}
+
+/// Sends a test notification to the caller's address through the
+/// configured channels.
+#[post("/notifications/test")]
+async fn test_notification_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let email = find_account(&data, &user.id).await?.email;
+
+    data.notifier.notify(&Notification::test(&email)).await?;
+
+    let json_response = GenericResponse {
+        status: "success".to_string(),
+        message: format!("Test notification sent to {}", email),
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// The caller's account. Service clients, which have none, get a 404.
+#[get("/me")]
+async fn get_profile_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let account = find_account(&data, &user.id).await?;
+    Ok(HttpResponse::Ok().json(profile_response(account)))
+}
+
+/// Changes the caller's display name or avatar. A new email address must
+/// be free, as when registering, and is only switched to once confirmed
+/// with the token emailed to it; see `POST /auth/confirm-email`.
+/// Notification opt-ins move with it.
+#[patch("/me")]
+async fn update_profile_handler(
+    user: AuthUser,
+    body: web::Json<UpdateProfileSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    let mut account = find_account(&data, &user.id).await?;
+
+    let mut new_email = None;
+    if let Some(email) = body.email {
+        let email = email.trim().to_lowercase();
+        validate_email(&email)?;
+        if email != account.email {
+            if data.users.find_by_email(&email).await?.is_some() {
+                return Err(AppError::Conflict(format!(
+                    "An account with email '{}' already exists",
+                    email
+                )));
+            }
+            new_email = Some(email);
+        }
+    }
+    if let Some(display_name) = body.display_name {
+        let display_name = display_name.trim();
+        if display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+            return Err(AppError::BadRequest(format!(
+                "displayName must be at most {} characters",
+                MAX_DISPLAY_NAME_LENGTH
+            )));
+        }
+        account.display_name = Some(display_name.to_string()).filter(|name| !name.is_empty());
+    }
+    if let Some(avatar_url) = body.avatar_url {
+        let avatar_url = avatar_url.trim();
+        if !avatar_url.is_empty() {
+            validate_avatar_url(avatar_url)?;
+        }
+        account.avatar_url = Some(avatar_url.to_string()).filter(|url| !url.is_empty());
+    }
+    account.updated_at = Utc::now();
+    data.users.update_profile(&account).await?;
+
+    if let Some(email) = new_email {
+        let token = data.tokens.issue_email_change(&account, &email)?;
+        let notification =
+            Notification::email_change(&email, &token, auth::EMAIL_CHANGE_TTL.as_secs() / 3600);
+        data.notifier.notify(&notification).await?;
+        log::info!("event=user_email_change_requested user_id={}", account.id);
+    }
+
+    Ok(HttpResponse::Ok().json(profile_response(account)))
+}
+
+/// Deletes the caller's account and erases their data, as
+/// [`accounts::erase`] describes. Refused with a 409 while they are the
+/// last owner of a workspace others belong to.
+#[delete("/me")]
+async fn delete_profile_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let account = find_account(&data, &user.id).await?;
+    accounts::erase(&data, &account).await?;
+
+    let mut response = HttpResponse::NoContent();
+    for cookie in data.tokens.clear_session_cookies() {
+        response.cookie(cookie);
+    }
+    Ok(response.finish())
+}
+
+async fn find_account(data: &AppState, id: &str) -> Result<User, AppError> {
+    data.users
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User with ID: {} not found", id)))
+}
+
+fn profile_response(user: User) -> ProfileResponse {
+    ProfileResponse {
+        status: "success".to_string(),
+        data: ProfileData { user },
+    }
+}
+
+fn validate_avatar_url(url: &str) -> Result<(), AppError> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(
+            "avatarUrl must be an http:// or https:// URL".to_string(),
+        ))
+    }
+}
+
+/// Queues an export of everything kept about the caller. Poll the job at
+/// the returned `Location`, then download the export from `GET /me/export`.
+#[post("/me/export")]
+async fn start_export_handler(
+    req: HttpRequest,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let account = find_account(&data, &user.id).await?;
+    let job_id = data.exports.start(&account.id).await?;
+    log::info!(
+        "event=account_export_queued user_id={} job_id={}",
+        account.id,
+        job_id
+    );
+
+    let record = data.exports.status(&account.id, &job_id).await?;
+    Ok(HttpResponse::Accepted()
+        .insert_header((header::LOCATION, urls::route(&req, "export", &[&job_id])?))
+        .json(export_response(record)))
+}
+
+/// Progress of one of the caller's exports.
+#[get("/me/export/{id}", name = "export")]
+async fn export_status_handler(
+    path: web::Path<String>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let record = data.exports.status(&user.id, &path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(export_response(record)))
+}
+
+/// The caller's latest finished export, as a JSON file.
+#[get("/me/export")]
+async fn download_export_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (info, contents) = data.exports.open(&user.id).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(exports::CONTENT_TYPE)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(exports::FILE_NAME.to_string())],
+        })
+        .no_chunking(info.size)
+        .streaming(contents))
+}
+
+fn export_response(record: JobRecord) -> ExportResponse {
+    ExportResponse {
+        status: "success".to_string(),
+        data: ExportData {
+            job_id: record.id,
+            status: record.status,
+            error: record.last_error,
+            requested_at: record.created_at,
+            updated_at: record.updated_at,
+        },
+    }
+}
+
+/// The caller's settings; defaults until they first save any. Notification
+/// opt-ins are those of the caller's address, as
+/// `/notifications/settings` shows them.
+#[get("/me/settings")]
+async fn get_user_settings_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let settings = data
+        .user_settings
+        .find(&user.id)
+        .await?
+        .unwrap_or_else(|| UserSettings::defaults(user.id.clone()));
+    let notifications = match data.users.find_by_id(&user.id).await? {
+        Some(account) => data.notification_settings.find(&account.email).await?,
+        None => None,
+    };
+
+    Ok(HttpResponse::Ok().json(user_settings_response(settings, notifications)))
+}
+
+#[patch("/me/settings")]
+async fn update_user_settings_handler(
+    user: AuthUser,
+    body: web::Json<UpdateUserSettingsSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let body = body.into_inner();
+    if body.page_size == Some(0) {
+        return Err(AppError::BadRequest(
+            "pageSize must be at least 1".to_string(),
+        ));
+    }
+    // Opt-ins belong to an address, which service clients do not have.
+    let account = data.users.find_by_id(&user.id).await?;
+    if body.notifications.is_some() && account.is_none() {
+        return Err(AppError::BadRequest(
+            "Only user accounts have notification settings".to_string(),
+        ));
+    }
+
+    let mut settings = data
+        .user_settings
+        .find(&user.id)
+        .await?
+        .unwrap_or_else(|| UserSettings::defaults(user.id));
+    if let Some(timezone) = body.timezone {
+        settings.timezone = timezone;
+    }
+    if let Some(page_size) = body.page_size {
+        settings.page_size = Some(page_size.min(data.pagination.max_limit));
+    }
+    if let Some(default_sort) = body.default_sort {
+        settings.default_sort = Some(default_sort);
+    }
+    settings.updated_at = Some(Utc::now());
+    data.user_settings.upsert(&settings).await?;
+
+    let notifications = match account {
+        Some(account) => {
+            let existing = data.notification_settings.find(&account.email).await?;
+            match body.notifications {
+                Some(update) => {
+                    let updated =
+                        updated_notification_settings(account.email, existing.as_ref(), &update);
+                    data.notification_settings.upsert(&updated).await?;
+                    Some(updated)
+                }
+                None => existing,
+            }
+        }
+        None => None,
+    };
+
+    Ok(HttpResponse::Ok().json(user_settings_response(settings, notifications)))
+}
+
+/// Addresses without notification settings are opted out of everything.
+fn user_settings_response(
+    settings: UserSettings,
+    notifications: Option<NotificationSettings>,
+) -> UserSettingsResponse {
+    UserSettingsResponse {
+        status: "success".to_string(),
+        settings: UserSettingsData {
+            notifications: NotificationOptIns {
+                reminders: notifications.as_ref().is_some_and(|n| n.reminders),
+                digest: notifications.as_ref().is_some_and(|n| n.digest),
+            },
+            settings,
+        },
+    }
+}
+
+#[get("/workspaces")]
+async fn workspaces_list_handler(
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let workspaces = data.workspaces.list_for_user(&user.id).await?;
+
+    let json_response = WorkspaceListResponse {
+        status: "success".to_string(),
+        results: workspaces.len(),
+        workspaces,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Creates a workspace with the caller as its owner.
+#[post("/workspaces")]
+async fn create_workspace_handler(
+    req: HttpRequest,
+    body: web::Json<CreateWorkspaceSchema>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let name = validate_workspace_name(&body.name)?;
+
+    let now = Utc::now();
+    let workspace = Workspace {
+        id: Uuid::new_v4().to_string(),
+        name,
+        created_at: now,
+        updated_at: now,
+    };
+    data.workspaces.insert(&workspace).await?;
+    data.workspaces
+        .upsert_member(&WorkspaceMember {
+            workspace_id: workspace.id.clone(),
+            user_id: user.id.clone(),
+            role: WorkspaceRole::Owner,
+            joined_at: now,
+        })
+        .await?;
+    log::info!(
+        "event=workspace_created workspace_id={} owner_id={}",
+        workspace.id,
+        user.id
+    );
+
+    let location = urls::workspace(&req, &workspace.id);
+    let json_response = SingleWorkspaceResponse {
+        status: "success".to_string(),
+        data: WorkspaceData { workspace },
+    };
+
+    Ok(created(&req, location, json_response))
+}
+
+#[get("/workspaces/{id}")]
+async fn get_workspace_handler(
+    path: web::Path<String>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    workspaces::require_member(data.workspaces.as_ref(), &id, &user).await?;
+
+    let workspace = find_workspace(&data, &id).await?;
+    let json_response = SingleWorkspaceResponse {
+        status: "success".to_string(),
+        data: WorkspaceData { workspace },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+#[patch("/workspaces/{id}")]
+async fn edit_workspace_handler(
+    path: web::Path<String>,
+    body: web::Json<UpdateWorkspaceSchema>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    workspaces::require_owner(data.workspaces.as_ref(), &id, &user).await?;
+
+    let mut workspace = find_workspace(&data, &id).await?;
+    if let Some(name) = &body.name {
+        workspace.name = validate_workspace_name(name)?;
+    }
+    workspace.updated_at = Utc::now();
+    data.workspaces.update(&workspace).await?;
+
+    let json_response = SingleWorkspaceResponse {
+        status: "success".to_string(),
+        data: WorkspaceData { workspace },
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Deletes a workspace together with its todos and memberships.
+#[delete("/workspaces/{id}")]
+async fn delete_workspace_handler(
+    path: web::Path<String>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    workspaces::require_owner(data.workspaces.as_ref(), &id, &user).await?;
+
+    let todo_ids = data
+        .todos
+        .find_ids(&TodoFilter {
+            scope: TodoScope::Workspace(id.clone()),
+            ..TodoFilter::default()
+        })
+        .await?;
+    if !todo_ids.is_empty() {
+        todos::delete_all(&data, &todo_ids, Some(user.id.clone())).await?;
+    }
+    data.workspaces.delete(&id).await?;
+    log::info!(
+        "event=workspace_deleted workspace_id={} todos_deleted={}",
+        id,
+        todo_ids.len()
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/workspaces/{id}/members")]
+async fn workspace_members_list_handler(
+    path: web::Path<String>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    workspaces::require_member(data.workspaces.as_ref(), &id, &user).await?;
+
+    let members = data.workspaces.list_members(&id).await?;
+    let json_response = WorkspaceMemberListResponse {
+        status: "success".to_string(),
+        results: members.len(),
+        members,
+    };
+
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Adds a registered user to the workspace by email, or changes the role of
+/// an existing member.
+#[post("/workspaces/{id}/members")]
+async fn add_workspace_member_handler(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<AddMemberSchema>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    workspaces::require_owner(data.workspaces.as_ref(), &id, &user).await?;
+
+    let email = body.email.trim().to_lowercase();
+    let invitee = data
+        .users
+        .find_by_email(&email)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No account with email '{}'", email)))?;
+    let role = body.role.unwrap_or(WorkspaceRole::Member);
+
+    let existing = data.workspaces.find_member(&id, &invitee.id).await?;
+    if existing.as_ref().map(|member| member.role) == Some(WorkspaceRole::Owner)
+        && role != WorkspaceRole::Owner
+    {
+        ensure_other_owner(&data, &id, &invitee.id).await?;
+    }
+
+    let is_new = existing.is_none();
+    let member = WorkspaceMember {
+        workspace_id: id,
+        user_id: invitee.id,
+        role,
+        joined_at: existing.map_or_else(Utc::now, |member| member.joined_at),
+    };
+    data.workspaces.upsert_member(&member).await?;
+
+    let location = urls::workspace_member(&req, &member.workspace_id, &member.user_id);
+    let json_response = SingleWorkspaceMemberResponse {
+        status: "success".to_string(),
+        member,
+    };
+
+    if is_new {
+        Ok(created(&req, location, json_response))
+    } else {
+        Ok(HttpResponse::Ok().json(json_response))
+    }
+}
+
+/// Removes a member. Owners can remove anyone; members can only leave.
+#[delete("/workspaces/{id}/members/{user_id}")]
+async fn remove_workspace_member_handler(
+    path: web::Path<(String, String)>,
+    user: AuthUser,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (id, member_id) = path.into_inner();
+    if member_id == user.id {
+        workspaces::require_member(data.workspaces.as_ref(), &id, &user).await?;
+    } else {
+        workspaces::require_owner(data.workspaces.as_ref(), &id, &user).await?;
+    }
+
+    let member = data
+        .workspaces
+        .find_member(&id, &member_id)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "User with ID: {} is not a member of this workspace",
+                member_id
+            ))
+        })?;
+    if member.role == WorkspaceRole::Owner {
+        ensure_other_owner(&data, &id, &member_id).await?;
+    }
+
+    data.workspaces.remove_member(&id, &member_id).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn find_workspace(data: &AppState, id: &str) -> Result<Workspace, AppError> {
+    data.workspaces
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Workspace with ID: {} not found", id)))
+}
+
+fn validate_workspace_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Workspace name must not be empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Refuses to demote or remove `user_id` if they are the workspace's only owner.
+async fn ensure_other_owner(data: &AppState, id: &str, user_id: &str) -> Result<(), AppError> {
+    let other_owner = data
+        .workspaces
+        .list_members(id)
+        .await?
+        .iter()
+        .any(|member| member.role == WorkspaceRole::Owner && member.user_id != user_id);
+    if !other_owner {
+        return Err(AppError::Conflict(
+            "A workspace must keep at least one owner".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reports malformed path segments, such as a todo ID that is not a UUID,
+/// as a JSON 400 like every other validation failure.
+fn path_error(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    let message = match err {
+        PathError::Deserialize(e) => e.to_string(),
+        err => err.to_string(),
+    };
+    AppError::BadRequest(message).into()
+}
+
+/// Reads an optional JSON body: `None` when the request has none, a 400
+/// when it has one that does not parse. `Option<web::Json<_>>` would take
+/// a malformed body for a missing one.
+fn optional_json<T: DeserializeOwned>(body: &[u8]) -> Result<Option<T>, AppError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    serde_json::from_slice(body)
+        .map(Some)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JSON body: {}", e)))
+}
+
+/// A 201 pointing at the new resource. Clients that send
+/// `Prefer: return=minimal` get the headers only.
+fn created(req: &HttpRequest, location: String, body: impl Serialize) -> HttpResponse {
+    let mut response = HttpResponse::Created();
+    response.insert_header((header::LOCATION, location));
+    if prefers_minimal(req) {
+        return response
+            .insert_header((PREFERENCE_APPLIED, "return=minimal"))
+            .finish();
+    }
+    response.json(body)
+}
+
+/// A 200 carrying an `ETag` hashed from the body and `Last-Modified`, or a
+/// bodiless 304 when the client's copy is still current. `If-None-Match`
+/// decides when sent: no `updated_at` can stand in for the body, as
+/// comment counts change without one and a listing changes when todos
+/// leave it. Otherwise `If-Modified-Since` is compared with `modified`.
+///
+/// The tag is weak: it is hashed before the format and casing middleware
+/// re-encode the body, so every representation of the same todos shares
+/// it. Routes that use this also answer `HEAD`; actix drops the body for
+/// those.
+fn conditional(
+    req: &HttpRequest,
+    modified: Option<DateTime<Utc>>,
+    body: impl Serialize,
+) -> Result<HttpResponse, AppError> {
+    let body = serde_json::to_vec(&body).map_err(|e| AppError::Internal(e.to_string()))?;
+    let digest = Sha256::digest(&body);
+    let etag = header::EntityTag::new_weak(hex::encode(&digest[..16]));
+    let last_modified =
+        modified.map(|modified| header::LastModified(SystemTime::from(modified).into()));
+
+    let not_modified = if req.headers().contains_key(header::IF_NONE_MATCH) {
+        match header::IfNoneMatch::parse(req) {
+            Ok(header::IfNoneMatch::Any) => true,
+            Ok(header::IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+            Err(_) => false,
+        }
+    } else {
+        // HTTP dates only have second precision.
+        header::IfModifiedSince::parse(req)
+            .ok()
+            .and_then(|header::IfModifiedSince(since)| {
+                SystemTime::from(since).duration_since(UNIX_EPOCH).ok()
+            })
+            .zip(modified)
+            .is_some_and(|(since, modified)| modified.timestamp() <= since.as_secs() as i64)
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(header::ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(last_modified);
+    }
+    if not_modified {
+        return Ok(response.finish());
+    }
+    Ok(response
+        .insert_header(header::ContentType::json())
+        .body(body))
+}
+
+fn prefers_minimal(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all("Prefer")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|preference| preference.split(';').next())
+        .any(|preference| preference.trim().eq_ignore_ascii_case("return=minimal"))
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    // The versioned scopes go first: `/api` would otherwise claim their paths.
+    for version in ApiVersion::ALL {
+        conf.service(routes(
+            web::scope(&version.base_path()).app_data(ApiMount(version)),
+        ));
+    }
+    // The representation on `/api` depends on `Accept`, so caches must key on it.
+    conf.service(
+        routes(web::scope(urls::API_BASE_PATH))
+            .wrap(middleware::DefaultHeaders::new().add((header::VARY, "Accept"))),
+    );
+
+    #[cfg(feature = "ui")]
+    conf.configure(crate::ui::config);
+}
+
+/// Every API route, mounted once per version.
+fn routes(scope: Scope) -> Scope {
+    let scope = scope
+        .app_data(web::PathConfig::default().error_handler(path_error))
+        .service(health_checker_handler)
+        .service(version_handler)
+        .service(todos_list_handler)
+        .service(create_todo_handler)
+        .service(batch_get_todos_handler)
+        // Registered before `/todos/{id}` so the literal path wins.
+        .service(todo_stats_handler)
+        .service(todo_count_handler)
+        .service(next_todo_handler)
+        .service(shared_with_me_handler)
+        .service(todos_by_title_handler)
+        .service(get_todo_handler)
+        // Registered before `/todos/{id}` so the literal paths win.
+        .service(complete_todos_handler)
+        .service(reorder_todos_handler)
+        .service(incomplete_todos_handler)
+        .service(json_patch_todo_handler)
+        .service(edit_todo_handler)
+        .service(set_todo_status_handler)
+        .service(assign_todo_handler)
+        .service(replace_todo_handler)
+        .service(todo_occurrences_handler)
+        .service(duplicate_todo_handler)
+        .service(archive_todo_handler)
+        .service(unarchive_todo_handler)
+        .service(share_todo_handler)
+        .service(todo_comments_list_handler)
+        .service(create_comment_handler)
+        .service(delete_comment_handler)
+        .service(todo_activity_handler)
+        .service(user_activity_handler)
+        .service(attachments_list_handler)
+        .service(upload_attachment_handler)
+        .service(presign_upload_handler)
+        .service(complete_upload_handler)
+        .service(download_url_handler)
+        .service(download_attachment_handler)
+        .service(delete_attachment_handler)
+        .service(delete_todo_handler)
+        .service(bulk_delete_todos_handler)
+        .service(undo_handler)
+        .service(sync_pull_handler)
+        .service(sync_push_handler)
+        .service(projects_list_handler)
+        .service(create_project_handler)
+        .service(get_project_handler)
+        .service(edit_project_handler)
+        .service(delete_project_handler)
+        .service(project_todos_handler)
+        .service(templates_list_handler)
+        .service(create_template_handler)
+        .service(get_template_handler)
+        .service(delete_template_handler)
+        .service(instantiate_template_handler)
+        .service(reminders_list_handler)
+        .service(cancel_reminder_handler)
+        .service(webhooks_list_handler)
+        .service(create_webhook_handler)
+        // Registered before `/webhooks/{id}` so the literal path wins.
+        .service(dead_letters_list_handler)
+        .service(get_webhook_handler)
+        .service(edit_webhook_handler)
+        .service(delete_webhook_handler)
+        .service(get_notification_settings_handler)
+        .service(update_notification_settings_handler)
+        .service(test_notification_handler)
+        .service(get_profile_handler)
+        .service(update_profile_handler)
+        .service(delete_profile_handler)
+        .service(start_export_handler)
+        .service(export_status_handler)
+        .service(download_export_handler)
+        .service(get_user_settings_handler)
+        .service(update_user_settings_handler)
+        .service(register_handler)
+        .service(login_handler)
+        .service(refresh_handler)
+        .service(logout_handler)
+        .service(csrf_token_handler)
+        .service(sessions_list_handler)
+        .service(revoke_session_handler)
+        .service(forgot_password_handler)
+        .service(reset_password_handler)
+        .service(confirm_email_handler)
+        .service(totp_login_handler)
+        .service(totp_enroll_handler)
+        .service(totp_confirm_handler)
+        .service(totp_backup_codes_handler)
+        .service(totp_disable_handler)
+        .service(oauth_start_handler)
+        .service(oauth_callback_handler)
+        .service(admin_stats_handler)
+        .service(clear_lockout_handler)
+        .service(reencrypt_content_handler)
+        .service(retention_preview_handler)
+        .service(list_schedules_handler)
+        .service(list_features_handler)
+        .service(set_feature_handler)
+        .service(clear_feature_handler)
+        .service(get_maintenance_handler)
+        .service(start_maintenance_handler)
+        .service(end_maintenance_handler)
+        .service(debug_info_handler)
+        .service(workspaces_list_handler)
+        .service(create_workspace_handler)
+        .service(get_workspace_handler)
+        .service(edit_workspace_handler)
+        .service(delete_workspace_handler)
+        .service(workspace_members_list_handler)
+        .service(add_workspace_member_handler)
+        .service(remove_workspace_member_handler);
+
+    #[cfg(feature = "seed")]
+    let scope = scope.service(seed_todos_handler);
+
+    #[cfg(feature = "chaos")]
+    let scope = scope
+        .service(get_fault_rules_handler)
+        .service(set_fault_rules_handler)
+        .service(clear_fault_rules_handler);
+
+    scope
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::dev::ServiceResponse;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use mockall::predicate::eq;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::app::{assemble_state, create_repositories};
+    use crate::config::{BlobBackend, Config, StorageBackend};
+    use crate::decoding::RowDecoding;
+    use crate::metrics::QueryMetrics;
+    use crate::repository::{
+        CoalescingTodoRepository, MockTodoRepository, RepositoryError, Resilience,
+        ResilientRepository, TodoRepository,
+    };
+    use crate::secrets::Secrets;
+
+    /// Runs `req` against the API with `todos` as the todo storage and
+    /// in-memory storage for everything else.
+    async fn call(todos: MockTodoRepository, req: test::TestRequest) -> ServiceResponse {
+        let mut config = Config::from_env();
+        config.storage.backend = StorageBackend::Memory;
+        config.attachments.backend = BlobBackend::Local;
+        config.attachments.local_dir =
+            std::env::temp_dir().join(format!("todo-attachments-{}", Uuid::new_v4()));
+
+        let query_metrics = Arc::new(QueryMetrics::new());
+        let secrets = Secrets::default();
+        let mut repositories = create_repositories(&config, query_metrics.clone(), &secrets)
+            .await
+            .unwrap();
+        repositories.todos = Arc::new(todos);
+        let (state, _queue) = assemble_state(&config, repositories, query_metrics, &secrets)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(super::config),
+        )
+        .await;
+        test::call_service(&app, req.to_request()).await
+    }
+
+    fn todo(id: TodoId, title: &str) -> Todo {
+        let now = Utc::now();
+        Todo {
+            id: Some(id),
+            title: title.to_string(),
+            content: "content".to_string(),
+            completed: Some(false),
+            archived: Some(false),
+            due_at: None,
+            recurrence: None,
+            series_id: None,
+            next_occurrence_id: None,
+            remind_at: None,
+            reminder_sent_at: None,
+            owner_id: None,
+            workspace_id: None,
+            assignee_id: None,
+            project_id: None,
+            position: None,
+            status: None,
+            created_at: Some(now),
+            updated_at: Some(now),
+            expires_at: None,
+            comment_count: None,
+            ttl_seconds: None,
+            content_html: None,
+        }
+    }
+
+    fn db_error() -> RepositoryError {
+        RepositoryError::Database("connection reset".to_string())
+    }
+
+    fn found(id: TodoId, title: &'static str) -> MockTodoRepository {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_find_by_id()
+            .with(eq(id))
+            .returning(move |id| Ok(Some(todo(*id, title))));
+        todos
+    }
+
+    /// Like [`found`], for a todo that is blocked.
+    fn blocked(id: TodoId, title: &'static str) -> MockTodoRepository {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_find_by_id().with(eq(id)).returning(move |id| {
+            Ok(Some(Todo {
+                status: Some(TodoStatus::Blocked),
+                ..todo(*id, title)
+            }))
+        });
+        todos
+    }
+
+    fn missing(id: TodoId) -> MockTodoRepository {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_find_by_id()
+            .with(eq(id))
+            .returning(|_| Ok(None));
+        todos
+    }
+
+    #[actix_web::test]
+    async fn version_reports_the_build() {
+        let req = test::TestRequest::get().uri("/api/version");
+        let body: Value = test::read_body_json(call(MockTodoRepository::new(), req).await).await;
+        assert_eq!(body["data"]["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["data"]["build"], build_info::version());
+    }
+
+    #[actix_web::test]
+    async fn list_returns_todos() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .withf(|options| options.offset == 0 && !options.include_archived)
+            .returning(|_| Ok(vec![todo(TodoId::generate(), "First")]));
+
+        let res = call(todos, test::TestRequest::get().uri("/api/todos")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["results"], 1);
+        assert_eq!(body["todos"][0]["title"], "First");
+        assert!(body.get("warnings").is_none());
+    }
+
+    #[actix_web::test]
+    async fn list_links_to_neighbouring_pages() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .withf(|options| options.offset == 1 && options.limit == 1)
+            .returning(|_| Ok(vec![todo(TodoId::generate(), "Second")]));
+
+        let req = test::TestRequest::get().uri("/api/v2/todos?page=2&limit=1");
+        let body: Value = test::read_body_json(call(todos, req).await).await;
+        assert_eq!(
+            body["_links"],
+            json!({
+                "self": { "href": "/api/v2/todos?page=2&limit=1" },
+                "next": { "href": "/api/v2/todos?limit=1&page=3" },
+                "prev": { "href": "/api/v2/todos?limit=1&page=1" },
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn list_links_leave_out_pages_past_either_end() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .withf(|options| options.offset == 0 && options.limit == 2)
+            .returning(|_| Ok(vec![todo(TodoId::generate(), "Only")]));
+
+        let req = test::TestRequest::get().uri("/api/todos?limit=2&completed=false");
+        let body: Value = test::read_body_json(call(todos, req).await).await;
+        assert_eq!(
+            body["_links"],
+            json!({ "self": { "href": "/api/todos?limit=2&completed=false" } })
+        );
+    }
+
+    #[actix_web::test]
+    async fn list_links_keep_the_filters_of_the_request() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .withf(|options| options.offset == 0 && options.limit == 1)
+            .returning(|_| Ok(vec![todo(TodoId::generate(), "First")]));
+
+        let req = test::TestRequest::get().uri("/api/v1/todos?completed=false&page=1&limit=1");
+        let body: Value = test::read_body_json(call(todos, req).await).await;
+        assert_eq!(
+            body["_links"]["next"]["href"],
+            "/api/v1/todos?completed=false&limit=1&page=2"
+        );
+        assert!(body["_links"].get("prev").is_none());
+    }
+
+    #[actix_web::test]
+    async fn get_links_stay_under_the_unversioned_prefix() {
+        let id = TodoId::generate();
+        let req = test::TestRequest::get().uri(&format!("/api/todos/{}", id));
+        let body: Value = test::read_body_json(call(found(id, "Linked"), req).await).await;
+
+        let href = format!("/api/todos/{}", id);
+        assert_eq!(body["_links"]["self"]["href"], href);
+        assert_eq!(
+            body["_links"]["complete"]["href"],
+            format!("{}/status", href)
+        );
+    }
+
+    #[actix_web::test]
+    async fn get_links_to_todo_actions() {
+        let id = TodoId::generate();
+        let req = test::TestRequest::get().uri(&format!("/api/v1/todos/{}", id));
+        let body: Value = test::read_body_json(call(found(id, "Linked"), req).await).await;
+
+        let href = format!("/api/v1/todos/{}", id);
+        assert_eq!(
+            body["_links"],
+            json!({
+                "self": { "href": href },
+                "update": { "href": href, "method": "PATCH" },
+                "delete": { "href": href, "method": "DELETE" },
+                "complete": { "href": format!("{}/status", href), "method": "PATCH" },
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn list_reports_database_errors() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_list().returning(|_| Err(db_error()));
+
+        let res = call(todos, test::TestRequest::get().uri("/api/todos")).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn list_warns_of_todos_left_out() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_list().returning(|_| {
+            let rows = vec![
+                Ok(todo(TodoId::generate(), "Readable")),
+                Err(RepositoryError::Decode(
+                    "todo 1: invalid due_at: -1".to_string(),
+                )),
+            ];
+            Ok(rows
+                .into_iter()
+                .filter_map(|row| RowDecoding::Lenient.check(row).unwrap())
+                .collect())
+        });
+
+        let res = call(todos, test::TestRequest::get().uri("/api/todos")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["results"], 1);
+        assert_eq!(
+            body["warnings"],
+            json!(["Stored data could not be decoded: todo 1: invalid due_at: -1"])
+        );
+    }
+
+    /// `todos` behind a breaker that opens on the first failure.
+    fn guarded(todos: MockTodoRepository) -> ResilientRepository<MockTodoRepository> {
+        let mut config = Config::from_env();
+        config.database.circuit_breaker.failure_threshold = 1;
+        let resilience = Resilience::new(&config.database, Arc::new(QueryMetrics::new()));
+        ResilientRepository::new(todos, Arc::new(resilience))
+    }
+
+    #[actix_web::test]
+    async fn rows_that_do_not_decode_leave_the_breaker_closed() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_find_by_id().times(2).returning(|_| {
+            Err(RepositoryError::Decode(
+                "todo 1: invalid status: someday".to_string(),
+            ))
+        });
+        let todos = guarded(todos);
+
+        let id = TodoId::generate();
+        for _ in 0..2 {
+            let result = todos.find_by_id(&id).await;
+            assert!(matches!(result, Err(RepositoryError::Decode(_))));
+        }
+    }
+
+    #[actix_web::test]
+    async fn database_errors_open_the_breaker() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_find_by_id()
+            .times(1)
+            .returning(|_| Err(db_error()));
+        let todos = guarded(todos);
+
+        let id = TodoId::generate();
+        assert!(matches!(
+            todos.find_by_id(&id).await,
+            Err(RepositoryError::Database(_))
+        ));
+        assert!(matches!(
+            todos.find_by_id(&id).await,
+            Err(RepositoryError::Unavailable)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn list_reports_unavailable_database() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .returning(|_| Err(RepositoryError::Unavailable));
+
+        let res = call(todos, test::TestRequest::get().uri("/api/todos")).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[cfg(feature = "ui")]
+    #[actix_web::test]
+    async fn ui_is_served_at_root() {
+        let req = test::TestRequest::get().uri("/");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = test::read_body(res).await;
+        assert!(std::str::from_utf8(&body).unwrap().contains("/ui/app.js"));
+    }
+
+    #[actix_web::test]
+    async fn create_inserts_todo() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_exists_with_title()
+            .with(eq("Buy milk"))
+            .returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(|write, entries| {
+                matches!(write, TodoWrite::Insert(todo)
+                    if todo.title == "Buy milk" && todo.completed == Some(false))
+                    && !entries.is_empty()
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .set_json(json!({ "title": "Buy milk", "content": "Two litres" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert!(res.headers().contains_key(header::LOCATION));
+    }
+
+    #[actix_web::test]
+    async fn create_strips_scripts_before_storing() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_exists_with_title()
+            .with(eq("Buy milk"))
+            .returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(|write, _| {
+                matches!(write, TodoWrite::Insert(todo)
+                    if todo.title == "Buy milk"
+                        && todo.content == "<em>Two</em> litres")
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::post().uri("/api/todos").set_json(json!({
+            "title": "<b>Buy</b> milk<script>alert(1)</script>",
+            "content": "<em>Two</em> litres<img src=\"x.png\" onerror=\"alert(1)\">"
+        }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn create_normalizes_the_title() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_exists_with_title()
+            .with(eq("Caf\u{e9} run"))
+            .returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(|write, _| {
+                matches!(write, TodoWrite::Insert(todo) if todo.title == "Caf\u{e9} run")
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .set_json(json!({ "title": "  Cafe\u{301}\t run ", "content": "Beans" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn create_rejects_duplicate_title() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_exists_with_title().returning(|_| Ok(true));
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .set_json(json!({ "title": "Buy milk", "content": "Two litres" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn create_reports_database_errors() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_exists_with_title().returning(|_| Ok(false));
+        todos.expect_commit().returning(|_, _| Err(db_error()));
+
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .set_json(json!({ "title": "Buy milk", "content": "Two litres" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn create_sets_expiry() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_exists_with_title().returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(|write, _| {
+                matches!(write, TodoWrite::Insert(todo) if todo.expires_at.is_some_and(|expires_at| {
+                    let ttl = (expires_at - Utc::now()).num_seconds();
+                    (3590..=3600).contains(&ttl)
+                }))
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::post().uri("/api/todos").set_json(json!({
+            "title": "Call back",
+            "content": "Within the hour",
+            "expiresInSeconds": 3600
+        }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let body: Value = test::read_body_json(res).await;
+        assert!(body["data"]["todo"]["expiresAt"].is_string());
+    }
+
+    #[actix_web::test]
+    async fn create_rejects_zero_expiry() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::post().uri("/api/todos").set_json(json!({
+            "title": "Call back",
+            "content": "Within the hour",
+            "expiresInSeconds": 0
+        }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn count_requires_token() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_count_for_owner().never();
+        todos.expect_find_by_id().never();
+
+        let req = test::TestRequest::get().uri("/api/todos/count");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn next_prefers_due_todos_over_blocked_and_done_ones() {
+        let ids = [TodoId::generate(), TodoId::generate(), TodoId::generate()];
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_stream()
+            .withf(|options| !options.include_archived)
+            .times(1)
+            .returning(move |_| {
+                let mut blocked = todo(ids[0], "Blocked");
+                blocked.status = Some(TodoStatus::Blocked);
+                blocked.due_at = Some(Utc::now());
+                let mut due = todo(ids[1], "Due tomorrow");
+                due.due_at = Some(Utc::now() + chrono::Duration::days(1));
+                let mut done = todo(ids[2], "Done");
+                done.completed = Some(true);
+                done.due_at = Some(Utc::now());
+                Ok(futures_util::stream::iter([Ok(blocked), Ok(due), Ok(done)]).boxed())
+            });
+
+        let req = test::TestRequest::get().uri("/api/todos/next");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["todo"]["id"], ids[1].to_string());
+    }
+
+    #[actix_web::test]
+    async fn next_without_open_todos_is_empty() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_stream()
+            .times(1)
+            .returning(|_| Ok(futures_util::stream::empty().boxed()));
+
+        let req = test::TestRequest::get().uri("/api/todos/next");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn list_passes_created_range() {
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .withf(|options| {
+                options.owner_id.is_none()
+                    && options.created_after.is_some()
+                    && options.created_before.is_none()
+            })
+            .times(1)
+            .returning(|_| Ok(Vec::new()));
+
+        let req = test::TestRequest::get().uri("/api/todos?created_after=2024-01-01T00:00:00Z");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn list_overdue_passes_open_todos_due_before_today() {
+        let (today, _) = timezones::day_bounds(Utc::now(), chrono_tz::Tz::UTC);
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_list()
+            .withf(move |options| {
+                options.completed == Some(false)
+                    && options.due_after.is_none()
+                    && options.due_before == Some(today)
+            })
+            .times(1)
+            .returning(|_| Ok(Vec::new()));
+
+        let req = test::TestRequest::get().uri("/api/todos?due=overdue");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn list_streams_ndjson() {
+        let ids = [TodoId::generate(), TodoId::generate()];
+        let mut todos = MockTodoRepository::new();
+        todos.expect_list().never();
+        todos
+            .expect_stream()
+            .withf(|options| options.offset == 0)
+            .times(1)
+            .returning(move |_| {
+                let todos = ids.map(|id| Ok(todo(id, "Streamed")));
+                Ok(futures_util::stream::iter(todos).boxed())
+            });
+
+        let req = test::TestRequest::get()
+            .uri("/api/todos?page=3")
+            .insert_header((header::ACCEPT, NDJSON));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_TYPE).unwrap(), NDJSON);
+
+        let body = test::read_body(res).await;
+        let lines: Vec<Value> = body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["id"], ids[1].to_string());
+    }
+
+    #[actix_web::test]
+    async fn list_mine_requires_token() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_list().never();
+
+        let req = test::TestRequest::get().uri("/api/todos?mine=true");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn by_title_returns_matches() {
+        let id = TodoId::generate();
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_find_by_title()
+            .withf(|title| title == "Buy milk")
+            .returning(move |_| Ok(vec![todo(id, "Buy milk")]));
+
+        let req = test::TestRequest::get().uri("/api/todos/by-title/Buy%20milk");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["results"], 1);
+        assert_eq!(body["todos"][0]["id"], id.to_string());
+    }
+
+    #[actix_web::test]
+    async fn get_returns_todo() {
+        let id = TodoId::generate();
+        let req = test::TestRequest::get().uri(&format!("/api/todos/{}", id));
+        let res = call(found(id, "Buy milk"), req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["todo"]["id"], id.to_string());
+    }
+
+    #[actix_web::test]
+    async fn get_renders_sanitized_markdown() {
+        let id = TodoId::generate();
+        let mut todos = MockTodoRepository::new();
+        todos.expect_find_by_id().with(eq(id)).returning(|id| {
+            let mut todo = todo(*id, "Buy milk");
+            todo.content = "**Oat** milk<script>alert(1)</script>".to_string();
+            Ok(Some(todo))
+        });
+
+        let req = test::TestRequest::get().uri(&format!("/api/todos/{}?render=html", id));
+        let body: Value = test::read_body_json(call(todos, req).await).await;
+        assert_eq!(
+            body["data"]["todo"]["contentHtml"],
+            "<p><strong>Oat</strong> milk</p>\n"
+        );
+
+        let req = test::TestRequest::get().uri(&format!("/api/todos/{}", id));
+        let body: Value = test::read_body_json(call(found(id, "Buy milk"), req).await).await;
+        assert!(body["data"]["todo"].get("contentHtml").is_none());
+    }
+
+    #[actix_web::test]
+    async fn get_reports_missing_todo() {
+        let id = TodoId::generate();
+        let req = test::TestRequest::get().uri(&format!("/api/todos/{}", id));
+        let res = call(missing(id), req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn get_reports_database_errors() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_find_by_id().returning(|_| Err(db_error()));
+
+        let req = test::TestRequest::get().uri(&format!("/api/todos/{}", TodoId::generate()));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn get_rejects_malformed_id() {
+        let req = test::TestRequest::get().uri("/api/todos/not-a-uuid");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn batch_get_splits_found_and_missing() {
+        let id = TodoId::generate();
+        let absent = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos
+            .expect_find_by_id()
+            .with(eq(absent))
+            .returning(|_| Ok(None));
+
+        let req = test::TestRequest::post()
+            .uri("/api/todos/batch-get")
+            .set_json(json!({ "ids": [id, absent, id] }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["results"], 1);
+        assert_eq!(body["todos"][0]["title"], "Buy milk");
+        assert_eq!(body["missing"], json!([absent]));
+    }
+
+    #[actix_web::test]
+    async fn duplicate_creates_copy() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos
+            .expect_exists_with_title()
+            .with(eq("Buy milk again"))
+            .returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(move |write, _| {
+                matches!(write, TodoWrite::Insert(todo) if todo.id != Some(id)
+                    && todo.title == "Buy milk again"
+                    && todo.content == "content"
+                    && todo.completed == Some(false))
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/todos/{}/duplicate", id))
+            .set_json(json!({ "titleSuffix": " again" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["todo"]["title"], "Buy milk again");
+    }
+
+    #[actix_web::test]
+    async fn duplicate_rejects_malformed_options() {
+        let id = TodoId::generate();
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/todos/{}/duplicate", id))
+            .set_json(json!({ "keepCompletion": "yes" }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn create_rejects_unknown_project() {
+        let req = test::TestRequest::post().uri("/api/todos").set_json(json!({
+            "title": "Buy milk",
+            "content": "content",
+            "projectId": "no-such-project",
+        }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn reorder_spaces_positions_in_given_order() {
+        let first = TodoId::generate();
+        let second = TodoId::generate();
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_find_by_id()
+            .returning(|id| Ok(Some(todo(*id, "Buy milk"))));
+        todos
+            .expect_commit()
+            .withf(move |write, _| match write {
+                TodoWrite::Patch(id, patch) => {
+                    (*id == second && patch.position == Some(0))
+                        || (*id == first && patch.position == Some(ordering::POSITION_STEP))
+                }
+                _ => false,
+            })
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::patch()
+            .uri("/api/todos/reorder")
+            .set_json(json!({ "ids": [second, first] }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["results"], 2);
+    }
+
+    #[actix_web::test]
+    async fn patch_updates_todo() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos
+            .expect_commit()
+            .withf(move |write, _| {
+                matches!(write, TodoWrite::Patch(todo_id, patch)
+                    if *todo_id == id && patch.completed == Some(true) && patch.title.is_none())
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "title": "Buy milk", "completed": true }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["modified"], true);
+        assert_eq!(body["data"]["todo"]["completed"], true);
+    }
+
+    #[actix_web::test]
+    async fn patch_skips_unchanged_todo() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "title": "Buy milk", "completed": false }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["modified"], false);
+    }
+
+    fn json_patch(id: TodoId, operations: Value) -> test::TestRequest {
+        test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .insert_header((header::CONTENT_TYPE, "application/json-patch+json"))
+            .set_payload(operations.to_string())
+    }
+
+    #[actix_web::test]
+    async fn json_patch_updates_todo() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos
+            .expect_commit()
+            .withf(move |write, _| {
+                matches!(write, TodoWrite::Patch(todo_id, patch) if *todo_id == id
+                    && patch.title.as_deref() == Some("Buy oat milk")
+                    && patch.completed == Some(true)
+                    && patch.content.is_none())
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = json_patch(
+            id,
+            json!([
+                { "op": "test", "path": "/title", "value": "Buy milk" },
+                { "op": "replace", "path": "/title", "value": "Buy oat milk" },
+                { "op": "add", "path": "/completed", "value": true }
+            ]),
+        );
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["todo"]["title"], "Buy oat milk");
+    }
+
+    #[actix_web::test]
+    async fn json_patch_rejects_other_paths() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos.expect_commit().never();
+
+        let req = json_patch(
+            id,
+            json!([{ "op": "replace", "path": "/ownerId", "value": "someone-else" }]),
+        );
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn json_patch_rejects_failed_test() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos.expect_commit().never();
+
+        let req = json_patch(
+            id,
+            json!([
+                { "op": "test", "path": "/title", "value": "Buy bread" },
+                { "op": "replace", "path": "/completed", "value": true }
+            ]),
+        );
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn json_patch_rejects_unknown_op() {
+        let id = TodoId::generate();
+        let req = json_patch(
+            id,
+            json!([{ "op": "merge", "path": "/title", "value": "Buy bread" }]),
+        );
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn status_move_keeps_completed_in_step() {
+        let id = TodoId::generate();
+        let mut todos = MockTodoRepository::new();
+        todos.expect_find_by_id().with(eq(id)).returning(move |id| {
+            Ok(Some(Todo {
+                status: Some(TodoStatus::InProgress),
+                ..todo(*id, "Buy milk")
+            }))
+        });
+        todos
+            .expect_commit()
+            .withf(move |write, _| {
+                matches!(write, TodoWrite::Patch(todo_id, patch) if *todo_id == id
+                    && patch.status == Some(TodoStatus::Done)
+                    && patch.completed == Some(true))
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}/status", id))
+            .set_json(json!({ "status": "done" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["todo"]["status"], "done");
+        assert_eq!(body["data"]["todo"]["completed"], true);
+    }
+
+    #[actix_web::test]
+    async fn status_move_outside_workflow_is_rejected() {
+        let id = TodoId::generate();
+        let mut todos = blocked(id, "Buy milk");
+        todos.expect_commit().never();
+
+        // The default workflow has blocked todos unblocked before they are done.
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}/status", id))
+            .set_json(json!({ "status": "done" }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn bulk_completion_reports_workflow_conflicts() {
+        let id = TodoId::generate();
+        let mut todos = blocked(id, "Buy milk");
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::patch()
+            .uri("/api/todos/complete")
+            .set_json(json!({ "ids": [id] }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["results"][0]["status"], "conflict");
+    }
+
+    #[actix_web::test]
+    async fn completing_outside_workflow_is_rejected() {
+        let id = TodoId::generate();
+        let mut todos = blocked(id, "Buy milk");
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "completed": true }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn assign_rejects_personal_todo() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/todos/{}/assign", id))
+            .set_json(json!({ "assigneeId": Uuid::new_v4().to_string() }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn list_assigned_to_me_requires_token() {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_list().never();
+
+        let req = test::TestRequest::get().uri("/api/todos?assigned_to=me");
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn activity_rejects_unknown_type() {
+        let id = TodoId::generate();
+        let todos = found(id, "Buy milk");
+
+        let req = test::TestRequest::get().uri(&format!(
+            "/api/todos/{}/activity?type=completed,renamed",
+            id
+        ));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn user_activity_requires_token() {
+        let req = test::TestRequest::get().uri("/api/activity");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn undo_requires_token() {
+        let req = test::TestRequest::post().uri("/api/undo");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn sync_requires_token() {
+        let req = test::TestRequest::get().uri("/api/sync");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn forgot_password_does_not_reveal_accounts() {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/forgot-password")
+            .set_json(json!({ "email": "nobody@example.com" }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+    }
+
+    #[actix_web::test]
+    async fn reset_password_rejects_unknown_tokens() {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/reset-password")
+            .set_json(json!({ "token": "abc123", "password": "correct horse battery" }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn clearing_lockouts_requires_an_admin() {
+        let req = test::TestRequest::delete().uri("/api/admin/lockouts?email=a@example.com");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn reencryption_requires_an_admin() {
+        let req = test::TestRequest::post().uri("/api/admin/encryption/reencrypt");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn retention_preview_requires_an_admin() {
+        let req = test::TestRequest::get().uri("/api/admin/retention/preview");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn schedules_require_an_admin() {
+        let req = test::TestRequest::get().uri("/api/admin/schedules");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn debug_info_requires_an_admin() {
+        let req = test::TestRequest::get().uri("/api/debug/info");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn features_require_an_admin() {
+        let req = test::TestRequest::get().uri("/api/admin/features");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::put()
+            .uri("/api/admin/features/graphql")
+            .set_json(json!({ "enabled": true }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn maintenance_requires_an_admin() {
+        let req = test::TestRequest::put()
+            .uri("/api/admin/maintenance")
+            .set_json(json!({ "message": "Upgrading the database" }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::delete().uri("/api/admin/maintenance");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn sessions_require_a_token() {
+        let req = test::TestRequest::get().uri("/api/auth/sessions");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn user_settings_require_a_token() {
+        let req = test::TestRequest::patch()
+            .uri("/api/me/settings")
+            .set_json(json!({ "pageSize": 5 }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn account_deletion_requires_a_token() {
+        let req = test::TestRequest::delete().uri("/api/me");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn exports_require_a_token() {
+        let req = test::TestRequest::post().uri("/api/me/export");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn refresh_requires_a_token() {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/refresh")
+            .set_json(json!({}));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn csrf_token_is_also_set_as_cookie() {
+        let req = test::TestRequest::get().uri("/api/auth/csrf");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cookie = res
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == csrf::CSRF_COOKIE)
+            .map(|cookie| cookie.value().to_string())
+            .unwrap();
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["csrfToken"], cookie);
+    }
+
+    #[actix_web::test]
+    async fn totp_login_rejects_invalid_challenges() {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/login/totp")
+            .set_json(json!({ "mfaToken": "not-a-token", "code": "123456" }));
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn oauth_rejects_unknown_providers() {
+        let req = test::TestRequest::get().uri("/api/auth/oauth/myspace/start");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get().uri("/api/auth/oauth/myspace/callback?code=a&state=b");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn patch_reports_missing_todo() {
+        let id = TodoId::generate();
+        let mut todos = missing(id);
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "completed": true }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn patch_reports_database_errors() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos.expect_commit().returning(|_, _| Err(db_error()));
+
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "completed": true }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn put_replaces_todo() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Draft");
+        todos
+            .expect_exists_with_title()
+            .with(eq("Final"))
+            .returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(|write, _| matches!(write, TodoWrite::Update(todo) if todo.title == "Final"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "title": "Final", "content": "Done", "completed": true }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn put_rejects_duplicate_title() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Draft");
+        todos.expect_exists_with_title().returning(|_| Ok(true));
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "title": "Taken", "content": "Done", "completed": false }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn put_reports_missing_todo() {
+        let id = TodoId::generate();
+        let mut todos = missing(id);
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/todos/{}", id))
+            .set_json(json!({ "title": "Fresh", "content": "New", "completed": false }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn put_upserts_missing_todo() {
+        let id = TodoId::generate();
+        let mut todos = missing(id);
+        todos.expect_exists_with_title().returning(|_| Ok(false));
+        todos
+            .expect_commit()
+            .withf(move |write, _| matches!(write, TodoWrite::Insert(todo) if todo.id == Some(id)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/todos/{}?upsert=true", id))
+            .set_json(json!({ "title": "Fresh", "content": "New", "completed": false }));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn delete_removes_todo() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos
+            .expect_commit()
+            .withf(move |write, _| matches!(write, TodoWrite::Delete(todo_id) if *todo_id == id))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let req = test::TestRequest::delete().uri(&format!("/api/todos/{}", id));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_web::test]
+    async fn delete_reports_missing_todo() {
+        let id = TodoId::generate();
+        let mut todos = missing(id);
+        todos.expect_commit().never();
+
+        let req = test::TestRequest::delete().uri(&format!("/api/todos/{}", id));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn list_rejects_pages_past_the_largest_offset() {
+        let req = test::TestRequest::get().uri("/api/todos?page=100000000000000000&limit=100");
+        let res = call(MockTodoRepository::new(), req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn bulk_delete_rejects_malformed_ids() {
+        for uri in ["/api/todos?completed=true", "/api/todos"] {
+            let req = test::TestRequest::delete()
+                .uri(uri)
+                .set_json(json!(["oops"]));
+            let res = call(MockTodoRepository::new(), req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body: Value = test::read_body_json(res).await;
+            let message = body["message"].as_str().unwrap();
+            assert!(message.starts_with("Invalid JSON body"), "{}", message);
+        }
+    }
+
+    #[actix_web::test]
+    async fn delete_reports_database_errors() {
+        let id = TodoId::generate();
+        let mut todos = found(id, "Buy milk");
+        todos
+            .expect_commit()
+            .returning(|_, _| Err(RepositoryError::Timeout));
+
+        let req = test::TestRequest::delete().uri(&format!("/api/todos/{}", id));
+        let res = call(todos, req).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_lookups_share_one_query() {
+        let id = TodoId::generate();
+        let mut todos = MockTodoRepository::new();
+        todos
+            .expect_find_by_id()
+            .with(eq(id))
+            .times(2)
+            .returning(|id| {
+                // Keeps the query going while the other lookups come in.
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                Ok(Some(todo(*id, "Buy milk")))
+            });
+        todos.expect_commit().times(1).returning(|_, _| Ok(()));
+        let metrics = Arc::new(QueryMetrics::new());
+        let todos = Arc::new(CoalescingTodoRepository::new(
+            Arc::new(todos),
+            metrics.clone(),
+        ));
+
+        let lookup = || {
+            let todos = todos.clone();
+            tokio::spawn(async move { todos.find_by_id(&id).await })
+        };
+        let first = lookup();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let others: Vec<_> = (0..3).map(|_| lookup()).collect();
+        for lookup in std::iter::once(first).chain(others) {
+            assert!(lookup.await.unwrap().unwrap().is_some());
+        }
+        let lookups = metrics.lookups();
+        assert_eq!((lookups.queried, lookups.shared), (1, 3));
+
+        // A lookup after a write queries again.
+        todos.commit(&TodoWrite::Delete(id), &[]).await.unwrap();
+        todos.find_by_id(&id).await.unwrap();
+        assert_eq!(metrics.lookups().queried, 2);
+    }
+
+    /// A mock finding every todo after `delay_ms`, noting whether each query
+    /// read its own writes.
+    fn slow_lookups(delay_ms: u64, own_writes: Arc<Mutex<Vec<bool>>>) -> MockTodoRepository {
+        let mut todos = MockTodoRepository::new();
+        todos.expect_find_by_id().returning(move |id| {
+            own_writes
+                .lock()
+                .unwrap()
+                .push(crate::consistency::reads_own_writes());
+            std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+            Ok(Some(todo(*id, "Buy milk")))
+        });
+        todos.expect_commit().returning(|_, _| Ok(()));
+        todos
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn lookups_started_after_a_write_do_not_join_earlier_ones() {
+        let id = TodoId::generate();
+        let metrics = Arc::new(QueryMetrics::new());
+        let todos = Arc::new(CoalescingTodoRepository::new(
+            Arc::new(slow_lookups(200, Arc::default())),
+            metrics.clone(),
+        ));
+
+        let before = {
+            let todos = todos.clone();
+            tokio::spawn(async move { todos.find_by_id(&id).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        // The write lands while the first lookup is still in flight.
+        todos.commit(&TodoWrite::Delete(id), &[]).await.unwrap();
+        let after = {
+            let todos = todos.clone();
+            tokio::spawn(async move { todos.find_by_id(&id).await })
+        };
+        before.await.unwrap().unwrap();
+        after.await.unwrap().unwrap();
+
+        let lookups = metrics.lookups();
+        assert_eq!((lookups.queried, lookups.shared), (2, 0));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn lookups_reading_their_own_writes_share_only_among_themselves() {
+        let id = TodoId::generate();
+        let own_writes = Arc::new(Mutex::new(Vec::new()));
+        let metrics = Arc::new(QueryMetrics::new());
+        let todos = Arc::new(CoalescingTodoRepository::new(
+            Arc::new(slow_lookups(200, own_writes.clone())),
+            metrics.clone(),
+        ));
+
+        let lookup = |read_own_writes: bool| {
+            let todos = todos.clone();
+            tokio::spawn(crate::consistency::with_reads_own_writes(
+                read_own_writes,
+                async move { todos.find_by_id(&id).await },
+            ))
+        };
+        let plain = lookup(false);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let strong: Vec<_> = (0..2).map(|_| lookup(true)).collect();
+        plain.await.unwrap().unwrap();
+        for lookup in strong {
+            lookup.await.unwrap().unwrap();
+        }
+
+        let lookups = metrics.lookups();
+        assert_eq!((lookups.queried, lookups.shared), (2, 1));
+        let mut queried = own_writes.lock().unwrap().clone();
+        queried.sort();
+        assert_eq!(queried, vec![false, true]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Translation of error messages into the language a client prefers, as
+//! given by `Accept-Language`. Messages are written in English; each
+//! catalog in `locales/`, embedded at build time, maps English messages to
+//! their translations, with `{}` marking the parts filled in when the
+//! message is made, such as an ID. A message a catalog lacks stays in
+//! English, as does every message for a language without a catalog.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header;
+use actix_web::middleware::Next;
+
+/// The language messages are written in.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Catalogs by primary language subtag.
+const CATALOGS: [(&str, &str); 3] = [
+    ("de", include_str!("../locales/de.json")),
+    ("es", include_str!("../locales/es.json")),
+    ("fr", include_str!("../locales/fr.json")),
+];
+
+tokio::task_local! {
+    static LANGUAGE: &'static str;
+}
+
+/// A catalog entry, as the literal text around the placeholders of the
+/// English message and of its translation.
+struct Entry {
+    english: Vec<String>,
+    translation: Vec<String>,
+}
+
+impl Entry {
+    /// The values `message` fills the placeholders with, when it is this
+    /// entry's English message.
+    fn values<'a>(&self, message: &'a str) -> Option<Vec<&'a str>> {
+        let (first, rest) = self.english.split_first()?;
+        let mut remaining = message.strip_prefix(first.as_str())?;
+        let mut values = Vec::with_capacity(rest.len());
+        for (i, part) in rest.iter().enumerate() {
+            if i + 1 == rest.len() {
+                values.push(remaining.strip_suffix(part.as_str())?);
+                remaining = "";
+            } else {
+                let at = remaining.find(part.as_str())?;
+                values.push(&remaining[..at]);
+                remaining = &remaining[at + part.len()..];
+            }
+        }
+        remaining.is_empty().then_some(values)
+    }
+
+    fn translate(&self, values: &[&str]) -> String {
+        let mut translated = self.translation[0].clone();
+        for (value, part) in values.iter().zip(&self.translation[1..]) {
+            translated.push_str(value);
+            translated.push_str(part);
+        }
+        translated
+    }
+}
+
+/// The parsed catalogs. Entries with the most literal text come first, so
+/// a message matches its own entry before a more general one such as
+/// `{} must not be empty`.
+fn catalogs() -> &'static HashMap<&'static str, Vec<Entry>> {
+    static PARSED: OnceLock<HashMap<&'static str, Vec<Entry>>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(language, json)| {
+                let messages: HashMap<String, String> =
+                    serde_json::from_str(json).expect("message catalogs are valid JSON");
+                let mut entries: Vec<Entry> = messages
+                    .iter()
+                    .map(|(english, translation)| Entry {
+                        english: english.split("{}").map(str::to_string).collect(),
+                        translation: translation.split("{}").map(str::to_string).collect(),
+                    })
+                    .filter(|entry| {
+                        let matches = entry.english.len() == entry.translation.len();
+                        if !matches {
+                            log::warn!(
+                                "event=catalog_entry_skipped language={} message=\"{}\"",
+                                language,
+                                entry.english.join("{}")
+                            );
+                        }
+                        matches
+                    })
+                    .collect();
+                entries.sort_by_key(|entry| {
+                    std::cmp::Reverse(entry.english.iter().map(String::len).sum::<usize>())
+                });
+                (*language, entries)
+            })
+            .collect()
+    })
+}
+
+/// `message` in `language`, or `None` when its catalog lacks the message
+/// or there is no catalog for `language`.
+pub fn translate(message: &str, language: &str) -> Option<String> {
+    catalogs().get(language)?.iter().find_map(|entry| {
+        let values = entry.values(message)?;
+        Some(entry.translate(&values))
+    })
+}
+
+/// The language of `accept_language` with the highest weight that is
+/// English or has a catalog, matched by primary subtag so that `de-CH`
+/// gets German. English when there is none.
+pub fn preferred_language(accept_language: &str) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let tag = params.next()?.trim();
+            let weight = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(weight) => weight.trim().parse().ok()?,
+                None => 1.0,
+            };
+            (!tag.is_empty() && weight > 0.0).then_some((tag, weight))
+        })
+        .collect();
+    // Stable, so equally weighted ranges keep the client's order.
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (tag, _) in ranges {
+        let primary = tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+        if primary == DEFAULT_LANGUAGE || primary == "*" {
+            return DEFAULT_LANGUAGE;
+        }
+        if let Some((language, _)) = CATALOGS.iter().find(|(language, _)| *language == primary) {
+            return language;
+        }
+    }
+    DEFAULT_LANGUAGE
+}
+
+/// Middleware running each request with the language its
+/// `Accept-Language` prefers, which error responses are written in.
+/// Errors from the middleware it wraps are rendered here, while the
+/// language is known, rather than by the server afterwards.
+pub async fn negotiate_language(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let language = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(DEFAULT_LANGUAGE, preferred_language);
+
+    LANGUAGE
+        .scope(language, async move {
+            next.call(req).await.map_err(|e| {
+                let response = e.error_response();
+                InternalError::from_response(e.to_string(), response).into()
+            })
+        })
+        .await
+}
+
+/// `message` in the current request's language where there is a
+/// translation, with the language it ended up in.
+pub fn localize(message: String) -> (String, &'static str) {
+    let language = LANGUAGE
+        .try_with(|language| *language)
+        .unwrap_or(DEFAULT_LANGUAGE);
+    match translate(&message, language) {
+        Some(translated) => (translated, language),
+        None => (message, DEFAULT_LANGUAGE),
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::{mpsc, Semaphore};
+use uuid::Uuid;
+
+use crate::config::JobConfig;
+use crate::leader::LeaderElector;
+use crate::model::{JobRecord, JobStatus};
+use crate::repository::{JobRepository, RepositoryError};
+
+/// How often finished jobs older than the retention period are purged.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct JobError(pub String);
+
+impl From<RepositoryError> for JobError {
+    fn from(e: RepositoryError) -> Self {
+        JobError(e.to_string())
+    }
+}
+
+/// A unit of background work, identified by `kind` and fed a JSON payload.
+/// Payloads are persisted, so a job must be able to run from its payload alone.
+#[async_trait]
+pub trait Job: Send + Sync {
+    fn kind(&self) -> &'static str;
+
+    async fn run(&self, payload: &serde_json::Value) -> Result<(), JobError>;
+
+    /// Attempts before the job is marked failed; `None` uses the queue default.
+    fn max_attempts(&self) -> Option<u32> {
+        None
+    }
+
+    /// Called once after the final attempt has failed.
+    async fn on_failure(&self, _payload: &serde_json::Value, _attempts: u32, _error: &JobError) {}
+
+    /// Whether scheduled runs happen on every instance rather than only on
+    /// the leader, for jobs keeping state of their own instance.
+    fn per_instance(&self) -> bool {
+        false
+    }
+}
+
+/// In-process job queue. Every job is persisted before it runs, retried with
+/// exponential backoff, and picked up again after a restart if it had not
+/// finished.
+#[derive(Clone)]
+pub struct JobQueue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    repository: Arc<dyn JobRepository>,
+    config: JobConfig,
+    jobs: RwLock<HashMap<&'static str, Arc<dyn Job>>>,
+    sender: mpsc::UnboundedSender<String>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<String>>>,
+    /// Decides where scheduled jobs run; without one, they run here.
+    leader: OnceLock<LeaderElector>,
+}
+
+impl JobQueue {
+    pub fn new(repository: Arc<dyn JobRepository>, config: &JobConfig) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        JobQueue {
+            inner: Arc::new(Inner {
+                repository,
+                config: config.clone(),
+                jobs: RwLock::new(HashMap::new()),
+                sender,
+                receiver: Mutex::new(Some(receiver)),
+                leader: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Registers the handler for `job.kind()`. Register every job before
+    /// calling [`JobQueue::start`].
+    pub fn register(&self, job: Arc<dyn Job>) {
+        self.inner.jobs.write().unwrap().insert(job.kind(), job);
+    }
+
+    /// Runs scheduled jobs only while `leader` leads, for deployments of
+    /// several instances. The election starts with the queue.
+    pub fn set_leader(&self, leader: LeaderElector) {
+        let _ = self.inner.leader.set(leader);
+    }
+
+    /// Whether scheduled `kind` jobs run on this instance.
+    pub fn runs_here(&self, kind: &str) -> bool {
+        let per_instance = self
+            .inner
+            .jobs
+            .read()
+            .unwrap()
+            .get(kind)
+            .is_some_and(|job| job.per_instance());
+        per_instance || self.inner.leads()
+    }
+
+    /// Persists a new job and queues it to run. Returns the job ID.
+    pub async fn enqueue(
+        &self,
+        kind: &str,
+        payload: serde_json::Value,
+    ) -> Result<String, RepositoryError> {
+        let max_attempts = self
+            .inner
+            .jobs
+            .read()
+            .unwrap()
+            .get(kind)
+            .and_then(|job| job.max_attempts())
+            .unwrap_or(self.inner.config.max_attempts)
+            .max(1);
+
+        let now = Utc::now();
+        let record = JobRecord {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            payload,
+            status: JobStatus::Queued,
+            attempts: 0,
+            max_attempts,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.inner.repository.insert(&record).await?;
+        let _ = self.inner.sender.send(record.id.clone());
+
+        Ok(record.id)
+    }
+
+    /// The job with `id`, as last saved; `None` once it has been purged.
+    pub async fn find(&self, id: &str) -> Result<Option<JobRecord>, RepositoryError> {
+        self.inner.repository.find_by_id(id).await
+    }
+
+    /// Enqueues a `kind` job with an empty payload every `interval`.
+    pub fn every(&self, kind: &'static str, interval: Duration) {
+        self.schedule(kind, move |now| {
+            now + chrono::Duration::from_std(interval).unwrap_or_default()
+        });
+    }
+
+    fn schedule(
+        &self,
+        kind: &'static str,
+        next_run: impl Fn(DateTime<Utc>) -> DateTime<Utc> + Send + 'static,
+    ) {
+        let queue = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let now = Utc::now();
+                let wait = (next_run(now) - now).to_std().unwrap_or_default();
+                tokio::time::sleep(wait).await;
+                if !queue.runs_here(kind) {
+                    continue;
+                }
+
+                if let Err(e) = queue.enqueue(kind, serde_json::Value::Null).await {
+                    log::warn!("event=job_schedule_failed kind={} error=\"{}\"", kind, e);
+                }
+            }
+        });
+    }
+
+    /// Starts the workers and re-queues jobs left unfinished by a previous run.
+    pub async fn start(&self) -> Result<usize, RepositoryError> {
+        let Some(mut receiver) = self.inner.receiver.lock().unwrap().take() else {
+            return Ok(0);
+        };
+
+        let inner = self.inner.clone();
+        let permits = Arc::new(Semaphore::new(inner.config.concurrency.max(1)));
+        tokio::spawn(async move {
+            while let Some(id) = receiver.recv().await {
+                let Ok(permit) = permits.clone().acquire_owned().await else {
+                    break;
+                };
+                let inner = inner.clone();
+                tokio::spawn(async move {
+                    inner.run(&id).await;
+                    drop(permit);
+                });
+            }
+        });
+
+        if let Some(leader) = self.inner.leader.get() {
+            leader.start();
+        }
+
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PURGE_INTERVAL).await;
+                if !inner.leads() {
+                    continue;
+                }
+                let cutoff = Utc::now()
+                    - chrono::Duration::from_std(inner.config.retention).unwrap_or_default();
+                if let Err(e) = inner.repository.purge_finished(cutoff).await {
+                    log::warn!("event=job_purge_failed error=\"{}\"", e);
+                }
+            }
+        });
+
+        let unfinished = self.inner.repository.list_unfinished().await?;
+        for record in &unfinished {
+            let _ = self.inner.sender.send(record.id.clone());
+        }
+        Ok(unfinished.len())
+    }
+}
+
+impl Inner {
+    /// Whether this instance runs the scheduled jobs of the deployment.
+    fn leads(&self) -> bool {
+        self.leader.get().is_none_or(LeaderElector::is_leader)
+    }
+
+    async fn run(self: &Arc<Self>, id: &str) {
+        let mut record = match self.repository.find_by_id(id).await {
+            Ok(Some(record)) if !record.status.is_finished() => record,
+            Ok(_) => return,
+            Err(e) => {
+                log::warn!("event=job_load_failed job_id={} error=\"{}\"", id, e);
+                self.retry_later(id.to_string(), self.config.initial_backoff);
+                return;
+            }
+        };
+
+        let job = self.jobs.read().unwrap().get(record.kind.as_str()).cloned();
+        let Some(job) = job else {
+            record.status = JobStatus::Failed;
+            record.last_error = Some(format!("no handler registered for {}", record.kind));
+            record.updated_at = Utc::now();
+            self.save(&record).await;
+            return;
+        };
+
+        record.status = JobStatus::Running;
+        record.attempts += 1;
+        record.updated_at = Utc::now();
+        self.save(&record).await;
+
+        let result = job.run(&record.payload).await;
+        record.updated_at = Utc::now();
+        match result {
+            Ok(()) => {
+                record.status = JobStatus::Succeeded;
+                record.last_error = None;
+                self.save(&record).await;
+            }
+            Err(e) if record.attempts < record.max_attempts => {
+                log::warn!(
+                    "event=job_attempt_failed job_id={} kind={} attempt={} error=\"{}\"",
+                    record.id,
+                    record.kind,
+                    record.attempts,
+                    e
+                );
+                record.status = JobStatus::Queued;
+                record.last_error = Some(e.to_string());
+                self.save(&record).await;
+                self.retry_later(record.id.clone(), self.backoff(record.attempts));
+            }
+            Err(e) => {
+                log::error!(
+                    "event=job_failed job_id={} kind={} attempts={} error=\"{}\"",
+                    record.id,
+                    record.kind,
+                    record.attempts,
+                    e
+                );
+                record.status = JobStatus::Failed;
+                record.last_error = Some(e.to_string());
+                self.save(&record).await;
+                job.on_failure(&record.payload, record.attempts, &e).await;
+            }
+        }
+    }
+
+    /// Delay before the attempt after `attempts` failures, doubling each time.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.config
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.config.max_backoff)
+    }
+
+    fn retry_later(&self, id: String, delay: Duration) {
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = sender.send(id);
+        });
+    }
+
+    async fn save(&self, record: &JobRecord) {
+        if let Err(e) = self.repository.update(record).await {
+            log::warn!("event=job_save_failed job_id={} error=\"{}\"", record.id, e);
+        }
+    }
+}
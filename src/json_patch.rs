@@ -0,0 +1,223 @@
+//! JSON Patch (RFC 6902) for `PATCH /todos/{id}` requests sent as
+//! `application/json-patch+json`. Operations are applied to a JSON
+//! document of the todo's editable fields, all or none of them.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The media type of a JSON Patch document.
+pub const CONTENT_TYPE: &str = "application/json-patch+json";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl PatchOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            PatchOperation::Add { .. } => "add",
+            PatchOperation::Remove { .. } => "remove",
+            PatchOperation::Replace { .. } => "replace",
+            PatchOperation::Move { .. } => "move",
+            PatchOperation::Copy { .. } => "copy",
+            PatchOperation::Test { .. } => "test",
+        }
+    }
+
+    /// The pointers the operation reads from or writes to.
+    fn pointers(&self) -> Vec<&str> {
+        match self {
+            PatchOperation::Add { path, .. }
+            | PatchOperation::Remove { path }
+            | PatchOperation::Replace { path, .. }
+            | PatchOperation::Test { path, .. } => vec![path],
+            PatchOperation::Move { from, path } | PatchOperation::Copy { from, path } => {
+                vec![from, path]
+            }
+        }
+    }
+}
+
+/// Applies `operations` in order to a copy of `document`, which is only
+/// replaced once all of them succeed. Every pointer must lead into one of
+/// the top-level `fields`.
+pub fn apply(
+    document: &mut Value,
+    operations: &[PatchOperation],
+    fields: &[&str],
+) -> Result<(), String> {
+    let mut patched = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_one(&mut patched, operation, fields)
+            .map_err(|e| format!("operation {} ({}): {}", index, operation.name(), e))?;
+    }
+    *document = patched;
+    Ok(())
+}
+
+fn apply_one(
+    document: &mut Value,
+    operation: &PatchOperation,
+    fields: &[&str],
+) -> Result<(), String> {
+    for pointer in operation.pointers() {
+        let tokens = parse_pointer(pointer)?;
+        if !tokens
+            .first()
+            .is_some_and(|field| fields.contains(&field.as_str()))
+        {
+            return Err(format!(
+                "path '{}' cannot be patched; allowed: {}",
+                pointer,
+                fields
+                    .iter()
+                    .map(|field| format!("/{}", field))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    match operation {
+        PatchOperation::Add { path, value } => add(document, &parse_pointer(path)?, value.clone()),
+        PatchOperation::Remove { path } => remove(document, &parse_pointer(path)?).map(drop),
+        PatchOperation::Replace { path, value } => {
+            let tokens = parse_pointer(path)?;
+            remove(document, &tokens)?;
+            add(document, &tokens, value.clone())
+        }
+        PatchOperation::Move { from, path } => {
+            if from == path {
+                return Ok(());
+            }
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move '{}' into itself", from));
+            }
+            let value = remove(document, &parse_pointer(from)?)?;
+            add(document, &parse_pointer(path)?, value)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = get(document, &parse_pointer(from)?)
+                .ok_or_else(|| format!("path '{}' does not exist", from))?
+                .clone();
+            add(document, &parse_pointer(path)?, value)
+        }
+        PatchOperation::Test { path, value } => match get(document, &parse_pointer(path)?) {
+            Some(actual) if actual == value => Ok(()),
+            Some(_) => Err(format!("value at '{}' does not match", path)),
+            None => Err(format!("path '{}' does not exist", path)),
+        },
+    }
+}
+
+/// The reference tokens of a JSON Pointer (RFC 6901), unescaped.
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(format!("path '{}' must start with '/'", pointer));
+    };
+    rest.split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    unescaped.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => unescaped.push('~'),
+                    Some('1') => unescaped.push('/'),
+                    _ => return Err(format!("path '{}' has an invalid '~' escape", pointer)),
+                }
+            }
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// An array index token: digits without leading zeros.
+fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn get<'a>(document: &'a Value, tokens: &[String]) -> Option<&'a Value> {
+    tokens
+        .iter()
+        .try_fold(document, |value, token| match value {
+            Value::Object(map) => map.get(token),
+            Value::Array(items) => items.get(array_index(token)?),
+            _ => None,
+        })
+}
+
+/// The parent of the value `tokens` points at, and its last token.
+fn parent<'a, 'b>(
+    document: &'a mut Value,
+    tokens: &'b [String],
+) -> Result<(&'a mut Value, &'b str), String> {
+    let Some((last, parents)) = tokens.split_last() else {
+        return Err("the whole document cannot be replaced".to_string());
+    };
+    let mut value = document;
+    for token in parents {
+        value = match value {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => array_index(token).and_then(|index| items.get_mut(index)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("path '/{}' does not exist", tokens.join("/")))?;
+    }
+    Ok((value, last))
+}
+
+fn add(document: &mut Value, tokens: &[String], value: Value) -> Result<(), String> {
+    let (parent, token) = parent(document, tokens)?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(token.to_string(), value);
+            Ok(())
+        }
+        Value::Array(items) => {
+            let index = match token {
+                "-" => items.len(),
+                _ => array_index(token)
+                    .filter(|index| *index <= items.len())
+                    .ok_or_else(|| format!("array index '{}' is out of bounds", token))?,
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        _ => Err(format!("cannot add '{}' to a scalar value", token)),
+    }
+}
+
+fn remove(document: &mut Value, tokens: &[String]) -> Result<Value, String> {
+    let missing = || format!("path '/{}' does not exist", tokens.join("/"));
+    let (parent, token) = parent(document, tokens)?;
+    match parent {
+        Value::Object(map) => map.remove(token).ok_or_else(missing),
+        Value::Array(items) => {
+            let index = array_index(token)
+                .filter(|index| *index < items.len())
+                .ok_or_else(missing)?;
+            Ok(items.remove(index))
+        }
+        _ => Err(missing()),
+    }
+}
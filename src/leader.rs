@@ -0,0 +1,107 @@
+//! Leader election between instances sharing a database. Each instance
+//! tries to take or renew a named lease every third of its length; the one
+//! holding it is the leader until it fails to renew in time. Scheduled jobs
+//! (reminders, the outbox relay, purges) run only on the leader, while jobs
+//! queued by a request still run on the instance that queued them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::repository::LeaseRepository;
+
+/// The lease held by the instance running scheduled background jobs.
+pub const WORKERS: &str = "workers";
+
+/// Takes part in the election for one lease.
+#[derive(Clone)]
+pub struct LeaderElector {
+    leases: Arc<dyn LeaseRepository>,
+    name: &'static str,
+    /// This instance, as the lease's holder.
+    holder: String,
+    lease: Duration,
+    leader: Arc<AtomicBool>,
+}
+
+impl LeaderElector {
+    pub fn new(leases: Arc<dyn LeaseRepository>, name: &'static str, lease: Duration) -> Self {
+        LeaderElector {
+            leases,
+            name,
+            holder: Uuid::new_v4().to_string(),
+            lease,
+            leader: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether this instance held the lease when it last tried to.
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::SeqCst)
+    }
+
+    /// Takes the lease if it is free or has expired, or renews it if this
+    /// instance holds it. Returns whether this instance now leads. A failure
+    /// to reach the database steps down, since the lease may run out before
+    /// the next try.
+    pub async fn try_lead(&self) -> bool {
+        let now = Utc::now();
+        let until = now + chrono::Duration::from_std(self.lease).unwrap_or_default();
+        let leading = match self
+            .leases
+            .acquire(self.name, &self.holder, now, until)
+            .await
+        {
+            Ok(acquired) => acquired,
+            Err(e) => {
+                log::warn!(
+                    "event=lease_acquire_failed lease={} error=\"{}\"",
+                    self.name,
+                    e
+                );
+                false
+            }
+        };
+        self.set_leader(leading);
+        leading
+    }
+
+    /// Tries for the lease now and then every third of its length.
+    pub fn start(&self) {
+        let elector = self.clone();
+        let interval = self.lease / 3;
+        tokio::spawn(async move {
+            loop {
+                elector.try_lead().await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Gives the lease up, so another instance can take over without
+    /// waiting for it to expire.
+    pub async fn resign(&self) {
+        if let Err(e) = self.leases.release(self.name, &self.holder).await {
+            log::warn!(
+                "event=lease_release_failed lease={} error=\"{}\"",
+                self.name,
+                e
+            );
+        }
+        self.set_leader(false);
+    }
+
+    fn set_leader(&self, leading: bool) {
+        if self.leader.swap(leading, Ordering::SeqCst) != leading {
+            log::info!(
+                "event=leadership_changed lease={} holder={} leader={}",
+                self.name,
+                self.holder,
+                leading
+            );
+        }
+    }
+}
@@ -0,0 +1,77 @@
+pub mod accounts;
+pub mod activity;
+pub mod app;
+pub mod assignments;
+pub mod auth;
+pub mod blobs;
+pub mod bridge;
+pub mod build_info;
+pub mod casing;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod compression;
+pub mod circuit_breaker;
+pub mod concurrency;
+pub mod config;
+pub mod consistency;
+pub mod csrf;
+pub mod db;
+pub mod decoding;
+pub mod diagnostics;
+pub mod digest;
+pub mod encryption;
+pub mod error;
+pub mod events;
+pub mod exports;
+pub mod expiry;
+pub mod features;
+#[cfg(feature = "seed")]
+pub mod fixtures;
+pub mod formats;
+pub mod handler;
+pub mod i18n;
+pub mod json_patch;
+pub mod jobs;
+pub mod leader;
+pub mod lockout;
+pub mod logging;
+pub mod maintenance;
+pub mod markdown;
+pub mod metrics;
+pub mod migrations;
+pub mod model;
+pub mod notifier;
+pub mod oauth;
+pub mod ordering;
+pub mod outbox;
+pub mod pagination;
+pub mod password_reset;
+pub mod preferences;
+pub mod projects;
+pub mod rate_limit;
+pub mod reminders;
+pub mod repository;
+pub mod response;
+pub mod retention;
+pub mod sanitize;
+pub mod scheduler;
+pub mod scheduling;
+pub mod secrets;
+pub mod sharing;
+pub mod signing;
+pub mod stats;
+pub mod suggestions;
+pub mod sync;
+pub mod templates;
+pub mod timezones;
+pub mod titles;
+pub mod todos;
+pub mod totp;
+#[cfg(feature = "ui")]
+pub mod ui;
+pub mod undo;
+pub mod urls;
+pub mod versioning;
+pub mod webhooks;
+pub mod workflow;
+pub mod workspaces;
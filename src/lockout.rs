@@ -0,0 +1,106 @@
+//! Temporary lockouts after repeated failed logins, per account and per
+//! client address, to slow down password guessing.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::LockoutConfig;
+use crate::error::AppError;
+use crate::repository::LoginAttemptRepository;
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email)
+}
+
+fn client_key(client: &str) -> String {
+    format!("client:{}", client)
+}
+
+#[derive(Clone)]
+pub struct LoginGuard {
+    repository: Arc<dyn LoginAttemptRepository>,
+    config: LockoutConfig,
+}
+
+impl LoginGuard {
+    pub fn new(repository: Arc<dyn LoginAttemptRepository>, config: &LockoutConfig) -> Self {
+        LoginGuard {
+            repository,
+            config: config.clone(),
+        }
+    }
+
+    fn duration(&self, duration: std::time::Duration) -> chrono::Duration {
+        chrono::Duration::from_std(duration).unwrap_or_default()
+    }
+
+    /// The account's and the client's keys, with their thresholds.
+    fn keys(&self, email: &str, client: &str) -> [(&'static str, String, usize); 2] {
+        [
+            ("account", account_key(email), self.config.account_threshold),
+            ("client", client_key(client), self.config.client_threshold),
+        ]
+    }
+
+    /// Refuses a login to `email` from `client` while either is locked
+    /// out, whether or not the password is right.
+    pub async fn check(&self, email: &str, client: &str) -> Result<(), AppError> {
+        let now = Utc::now();
+        for (_, key, threshold) in self.keys(email, client) {
+            if threshold == 0 {
+                continue;
+            }
+            let locked_until: Option<DateTime<Utc>> = self.repository.locked_until(&key).await?;
+            if let Some(until) = locked_until.filter(|until| *until > now) {
+                return Err(AppError::Locked(
+                    "Too many failed logins, try again later".to_string(),
+                    until,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a failed login, locking the account or the client out once
+    /// it reaches its threshold within the window.
+    pub async fn record_failure(&self, email: &str, client: &str) -> Result<(), AppError> {
+        let now = Utc::now();
+        let since = now - self.duration(self.config.window);
+        for (kind, key, threshold) in self.keys(email, client) {
+            if threshold == 0 {
+                continue;
+            }
+            let failures = self.repository.record_failure(&key, now, since).await?;
+            if failures >= threshold {
+                let until = now + self.duration(self.config.duration);
+                self.repository.lock(&key, until).await?;
+                log::warn!(
+                    "event=login_locked lock={} failures={} until={}",
+                    kind,
+                    failures,
+                    until.to_rfc3339()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the failed logins of an account that just logged in.
+    pub async fn record_success(&self, email: &str) -> Result<(), AppError> {
+        if self.config.account_threshold > 0 {
+            self.repository.clear(&account_key(email)).await?;
+        }
+        Ok(())
+    }
+
+    /// Lifts the lockout of an account and forgets its failed logins.
+    pub async fn clear_account(&self, email: &str) -> Result<(), AppError> {
+        Ok(self.repository.clear(&account_key(email)).await?)
+    }
+
+    /// Lifts the lockout of a client address and forgets its failed logins.
+    pub async fn clear_client(&self, client: &str) -> Result<(), AppError> {
+        Ok(self.repository.clear(&client_key(client)).await?)
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes};
+use actix_web::HttpRequest;
+use serde_json::Value;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::auth;
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Header carrying the request ID. A valid ID sent by the client (or a
+/// proxy in front of us) is kept so logs can be correlated across services;
+/// otherwise a new one is generated. Either way it is echoed in the response.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest client-supplied request ID that is accepted.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Larger bodies are never sampled, so uploads are not buffered for logging.
+const MAX_LOGGED_BODY_BYTES: u64 = 16 * 1024;
+
+/// Replaces the values of redacted fields in logged bodies.
+const REDACTED: &str = "[REDACTED]";
+
+/// Installs the global subscriber. Records from the `log` crate are
+/// forwarded to it, so the existing `log::` calls need no changes.
+pub fn init(config: &LoggingConfig) {
+    let (filter, invalid) = match EnvFilter::try_new(&config.level) {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new("info"), Some(e)),
+    };
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match config.format {
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+        LogFormat::Pretty => builder.init(),
+    }
+
+    if let Some(e) = invalid {
+        log::warn!(
+            "event=invalid_log_level level={:?} error=\"{}\"",
+            config.level,
+            e
+        );
+    }
+}
+
+/// Middleware that runs each request in a span holding its ID, method,
+/// route, path and caller, so every event logged while handling it carries
+/// them, and logs one line with the status and latency when it completes.
+/// A sample of JSON request bodies, as configured in the `LoggingConfig`
+/// app data, is logged with sensitive fields redacted.
+pub async fn log_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    // The route template rather than the path, so IDs do not explode the
+    // cardinality of the field.
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let caller = caller(req.request());
+    let body = match req.app_data::<web::Data<LoggingConfig>>().cloned() {
+        Some(config) if rand::random::<f64>() < config.body_sample_rate => {
+            sample_body(&mut req, &config.redact_fields).await
+        }
+        _ => None,
+    };
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        route = %route,
+        path = %req.path(),
+        caller = %caller,
+    );
+    let started = Instant::now();
+    let result = next.call(req).instrument(span.clone()).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let _entered = span.enter();
+    match result {
+        Ok(mut res) => {
+            let status = res.status().as_u16();
+            match res.response().error() {
+                Some(e) if res.status().is_server_error() => tracing::error!(
+                    status,
+                    latency_ms,
+                    body = body.as_deref(),
+                    error = %e,
+                    "request failed"
+                ),
+                _ => tracing::info!(
+                    status,
+                    latency_ms,
+                    body = body.as_deref(),
+                    "request completed"
+                ),
+            }
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static("x-request-id"), value);
+            }
+            Ok(res)
+        }
+        Err(e) => {
+            let status = e.as_response_error().status_code().as_u16();
+            tracing::warn!(
+                status,
+                latency_ms,
+                body = body.as_deref(),
+                error = %e,
+                "request rejected"
+            );
+            Err(e)
+        }
+    }
+}
+
+/// The authenticated user's ID, `anonymous` without a bearer token, or
+/// `invalid-token` when the token does not verify.
+fn caller(req: &HttpRequest) -> String {
+    if !req.headers().contains_key(header::AUTHORIZATION) {
+        return "anonymous".to_string();
+    }
+    auth::authenticate(req)
+        .map(|user| user.id)
+        .unwrap_or_else(|_| "invalid-token".to_string())
+}
+
+/// Reads a small JSON body for logging and puts it back for the handler.
+/// Other bodies are left alone and not logged.
+async fn sample_body(req: &mut ServiceRequest, redact_fields: &[String]) -> Option<String> {
+    let is_json = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if !is_json || length.is_none_or(|length| length > MAX_LOGGED_BODY_BYTES) {
+        return None;
+    }
+
+    let bytes = req.extract::<Bytes>().await.ok()?;
+    req.set_payload(bytes.clone().into());
+
+    let mut value: Value = serde_json::from_slice(&bytes).ok()?;
+    redact(&mut value, redact_fields);
+    Some(value.to_string())
+}
+
+fn redact(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if fields.iter().any(|field| field.eq_ignore_ascii_case(key)) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value, fields);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| redact(value, fields)),
+        _ => {}
+    }
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
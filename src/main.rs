@@ -1,56 +1,86 @@
-mod handler;
-mod model;
-mod response;
-
 use actix_cors::Cors;
-use actix_web::middleware::Logger;
+use actix_web::middleware;
 use actix_web::{http::header, web, App, HttpServer};
-use model::AppState;
-use scylla::{Session, SessionBuilder};
-
-async fn create_db_session() -> Session {
-    SessionBuilder::new()
-        .known_node("127.0.0.1:9042")
-        .build()
-        .await
-        .expect("Failed to connect to Scylla")
-}
+use simple_api_actix_web::app::build_state;
+use simple_api_actix_web::config::Config;
+use simple_api_actix_web::{
+    build_info, casing, compression, concurrency, consistency, csrf, formats, handler, i18n,
+    logging, maintenance, preferences, signing, workspaces,
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    if std::env::var_os("RUST_LOG").is_none() {
-        std::env::set_var("RUST_LOG", "actix_web=info");
-    }
-    env_logger::init();
-
-    // Connect to Scylla
-    let session = create_db_session().await;
-    println!("✅ Connected to Scylla database");
+    let config = Config::from_env();
+    logging::init(&config.logging);
 
-    let app_state = AppState::new(session);
+    let app_state = build_state(&config).await?;
     let app_data = web::Data::new(app_state);
 
-    println!("🚀 Server started successfully");
+    let field_case = config.server.field_case;
+    let trusted_proxies = config.server.trusted_proxies.clone();
+    let compression = config.compression;
+    let cookies = config.auth.cookies;
+    let logging_config = web::Data::new(config.logging.clone());
+    #[cfg(feature = "chaos")]
+    let fault_injector = web::Data::new(simple_api_actix_web::chaos::FaultInjector::new());
+
+    log::info!(
+        "event=server_started host={} port={} version={} backend={:?} features={}",
+        config.server.host,
+        config.server.port,
+        build_info::version(),
+        config.storage.backend,
+        build_info::features().join(",")
+    );
+    #[cfg(feature = "chaos")]
+    log::warn!("event=fault_injection_available path=/api/dev/chaos");
 
     HttpServer::new(move || {
         let cors = Cors::default()
             .allowed_origin("http://localhost:3000")
             .allowed_origin("http://localhost:3000/")
-            .allowed_methods(vec!["GET", "POST", "PATCH", "DELETE"])
+            .allowed_methods(vec!["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE"])
             .allowed_headers(vec![
                 header::CONTENT_TYPE,
                 header::AUTHORIZATION,
                 header::ACCEPT,
             ])
+            .allowed_header(workspaces::WORKSPACE_HEADER)
+            .allowed_header(casing::FIELD_CASE_HEADER)
+            .allowed_header(logging::REQUEST_ID_HEADER)
+            .allowed_header(csrf::CSRF_HEADER)
+            .allowed_header(consistency::READ_YOUR_WRITES_HEADER)
             .supports_credentials();
         
-        App::new()
+        let app = App::new()
             .app_data(app_data.clone())
-            .configure(handler::config)
+            .app_data(field_case)
+            .app_data(trusted_proxies.clone())
+            .app_data(compression)
+            .app_data(cookies)
+            .app_data(logging_config.clone())
+            .configure(handler::config);
+        // Innermost, so injected faults are logged and counted like real ones.
+        #[cfg(feature = "chaos")]
+        let app = app
+            .app_data(fault_injector.clone())
+            .wrap(middleware::from_fn(simple_api_actix_web::chaos::inject_faults));
+
+        app.wrap(middleware::from_fn(concurrency::limit_concurrency))
+            .wrap(middleware::from_fn(maintenance::refuse_during_maintenance))
+            .wrap(middleware::from_fn(preferences::load_user_settings))
+            .wrap(middleware::from_fn(consistency::read_your_writes))
+            .wrap(middleware::from_fn(csrf::protect))
+            .wrap(middleware::from_fn(casing::apply_field_case))
+            .wrap(middleware::from_fn(formats::negotiate_format))
+            .wrap(middleware::from_fn(compression::compress_responses))
+            .wrap(middleware::from_fn(signing::verify_signatures))
+            .wrap(middleware::from_fn(i18n::negotiate_language))
             .wrap(cors)
-            .wrap(Logger::default())
+            .wrap(middleware::from_fn(logging::log_requests))
+            .wrap(middleware::from_fn(build_info::add_version_header))
     })
-    .bind(("127.0.0.1", 8000))?
+    .bind((config.server.host.as_str(), config.server.port))?
     .run()
     .await
 }
\ No newline at end of file
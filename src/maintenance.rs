@@ -0,0 +1,298 @@
+//! Maintenance mode, for work on the database or a migration that must not
+//! race with writes. While an admin has it on, requests are refused with a
+//! 503, its `Retry-After` and the message the admin gave, so none of them
+//! reach the database. Reads can go on being served from a cache of the
+//! responses last read before, holding `MAINTENANCE_READ_CACHE_SIZE` of
+//! them, unless the admin turned reads off or the cache is off; one not in
+//! the cache is refused like a write. The admin routes and sign-in stay
+//! open, so maintenance can be ended. It is stored in the database, and
+//! each instance checks for it every `MAINTENANCE_REFRESH_SECS`.
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use actix_web::body::{self, BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes};
+use actix_web::HttpResponse;
+use chrono::Utc;
+use lru::LruCache;
+
+use crate::config::MaintenanceConfig;
+use crate::error::AppError;
+use crate::model::{AppState, Maintenance, StartMaintenanceSchema};
+use crate::repository::{MaintenanceRepository, RepositoryError};
+
+/// Routes served during maintenance whatever the method, by suffix.
+const ALWAYS_OPEN: [&str; 5] = [
+    "/auth/login",
+    "/auth/login/totp",
+    "/auth/refresh",
+    "/healthchecker",
+    "/version",
+];
+
+/// Largest response body kept to be served during maintenance.
+const MAX_CACHED_READ_BYTES: u64 = 1024 * 1024;
+
+/// Who a request was made as: its `Authorization` and `Cookie` headers.
+type Credentials = (Option<HeaderValue>, Option<HeaderValue>);
+
+/// What a read's response depends on besides the database.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ReadKey {
+    credentials: Credentials,
+    uri: String,
+    accept: Option<HeaderValue>,
+    language: Option<HeaderValue>,
+}
+
+impl ReadKey {
+    fn of(req: &ServiceRequest) -> Self {
+        let headers = req.headers();
+        ReadKey {
+            credentials: (
+                headers.get(header::AUTHORIZATION).cloned(),
+                headers.get(header::COOKIE).cloned(),
+            ),
+            uri: req.uri().to_string(),
+            accept: headers.get(header::ACCEPT).cloned(),
+            language: headers.get(header::ACCEPT_LANGUAGE).cloned(),
+        }
+    }
+}
+
+/// The responses to recent reads, replaced each time they are read again
+/// and dropped when the same caller changes something, so a read during
+/// maintenance sees what it last did. Changes made by anyone else, e.g. to
+/// a shared todo, are only seen once the read is made again.
+struct ReadCache {
+    entries: Mutex<LruCache<ReadKey, (HeaderMap, Bytes)>>,
+}
+
+impl ReadCache {
+    fn get(&self, key: &ReadKey) -> Option<HttpResponse> {
+        let (headers, body) = self.entries.lock().unwrap().get(key).cloned()?;
+        let mut response = HttpResponse::Ok().body(body);
+        *response.headers_mut() = headers;
+        Some(response)
+    }
+
+    fn forget(&self, credentials: &Credentials) {
+        let mut entries = self.entries.lock().unwrap();
+        let stale: Vec<ReadKey> = entries
+            .iter()
+            .filter(|(key, _)| key.credentials == *credentials)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            entries.pop(&key);
+        }
+    }
+
+    /// Keeps `res` if it is a complete read worth serving again.
+    async fn record<B: MessageBody + 'static>(
+        &self,
+        key: ReadKey,
+        res: ServiceResponse<B>,
+    ) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+        let is_small = matches!(
+            res.response().body().size(),
+            BodySize::Sized(size) if size <= MAX_CACHED_READ_BYTES
+        );
+        if res.status() != StatusCode::OK
+            || !is_small
+            || res.headers().contains_key(header::SET_COOKIE)
+        {
+            return Ok(res.map_into_boxed_body());
+        }
+
+        let (req, res) = res.into_parts();
+        let (res, body) = res.into_parts();
+        let bytes = body::to_bytes(body).await.map_err(|e| {
+            let e: Box<dyn std::error::Error> = e.into();
+            AppError::Internal(e.to_string())
+        })?;
+        self.entries
+            .lock()
+            .unwrap()
+            .put(key, (res.headers().clone(), bytes.clone()));
+        Ok(ServiceResponse::new(
+            req,
+            res.set_body(bytes).map_into_boxed_body(),
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct MaintenanceMode {
+    repository: Arc<dyn MaintenanceRepository>,
+    /// The maintenance under way as last read.
+    current: Arc<RwLock<Option<Maintenance>>>,
+    /// `None` when `MAINTENANCE_READ_CACHE_SIZE` is zero.
+    reads: Option<Arc<ReadCache>>,
+    config: MaintenanceConfig,
+}
+
+impl MaintenanceMode {
+    pub fn new(config: &MaintenanceConfig, repository: Arc<dyn MaintenanceRepository>) -> Self {
+        MaintenanceMode {
+            repository,
+            current: Arc::new(RwLock::new(None)),
+            reads: NonZeroUsize::new(config.read_cache_size).map(|capacity| {
+                Arc::new(ReadCache {
+                    entries: Mutex::new(LruCache::new(capacity)),
+                })
+            }),
+            config: config.clone(),
+        }
+    }
+
+    pub fn current(&self) -> Option<Maintenance> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Starts maintenance, or changes the one under way. This instance
+    /// follows at once, the others on their next refresh.
+    pub async fn enable(
+        &self,
+        started_by: &str,
+        request: StartMaintenanceSchema,
+    ) -> Result<Maintenance, RepositoryError> {
+        let retry_after_secs = request.retry_after_secs.unwrap_or(
+            self.config
+                .retry_after
+                .as_secs()
+                .try_into()
+                .unwrap_or(u32::MAX),
+        );
+        let maintenance = Maintenance {
+            message: request
+                .message
+                .unwrap_or_else(|| self.config.message.clone()),
+            retry_after_secs,
+            allow_reads: request.allow_reads.unwrap_or(true),
+            started_by: started_by.to_string(),
+            started_at: Utc::now(),
+        };
+        self.repository.start(&maintenance).await?;
+        *self.current.write().unwrap() = Some(maintenance.clone());
+        Ok(maintenance)
+    }
+
+    pub async fn disable(&self) -> Result<(), RepositoryError> {
+        self.repository.end().await?;
+        *self.current.write().unwrap() = None;
+        Ok(())
+    }
+
+    /// Reads the maintenance under way again, keeping what was last read if
+    /// that fails.
+    pub async fn refresh(&self) {
+        let current = match self.repository.current().await {
+            Ok(current) => current,
+            Err(e) => {
+                log::warn!("event=maintenance_refresh_failed error=\"{}\"", e);
+                return;
+            }
+        };
+        let previous = std::mem::replace(&mut *self.current.write().unwrap(), current.clone());
+        match (previous, current) {
+            (None, Some(maintenance)) => log::info!(
+                "event=maintenance_observed started_by={} allow_reads={}",
+                maintenance.started_by,
+                maintenance.allow_reads
+            ),
+            (Some(_), None) => log::info!("event=maintenance_over"),
+            _ => {}
+        }
+    }
+
+    /// Refreshes every `MAINTENANCE_REFRESH_SECS`.
+    pub fn start(&self) {
+        let mode = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(mode.config.refresh_interval).await;
+                mode.refresh().await;
+            }
+        });
+    }
+
+    /// Refuses a request to `route` (its template, e.g.
+    /// `/api/todos/{id}`) while maintenance is under way, unless it is to
+    /// a route that stays open. Reads are refused here too; see
+    /// [`MaintenanceMode::cached_read`].
+    pub fn check(&self, method: &Method, route: &str) -> Result<(), AppError> {
+        let current = self.current.read().unwrap();
+        let Some(maintenance) = current.as_ref() else {
+            return Ok(());
+        };
+        let open = *method == Method::OPTIONS
+            || route.contains("/admin/")
+            || ALWAYS_OPEN.iter().any(|suffix| route.ends_with(suffix));
+        if open {
+            return Ok(());
+        }
+        Err(AppError::Maintenance(
+            maintenance.message.clone(),
+            Duration::from_secs(maintenance.retry_after_secs.into()),
+        ))
+    }
+
+    /// The response last read for `req`, if it is a read, reads are
+    /// allowed during the maintenance under way and the cache has it.
+    fn cached_read(&self, req: &ServiceRequest) -> Option<HttpResponse> {
+        let allow_reads = self
+            .current
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|maintenance| maintenance.allow_reads);
+        if !allow_reads || req.method() != Method::GET {
+            return None;
+        }
+        self.reads.as_ref()?.get(&ReadKey::of(req))
+    }
+}
+
+/// Middleware refusing requests the `AppState` maintenance mode does not
+/// let through, and answering reads it allows from its cache. Requests
+/// matching no route go on to their 404.
+pub async fn refuse_during_maintenance(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if let Some(route) = req.match_pattern() {
+        if let Err(refused) = state.maintenance.check(req.method(), &route) {
+            return match state.maintenance.cached_read(&req) {
+                Some(response) => Ok(req.into_response(response)),
+                None => Err(refused.into()),
+            };
+        }
+    }
+
+    let Some(reads) = state.maintenance.reads.clone() else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let method = req.method().clone();
+    let key = ReadKey::of(&req);
+    let res = next.call(req).await?;
+    match method {
+        Method::GET => reads.record(key, res).await,
+        Method::HEAD | Method::OPTIONS => Ok(res.map_into_boxed_body()),
+        _ => {
+            if res.status().is_success() {
+                reads.forget(&key.credentials);
+            }
+            Ok(res.map_into_boxed_body())
+        }
+    }
+}
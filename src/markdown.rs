@@ -0,0 +1,75 @@
+//! Rendering of todo content, which is Markdown, into HTML for clients
+//! that ask for it with `?render=html`. The HTML is sanitized, so it can
+//! be inserted into a page as it is, and cached per todo version.
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use pulldown_cmark::{html, Options, Parser};
+use serde::Deserialize;
+
+use crate::model::{Todo, TodoId};
+
+/// How read endpoints return `content`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Render {
+    /// Adds `contentHtml`, the content rendered and sanitized.
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenderQuery {
+    pub render: Option<Render>,
+}
+
+/// Renders Markdown to HTML, keeping only the tags and attributes
+/// `ammonia` considers safe; scripts, event handlers and `javascript:`
+/// links are dropped.
+pub fn render(markdown: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, Options::all()));
+    ammonia::clean(&unsafe_html)
+}
+
+/// Rendered content of recently read todos. A todo's version is its
+/// `updated_at`, so an edit makes its old entry unreachable, and the least
+/// recently used entries go once the cache is full.
+pub struct RenderCache {
+    entries: Option<Mutex<LruCache<Version, Arc<str>>>>,
+}
+
+/// A todo's ID and `updated_at`.
+type Version = (TodoId, Option<DateTime<Utc>>);
+
+impl RenderCache {
+    /// Holds up to `capacity` todos' HTML; zero renders every time.
+    pub fn new(capacity: usize) -> Self {
+        RenderCache {
+            entries: NonZeroUsize::new(capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    /// Fills in `content_html` on each todo.
+    pub fn apply(&self, todos: &mut [Todo]) {
+        for todo in todos {
+            todo.content_html = Some(self.html(todo).to_string());
+        }
+    }
+
+    fn html(&self, todo: &Todo) -> Arc<str> {
+        let (Some(entries), Some(id)) = (&self.entries, todo.id) else {
+            return render(&todo.content).into();
+        };
+        let key = (id, todo.updated_at);
+        if let Some(html) = entries.lock().unwrap().get(&key) {
+            return html.clone();
+        }
+        let html: Arc<str> = render(&todo.content).into();
+        entries.lock().unwrap().put(key, html.clone());
+        html
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use scylla::Session;
+
+/// Latency samples kept per operation for percentile estimates.
+const SAMPLE_WINDOW: usize = 512;
+
+/// Per-operation database call counts and recent latencies, recorded by the
+/// resilient repository wrapper, how todo lookups were coalesced, plus the
+/// Scylla cluster's state once a session is being watched.
+#[derive(Default)]
+pub struct QueryMetrics {
+    operations: Mutex<BTreeMap<&'static str, OperationMetrics>>,
+    in_flight: AtomicUsize,
+    lookups_queried: AtomicU64,
+    lookups_shared: AtomicU64,
+    session: OnceLock<Arc<Session>>,
+}
+
+/// Counts a call as in flight until dropped, including when a timeout
+/// abandons it.
+pub struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct OperationMetrics {
+    calls: u64,
+    errors: u64,
+    max: Duration,
+    recent: VecDeque<Duration>,
+}
+
+/// The Scylla cluster as the driver sees it. The driver's counters cover
+/// every statement it sent, including retries and speculative executions.
+#[derive(Debug, Clone)]
+pub struct ClusterSnapshot {
+    pub nodes: usize,
+    pub nodes_up: usize,
+    pub queries: u64,
+    pub errors: u64,
+    pub retries: u64,
+}
+
+/// A node of the Scylla cluster, as the driver last saw it.
+#[derive(Debug, Clone)]
+pub struct NodeSnapshot {
+    pub address: String,
+    pub datacenter: Option<String>,
+    pub rack: Option<String>,
+    pub up: bool,
+}
+
+/// Lookups of a todo by ID: those that queried the database, and those that
+/// got the result of a concurrent lookup's query instead.
+#[derive(Debug, Clone, Copy)]
+pub struct LookupSnapshot {
+    pub queried: u64,
+    pub shared: u64,
+}
+
+/// Point-in-time view of one operation's metrics. Percentiles cover the
+/// most recent calls only; `calls`, `errors` and `max` cover the process
+/// lifetime.
+#[derive(Debug, Clone)]
+pub struct OperationSnapshot {
+    pub operation: &'static str,
+    pub calls: u64,
+    pub errors: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl QueryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a call as in flight for as long as the returned guard lives.
+    pub fn begin(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(&self.in_flight)
+    }
+
+    /// Database calls currently running.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Reports the state of `session`'s cluster from now on; only the
+    /// first session watched counts.
+    pub fn watch_cluster(&self, session: Arc<Session>) {
+        let _ = self.session.set(session);
+    }
+
+    /// The watched cluster's state, `None` for the SQL and memory backends.
+    pub fn cluster(&self) -> Option<ClusterSnapshot> {
+        let session = self.session.get()?;
+        let cluster = session.get_cluster_data();
+        let nodes = cluster.get_nodes_info();
+        let metrics = session.get_metrics();
+        Some(ClusterSnapshot {
+            nodes: nodes.len(),
+            nodes_up: nodes.iter().filter(|node| !node.is_down()).count(),
+            queries: metrics.get_queries_num() + metrics.get_queries_iter_num(),
+            errors: metrics.get_errors_num() + metrics.get_errors_iter_num(),
+            retries: metrics.get_retries_num(),
+        })
+    }
+
+    /// The watched cluster's nodes, `None` for the SQL and memory backends.
+    pub fn nodes(&self) -> Option<Vec<NodeSnapshot>> {
+        let cluster = self.session.get()?.get_cluster_data();
+        Some(
+            cluster
+                .get_nodes_info()
+                .iter()
+                .map(|node| NodeSnapshot {
+                    address: node.address.to_string(),
+                    datacenter: node.datacenter.clone(),
+                    rack: node.rack.clone(),
+                    up: !node.is_down(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Counts a lookup by ID, `shared` when it joined another's query.
+    pub fn record_lookup(&self, shared: bool) {
+        let counter = if shared {
+            &self.lookups_shared
+        } else {
+            &self.lookups_queried
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn lookups(&self) -> LookupSnapshot {
+        LookupSnapshot {
+            queried: self.lookups_queried.load(Ordering::Relaxed),
+            shared: self.lookups_shared.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn record(&self, operation: &'static str, elapsed: Duration, ok: bool) {
+        let mut operations = self.operations.lock().unwrap();
+        let metrics = operations.entry(operation).or_default();
+        metrics.calls += 1;
+        if !ok {
+            metrics.errors += 1;
+        }
+        metrics.max = metrics.max.max(elapsed);
+        if metrics.recent.len() == SAMPLE_WINDOW {
+            metrics.recent.pop_front();
+        }
+        metrics.recent.push_back(elapsed);
+    }
+
+    /// Snapshots of every operation seen so far, by operation name.
+    pub fn snapshot(&self) -> Vec<OperationSnapshot> {
+        let operations = self.operations.lock().unwrap();
+        operations
+            .iter()
+            .map(|(operation, metrics)| {
+                let mut sorted: Vec<Duration> = metrics.recent.iter().copied().collect();
+                sorted.sort_unstable();
+                OperationSnapshot {
+                    operation,
+                    calls: metrics.calls,
+                    errors: metrics.errors,
+                    p50: percentile(&sorted, 50),
+                    p95: percentile(&sorted, 95),
+                    p99: percentile(&sorted, 99),
+                    max: metrics.max,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * pct).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
@@ -0,0 +1,538 @@
+use chrono::Utc;
+use futures_util::StreamExt;
+use scylla::frame::value::CqlTimestamp;
+use scylla::transport::errors::QueryError;
+use scylla::transport::iterator::NextRowError;
+use scylla::{IntoTypedRows, Session};
+
+use crate::config::Replication;
+
+/// A versioned CQL script from `migrations/scylla`, embedded at compile time.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub cql: &'static str,
+    /// Tables whose rows are copied once the script has run, for changes
+    /// CQL cannot make in place such as changing a key column's type.
+    pub copies: &'static [TableCopy],
+}
+
+/// Copies every row of `from` into `to`. Rows go through CQL's JSON
+/// support, which converts between compatible column types (a text UUID
+/// into `uuid`, say); rows that do not convert are logged and skipped.
+pub struct TableCopy {
+    pub from: &'static str,
+    pub to: &'static str,
+    /// Selectors read from `from`, `*` for every column. Aliases rename
+    /// columns to those of `to`.
+    pub columns: &'static str,
+}
+
+/// How a database's applied migrations compare to those this build has.
+#[derive(Debug, Default)]
+pub struct SchemaStatus {
+    /// The highest version applied, `None` before any.
+    pub latest: Option<i64>,
+    /// Migrations not applied yet, as version and name.
+    pub pending: Vec<(i64, String)>,
+    /// Applied migrations whose script has changed since. Scylla keeps no
+    /// checksums, so only the SQL backends report these.
+    pub modified: Vec<i64>,
+    /// Applied versions this build does not know, e.g. from a newer release.
+    pub unknown: Vec<i64>,
+}
+
+impl SchemaStatus {
+    pub fn is_current(&self) -> bool {
+        self.pending.is_empty() && self.modified.is_empty() && self.unknown.is_empty()
+    }
+}
+
+/// Compares the migrations sqlx recorded as applied, by version and
+/// checksum, with those `migrator` embeds.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+pub fn compare(migrator: &sqlx::migrate::Migrator, applied: &[(i64, Vec<u8>)]) -> SchemaStatus {
+    let known = |version: i64| migrator.iter().find(|m| m.version == version);
+    SchemaStatus {
+        latest: applied.iter().map(|(version, _)| *version).max(),
+        pending: migrator
+            .iter()
+            .filter(|m| !applied.iter().any(|(version, _)| *version == m.version))
+            .map(|m| (m.version, m.description.to_string()))
+            .collect(),
+        modified: applied
+            .iter()
+            .filter(|(version, checksum)| {
+                known(*version).is_some_and(|m| m.checksum.as_ref() != checksum.as_slice())
+            })
+            .map(|(version, _)| *version)
+            .collect(),
+        unknown: applied
+            .iter()
+            .filter(|(version, _)| known(*version).is_none())
+            .map(|(version, _)| *version)
+            .collect(),
+    }
+}
+
+/// Every Scylla migration, in the order it must be applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_todos",
+        cql: include_str!("../migrations/scylla/0001_create_todos.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 2,
+        name: "add_archived",
+        cql: include_str!("../migrations/scylla/0002_add_archived.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 3,
+        name: "add_recurrence",
+        cql: include_str!("../migrations/scylla/0003_add_recurrence.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 4,
+        name: "add_reminders",
+        cql: include_str!("../migrations/scylla/0004_add_reminders.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 5,
+        name: "create_webhooks",
+        cql: include_str!("../migrations/scylla/0005_create_webhooks.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 6,
+        name: "create_notification_settings",
+        cql: include_str!("../migrations/scylla/0006_create_notification_settings.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 7,
+        name: "create_jobs",
+        cql: include_str!("../migrations/scylla/0007_create_jobs.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 8,
+        name: "add_users",
+        cql: include_str!("../migrations/scylla/0008_add_users.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 9,
+        name: "add_workspaces",
+        cql: include_str!("../migrations/scylla/0009_add_workspaces.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 10,
+        name: "add_todo_acl",
+        cql: include_str!("../migrations/scylla/0010_add_todo_acl.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 11,
+        name: "add_comments",
+        cql: include_str!("../migrations/scylla/0011_add_comments.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 12,
+        name: "add_attachments",
+        cql: include_str!("../migrations/scylla/0012_add_attachments.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 13,
+        name: "uuid_todo_ids",
+        cql: include_str!("../migrations/scylla/0013_uuid_todo_ids.cql"),
+        // Todo IDs were stored as text; the uuid-keyed tables replace the
+        // originals, which are left in place and no longer read.
+        copies: &[
+            TableCopy {
+                from: "todo_db.todos",
+                to: "todo_db.todos_v2",
+                columns: "*",
+            },
+            TableCopy {
+                from: "todo_db.todos_by_workspace",
+                to: "todo_db.todos_by_workspace_v2",
+                columns: "*",
+            },
+            TableCopy {
+                from: "todo_db.todo_acl",
+                to: "todo_db.todo_acl_v2",
+                columns: "*",
+            },
+            TableCopy {
+                from: "todo_db.comments",
+                to: "todo_db.comments_v2",
+                columns: "*",
+            },
+            TableCopy {
+                from: "todo_db.attachments",
+                to: "todo_db.attachments_v2",
+                columns: "*",
+            },
+        ],
+    },
+    Migration {
+        version: 14,
+        name: "add_todo_counts",
+        cql: include_str!("../migrations/scylla/0014_add_todo_counts.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 15,
+        name: "add_todos_by_title",
+        cql: include_str!("../migrations/scylla/0015_add_todos_by_title.cql"),
+        copies: &[],
+    },
+    // Unowned todos have no partition to go in, so their rows are skipped.
+    Migration {
+        version: 16,
+        name: "add_todos_by_owner",
+        cql: include_str!("../migrations/scylla/0016_add_todos_by_owner.cql"),
+        copies: &[TableCopy {
+            from: "todo_db.todos_v2",
+            to: "todo_db.todos_by_owner",
+            columns: "owner_id, created_at, id AS todo_id",
+        }],
+    },
+    Migration {
+        version: 17,
+        name: "add_templates",
+        cql: include_str!("../migrations/scylla/0017_add_templates.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 18,
+        name: "add_projects",
+        cql: include_str!("../migrations/scylla/0018_add_projects.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 19,
+        name: "add_todo_position",
+        cql: include_str!("../migrations/scylla/0019_add_todo_position.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 20,
+        name: "add_todo_status",
+        cql: include_str!("../migrations/scylla/0020_add_todo_status.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 21,
+        name: "add_todo_assignee",
+        cql: include_str!("../migrations/scylla/0021_add_todo_assignee.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 22,
+        name: "add_activity",
+        cql: include_str!("../migrations/scylla/0022_add_activity.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 23,
+        name: "add_undo_log",
+        cql: include_str!("../migrations/scylla/0023_add_undo_log.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 24,
+        name: "add_todo_changes",
+        cql: include_str!("../migrations/scylla/0024_add_todo_changes.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 25,
+        name: "add_refresh_tokens",
+        cql: include_str!("../migrations/scylla/0025_add_refresh_tokens.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 26,
+        name: "add_user_identities",
+        cql: include_str!("../migrations/scylla/0026_add_user_identities.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 27,
+        name: "add_password_reset_tokens",
+        cql: include_str!("../migrations/scylla/0027_add_password_reset_tokens.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 28,
+        name: "add_login_lockouts",
+        cql: include_str!("../migrations/scylla/0028_add_login_lockouts.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 29,
+        name: "add_totp",
+        cql: include_str!("../migrations/scylla/0029_add_totp.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 30,
+        name: "add_sessions",
+        cql: include_str!("../migrations/scylla/0030_add_sessions.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 31,
+        name: "add_outbox",
+        cql: include_str!("../migrations/scylla/0031_add_outbox.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 32,
+        name: "add_outbox_consumer",
+        cql: include_str!("../migrations/scylla/0032_add_outbox_consumer.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 33,
+        name: "add_title_key",
+        cql: include_str!("../migrations/scylla/0033_add_title_key.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 34,
+        name: "create_user_settings",
+        cql: include_str!("../migrations/scylla/0034_create_user_settings.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 35,
+        name: "add_user_preferences",
+        cql: include_str!("../migrations/scylla/0035_add_user_preferences.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 36,
+        name: "add_user_profiles",
+        cql: include_str!("../migrations/scylla/0036_add_user_profiles.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 37,
+        name: "add_leases",
+        cql: include_str!("../migrations/scylla/0037_add_leases.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 38,
+        name: "add_feature_flags",
+        cql: include_str!("../migrations/scylla/0038_add_feature_flags.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 39,
+        name: "add_maintenance",
+        cql: include_str!("../migrations/scylla/0039_add_maintenance.cql"),
+        copies: &[],
+    },
+    Migration {
+        version: 40,
+        name: "index_password_reset_tokens_by_user",
+        cql: include_str!("../migrations/scylla/0040_index_password_reset_tokens_by_user.cql"),
+        copies: &[],
+    },
+];
+
+/// Creates the `todo_db` keyspace with `replication` unless it exists.
+/// An existing keyspace's replication is left as it is.
+pub async fn create_keyspace(
+    session: &Session,
+    replication: &Replication,
+) -> Result<(), QueryError> {
+    let options = match replication {
+        Replication::Simple { factor } => {
+            format!(
+                "'class': 'SimpleStrategy', 'replication_factor': {}",
+                factor
+            )
+        }
+        Replication::NetworkTopology { datacenters } => {
+            let factors = datacenters
+                .iter()
+                .map(|(name, factor)| format!("'{}': {}", name.replace('\'', "''"), factor))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("'class': 'NetworkTopologyStrategy', {}", factors)
+        }
+    };
+    session
+        .query(
+            format!(
+                "CREATE KEYSPACE IF NOT EXISTS todo_db WITH replication = {{{}}}",
+                options
+            ),
+            &[],
+        )
+        .await?;
+    session.await_schema_agreement().await?;
+    log::info!(
+        "event=keyspace_ready keyspace=todo_db replication={:?}",
+        replication
+    );
+    Ok(())
+}
+
+/// Applies pending migrations and records them in `todo_db.schema_migrations`.
+/// Returns the versions that were applied by this call.
+pub async fn run(session: &Session) -> Result<Vec<i32>, QueryError> {
+    session
+        .query(
+            "CREATE TABLE IF NOT EXISTS todo_db.schema_migrations (version int PRIMARY KEY, name text, applied_at timestamp)",
+            &[],
+        )
+        .await?;
+
+    let applied = applied_versions(session).await?;
+    let mut newly_applied = Vec::new();
+
+    for migration in MIGRATIONS {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+
+        log::info!(
+            "event=migration_apply version={} name={}",
+            migration.version,
+            migration.name
+        );
+        for statement in statements(migration.cql) {
+            session.query(statement, &[]).await?;
+        }
+        session.await_schema_agreement().await?;
+        for copy in migration.copies {
+            copy_table(session, copy).await?;
+        }
+
+        session
+            .query(
+                "INSERT INTO todo_db.schema_migrations (version, name, applied_at) VALUES (?, ?, ?)",
+                (
+                    migration.version,
+                    migration.name,
+                    CqlTimestamp(Utc::now().timestamp_millis()),
+                ),
+            )
+            .await?;
+        newly_applied.push(migration.version);
+    }
+
+    Ok(newly_applied)
+}
+
+/// Compares the migrations recorded in `todo_db.schema_migrations` with
+/// [`MIGRATIONS`], without applying any.
+pub async fn status(session: &Session) -> Result<SchemaStatus, QueryError> {
+    let tracked = session
+        .query(
+            "SELECT table_name FROM system_schema.tables WHERE keyspace_name = 'todo_db' AND table_name = 'schema_migrations'",
+            &[],
+        )
+        .await?
+        .rows
+        .is_some_and(|rows| !rows.is_empty());
+    let applied = match tracked {
+        true => applied_versions(session).await?,
+        false => Vec::new(),
+    };
+
+    Ok(SchemaStatus {
+        latest: applied.iter().max().map(|version| i64::from(*version)),
+        pending: MIGRATIONS
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| (migration.version.into(), migration.name.to_string()))
+            .collect(),
+        modified: Vec::new(),
+        unknown: applied
+            .into_iter()
+            .filter(|version| !MIGRATIONS.iter().any(|m| m.version == *version))
+            .map(i64::from)
+            .collect(),
+    })
+}
+
+pub async fn applied_versions(session: &Session) -> Result<Vec<i32>, QueryError> {
+    let rows = session
+        .query("SELECT version FROM todo_db.schema_migrations", &[])
+        .await?
+        .rows;
+
+    let mut versions: Vec<i32> = rows
+        .map(|rows| {
+            rows.into_typed::<(i32,)>()
+                .flatten()
+                .map(|(version,)| version)
+                .collect()
+        })
+        .unwrap_or_default();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+async fn copy_table(session: &Session, copy: &TableCopy) -> Result<(), QueryError> {
+    let insert = session
+        .prepare(format!("INSERT INTO {} JSON ?", copy.to))
+        .await?;
+    let mut rows = session
+        .query_iter(
+            format!("SELECT JSON {} FROM {}", copy.columns, copy.from),
+            &[],
+        )
+        .await?
+        .into_typed::<(String,)>();
+
+    let (mut copied, mut skipped) = (0, 0);
+    while let Some(row) = rows.next().await {
+        let (json,) = row.map_err(|e| match e {
+            NextRowError::QueryError(e) => e,
+            NextRowError::FromRowError(e) => QueryError::InvalidMessage(e.to_string()),
+        })?;
+        match session.execute(&insert, (&json,)).await {
+            Ok(_) => copied += 1,
+            Err(e) => {
+                log::warn!(
+                    "event=migration_copy_skipped table={} error=\"{}\"",
+                    copy.from,
+                    e
+                );
+                skipped += 1;
+            }
+        }
+    }
+
+    log::info!(
+        "event=migration_copy from={} to={} copied={} skipped={}",
+        copy.from,
+        copy.to,
+        copied,
+        skipped
+    );
+    Ok(())
+}
+
+/// Splits a script into individual statements; the driver only accepts one per query.
+fn statements(cql: &str) -> impl Iterator<Item = String> + '_ {
+    cql.split(';')
+        .map(str::trim)
+        .filter(|statement| !statement.is_empty())
+        .map(str::to_string)
+}
@@ -1,41 +1,1405 @@
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
-use scylla::Session;
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::{RefreshTokenService, TokenService};
+use crate::blobs::{BlobStore, UploadPolicy};
+use crate::concurrency::ConcurrencyLimiter;
+use crate::config::{PaginationConfig, SuggestionWeights};
+use crate::diagnostics::Diagnostics;
+use crate::features::FeatureFlags;
+use crate::maintenance::MaintenanceMode;
+use crate::encryption::ContentEncryption;
+use crate::exports::AccountExports;
+use crate::events::EventBus;
+use crate::lockout::LoginGuard;
+use crate::markdown::{Render, RenderCache};
+use crate::metrics::QueryMetrics;
+use crate::notifier::Notifier;
+use crate::oauth::OAuthClient;
+use crate::outbox::OutboxRelay;
+use crate::password_reset::PasswordResetService;
+use crate::repository::{
+    ActivityRepository, AttachmentRepository, ChangeRepository, CommentRepository,
+    NotificationSettingsRepository, ProjectRepository, TemplateRepository, TodoAclRepository,
+    TodoRepository, TodoSort, UserRepository, UserSettingsRepository, WebhookRepository,
+    WorkspaceRepository,
+};
+use crate::retention::Retention;
+use crate::sanitize::Sanitizer;
+use crate::scheduler::Scheduler;
+use crate::scheduling::{Recurrence, RecurrenceScheduler};
+use crate::signing::SignatureVerifier;
+use crate::stats::TodoStats;
+use crate::timezones;
+use crate::totp::TotpService;
+use crate::undo::UndoLog;
+use crate::webhooks::TodoEvent;
+use crate::workflow::StatusWorkflow;
+
+/// A todo's ID. Always a UUID, so malformed IDs are rejected when they are
+/// parsed rather than surfacing later as a missing todo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct TodoId(pub Uuid);
+
+impl TodoId {
+    pub fn generate() -> Self {
+        TodoId(Uuid::new_v4())
+    }
+}
+
+impl fmt::Display for TodoId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid todo ID '{0}': expected a UUID")]
+pub struct InvalidTodoId(String);
+
+impl FromStr for TodoId {
+    type Err = InvalidTodoId;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(value)
+            .map(TodoId)
+            .map_err(|_| InvalidTodoId(value.to_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for TodoId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
 
-#[allow(non_snake_case)]
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Todo {
-    pub id: Option<String>,
+    pub id: Option<TodoId>,
     pub title: String,
     pub content: String,
     pub completed: Option<bool>,
-    pub createdAt: Option<DateTime<Utc>>,
-    pub updatedAt: Option<DateTime<Utc>>,
+    pub archived: Option<bool>,
+    /// Given and shown in the user's time zone; see [`timezones`].
+    #[serde(default, with = "timezones::local")]
+    pub due_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+    /// ID of the first todo in a recurring series, shared by every occurrence.
+    pub series_id: Option<TodoId>,
+    /// Set once the occurrence following this one has been created.
+    pub next_occurrence_id: Option<TodoId>,
+    #[serde(default, with = "timezones::local")]
+    pub remind_at: Option<DateTime<Utc>>,
+    /// When the reminder notification went out; `None` while it is pending.
+    pub reminder_sent_at: Option<DateTime<Utc>>,
+    /// The user who created the todo; `None` for todos created anonymously.
+    pub owner_id: Option<String>,
+    /// The workspace the todo belongs to; `None` for personal todos.
+    pub workspace_id: Option<String>,
+    /// The workspace member working on the todo; set through
+    /// `POST /todos/{id}/assign`.
+    #[serde(default)]
+    pub assignee_id: Option<String>,
+    /// The project grouping the todo, in the same workspace.
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// Place in manually ordered lists, lowest first; set by the client or
+    /// through `PATCH /todos/reorder`.
+    #[serde(default)]
+    pub position: Option<i64>,
+    /// Stage on the board; read it through [`Todo::status`], which keeps
+    /// it in line with `completed`.
+    #[serde(default)]
+    pub status: Option<TodoStatus>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    /// When the todo is deleted automatically; `None` for todos that do not
+    /// expire. Set from `expiresInSeconds` on creation.
+    #[serde(default, skip_deserializing)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Filled in when a todo is fetched or listed through the API; not stored.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub comment_count: Option<usize>,
+    /// Seconds left until `expires_at`, filled in when listing with
+    /// `include_ttl=true`; not stored.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<i64>,
+    /// `content` rendered from Markdown to sanitized HTML, filled in when
+    /// reading with `render=html`; not stored.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+}
+
+impl Todo {
+    /// Whether the todo has expired by `now`; backends without native
+    /// expiry hide such todos until the sweep deletes them.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// The todo's status. `completed` is still written on its own by older
+    /// clients, so it wins: a completed todo is done, and an incomplete one
+    /// whose stored status says done is back in the backlog.
+    pub fn status(&self) -> TodoStatus {
+        match (self.completed.unwrap_or(false), self.status) {
+            (true, _) => TodoStatus::Done,
+            (false, None | Some(TodoStatus::Done)) => TodoStatus::Backlog,
+            (false, Some(status)) => status,
+        }
+    }
+}
+
+/// Where a todo stands; moves between statuses are limited by the
+/// configured [`StatusWorkflow`](crate::workflow::StatusWorkflow).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Backlog,
+    InProgress,
+    Blocked,
+    Done,
+}
+
+impl TodoStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoStatus::Backlog => "backlog",
+            TodoStatus::InProgress => "in_progress",
+            TodoStatus::Blocked => "blocked",
+            TodoStatus::Done => "done",
+        }
+    }
+}
+
+impl FromStr for TodoStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "backlog" => Ok(TodoStatus::Backlog),
+            "in_progress" => Ok(TodoStatus::InProgress),
+            "blocked" => Ok(TodoStatus::Blocked),
+            "done" => Ok(TodoStatus::Done),
+            other => Err(format!("unknown status: {}", other)),
+        }
+    }
+}
+
+/// Body of `POST /todos`: the todo plus options that only apply on creation.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTodoSchema {
+    #[serde(flatten)]
+    pub todo: Todo,
+    /// Deletes the todo automatically this many seconds after creation.
+    #[serde(alias = "expires_in_seconds")]
+    pub expires_in_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "user" => Ok(Role::User),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: String,
+    pub email: String,
+    /// Argon2 PHC string; never returned by the API. Empty for accounts
+    /// created by signing in with an external provider, which have no
+    /// password until they set one.
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub role: Role,
+    /// Name shown to other users instead of the email address.
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An account at an external identity provider, linked to the local user
+/// it signs in as.
+#[derive(Debug, Clone)]
+pub struct UserIdentity {
+    /// e.g. `google`.
+    pub provider: String,
+    /// The provider's stable ID for the account; emails can change.
+    pub subject: String,
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A refresh token as stored: only the SHA-256 of its value is kept.
+/// Tokens obtained by rotating one another share the family of the login
+/// that started the chain, and are revoked together.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub family_id: String,
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the token was exchanged for a new one; it is spent from then on.
+    pub used_at: Option<DateTime<Utc>>,
+    /// How the user authenticated at the login that started the family,
+    /// as `amr` values; access tokens from a rotation carry them on.
+    pub amr: Vec<String>,
+}
+
+/// A login on one device. It lasts as long as the refresh token family
+/// the login started, whose ID it shares.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSession {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    /// The client's `User-Agent`, telling devices apart.
+    pub user_agent: Option<String>,
+    /// Where the session was last used from.
+    pub ip_address: String,
+    pub created_at: DateTime<Utc>,
+    /// The last login or refresh; using an access token does not count.
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A user's TOTP second factor.
+#[derive(Debug, Clone)]
+pub struct UserTotp {
+    /// The shared secret, encrypted with the configured key.
+    pub secret: String,
+    /// When the enrollment was confirmed with a first code. Until then
+    /// logins do not ask for a code.
+    pub enabled_at: Option<DateTime<Utc>>,
+    /// Time step of the last code accepted, so each code works once.
+    pub last_step: i64,
+    /// SHA-256 of the backup codes not used yet.
+    pub backup_codes: Vec<String>,
+}
+
+/// A password reset token as stored, by the SHA-256 of its value like
+/// refresh tokens.
+#[derive(Debug, Clone)]
+pub struct PasswordResetToken {
+    pub token_hash: String,
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// When the password was reset with it; it is spent from then on.
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceRole {
+    /// Can rename or delete the workspace and manage its members.
+    Owner,
+    Member,
+}
+
+impl WorkspaceRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkspaceRole::Owner => "owner",
+            WorkspaceRole::Member => "member",
+        }
+    }
+}
+
+impl std::str::FromStr for WorkspaceRole {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "owner" => Ok(WorkspaceRole::Owner),
+            "member" => Ok(WorkspaceRole::Member),
+            other => Err(format!("unknown workspace role: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceMember {
+    pub workspace_id: String,
+    pub user_id: String,
+    pub role: WorkspaceRole,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SharePermission {
+    Read,
+    /// Read plus editing; deleting and re-sharing stay with the owner.
+    Write,
+}
+
+impl SharePermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SharePermission::Read => "read",
+            SharePermission::Write => "write",
+        }
+    }
+}
+
+impl std::str::FromStr for SharePermission {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "read" => Ok(SharePermission::Read),
+            "write" => Ok(SharePermission::Write),
+            other => Err(format!("unknown share permission: {}", other)),
+        }
+    }
+}
+
+/// Access to a todo granted by its owner to another user.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoShare {
+    pub todo_id: TodoId,
+    pub user_id: String,
+    pub permission: SharePermission,
+    pub shared_at: DateTime<Utc>,
+}
+
+/// A comment left on a todo by a registered user.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub id: String,
+    pub todo_id: TodoId,
+    pub author_id: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What happened to a todo in an [`Activity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Created,
+    TitleEdited,
+    Completed,
+    Commented,
+    Assigned,
+}
+
+impl ActivityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityKind::Created => "created",
+            ActivityKind::TitleEdited => "title_edited",
+            ActivityKind::Completed => "completed",
+            ActivityKind::Commented => "commented",
+            ActivityKind::Assigned => "assigned",
+        }
+    }
+}
+
+impl FromStr for ActivityKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "created" => Ok(ActivityKind::Created),
+            "title_edited" => Ok(ActivityKind::TitleEdited),
+            "completed" => Ok(ActivityKind::Completed),
+            "commented" => Ok(ActivityKind::Commented),
+            "assigned" => Ok(ActivityKind::Assigned),
+            other => Err(format!("unknown activity type: {}", other)),
+        }
+    }
+}
+
+/// One entry of the activity log. `detail` depends on the kind: the title
+/// for `created` and `title_edited`, the comment ID for `commented` and the
+/// assignee's ID for `assigned` (`None` when the todo was unassigned).
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    pub id: String,
+    pub todo_id: TodoId,
+    /// The user who did it; `None` for anonymous requests.
+    pub actor_id: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: ActivityKind,
+    pub detail: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// The kind of change an [`UndoEntry`] reverses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UndoAction {
+    Delete,
+    Complete,
+    Incomplete,
+}
+
+impl UndoAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UndoAction::Delete => "delete",
+            UndoAction::Complete => "complete",
+            UndoAction::Incomplete => "incomplete",
+        }
+    }
+}
+
+impl FromStr for UndoAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "delete" => Ok(UndoAction::Delete),
+            "complete" => Ok(UndoAction::Complete),
+            "incomplete" => Ok(UndoAction::Incomplete),
+            other => Err(format!("unknown undo action: {}", other)),
+        }
+    }
+}
+
+/// A change `POST /undo` can reverse, with the todos it touched as they
+/// were before it.
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    pub id: String,
+    pub actor_id: String,
+    pub action: UndoAction,
+    pub todos: Vec<Todo>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How many changes each replica has made to a todo: the server counts
+/// under [`VersionVector::SERVER`], sync clients under their own replica ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct VersionVector(pub BTreeMap<String, u64>);
+
+impl VersionVector {
+    /// The replica of changes made through the regular API.
+    pub const SERVER: &'static str = "server";
+
+    /// Counts one more change by `replica`.
+    pub fn increment(&mut self, replica: &str) {
+        *self.0.entry(replica.to_string()).or_default() += 1;
+    }
+
+    /// The changes seen by either vector.
+    pub fn merge(&self, other: &VersionVector) -> VersionVector {
+        let mut merged = self.clone();
+        for (replica, count) in &other.0 {
+            let entry = merged.0.entry(replica.clone()).or_default();
+            *entry = (*entry).max(*count);
+        }
+        merged
+    }
+
+    /// `Less` when `other` has seen every change this one has and more,
+    /// `Greater` the other way around, `Equal` when both saw the same and
+    /// `None` when each saw changes the other did not.
+    pub fn compare(&self, other: &VersionVector) -> Option<Ordering> {
+        let replicas = self.0.keys().chain(other.0.keys());
+        let (mut behind, mut ahead) = (false, false);
+        for replica in replicas {
+            let mine = self.0.get(replica).copied().unwrap_or(0);
+            let theirs = other.0.get(replica).copied().unwrap_or(0);
+            behind |= mine < theirs;
+            ahead |= mine > theirs;
+        }
+        match (behind, ahead) {
+            (false, false) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (true, true) => None,
+        }
+    }
+}
+
+/// What became of a change pushed through `POST /sync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncOutcome {
+    Applied,
+    /// The server's version won; the client should take it instead.
+    Conflict,
+    /// The change is not allowed, e.g. it reuses a taken title.
+    Rejected,
+}
+
+/// The latest change to a todo, for `GET /sync`. A deleted todo keeps its
+/// row as a tombstone, with the scope it had so only its viewers see it.
+#[derive(Debug, Clone)]
+pub struct TodoChange {
+    pub todo_id: TodoId,
+    /// Orders changes; see [`crate::sync::next_token`].
+    pub token: i64,
+    pub workspace_id: Option<String>,
+    pub owner_id: Option<String>,
+    pub deleted: bool,
+    pub version: VersionVector,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A domain event waiting in the outbox to be delivered to one consumer;
+/// see [`crate::outbox`]. `payload` is the message as it is published.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: String,
+    /// Who the entry is for, e.g. `webhooks` or `sink`.
+    pub consumer: String,
+    /// The event's name, e.g. `todo_created`.
+    pub event: String,
+    pub todo_id: TodoId,
+    pub payload: String,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A named group of todos in a workspace, or among personal todos when
+/// `workspace_id` is `None`. Archiving a project archives its todos.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub workspace_id: Option<String>,
+    /// The user who created the project; `None` when created anonymously.
+    pub owner_id: Option<String>,
+    pub archived: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A reusable todo owned by a registered user. The title and content may
+/// hold `{{variable}}` placeholders filled in when the template is
+/// instantiated.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub id: String,
+    pub owner_id: String,
+    pub name: String,
+    pub title: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Metadata of a file attached to a todo; the contents are kept in the blob
+/// store under the attachment's ID.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub todo_id: TodoId,
+    pub file_name: String,
+    pub content_type: String,
+    /// Size in bytes.
+    pub size: u64,
+    pub uploaded_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A registered callback URL for todo events.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// HMAC key for signing deliveries; never returned by the API.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Events to deliver; empty means every event.
+    pub events: Vec<TodoEvent>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn subscribes_to(&self, event: TodoEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// A webhook delivery that still failed after every retry.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: TodoEvent,
+    pub payload: serde_json::Value,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Which notification emails an address has opted in to.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationSettings {
+    pub email: String,
+    pub reminders: bool,
+    pub digest: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A user's preferences, from `/api/me/settings`. Notification opt-ins
+/// are kept with the [`NotificationSettings`] of the user's address.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSettings {
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    /// IANA name of the zone due dates are given and shown in, and whose
+    /// days the `overdue` and `today` filters follow.
+    pub timezone: Tz,
+    /// Page size of lists requested without `limit`; the server's default
+    /// when `None`. Capped like `limit`.
+    pub page_size: Option<usize>,
+    /// Order of todo lists requested without `sort_by`.
+    pub default_sort: Option<TodoSort>,
+    /// `None` until the user first saves their settings.
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl UserSettings {
+    /// The settings of a user who has not saved any.
+    pub fn defaults(user_id: String) -> Self {
+        UserSettings {
+            user_id,
+            timezone: Tz::UTC,
+            page_size: None,
+            default_sort: None,
+            updated_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "queued" => Ok(JobStatus::Queued),
+            "running" => Ok(JobStatus::Running),
+            "succeeded" => Ok(JobStatus::Succeeded),
+            "failed" => Ok(JobStatus::Failed),
+            other => Err(format!("unknown job status: {}", other)),
+        }
+    }
+}
+
+/// Persisted state of one background job.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRecord {
+    pub id: String,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 pub struct AppState {
-    pub db: Arc<Session>,
+    pub todos: Arc<dyn TodoRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub workspaces: Arc<dyn WorkspaceRepository>,
+    pub acl: Arc<dyn TodoAclRepository>,
+    pub comments: Arc<dyn CommentRepository>,
+    pub activity: Arc<dyn ActivityRepository>,
+    pub attachments: Arc<dyn AttachmentRepository>,
+    pub templates: Arc<dyn TemplateRepository>,
+    pub projects: Arc<dyn ProjectRepository>,
+    pub blobs: Arc<dyn BlobStore>,
+    pub uploads: UploadPolicy,
+    pub pagination: PaginationConfig,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub notification_settings: Arc<dyn NotificationSettingsRepository>,
+    pub user_settings: Arc<dyn UserSettingsRepository>,
+    pub recurrence: RecurrenceScheduler,
+    pub events: EventBus,
+    pub relay: OutboxRelay,
+    pub notifier: Arc<dyn Notifier>,
+    pub stats: TodoStats,
+    pub tokens: TokenService,
+    pub query_metrics: Arc<QueryMetrics>,
+    pub concurrency: ConcurrencyLimiter,
+    pub workflow: StatusWorkflow,
+    pub suggestions: SuggestionWeights,
+    pub markdown: RenderCache,
+    pub sanitizer: Sanitizer,
+    pub undo: UndoLog,
+    pub changes: Arc<dyn ChangeRepository>,
+    pub refresh_tokens: RefreshTokenService,
+    pub oauth: OAuthClient,
+    pub password_resets: PasswordResetService,
+    pub login_guard: LoginGuard,
+    pub totp: TotpService,
+    pub encryption: ContentEncryption,
+    pub exports: AccountExports,
+    pub retention: Retention,
+    pub scheduler: Scheduler,
+    pub signatures: SignatureVerifier,
+    pub diagnostics: Diagnostics,
+    pub features: FeatureFlags,
+    pub maintenance: MaintenanceMode,
 }
 
 impl AppState {
-    pub fn new(session: Session) -> AppState {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        todos: Arc<dyn TodoRepository>,
+        users: Arc<dyn UserRepository>,
+        workspaces: Arc<dyn WorkspaceRepository>,
+        acl: Arc<dyn TodoAclRepository>,
+        comments: Arc<dyn CommentRepository>,
+        activity: Arc<dyn ActivityRepository>,
+        attachments: Arc<dyn AttachmentRepository>,
+        templates: Arc<dyn TemplateRepository>,
+        projects: Arc<dyn ProjectRepository>,
+        blobs: Arc<dyn BlobStore>,
+        uploads: UploadPolicy,
+        pagination: PaginationConfig,
+        webhooks: Arc<dyn WebhookRepository>,
+        notification_settings: Arc<dyn NotificationSettingsRepository>,
+        user_settings: Arc<dyn UserSettingsRepository>,
+        recurrence: RecurrenceScheduler,
+        events: EventBus,
+        relay: OutboxRelay,
+        notifier: Arc<dyn Notifier>,
+        stats: TodoStats,
+        tokens: TokenService,
+        query_metrics: Arc<QueryMetrics>,
+        concurrency: ConcurrencyLimiter,
+        workflow: StatusWorkflow,
+        suggestions: SuggestionWeights,
+        markdown: RenderCache,
+        sanitizer: Sanitizer,
+        undo: UndoLog,
+        changes: Arc<dyn ChangeRepository>,
+        refresh_tokens: RefreshTokenService,
+        oauth: OAuthClient,
+        password_resets: PasswordResetService,
+        login_guard: LoginGuard,
+        totp: TotpService,
+        encryption: ContentEncryption,
+        exports: AccountExports,
+        retention: Retention,
+        scheduler: Scheduler,
+        signatures: SignatureVerifier,
+        diagnostics: Diagnostics,
+        features: FeatureFlags,
+        maintenance: MaintenanceMode,
+    ) -> AppState {
         AppState {
-            db: Arc::new(session),
+            todos,
+            users,
+            workspaces,
+            acl,
+            comments,
+            activity,
+            attachments,
+            templates,
+            projects,
+            blobs,
+            uploads,
+            pagination,
+            webhooks,
+            notification_settings,
+            user_settings,
+            recurrence,
+            events,
+            relay,
+            notifier,
+            stats,
+            tokens,
+            query_metrics,
+            concurrency,
+            workflow,
+            suggestions,
+            markdown,
+            sanitizer,
+            undo,
+            changes,
+            refresh_tokens,
+            oauth,
+            password_resets,
+            login_guard,
+            totp,
+            encryption,
+            exports,
+            retention,
+            scheduler,
+            signatures,
+            diagnostics,
+            features,
+            maintenance,
+        }
+    }
+}
+
+#[cfg(feature = "seed")]
+#[derive(Debug, Deserialize)]
+pub struct SeedQuery {
+    pub count: Option<usize>,
+}
+
+/// `PUT /dev/chaos`.
+#[cfg(feature = "chaos")]
+#[derive(Debug, Deserialize)]
+pub struct SetFaultRulesSchema {
+    pub rules: Vec<crate::chaos::FaultRule>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkDeleteQuery {
+    pub completed: Option<bool>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only report how many todos would be deleted.
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchIdsSchema {
+    pub ids: Vec<TodoId>,
+}
+
+/// Body of `PUT /todos/{id}`. Every field is replaced; optional fields left
+/// out are cleared.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceTodoSchema {
+    pub title: String,
+    pub content: String,
+    pub completed: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default, with = "timezones::local")]
+    pub due_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+    #[serde(default, with = "timezones::local")]
+    pub remind_at: Option<DateTime<Utc>>,
+    pub project_id: Option<String>,
+    pub position: Option<i64>,
+}
+
+/// Body of `POST /todos/{id}/duplicate`; may be left out entirely.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateTodoSchema {
+    /// Appended to the copied title, which must stay unique. Defaults to
+    /// `" (copy)"`.
+    #[serde(alias = "title_suffix")]
+    pub title_suffix: Option<String>,
+    /// Copy the completion state too instead of starting incomplete.
+    #[serde(alias = "keep_completion")]
+    pub keep_completion: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TodoListQuery {
+    /// Include `ttlSeconds`, the time left before each expiring todo is deleted.
+    pub include_ttl: Option<bool>,
+    /// Stream every matching todo as newline-delimited JSON, ignoring
+    /// paging. Also chosen by `Accept: application/x-ndjson`.
+    pub stream: Option<bool>,
+    /// Only the caller's own todos; requires a bearer token.
+    pub mine: Option<bool>,
+    /// Only todos created at or after this time.
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only todos created before this time.
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only todos in this project.
+    pub project_id: Option<String>,
+    /// `created_at` (the default) or `position`.
+    pub sort_by: Option<TodoSort>,
+    /// Only todos with this status.
+    pub status: Option<TodoStatus>,
+    /// Only todos assigned to this user; `me` for the caller.
+    pub assigned_to: Option<String>,
+    /// `html` adds each todo's content rendered from Markdown. Ignored when
+    /// streaming.
+    pub render: Option<Render>,
+    /// Only todos due on a day of the caller's time zone.
+    pub due: Option<DueFilter>,
+}
+
+/// Due date filters of `GET /todos`, by the days of the caller's time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DueFilter {
+    /// Open todos due before today.
+    Overdue,
+    /// Todos due today, done or not.
+    Today,
+}
+
+/// Filters of `GET /todos/count`; none counts every todo the caller owns.
+#[derive(Debug, Deserialize)]
+pub struct TodoCountQuery {
+    pub completed: Option<bool>,
+    /// Only todos in this project.
+    pub project: Option<String>,
+    /// Rejected: todos have no tags.
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityListQuery {
+    /// Only these kinds of activity, comma separated (e.g.
+    /// `completed,commented`).
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+}
+
+impl ActivityListQuery {
+    /// The requested kinds; empty when every kind is wanted.
+    pub fn kinds(&self) -> Result<Vec<ActivityKind>, String> {
+        let mut kinds = Vec::new();
+        for kind in self.kind.iter().flat_map(|kinds| kinds.split(',')) {
+            let kind = kind.trim();
+            if kind.is_empty() {
+                continue;
+            }
+            let kind = kind.parse()?;
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
         }
+        Ok(kinds)
     }
 }
 
 #[derive(Debug, Deserialize)]
-pub struct QueryOptions {
-    pub page: Option<usize>,
+pub struct SyncQuery {
+    /// The `nextToken` of the previous sync; without it every todo is sent.
+    pub since: Option<String>,
     pub limit: Option<usize>,
 }
 
-#[allow(non_snake_case)]
+/// Body of `POST /sync`: changes made by one client replica while offline.
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushSyncSchema {
+    #[serde(alias = "replica_id")]
+    pub replica_id: String,
+    pub changes: Vec<SyncChangeSchema>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncChangeSchema {
+    pub id: TodoId,
+    #[serde(default)]
+    pub deleted: bool,
+    /// The todo as the client has it; required unless `deleted`.
+    pub todo: Option<SyncTodoSchema>,
+    /// The version the change was made on, with the change counted.
+    #[serde(default)]
+    pub version: VersionVector,
+    /// When the client made the change; breaks ties between concurrent
+    /// changes.
+    #[serde(alias = "modified_at")]
+    pub modified_at: DateTime<Utc>,
+}
+
+/// The fields a sync client may change.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncTodoSchema {
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default, alias = "due_at", with = "timezones::local")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default, alias = "remind_at", with = "timezones::local")]
+    pub remind_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplaceTodoQuery {
+    /// Create the todo under the given ID when it does not exist yet.
+    pub upsert: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UpdateTodoSchema {
     pub title: Option<String>,
     pub content: Option<String>,
     pub completed: Option<bool>,
-}
\ No newline at end of file
+    #[serde(default, with = "timezones::local")]
+    pub due_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+    #[serde(default, with = "timezones::local")]
+    pub remind_at: Option<DateTime<Utc>>,
+    /// Moves the todo into this project; `PUT` takes it out of one.
+    pub project_id: Option<String>,
+    pub position: Option<i64>,
+}
+
+/// Body of `POST /todos/{id}/assign`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignTodoSchema {
+    /// A member of the todo's workspace; `null` or leaving it out
+    /// unassigns the todo.
+    #[serde(alias = "assignee_id")]
+    pub assignee_id: Option<String>,
+}
+
+/// Body of `PATCH /todos/{id}/status`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateTodoStatusSchema {
+    pub status: TodoStatus,
+}
+
+/// Body of `PATCH /todos/reorder`: either every todo of a list in its new
+/// order, or one todo moved next to another.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ReorderTodosSchema {
+    Ordered {
+        ids: Vec<TodoId>,
+    },
+    Move {
+        id: TodoId,
+        before: Option<TodoId>,
+        after: Option<TodoId>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterUserSchema {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginSchema {
+    pub email: String,
+    pub password: String,
+}
+
+/// `DELETE /admin/lockouts`: the account and/or client address to unlock.
+#[derive(Debug, Deserialize)]
+pub struct ClearLockoutQuery {
+    pub email: Option<String>,
+    pub client: Option<String>,
+}
+
+/// The API in maintenance mode, as stored for every instance to honor; see
+/// [`crate::maintenance`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Maintenance {
+    /// Why requests are refused, as told to clients.
+    pub message: String,
+    /// How long refused clients are told to wait before trying again.
+    pub retry_after_secs: u32,
+    /// Whether reads are still served, from the responses cached before.
+    pub allow_reads: bool,
+    /// The admin who started it.
+    pub started_by: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// `PUT /admin/maintenance`; fields left out take their configured default.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartMaintenanceSchema {
+    pub message: Option<String>,
+    #[serde(alias = "retry_after_secs")]
+    pub retry_after_secs: Option<u32>,
+    #[serde(alias = "allow_reads")]
+    pub allow_reads: Option<bool>,
+}
+
+/// `PUT /admin/features/{name}`.
+#[derive(Debug, Deserialize)]
+pub struct SetFeatureFlagSchema {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordSchema {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordSchema {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmEmailSchema {
+    pub token: String,
+}
+
+/// A code from an authenticator app, or a backup code.
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeSchema {
+    pub code: String,
+}
+
+/// Body of `POST /auth/login/totp`: the token from the first login step
+/// and a code.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpLoginSchema {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+/// Body of `POST /auth/refresh` and `POST /auth/logout`. The token may be
+/// left out in cookie sessions, which read it from its cookie.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenSchema {
+    pub refresh_token: Option<String>,
+}
+
+/// Query of the redirect back from an identity provider: `code` and
+/// `state` on success, `error` when the user declined.
+#[derive(Debug, Deserialize)]
+pub struct OAuthCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWorkspaceSchema {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWorkspaceSchema {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCommentSchema {
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateProjectSchema {
+    pub name: String,
+}
+
+/// Body of `PATCH /projects/{id}`. Archiving also archives the project's
+/// todos; unarchiving leaves them archived.
+#[derive(Debug, Deserialize)]
+pub struct UpdateProjectSchema {
+    pub name: Option<String>,
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectListQuery {
+    /// Include archived projects.
+    pub include_archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTemplateSchema {
+    pub name: String,
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+}
+
+/// Body of `POST /templates/{id}/instantiate`; may be left out when the
+/// template has no placeholders.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstantiateTemplateSchema {
+    /// Values for the template's `{{variable}}` placeholders.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default, with = "timezones::local")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "timezones::local")]
+    pub remind_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignUploadSchema {
+    pub file_name: String,
+    pub content_type: String,
+    /// Exact size of the file in bytes; the upload URL only accepts this many.
+    pub size: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompleteUploadSchema {
+    pub file_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareTodoSchema {
+    pub email: String,
+    /// Defaults to `read`.
+    pub permission: Option<SharePermission>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddMemberSchema {
+    pub email: String,
+    /// Defaults to `member`.
+    pub role: Option<WorkspaceRole>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookSchema {
+    pub url: String,
+    pub secret: String,
+    pub events: Option<Vec<TodoEvent>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookSchema {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<TodoEvent>>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterQuery {
+    /// Newest first; defaults to 50, capped at 500.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotificationSettingsSchema {
+    pub reminders: Option<bool>,
+    pub digest: Option<bool>,
+}
+
+/// Body of `PATCH /api/me/settings`; fields left out keep their value.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserSettingsSchema {
+    pub timezone: Option<Tz>,
+    #[serde(alias = "page_size")]
+    pub page_size: Option<usize>,
+    #[serde(alias = "default_sort")]
+    pub default_sort: Option<TodoSort>,
+    pub notifications: Option<UpdateNotificationSettingsSchema>,
+}
+
+/// Body of `PATCH /api/me`; fields left out keep their value, and an
+/// empty `displayName` or `avatarUrl` removes it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfileSchema {
+    pub email: Option<String>,
+    #[serde(alias = "display_name")]
+    pub display_name: Option<String>,
+    #[serde(alias = "avatar_url")]
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// Comma-separated completion-rate windows in days (default `7,30`).
+    pub windows: Option<String>,
+    /// Days of creation trend to return (default 14).
+    pub days: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OccurrencesQuery {
+    /// How many future occurrences to project (default 5, max 50).
+    pub upcoming: Option<usize>,
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Notification, NotificationKind, Notifier, NotifierError};
+use crate::config::SmtpConfig;
+use crate::repository::NotificationSettingsRepository;
+
+/// Sends notifications as plain-text email over SMTP. Without an explicit
+/// recipient, mail goes to every address that opted in to that kind.
+pub struct EmailNotifier {
+    transport: RwLock<AsyncSmtpTransport<Tokio1Executor>>,
+    config: SmtpConfig,
+    from: Mailbox,
+    settings: Arc<dyn NotificationSettingsRepository>,
+}
+
+impl EmailNotifier {
+    pub fn new(
+        config: &SmtpConfig,
+        settings: Arc<dyn NotificationSettingsRepository>,
+    ) -> Result<Self, NotifierError> {
+        Ok(EmailNotifier {
+            transport: RwLock::new(transport(config)?),
+            config: config.clone(),
+            from: config.from.parse().map_err(delivery_error)?,
+            settings,
+        })
+    }
+
+    /// Logs in to the SMTP server with `password` from now on.
+    pub fn set_password(&self, password: &str) {
+        let config = SmtpConfig {
+            password: Some(password.to_string()),
+            ..self.config.clone()
+        };
+        match transport(&config) {
+            Ok(transport) => *self.transport.write().unwrap() = transport,
+            Err(e) => log::warn!("event=smtp_reconfigure_failed error=\"{}\"", e),
+        }
+    }
+
+    async fn recipients(&self, notification: &Notification) -> Result<Vec<String>, NotifierError> {
+        if let Some(recipient) = &notification.recipient {
+            return Ok(vec![recipient.clone()]);
+        }
+
+        let settings = self.settings.list().await.map_err(delivery_error)?;
+        Ok(settings
+            .into_iter()
+            .filter(|settings| match notification.event {
+                NotificationKind::Reminder => settings.reminders,
+                NotificationKind::Digest => settings.digest,
+                NotificationKind::Assignment
+                | NotificationKind::PasswordReset
+                | NotificationKind::EmailChange
+                | NotificationKind::Test => false,
+            })
+            .map(|settings| settings.email)
+            .collect())
+    }
+}
+
+fn transport(config: &SmtpConfig) -> Result<AsyncSmtpTransport<Tokio1Executor>, NotifierError> {
+    let mut builder = if config.starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+            .map_err(delivery_error)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+    };
+    builder = builder.port(config.port).timeout(Some(config.timeout));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    Ok(builder.build())
+}
+
+fn delivery_error(e: impl std::fmt::Display) -> NotifierError {
+    NotifierError::Delivery(e.to_string())
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifierError> {
+        for recipient in self.recipients(notification).await? {
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(recipient.parse().map_err(delivery_error)?)
+                .subject(&notification.subject)
+                .body(notification.message.clone())
+                .map_err(delivery_error)?;
+
+            // Cloning shares the connection pool.
+            let transport = self.transport.read().unwrap().clone();
+            transport.send(message).await.map_err(delivery_error)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+mod email;
+mod templates;
+mod webhook;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::config::NotifierConfig;
+use crate::model::Todo;
+use crate::repository::NotificationSettingsRepository;
+use crate::secrets::{self, Secrets};
+
+pub use self::email::EmailNotifier;
+pub use self::webhook::WebhookNotifier;
+
+#[derive(Debug, thiserror::Error)]
+pub enum NotifierError {
+    #[error("Notification delivery failed: {0}")]
+    Delivery(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    Reminder,
+    Digest,
+    Assignment,
+    PasswordReset,
+    EmailChange,
+    Test,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Reminder => "reminder",
+            NotificationKind::Digest => "digest",
+            NotificationKind::Assignment => "assignment",
+            NotificationKind::PasswordReset => "password_reset",
+            NotificationKind::EmailChange => "email_change",
+            NotificationKind::Test => "test",
+        }
+    }
+}
+
+/// A message about one or more todos, e.g. a due reminder or the daily digest.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub event: NotificationKind,
+    /// Deliver only to this address; otherwise every opted-in recipient gets it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<String>,
+    pub subject: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub todo: Option<Todo>,
+}
+
+impl Notification {
+    pub fn reminder(todo: &Todo) -> Notification {
+        Notification {
+            event: NotificationKind::Reminder,
+            recipient: None,
+            subject: format!("Reminder: {}", todo.title),
+            message: templates::reminder(todo),
+            todo: Some(todo.clone()),
+        }
+    }
+
+    pub fn digest(todos: &[Todo]) -> Notification {
+        Notification {
+            event: NotificationKind::Digest,
+            recipient: None,
+            subject: format!("Your daily digest: {} open todo(s)", todos.len()),
+            message: templates::digest(todos),
+            todo: None,
+        }
+    }
+
+    /// Tells the new assignee of `todo`, reached at `recipient`.
+    pub fn assigned(todo: &Todo, recipient: &str) -> Notification {
+        Notification {
+            event: NotificationKind::Assignment,
+            recipient: Some(recipient.to_string()),
+            subject: format!("Assigned to you: {}", todo.title),
+            message: templates::assigned(todo),
+            todo: Some(todo.clone()),
+        }
+    }
+
+    /// Carries a password reset token to the account's owner. `reset` is
+    /// the link to follow, or the bare token when there is no reset page.
+    pub fn password_reset(recipient: &str, reset: &str, ttl_minutes: u64) -> Notification {
+        Notification {
+            event: NotificationKind::PasswordReset,
+            recipient: Some(recipient.to_string()),
+            subject: "Reset your password".to_string(),
+            message: templates::password_reset(reset, ttl_minutes),
+            todo: None,
+        }
+    }
+
+    /// Carries the token confirming a change of email address to the new
+    /// address.
+    pub fn email_change(recipient: &str, token: &str, ttl_hours: u64) -> Notification {
+        Notification {
+            event: NotificationKind::EmailChange,
+            recipient: Some(recipient.to_string()),
+            subject: "Confirm your new email address".to_string(),
+            message: templates::email_change(token, ttl_hours),
+            todo: None,
+        }
+    }
+
+    pub fn test(recipient: &str) -> Notification {
+        Notification {
+            event: NotificationKind::Test,
+            recipient: Some(recipient.to_string()),
+            subject: "Test notification".to_string(),
+            message: templates::test(),
+            todo: None,
+        }
+    }
+}
+
+/// Delivery channel for notifications. Implementations must be safe to call
+/// concurrently from background workers.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifierError>;
+}
+
+/// Writes notifications to the log; used when no delivery channel is configured.
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifierError> {
+        log::info!(
+            "event=notification kind={} subject={:?}",
+            notification.event.as_str(),
+            notification.subject
+        );
+        Ok(())
+    }
+}
+
+/// Sends every notification through each channel in turn. Fails if any
+/// channel fails, after trying all of them.
+pub struct FanoutNotifier {
+    notifiers: Vec<Arc<dyn Notifier>>,
+}
+
+#[async_trait]
+impl Notifier for FanoutNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifierError> {
+        let mut result = Ok(());
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.notify(notification).await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+/// Builds the notifier from configuration: webhook and/or email, or the log
+/// notifier when neither is set up. The email notifier follows rotations
+/// of the SMTP password in `secrets`.
+pub fn from_config(
+    config: &NotifierConfig,
+    settings: Arc<dyn NotificationSettingsRepository>,
+    secrets: &Secrets,
+) -> Result<Arc<dyn Notifier>, NotifierError> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+    if let Some(url) = &config.webhook_url {
+        notifiers.push(Arc::new(WebhookNotifier::new(url.clone(), config.timeout)?));
+    }
+    if let Some(smtp) = &config.smtp {
+        let email = Arc::new(EmailNotifier::new(smtp, settings)?);
+        let rotated = email.clone();
+        secrets.watch(secrets::SMTP_PASSWORD, move |password| {
+            rotated.set_password(password)
+        });
+        notifiers.push(email);
+    }
+
+    match notifiers.len() {
+        0 => Ok(Arc::new(LogNotifier)),
+        1 => Ok(notifiers.remove(0)),
+        _ => Ok(Arc::new(FanoutNotifier { notifiers })),
+    }
+}
@@ -0,0 +1,87 @@
+//! Plain-text email bodies. Placeholders are written as `{{name}}`.
+
+use chrono::{DateTime, Utc};
+
+use crate::model::Todo;
+use crate::timezones;
+
+const REMINDER: &str = include_str!("../../templates/email/reminder.txt");
+const DIGEST: &str = include_str!("../../templates/email/digest.txt");
+const ASSIGNED: &str = include_str!("../../templates/email/assigned.txt");
+const PASSWORD_RESET: &str = include_str!("../../templates/email/password_reset.txt");
+const EMAIL_CHANGE: &str = include_str!("../../templates/email/email_change.txt");
+const TEST: &str = include_str!("../../templates/email/test.txt");
+
+fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = template.to_string();
+    for (name, value) in values {
+        rendered = rendered.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    rendered
+}
+
+pub fn reminder(todo: &Todo) -> String {
+    render_todo(REMINDER, todo)
+}
+
+pub fn assigned(todo: &Todo) -> String {
+    render_todo(ASSIGNED, todo)
+}
+
+/// Fills in a template showing one todo.
+fn render_todo(template: &str, todo: &Todo) -> String {
+    let due_at = todo
+        .due_at
+        .map(local_time)
+        .unwrap_or_else(|| "not set".to_string());
+    let id = todo.id.map(|id| id.to_string()).unwrap_or_default();
+    render(
+        template,
+        &[
+            ("title", &todo.title),
+            ("content", &todo.content),
+            ("due_at", &due_at),
+            ("id", &id),
+        ],
+    )
+}
+
+pub fn digest(todos: &[Todo]) -> String {
+    let lines: Vec<String> = todos
+        .iter()
+        .map(|todo| match todo.due_at {
+            Some(due_at) => format!("- {} (due {})", todo.title, local_time(due_at)),
+            None => format!("- {}", todo.title),
+        })
+        .collect();
+    render(
+        DIGEST,
+        &[
+            ("count", &todos.len().to_string()),
+            ("todos", &lines.join("\n")),
+        ],
+    )
+}
+
+/// `time` in the current time zone, which is UTC outside a request.
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&timezones::current()).to_rfc3339()
+}
+
+pub fn password_reset(reset: &str, ttl_minutes: u64) -> String {
+    render(
+        PASSWORD_RESET,
+        &[("reset", reset), ("minutes", &ttl_minutes.to_string())],
+    )
+}
+
+pub fn email_change(token: &str, ttl_hours: u64) -> String {
+    render(
+        EMAIL_CHANGE,
+        &[("token", token), ("hours", &ttl_hours.to_string())],
+    )
+}
+
+pub fn test() -> String {
+    TEST.to_string()
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use super::{Notification, Notifier, NotifierError};
+
+/// POSTs each notification as JSON to a fixed URL. Any non-2xx response
+/// counts as a failed delivery.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String, timeout: Duration) -> Result<Self, NotifierError> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(delivery_error)?;
+
+        Ok(WebhookNotifier { client, url })
+    }
+}
+
+fn delivery_error(e: impl std::fmt::Display) -> NotifierError {
+    NotifierError::Delivery(e.to_string())
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<(), NotifierError> {
+        self.client
+            .post(&self.url)
+            .json(notification)
+            .send()
+            .await
+            .map_err(delivery_error)?
+            .error_for_status()
+            .map_err(delivery_error)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! Sign-in with Google or GitHub through the OAuth2 authorization-code
+//! flow, with PKCE. The provider vouches for the account and its email;
+//! the account is then linked to a local user, who gets our usual tokens.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::HttpRequest;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::{OAuthConfig, OAuthProviderConfig};
+use crate::error::AppError;
+use crate::model::{AppState, User, UserIdentity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OAuthProvider {
+    Google,
+    GitHub,
+}
+
+impl OAuthProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "google",
+            OAuthProvider::GitHub => "github",
+        }
+    }
+
+    fn authorize_url(&self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+            OAuthProvider::GitHub => "https://github.com/login/oauth/authorize",
+        }
+    }
+
+    fn token_url(&self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "https://oauth2.googleapis.com/token",
+            OAuthProvider::GitHub => "https://github.com/login/oauth/access_token",
+        }
+    }
+
+    /// Just enough to learn who the user is and their verified email.
+    fn scope(&self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "openid email",
+            OAuthProvider::GitHub => "read:user user:email",
+        }
+    }
+
+    /// Name of the cookie holding a started sign-in's state and verifier.
+    fn cookie_name(&self) -> String {
+        format!("oauth_{}", self.as_str())
+    }
+}
+
+impl fmt::Display for OAuthProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OAuthProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "google" => Ok(OAuthProvider::Google),
+            "github" => Ok(OAuthProvider::GitHub),
+            other => Err(format!("unknown OAuth provider: {}", other)),
+        }
+    }
+}
+
+/// An account as reported by its provider.
+#[derive(Debug, Clone)]
+pub struct ExternalAccount {
+    pub provider: OAuthProvider,
+    pub subject: String,
+    /// Lowercased, and only set when the provider has verified it.
+    pub email: Option<String>,
+}
+
+/// A started sign-in: the provider page to send the user to, and the
+/// cookie the callback is checked against.
+pub struct Authorization {
+    pub url: String,
+    pub cookie: Cookie<'static>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GoogleUserInfo {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+#[derive(Deserialize)]
+struct GitHubUser {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// Talks to the configured providers on behalf of the sign-in endpoints.
+#[derive(Clone)]
+pub struct OAuthClient {
+    http: reqwest::Client,
+    google: Option<OAuthProviderConfig>,
+    github: Option<OAuthProviderConfig>,
+    state_ttl: Duration,
+}
+
+impl OAuthClient {
+    pub fn new(config: &OAuthConfig) -> Result<Self, reqwest::Error> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            // GitHub's API refuses requests without one.
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()?;
+
+        Ok(OAuthClient {
+            http,
+            google: config.google.clone(),
+            github: config.github.clone(),
+            state_ttl: config.state_ttl,
+        })
+    }
+
+    fn config(&self, provider: OAuthProvider) -> Result<&OAuthProviderConfig, AppError> {
+        match provider {
+            OAuthProvider::Google => self.google.as_ref(),
+            OAuthProvider::GitHub => self.github.as_ref(),
+        }
+        .ok_or_else(|| AppError::NotFound(format!("Sign-in with {} is not enabled", provider)))
+    }
+
+    /// Starts a sign-in with `provider`. The state and PKCE verifier go
+    /// into an HTTP-only cookie rather than anywhere the provider sees, so
+    /// only the browser that started the sign-in can finish it.
+    pub fn authorize(&self, provider: OAuthProvider) -> Result<Authorization, AppError> {
+        let config = self.config(provider)?;
+        let state = hex::encode(rand::thread_rng().gen::<[u8; 16]>());
+        let verifier = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+        let url = reqwest::Url::parse_with_params(
+            provider.authorize_url(),
+            [
+                ("response_type", "code"),
+                ("client_id", config.client_id.as_str()),
+                ("redirect_uri", config.redirect_url.as_str()),
+                ("scope", provider.scope()),
+                ("state", state.as_str()),
+                ("code_challenge", challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| AppError::Internal(format!("Invalid {} authorize URL: {}", provider, e)))?;
+
+        let cookie = Cookie::build(provider.cookie_name(), format!("{}.{}", state, verifier))
+            .path("/")
+            .http_only(true)
+            .secure(config.redirect_url.starts_with("https://"))
+            // The callback is a top-level navigation from the provider's site.
+            .same_site(SameSite::Lax)
+            .max_age(
+                actix_web::cookie::time::Duration::try_from(self.state_ttl).unwrap_or_default(),
+            )
+            .finish();
+
+        Ok(Authorization {
+            url: url.into(),
+            cookie,
+        })
+    }
+
+    /// The PKCE verifier of the sign-in started by the browser making the
+    /// callback `req`, if the callback's `state` matches it.
+    pub fn verifier(
+        &self,
+        provider: OAuthProvider,
+        req: &HttpRequest,
+        state: &str,
+    ) -> Result<String, AppError> {
+        self.config(provider)?;
+        req.cookie(&provider.cookie_name())
+            .as_ref()
+            .and_then(|cookie| cookie.value().split_once('.'))
+            .filter(|(expected, _)| *expected == state)
+            .map(|(_, verifier)| verifier.to_string())
+            .ok_or_else(|| {
+                AppError::Unauthorized(format!(
+                    "Sign-in with {} expired or was started elsewhere; start again",
+                    provider
+                ))
+            })
+    }
+
+    /// Expires the cookie set by [`OAuthClient::authorize`].
+    pub fn clear_cookie(&self, provider: OAuthProvider) -> Cookie<'static> {
+        let mut cookie = Cookie::build(provider.cookie_name(), "").path("/").finish();
+        cookie.make_removal();
+        cookie
+    }
+
+    /// Trades the authorization `code` for an access token and looks up
+    /// the account it was issued for.
+    pub async fn exchange(
+        &self,
+        provider: OAuthProvider,
+        code: &str,
+        verifier: &str,
+    ) -> Result<ExternalAccount, AppError> {
+        let config = self.config(provider)?;
+        let unreachable =
+            |e: reqwest::Error| AppError::BadGateway(format!("{} sign-in failed: {}", provider, e));
+
+        let token: TokenResponse = self
+            .http
+            .post(provider.token_url())
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", config.redirect_url.as_str()),
+                ("client_id", config.client_id.as_str()),
+                ("client_secret", config.client_secret.as_str()),
+                ("code_verifier", verifier),
+            ])
+            .send()
+            .await
+            .map_err(unreachable)?
+            .json()
+            .await
+            .map_err(unreachable)?;
+        // GitHub reports a bad code with a 200 and an `error` field, so the
+        // status alone does not tell.
+        let access_token = token.access_token.ok_or_else(|| {
+            AppError::Unauthorized(format!(
+                "Sign-in with {} was refused: {}",
+                provider,
+                token
+                    .error_description
+                    .or(token.error)
+                    .unwrap_or_else(|| "no access token".to_string())
+            ))
+        })?;
+
+        match provider {
+            OAuthProvider::Google => {
+                let info: GoogleUserInfo = self
+                    .get_json(
+                        provider,
+                        "https://openidconnect.googleapis.com/v1/userinfo",
+                        &access_token,
+                    )
+                    .await?;
+                Ok(ExternalAccount {
+                    provider,
+                    subject: info.sub,
+                    email: info
+                        .email
+                        .filter(|_| info.email_verified)
+                        .map(|email| email.trim().to_lowercase()),
+                })
+            }
+            OAuthProvider::GitHub => {
+                let user: GitHubUser = self
+                    .get_json(provider, "https://api.github.com/user", &access_token)
+                    .await?;
+                let emails: Vec<GitHubEmail> = self
+                    .get_json(
+                        provider,
+                        "https://api.github.com/user/emails",
+                        &access_token,
+                    )
+                    .await?;
+                Ok(ExternalAccount {
+                    provider,
+                    subject: user.id.to_string(),
+                    email: emails
+                        .into_iter()
+                        .find(|email| email.primary && email.verified)
+                        .map(|email| email.email.trim().to_lowercase()),
+                })
+            }
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        provider: OAuthProvider,
+        url: &str,
+        access_token: &str,
+    ) -> Result<T, AppError> {
+        let failed = |e: reqwest::Error| {
+            AppError::BadGateway(format!("Failed to fetch the {} account: {}", provider, e))
+        };
+        self.http
+            .get(url)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(failed)?
+            .json()
+            .await
+            .map_err(failed)
+    }
+}
+
+/// The local user `account` signs in as. An account seen before signs in
+/// as the user it was linked to; a new one is linked to the user with its
+/// verified email, who is created if there is none.
+///
+/// Registering with a password never proved the address, so an account
+/// whose email belongs to a password user is refused with a conflict
+/// rather than signing in past the password and second factor.
+pub async fn resolve_user(data: &AppState, account: &ExternalAccount) -> Result<User, AppError> {
+    let users = data.users.as_ref();
+    let provider = account.provider.as_str();
+    if let Some(user) = users.find_by_identity(provider, &account.subject).await? {
+        return Ok(user);
+    }
+
+    let email = account.email.clone().ok_or_else(|| {
+        AppError::Forbidden(format!(
+            "The {} account has no verified email address",
+            account.provider
+        ))
+    })?;
+    let user = match users.find_by_email(&email).await? {
+        Some(user) if !user.password_hash.is_empty() => {
+            return Err(AppError::Conflict(format!(
+                "{} is registered with a password; sign in with it instead",
+                email
+            )));
+        }
+        Some(user) => user,
+        None => {
+            let now = Utc::now();
+            let user = User {
+                id: Uuid::new_v4().to_string(),
+                role: data.tokens.role_for_verified(&email),
+                email,
+                password_hash: String::new(),
+                display_name: None,
+                avatar_url: None,
+                created_at: now,
+                updated_at: now,
+            };
+            users.insert(&user).await?;
+            log::info!(
+                "event=user_registered user_id={} role={} provider={}",
+                user.id,
+                user.role.as_str(),
+                provider
+            );
+            user
+        }
+    };
+
+    users
+        .link_identity(&UserIdentity {
+            provider: provider.to_string(),
+            subject: account.subject.clone(),
+            user_id: user.id.clone(),
+            created_at: Utc::now(),
+        })
+        .await?;
+    log::info!(
+        "event=identity_linked user_id={} provider={}",
+        user.id,
+        provider
+    );
+    Ok(user)
+}
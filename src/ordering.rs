@@ -0,0 +1,142 @@
+//! Manual ordering of todos through their `position`. Positions are spaced
+//! out so a moved todo usually only needs a position of its own; when its
+//! new neighbours leave no room, the whole list is renumbered.
+
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{AppState, Todo};
+use crate::outbox;
+use crate::projects;
+use crate::repository::{ListOptions, TodoPatch, TodoSort, TodoVisibility, TodoWrite};
+
+/// Gap between the positions of neighbouring todos in a renumbered list.
+pub const POSITION_STEP: i64 = 1024;
+
+/// Where a moved todo goes relative to the todo it is moved next to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    Before,
+    After,
+}
+
+/// The position of the `index`-th todo of a renumbered list.
+pub fn spaced(index: usize) -> i64 {
+    index as i64 * POSITION_STEP
+}
+
+/// A position strictly between `previous` and `next`, `None` at either end
+/// of the list, or `None` when there is no room left between them.
+pub fn between(previous: Option<i64>, next: Option<i64>) -> Option<i64> {
+    match (previous, next) {
+        (None, None) => Some(0),
+        (Some(previous), None) => previous.checked_add(POSITION_STEP),
+        (None, Some(next)) => next.checked_sub(POSITION_STEP),
+        (Some(previous), Some(next)) => next
+            .checked_sub(previous)
+            .filter(|gap| *gap >= 2)
+            .map(|gap| previous + gap / 2),
+    }
+}
+
+/// Writes the given positions, skipping todos already in place, and
+/// returns the todos that changed.
+pub async fn set_positions(
+    data: &AppState,
+    positions: Vec<(Todo, i64)>,
+    now: DateTime<Utc>,
+) -> Result<Vec<Todo>, AppError> {
+    let mut updated = Vec::new();
+    for (mut todo, position) in positions {
+        let Some(id) = todo.id else {
+            continue;
+        };
+        if todo.position == Some(position) {
+            continue;
+        }
+        let patch = TodoPatch {
+            position: Some(position),
+            updated_at: now,
+            expires_at: todo.expires_at,
+            ..TodoPatch::default()
+        };
+        patch.apply(&mut todo);
+        let event = DomainEvent::TodoUpdated {
+            id,
+            todo: Some(todo.clone()),
+            title: None,
+            actor_id: None,
+        };
+        outbox::commit(data, TodoWrite::Patch(id, patch), vec![event]).await?;
+        updated.push(todo);
+    }
+    Ok(updated)
+}
+
+/// Moves `moved` just before or after `anchor` among the todos of its
+/// workspace, or the personal todos, and returns the todos that changed.
+pub async fn move_todo(
+    data: &AppState,
+    moved: Todo,
+    anchor: &Todo,
+    placement: Placement,
+    now: DateTime<Utc>,
+) -> Result<Vec<Todo>, AppError> {
+    let siblings: Vec<Todo> = data
+        .todos
+        .list(&ListOptions {
+            offset: 0,
+            // Everything; SQL backends bind the limit as an i64.
+            limit: i64::MAX as usize,
+            include_archived: true,
+            scope: projects::scope(moved.workspace_id.as_deref()),
+            visibility: TodoVisibility::All,
+            owner_id: None,
+            created_after: None,
+            created_before: None,
+            project_id: None,
+            status: None,
+            assignee_id: None,
+            completed: None,
+            due_after: None,
+            due_before: None,
+            sort: TodoSort::Position,
+        })
+        .await?
+        .into_iter()
+        .filter(|todo| todo.id != moved.id)
+        .collect();
+
+    let Some(index) = siblings.iter().position(|todo| todo.id == anchor.id) else {
+        return Err(AppError::BadRequest(
+            "Todos can only be moved next to todos in the same workspace".to_string(),
+        ));
+    };
+    let index = match placement {
+        Placement::Before => index,
+        Placement::After => index + 1,
+    };
+
+    // A neighbour without a position has no room next to it either.
+    let previous = index.checked_sub(1).map(|i| siblings[i].position);
+    let next = siblings.get(index).map(|todo| todo.position);
+    let position = match (previous, next) {
+        (Some(None), _) | (_, Some(None)) => None,
+        (previous, next) => between(previous.flatten(), next.flatten()),
+    };
+
+    match position {
+        Some(position) => set_positions(data, vec![(moved, position)], now).await,
+        None => {
+            let mut order = siblings;
+            order.insert(index, moved);
+            let positions = order
+                .into_iter()
+                .enumerate()
+                .map(|(index, todo)| (todo, spaced(index)))
+                .collect();
+            set_positions(data, positions, now).await
+        }
+    }
+}
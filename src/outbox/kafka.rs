@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{EventSink, SinkError};
+use crate::config::KafkaConfig;
+use crate::model::OutboxEntry;
+
+const JSON_RECORDS: &str = "application/vnd.kafka.json.v2+json";
+
+/// Produces each message to a Kafka topic through the Confluent REST Proxy,
+/// keyed by todo ID so a todo's events land on one partition, in order.
+pub struct KafkaSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// The part of the proxy's produce response that reports failed records.
+#[derive(Deserialize)]
+struct ProduceResponse {
+    offsets: Vec<ProduceOffset>,
+}
+
+#[derive(Deserialize)]
+struct ProduceOffset {
+    error: Option<String>,
+}
+
+impl KafkaSink {
+    pub fn new(config: &KafkaConfig, timeout: Duration) -> Result<Self, SinkError> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(sink_error)?;
+
+        Ok(KafkaSink {
+            client,
+            url: format!(
+                "{}/topics/{}",
+                config.rest_url.trim_end_matches('/'),
+                config.topic
+            ),
+        })
+    }
+}
+
+fn sink_error(e: impl std::fmt::Display) -> SinkError {
+    SinkError(e.to_string())
+}
+
+#[async_trait]
+impl EventSink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn publish(&self, entry: &OutboxEntry) -> Result<(), SinkError> {
+        let value: serde_json::Value = serde_json::from_str(&entry.payload).map_err(sink_error)?;
+        let body = json!({
+            "records": [{ "key": entry.todo_id.to_string(), "value": value }],
+        });
+
+        let response: ProduceResponse = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, JSON_RECORDS)
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(sink_error)?
+            .error_for_status()
+            .map_err(sink_error)?
+            .json()
+            .await
+            .map_err(sink_error)?;
+
+        match response.offsets.into_iter().find_map(|offset| offset.error) {
+            Some(error) => Err(SinkError(error)),
+            None => Ok(()),
+        }
+    }
+}
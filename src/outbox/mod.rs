@@ -0,0 +1,427 @@
+//! The transactional outbox. A todo change is stored together with one
+//! outbox entry per event and consumer, in the same transaction, or on
+//! Scylla the same logged batch, so no event is lost to a crash or failure
+//! between the write and whatever reacts to it. A relay job then hands each
+//! consumer its entries, oldest first: webhooks, the activity log and, when
+//! one is configured, an external broker such as Kafka or NATS. An entry is
+//! only marked delivered once its consumer has accepted it, so one whose
+//! delivery fails, or whose mark is lost, goes out again on a later run:
+//! delivery is at least once, and broker consumers should drop messages
+//! whose `id` they have already seen.
+
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+pub(crate) mod nats;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::config::{EventSinkBackend, EventSinkConfig};
+use crate::error::AppError;
+use crate::events::{DomainEvent, EventHandler};
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::model::{AppState, OutboxEntry, Todo, TodoId};
+use crate::repository::{OutboxRepository, TodoWrite};
+
+#[cfg(feature = "kafka")]
+pub use self::kafka::KafkaSink;
+#[cfg(feature = "nats")]
+pub use self::nats::NatsSink;
+
+/// The consumer name of the external event sink's entries.
+pub const SINK_CONSUMER: &str = "sink";
+
+/// Most writes [`commit_all`] has in flight at once.
+pub const COMMIT_CONCURRENCY: usize = 16;
+
+/// How often delivered entries older than the retention period are purged.
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct SinkError(pub String);
+
+/// The JSON message an outbox entry carries, and that is published to the
+/// sink. `todo` is left out for deletions, completions and batch updates
+/// that do not load the todo; `title` is only set when it was edited.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventMessage {
+    pub id: String,
+    pub event: String,
+    pub occurred_at: DateTime<Utc>,
+    pub todo_id: TodoId,
+    pub actor_id: Option<String>,
+    pub title: Option<String>,
+    pub todo: Option<Todo>,
+}
+
+impl EventMessage {
+    pub fn new(event: &DomainEvent, occurred_at: DateTime<Utc>) -> Self {
+        EventMessage {
+            id: Uuid::new_v4().to_string(),
+            event: event.name().to_string(),
+            occurred_at,
+            todo_id: event.todo_id(),
+            actor_id: event.actor_id().map(str::to_string),
+            title: event.title().map(str::to_string),
+            todo: event.todo().cloned(),
+        }
+    }
+
+    /// The domain event the message was made from.
+    pub fn into_event(self) -> Result<DomainEvent, String> {
+        let EventMessage {
+            event,
+            todo_id: id,
+            actor_id,
+            title,
+            todo,
+            ..
+        } = self;
+        match event.as_str() {
+            "todo_created" => Ok(DomainEvent::TodoCreated {
+                todo: todo.ok_or("todo_created without a todo")?,
+                actor_id,
+            }),
+            "todo_updated" => Ok(DomainEvent::TodoUpdated {
+                id,
+                todo,
+                title,
+                actor_id,
+            }),
+            "todo_deleted" => Ok(DomainEvent::TodoDeleted { id, actor_id }),
+            "todo_completed" => Ok(DomainEvent::TodoCompleted { id, actor_id }),
+            other => Err(format!("unknown event: {}", other)),
+        }
+    }
+}
+
+/// The outbox entries announcing `events`, happening at `occurred_at`: one
+/// per event and consumer, all consumers getting the same message.
+pub fn entries(
+    events: &[DomainEvent],
+    consumers: &[&str],
+    occurred_at: DateTime<Utc>,
+) -> Vec<OutboxEntry> {
+    let mut entries = Vec::with_capacity(events.len() * consumers.len());
+    for (index, event) in events.iter().enumerate() {
+        let message = EventMessage::new(event, occurred_at);
+        let payload = serde_json::to_string(&message).expect("event messages serialize");
+        for consumer in consumers {
+            entries.push(OutboxEntry {
+                id: Uuid::new_v4().to_string(),
+                consumer: consumer.to_string(),
+                event: message.event.clone(),
+                todo_id: message.todo_id,
+                payload: payload.clone(),
+                // Events of one change keep their order, even where the
+                // clock does not tell them apart.
+                created_at: occurred_at + chrono::Duration::microseconds(index as i64),
+                delivered_at: None,
+            });
+        }
+    }
+    entries
+}
+
+/// Makes `write` and stores the outbox entries for `events` with it, then
+/// has the relay deliver them. The events also go out on the in-process
+/// bus, which is best effort.
+pub async fn commit(
+    data: &AppState,
+    write: TodoWrite,
+    events: Vec<DomainEvent>,
+) -> Result<(), AppError> {
+    commit_all(data, vec![(write, events)]).await
+}
+
+/// [`commit`] for several writes, up to [`COMMIT_CONCURRENCY`] at a time.
+/// Each write is only stored as one with its own events: on error, the
+/// writes before the failed one may have been made.
+pub async fn commit_all(
+    data: &AppState,
+    changes: Vec<(TodoWrite, Vec<DomainEvent>)>,
+) -> Result<(), AppError> {
+    let occurred_at = Utc::now();
+    let consumers = data.relay.consumers();
+    let mut committed = stream::iter(changes)
+        .map(|(write, events)| async move {
+            let entries = entries(&events, consumers, occurred_at);
+            data.todos.commit(&write, &entries).await?;
+            Ok::<_, AppError>(events)
+        })
+        .buffered(COMMIT_CONCURRENCY);
+
+    let mut result = Ok(());
+    let mut any = false;
+    while let Some(events) = committed.next().await {
+        match events {
+            Ok(events) => {
+                any = true;
+                for event in events {
+                    data.events.publish(event);
+                }
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    if any && !consumers.is_empty() {
+        data.relay.wake().await;
+    }
+    result
+}
+
+/// A broker that outbox entries are published to.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Publishes `entry.payload`. `Ok` means the broker has the message; an
+    /// error leaves the entry in the outbox to be published again.
+    async fn publish(&self, entry: &OutboxEntry) -> Result<(), SinkError>;
+}
+
+/// Builds the sink selected by `EVENT_SINK`, or `None` when events are not
+/// published.
+pub fn from_config(config: &EventSinkConfig) -> Result<Option<Arc<dyn EventSink>>, SinkError> {
+    match config.backend {
+        EventSinkBackend::None => Ok(None),
+        #[cfg(feature = "kafka")]
+        EventSinkBackend::Kafka => Ok(Some(Arc::new(KafkaSink::new(
+            &config.kafka,
+            config.timeout,
+        )?))),
+        #[cfg(not(feature = "kafka"))]
+        EventSinkBackend::Kafka => Err(SinkError(
+            "The Kafka event sink requires building with the `kafka` feature".to_string(),
+        )),
+        #[cfg(feature = "nats")]
+        EventSinkBackend::Nats => Ok(Some(Arc::new(NatsSink::new(&config.nats, config.timeout)))),
+        #[cfg(not(feature = "nats"))]
+        EventSinkBackend::Nats => Err(SinkError(
+            "The NATS event sink requires building with the `nats` feature".to_string(),
+        )),
+    }
+}
+
+/// Something outbox entries are delivered to.
+#[derive(Clone)]
+pub enum Consumer {
+    /// Gets the domain event back from each entry, under the handler's name.
+    Handler(Arc<dyn EventHandler>),
+    /// Gets the entries as they are, under [`SINK_CONSUMER`].
+    Sink(Arc<dyn EventSink>),
+}
+
+impl Consumer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Consumer::Handler(handler) => handler.name(),
+            Consumer::Sink(_) => SINK_CONSUMER,
+        }
+    }
+
+    async fn deliver(&self, entry: &OutboxEntry) -> Result<(), String> {
+        match self {
+            Consumer::Handler(handler) => {
+                let event = serde_json::from_str::<EventMessage>(&entry.payload)
+                    .map_err(|e| e.to_string())
+                    .and_then(EventMessage::into_event);
+                match event {
+                    Ok(event) => handler.handle(&event).await.map_err(|e| e.to_string()),
+                    // Trying again would not help, and would hold up every
+                    // later entry.
+                    Err(e) => {
+                        log::error!(
+                            "event=outbox_entry_skipped consumer={} entry_id={} error=\"{}\"",
+                            self.name(),
+                            entry.id,
+                            e
+                        );
+                        Ok(())
+                    }
+                }
+            }
+            Consumer::Sink(sink) => sink
+                .publish(entry)
+                .await
+                .map_err(|e| format!("{}: {}", sink.name(), e)),
+        }
+    }
+}
+
+/// Runs the relay job and knows who entries are written for.
+#[derive(Clone)]
+pub struct OutboxRelay {
+    jobs: JobQueue,
+    consumers: Arc<[&'static str]>,
+}
+
+impl OutboxRelay {
+    /// Registers the relay job for `consumers` and schedules it every
+    /// `interval`, so entries a run right after their write did not get to
+    /// are picked up. Also registers the purge.
+    pub fn new(
+        jobs: JobQueue,
+        repository: Arc<dyn OutboxRepository>,
+        consumers: Vec<Consumer>,
+        config: &EventSinkConfig,
+    ) -> Self {
+        let names = consumers.iter().map(Consumer::name).collect();
+        jobs.register(Arc::new(OutboxRelayJob::new(
+            repository.clone(),
+            consumers,
+            config.batch_size,
+        )));
+        jobs.every(OutboxRelayJob::KIND, config.relay_interval);
+        jobs.register(Arc::new(OutboxPurgeJob {
+            repository,
+            retention: config.retention,
+        }));
+        jobs.every(OutboxPurgeJob::KIND, PURGE_INTERVAL);
+
+        OutboxRelay {
+            jobs,
+            consumers: names,
+        }
+    }
+
+    /// The consumers every event gets an entry for.
+    pub fn consumers(&self) -> &[&'static str] {
+        &self.consumers
+    }
+
+    /// Has the relay run now rather than at its next interval. Only the
+    /// leader relays, so elsewhere the entries wait for its next run.
+    pub async fn wake(&self) {
+        if !self.jobs.runs_here(OutboxRelayJob::KIND) {
+            return;
+        }
+        if let Err(e) = self
+            .jobs
+            .enqueue(OutboxRelayJob::KIND, serde_json::Value::Null)
+            .await
+        {
+            log::warn!("event=outbox_relay_wake_failed error=\"{}\"", e);
+        }
+    }
+}
+
+/// Delivers waiting outbox entries to their consumers in the order they
+/// were written. A consumer's first failure ends its part of the run, so a
+/// todo's events never overtake each other; other consumers carry on.
+pub struct OutboxRelayJob {
+    repository: Arc<dyn OutboxRepository>,
+    consumers: Vec<Consumer>,
+    batch_size: usize,
+    /// Runs must not overlap, or both would deliver the same entries.
+    running: Mutex<()>,
+}
+
+impl OutboxRelayJob {
+    pub const KIND: &'static str = "outbox.relay";
+
+    pub fn new(
+        repository: Arc<dyn OutboxRepository>,
+        consumers: Vec<Consumer>,
+        batch_size: usize,
+    ) -> Self {
+        OutboxRelayJob {
+            repository,
+            consumers,
+            batch_size,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Delivers up to a batch of `consumer`'s entries; returns how many.
+    async fn relay(&self, consumer: &Consumer) -> Result<usize, String> {
+        let pending = self
+            .repository
+            .pending(consumer.name(), self.batch_size)
+            .await
+            .map_err(|e| e.to_string())?;
+        for entry in &pending {
+            consumer
+                .deliver(entry)
+                .await
+                .map_err(|e| format!("outbox entry {} was not delivered: {}", entry.id, e))?;
+            self.repository
+                .mark_delivered(&entry.id, Utc::now())
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(pending.len())
+    }
+}
+
+#[async_trait]
+impl Job for OutboxRelayJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    /// Later runs pick up whatever this one did not get to.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let _running = self.running.lock().await;
+        let mut failures = Vec::new();
+        for consumer in &self.consumers {
+            match self.relay(consumer).await {
+                Ok(0) => {}
+                Ok(count) => log::debug!(
+                    "event=outbox_relayed consumer={} count={}",
+                    consumer.name(),
+                    count
+                ),
+                Err(e) => failures.push(format!("{}: {}", consumer.name(), e)),
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(JobError(failures.join("; ")))
+        }
+    }
+}
+
+/// Deletes delivered entries once they are older than `retention`.
+pub struct OutboxPurgeJob {
+    pub repository: Arc<dyn OutboxRepository>,
+    pub retention: Duration,
+}
+
+impl OutboxPurgeJob {
+    pub const KIND: &'static str = "outbox.purge";
+}
+
+#[async_trait]
+impl Job for OutboxPurgeJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let before = Utc::now() - chrono::Duration::from_std(self.retention).unwrap_or_default();
+        let purged = self.repository.purge_delivered(before).await?;
+        if purged > 0 {
+            log::info!("event=outbox_purged count={}", purged);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::{EventSink, SinkError};
+use crate::config::NatsConfig;
+use crate::model::OutboxEntry;
+
+const CONNECT: &[u8] =
+    b"CONNECT {\"verbose\":false,\"pedantic\":false,\"headers\":true,\"name\":\"todo-api\"}\r\n";
+
+/// Opens a client connection to the server at `address`.
+pub(crate) async fn connect(address: &str) -> io::Result<BufStream<TcpStream>> {
+    let mut stream = BufStream::new(TcpStream::connect(address).await?);
+    let mut info = String::new();
+    stream.read_line(&mut info).await?;
+    if !info.starts_with("INFO") {
+        return Err(io::Error::other(format!(
+            "expected INFO from the server, got {:?}",
+            info.trim_end()
+        )));
+    }
+    stream.write_all(CONNECT).await?;
+    Ok(stream)
+}
+
+/// Publishes each message to `<prefix>.<event>` over the NATS client
+/// protocol. Every publish is followed by a `PING`, and only counts once
+/// the server's `PONG` shows it processed the message. Messages carry a
+/// `Nats-Msg-Id` header, so a JetStream stream on the subjects drops the
+/// duplicates a retried publish can cause.
+pub struct NatsSink {
+    address: String,
+    subject_prefix: String,
+    timeout: Duration,
+    /// Opened on first use, and again after any error.
+    connection: Mutex<Option<BufStream<TcpStream>>>,
+}
+
+impl NatsSink {
+    pub fn new(config: &NatsConfig, timeout: Duration) -> Self {
+        NatsSink {
+            address: config.address.clone(),
+            subject_prefix: config.subject_prefix.clone(),
+            timeout,
+            connection: Mutex::new(None),
+        }
+    }
+
+    async fn send(&self, stream: &mut BufStream<TcpStream>, entry: &OutboxEntry) -> io::Result<()> {
+        let headers = format!("NATS/1.0\r\nNats-Msg-Id: {}\r\n\r\n", entry.id);
+        let command = format!(
+            "HPUB {}.{} {} {}\r\n",
+            self.subject_prefix,
+            entry.event,
+            headers.len(),
+            headers.len() + entry.payload.len()
+        );
+        stream.write_all(command.as_bytes()).await?;
+        stream.write_all(headers.as_bytes()).await?;
+        stream.write_all(entry.payload.as_bytes()).await?;
+        stream.write_all(b"\r\nPING\r\n").await?;
+        stream.flush().await?;
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            match line.trim_end() {
+                "PONG" => return Ok(()),
+                "PING" => {
+                    stream.write_all(b"PONG\r\n").await?;
+                    stream.flush().await?;
+                }
+                error if error.starts_with("-ERR") => return Err(io::Error::other(error)),
+                // `+OK` and updated `INFO`s.
+                _ => {}
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventSink for NatsSink {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn publish(&self, entry: &OutboxEntry) -> Result<(), SinkError> {
+        let mut connection = self.connection.lock().await;
+        let result = tokio::time::timeout(self.timeout, async {
+            let stream = match &mut *connection {
+                Some(stream) => stream,
+                slot => slot.insert(connect(&self.address).await?),
+            };
+            self.send(stream, entry).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()));
+
+        result.map_err(|e| {
+            *connection = None;
+            SinkError(format!("{}: {}", self.address, e))
+        })
+    }
+}
@@ -0,0 +1,80 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use serde::Deserialize;
+
+use crate::config::PaginationConfig;
+use crate::error::AppError;
+use crate::model::AppState;
+use crate::preferences;
+
+/// Paging options for list endpoints, taken from the `page`, `limit` and
+/// `include_archived` query parameters. `page` counts from 1; `limit`
+/// defaults to the user's page size, or else the server's, and is capped
+/// by the server's [`PaginationConfig`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueryOptions {
+    pub limit: usize,
+    /// Rows to skip to reach the requested page.
+    pub offset: usize,
+    pub include_archived: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawQueryOptions {
+    page: Option<usize>,
+    limit: Option<usize>,
+    include_archived: Option<bool>,
+}
+
+impl QueryOptions {
+    pub fn parse(query: &str, config: &PaginationConfig) -> Result<Self, AppError> {
+        let raw = web::Query::<RawQueryOptions>::from_query(query)
+            .map_err(|e| AppError::BadRequest(e.to_string()))?
+            .into_inner();
+
+        let limit = match raw.limit {
+            Some(0) => return Err(AppError::BadRequest("limit must be at least 1".to_string())),
+            Some(limit) => limit.min(config.max_limit),
+            None => config.default_limit.min(config.max_limit),
+        };
+        let page = match raw.page {
+            Some(0) => return Err(AppError::BadRequest("page must be at least 1".to_string())),
+            Some(page) => page,
+            None => 1,
+        };
+        // The SQL backends bind the offset as an `i64`.
+        let offset = (page - 1)
+            .checked_mul(limit)
+            .filter(|offset| i64::try_from(*offset).is_ok())
+            .ok_or_else(|| AppError::BadRequest(format!("page {} is out of range", page)))?;
+
+        Ok(QueryOptions {
+            limit,
+            offset,
+            include_archived: raw.include_archived.unwrap_or(false),
+        })
+    }
+}
+
+impl FromRequest for QueryOptions {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = req
+            .app_data::<web::Data<AppState>>()
+            .ok_or_else(|| AppError::Internal("Application state is missing".to_string()))
+            .and_then(|state| {
+                let mut config = state.pagination;
+                if let Some(page_size) =
+                    preferences::of(req).and_then(|settings| settings.page_size)
+                {
+                    config.default_limit = page_size;
+                }
+                QueryOptions::parse(req.query_string(), &config)
+            });
+        ready(result)
+    }
+}
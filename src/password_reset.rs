@@ -0,0 +1,106 @@
+//! Password resets: a single-use token emailed to the account's owner,
+//! redeemed for a new password.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use rand::Rng;
+
+use crate::auth::hash_token;
+use crate::config::PasswordResetConfig;
+use crate::error::AppError;
+use crate::model::{PasswordResetToken, User};
+use crate::notifier::Notification;
+use crate::rate_limit::RateLimiter;
+use crate::repository::PasswordResetRepository;
+
+#[derive(Clone)]
+pub struct PasswordResetService {
+    repository: Arc<dyn PasswordResetRepository>,
+    ttl: Duration,
+    url: Option<String>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl PasswordResetService {
+    pub fn new(repository: Arc<dyn PasswordResetRepository>, config: &PasswordResetConfig) -> Self {
+        PasswordResetService {
+            repository,
+            ttl: config.ttl,
+            url: config.url.clone(),
+            limiter: Arc::new(RateLimiter::new(
+                config.hourly_limit,
+                Duration::from_secs(60 * 60),
+            )),
+        }
+    }
+
+    /// Counts a reset request for `email` from `client` against the limits
+    /// of both. Applies whether or not the address has an account, so the
+    /// limit gives nothing away either.
+    pub fn check_limit(&self, email: &str, client: &str) -> Result<(), AppError> {
+        for (limit, key) in [("email", email), ("client", client)] {
+            if let Err(retry_after) = self.limiter.hit(&format!("{}:{}", limit, key)) {
+                log::warn!("event=password_reset_rate_limited limit={}", limit);
+                return Err(AppError::TooManyRequests(
+                    "Too many password reset requests, try again later".to_string(),
+                    retry_after,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores a new reset token for `user` and returns the email carrying
+    /// it. Tokens issued earlier stay valid until they expire or one of
+    /// them is redeemed.
+    pub async fn issue(&self, user: &User) -> Result<Notification, AppError> {
+        let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let now = Utc::now();
+        self.repository
+            .insert(&PasswordResetToken {
+                token_hash: hash_token(&token),
+                user_id: user.id.clone(),
+                created_at: now,
+                expires_at: now + chrono::Duration::from_std(self.ttl).unwrap_or_default(),
+                used_at: None,
+            })
+            .await?;
+
+        let reset = match &self.url {
+            Some(url) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{}{}token={}", url, separator, token)
+            }
+            None => token,
+        };
+        Ok(Notification::password_reset(
+            &user.email,
+            &reset,
+            self.ttl.as_secs() / 60,
+        ))
+    }
+
+    /// Spends `token`, returning the ID of the user it was issued to. The
+    /// user's other outstanding tokens are spent with it, so an older link
+    /// cannot reset the password again.
+    pub async fn redeem(&self, token: &str) -> Result<String, AppError> {
+        let invalid = || AppError::BadRequest("Invalid or expired reset token".to_string());
+        let now = Utc::now();
+
+        let stored = self
+            .repository
+            .find(&hash_token(token))
+            .await?
+            .ok_or_else(invalid)?;
+        if stored.expires_at <= now
+            || stored.used_at.is_some()
+            || !self.repository.mark_used(&stored.token_hash, now).await?
+        {
+            return Err(invalid());
+        }
+        self.repository.mark_all_used(&stored.user_id, now).await?;
+        Ok(stored.user_id)
+    }
+}
@@ -0,0 +1,54 @@
+//! Per-user settings from `/api/me/settings`. They are looked up once per
+//! request from callers with a token and kept in the request's extensions,
+//! where the list endpoints find the user's page size and sort order; the
+//! request also runs in the user's time zone (see [`timezones`]).
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpRequest};
+use chrono_tz::Tz;
+
+use crate::auth;
+use crate::model::{AppState, UserSettings};
+use crate::timezones;
+
+/// Middleware loading the settings of the request's user. A failed lookup
+/// is logged and the request goes ahead with the defaults.
+pub async fn load_user_settings(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let settings = match (
+        auth::authenticate(req.request()),
+        req.app_data::<web::Data<AppState>>(),
+    ) {
+        (Ok(user), Some(data)) => data
+            .user_settings
+            .find(&user.id)
+            .await
+            .inspect_err(|e| {
+                log::warn!(
+                    "event=user_settings_lookup_failed user_id={} error=\"{}\"",
+                    user.id,
+                    e
+                );
+            })
+            .unwrap_or_default(),
+        _ => None,
+    };
+
+    let zone = settings
+        .as_ref()
+        .map_or(Tz::UTC, |settings| settings.timezone);
+    if let Some(settings) = settings {
+        req.extensions_mut().insert(settings);
+    }
+    timezones::scope(zone, next.call(req)).await
+}
+
+/// The settings of the request's user; `None` for anonymous callers and
+/// users who have not saved any.
+pub fn of(req: &HttpRequest) -> Option<UserSettings> {
+    req.extensions().get::<UserSettings>().cloned()
+}
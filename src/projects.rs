@@ -0,0 +1,180 @@
+//! Projects group todos within a workspace, or among personal todos.
+//! Workspace projects are shared by the members; personal ones belong to
+//! the user who created them. Archiving a project archives its todos;
+//! deleting one leaves its todos in place outside any project.
+
+use chrono::{DateTime, Utc};
+
+use crate::auth::AuthUser;
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{AppState, Project, Todo};
+use crate::outbox;
+use crate::repository::{ListOptions, TodoScope, TodoSort, TodoVisibility, TodoWrite};
+
+/// The scope holding the todos of projects in `workspace_id`.
+pub fn scope(workspace_id: Option<&str>) -> TodoScope {
+    match workspace_id {
+        Some(workspace_id) => TodoScope::Workspace(workspace_id.to_string()),
+        None => TodoScope::Personal,
+    }
+}
+
+pub fn validate_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Project name must not be empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// The user whose personal projects a request in `workspace_id` works
+/// with: none inside a workspace, the caller outside one. Personal
+/// projects have an owner, so they need a signed-in caller.
+pub fn owner(
+    workspace_id: Option<&str>,
+    user: Option<&AuthUser>,
+) -> Result<Option<String>, AppError> {
+    match (workspace_id, user) {
+        (Some(_), _) => Ok(None),
+        (None, Some(user)) => Ok(Some(user.id.clone())),
+        (None, None) => Err(AppError::Unauthorized(
+            "Personal projects require a bearer token".to_string(),
+        )),
+    }
+}
+
+/// Project `id` in `workspace_id`, or among the personal projects of
+/// `owner_id`. Other users' personal projects are not found.
+pub async fn find(
+    data: &AppState,
+    workspace_id: Option<&str>,
+    owner_id: Option<&str>,
+    id: &str,
+) -> Result<Option<Project>, AppError> {
+    let project = data.projects.find_by_id(workspace_id, id).await?;
+    Ok(project.filter(|project| is_visible(project, owner_id)))
+}
+
+/// The projects of `workspace_id`, or the personal ones of `owner_id`,
+/// oldest first.
+pub async fn list(
+    data: &AppState,
+    workspace_id: Option<&str>,
+    owner_id: Option<&str>,
+) -> Result<Vec<Project>, AppError> {
+    let mut projects = data.projects.list(workspace_id).await?;
+    projects.retain(|project| is_visible(project, owner_id));
+    Ok(projects)
+}
+
+fn is_visible(project: &Project, owner_id: Option<&str>) -> bool {
+    project.workspace_id.is_some()
+        || owner_id.is_some_and(|owner_id| project.owner_id.as_deref() == Some(owner_id))
+}
+
+/// Checks that a todo in `workspace_id` owned by `owner_id` can be put in
+/// project `id`: the project must exist in the same workspace, or among
+/// the owner's personal projects, and not be archived.
+pub async fn check_assignable(
+    data: &AppState,
+    workspace_id: Option<&str>,
+    owner_id: Option<&str>,
+    id: &str,
+) -> Result<(), AppError> {
+    let project = find(data, workspace_id, owner_id, id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest(format!("Project with ID: {} does not exist", id)))?;
+    if project.archived {
+        return Err(AppError::BadRequest(format!(
+            "Project with ID: {} is archived",
+            id
+        )));
+    }
+    Ok(())
+}
+
+/// Every todo of `project`, archived ones included when `include_archived`.
+async fn todos_of(
+    data: &AppState,
+    project: &Project,
+    include_archived: bool,
+) -> Result<Vec<Todo>, AppError> {
+    Ok(data
+        .todos
+        .list(&ListOptions {
+            offset: 0,
+            // Everything; SQL backends bind the limit as an i64.
+            limit: i64::MAX as usize,
+            include_archived,
+            scope: scope(project.workspace_id.as_deref()),
+            visibility: TodoVisibility::All,
+            owner_id: None,
+            created_after: None,
+            created_before: None,
+            project_id: Some(project.id.clone()),
+            status: None,
+            assignee_id: None,
+            completed: None,
+            due_after: None,
+            due_before: None,
+            sort: TodoSort::CreatedAt,
+        })
+        .await?)
+}
+
+/// Archives the todos of `project` that are not archived yet and returns
+/// how many there were.
+pub async fn archive_todos(
+    data: &AppState,
+    project: &Project,
+    now: DateTime<Utc>,
+) -> Result<usize, AppError> {
+    let todos = todos_of(data, project, false).await?;
+    let count = todos.len();
+    for mut todo in todos {
+        todo.archived = Some(true);
+        todo.updated_at = Some(now);
+        let events = todo
+            .id
+            .map(|id| DomainEvent::TodoUpdated {
+                id,
+                todo: Some(todo.clone()),
+                title: None,
+                actor_id: None,
+            })
+            .into_iter()
+            .collect();
+        outbox::commit(data, TodoWrite::Update(todo), events).await?;
+    }
+    Ok(count)
+}
+
+/// Takes every todo out of `project` before it is deleted and returns how
+/// many there were.
+pub async fn detach_todos(
+    data: &AppState,
+    project: &Project,
+    now: DateTime<Utc>,
+) -> Result<usize, AppError> {
+    let todos = todos_of(data, project, true).await?;
+    let count = todos.len();
+    for mut todo in todos {
+        todo.project_id = None;
+        todo.updated_at = Some(now);
+        let events = todo
+            .id
+            .map(|id| DomainEvent::TodoUpdated {
+                id,
+                todo: Some(todo.clone()),
+                title: None,
+                actor_id: None,
+            })
+            .into_iter()
+            .collect();
+        outbox::commit(data, TodoWrite::Update(todo), events).await?;
+    }
+    Ok(count)
+}
@@ -0,0 +1,56 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Allows up to `limit` hits per key within a sliding window. Hits are
+/// counted in memory, so each instance enforces the limit on its own.
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    hits: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// A `limit` of 0 lets everything through.
+    pub fn new(limit: usize, window: Duration) -> Self {
+        RateLimiter {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a hit for `key`, or returns how long until one would be
+    /// allowed when the key is over its limit.
+    pub fn hit(&self, key: &str) -> Result<(), Duration> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+        // Keys whose hits have all left the window are dropped, so the map
+        // only holds recent callers.
+        hits.retain(|_, times| {
+            times
+                .back()
+                .is_some_and(|last| now.duration_since(*last) < self.window)
+        });
+
+        let times = hits.entry(key.to_string()).or_default();
+        while times
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            times.pop_front();
+        }
+        match times.front() {
+            Some(first) if times.len() >= self.limit => {
+                Err(self.window.saturating_sub(now.duration_since(*first)))
+            }
+            _ => {
+                times.push_back(now);
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use chrono_tz::Tz;
+
+use crate::jobs::{Job, JobError};
+use crate::notifier::{Notification, Notifier};
+use crate::repository::{RepositoryError, TodoRepository, UserSettingsRepository};
+use crate::timezones;
+
+/// Job that sends due reminders; scheduled every reminder poll interval.
+pub struct ReminderScanJob {
+    pub repository: Arc<dyn TodoRepository>,
+    pub user_settings: Arc<dyn UserSettingsRepository>,
+    pub notifier: Arc<dyn Notifier>,
+}
+
+impl ReminderScanJob {
+    pub const KIND: &'static str = "reminders.scan";
+}
+
+#[async_trait]
+impl Job for ReminderScanJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        deliver_due(
+            self.repository.as_ref(),
+            self.user_settings.as_ref(),
+            self.notifier.as_ref(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// The next scheduled scan is the retry.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+}
+
+/// Sends every pending reminder that is due and marks it as sent. Each
+/// shows the due date in the time zone of the todo's owner. Failed
+/// deliveries stay pending and are retried on the next scan. Returns how
+/// many reminders were sent.
+pub async fn deliver_due(
+    repository: &dyn TodoRepository,
+    user_settings: &dyn UserSettingsRepository,
+    notifier: &dyn Notifier,
+) -> Result<usize, RepositoryError> {
+    let mut sent = 0;
+
+    for mut todo in repository.pending_reminders(Some(Utc::now())).await? {
+        let zone = match &todo.owner_id {
+            Some(owner_id) => user_settings
+                .find(owner_id)
+                .await?
+                .map_or(Tz::UTC, |settings| settings.timezone),
+            None => Tz::UTC,
+        };
+        let notification = timezones::with_zone(zone, || Notification::reminder(&todo));
+        if let Err(e) = notifier.notify(&notification).await {
+            log::warn!(
+                "event=reminder_delivery_failed todo_id={} error=\"{}\"",
+                todo.id.map(|id| id.to_string()).unwrap_or_default(),
+                e
+            );
+            continue;
+        }
+
+        todo.reminder_sent_at = Some(Utc::now());
+        repository.update(&todo).await?;
+
+        log::info!(
+            "event=reminder_sent todo_id={}",
+            todo.id.map(|id| id.to_string()).unwrap_or_default()
+        );
+        sent += 1;
+    }
+
+    Ok(sent)
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+
+use super::{
+    ListOptions, RepositoryError, TodoFilter, TodoPatch, TodoRepository, TodoScope, TodoStream,
+    TodoWrite,
+};
+use crate::consistency;
+use crate::metrics::QueryMetrics;
+use crate::model::{OutboxEntry, Todo, TodoId};
+
+type Flight = Shared<BoxFuture<'static, Result<Option<Todo>, RepositoryError>>>;
+
+/// A lookup in flight: the todo, and whether it reads its own writes.
+type FlightKey = (TodoId, bool);
+
+/// Decorates another repository so concurrent lookups of the same todo
+/// share one query, as during a storm of client retries. A lookup only
+/// joins a query started since the last write through the repository, at
+/// the same read consistency, so a client reading its own write never gets
+/// the todo from before it. How many lookups queried and how many shared
+/// are counted in `metrics`.
+pub struct CoalescingTodoRepository {
+    inner: Arc<dyn TodoRepository>,
+    /// Lookups in flight, with the generation they started in.
+    flights: Arc<Mutex<HashMap<FlightKey, (u64, Flight)>>>,
+    /// Moved on by every write.
+    generation: AtomicU64,
+    metrics: Arc<QueryMetrics>,
+}
+
+impl CoalescingTodoRepository {
+    pub fn new(inner: Arc<dyn TodoRepository>, metrics: Arc<QueryMetrics>) -> Self {
+        CoalescingTodoRepository {
+            inner,
+            flights: Arc::new(Mutex::new(HashMap::new())),
+            generation: AtomicU64::new(0),
+            metrics,
+        }
+    }
+
+    /// Ends the sharing of lookups started before a write, once it is done.
+    fn wrote<T>(&self, result: Result<T, RepositoryError>) -> Result<T, RepositoryError> {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        result
+    }
+}
+
+#[async_trait]
+impl TodoRepository for CoalescingTodoRepository {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.list(options).await
+    }
+
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        self.inner.stream(options).await
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let own_writes = consistency::reads_own_writes();
+        let key = (*id, own_writes);
+        let (flight, shared) = {
+            let mut flights = self.flights.lock().unwrap();
+            match flights.get(&key) {
+                Some((started, flight)) if *started == generation => (flight.clone(), true),
+                _ => {
+                    let (inner, in_flight) = (self.inner.clone(), self.flights.clone());
+                    // Whichever lookup ends up polling the query, it runs at
+                    // the consistency of the one that started it.
+                    let flight = consistency::with_reads_own_writes(own_writes, async move {
+                        let result = inner.find_by_id(&key.0).await;
+                        let mut flights = in_flight.lock().unwrap();
+                        if flights
+                            .get(&key)
+                            .is_some_and(|(started, _)| *started == generation)
+                        {
+                            flights.remove(&key);
+                        }
+                        result
+                    })
+                    .boxed()
+                    .shared();
+                    flights.insert(key, (generation, flight.clone()));
+                    (flight, false)
+                }
+            }
+        };
+        self.metrics.record_lookup(shared);
+        flight.await
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        self.inner.exists_with_title(title).await
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.find_by_title(title).await
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.insert(todo).await)
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.insert_many(todos).await)
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.update(todo).await)
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.update_fields(id, patch).await)
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.commit(write, entries).await)
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.delete(id).await)
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.wrote(self.inner.set_completed(ids, completed, updated_at).await)
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.existing_ids(ids, scope).await
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.find_ids(filter).await
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.wrote(self.inner.delete_many(ids).await)
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.list_series(series_id).await
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.pending_recurrences().await
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.pending_reminders(due_before).await
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        self.wrote(self.inner.delete_expired(now).await)
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        self.inner.count_for_owner(owner_id).await
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        self.inner.owner_counts().await
+    }
+
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        self.inner.reconcile_counts().await
+    }
+
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        self.inner.rebuild_indexes().await
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::future;
+use futures_util::stream::StreamExt;
+
+use super::{
+    ListOptions, RepositoryError, TodoFilter, TodoPatch, TodoRepository, TodoScope, TodoStream,
+    TodoWrite,
+};
+use crate::decoding::RowDecoding;
+use crate::encryption::ContentCipher;
+use crate::model::{OutboxEntry, Todo, TodoId};
+
+/// Decorates another repository so todo content is sealed before it is
+/// written and opened again when read; callers only ever see plain text.
+/// The inner repository is shared with the re-encryption job, which needs
+/// to see the stored values. Todos whose content cannot be opened are
+/// left out of reads of many as `decoding` says.
+pub struct EncryptedTodoRepository {
+    inner: Arc<dyn TodoRepository>,
+    cipher: ContentCipher,
+    decoding: RowDecoding,
+}
+
+impl EncryptedTodoRepository {
+    pub fn new(
+        inner: Arc<dyn TodoRepository>,
+        cipher: ContentCipher,
+        decoding: RowDecoding,
+    ) -> Self {
+        EncryptedTodoRepository {
+            inner,
+            cipher,
+            decoding,
+        }
+    }
+
+    fn seal(&self, todo: &Todo) -> Result<Todo, RepositoryError> {
+        Ok(Todo {
+            content: self.cipher.seal(&todo.content)?,
+            ..todo.clone()
+        })
+    }
+
+    fn open(&self, mut todo: Todo) -> Result<Todo, RepositoryError> {
+        todo.content = self.cipher.open(&todo.content)?;
+        Ok(todo)
+    }
+
+    fn open_all(&self, todos: Vec<Todo>) -> Result<Vec<Todo>, RepositoryError> {
+        let mut opened = Vec::with_capacity(todos.len());
+        for todo in todos {
+            opened.extend(self.decoding.check(self.open(todo))?);
+        }
+        Ok(opened)
+    }
+}
+
+#[async_trait]
+impl TodoRepository for EncryptedTodoRepository {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        self.open_all(self.inner.list(options).await?)
+    }
+
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        let cipher = self.cipher.clone();
+        let decoding = self.decoding;
+        Ok(self
+            .inner
+            .stream(options)
+            .await?
+            .filter_map(move |todo| {
+                let opened = match todo {
+                    Ok(mut todo) => cipher.open(&todo.content).map(|content| {
+                        todo.content = content;
+                        todo
+                    }),
+                    Err(e) => return future::ready(Some(Err(e))),
+                };
+                future::ready(decoding.check(opened).transpose())
+            })
+            .boxed())
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        self.inner
+            .find_by_id(id)
+            .await?
+            .map(|todo| self.open(todo))
+            .transpose()
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        self.inner.exists_with_title(title).await
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        self.open_all(self.inner.find_by_title(title).await?)
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.inner.insert(&self.seal(todo)?).await
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        let todos = todos
+            .iter()
+            .map(|todo| self.seal(todo))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.inner.insert_many(&todos).await
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.inner.update(&self.seal(todo)?).await
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        match &patch.content {
+            Some(content) => {
+                let patch = TodoPatch {
+                    content: Some(self.cipher.seal(content)?),
+                    ..patch.clone()
+                };
+                self.inner.update_fields(id, &patch).await
+            }
+            None => self.inner.update_fields(id, patch).await,
+        }
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        let write = match write {
+            TodoWrite::Insert(todo) => TodoWrite::Insert(self.seal(todo)?),
+            TodoWrite::Update(todo) => TodoWrite::Update(self.seal(todo)?),
+            TodoWrite::Patch(id, patch) => TodoWrite::Patch(
+                *id,
+                TodoPatch {
+                    content: match &patch.content {
+                        Some(content) => Some(self.cipher.seal(content)?),
+                        None => None,
+                    },
+                    ..patch.clone()
+                },
+            ),
+            TodoWrite::Delete(id) => TodoWrite::Delete(*id),
+        };
+        self.inner.commit(&write, entries).await
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        self.inner.delete(id).await
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.set_completed(ids, completed, updated_at).await
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.existing_ids(ids, scope).await
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.find_ids(filter).await
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.inner.delete_many(ids).await
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        self.open_all(self.inner.list_series(series_id).await?)
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        self.open_all(self.inner.pending_recurrences().await?)
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        self.open_all(self.inner.pending_reminders(due_before).await?)
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.delete_expired(now).await
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        self.inner.count_for_owner(owner_id).await
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        self.inner.owner_counts().await
+    }
+
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        self.inner.reconcile_counts().await
+    }
+
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        self.inner.rebuild_indexes().await
+    }
+}
@@ -0,0 +1,1658 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+
+use super::{
+    is_pending_recurrence, is_pending_reminder, ActivityRepository, AttachmentRepository,
+    ChangeRepository, CommentRepository, FeatureFlagRepository, JobRepository, LeaseRepository,
+    ListOptions, LoginAttemptRepository, MaintenanceRepository, NotificationSettingsRepository,
+    OutboxRepository, PasswordResetRepository, ProjectRepository, RefreshTokenRepository,
+    RepositoryError, SchemaRepository, SessionRepository, TemplateRepository, TodoAclRepository,
+    TodoFilter, TodoPatch, TodoRepository, TodoScope, TodoStream, TodoWrite, UndoRepository,
+    UserRepository, UserSettingsRepository, WebhookRepository, WorkspaceRepository,
+};
+use crate::migrations::SchemaStatus;
+use crate::model::{
+    Activity, ActivityKind, Attachment, Comment, DeadLetter, JobRecord, Maintenance,
+    NotificationSettings, OutboxEntry, PasswordResetToken, Project, RefreshToken, Role, Template,
+    Todo, TodoChange, TodoId, TodoShare, UndoEntry, User, UserIdentity, UserSession, UserSettings,
+    UserTotp, Webhook, Workspace, WorkspaceMember,
+};
+use crate::titles;
+
+/// Process-local storage for development and tests. Nothing survives a restart.
+#[derive(Default)]
+pub struct InMemoryTodoRepository {
+    todos: RwLock<HashMap<TodoId, Todo>>,
+    /// Where `commit` puts its entries.
+    outbox: Arc<InMemoryOutboxRepository>,
+}
+
+impl InMemoryTodoRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A repository that commits its outbox entries to `outbox`.
+    pub fn with_outbox(outbox: Arc<InMemoryOutboxRepository>) -> Self {
+        InMemoryTodoRepository {
+            todos: RwLock::default(),
+            outbox,
+        }
+    }
+}
+
+#[async_trait]
+impl TodoRepository for InMemoryTodoRepository {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        let now = Utc::now();
+        let todos = self.todos.read().unwrap();
+        let mut all: Vec<&Todo> = todos
+            .values()
+            .filter(|todo| !todo.is_expired(now))
+            .filter(|todo| options.matches(todo))
+            .collect();
+        all.sort_by(|a, b| options.sort.compare(a, b));
+        Ok(all
+            .into_iter()
+            .skip(options.offset)
+            .take(options.limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        let todos = self.list(options).await?;
+        Ok(stream::iter(todos.into_iter().map(Ok)).boxed())
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|todo| !todo.is_expired(Utc::now()))
+            .cloned())
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        let key = titles::key(title);
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .values()
+            .any(|todo| titles::key(&todo.title) == key && !todo.is_expired(Utc::now())))
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        let now = Utc::now();
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .values()
+            .filter(|todo| todo.title == title && !todo.is_expired(now))
+            .cloned()
+            .collect())
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        let id = todo
+            .id
+            .ok_or_else(|| RepositoryError::Database("todo has no ID".to_string()))?;
+        self.todos.write().unwrap().insert(id, todo.clone());
+        Ok(())
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        for todo in todos {
+            self.insert(todo).await?;
+        }
+        Ok(())
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        // Mirrors Scylla, where UPDATE is an upsert.
+        self.insert(todo).await
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        if let Some(todo) = self.todos.write().unwrap().get_mut(id) {
+            patch.apply(todo);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        self.todos.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        // Both locks are held for the whole change, so no reader sees one
+        // half of it without the other.
+        let mut todos = self.todos.write().unwrap();
+        let mut outbox = self.outbox.entries.write().unwrap();
+        match write {
+            TodoWrite::Insert(todo) | TodoWrite::Update(todo) => {
+                let id = todo
+                    .id
+                    .ok_or_else(|| RepositoryError::Database("todo has no ID".to_string()))?;
+                todos.insert(id, todo.clone());
+            }
+            TodoWrite::Patch(id, patch) => {
+                if let Some(todo) = todos.get_mut(id) {
+                    patch.apply(todo);
+                }
+            }
+            TodoWrite::Delete(id) => {
+                todos.remove(id);
+            }
+        }
+        for entry in entries {
+            outbox.insert(entry.id.clone(), entry.clone());
+        }
+        Ok(())
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        let mut todos = self.todos.write().unwrap();
+        let mut updated = Vec::new();
+        for id in ids {
+            if let Some(todo) = todos.get_mut(id) {
+                todo.completed = Some(completed);
+                todo.updated_at = Some(updated_at);
+                updated.push(*id);
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        let todos = self.todos.read().unwrap();
+        let mut existing: Vec<TodoId> = Vec::new();
+        for id in ids {
+            let in_scope = todos.get(id).is_some_and(|todo| scope.contains(todo));
+            if in_scope && !existing.contains(id) {
+                existing.push(*id);
+            }
+        }
+        Ok(existing)
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, todo)| filter.matches(todo))
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let mut todos = self.todos.write().unwrap();
+        for id in ids {
+            todos.remove(id);
+        }
+        Ok(())
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .values()
+            .filter(|todo| todo.series_id.as_ref() == Some(series_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .values()
+            .filter(|todo| is_pending_recurrence(todo))
+            .cloned()
+            .collect())
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        let mut reminders: Vec<Todo> = self
+            .todos
+            .read()
+            .unwrap()
+            .values()
+            .filter(|todo| is_pending_reminder(todo, due_before))
+            .cloned()
+            .collect();
+        reminders.sort_by_key(|todo| todo.remind_at);
+        Ok(reminders)
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        let mut todos = self.todos.write().unwrap();
+        let expired: Vec<TodoId> = todos
+            .iter()
+            .filter(|(_, todo)| todo.is_expired(now))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &expired {
+            todos.remove(id);
+        }
+        Ok(expired)
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        let now = Utc::now();
+        Ok(self
+            .todos
+            .read()
+            .unwrap()
+            .values()
+            .filter(|todo| !todo.is_expired(now) && todo.owner_id.as_deref() == owner_id)
+            .count() as u64)
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        let now = Utc::now();
+        let mut counts = HashMap::new();
+        for todo in self.todos.read().unwrap().values() {
+            if !todo.is_expired(now) {
+                *counts.entry(todo.owner_id.clone()).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        Ok(0)
+    }
+
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        Ok(0)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryWebhookRepository {
+    webhooks: RwLock<HashMap<String, Webhook>>,
+    dead_letters: RwLock<Vec<DeadLetter>>,
+}
+
+impl InMemoryWebhookRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for InMemoryWebhookRepository {
+    async fn list(&self) -> Result<Vec<Webhook>, RepositoryError> {
+        let mut webhooks: Vec<Webhook> = self.webhooks.read().unwrap().values().cloned().collect();
+        webhooks.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(webhooks)
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Webhook>, RepositoryError> {
+        Ok(self.webhooks.read().unwrap().get(id).cloned())
+    }
+
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        self.webhooks
+            .write()
+            .unwrap()
+            .insert(webhook.id.clone(), webhook.clone());
+        Ok(())
+    }
+
+    async fn update(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        self.insert(webhook).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        self.webhooks.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn add_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError> {
+        self.dead_letters.write().unwrap().push(dead_letter.clone());
+        Ok(())
+    }
+
+    async fn list_dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter>, RepositoryError> {
+        Ok(self
+            .dead_letters
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryNotificationSettingsRepository {
+    settings: RwLock<HashMap<String, NotificationSettings>>,
+}
+
+impl InMemoryNotificationSettingsRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl NotificationSettingsRepository for InMemoryNotificationSettingsRepository {
+    async fn list(&self) -> Result<Vec<NotificationSettings>, RepositoryError> {
+        Ok(self.settings.read().unwrap().values().cloned().collect())
+    }
+
+    async fn find(&self, email: &str) -> Result<Option<NotificationSettings>, RepositoryError> {
+        Ok(self.settings.read().unwrap().get(email).cloned())
+    }
+
+    async fn upsert(&self, settings: &NotificationSettings) -> Result<(), RepositoryError> {
+        self.settings
+            .write()
+            .unwrap()
+            .insert(settings.email.clone(), settings.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, email: &str) -> Result<(), RepositoryError> {
+        self.settings.write().unwrap().remove(email);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryUserSettingsRepository {
+    settings: RwLock<HashMap<String, UserSettings>>,
+}
+
+impl InMemoryUserSettingsRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserSettingsRepository for InMemoryUserSettingsRepository {
+    async fn find(&self, user_id: &str) -> Result<Option<UserSettings>, RepositoryError> {
+        Ok(self.settings.read().unwrap().get(user_id).cloned())
+    }
+
+    async fn upsert(&self, settings: &UserSettings) -> Result<(), RepositoryError> {
+        self.settings
+            .write()
+            .unwrap()
+            .insert(settings.user_id.clone(), settings.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError> {
+        self.settings.write().unwrap().remove(user_id);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryJobRepository {
+    jobs: RwLock<HashMap<String, JobRecord>>,
+}
+
+impl InMemoryJobRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl JobRepository for InMemoryJobRepository {
+    async fn insert(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        self.jobs
+            .write()
+            .unwrap()
+            .insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    async fn update(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        self.insert(job).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<JobRecord>, RepositoryError> {
+        Ok(self.jobs.read().unwrap().get(id).cloned())
+    }
+
+    async fn list_unfinished(&self) -> Result<Vec<JobRecord>, RepositoryError> {
+        Ok(self
+            .jobs
+            .read()
+            .unwrap()
+            .values()
+            .filter(|job| !job.status.is_finished())
+            .cloned()
+            .collect())
+    }
+
+    async fn purge_finished(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let mut jobs = self.jobs.write().unwrap();
+        let count = jobs.len();
+        jobs.retain(|_, job| !(job.status.is_finished() && job.updated_at < before));
+        Ok(count - jobs.len())
+    }
+}
+
+/// Memory storage has no schema, so it is always current.
+#[derive(Default)]
+pub struct InMemorySchemaRepository;
+
+impl InMemorySchemaRepository {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl SchemaRepository for InMemorySchemaRepository {
+    async fn status(&self) -> Result<SchemaStatus, RepositoryError> {
+        Ok(SchemaStatus::default())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryFeatureFlagRepository {
+    flags: RwLock<HashMap<String, bool>>,
+}
+
+impl InMemoryFeatureFlagRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl FeatureFlagRepository for InMemoryFeatureFlagRepository {
+    async fn list(&self) -> Result<HashMap<String, bool>, RepositoryError> {
+        Ok(self.flags.read().unwrap().clone())
+    }
+
+    async fn set(&self, name: &str, enabled: bool) -> Result<(), RepositoryError> {
+        self.flags
+            .write()
+            .unwrap()
+            .insert(name.to_string(), enabled);
+        Ok(())
+    }
+
+    async fn clear(&self, name: &str) -> Result<(), RepositoryError> {
+        self.flags.write().unwrap().remove(name);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryMaintenanceRepository {
+    current: RwLock<Option<Maintenance>>,
+}
+
+impl InMemoryMaintenanceRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl MaintenanceRepository for InMemoryMaintenanceRepository {
+    async fn current(&self) -> Result<Option<Maintenance>, RepositoryError> {
+        Ok(self.current.read().unwrap().clone())
+    }
+
+    async fn start(&self, maintenance: &Maintenance) -> Result<(), RepositoryError> {
+        *self.current.write().unwrap() = Some(maintenance.clone());
+        Ok(())
+    }
+
+    async fn end(&self) -> Result<(), RepositoryError> {
+        *self.current.write().unwrap() = None;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryLeaseRepository {
+    /// Holder and expiry by lease name.
+    leases: RwLock<HashMap<String, (String, DateTime<Utc>)>>,
+}
+
+impl InMemoryLeaseRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LeaseRepository for InMemoryLeaseRepository {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let mut leases = self.leases.write().unwrap();
+        if let Some((current, expires_at)) = leases.get(name) {
+            if current != holder && *expires_at > now {
+                return Ok(false);
+            }
+        }
+        leases.insert(name.to_string(), (holder.to_string(), until));
+        Ok(true)
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), RepositoryError> {
+        let mut leases = self.leases.write().unwrap();
+        if leases
+            .get(name)
+            .is_some_and(|(current, _)| current == holder)
+        {
+            leases.remove(name);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    users: RwLock<HashMap<String, User>>,
+    /// User IDs keyed by `(provider, subject)`.
+    identities: RwLock<HashMap<(String, String), String>>,
+    totp: RwLock<HashMap<String, UserTotp>>,
+}
+
+impl InMemoryUserRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError> {
+        self.users
+            .write()
+            .unwrap()
+            .insert(user.id.clone(), user.clone());
+        Ok(())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        Ok(self
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .find(|user| user.email == email)
+            .cloned())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError> {
+        Ok(self.users.read().unwrap().get(id).cloned())
+    }
+
+    async fn count(&self) -> Result<usize, RepositoryError> {
+        Ok(self.users.read().unwrap().len())
+    }
+
+    async fn update_profile(&self, user: &User) -> Result<(), RepositoryError> {
+        if let Some(stored) = self.users.write().unwrap().get_mut(&user.id) {
+            stored.email = user.email.clone();
+            stored.display_name = user.display_name.clone();
+            stored.avatar_url = user.avatar_url.clone();
+            stored.updated_at = user.updated_at;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        self.users.write().unwrap().remove(id);
+        self.identities
+            .write()
+            .unwrap()
+            .retain(|_, user_id| user_id != id);
+        self.totp.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn update_password(
+        &self,
+        id: &str,
+        password_hash: &str,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        if let Some(user) = self.users.write().unwrap().get_mut(id) {
+            user.password_hash = password_hash.to_string();
+            user.updated_at = updated_at;
+        }
+        Ok(())
+    }
+
+    async fn update_role(
+        &self,
+        id: &str,
+        role: Role,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        if let Some(user) = self.users.write().unwrap().get_mut(id) {
+            user.role = role;
+            user.updated_at = updated_at;
+        }
+        Ok(())
+    }
+
+    async fn link_identity(&self, identity: &UserIdentity) -> Result<(), RepositoryError> {
+        self.identities.write().unwrap().insert(
+            (identity.provider.clone(), identity.subject.clone()),
+            identity.user_id.clone(),
+        );
+        Ok(())
+    }
+
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let user_id = self
+            .identities
+            .read()
+            .unwrap()
+            .get(&(provider.to_string(), subject.to_string()))
+            .cloned();
+        match user_id {
+            Some(user_id) => self.find_by_id(&user_id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn find_totp(&self, user_id: &str) -> Result<Option<UserTotp>, RepositoryError> {
+        Ok(self.totp.read().unwrap().get(user_id).cloned())
+    }
+
+    async fn save_totp(
+        &self,
+        user_id: &str,
+        totp: Option<&UserTotp>,
+    ) -> Result<(), RepositoryError> {
+        let mut stored = self.totp.write().unwrap();
+        match totp {
+            Some(totp) => stored.insert(user_id.to_string(), totp.clone()),
+            None => stored.remove(user_id),
+        };
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryWorkspaceRepository {
+    workspaces: RwLock<HashMap<String, Workspace>>,
+    /// Keyed by `(workspace_id, user_id)`.
+    members: RwLock<HashMap<(String, String), WorkspaceMember>>,
+}
+
+impl InMemoryWorkspaceRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WorkspaceRepository for InMemoryWorkspaceRepository {
+    async fn insert(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        self.workspaces
+            .write()
+            .unwrap()
+            .insert(workspace.id.clone(), workspace.clone());
+        Ok(())
+    }
+
+    async fn update(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        self.insert(workspace).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        self.workspaces.write().unwrap().remove(id);
+        self.members
+            .write()
+            .unwrap()
+            .retain(|(workspace_id, _), _| workspace_id != id);
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Workspace>, RepositoryError> {
+        Ok(self.workspaces.read().unwrap().get(id).cloned())
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Workspace>, RepositoryError> {
+        let members = self.members.read().unwrap();
+        let workspaces = self.workspaces.read().unwrap();
+        let mut found: Vec<Workspace> = members
+            .keys()
+            .filter(|(_, member_id)| member_id == user_id)
+            .filter_map(|(workspace_id, _)| workspaces.get(workspace_id).cloned())
+            .collect();
+        found.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(found)
+    }
+
+    async fn upsert_member(&self, member: &WorkspaceMember) -> Result<(), RepositoryError> {
+        self.members.write().unwrap().insert(
+            (member.workspace_id.clone(), member.user_id.clone()),
+            member.clone(),
+        );
+        Ok(())
+    }
+
+    async fn remove_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<(), RepositoryError> {
+        self.members
+            .write()
+            .unwrap()
+            .remove(&(workspace_id.to_string(), user_id.to_string()));
+        Ok(())
+    }
+
+    async fn find_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceMember>, RepositoryError> {
+        Ok(self
+            .members
+            .read()
+            .unwrap()
+            .get(&(workspace_id.to_string(), user_id.to_string()))
+            .cloned())
+    }
+
+    async fn list_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMember>, RepositoryError> {
+        let mut members: Vec<WorkspaceMember> = self
+            .members
+            .read()
+            .unwrap()
+            .values()
+            .filter(|member| member.workspace_id == workspace_id)
+            .cloned()
+            .collect();
+        members.sort_by_key(|member| member.joined_at);
+        Ok(members)
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryTodoAclRepository {
+    /// Keyed by `(todo_id, user_id)`.
+    shares: RwLock<HashMap<(TodoId, String), TodoShare>>,
+}
+
+impl InMemoryTodoAclRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TodoAclRepository for InMemoryTodoAclRepository {
+    async fn upsert(&self, share: &TodoShare) -> Result<(), RepositoryError> {
+        self.shares
+            .write()
+            .unwrap()
+            .insert((share.todo_id, share.user_id.clone()), share.clone());
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        todo_id: &TodoId,
+        user_id: &str,
+    ) -> Result<Option<TodoShare>, RepositoryError> {
+        Ok(self
+            .shares
+            .read()
+            .unwrap()
+            .get(&(*todo_id, user_id.to_string()))
+            .cloned())
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<TodoShare>, RepositoryError> {
+        let mut shares: Vec<TodoShare> = self
+            .shares
+            .read()
+            .unwrap()
+            .values()
+            .filter(|share| share.user_id == user_id)
+            .cloned()
+            .collect();
+        shares.sort_by_key(|share| std::cmp::Reverse(share.shared_at));
+        Ok(shares)
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.shares
+            .write()
+            .unwrap()
+            .retain(|(todo_id, _), _| !todo_ids.contains(todo_id));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryCommentRepository {
+    comments: RwLock<HashMap<String, Comment>>,
+}
+
+impl InMemoryCommentRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CommentRepository for InMemoryCommentRepository {
+    async fn insert(&self, comment: &Comment) -> Result<(), RepositoryError> {
+        self.comments
+            .write()
+            .unwrap()
+            .insert(comment.id.clone(), comment.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Comment>, RepositoryError> {
+        Ok(self
+            .comments
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|comment| comment.todo_id == *todo_id)
+            .cloned())
+    }
+
+    async fn delete(&self, _todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        self.comments.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        todo_id: &TodoId,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Comment>, RepositoryError> {
+        let mut comments: Vec<Comment> = self
+            .comments
+            .read()
+            .unwrap()
+            .values()
+            .filter(|comment| comment.todo_id == *todo_id)
+            .cloned()
+            .collect();
+        comments.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(comments.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn count_by_todo(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<HashMap<TodoId, usize>, RepositoryError> {
+        let mut counts = HashMap::new();
+        for comment in self.comments.read().unwrap().values() {
+            if todo_ids.contains(&comment.todo_id) {
+                *counts.entry(comment.todo_id).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.comments
+            .write()
+            .unwrap()
+            .retain(|_, comment| !todo_ids.contains(&comment.todo_id));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryActivityRepository {
+    entries: RwLock<Vec<Activity>>,
+}
+
+impl InMemoryActivityRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn page(
+        &self,
+        matches: impl Fn(&Activity) -> bool,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Vec<Activity> {
+        let mut entries: Vec<Activity> = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|activity| matches(activity))
+            .filter(|activity| kinds.is_empty() || kinds.contains(&activity.kind))
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| {
+            a.occurred_at
+                .cmp(&b.occurred_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        entries.into_iter().skip(offset).take(limit).collect()
+    }
+}
+
+#[async_trait]
+impl ActivityRepository for InMemoryActivityRepository {
+    async fn insert(&self, activity: &Activity) -> Result<(), RepositoryError> {
+        self.entries.write().unwrap().push(activity.clone());
+        Ok(())
+    }
+
+    async fn list_for_todo(
+        &self,
+        todo_id: &TodoId,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        Ok(self.page(
+            |activity| activity.todo_id == *todo_id,
+            kinds,
+            offset,
+            limit,
+        ))
+    }
+
+    async fn list_for_actor(
+        &self,
+        actor_id: &str,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        Ok(self.page(
+            |activity| activity.actor_id.as_deref() == Some(actor_id),
+            kinds,
+            offset,
+            limit,
+        ))
+    }
+
+    async fn list_before(&self, before: DateTime<Utc>) -> Result<Vec<Activity>, RepositoryError> {
+        Ok(self.page(|activity| activity.occurred_at < before, &[], 0, usize::MAX))
+    }
+
+    async fn delete(&self, entries: &[Activity]) -> Result<(), RepositoryError> {
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|activity| !entries.iter().any(|entry| entry.id == activity.id));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryUndoRepository {
+    entries: RwLock<Vec<UndoEntry>>,
+}
+
+impl InMemoryUndoRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UndoRepository for InMemoryUndoRepository {
+    async fn insert(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        self.entries.write().unwrap().push(entry.clone());
+        Ok(())
+    }
+
+    async fn latest(
+        &self,
+        actor_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<UndoEntry>, RepositoryError> {
+        Ok(self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.actor_id == actor_id && entry.created_at >= since)
+            .max_by(|a, b| {
+                a.created_at
+                    .cmp(&b.created_at)
+                    .then_with(|| a.id.cmp(&b.id))
+            })
+            .cloned())
+    }
+
+    async fn delete(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|stored| stored.id != entry.id);
+        Ok(())
+    }
+
+    async fn delete_before(
+        &self,
+        actor_id: &str,
+        before: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|entry| entry.actor_id != actor_id || entry.created_at >= before);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryChangeRepository {
+    changes: RwLock<HashMap<TodoId, TodoChange>>,
+}
+
+impl InMemoryChangeRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ChangeRepository for InMemoryChangeRepository {
+    async fn record(&self, change: &TodoChange) -> Result<(), RepositoryError> {
+        self.changes
+            .write()
+            .unwrap()
+            .insert(change.todo_id, change.clone());
+        Ok(())
+    }
+
+    async fn find(&self, todo_id: &TodoId) -> Result<Option<TodoChange>, RepositoryError> {
+        Ok(self.changes.read().unwrap().get(todo_id).cloned())
+    }
+
+    async fn since(&self, token: i64, limit: usize) -> Result<Vec<TodoChange>, RepositoryError> {
+        let mut changes: Vec<TodoChange> = self
+            .changes
+            .read()
+            .unwrap()
+            .values()
+            .filter(|change| change.token > token)
+            .cloned()
+            .collect();
+        changes.sort_by_key(|change| change.token);
+        changes.truncate(limit);
+        Ok(changes)
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let mut changes = self.changes.write().unwrap();
+        let count = changes.len();
+        changes.retain(|_, change| !(change.deleted && change.changed_at < before));
+        Ok(count - changes.len())
+    }
+
+    async fn list_deleted(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<TodoChange>, RepositoryError> {
+        Ok(self
+            .changes
+            .read()
+            .unwrap()
+            .values()
+            .filter(|change| change.deleted && change.changed_at < before)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let mut changes = self.changes.write().unwrap();
+        for todo_id in todo_ids {
+            changes.remove(todo_id);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryOutboxRepository {
+    entries: RwLock<HashMap<String, OutboxEntry>>,
+}
+
+impl InMemoryOutboxRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for InMemoryOutboxRepository {
+    async fn insert(&self, entry: &OutboxEntry) -> Result<(), RepositoryError> {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(entry.id.clone(), entry.clone());
+        Ok(())
+    }
+
+    async fn pending(
+        &self,
+        consumer: &str,
+        limit: usize,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        let mut entries: Vec<OutboxEntry> = self
+            .entries
+            .read()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.consumer == consumer && entry.delivered_at.is_none())
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.created_at);
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    async fn mark_delivered(&self, id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        if let Some(entry) = self.entries.write().unwrap().get_mut(id) {
+            entry.delivered_at = Some(at);
+        }
+        Ok(())
+    }
+
+    async fn purge_delivered(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let mut entries = self.entries.write().unwrap();
+        let count = entries.len();
+        entries.retain(|_, entry| entry.delivered_at.is_none_or(|at| at >= before));
+        Ok(count - entries.len())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryRefreshTokenRepository {
+    tokens: RwLock<HashMap<String, RefreshToken>>,
+    revoked: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl InMemoryRefreshTokenRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for InMemoryRefreshTokenRepository {
+    async fn insert(&self, token: &RefreshToken) -> Result<(), RepositoryError> {
+        self.tokens
+            .write()
+            .unwrap()
+            .insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        Ok(self.tokens.read().unwrap().get(token_hash).cloned())
+    }
+
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(token_hash) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(
+        &self,
+        family_id: &str,
+        until: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        self.revoked
+            .write()
+            .unwrap()
+            .insert(family_id.to_string(), until);
+        Ok(())
+    }
+
+    async fn is_revoked(&self, family_id: &str) -> Result<bool, RepositoryError> {
+        Ok(self.revoked.read().unwrap().contains_key(family_id))
+    }
+}
+
+/// Sessions keyed by `(user_id, id)`.
+#[derive(Default)]
+pub struct InMemorySessionRepository {
+    sessions: RwLock<HashMap<(String, String), UserSession>>,
+}
+
+impl InMemorySessionRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl SessionRepository for InMemorySessionRepository {
+    async fn save(&self, session: &UserSession) -> Result<(), RepositoryError> {
+        self.sessions.write().unwrap().insert(
+            (session.user_id.clone(), session.id.clone()),
+            session.clone(),
+        );
+        Ok(())
+    }
+
+    async fn find(&self, user_id: &str, id: &str) -> Result<Option<UserSession>, RepositoryError> {
+        Ok(self
+            .sessions
+            .read()
+            .unwrap()
+            .get(&(user_id.to_string(), id.to_string()))
+            .cloned())
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, RepositoryError> {
+        let mut sessions: Vec<UserSession> = self
+            .sessions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|session| session.user_id == user_id && session.expires_at > now)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+        self.sessions
+            .write()
+            .unwrap()
+            .remove(&(user_id.to_string(), id.to_string()));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryLoginAttemptRepository {
+    failures: RwLock<HashMap<String, Vec<DateTime<Utc>>>>,
+    lockouts: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl InMemoryLoginAttemptRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for InMemoryLoginAttemptRepository {
+    async fn record_failure(
+        &self,
+        key: &str,
+        at: DateTime<Utc>,
+        since: DateTime<Utc>,
+    ) -> Result<usize, RepositoryError> {
+        let mut failures = self.failures.write().unwrap();
+        let times = failures.entry(key.to_string()).or_default();
+        times.retain(|time| *time > since);
+        times.push(at);
+        Ok(times.len())
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.failures.write().unwrap().remove(key);
+        self.lockouts
+            .write()
+            .unwrap()
+            .insert(key.to_string(), until);
+        Ok(())
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        Ok(self.lockouts.read().unwrap().get(key).copied())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), RepositoryError> {
+        self.failures.write().unwrap().remove(key);
+        self.lockouts.write().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryPasswordResetRepository {
+    tokens: RwLock<HashMap<String, PasswordResetToken>>,
+}
+
+impl InMemoryPasswordResetRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl PasswordResetRepository for InMemoryPasswordResetRepository {
+    async fn insert(&self, token: &PasswordResetToken) -> Result<(), RepositoryError> {
+        self.tokens
+            .write()
+            .unwrap()
+            .insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        Ok(self.tokens.read().unwrap().get(token_hash).cloned())
+    }
+
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let mut tokens = self.tokens.write().unwrap();
+        match tokens.get_mut(token_hash) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn mark_all_used(&self, user_id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        for token in self.tokens.write().unwrap().values_mut() {
+            if token.user_id == user_id && token.used_at.is_none() {
+                token.used_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryTemplateRepository {
+    templates: RwLock<HashMap<String, Template>>,
+}
+
+impl InMemoryTemplateRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TemplateRepository for InMemoryTemplateRepository {
+    async fn insert(&self, template: &Template) -> Result<(), RepositoryError> {
+        self.templates
+            .write()
+            .unwrap()
+            .insert(template.id.clone(), template.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        owner_id: &str,
+        id: &str,
+    ) -> Result<Option<Template>, RepositoryError> {
+        Ok(self
+            .templates
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|template| template.owner_id == owner_id)
+            .cloned())
+    }
+
+    async fn list_for_owner(&self, owner_id: &str) -> Result<Vec<Template>, RepositoryError> {
+        let mut templates: Vec<Template> = self
+            .templates
+            .read()
+            .unwrap()
+            .values()
+            .filter(|template| template.owner_id == owner_id)
+            .cloned()
+            .collect();
+        templates.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(templates)
+    }
+
+    async fn delete(&self, owner_id: &str, id: &str) -> Result<(), RepositoryError> {
+        let mut templates = self.templates.write().unwrap();
+        if templates
+            .get(id)
+            .is_some_and(|template| template.owner_id == owner_id)
+        {
+            templates.remove(id);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryProjectRepository {
+    projects: RwLock<HashMap<String, Project>>,
+}
+
+impl InMemoryProjectRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for InMemoryProjectRepository {
+    async fn insert(&self, project: &Project) -> Result<(), RepositoryError> {
+        self.projects
+            .write()
+            .unwrap()
+            .insert(project.id.clone(), project.clone());
+        Ok(())
+    }
+
+    async fn update(&self, project: &Project) -> Result<(), RepositoryError> {
+        self.insert(project).await
+    }
+
+    async fn find_by_id(
+        &self,
+        workspace_id: Option<&str>,
+        id: &str,
+    ) -> Result<Option<Project>, RepositoryError> {
+        Ok(self
+            .projects
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|project| project.workspace_id.as_deref() == workspace_id)
+            .cloned())
+    }
+
+    async fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Project>, RepositoryError> {
+        let mut projects: Vec<Project> = self
+            .projects
+            .read()
+            .unwrap()
+            .values()
+            .filter(|project| project.workspace_id.as_deref() == workspace_id)
+            .cloned()
+            .collect();
+        projects.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(projects)
+    }
+
+    async fn delete(&self, workspace_id: Option<&str>, id: &str) -> Result<(), RepositoryError> {
+        let mut projects = self.projects.write().unwrap();
+        if projects
+            .get(id)
+            .is_some_and(|project| project.workspace_id.as_deref() == workspace_id)
+        {
+            projects.remove(id);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryAttachmentRepository {
+    attachments: RwLock<HashMap<String, Attachment>>,
+}
+
+impl InMemoryAttachmentRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AttachmentRepository for InMemoryAttachmentRepository {
+    async fn insert(&self, attachment: &Attachment) -> Result<(), RepositoryError> {
+        self.attachments
+            .write()
+            .unwrap()
+            .insert(attachment.id.clone(), attachment.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Attachment>, RepositoryError> {
+        Ok(self
+            .attachments
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|attachment| attachment.todo_id == *todo_id)
+            .cloned())
+    }
+
+    async fn list(&self, todo_id: &TodoId) -> Result<Vec<Attachment>, RepositoryError> {
+        let mut attachments: Vec<Attachment> = self
+            .attachments
+            .read()
+            .unwrap()
+            .values()
+            .filter(|attachment| attachment.todo_id == *todo_id)
+            .cloned()
+            .collect();
+        attachments.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(attachments)
+    }
+
+    async fn delete(&self, _todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        self.attachments.write().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn delete_for_todos(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<Vec<Attachment>, RepositoryError> {
+        let mut attachments = self.attachments.write().unwrap();
+        let removed: Vec<Attachment> = attachments
+            .values()
+            .filter(|attachment| todo_ids.contains(&attachment.todo_id))
+            .cloned()
+            .collect();
+        for attachment in &removed {
+            attachments.remove(&attachment.id);
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,1064 @@
+mod coalescing;
+mod encrypted;
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod resilient;
+mod scylla;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod synced;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::migrations::SchemaStatus;
+use crate::model::{
+    Activity, ActivityKind, Attachment, Comment, DeadLetter, JobRecord, Maintenance,
+    NotificationSettings, OutboxEntry, PasswordResetToken, Project, RefreshToken, Role, Template,
+    Todo, TodoChange, TodoId, TodoShare, TodoStatus, UndoEntry, User, UserIdentity, UserSession,
+    UserSettings, UserTotp, VersionVector, Webhook, Workspace, WorkspaceMember,
+};
+use crate::scheduling::Recurrence;
+
+pub use self::coalescing::CoalescingTodoRepository;
+pub use self::encrypted::EncryptedTodoRepository;
+pub use self::memory::{
+    InMemoryActivityRepository, InMemoryAttachmentRepository, InMemoryChangeRepository,
+    InMemoryCommentRepository, InMemoryFeatureFlagRepository, InMemoryJobRepository,
+    InMemoryLeaseRepository, InMemoryLoginAttemptRepository, InMemoryMaintenanceRepository,
+    InMemoryNotificationSettingsRepository, InMemoryOutboxRepository,
+    InMemoryPasswordResetRepository, InMemoryProjectRepository, InMemoryRefreshTokenRepository,
+    InMemorySchemaRepository, InMemorySessionRepository, InMemoryTemplateRepository,
+    InMemoryTodoAclRepository, InMemoryTodoRepository, InMemoryUndoRepository,
+    InMemoryUserRepository, InMemoryUserSettingsRepository, InMemoryWebhookRepository,
+    InMemoryWorkspaceRepository,
+};
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresTodoRepository;
+pub use self::resilient::{Resilience, ResilientRepository};
+pub use self::scylla::{decode_todos, decode_window, ScyllaTodoRepository};
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteTodoRepository;
+pub use self::synced::SyncedTodoRepository;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum RepositoryError {
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("Database query timed out")]
+    Timeout,
+    #[error("Database is unavailable, try again later")]
+    Unavailable,
+    /// The database answered, but with a stored value that cannot be read
+    /// back, such as a malformed timestamp.
+    #[error("Stored data could not be decoded: {0}")]
+    Decode(String),
+}
+
+/// Storage handles for the configured backend.
+pub struct Repositories {
+    pub todos: Arc<dyn TodoRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub workspaces: Arc<dyn WorkspaceRepository>,
+    pub acl: Arc<dyn TodoAclRepository>,
+    pub comments: Arc<dyn CommentRepository>,
+    pub activity: Arc<dyn ActivityRepository>,
+    pub undo: Arc<dyn UndoRepository>,
+    pub changes: Arc<dyn ChangeRepository>,
+    pub outbox: Arc<dyn OutboxRepository>,
+    pub refresh_tokens: Arc<dyn RefreshTokenRepository>,
+    pub password_resets: Arc<dyn PasswordResetRepository>,
+    pub login_attempts: Arc<dyn LoginAttemptRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub attachments: Arc<dyn AttachmentRepository>,
+    pub templates: Arc<dyn TemplateRepository>,
+    pub projects: Arc<dyn ProjectRepository>,
+    pub webhooks: Arc<dyn WebhookRepository>,
+    pub notification_settings: Arc<dyn NotificationSettingsRepository>,
+    pub user_settings: Arc<dyn UserSettingsRepository>,
+    pub jobs: Arc<dyn JobRepository>,
+    pub leases: Arc<dyn LeaseRepository>,
+    pub schema: Arc<dyn SchemaRepository>,
+    pub feature_flags: Arc<dyn FeatureFlagRepository>,
+    pub maintenance: Arc<dyn MaintenanceRepository>,
+}
+
+/// Which todos an operation can see. Requests without a workspace only see
+/// personal todos, so workspaces stay isolated from each other and from
+/// unscoped callers; background workers use `All`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TodoScope {
+    #[default]
+    All,
+    Personal,
+    Workspace(String),
+}
+
+impl TodoScope {
+    pub fn contains(&self, todo: &Todo) -> bool {
+        match self {
+            TodoScope::All => true,
+            TodoScope::Personal => todo.workspace_id.is_none(),
+            TodoScope::Workspace(id) => todo.workspace_id.as_deref() == Some(id.as_str()),
+        }
+    }
+
+    /// The workspace new todos are created in.
+    pub fn workspace_id(&self) -> Option<&str> {
+        match self {
+            TodoScope::Workspace(id) => Some(id),
+            TodoScope::All | TodoScope::Personal => None,
+        }
+    }
+}
+
+/// Which todos in its scope a listing shows, following the rule
+/// [`crate::sharing::authorize`] applies to a single todo.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TodoVisibility {
+    /// Every todo, for background work and the caller's own data.
+    #[default]
+    All,
+    /// Workspace todos, todos without an owner, those owned by `user_id`
+    /// and those in `shared` with them. Anonymous callers have no ID.
+    Caller {
+        user_id: Option<String>,
+        shared: Vec<TodoId>,
+    },
+}
+
+impl TodoVisibility {
+    pub fn allows(&self, todo: &Todo) -> bool {
+        match self {
+            TodoVisibility::All => true,
+            TodoVisibility::Caller { user_id, shared } => {
+                todo.workspace_id.is_some()
+                    || todo.owner_id.is_none()
+                    || todo.owner_id == *user_id
+                    || todo.id.is_some_and(|id| shared.contains(&id))
+            }
+        }
+    }
+}
+
+/// Paging and visibility options for listing todos.
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    pub offset: usize,
+    pub limit: usize,
+    pub include_archived: bool,
+    pub scope: TodoScope,
+    pub visibility: TodoVisibility,
+    /// Only todos owned by this user.
+    pub owner_id: Option<String>,
+    /// Only todos created at or after this time.
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only todos created before this time.
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only todos in this project.
+    pub project_id: Option<String>,
+    /// Only todos with this status, as [`Todo::status`] reports it.
+    pub status: Option<TodoStatus>,
+    /// Only todos assigned to this user.
+    pub assignee_id: Option<String>,
+    /// Only todos completed, or open, as `completed` says.
+    pub completed: Option<bool>,
+    /// Only todos due at or after this time.
+    pub due_after: Option<DateTime<Utc>>,
+    /// Only todos due before this time.
+    pub due_before: Option<DateTime<Utc>>,
+    pub sort: TodoSort,
+}
+
+/// Order of listed todos.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoSort {
+    /// Oldest first.
+    #[default]
+    CreatedAt,
+    /// By `position`, todos without one last and oldest first.
+    Position,
+}
+
+impl TodoSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoSort::CreatedAt => "created_at",
+            TodoSort::Position => "position",
+        }
+    }
+
+    /// Compares two todos in this order, for backends that sort in memory.
+    pub fn compare(&self, a: &Todo, b: &Todo) -> Ordering {
+        let by_creation = || {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        };
+        match self {
+            TodoSort::CreatedAt => by_creation(),
+            TodoSort::Position => match (a.position, b.position) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then_with(by_creation),
+        }
+    }
+}
+
+impl FromStr for TodoSort {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "created_at" => Ok(TodoSort::CreatedAt),
+            "position" => Ok(TodoSort::Position),
+            other => Err(format!("unknown sort order: {}", other)),
+        }
+    }
+}
+
+impl ListOptions {
+    /// Evaluates everything but paging in memory, for backends that cannot
+    /// push it into the query.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        let created_at = todo.created_at;
+        (self.include_archived || !todo.archived.unwrap_or(false))
+            && self.scope.contains(todo)
+            && self.visibility.allows(todo)
+            && (self.owner_id.is_none() || todo.owner_id == self.owner_id)
+            && (self.project_id.is_none() || todo.project_id == self.project_id)
+            && self.status.is_none_or(|status| todo.status() == status)
+            && (self.assignee_id.is_none() || todo.assignee_id == self.assignee_id)
+            && self
+                .created_after
+                .is_none_or(|after| created_at.is_some_and(|created_at| created_at >= after))
+            && self
+                .created_before
+                .is_none_or(|before| created_at.is_some_and(|created_at| created_at < before))
+            && self
+                .completed
+                .is_none_or(|completed| todo.completed.unwrap_or(false) == completed)
+            && self
+                .due_after
+                .is_none_or(|after| todo.due_at.is_some_and(|due_at| due_at >= after))
+            && self
+                .due_before
+                .is_none_or(|before| todo.due_at.is_some_and(|due_at| due_at < before))
+    }
+}
+
+/// Criteria for bulk operations; `None` fields are not filtered on.
+#[derive(Debug, Clone, Default)]
+pub struct TodoFilter {
+    pub completed: Option<bool>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub scope: TodoScope,
+    /// Which todos in the scope may be picked, as for listings.
+    pub visibility: TodoVisibility,
+}
+
+impl TodoFilter {
+    pub fn is_empty(&self) -> bool {
+        self.completed.is_none() && self.created_before.is_none()
+    }
+
+    /// Evaluates the filter in memory, for backends that cannot push it into the query.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        if !self.scope.contains(todo) || !self.visibility.allows(todo) {
+            return false;
+        }
+        if let Some(completed) = self.completed {
+            if todo.completed.unwrap_or(false) != completed {
+                return false;
+            }
+        }
+        if let Some(created_before) = self.created_before {
+            if todo
+                .created_at
+                .is_none_or(|created_at| created_at >= created_before)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Columns to change in a partial update; `None` fields keep their stored
+/// value, so concurrent edits of other fields are not overwritten.
+#[derive(Debug, Clone, Default)]
+pub struct TodoPatch {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub completed: Option<bool>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+    pub series_id: Option<TodoId>,
+    pub remind_at: Option<DateTime<Utc>>,
+    /// Clears `reminder_sent_at` so the reminder fires again.
+    pub rearm_reminder: bool,
+    pub project_id: Option<String>,
+    pub position: Option<i64>,
+    pub status: Option<TodoStatus>,
+    /// `Some(None)` unassigns the todo.
+    pub assignee_id: Option<Option<String>>,
+    pub updated_at: DateTime<Utc>,
+    /// When the todo expires. Not changed; the written cells get a
+    /// matching TTL on Scylla.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TodoPatch {
+    /// Whether the patch changes nothing but `updated_at`.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.content.is_none()
+            && self.completed.is_none()
+            && self.due_at.is_none()
+            && self.recurrence.is_none()
+            && self.series_id.is_none()
+            && self.remind_at.is_none()
+            && !self.rearm_reminder
+            && self.project_id.is_none()
+            && self.position.is_none()
+            && self.status.is_none()
+            && self.assignee_id.is_none()
+    }
+
+    /// Applies the patch in memory, for backends that store whole todos and
+    /// for building the response.
+    pub fn apply(&self, todo: &mut Todo) {
+        if let Some(title) = &self.title {
+            todo.title = title.clone();
+        }
+        if let Some(content) = &self.content {
+            todo.content = content.clone();
+        }
+        if let Some(completed) = self.completed {
+            todo.completed = Some(completed);
+        }
+        if let Some(due_at) = self.due_at {
+            todo.due_at = Some(due_at);
+        }
+        if let Some(recurrence) = &self.recurrence {
+            todo.recurrence = Some(recurrence.clone());
+        }
+        if let Some(series_id) = self.series_id {
+            todo.series_id = Some(series_id);
+        }
+        if let Some(remind_at) = self.remind_at {
+            todo.remind_at = Some(remind_at);
+        }
+        if self.rearm_reminder {
+            todo.reminder_sent_at = None;
+        }
+        if let Some(project_id) = &self.project_id {
+            todo.project_id = Some(project_id.clone());
+        }
+        if let Some(position) = self.position {
+            todo.position = Some(position);
+        }
+        if let Some(status) = self.status {
+            todo.status = Some(status);
+        }
+        if let Some(assignee_id) = &self.assignee_id {
+            todo.assignee_id = assignee_id.clone();
+        }
+        todo.updated_at = Some(self.updated_at);
+    }
+}
+
+/// One change to one todo, stored together with the outbox entries that
+/// announce it by [`TodoRepository::commit`].
+#[derive(Debug, Clone)]
+pub enum TodoWrite {
+    Insert(Todo),
+    Update(Todo),
+    Patch(TodoId, TodoPatch),
+    Delete(TodoId),
+}
+
+impl TodoWrite {
+    pub fn todo_id(&self) -> Option<TodoId> {
+        match self {
+            TodoWrite::Insert(todo) | TodoWrite::Update(todo) => todo.id,
+            TodoWrite::Patch(id, _) | TodoWrite::Delete(id) => Some(*id),
+        }
+    }
+}
+
+/// Todos read one at a time, in the order `list` returns them.
+pub type TodoStream = BoxStream<'static, Result<Todo, RepositoryError>>;
+
+/// Rows fetched per round trip while streaming.
+pub const STREAM_PAGE_SIZE: usize = 500;
+
+/// Streams what `options` selects by calling `list` for a page of
+/// [`STREAM_PAGE_SIZE`] todos at a time, for backends without a cursor
+/// that can outlive the call.
+pub fn paged_stream<R: TodoRepository + 'static>(
+    repository: R,
+    options: ListOptions,
+) -> TodoStream {
+    stream::try_unfold(
+        (Arc::new(repository), Some(options)),
+        |(repository, options)| async move {
+            let Some(mut options) = options else {
+                return Ok(None);
+            };
+            let page_options = ListOptions {
+                limit: options.limit.min(STREAM_PAGE_SIZE),
+                ..options.clone()
+            };
+            let page = repository.list(&page_options).await?;
+
+            let next =
+                (page.len() == page_options.limit && page.len() < options.limit).then(|| {
+                    options.offset += page.len();
+                    options.limit -= page.len();
+                    options
+                });
+            Ok(Some((page, (repository, next))))
+        },
+    )
+    .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+    .try_flatten()
+    .boxed()
+}
+
+/// Whether the todo has a reminder that has not been sent yet and, when
+/// `due_before` is given, is due by then.
+pub fn is_pending_reminder(todo: &Todo, due_before: Option<DateTime<Utc>>) -> bool {
+    match (todo.remind_at, todo.reminder_sent_at) {
+        (Some(remind_at), None) => due_before.is_none_or(|due_before| remind_at <= due_before),
+        _ => false,
+    }
+}
+
+/// The todos of an undo entry as stored in a text column.
+fn undo_todos_json(todos: &[Todo]) -> Result<String, RepositoryError> {
+    serde_json::to_string(todos).map_err(|e| RepositoryError::Database(e.to_string()))
+}
+
+/// A version vector as stored in a text column.
+fn version_json(version: &VersionVector) -> Result<String, RepositoryError> {
+    serde_json::to_string(version).map_err(|e| RepositoryError::Database(e.to_string()))
+}
+
+/// Whether a completed recurring todo still needs its next occurrence created.
+pub fn is_pending_recurrence(todo: &Todo) -> bool {
+    todo.completed.unwrap_or(false)
+        && todo.recurrence.is_some()
+        && todo.next_occurrence_id.is_none()
+}
+
+/// Storage operations for todos. Handlers only talk to this trait so the
+/// backend can be swapped or decorated (timeouts, circuit breaking).
+/// Unit tests get a `MockTodoRepository` generated from it.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait TodoRepository: Send + Sync {
+    /// Returns one page of todos, skipping `offset` and returning at most
+    /// `limit`. Archived todos are left out unless requested.
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError>;
+
+    /// Like `list`, but hands todos out as they are read rather than
+    /// collecting them, so huge result sets need not fit in memory.
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError>;
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError>;
+
+    /// Whether a todo's title has the same [`crate::titles::key`] as `title`.
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError>;
+
+    /// Every todo whose title is exactly `title`.
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError>;
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError>;
+
+    /// Inserts the given todos in batches. Depending on the backend a
+    /// failure can leave part of them stored.
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError>;
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError>;
+
+    /// Writes only the columns `patch` changes, plus `updated_at`.
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError>;
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError>;
+
+    /// Makes `write` and stores `entries` in the outbox as one: either both
+    /// are stored or neither is. On Scylla that is a logged batch, which
+    /// leaves the owner counts out; they are adjusted after it as usual.
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError>;
+
+    /// Sets `completed` on every existing todo in `ids` in one batch and
+    /// returns the IDs that were actually found and updated.
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError>;
+
+    /// Returns the subset of `ids` that exist within `scope`, preserving the
+    /// requested order.
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError>;
+
+    /// Returns the IDs of all todos matching `filter`.
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError>;
+
+    /// Deletes the given todos in batches.
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError>;
+
+    /// Every occurrence of a recurring series, including the first one.
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError>;
+
+    /// Completed recurring todos whose next occurrence has not been created yet.
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError>;
+
+    /// Todos with an unsent reminder, optionally only those due by
+    /// `due_before`, ordered by reminder time.
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError>;
+
+    /// Deletes the todos that expired by `now` and returns their IDs. Scylla
+    /// drops expired rows itself through their TTL, so it returns none.
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError>;
+
+    /// Number of todos owned by `owner_id`, or created without an owner
+    /// when `None`. Archived todos are included.
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError>;
+
+    /// Todo counts of every owner; todos without an owner are under `None`.
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError>;
+
+    /// Recomputes maintained counts from the todos themselves and returns
+    /// how many owners' counts had drifted. Backends that count on the fly
+    /// have nothing to do.
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError>;
+
+    /// Rebuilds the indexes todos are looked up by and returns how many
+    /// entries were missing or stale, title keys included. SQL backends
+    /// rebuild their indexes in place; Scylla rewrites its lookup tables
+    /// from the todos.
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError>;
+}
+
+/// Storage for user accounts.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError>;
+
+    /// Emails are stored lowercased, so `email` must be too.
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError>;
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError>;
+
+    async fn count(&self) -> Result<usize, RepositoryError>;
+
+    /// Saves the user's email, display name, avatar URL and `updated_at`.
+    async fn update_profile(&self, user: &User) -> Result<(), RepositoryError>;
+
+    /// Deletes the user along with their second factor and the external
+    /// accounts linked to them.
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError>;
+
+    /// Replaces the user's password hash.
+    async fn update_password(
+        &self,
+        id: &str,
+        password_hash: &str,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+
+    /// Changes the user's role.
+    async fn update_role(
+        &self,
+        id: &str,
+        role: Role,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+
+    /// Links an account at an external provider to a user, replacing any
+    /// earlier link of the same account.
+    async fn link_identity(&self, identity: &UserIdentity) -> Result<(), RepositoryError>;
+
+    /// The user an external account is linked to.
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError>;
+
+    async fn find_totp(&self, user_id: &str) -> Result<Option<UserTotp>, RepositoryError>;
+
+    /// Replaces the user's second factor; `None` removes it.
+    async fn save_totp(
+        &self,
+        user_id: &str,
+        totp: Option<&UserTotp>,
+    ) -> Result<(), RepositoryError>;
+}
+
+/// Storage for workspaces and their memberships.
+#[async_trait]
+pub trait WorkspaceRepository: Send + Sync {
+    async fn insert(&self, workspace: &Workspace) -> Result<(), RepositoryError>;
+
+    async fn update(&self, workspace: &Workspace) -> Result<(), RepositoryError>;
+
+    /// Deletes the workspace and all of its memberships.
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError>;
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Workspace>, RepositoryError>;
+
+    /// Workspaces `user_id` is a member of, oldest first.
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Workspace>, RepositoryError>;
+
+    /// Adds a member, or changes the role of an existing one.
+    async fn upsert_member(&self, member: &WorkspaceMember) -> Result<(), RepositoryError>;
+
+    async fn remove_member(&self, workspace_id: &str, user_id: &str)
+        -> Result<(), RepositoryError>;
+
+    async fn find_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceMember>, RepositoryError>;
+
+    async fn list_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMember>, RepositoryError>;
+}
+
+/// Storage for todos shared with users other than their owner.
+#[async_trait]
+pub trait TodoAclRepository: Send + Sync {
+    /// Shares a todo, or changes the permission of an existing share.
+    async fn upsert(&self, share: &TodoShare) -> Result<(), RepositoryError>;
+
+    async fn find(
+        &self,
+        todo_id: &TodoId,
+        user_id: &str,
+    ) -> Result<Option<TodoShare>, RepositoryError>;
+
+    /// Todos shared with `user_id`, most recently shared first.
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<TodoShare>, RepositoryError>;
+
+    /// Removes every share of the given todos, once they are deleted.
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError>;
+}
+
+/// Storage for comments on todos.
+#[async_trait]
+pub trait CommentRepository: Send + Sync {
+    async fn insert(&self, comment: &Comment) -> Result<(), RepositoryError>;
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Comment>, RepositoryError>;
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError>;
+
+    /// A page of a todo's comments, oldest first.
+    async fn list(
+        &self,
+        todo_id: &TodoId,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Comment>, RepositoryError>;
+
+    /// Comment counts for the given todos; todos without comments are left out.
+    async fn count_by_todo(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<HashMap<TodoId, usize>, RepositoryError>;
+
+    /// Removes every comment on the given todos, once they are deleted.
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError>;
+}
+
+/// The activity log. Entries outlive the todos they are about, so a user's
+/// feed keeps what they did to todos since deleted.
+#[async_trait]
+pub trait ActivityRepository: Send + Sync {
+    async fn insert(&self, activity: &Activity) -> Result<(), RepositoryError>;
+
+    /// A page of a todo's activity, oldest first, limited to `kinds` unless
+    /// that is empty.
+    async fn list_for_todo(
+        &self,
+        todo_id: &TodoId,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError>;
+
+    /// A page of what `actor_id` did, oldest first, limited to `kinds`
+    /// unless that is empty.
+    async fn list_for_actor(
+        &self,
+        actor_id: &str,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError>;
+
+    /// Every entry that occurred before `before`, oldest first.
+    async fn list_before(&self, before: DateTime<Utc>) -> Result<Vec<Activity>, RepositoryError>;
+
+    async fn delete(&self, entries: &[Activity]) -> Result<(), RepositoryError>;
+}
+
+/// The undo log. Entries are only kept for as long as they can be undone.
+#[async_trait]
+pub trait UndoRepository: Send + Sync {
+    async fn insert(&self, entry: &UndoEntry) -> Result<(), RepositoryError>;
+
+    /// The most recent of `actor_id`'s entries made at or after `since`.
+    async fn latest(
+        &self,
+        actor_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<UndoEntry>, RepositoryError>;
+
+    async fn delete(&self, entry: &UndoEntry) -> Result<(), RepositoryError>;
+
+    /// Removes `actor_id`'s entries made before `before`, once they are too
+    /// old to undo.
+    async fn delete_before(
+        &self,
+        actor_id: &str,
+        before: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+}
+
+/// The change log read by sync clients: the latest change to each todo,
+/// deleted ones included.
+#[async_trait]
+pub trait ChangeRepository: Send + Sync {
+    /// Stores `change` in place of the todo's previous one.
+    async fn record(&self, change: &TodoChange) -> Result<(), RepositoryError>;
+
+    async fn find(&self, todo_id: &TodoId) -> Result<Option<TodoChange>, RepositoryError>;
+
+    /// Up to `limit` changes made after `token`, in token order.
+    async fn since(&self, token: i64, limit: usize) -> Result<Vec<TodoChange>, RepositoryError>;
+
+    /// Deletes the tombstones of todos deleted before `before` and returns
+    /// how many there were. Clients that last synced before then no longer
+    /// learn of those deletions.
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError>;
+
+    /// The tombstones of todos deleted before `before`.
+    async fn list_deleted(&self, before: DateTime<Utc>)
+        -> Result<Vec<TodoChange>, RepositoryError>;
+
+    /// Forgets the changes to `todo_ids`, as [`Self::purge_deleted`] does
+    /// for tombstones.
+    async fn delete(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError>;
+}
+
+/// Domain events waiting to be delivered to their consumers, kept until
+/// they have been. Entries are mostly written by [`TodoRepository::commit`],
+/// together with the change they announce.
+#[async_trait]
+pub trait OutboxRepository: Send + Sync {
+    async fn insert(&self, entry: &OutboxEntry) -> Result<(), RepositoryError>;
+
+    /// Up to `limit` entries for `consumer` not yet delivered, oldest first.
+    async fn pending(
+        &self,
+        consumer: &str,
+        limit: usize,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError>;
+
+    async fn mark_delivered(&self, id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError>;
+
+    /// Deletes entries delivered before `before`; returns how many.
+    async fn purge_delivered(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError>;
+}
+
+/// Refresh tokens, looked up by the hash of their value, and the token
+/// families that were revoked.
+#[async_trait]
+pub trait RefreshTokenRepository: Send + Sync {
+    async fn insert(&self, token: &RefreshToken) -> Result<(), RepositoryError>;
+
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError>;
+
+    /// Marks the token used at `at`, unless it already was: `false` when
+    /// another request spent it first.
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>)
+        -> Result<bool, RepositoryError>;
+
+    /// Revokes every token of the family. The revocation only needs to be
+    /// kept until `until`, when the family's last token expires anyway.
+    async fn revoke_family(
+        &self,
+        family_id: &str,
+        until: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+
+    async fn is_revoked(&self, family_id: &str) -> Result<bool, RepositoryError>;
+}
+
+/// Logins per user, for reviewing and revoking them. Stores with native
+/// TTL drop sessions once they expire.
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    /// Inserts the session, or replaces the one with the same ID.
+    async fn save(&self, session: &UserSession) -> Result<(), RepositoryError>;
+
+    async fn find(&self, user_id: &str, id: &str) -> Result<Option<UserSession>, RepositoryError>;
+
+    /// The user's sessions not expired by `now`, last seen first.
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, RepositoryError>;
+
+    async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError>;
+}
+
+/// Failed logins and the lockouts they lead to, by key: an account or a
+/// client address.
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    /// Records a failed login at `at` and returns how many `key` has had
+    /// since `since`, this one included. Older failures no longer count,
+    /// and backends may drop them.
+    async fn record_failure(
+        &self,
+        key: &str,
+        at: DateTime<Utc>,
+        since: DateTime<Utc>,
+    ) -> Result<usize, RepositoryError>;
+
+    /// Locks `key` until `until` and forgets its failures, so counting
+    /// starts afresh once the lockout ends.
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RepositoryError>;
+
+    /// When the latest lockout of `key` ends, which may be in the past.
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RepositoryError>;
+
+    /// Forgets the failures and any lockout of `key`.
+    async fn clear(&self, key: &str) -> Result<(), RepositoryError>;
+}
+
+/// Password reset tokens, looked up by the hash of their value. Backends
+/// with native TTL drop them once they expire.
+#[async_trait]
+pub trait PasswordResetRepository: Send + Sync {
+    async fn insert(&self, token: &PasswordResetToken) -> Result<(), RepositoryError>;
+
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError>;
+
+    /// Marks the token used at `at`, unless it already was: `false` when
+    /// another request spent it first.
+    async fn mark_used(&self, token_hash: &str, at: DateTime<Utc>)
+        -> Result<bool, RepositoryError>;
+
+    /// Marks every token of `user_id` that is still unused as used at `at`.
+    async fn mark_all_used(&self, user_id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError>;
+}
+
+/// Storage for attachment metadata. The contents are kept by a
+/// [`crate::blobs::BlobStore`].
+#[async_trait]
+pub trait AttachmentRepository: Send + Sync {
+    async fn insert(&self, attachment: &Attachment) -> Result<(), RepositoryError>;
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Attachment>, RepositoryError>;
+
+    /// A todo's attachments, oldest first.
+    async fn list(&self, todo_id: &TodoId) -> Result<Vec<Attachment>, RepositoryError>;
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError>;
+
+    /// Removes the attachments of the given todos, once they are deleted,
+    /// and returns them so their contents can be deleted too.
+    async fn delete_for_todos(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<Vec<Attachment>, RepositoryError>;
+}
+
+/// Storage for todo templates, partitioned by owner.
+#[async_trait]
+pub trait TemplateRepository: Send + Sync {
+    async fn insert(&self, template: &Template) -> Result<(), RepositoryError>;
+
+    async fn find_by_id(
+        &self,
+        owner_id: &str,
+        id: &str,
+    ) -> Result<Option<Template>, RepositoryError>;
+
+    /// A user's templates, oldest first.
+    async fn list_for_owner(&self, owner_id: &str) -> Result<Vec<Template>, RepositoryError>;
+
+    async fn delete(&self, owner_id: &str, id: &str) -> Result<(), RepositoryError>;
+}
+
+/// Storage for projects, partitioned by workspace; personal projects,
+/// with no workspace, share one partition.
+#[async_trait]
+pub trait ProjectRepository: Send + Sync {
+    async fn insert(&self, project: &Project) -> Result<(), RepositoryError>;
+
+    async fn update(&self, project: &Project) -> Result<(), RepositoryError>;
+
+    async fn find_by_id(
+        &self,
+        workspace_id: Option<&str>,
+        id: &str,
+    ) -> Result<Option<Project>, RepositoryError>;
+
+    /// The projects of a workspace, or the personal ones, oldest first.
+    async fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Project>, RepositoryError>;
+
+    async fn delete(&self, workspace_id: Option<&str>, id: &str) -> Result<(), RepositoryError>;
+}
+
+/// Storage for webhook registrations and their dead-lettered deliveries.
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn list(&self) -> Result<Vec<Webhook>, RepositoryError>;
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Webhook>, RepositoryError>;
+
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError>;
+
+    async fn update(&self, webhook: &Webhook) -> Result<(), RepositoryError>;
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError>;
+
+    async fn add_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError>;
+
+    /// The most recent dead letters, newest first.
+    async fn list_dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter>, RepositoryError>;
+}
+
+/// Per-address email opt-ins, keyed by email.
+#[async_trait]
+pub trait NotificationSettingsRepository: Send + Sync {
+    async fn list(&self) -> Result<Vec<NotificationSettings>, RepositoryError>;
+
+    async fn find(&self, email: &str) -> Result<Option<NotificationSettings>, RepositoryError>;
+
+    async fn upsert(&self, settings: &NotificationSettings) -> Result<(), RepositoryError>;
+
+    async fn delete(&self, email: &str) -> Result<(), RepositoryError>;
+}
+
+/// Per-user preferences, keyed by user ID.
+#[async_trait]
+pub trait UserSettingsRepository: Send + Sync {
+    /// `None` for users who have not saved any settings.
+    async fn find(&self, user_id: &str) -> Result<Option<UserSettings>, RepositoryError>;
+
+    async fn upsert(&self, settings: &UserSettings) -> Result<(), RepositoryError>;
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError>;
+}
+
+/// Persisted state for the background job queue.
+#[async_trait]
+pub trait JobRepository: Send + Sync {
+    async fn insert(&self, job: &JobRecord) -> Result<(), RepositoryError>;
+
+    async fn update(&self, job: &JobRecord) -> Result<(), RepositoryError>;
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<JobRecord>, RepositoryError>;
+
+    /// Jobs that are queued or were running when the process stopped.
+    async fn list_unfinished(&self) -> Result<Vec<JobRecord>, RepositoryError>;
+
+    /// Deletes finished jobs last updated before `before`; returns how many.
+    async fn purge_finished(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError>;
+}
+
+/// Time-limited leases on roles only one instance may hold at once, such
+/// as running the scheduled jobs; see [`crate::leader`].
+#[async_trait]
+pub trait LeaseRepository: Send + Sync {
+    /// Gives `holder` the lease on `name` until `until`, renewing it if
+    /// `holder` has it already, unless someone else's lease runs past
+    /// `now`. Returns whether `holder` has the lease.
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError>;
+
+    /// Ends `holder`'s lease on `name`, if it has it.
+    async fn release(&self, name: &str, holder: &str) -> Result<(), RepositoryError>;
+}
+
+/// The migrations applied to the database.
+#[async_trait]
+pub trait SchemaRepository: Send + Sync {
+    /// How the applied migrations compare to those this build has.
+    async fn status(&self) -> Result<SchemaStatus, RepositoryError>;
+}
+
+/// Feature flags switched on or off at runtime, overriding their configured
+/// defaults; see [`crate::features`].
+#[async_trait]
+pub trait FeatureFlagRepository: Send + Sync {
+    /// Whether each stored flag is enabled, by name.
+    async fn list(&self) -> Result<HashMap<String, bool>, RepositoryError>;
+
+    async fn set(&self, name: &str, enabled: bool) -> Result<(), RepositoryError>;
+
+    /// Forgets the stored value of `name`, leaving it at its default.
+    async fn clear(&self, name: &str) -> Result<(), RepositoryError>;
+}
+
+/// Whether the API is in maintenance mode; see [`crate::maintenance`].
+#[async_trait]
+pub trait MaintenanceRepository: Send + Sync {
+    /// The maintenance under way, if any.
+    async fn current(&self) -> Result<Option<Maintenance>, RepositoryError>;
+
+    /// Starts `maintenance`, replacing any under way.
+    async fn start(&self, maintenance: &Maintenance) -> Result<(), RepositoryError>;
+
+    async fn end(&self) -> Result<(), RepositoryError>;
+}
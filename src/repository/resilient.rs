@@ -0,0 +1,1041 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{
+    ActivityRepository, AttachmentRepository, ChangeRepository, CommentRepository,
+    FeatureFlagRepository, JobRepository, LeaseRepository, ListOptions, LoginAttemptRepository,
+    MaintenanceRepository, NotificationSettingsRepository, OutboxRepository,
+    PasswordResetRepository, ProjectRepository, RefreshTokenRepository, RepositoryError,
+    SchemaRepository, SessionRepository, TemplateRepository, TodoAclRepository, TodoFilter,
+    TodoPatch, TodoRepository, TodoScope, TodoStream, TodoWrite, UndoRepository, UserRepository,
+    UserSettingsRepository, WebhookRepository, WorkspaceRepository,
+};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::DatabaseConfig;
+use crate::metrics::QueryMetrics;
+use crate::migrations::SchemaStatus;
+use crate::model::{
+    Activity, ActivityKind, Attachment, Comment, DeadLetter, JobRecord, Maintenance,
+    NotificationSettings, OutboxEntry, PasswordResetToken, Project, RefreshToken, Role, Template,
+    Todo, TodoChange, TodoId, TodoShare, UndoEntry, User, UserIdentity, UserSession, UserSettings,
+    UserTotp, Webhook, Workspace, WorkspaceMember,
+};
+
+/// The per-call timeout and circuit breaker of one database, shared by every
+/// [`ResilientRepository`] in front of it: once the cluster fails calls of
+/// one kind, all of them fail fast. Every call's latency is recorded in
+/// `metrics` under the operation name, and calls slower than the configured
+/// threshold are logged.
+pub struct Resilience {
+    timeout: Duration,
+    slow_query_threshold: Option<Duration>,
+    breaker: CircuitBreaker,
+    metrics: Arc<QueryMetrics>,
+}
+
+impl Resilience {
+    pub fn new(config: &DatabaseConfig, metrics: Arc<QueryMetrics>) -> Self {
+        Resilience {
+            timeout: config.query_timeout,
+            slow_query_threshold: config.slow_query_threshold,
+            breaker: CircuitBreaker::new(&config.circuit_breaker),
+            metrics,
+        }
+    }
+
+    async fn guard<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = Result<T, RepositoryError>>,
+    ) -> Result<T, RepositoryError> {
+        let Some(permit) = self.breaker.try_acquire() else {
+            return Err(RepositoryError::Unavailable);
+        };
+
+        let started = Instant::now();
+        let result = {
+            let _in_flight = self.metrics.begin();
+            tokio::time::timeout(self.timeout, call).await
+        };
+        let elapsed = started.elapsed();
+        let ok = matches!(result, Ok(Ok(_)));
+        self.metrics.record(operation, elapsed, ok);
+        if self
+            .slow_query_threshold
+            .is_some_and(|threshold| elapsed >= threshold)
+        {
+            // Only the operation is named: the arguments can hold todo
+            // contents and credentials, so they stay out of the logs.
+            log::warn!(
+                "event=slow_query operation={} elapsed_ms={} ok={} params=[redacted]",
+                operation,
+                elapsed.as_millis(),
+                ok
+            );
+        }
+
+        match result {
+            Ok(Ok(value)) => {
+                self.breaker.on_success(permit);
+                Ok(value)
+            }
+            // The database answered; a bad row says nothing of its health.
+            Ok(Err(e @ RepositoryError::Decode(_))) => {
+                self.breaker.on_success(permit);
+                Err(e)
+            }
+            Ok(Err(e)) => {
+                self.breaker.on_failure(permit);
+                Err(e)
+            }
+            Err(_) => {
+                self.breaker.on_failure(permit);
+                Err(RepositoryError::Timeout)
+            }
+        }
+    }
+}
+
+/// Decorates another repository with its database's [`Resilience`], so a
+/// slow or failing cluster turns into fast 503s instead of hung requests.
+pub struct ResilientRepository<R> {
+    inner: R,
+    resilience: Arc<Resilience>,
+}
+
+impl<R> ResilientRepository<R> {
+    pub fn new(inner: R, resilience: Arc<Resilience>) -> Self {
+        ResilientRepository { inner, resilience }
+    }
+
+    async fn guard<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = Result<T, RepositoryError>>,
+    ) -> Result<T, RepositoryError> {
+        self.resilience.guard(operation, call).await
+    }
+}
+
+#[async_trait]
+impl<R: TodoRepository> TodoRepository for ResilientRepository<R> {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        self.guard("list", self.inner.list(options)).await
+    }
+
+    /// Only opening the stream is guarded; reading it is up to the caller.
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        self.guard("stream", self.inner.stream(options)).await
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        self.guard("find_by_id", self.inner.find_by_id(id)).await
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        self.guard("exists_with_title", self.inner.exists_with_title(title))
+            .await
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        self.guard("find_by_title", self.inner.find_by_title(title))
+            .await
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.guard("insert", self.inner.insert(todo)).await
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        self.guard("insert_many", self.inner.insert_many(todos))
+            .await
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.guard("update", self.inner.update(todo)).await
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        self.guard("update_fields", self.inner.update_fields(id, patch))
+            .await
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        self.guard("delete", self.inner.delete(id)).await
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        self.guard("commit", self.inner.commit(write, entries))
+            .await
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.guard(
+            "set_completed",
+            self.inner.set_completed(ids, completed, updated_at),
+        )
+        .await
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.guard("existing_ids", self.inner.existing_ids(ids, scope))
+            .await
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        self.guard("find_ids", self.inner.find_ids(filter)).await
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.guard("delete_many", self.inner.delete_many(ids)).await
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        self.guard("list_series", self.inner.list_series(series_id))
+            .await
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        self.guard("pending_recurrences", self.inner.pending_recurrences())
+            .await
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        self.guard(
+            "pending_reminders",
+            self.inner.pending_reminders(due_before),
+        )
+        .await
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        self.guard("delete_expired", self.inner.delete_expired(now))
+            .await
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        self.guard("count_for_owner", self.inner.count_for_owner(owner_id))
+            .await
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        self.guard("owner_counts", self.inner.owner_counts()).await
+    }
+
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        self.guard("reconcile_counts", self.inner.reconcile_counts())
+            .await
+    }
+
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        self.guard("rebuild_indexes", self.inner.rebuild_indexes())
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: UserRepository> UserRepository for ResilientRepository<R> {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError> {
+        self.guard("users.insert", self.inner.insert(user)).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        self.guard("users.find_by_email", self.inner.find_by_email(email))
+            .await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError> {
+        self.guard("users.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn count(&self) -> Result<usize, RepositoryError> {
+        self.guard("users.count", self.inner.count()).await
+    }
+
+    async fn update_profile(&self, user: &User) -> Result<(), RepositoryError> {
+        self.guard("users.update_profile", self.inner.update_profile(user))
+            .await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        self.guard("users.delete", self.inner.delete(id)).await
+    }
+
+    async fn update_password(
+        &self,
+        id: &str,
+        password_hash: &str,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        self.guard(
+            "users.update_password",
+            self.inner.update_password(id, password_hash, updated_at),
+        )
+        .await
+    }
+
+    async fn update_role(
+        &self,
+        id: &str,
+        role: Role,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        self.guard(
+            "users.update_role",
+            self.inner.update_role(id, role, updated_at),
+        )
+        .await
+    }
+
+    async fn link_identity(&self, identity: &UserIdentity) -> Result<(), RepositoryError> {
+        self.guard("users.link_identity", self.inner.link_identity(identity))
+            .await
+    }
+
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        self.guard(
+            "users.find_by_identity",
+            self.inner.find_by_identity(provider, subject),
+        )
+        .await
+    }
+
+    async fn find_totp(&self, user_id: &str) -> Result<Option<UserTotp>, RepositoryError> {
+        self.guard("users.find_totp", self.inner.find_totp(user_id))
+            .await
+    }
+
+    async fn save_totp(
+        &self,
+        user_id: &str,
+        totp: Option<&UserTotp>,
+    ) -> Result<(), RepositoryError> {
+        self.guard("users.save_totp", self.inner.save_totp(user_id, totp))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: WorkspaceRepository> WorkspaceRepository for ResilientRepository<R> {
+    async fn insert(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        self.guard("workspaces.insert", self.inner.insert(workspace))
+            .await
+    }
+
+    async fn update(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        self.guard("workspaces.update", self.inner.update(workspace))
+            .await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        self.guard("workspaces.delete", self.inner.delete(id)).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Workspace>, RepositoryError> {
+        self.guard("workspaces.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Workspace>, RepositoryError> {
+        self.guard(
+            "workspaces.list_for_user",
+            self.inner.list_for_user(user_id),
+        )
+        .await
+    }
+
+    async fn upsert_member(&self, member: &WorkspaceMember) -> Result<(), RepositoryError> {
+        self.guard("workspaces.upsert_member", self.inner.upsert_member(member))
+            .await
+    }
+
+    async fn remove_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<(), RepositoryError> {
+        self.guard(
+            "workspaces.remove_member",
+            self.inner.remove_member(workspace_id, user_id),
+        )
+        .await
+    }
+
+    async fn find_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceMember>, RepositoryError> {
+        self.guard(
+            "workspaces.find_member",
+            self.inner.find_member(workspace_id, user_id),
+        )
+        .await
+    }
+
+    async fn list_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMember>, RepositoryError> {
+        self.guard(
+            "workspaces.list_members",
+            self.inner.list_members(workspace_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: TodoAclRepository> TodoAclRepository for ResilientRepository<R> {
+    async fn upsert(&self, share: &TodoShare) -> Result<(), RepositoryError> {
+        self.guard("acl.upsert", self.inner.upsert(share)).await
+    }
+
+    async fn find(
+        &self,
+        todo_id: &TodoId,
+        user_id: &str,
+    ) -> Result<Option<TodoShare>, RepositoryError> {
+        self.guard("acl.find", self.inner.find(todo_id, user_id))
+            .await
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<TodoShare>, RepositoryError> {
+        self.guard("acl.list_for_user", self.inner.list_for_user(user_id))
+            .await
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.guard(
+            "acl.delete_for_todos",
+            self.inner.delete_for_todos(todo_ids),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: CommentRepository> CommentRepository for ResilientRepository<R> {
+    async fn insert(&self, comment: &Comment) -> Result<(), RepositoryError> {
+        self.guard("comments.insert", self.inner.insert(comment))
+            .await
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Comment>, RepositoryError> {
+        self.guard("comments.find_by_id", self.inner.find_by_id(todo_id, id))
+            .await
+    }
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        self.guard("comments.delete", self.inner.delete(todo_id, id))
+            .await
+    }
+
+    async fn list(
+        &self,
+        todo_id: &TodoId,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Comment>, RepositoryError> {
+        self.guard("comments.list", self.inner.list(todo_id, offset, limit))
+            .await
+    }
+
+    async fn count_by_todo(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<HashMap<TodoId, usize>, RepositoryError> {
+        self.guard("comments.count_by_todo", self.inner.count_by_todo(todo_ids))
+            .await
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.guard(
+            "comments.delete_for_todos",
+            self.inner.delete_for_todos(todo_ids),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: ActivityRepository> ActivityRepository for ResilientRepository<R> {
+    async fn insert(&self, activity: &Activity) -> Result<(), RepositoryError> {
+        self.guard("activity.insert", self.inner.insert(activity))
+            .await
+    }
+
+    async fn list_for_todo(
+        &self,
+        todo_id: &TodoId,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        self.guard(
+            "activity.list_for_todo",
+            self.inner.list_for_todo(todo_id, kinds, offset, limit),
+        )
+        .await
+    }
+
+    async fn list_for_actor(
+        &self,
+        actor_id: &str,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        self.guard(
+            "activity.list_for_actor",
+            self.inner.list_for_actor(actor_id, kinds, offset, limit),
+        )
+        .await
+    }
+
+    async fn list_before(&self, before: DateTime<Utc>) -> Result<Vec<Activity>, RepositoryError> {
+        self.guard("activity.list_before", self.inner.list_before(before))
+            .await
+    }
+
+    async fn delete(&self, entries: &[Activity]) -> Result<(), RepositoryError> {
+        self.guard("activity.delete", self.inner.delete(entries))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: UndoRepository> UndoRepository for ResilientRepository<R> {
+    async fn insert(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        self.guard("undo.insert", self.inner.insert(entry)).await
+    }
+
+    async fn latest(
+        &self,
+        actor_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<UndoEntry>, RepositoryError> {
+        self.guard("undo.latest", self.inner.latest(actor_id, since))
+            .await
+    }
+
+    async fn delete(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        self.guard("undo.delete", self.inner.delete(entry)).await
+    }
+
+    async fn delete_before(
+        &self,
+        actor_id: &str,
+        before: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        self.guard(
+            "undo.delete_before",
+            self.inner.delete_before(actor_id, before),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: ChangeRepository> ChangeRepository for ResilientRepository<R> {
+    async fn record(&self, change: &TodoChange) -> Result<(), RepositoryError> {
+        self.guard("changes.record", self.inner.record(change))
+            .await
+    }
+
+    async fn find(&self, todo_id: &TodoId) -> Result<Option<TodoChange>, RepositoryError> {
+        self.guard("changes.find", self.inner.find(todo_id)).await
+    }
+
+    async fn since(&self, token: i64, limit: usize) -> Result<Vec<TodoChange>, RepositoryError> {
+        self.guard("changes.since", self.inner.since(token, limit))
+            .await
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        self.guard("changes.purge_deleted", self.inner.purge_deleted(before))
+            .await
+    }
+
+    async fn list_deleted(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<TodoChange>, RepositoryError> {
+        self.guard("changes.list_deleted", self.inner.list_deleted(before))
+            .await
+    }
+
+    async fn delete(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        self.guard("changes.delete", self.inner.delete(todo_ids))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: OutboxRepository> OutboxRepository for ResilientRepository<R> {
+    async fn insert(&self, entry: &OutboxEntry) -> Result<(), RepositoryError> {
+        self.guard("outbox.insert", self.inner.insert(entry)).await
+    }
+
+    async fn pending(
+        &self,
+        consumer: &str,
+        limit: usize,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        self.guard("outbox.pending", self.inner.pending(consumer, limit))
+            .await
+    }
+
+    async fn mark_delivered(&self, id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.guard("outbox.mark_delivered", self.inner.mark_delivered(id, at))
+            .await
+    }
+
+    async fn purge_delivered(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        self.guard("outbox.purge_delivered", self.inner.purge_delivered(before))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: RefreshTokenRepository> RefreshTokenRepository for ResilientRepository<R> {
+    async fn insert(&self, token: &RefreshToken) -> Result<(), RepositoryError> {
+        self.guard("refresh_tokens.insert", self.inner.insert(token))
+            .await
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        self.guard("refresh_tokens.find", self.inner.find(token_hash))
+            .await
+    }
+
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        self.guard(
+            "refresh_tokens.mark_used",
+            self.inner.mark_used(token_hash, at),
+        )
+        .await
+    }
+
+    async fn revoke_family(
+        &self,
+        family_id: &str,
+        until: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        self.guard(
+            "refresh_tokens.revoke_family",
+            self.inner.revoke_family(family_id, until),
+        )
+        .await
+    }
+
+    async fn is_revoked(&self, family_id: &str) -> Result<bool, RepositoryError> {
+        self.guard(
+            "refresh_tokens.is_revoked",
+            self.inner.is_revoked(family_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: SessionRepository> SessionRepository for ResilientRepository<R> {
+    async fn save(&self, session: &UserSession) -> Result<(), RepositoryError> {
+        self.guard("sessions.save", self.inner.save(session)).await
+    }
+
+    async fn find(&self, user_id: &str, id: &str) -> Result<Option<UserSession>, RepositoryError> {
+        self.guard("sessions.find", self.inner.find(user_id, id))
+            .await
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, RepositoryError> {
+        self.guard(
+            "sessions.find_by_user",
+            self.inner.find_by_user(user_id, now),
+        )
+        .await
+    }
+
+    async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+        self.guard("sessions.delete", self.inner.delete(user_id, id))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: LoginAttemptRepository> LoginAttemptRepository for ResilientRepository<R> {
+    async fn record_failure(
+        &self,
+        key: &str,
+        at: DateTime<Utc>,
+        since: DateTime<Utc>,
+    ) -> Result<usize, RepositoryError> {
+        self.guard(
+            "login_attempts.record_failure",
+            self.inner.record_failure(key, at, since),
+        )
+        .await
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.guard("login_attempts.lock", self.inner.lock(key, until))
+            .await
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        self.guard("login_attempts.locked_until", self.inner.locked_until(key))
+            .await
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), RepositoryError> {
+        self.guard("login_attempts.clear", self.inner.clear(key))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: PasswordResetRepository> PasswordResetRepository for ResilientRepository<R> {
+    async fn insert(&self, token: &PasswordResetToken) -> Result<(), RepositoryError> {
+        self.guard("password_resets.insert", self.inner.insert(token))
+            .await
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        self.guard("password_resets.find", self.inner.find(token_hash))
+            .await
+    }
+
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        self.guard(
+            "password_resets.mark_used",
+            self.inner.mark_used(token_hash, at),
+        )
+        .await
+    }
+
+    async fn mark_all_used(&self, user_id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        self.guard(
+            "password_resets.mark_all_used",
+            self.inner.mark_all_used(user_id, at),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: AttachmentRepository> AttachmentRepository for ResilientRepository<R> {
+    async fn insert(&self, attachment: &Attachment) -> Result<(), RepositoryError> {
+        self.guard("attachments.insert", self.inner.insert(attachment))
+            .await
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Attachment>, RepositoryError> {
+        self.guard("attachments.find_by_id", self.inner.find_by_id(todo_id, id))
+            .await
+    }
+
+    async fn list(&self, todo_id: &TodoId) -> Result<Vec<Attachment>, RepositoryError> {
+        self.guard("attachments.list", self.inner.list(todo_id))
+            .await
+    }
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        self.guard("attachments.delete", self.inner.delete(todo_id, id))
+            .await
+    }
+
+    async fn delete_for_todos(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<Vec<Attachment>, RepositoryError> {
+        self.guard(
+            "attachments.delete_for_todos",
+            self.inner.delete_for_todos(todo_ids),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: TemplateRepository> TemplateRepository for ResilientRepository<R> {
+    async fn insert(&self, template: &Template) -> Result<(), RepositoryError> {
+        self.guard("templates.insert", self.inner.insert(template))
+            .await
+    }
+
+    async fn find_by_id(
+        &self,
+        owner_id: &str,
+        id: &str,
+    ) -> Result<Option<Template>, RepositoryError> {
+        self.guard("templates.find_by_id", self.inner.find_by_id(owner_id, id))
+            .await
+    }
+
+    async fn list_for_owner(&self, owner_id: &str) -> Result<Vec<Template>, RepositoryError> {
+        self.guard(
+            "templates.list_for_owner",
+            self.inner.list_for_owner(owner_id),
+        )
+        .await
+    }
+
+    async fn delete(&self, owner_id: &str, id: &str) -> Result<(), RepositoryError> {
+        self.guard("templates.delete", self.inner.delete(owner_id, id))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: ProjectRepository> ProjectRepository for ResilientRepository<R> {
+    async fn insert(&self, project: &Project) -> Result<(), RepositoryError> {
+        self.guard("projects.insert", self.inner.insert(project))
+            .await
+    }
+
+    async fn update(&self, project: &Project) -> Result<(), RepositoryError> {
+        self.guard("projects.update", self.inner.update(project))
+            .await
+    }
+
+    async fn find_by_id(
+        &self,
+        workspace_id: Option<&str>,
+        id: &str,
+    ) -> Result<Option<Project>, RepositoryError> {
+        self.guard(
+            "projects.find_by_id",
+            self.inner.find_by_id(workspace_id, id),
+        )
+        .await
+    }
+
+    async fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Project>, RepositoryError> {
+        self.guard("projects.list", self.inner.list(workspace_id))
+            .await
+    }
+
+    async fn delete(&self, workspace_id: Option<&str>, id: &str) -> Result<(), RepositoryError> {
+        self.guard("projects.delete", self.inner.delete(workspace_id, id))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: WebhookRepository> WebhookRepository for ResilientRepository<R> {
+    async fn list(&self) -> Result<Vec<Webhook>, RepositoryError> {
+        self.guard("webhooks.list", self.inner.list()).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Webhook>, RepositoryError> {
+        self.guard("webhooks.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        self.guard("webhooks.insert", self.inner.insert(webhook))
+            .await
+    }
+
+    async fn update(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        self.guard("webhooks.update", self.inner.update(webhook))
+            .await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        self.guard("webhooks.delete", self.inner.delete(id)).await
+    }
+
+    async fn add_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError> {
+        self.guard(
+            "webhooks.add_dead_letter",
+            self.inner.add_dead_letter(dead_letter),
+        )
+        .await
+    }
+
+    async fn list_dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter>, RepositoryError> {
+        self.guard(
+            "webhooks.list_dead_letters",
+            self.inner.list_dead_letters(limit),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl<R: NotificationSettingsRepository> NotificationSettingsRepository for ResilientRepository<R> {
+    async fn list(&self) -> Result<Vec<NotificationSettings>, RepositoryError> {
+        self.guard("notification_settings.list", self.inner.list())
+            .await
+    }
+
+    async fn find(&self, email: &str) -> Result<Option<NotificationSettings>, RepositoryError> {
+        self.guard("notification_settings.find", self.inner.find(email))
+            .await
+    }
+
+    async fn upsert(&self, settings: &NotificationSettings) -> Result<(), RepositoryError> {
+        self.guard("notification_settings.upsert", self.inner.upsert(settings))
+            .await
+    }
+
+    async fn delete(&self, email: &str) -> Result<(), RepositoryError> {
+        self.guard("notification_settings.delete", self.inner.delete(email))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: UserSettingsRepository> UserSettingsRepository for ResilientRepository<R> {
+    async fn find(&self, user_id: &str) -> Result<Option<UserSettings>, RepositoryError> {
+        self.guard("user_settings.find", self.inner.find(user_id))
+            .await
+    }
+
+    async fn upsert(&self, settings: &UserSettings) -> Result<(), RepositoryError> {
+        self.guard("user_settings.upsert", self.inner.upsert(settings))
+            .await
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError> {
+        self.guard("user_settings.delete", self.inner.delete(user_id))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: JobRepository> JobRepository for ResilientRepository<R> {
+    async fn insert(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        self.guard("jobs.insert", self.inner.insert(job)).await
+    }
+
+    async fn update(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        self.guard("jobs.update", self.inner.update(job)).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<JobRecord>, RepositoryError> {
+        self.guard("jobs.find_by_id", self.inner.find_by_id(id))
+            .await
+    }
+
+    async fn list_unfinished(&self) -> Result<Vec<JobRecord>, RepositoryError> {
+        self.guard("jobs.list_unfinished", self.inner.list_unfinished())
+            .await
+    }
+
+    async fn purge_finished(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        self.guard("jobs.purge_finished", self.inner.purge_finished(before))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: LeaseRepository> LeaseRepository for ResilientRepository<R> {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        self.guard(
+            "leases.acquire",
+            self.inner.acquire(name, holder, now, until),
+        )
+        .await
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), RepositoryError> {
+        self.guard("leases.release", self.inner.release(name, holder))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: SchemaRepository> SchemaRepository for ResilientRepository<R> {
+    async fn status(&self) -> Result<SchemaStatus, RepositoryError> {
+        self.guard("schema.status", self.inner.status()).await
+    }
+}
+
+#[async_trait]
+impl<R: FeatureFlagRepository> FeatureFlagRepository for ResilientRepository<R> {
+    async fn list(&self) -> Result<HashMap<String, bool>, RepositoryError> {
+        self.guard("feature_flags.list", self.inner.list()).await
+    }
+
+    async fn set(&self, name: &str, enabled: bool) -> Result<(), RepositoryError> {
+        self.guard("feature_flags.set", self.inner.set(name, enabled))
+            .await
+    }
+
+    async fn clear(&self, name: &str) -> Result<(), RepositoryError> {
+        self.guard("feature_flags.clear", self.inner.clear(name))
+            .await
+    }
+}
+
+#[async_trait]
+impl<R: MaintenanceRepository> MaintenanceRepository for ResilientRepository<R> {
+    async fn current(&self) -> Result<Option<Maintenance>, RepositoryError> {
+        self.guard("maintenance.current", self.inner.current())
+            .await
+    }
+
+    async fn start(&self, maintenance: &Maintenance) -> Result<(), RepositoryError> {
+        self.guard("maintenance.start", self.inner.start(maintenance))
+            .await
+    }
+
+    async fn end(&self) -> Result<(), RepositoryError> {
+        self.guard("maintenance.end", self.inner.end()).await
+    }
+}
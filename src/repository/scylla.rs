@@ -0,0 +1,4351 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::prelude::*;
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+use scylla::batch::{Batch, BatchType};
+use scylla::cql_to_rust::FromRow;
+use scylla::frame::response::result::{CqlValue, Row};
+use scylla::frame::value::{Counter, CqlTimestamp, MaybeUnset};
+use scylla::query::Query;
+use scylla::serialize::row::SerializeRow;
+use scylla::{IntoTypedRows, Session};
+
+use super::{
+    is_pending_recurrence, is_pending_reminder, undo_todos_json, version_json, ActivityRepository,
+    AttachmentRepository, ChangeRepository, CommentRepository, FeatureFlagRepository,
+    JobRepository, LeaseRepository, ListOptions, LoginAttemptRepository, MaintenanceRepository,
+    NotificationSettingsRepository, OutboxRepository, PasswordResetRepository, ProjectRepository,
+    RefreshTokenRepository, RepositoryError, SchemaRepository, SessionRepository,
+    TemplateRepository, TodoAclRepository, TodoFilter, TodoPatch, TodoRepository, TodoScope,
+    TodoSort, TodoStream, TodoWrite, UndoRepository, UserRepository, UserSettingsRepository,
+    WebhookRepository, WorkspaceRepository, STREAM_PAGE_SIZE,
+};
+use crate::config::ConsistencyConfig;
+use crate::decoding::RowDecoding;
+use crate::migrations::{self, SchemaStatus};
+use crate::model::{
+    Activity, ActivityKind, Attachment, Comment, DeadLetter, JobRecord, JobStatus, Maintenance,
+    NotificationSettings, OutboxEntry, PasswordResetToken, Project, RefreshToken, Role, Template,
+    Todo, TodoChange, TodoId, TodoShare, UndoEntry, User, UserIdentity, UserSession, UserSettings,
+    UserTotp, Webhook, Workspace, WorkspaceMember,
+};
+use crate::scheduling::Recurrence;
+use crate::titles;
+use uuid::Uuid;
+
+/// A `SELECT_TODOS` row, and the values of `INSERT_TODO` followed by the
+/// TTL. A struct since the columns outnumber the driver's tuple impls.
+#[derive(scylla::FromRow, scylla::SerializeRow)]
+#[scylla(flavor = "enforce_order", skip_name_checks)]
+struct TodoRow {
+    id: Uuid,
+    title: String,
+    content: String,
+    completed: bool,
+    created_at: CqlTimestamp,
+    updated_at: CqlTimestamp,
+    archived: Option<bool>,
+    due_at: Option<CqlTimestamp>,
+    recurrence: Option<String>,
+    series_id: Option<Uuid>,
+    next_occurrence_id: Option<Uuid>,
+    remind_at: Option<CqlTimestamp>,
+    reminder_sent_at: Option<CqlTimestamp>,
+    owner_id: Option<String>,
+    workspace_id: Option<String>,
+    project_id: Option<String>,
+    position: Option<i64>,
+    status: Option<String>,
+    assignee_id: Option<String>,
+    title_key: Option<String>,
+    ttl: Option<i32>,
+}
+
+/// The values of `UPDATE_TODO`, in its order; a struct for the same
+/// reason as [`TodoRow`].
+#[derive(scylla::SerializeRow)]
+#[scylla(flavor = "enforce_order", skip_name_checks)]
+struct TodoUpdate {
+    ttl: i32,
+    title: String,
+    content: String,
+    completed: bool,
+    updated_at: CqlTimestamp,
+    archived: bool,
+    due_at: Option<CqlTimestamp>,
+    recurrence: Option<String>,
+    series_id: Option<Uuid>,
+    next_occurrence_id: Option<Uuid>,
+    remind_at: Option<CqlTimestamp>,
+    reminder_sent_at: Option<CqlTimestamp>,
+    project_id: Option<String>,
+    position: Option<i64>,
+    status: Option<String>,
+    assignee_id: Option<String>,
+    title_key: String,
+    id: Uuid,
+}
+
+/// Expiring todos are written with a TTL and left for Scylla to drop;
+/// `expires_at` is not stored but derived from the remaining TTL on read.
+const INSERT_TODO: &str = "INSERT INTO todo_db.todos_v2 (id, title, content, completed, created_at, updated_at, archived, due_at, recurrence, series_id, next_occurrence_id, remind_at, reminder_sent_at, owner_id, workspace_id, project_id, position, status, assignee_id, title_key) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) USING TTL ?";
+
+const UPDATE_TODO: &str = "UPDATE todo_db.todos_v2 USING TTL ? SET title = ?, content = ?, completed = ?, updated_at = ?, archived = ?, due_at = ?, recurrence = ?, series_id = ?, next_occurrence_id = ?, remind_at = ?, reminder_sent_at = ?, project_id = ?, position = ?, status = ?, assignee_id = ?, title_key = ? WHERE id = ?";
+
+/// Unset values leave their column untouched, so one statement covers
+/// every combination of changed fields without tombstones.
+const UPDATE_TODO_FIELDS: &str = "UPDATE todo_db.todos_v2 USING TTL ? SET title = ?, title_key = ?, content = ?, completed = ?, updated_at = ?, due_at = ?, recurrence = ?, series_id = ?, remind_at = ?, reminder_sent_at = ?, project_id = ?, position = ?, status = ?, assignee_id = ? WHERE id = ?";
+
+const DELETE_TODO: &str = "DELETE FROM todo_db.todos_v2 WHERE id = ?";
+
+const INSERT_TODO_BY_WORKSPACE: &str =
+    "INSERT INTO todo_db.todos_by_workspace_v2 (workspace_id, todo_id) VALUES (?, ?) USING TTL ?";
+
+const DELETE_TODO_BY_WORKSPACE: &str =
+    "DELETE FROM todo_db.todos_by_workspace_v2 WHERE workspace_id = ? AND todo_id = ?";
+
+const INSERT_TODO_BY_OWNER: &str = "INSERT INTO todo_db.todos_by_owner (owner_id, created_at, todo_id) VALUES (?, ?, ?) USING TTL ?";
+
+const DELETE_TODO_BY_OWNER: &str =
+    "DELETE FROM todo_db.todos_by_owner WHERE owner_id = ? AND created_at = ? AND todo_id = ?";
+
+const UPDATE_TODO_TITLE_KEY: &str =
+    "UPDATE todo_db.todos_v2 USING TTL ? SET title_key = ? WHERE id = ?";
+
+const SELECT_TODOS: &str = "SELECT id, title, content, completed, created_at, updated_at, archived, due_at, recurrence, series_id, next_occurrence_id, remind_at, reminder_sent_at, owner_id, workspace_id, project_id, position, status, assignee_id, title_key, TTL(title) FROM todo_db.todos_v2";
+
+/// Key of the `todo_counts` row for todos without an owner; Scylla does not
+/// allow an empty partition key and user IDs are UUIDs, so it cannot clash.
+const UNOWNED_COUNT_KEY: &str = "unowned";
+
+const UPDATE_TODO_COUNT: &str =
+    "UPDATE todo_db.todo_counts SET todos = todos + ? WHERE owner_id = ?";
+
+type WorkspaceRowTuple = (String, String, CqlTimestamp, CqlTimestamp);
+
+const SELECT_WORKSPACES: &str = "SELECT id, name, created_at, updated_at FROM todo_db.workspaces";
+
+type WorkspaceMemberRowTuple = (String, String, String, CqlTimestamp);
+
+type AttachmentRowTuple = (
+    String,
+    Uuid,
+    String,
+    String,
+    i64,
+    Option<String>,
+    CqlTimestamp,
+);
+
+const SELECT_ATTACHMENTS: &str = "SELECT id, todo_id, file_name, content_type, size, uploaded_by, created_at FROM todo_db.attachments_v2";
+
+type TemplateRowTuple = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    CqlTimestamp,
+    CqlTimestamp,
+);
+
+const SELECT_TEMPLATES: &str =
+    "SELECT id, owner_id, name, title, content, created_at, updated_at FROM todo_db.templates";
+
+type ProjectRowTuple = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<bool>,
+    CqlTimestamp,
+    CqlTimestamp,
+);
+
+const SELECT_PROJECTS: &str = "SELECT id, name, workspace_id, owner_id, archived, created_at, updated_at FROM todo_db.projects";
+
+/// Partition key of personal projects, which have no workspace; workspace
+/// IDs are UUIDs, so it cannot clash.
+const PERSONAL_PROJECTS_KEY: &str = "personal";
+
+type CommentRowTuple = (String, Uuid, String, String, CqlTimestamp, CqlTimestamp);
+
+const SELECT_COMMENTS: &str =
+    "SELECT id, todo_id, author_id, body, created_at, updated_at FROM todo_db.comments_v2";
+
+type ActivityRowTuple = (
+    String,
+    Uuid,
+    Option<String>,
+    String,
+    Option<String>,
+    CqlTimestamp,
+);
+
+const SELECT_ACTIVITY_BY_TODO: &str =
+    "SELECT id, todo_id, actor_id, kind, detail, occurred_at FROM todo_db.activity_by_todo";
+
+const SELECT_ACTIVITY_BY_ACTOR: &str =
+    "SELECT id, todo_id, actor_id, kind, detail, occurred_at FROM todo_db.activity_by_actor";
+
+type UndoRowTuple = (String, String, String, String, CqlTimestamp);
+
+type ChangeRowTuple = (
+    Uuid,
+    i64,
+    Option<String>,
+    Option<String>,
+    bool,
+    String,
+    CqlTimestamp,
+);
+
+const SELECT_TODO_CHANGES: &str = "SELECT todo_id, token, workspace_id, owner_id, deleted, version, changed_at FROM todo_db.todo_changes";
+
+type RefreshTokenRowTuple = (
+    String,
+    String,
+    String,
+    CqlTimestamp,
+    CqlTimestamp,
+    Option<CqlTimestamp>,
+    Option<String>,
+);
+
+type PasswordResetRowTuple = (
+    String,
+    String,
+    CqlTimestamp,
+    CqlTimestamp,
+    Option<CqlTimestamp>,
+);
+
+type TodoShareRowTuple = (Uuid, String, String, CqlTimestamp);
+
+const SELECT_TODO_ACL: &str =
+    "SELECT todo_id, user_id, permission, shared_at FROM todo_db.todo_acl_v2";
+
+const SELECT_WORKSPACE_MEMBERS: &str =
+    "SELECT workspace_id, user_id, role, joined_at FROM todo_db.workspace_members";
+
+type UserRowTuple = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    CqlTimestamp,
+    CqlTimestamp,
+);
+
+type UserTotpRowTuple = (
+    Option<String>,
+    Option<CqlTimestamp>,
+    Option<i64>,
+    Option<String>,
+);
+
+type UserSessionRowTuple = (
+    String,
+    String,
+    Option<String>,
+    String,
+    CqlTimestamp,
+    CqlTimestamp,
+    CqlTimestamp,
+);
+
+const SELECT_SESSIONS: &str = "SELECT id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at FROM todo_db.sessions";
+
+const SELECT_USERS: &str = "SELECT id, email, password_hash, role, display_name, avatar_url, created_at, updated_at FROM todo_db.users";
+
+type WebhookRowTuple = (
+    String,
+    String,
+    String,
+    String,
+    bool,
+    CqlTimestamp,
+    CqlTimestamp,
+);
+
+type DeadLetterRowTuple = (String, String, String, String, i32, String, CqlTimestamp);
+
+type NotificationSettingsRowTuple = (String, Option<bool>, Option<bool>, CqlTimestamp);
+
+const SELECT_NOTIFICATION_SETTINGS: &str =
+    "SELECT email, reminders, digest, updated_at FROM todo_db.notification_settings";
+
+type JobRowTuple = (
+    String,
+    String,
+    String,
+    String,
+    i32,
+    i32,
+    Option<String>,
+    CqlTimestamp,
+    CqlTimestamp,
+);
+
+const SELECT_JOBS: &str = "SELECT id, kind, payload, status, attempts, max_attempts, last_error, created_at, updated_at FROM todo_db.jobs";
+
+type OutboxRowTuple = (
+    String,
+    Option<String>,
+    String,
+    Uuid,
+    String,
+    CqlTimestamp,
+    Option<CqlTimestamp>,
+);
+
+const SELECT_OUTBOX: &str =
+    "SELECT id, consumer, event, todo_id, payload, created_at, delivered_at FROM todo_db.outbox";
+
+const INSERT_OUTBOX: &str = "INSERT INTO todo_db.outbox (id, consumer, event, todo_id, payload, delivered, created_at, delivered_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+
+const SELECT_WEBHOOKS: &str =
+    "SELECT id, url, secret, events, active, created_at, updated_at FROM todo_db.webhooks";
+
+/// IDs per `IN` lookup; large IN lists fan out to many partitions from one coordinator.
+const LOOKUP_CHUNK_SIZE: usize = 50;
+
+/// Statements per single-partition batch written by [`write_by_partition`].
+const PARTITION_BATCH_SIZE: usize = 100;
+
+/// Partitions [`write_by_partition`] writes to at the same time.
+const PARTITION_WRITE_CONCURRENCY: usize = 16;
+
+pub struct ScyllaTodoRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+    decoding: RowDecoding,
+}
+
+impl ScyllaTodoRepository {
+    pub fn new(
+        session: Arc<Session>,
+        consistency: ConsistencyConfig,
+        decoding: RowDecoding,
+    ) -> Self {
+        ScyllaTodoRepository {
+            session,
+            consistency,
+            decoding,
+        }
+    }
+
+    /// A webhook repository sharing this repository's session.
+    pub fn webhooks(&self) -> ScyllaWebhookRepository {
+        ScyllaWebhookRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A notification settings repository sharing this repository's session.
+    pub fn notification_settings(&self) -> ScyllaNotificationSettingsRepository {
+        ScyllaNotificationSettingsRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A user settings repository sharing this repository's session.
+    pub fn user_settings(&self) -> ScyllaUserSettingsRepository {
+        ScyllaUserSettingsRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A workspace repository sharing this repository's session.
+    pub fn workspaces(&self) -> ScyllaWorkspaceRepository {
+        ScyllaWorkspaceRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// An attachment repository sharing this repository's session.
+    pub fn attachments(&self) -> ScyllaAttachmentRepository {
+        ScyllaAttachmentRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A template repository sharing this repository's session.
+    pub fn templates(&self) -> ScyllaTemplateRepository {
+        ScyllaTemplateRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A project repository sharing this repository's session.
+    pub fn projects(&self) -> ScyllaProjectRepository {
+        ScyllaProjectRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A comment repository sharing this repository's session.
+    pub fn comments(&self) -> ScyllaCommentRepository {
+        ScyllaCommentRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// An activity log sharing this repository's session.
+    pub fn activity(&self) -> ScyllaActivityRepository {
+        ScyllaActivityRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// An undo log sharing this repository's session.
+    pub fn undo(&self) -> ScyllaUndoRepository {
+        ScyllaUndoRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A change log sharing this repository's session.
+    pub fn changes(&self) -> ScyllaChangeRepository {
+        ScyllaChangeRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// An event outbox sharing this repository's session.
+    pub fn outbox(&self) -> ScyllaOutboxRepository {
+        ScyllaOutboxRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A refresh token store sharing this repository's session.
+    pub fn refresh_tokens(&self) -> ScyllaRefreshTokenRepository {
+        ScyllaRefreshTokenRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A login session store sharing this repository's session.
+    pub fn sessions(&self) -> ScyllaSessionRepository {
+        ScyllaSessionRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A failed login store sharing this repository's session.
+    pub fn login_attempts(&self) -> ScyllaLoginAttemptRepository {
+        ScyllaLoginAttemptRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A password reset token store sharing this repository's session.
+    pub fn password_resets(&self) -> ScyllaPasswordResetRepository {
+        ScyllaPasswordResetRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A todo ACL repository sharing this repository's session.
+    pub fn acl(&self) -> ScyllaTodoAclRepository {
+        ScyllaTodoAclRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A user repository sharing this repository's session.
+    pub fn users(&self) -> ScyllaUserRepository {
+        ScyllaUserRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A job repository sharing this repository's session.
+    pub fn jobs(&self) -> ScyllaJobRepository {
+        ScyllaJobRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A schema repository sharing this repository's session.
+    pub fn schema(&self) -> ScyllaSchemaRepository {
+        ScyllaSchemaRepository {
+            session: self.session.clone(),
+        }
+    }
+
+    /// A lease repository sharing this repository's session.
+    pub fn leases(&self) -> ScyllaLeaseRepository {
+        ScyllaLeaseRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A maintenance repository sharing this repository's session.
+    pub fn maintenance(&self) -> ScyllaMaintenanceRepository {
+        ScyllaMaintenanceRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    /// A feature flag repository sharing this repository's session.
+    pub fn feature_flags(&self) -> ScyllaFeatureFlagRepository {
+        ScyllaFeatureFlagRepository {
+            session: self.session.clone(),
+            consistency: self.consistency,
+        }
+    }
+
+    fn read(&self, text: &str) -> Query {
+        read_query(text, &self.consistency)
+    }
+
+    fn write(&self, text: &str) -> Query {
+        write_query(text, &self.consistency)
+    }
+}
+
+/// Builds a statement at the configured read consistency, or the
+/// read-your-writes one for requests that asked for it.
+fn read_query(text: &str, consistency: &ConsistencyConfig) -> Query {
+    let mut query = Query::new(text);
+    query.set_consistency(match crate::consistency::reads_own_writes() {
+        true => consistency.read_your_writes,
+        false => consistency.read,
+    });
+    // Lets speculative execution send slow reads to another replica.
+    query.set_is_idempotent(true);
+    query
+}
+
+/// Builds a statement at the configured write consistency; the serial
+/// consistency only takes effect for conditional (LWT) statements.
+fn write_query(text: &str, consistency: &ConsistencyConfig) -> Query {
+    let mut query = Query::new(text);
+    query.set_consistency(consistency.write);
+    query.set_serial_consistency(Some(consistency.serial));
+    query
+}
+
+/// Writes `rows`, each a partition key and the statement's bound values,
+/// grouped by partition. Rows sharing a partition go out as unlogged
+/// batches of at most [`PARTITION_BATCH_SIZE`], which the partition's
+/// replicas apply atomically without the batchlog a multi-partition logged
+/// batch costs. The statement is prepared, so the driver routes each write
+/// straight to a replica owning its token, and up to
+/// [`PARTITION_WRITE_CONCURRENCY`] partitions are written in parallel.
+///
+/// Writes to different partitions are independent: on error some of them
+/// may have been applied, so statements should be idempotent.
+async fn write_by_partition<K: Eq + Hash, V: SerializeRow>(
+    session: &Session,
+    consistency: &ConsistencyConfig,
+    statement: &str,
+    rows: impl IntoIterator<Item = (K, V)>,
+) -> Result<(), RepositoryError> {
+    let mut partitions: Vec<Vec<V>> = Vec::new();
+    let mut index: HashMap<K, usize> = HashMap::new();
+    for (key, values) in rows {
+        let position = *index.entry(key).or_insert_with(|| {
+            partitions.push(Vec::new());
+            partitions.len() - 1
+        });
+        partitions[position].push(values);
+    }
+    if partitions.is_empty() {
+        return Ok(());
+    }
+
+    let mut prepared = session.prepare(statement).await.map_err(db_error)?;
+    prepared.set_consistency(consistency.write);
+    prepared.set_is_idempotent(true);
+    let prepared = &prepared;
+
+    let mut chunks = Vec::new();
+    for mut partition in partitions {
+        while partition.len() > PARTITION_BATCH_SIZE {
+            let rest = partition.split_off(PARTITION_BATCH_SIZE);
+            chunks.push(partition);
+            partition = rest;
+        }
+        chunks.push(partition);
+    }
+
+    futures_util::stream::iter(chunks)
+        .map(|mut chunk| async move {
+            if chunk.len() == 1 {
+                let values = chunk.pop().expect("chunk has one row");
+                session.execute(prepared, values).await.map_err(db_error)?;
+                return Ok(());
+            }
+            let mut batch = Batch::new(BatchType::Unlogged);
+            batch.set_consistency(consistency.write);
+            batch.set_is_idempotent(true);
+            for _ in &chunk {
+                batch.append_statement(prepared.clone());
+            }
+            session.batch(&batch, chunk).await.map_err(db_error)?;
+            Ok(())
+        })
+        .buffer_unordered(PARTITION_WRITE_CONCURRENCY)
+        .try_collect::<Vec<()>>()
+        .await?;
+
+    Ok(())
+}
+
+/// Decodes the rows of a `SELECT_TODOS` query, leaving out or failing on
+/// those that do not decode as `decoding` says. Public for the benchmarks.
+pub fn decode_todos(rows: Vec<Row>, decoding: RowDecoding) -> Result<Vec<Todo>, RepositoryError> {
+    rows.into_iter()
+        .filter_map(|row| decoding.check(decode_todo(row)).transpose())
+        .collect()
+}
+
+/// The todos in `options`' window of the rows of a `SELECT_TODOS` query,
+/// decoding rows as they are needed: none past the end of the window, and
+/// only one at a time before it. Public for the benchmarks.
+pub fn decode_window<S, E>(rows: S, options: &ListOptions, decoding: RowDecoding) -> TodoStream
+where
+    S: Stream<Item = Result<Row, E>> + Send + 'static,
+    E: std::fmt::Display + 'static,
+{
+    let (offset, limit) = (options.offset, options.limit);
+    let options = options.clone();
+    rows.map_err(db_error)
+        .try_filter_map(move |row| future::ready(decoding.check(decode_todo(row))))
+        .try_filter(move |todo| future::ready(options.matches(todo)))
+        .skip(offset)
+        .take(limit)
+        .boxed()
+}
+
+/// A stored todo from its row. Fails on values nothing could have written,
+/// rather than leaving them out of the todo: saved again, it would lose
+/// them for good.
+impl TryFrom<TodoRow> for Todo {
+    type Error = RepositoryError;
+
+    fn try_from(row: TodoRow) -> Result<Self, Self::Error> {
+        let TodoRow {
+            id,
+            title,
+            content,
+            completed,
+            created_at,
+            updated_at,
+            archived,
+            due_at,
+            recurrence,
+            series_id,
+            next_occurrence_id,
+            remind_at,
+            reminder_sent_at,
+            owner_id,
+            workspace_id,
+            project_id,
+            position,
+            status,
+            assignee_id,
+            title_key: _,
+            ttl,
+        } = row;
+        let invalid = |column: &str, detail: &dyn std::fmt::Display| {
+            RepositoryError::Decode(format!("todo {}: invalid {}: {}", id, column, detail))
+        };
+        let timestamp = |column: &str, timestamp: CqlTimestamp| {
+            from_timestamp(timestamp).ok_or_else(|| invalid(column, &timestamp.0))
+        };
+        let optional_timestamp = |column: &str, value: Option<CqlTimestamp>| {
+            value.map(|value| timestamp(column, value)).transpose()
+        };
+
+        Ok(Todo {
+            id: Some(TodoId(id)),
+            title,
+            content,
+            completed: Some(completed),
+            archived: Some(archived.unwrap_or(false)),
+            due_at: optional_timestamp("due_at", due_at)?,
+            recurrence: recurrence
+                .map(|json| serde_json::from_str(&json).map_err(|e| invalid("recurrence", &e)))
+                .transpose()?,
+            series_id: series_id.map(TodoId),
+            next_occurrence_id: next_occurrence_id.map(TodoId),
+            remind_at: optional_timestamp("remind_at", remind_at)?,
+            reminder_sent_at: optional_timestamp("reminder_sent_at", reminder_sent_at)?,
+            owner_id,
+            workspace_id,
+            project_id,
+            position,
+            status: status
+                .map(|status| status.parse().map_err(|e| invalid("status", &e)))
+                .transpose()?,
+            assignee_id,
+            created_at: Some(timestamp("created_at", created_at)?),
+            updated_at: Some(timestamp("updated_at", updated_at)?),
+            expires_at: ttl
+                .filter(|ttl| *ttl > 0)
+                .map(|ttl| Utc::now() + chrono::Duration::seconds(ttl.into())),
+            comment_count: None,
+            ttl_seconds: None,
+            content_html: None,
+        })
+    }
+}
+
+/// A todo from a `SELECT_TODOS` row, failing on a row whose columns do
+/// not have the expected types.
+fn decode_todo(row: Row) -> Result<Todo, RepositoryError> {
+    TodoRow::from_row(row).map_err(decode_error)?.try_into()
+}
+
+/// The TTL that makes a row written now expire at `expires_at`; 0, meaning
+/// none, for todos that do not expire. A todo already past its expiry gets
+/// the shortest TTL so it disappears at once.
+/// Whether a lightweight transaction was applied, which the first column
+/// of its result says.
+fn applied(rows: Option<Vec<Row>>) -> bool {
+    rows.and_then(|rows| rows.into_iter().next())
+        .and_then(|row| row.columns.into_iter().next().flatten())
+        .is_some_and(|applied| applied == CqlValue::Boolean(true))
+}
+
+fn ttl_for(expires_at: Option<DateTime<Utc>>) -> i32 {
+    match expires_at {
+        Some(expires_at) => (expires_at - Utc::now())
+            .num_seconds()
+            .clamp(1, i32::MAX.into()) as i32,
+        None => 0,
+    }
+}
+
+/// Values for `INSERT_TODO`, in its column order followed by the TTL.
+fn todo_to_row(todo: &Todo) -> Result<TodoRow, RepositoryError> {
+    Ok(TodoRow {
+        id: todo_uuid(todo)?,
+        title: todo.title.clone(),
+        content: todo.content.clone(),
+        completed: todo.completed.unwrap_or(false),
+        created_at: to_timestamp(todo.created_at),
+        updated_at: to_timestamp(todo.updated_at),
+        archived: Some(todo.archived.unwrap_or(false)),
+        due_at: todo.due_at.map(|due_at| to_timestamp(Some(due_at))),
+        recurrence: recurrence_json(todo.recurrence.as_ref()),
+        series_id: todo.series_id.map(|id| id.0),
+        next_occurrence_id: todo.next_occurrence_id.map(|id| id.0),
+        remind_at: todo
+            .remind_at
+            .map(|remind_at| to_timestamp(Some(remind_at))),
+        reminder_sent_at: todo
+            .reminder_sent_at
+            .map(|sent_at| to_timestamp(Some(sent_at))),
+        owner_id: todo.owner_id.clone(),
+        workspace_id: todo.workspace_id.clone(),
+        project_id: todo.project_id.clone(),
+        position: todo.position,
+        status: todo.status.map(|status| status.as_str().to_string()),
+        assignee_id: todo.assignee_id.clone(),
+        title_key: Some(titles::key(&todo.title)),
+        ttl: Some(ttl_for(todo.expires_at)),
+    })
+}
+
+/// Values for `UPDATE_TODO`. The written cells must expire with the rest
+/// of the row.
+fn todo_update(todo: &Todo) -> Result<TodoUpdate, RepositoryError> {
+    Ok(TodoUpdate {
+        ttl: ttl_for(todo.expires_at),
+        title: todo.title.clone(),
+        content: todo.content.clone(),
+        completed: todo.completed.unwrap_or(false),
+        updated_at: to_timestamp(todo.updated_at),
+        archived: todo.archived.unwrap_or(false),
+        due_at: todo.due_at.map(|due_at| to_timestamp(Some(due_at))),
+        recurrence: recurrence_json(todo.recurrence.as_ref()),
+        series_id: todo.series_id.map(|id| id.0),
+        next_occurrence_id: todo.next_occurrence_id.map(|id| id.0),
+        remind_at: todo
+            .remind_at
+            .map(|remind_at| to_timestamp(Some(remind_at))),
+        reminder_sent_at: todo
+            .reminder_sent_at
+            .map(|sent_at| to_timestamp(Some(sent_at))),
+        project_id: todo.project_id.clone(),
+        position: todo.position,
+        status: todo.status.map(|status| status.as_str().to_string()),
+        assignee_id: todo.assignee_id.clone(),
+        title_key: titles::key(&todo.title),
+        id: todo_uuid(todo)?,
+    })
+}
+
+/// Values for `UPDATE_TODO_FIELDS`.
+fn patch_values<'a>(id: &TodoId, patch: &'a TodoPatch) -> impl SerializeRow + Sync + 'a {
+    (
+        ttl_for(patch.expires_at),
+        maybe_unset(patch.title.as_deref()),
+        maybe_unset(patch.title.as_deref().map(titles::key)),
+        maybe_unset(patch.content.as_deref()),
+        maybe_unset(patch.completed),
+        to_timestamp(Some(patch.updated_at)),
+        maybe_unset(patch.due_at.map(|due_at| to_timestamp(Some(due_at)))),
+        maybe_unset(
+            patch
+                .recurrence
+                .as_ref()
+                .and_then(|recurrence| recurrence_json(Some(recurrence))),
+        ),
+        maybe_unset(patch.series_id.map(|id| id.0)),
+        maybe_unset(
+            patch
+                .remind_at
+                .map(|remind_at| to_timestamp(Some(remind_at))),
+        ),
+        if patch.rearm_reminder {
+            MaybeUnset::Set(None::<CqlTimestamp>)
+        } else {
+            MaybeUnset::Unset
+        },
+        maybe_unset(patch.project_id.as_deref()),
+        maybe_unset(patch.position),
+        maybe_unset(patch.status.map(|status| status.as_str())),
+        maybe_unset(patch.assignee_id.as_ref().map(Option::as_deref)),
+        id.0,
+    )
+}
+
+fn todo_uuid(todo: &Todo) -> Result<Uuid, RepositoryError> {
+    todo.id
+        .map(|id| id.0)
+        .ok_or_else(|| RepositoryError::Database("todo has no ID".to_string()))
+}
+
+fn uuids(ids: &[TodoId]) -> Vec<Uuid> {
+    ids.iter().map(|id| id.0).collect()
+}
+
+fn db_error(e: impl std::fmt::Display) -> RepositoryError {
+    RepositoryError::Database(e.to_string())
+}
+
+/// For a stored value that cannot be read back.
+fn decode_error(e: impl std::fmt::Display) -> RepositoryError {
+    RepositoryError::Decode(e.to_string())
+}
+
+#[async_trait]
+impl TodoRepository for ScyllaTodoRepository {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        let mut todos = match (&options.owner_id, &options.project_id, &options.scope) {
+            (Some(owner_id), _, _) => self.fetch_owner(owner_id, options).await?,
+            (None, Some(project_id), _) => self.fetch_project(project_id).await?,
+            (None, None, TodoScope::Workspace(workspace_id)) => {
+                self.fetch_workspace(workspace_id).await?
+            }
+            // Read a page at a time, stopping once the page asked for is
+            // full, rather than decoding the whole table to return a page.
+            (None, None, TodoScope::All | TodoScope::Personal)
+                if options.sort != TodoSort::Position =>
+            {
+                return self.scan_todos(options).await?.try_collect().await;
+            }
+            (None, None, TodoScope::All | TodoScope::Personal) => self.fetch_all().await?,
+        };
+        // Other listings keep the order they are read in.
+        if options.sort == TodoSort::Position {
+            todos.sort_by(|a, b| options.sort.compare(a, b));
+        }
+
+        Ok(todos
+            .into_iter()
+            .filter(|todo| options.matches(todo))
+            .skip(options.offset)
+            .take(options.limit)
+            .collect())
+    }
+
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        // Owner, project and workspace listings read one partition or index
+        // entry, which is small enough to collect. Sorting by position needs
+        // every todo before the first can be returned anyway.
+        if options.owner_id.is_some()
+            || options.project_id.is_some()
+            || options.sort != TodoSort::CreatedAt
+            || matches!(options.scope, TodoScope::Workspace(_))
+        {
+            let todos = self.list(options).await?;
+            return Ok(stream::iter(todos.into_iter().map(Ok)).boxed());
+        }
+        self.scan_todos(options).await
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        let query = format!("{} WHERE id = ?", SELECT_TODOS);
+
+        let rows = self
+            .session
+            .query(self.read(&query), (id.0,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        rows.and_then(|rows| rows.into_iter().next())
+            .map(decode_todo)
+            .transpose()
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        let query = "SELECT id FROM todo_db.todos_by_title_key WHERE title_key = ? LIMIT 1";
+
+        let rows = self
+            .session
+            .query(self.read(query), (titles::key(title),))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows.is_some_and(|rows| !rows.is_empty()))
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        let query = "SELECT id FROM todo_db.todos_by_title WHERE title = ?";
+
+        let rows = self
+            .session
+            .query(self.read(query), (title,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let ids: Vec<Uuid> = rows
+            .map(|rows| {
+                rows.into_typed::<(Uuid,)>()
+                    .flatten()
+                    .map(|(id,)| id)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.fetch_by_ids(&ids).await
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.session
+            .query(self.write(INSERT_TODO), todo_to_row(todo)?)
+            .await
+            .map_err(db_error)?;
+        self.adjust_counts(count_deltas([todo.owner_id.as_deref()], 1))
+            .await;
+
+        if let Some(workspace_id) = &todo.workspace_id {
+            self.session
+                .query(
+                    self.write(INSERT_TODO_BY_WORKSPACE),
+                    (workspace_id, todo_uuid(todo)?, ttl_for(todo.expires_at)),
+                )
+                .await
+                .map_err(db_error)?;
+        }
+        if let Some(owner_id) = &todo.owner_id {
+            self.session
+                .query(
+                    self.write(INSERT_TODO_BY_OWNER),
+                    (
+                        owner_id,
+                        to_timestamp(todo.created_at),
+                        todo_uuid(todo)?,
+                        ttl_for(todo.expires_at),
+                    ),
+                )
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        let mut rows = Vec::with_capacity(todos.len());
+        let mut lookups = Vec::new();
+        let mut owner_lookups = Vec::new();
+        for todo in todos {
+            let id = todo_uuid(todo)?;
+            let ttl = ttl_for(todo.expires_at);
+            rows.push((id, todo_to_row(todo)?));
+            // Workspace todos also need a row in the by-workspace table, and
+            // owned todos one in the by-owner table.
+            if let Some(workspace_id) = &todo.workspace_id {
+                lookups.push((workspace_id, (workspace_id, id, ttl)));
+            }
+            if let Some(owner_id) = &todo.owner_id {
+                owner_lookups.push((owner_id, (owner_id, to_timestamp(todo.created_at), id, ttl)));
+            }
+        }
+
+        write_by_partition(&self.session, &self.consistency, INSERT_TODO, rows).await?;
+        self.adjust_counts(count_deltas(
+            todos.iter().map(|todo| todo.owner_id.as_deref()),
+            1,
+        ))
+        .await;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            INSERT_TODO_BY_WORKSPACE,
+            lookups,
+        )
+        .await?;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            INSERT_TODO_BY_OWNER,
+            owner_lookups,
+        )
+        .await
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        let values = todo_update(todo)?;
+
+        self.session
+            .query(self.write(UPDATE_TODO), values)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        self.session
+            .query(self.write(UPDATE_TODO_FIELDS), patch_values(id, patch))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        self.delete_many(std::slice::from_ref(id)).await
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        // Each statement's values live until the batch has been sent.
+        let (row, by_workspace, by_owner, update, patch, deletion, keys, lookup, owner_lookup);
+        let outbox: Vec<_> = entries.iter().map(outbox_values).collect();
+        let mut values: Vec<&(dyn SerializeRow + Sync)> = Vec::new();
+        let mut batch = Batch::new(BatchType::Logged);
+        batch.set_consistency(self.consistency.write);
+        let mut count_delta = None;
+
+        match write {
+            TodoWrite::Insert(todo) => {
+                let (id, ttl) = (todo_uuid(todo)?, ttl_for(todo.expires_at));
+                row = todo_to_row(todo)?;
+                batch.append_statement(INSERT_TODO);
+                values.push(&row);
+                if let Some(workspace_id) = &todo.workspace_id {
+                    by_workspace = (workspace_id, id, ttl);
+                    batch.append_statement(INSERT_TODO_BY_WORKSPACE);
+                    values.push(&by_workspace);
+                }
+                if let Some(owner_id) = &todo.owner_id {
+                    by_owner = (owner_id, to_timestamp(todo.created_at), id, ttl);
+                    batch.append_statement(INSERT_TODO_BY_OWNER);
+                    values.push(&by_owner);
+                }
+                count_delta = Some((todo.owner_id.clone(), 1));
+            }
+            TodoWrite::Update(todo) => {
+                update = todo_update(todo)?;
+                batch.append_statement(UPDATE_TODO);
+                values.push(&update);
+            }
+            TodoWrite::Patch(id, todo_patch) => {
+                patch = patch_values(id, todo_patch);
+                batch.append_statement(UPDATE_TODO_FIELDS);
+                values.push(&patch);
+            }
+            TodoWrite::Delete(id) => {
+                keys = self.todo_keys(std::slice::from_ref(id)).await?.pop();
+                deletion = (id.0,);
+                batch.append_statement(DELETE_TODO);
+                values.push(&deletion);
+                // Only a todo that still exists is taken off its owner's count.
+                if let Some(keys) = &keys {
+                    if let Some(workspace_id) = &keys.workspace_id {
+                        lookup = (workspace_id, keys.id.0);
+                        batch.append_statement(DELETE_TODO_BY_WORKSPACE);
+                        values.push(&lookup);
+                    }
+                    if let Some(owner_id) = &keys.owner_id {
+                        owner_lookup = (owner_id, keys.created_at, keys.id.0);
+                        batch.append_statement(DELETE_TODO_BY_OWNER);
+                        values.push(&owner_lookup);
+                    }
+                    count_delta = Some((keys.owner_id.clone(), -1));
+                }
+            }
+        }
+        for entry in &outbox {
+            batch.append_statement(INSERT_OUTBOX);
+            values.push(entry);
+        }
+
+        self.session.batch(&batch, values).await.map_err(db_error)?;
+        if let Some((owner_id, delta)) = count_delta {
+            self.adjust_counts(count_deltas([owner_id.as_deref()], delta))
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        // UPDATE is an upsert in Scylla, so look up which IDs exist first to
+        // avoid materialising half-empty rows for unknown IDs. The updated
+        // cells must expire with the rest of the row, so the TTL is read too.
+        let query = "SELECT id, TTL(title) FROM todo_db.todos_v2 WHERE id IN ?";
+        let ttls: HashMap<TodoId, i32> = self
+            .session
+            .query(self.read(query), (uuids(ids),))
+            .await
+            .map_err(db_error)?
+            .rows
+            .map(|rows| {
+                rows.into_typed::<(Uuid, Option<i32>)>()
+                    .flatten()
+                    .map(|(id, ttl)| (TodoId(id), ttl.unwrap_or(0)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut existing: Vec<TodoId> = Vec::new();
+        for id in ids {
+            if ttls.contains_key(id) && !existing.contains(id) {
+                existing.push(*id);
+            }
+        }
+        if existing.is_empty() {
+            return Ok(existing);
+        }
+
+        let query =
+            "UPDATE todo_db.todos_v2 USING TTL ? SET completed = ?, updated_at = ? WHERE id = ?";
+        let timestamp = to_timestamp(Some(updated_at));
+        let rows = existing
+            .iter()
+            .map(|id| (id.0, (ttls[id], completed, timestamp, id.0)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await?;
+
+        Ok(existing)
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        let found: std::collections::HashSet<TodoId> = self
+            .todo_keys(ids)
+            .await?
+            .into_iter()
+            .filter(|keys| match scope {
+                TodoScope::All => true,
+                TodoScope::Personal => keys.workspace_id.is_none(),
+                TodoScope::Workspace(id) => keys.workspace_id.as_deref() == Some(id.as_str()),
+            })
+            .map(|keys| keys.id)
+            .collect();
+
+        let mut existing: Vec<TodoId> = Vec::new();
+        for id in ids {
+            if found.contains(id) && !existing.contains(id) {
+                existing.push(*id);
+            }
+        }
+        Ok(existing)
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        // None of the columns is indexed, so this is a full scan filtered client-side.
+        let todos = self.fetch_all().await?;
+
+        Ok(todos
+            .into_iter()
+            .filter(|todo| filter.matches(todo))
+            .filter_map(|todo| todo.id)
+            .collect())
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        // Workspace and owned todos also have rows in the lookup tables, and
+        // only todos that still exist are taken off their owner's count.
+        let mut lookups = Vec::new();
+        let mut owner_lookups = Vec::new();
+        let mut owners = Vec::new();
+        for chunk in ids.chunks(LOOKUP_CHUNK_SIZE) {
+            for keys in self.todo_keys(chunk).await? {
+                if let Some(workspace_id) = keys.workspace_id {
+                    lookups.push((workspace_id.clone(), (workspace_id, keys.id.0)));
+                }
+                if let Some(owner_id) = &keys.owner_id {
+                    owner_lookups.push((
+                        owner_id.clone(),
+                        (owner_id.clone(), keys.created_at, keys.id.0),
+                    ));
+                }
+                owners.push(keys.owner_id);
+            }
+        }
+
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            DELETE_TODO_BY_WORKSPACE,
+            lookups,
+        )
+        .await?;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            DELETE_TODO_BY_OWNER,
+            owner_lookups,
+        )
+        .await?;
+        let rows = ids.iter().map(|id| (id.0, (id.0,)));
+        write_by_partition(&self.session, &self.consistency, DELETE_TODO, rows).await?;
+        self.adjust_counts(count_deltas(owners.iter().map(Option::as_deref), -1))
+            .await;
+        Ok(())
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        let query = format!("{} WHERE series_id = ?", SELECT_TODOS);
+
+        let rows = self
+            .session
+            .query(self.read(&query), (series_id.0,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| decode_todos(rows, self.decoding))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        let todos = self.fetch_all().await?;
+
+        Ok(todos.into_iter().filter(is_pending_recurrence).collect())
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        let mut reminders: Vec<Todo> = self
+            .fetch_all()
+            .await?
+            .into_iter()
+            .filter(|todo| is_pending_reminder(todo, due_before))
+            .collect();
+        reminders.sort_by_key(|todo| todo.remind_at);
+        Ok(reminders)
+    }
+
+    async fn delete_expired(&self, _now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        Ok(Vec::new())
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        let query = "SELECT todos FROM todo_db.todo_counts WHERE owner_id = ?";
+
+        let rows = self
+            .session
+            .query(self.read(query), (count_key(owner_id),))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<(Option<Counter>,)>().flatten().next())
+            .and_then(|(todos,)| todos)
+            .map_or(0, |Counter(todos)| todos.max(0) as u64))
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        Ok(self
+            .stored_counts()
+            .await?
+            .into_iter()
+            .filter(|(_, todos)| *todos > 0)
+            .map(|(key, todos)| {
+                let owner_id = (key != UNOWNED_COUNT_KEY).then_some(key);
+                (owner_id, todos as u64)
+            })
+            .collect())
+    }
+
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        let mut actual: HashMap<String, i64> = HashMap::new();
+        for todo in self.fetch_all().await? {
+            *actual
+                .entry(count_key(todo.owner_id.as_deref()).to_string())
+                .or_default() += 1;
+        }
+        let stored = self.stored_counts().await?;
+
+        let mut deltas: HashMap<String, i64> = HashMap::new();
+        for (key, todos) in &actual {
+            deltas.insert(key.clone(), todos - stored.get(key).copied().unwrap_or(0));
+        }
+        for (key, todos) in stored {
+            if !actual.contains_key(&key) {
+                deltas.insert(key, -todos);
+            }
+        }
+        deltas.retain(|_, delta| *delta != 0);
+
+        let corrected = deltas.len();
+        for (key, delta) in deltas {
+            self.session
+                .query(self.write(UPDATE_TODO_COUNT), (Counter(delta), &key))
+                .await
+                .map_err(db_error)?;
+        }
+        Ok(corrected)
+    }
+
+    /// Deletes lookup rows that point at no todo, or at one that moved, and
+    /// writes those that are missing.
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        let mut by_workspace = HashMap::new();
+        let mut by_owner = HashMap::new();
+        for todo in self.fetch_all().await? {
+            let id = todo_uuid(&todo)?;
+            let ttl = ttl_for(todo.expires_at);
+            if let Some(workspace_id) = todo.workspace_id {
+                by_workspace.insert((workspace_id, id), ttl);
+            }
+            if let Some(owner_id) = todo.owner_id {
+                by_owner.insert((owner_id, to_timestamp(todo.created_at).0, id), ttl);
+            }
+        }
+
+        let stored_by_workspace: Vec<(String, Uuid)> = self
+            .scan("SELECT workspace_id, todo_id FROM todo_db.todos_by_workspace_v2")
+            .await?;
+        let stored_by_owner: Vec<(String, CqlTimestamp, Uuid)> = self
+            .scan("SELECT owner_id, created_at, todo_id FROM todo_db.todos_by_owner")
+            .await?;
+
+        // What is stored already needs no writing; what is left over is stale.
+        let stale_by_workspace: Vec<(String, Uuid)> = stored_by_workspace
+            .into_iter()
+            .filter(|key| by_workspace.remove(key).is_none())
+            .collect();
+        let stale_by_owner: Vec<(String, i64, Uuid)> = stored_by_owner
+            .into_iter()
+            .map(|(owner_id, created_at, id)| (owner_id, created_at.0, id))
+            .filter(|key| by_owner.remove(key).is_none())
+            .collect();
+        // Todos written before titles had keys are missing from the
+        // by-title-key view until theirs is written, with the row's TTL.
+        let missing_title_keys: Vec<(Uuid, String, i32)> = self
+            .scan::<(Uuid, String, Option<String>, Option<i32>)>(
+                "SELECT id, title, title_key, TTL(title) FROM todo_db.todos_v2",
+            )
+            .await?
+            .into_iter()
+            .filter_map(|(id, title, stored_key, ttl)| {
+                let key = titles::key(&title);
+                (stored_key.as_ref() != Some(&key)).then(|| (id, key, ttl.unwrap_or(0)))
+            })
+            .collect();
+        let corrected = stale_by_workspace.len()
+            + stale_by_owner.len()
+            + by_workspace.len()
+            + by_owner.len()
+            + missing_title_keys.len();
+
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            DELETE_TODO_BY_WORKSPACE,
+            stale_by_workspace
+                .iter()
+                .map(|(workspace_id, id)| (workspace_id, (workspace_id, id))),
+        )
+        .await?;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            DELETE_TODO_BY_OWNER,
+            stale_by_owner.iter().map(|(owner_id, created_at, id)| {
+                (owner_id, (owner_id, CqlTimestamp(*created_at), id))
+            }),
+        )
+        .await?;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            INSERT_TODO_BY_WORKSPACE,
+            by_workspace
+                .iter()
+                .map(|((workspace_id, id), ttl)| (workspace_id, (workspace_id, id, ttl))),
+        )
+        .await?;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            INSERT_TODO_BY_OWNER,
+            by_owner.iter().map(|((owner_id, created_at, id), ttl)| {
+                (owner_id, (owner_id, CqlTimestamp(*created_at), id, ttl))
+            }),
+        )
+        .await?;
+        write_by_partition(
+            &self.session,
+            &self.consistency,
+            UPDATE_TODO_TITLE_KEY,
+            missing_title_keys
+                .iter()
+                .map(|(id, key, ttl)| (id, (ttl, key, id))),
+        )
+        .await?;
+
+        Ok(corrected)
+    }
+}
+
+impl ScyllaTodoRepository {
+    /// Reads a workspace's todo IDs from its partition of the by-workspace
+    /// table, then the todos themselves.
+    async fn fetch_workspace(&self, workspace_id: &str) -> Result<Vec<Todo>, RepositoryError> {
+        let query = "SELECT todo_id FROM todo_db.todos_by_workspace_v2 WHERE workspace_id = ?";
+
+        let rows = self
+            .session
+            .query(self.read(query), (workspace_id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let ids: Vec<Uuid> = rows
+            .map(|rows| {
+                rows.into_typed::<(Uuid,)>()
+                    .flatten()
+                    .map(|(id,)| id)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.fetch_by_ids(&ids).await
+    }
+
+    /// The todos of a project, through the secondary index on `project_id`.
+    async fn fetch_project(&self, project_id: &str) -> Result<Vec<Todo>, RepositoryError> {
+        let query = format!("{} WHERE project_id = ?", SELECT_TODOS);
+
+        let rows = self
+            .session
+            .query(self.read(&query), (project_id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| decode_todos(rows, self.decoding))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// Reads an owner's todo IDs from their partition of the by-owner
+    /// table, narrowed to the requested creation range, then the todos
+    /// themselves, oldest first.
+    async fn fetch_owner(
+        &self,
+        owner_id: &str,
+        options: &ListOptions,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        let query = "SELECT todo_id FROM todo_db.todos_by_owner WHERE owner_id = ? AND created_at >= ? AND created_at < ?";
+        let after = options
+            .created_after
+            .map_or(CqlTimestamp(i64::MIN), |at| to_timestamp(Some(at)));
+        let before = options
+            .created_before
+            .map_or(CqlTimestamp(i64::MAX), |at| to_timestamp(Some(at)));
+
+        let rows = self
+            .session
+            .query(self.read(query), (owner_id, after, before))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let ids: Vec<Uuid> = rows
+            .map(|rows| {
+                rows.into_typed::<(Uuid,)>()
+                    .flatten()
+                    .map(|(id,)| id)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // IN lookups come back in token order.
+        let mut todos = self.fetch_by_ids(&ids).await?;
+        todos.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(todos)
+    }
+
+    /// [`LOOKUP_CHUNK_SIZE`]; IDs with no todo are skipped.
+    async fn fetch_by_ids(&self, ids: &[Uuid]) -> Result<Vec<Todo>, RepositoryError> {
+        let mut todos = Vec::with_capacity(ids.len());
+        let query = format!("{} WHERE id IN ?", SELECT_TODOS);
+        for chunk in ids.chunks(LOOKUP_CHUNK_SIZE) {
+            let rows = self
+                .session
+                .query(self.read(&query), (chunk,))
+                .await
+                .map_err(db_error)?
+                .rows;
+
+            if let Some(rows) = rows {
+                todos.extend(decode_todos(rows, self.decoding)?);
+            }
+        }
+
+        Ok(todos)
+    }
+
+    /// Lookup-table keys of each of `ids` that exists.
+    async fn todo_keys(&self, ids: &[TodoId]) -> Result<Vec<TodoKeys>, RepositoryError> {
+        let query =
+            "SELECT id, workspace_id, owner_id, created_at FROM todo_db.todos_v2 WHERE id IN ?";
+
+        let rows = self
+            .session
+            .query(self.read(query), (uuids(ids),))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<(Uuid, Option<String>, Option<String>, CqlTimestamp)>()
+                    .flatten()
+                    .map(|(id, workspace_id, owner_id, created_at)| TodoKeys {
+                        id: TodoId(id),
+                        workspace_id,
+                        owner_id,
+                        created_at,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// The todos matching `options` in the order they are stored, read a
+    /// page at a time; reading stops once the window is full.
+    async fn scan_todos(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        let mut query = self.read(SELECT_TODOS);
+        query.set_page_size(STREAM_PAGE_SIZE as i32);
+        let rows = self
+            .session
+            .query_iter(query, &[])
+            .await
+            .map_err(db_error)?;
+        Ok(decode_window(rows, options, self.decoding))
+    }
+
+    async fn fetch_all(&self) -> Result<Vec<Todo>, RepositoryError> {
+        let rows = self
+            .session
+            .query(self.read(SELECT_TODOS), &[])
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| decode_todos(rows, self.decoding))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// Every row `query` selects, read a page at a time.
+    async fn scan<T: FromRow>(&self, query: &str) -> Result<Vec<T>, RepositoryError> {
+        let mut query = self.read(query);
+        query.set_page_size(STREAM_PAGE_SIZE as i32);
+        self.session
+            .query_iter(query, &[])
+            .await
+            .map_err(db_error)?
+            .into_typed::<T>()
+            .map_err(db_error)
+            .try_collect()
+            .await
+    }
+
+    /// Every row of the counter table, by key.
+    async fn stored_counts(&self) -> Result<HashMap<String, i64>, RepositoryError> {
+        let query = "SELECT owner_id, todos FROM todo_db.todo_counts";
+
+        let rows = self
+            .session
+            .query(self.read(query), &[])
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<(String, Option<Counter>)>()
+                    .flatten()
+                    .map(|(key, todos)| (key, todos.map_or(0, |Counter(todos)| todos)))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Applies `deltas` to the per-owner counters. Counter updates cannot be
+    /// retried safely, so a failure is only logged: the todos themselves are
+    /// already written, and the reconciliation job repairs the drift.
+    async fn adjust_counts(&self, deltas: HashMap<String, i64>) {
+        for (key, delta) in deltas {
+            let result = self
+                .session
+                .query(self.write(UPDATE_TODO_COUNT), (Counter(delta), &key))
+                .await;
+            if let Err(e) = result {
+                log::warn!(
+                    "event=todo_count_update_failed owner_id={} delta={} error=\"{}\"",
+                    key,
+                    delta,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Keys a todo's rows in the lookup tables are stored under.
+struct TodoKeys {
+    id: TodoId,
+    workspace_id: Option<String>,
+    owner_id: Option<String>,
+    created_at: CqlTimestamp,
+}
+
+fn count_key(owner_id: Option<&str>) -> &str {
+    owner_id.unwrap_or(UNOWNED_COUNT_KEY)
+}
+
+/// `delta` for each todo, summed per owner.
+fn count_deltas<'a>(
+    owners: impl IntoIterator<Item = Option<&'a str>>,
+    delta: i64,
+) -> HashMap<String, i64> {
+    let mut deltas = HashMap::new();
+    for owner_id in owners {
+        *deltas.entry(count_key(owner_id).to_string()).or_default() += delta;
+    }
+    deltas
+}
+
+fn maybe_unset<T>(value: Option<T>) -> MaybeUnset<T> {
+    value.map_or(MaybeUnset::Unset, MaybeUnset::Set)
+}
+
+fn recurrence_json(recurrence: Option<&Recurrence>) -> Option<String> {
+    recurrence.and_then(|recurrence| serde_json::to_string(recurrence).ok())
+}
+
+fn from_timestamp(timestamp: CqlTimestamp) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_millis(timestamp.0)
+}
+
+fn to_timestamp(datetime: Option<DateTime<Utc>>) -> CqlTimestamp {
+    CqlTimestamp(datetime.unwrap_or_else(Utc::now).timestamp_millis())
+}
+
+pub struct ScyllaWebhookRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn webhook_from_row(row: WebhookRowTuple) -> Webhook {
+    let (id, url, secret, events, active, created_at, updated_at) = row;
+    Webhook {
+        id,
+        url,
+        secret,
+        events: serde_json::from_str(&events).unwrap_or_default(),
+        active,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    }
+}
+
+fn dead_letter_from_row(row: DeadLetterRowTuple) -> Result<DeadLetter, RepositoryError> {
+    let (id, webhook_id, event, payload, attempts, last_error, failed_at) = row;
+    Ok(DeadLetter {
+        id,
+        webhook_id,
+        event: event.parse().map_err(decode_error)?,
+        payload: serde_json::from_str(&payload).map_err(decode_error)?,
+        attempts: attempts as u32,
+        last_error,
+        failed_at: from_timestamp(failed_at).unwrap_or_default(),
+    })
+}
+
+#[async_trait]
+impl WebhookRepository for ScyllaWebhookRepository {
+    async fn list(&self) -> Result<Vec<Webhook>, RepositoryError> {
+        let rows = self
+            .session
+            .query(read_query(SELECT_WEBHOOKS, &self.consistency), &[])
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let mut webhooks: Vec<Webhook> = rows
+            .map(|rows| {
+                rows.into_typed::<WebhookRowTuple>()
+                    .flatten()
+                    .map(webhook_from_row)
+                    .collect()
+            })
+            .unwrap_or_default();
+        webhooks.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(webhooks)
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Webhook>, RepositoryError> {
+        let query = format!("{} WHERE id = ?", SELECT_WEBHOOKS);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<WebhookRowTuple>().next())
+            .and_then(Result::ok)
+            .map(webhook_from_row))
+    }
+
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.webhooks (id, url, secret, events, active, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &webhook.id,
+                    &webhook.url,
+                    &webhook.secret,
+                    events_json(webhook),
+                    webhook.active,
+                    to_timestamp(Some(webhook.created_at)),
+                    to_timestamp(Some(webhook.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        // Every column is written, so an upsert is equivalent to an update.
+        self.insert(webhook).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.webhooks WHERE id = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (id,))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn add_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.webhook_dead_letters (id, webhook_id, event, payload, attempts, last_error, failed_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &dead_letter.id,
+                    &dead_letter.webhook_id,
+                    dead_letter.event.as_str(),
+                    dead_letter.payload.to_string(),
+                    dead_letter.attempts as i32,
+                    &dead_letter.last_error,
+                    to_timestamp(Some(dead_letter.failed_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list_dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter>, RepositoryError> {
+        // The table is keyed by ID only, so sort client-side.
+        let query = "SELECT id, webhook_id, event, payload, attempts, last_error, failed_at FROM todo_db.webhook_dead_letters";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), &[])
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let mut dead_letters = rows
+            .map(|rows| {
+                rows.into_typed::<DeadLetterRowTuple>()
+                    .flatten()
+                    .map(dead_letter_from_row)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        dead_letters.sort_by_key(|dead_letter| std::cmp::Reverse(dead_letter.failed_at));
+        dead_letters.truncate(limit);
+        Ok(dead_letters)
+    }
+}
+
+fn events_json(webhook: &Webhook) -> String {
+    serde_json::to_string(&webhook.events).unwrap_or_else(|_| "[]".to_string())
+}
+
+pub struct ScyllaNotificationSettingsRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn notification_settings_from_row(row: NotificationSettingsRowTuple) -> NotificationSettings {
+    let (email, reminders, digest, updated_at) = row;
+    NotificationSettings {
+        email,
+        reminders: reminders.unwrap_or(false),
+        digest: digest.unwrap_or(false),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    }
+}
+
+#[async_trait]
+impl NotificationSettingsRepository for ScyllaNotificationSettingsRepository {
+    async fn list(&self) -> Result<Vec<NotificationSettings>, RepositoryError> {
+        let rows = self
+            .session
+            .query(
+                read_query(SELECT_NOTIFICATION_SETTINGS, &self.consistency),
+                &[],
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<NotificationSettingsRowTuple>()
+                    .flatten()
+                    .map(notification_settings_from_row)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn find(&self, email: &str) -> Result<Option<NotificationSettings>, RepositoryError> {
+        let query = format!("{} WHERE email = ?", SELECT_NOTIFICATION_SETTINGS);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (email,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<NotificationSettingsRowTuple>().next())
+            .and_then(Result::ok)
+            .map(notification_settings_from_row))
+    }
+
+    async fn upsert(&self, settings: &NotificationSettings) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.notification_settings (email, reminders, digest, updated_at) VALUES (?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &settings.email,
+                    settings.reminders,
+                    settings.digest,
+                    to_timestamp(Some(settings.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, email: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.notification_settings WHERE email = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (email,))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct ScyllaUserSettingsRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl UserSettingsRepository for ScyllaUserSettingsRepository {
+    async fn find(&self, user_id: &str) -> Result<Option<UserSettings>, RepositoryError> {
+        let query = "SELECT timezone, page_size, default_sort, updated_at FROM todo_db.user_settings WHERE user_id = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (user_id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let Some((timezone, page_size, default_sort, updated_at)) = rows
+            .and_then(|rows| {
+                rows.into_typed::<(String, Option<i32>, Option<String>, CqlTimestamp)>()
+                    .next()
+            })
+            .transpose()
+            .map_err(db_error)?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(UserSettings {
+            user_id: user_id.to_string(),
+            timezone: timezone.parse().map_err(decode_error)?,
+            page_size: page_size.map(|size| size as usize),
+            default_sort: default_sort
+                .map(|sort| sort.parse())
+                .transpose()
+                .map_err(db_error)?,
+            updated_at: from_timestamp(updated_at),
+        }))
+    }
+
+    async fn upsert(&self, settings: &UserSettings) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.user_settings (user_id, timezone, page_size, default_sort, updated_at) VALUES (?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &settings.user_id,
+                    settings.timezone.name(),
+                    settings.page_size.map(|size| size as i32),
+                    settings.default_sort.map(|sort| sort.as_str()),
+                    to_timestamp(settings.updated_at),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.user_settings WHERE user_id = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (user_id,))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct ScyllaJobRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn job_from_row(row: JobRowTuple) -> Result<JobRecord, RepositoryError> {
+    let (id, kind, payload, status, attempts, max_attempts, last_error, created_at, updated_at) =
+        row;
+    Ok(JobRecord {
+        id,
+        kind,
+        payload: serde_json::from_str(&payload).map_err(decode_error)?,
+        status: status.parse().map_err(decode_error)?,
+        attempts: attempts as u32,
+        max_attempts: max_attempts as u32,
+        last_error,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    })
+}
+
+impl ScyllaJobRepository {
+    async fn find_by_status(&self, status: JobStatus) -> Result<Vec<JobRecord>, RepositoryError> {
+        let query = format!("{} WHERE status = ?", SELECT_JOBS);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (status.as_str(),))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        rows.map(|rows| {
+            rows.into_typed::<JobRowTuple>()
+                .flatten()
+                .map(job_from_row)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map(Option::unwrap_or_default)
+    }
+}
+
+#[async_trait]
+impl JobRepository for ScyllaJobRepository {
+    async fn insert(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.jobs (id, kind, payload, status, attempts, max_attempts, last_error, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &job.id,
+                    &job.kind,
+                    job.payload.to_string(),
+                    job.status.as_str(),
+                    job.attempts as i32,
+                    job.max_attempts as i32,
+                    &job.last_error,
+                    to_timestamp(Some(job.created_at)),
+                    to_timestamp(Some(job.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        // Every column is written, so an upsert is equivalent to an update.
+        self.insert(job).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<JobRecord>, RepositoryError> {
+        let query = format!("{} WHERE id = ?", SELECT_JOBS);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        rows.and_then(|rows| rows.into_typed::<JobRowTuple>().next())
+            .and_then(Result::ok)
+            .map(job_from_row)
+            .transpose()
+    }
+
+    async fn list_unfinished(&self) -> Result<Vec<JobRecord>, RepositoryError> {
+        let mut jobs = self.find_by_status(JobStatus::Queued).await?;
+        jobs.extend(self.find_by_status(JobStatus::Running).await?);
+        jobs.sort_by_key(|job| job.created_at);
+        Ok(jobs)
+    }
+
+    async fn purge_finished(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let mut ids = Vec::new();
+        for status in [JobStatus::Succeeded, JobStatus::Failed] {
+            ids.extend(
+                self.find_by_status(status)
+                    .await?
+                    .into_iter()
+                    .filter(|job| job.updated_at < before)
+                    .map(|job| job.id),
+            );
+        }
+
+        let query = "DELETE FROM todo_db.jobs WHERE id = ?";
+        let rows = ids.iter().map(|id| (id.as_str(), (id.as_str(),)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await?;
+
+        Ok(ids.len())
+    }
+}
+
+pub struct ScyllaSchemaRepository {
+    session: Arc<Session>,
+}
+
+#[async_trait]
+impl SchemaRepository for ScyllaSchemaRepository {
+    async fn status(&self) -> Result<SchemaStatus, RepositoryError> {
+        migrations::status(&self.session).await.map_err(db_error)
+    }
+}
+
+/// Leases as rows that expire through their TTL, taken and renewed with
+/// lightweight transactions.
+pub struct ScyllaLeaseRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl LeaseRepository for ScyllaLeaseRepository {
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        _now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let ttl = ttl_for(Some(until));
+
+        let query =
+            "INSERT INTO todo_db.leases (name, holder) VALUES (?, ?) IF NOT EXISTS USING TTL ?";
+        let rows = self
+            .session
+            .query(write_query(query, &self.consistency), (name, holder, ttl))
+            .await
+            .map_err(db_error)?
+            .rows;
+        if applied(rows) {
+            return Ok(true);
+        }
+
+        let query = "UPDATE todo_db.leases USING TTL ? SET holder = ? WHERE name = ? IF holder = ?";
+        let rows = self
+            .session
+            .query(
+                write_query(query, &self.consistency),
+                (ttl, holder, name, holder),
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+        Ok(applied(rows))
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.leases WHERE name = ? IF holder = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (name, holder))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+/// The key of the single row holding the maintenance under way.
+const CURRENT_MAINTENANCE: &str = "current";
+
+type MaintenanceRowTuple = (
+    Option<String>,
+    Option<i32>,
+    Option<bool>,
+    Option<String>,
+    Option<CqlTimestamp>,
+);
+
+fn maintenance_from_row(row: MaintenanceRowTuple) -> Maintenance {
+    let (message, retry_after_secs, allow_reads, started_by, started_at) = row;
+    Maintenance {
+        message: message.unwrap_or_default(),
+        retry_after_secs: retry_after_secs.unwrap_or(0).max(0) as u32,
+        allow_reads: allow_reads.unwrap_or(false),
+        started_by: started_by.unwrap_or_default(),
+        started_at: started_at.and_then(from_timestamp).unwrap_or_default(),
+    }
+}
+
+pub struct ScyllaMaintenanceRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl MaintenanceRepository for ScyllaMaintenanceRepository {
+    async fn current(&self) -> Result<Option<Maintenance>, RepositoryError> {
+        let query = "SELECT message, retry_after_secs, allow_reads, started_by, started_at FROM todo_db.maintenance WHERE id = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (CURRENT_MAINTENANCE,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<MaintenanceRowTuple>().next())
+            .and_then(Result::ok)
+            .map(maintenance_from_row))
+    }
+
+    async fn start(&self, maintenance: &Maintenance) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.maintenance (id, message, retry_after_secs, allow_reads, started_by, started_at) VALUES (?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    CURRENT_MAINTENANCE,
+                    &maintenance.message,
+                    i32::try_from(maintenance.retry_after_secs).unwrap_or(i32::MAX),
+                    maintenance.allow_reads,
+                    &maintenance.started_by,
+                    to_timestamp(Some(maintenance.started_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn end(&self) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.maintenance WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (CURRENT_MAINTENANCE,),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct ScyllaFeatureFlagRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl FeatureFlagRepository for ScyllaFeatureFlagRepository {
+    async fn list(&self) -> Result<HashMap<String, bool>, RepositoryError> {
+        let query = "SELECT name, enabled FROM todo_db.feature_flags";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), &[])
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<(String, Option<bool>)>()
+                    .flatten()
+                    .filter_map(|(name, enabled)| Some((name, enabled?)))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn set(&self, name: &str, enabled: bool) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.feature_flags (name, enabled) VALUES (?, ?)";
+
+        self.session
+            .query(write_query(query, &self.consistency), (name, enabled))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, name: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.feature_flags WHERE name = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (name,))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct ScyllaUserRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn user_from_row(row: UserRowTuple) -> Result<User, RepositoryError> {
+    let (id, email, password_hash, role, display_name, avatar_url, created_at, updated_at) = row;
+    Ok(User {
+        id,
+        email,
+        password_hash,
+        role: role.parse().map_err(decode_error)?,
+        display_name,
+        avatar_url,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    })
+}
+
+impl ScyllaUserRepository {
+    async fn find_one(&self, column: &str, value: &str) -> Result<Option<User>, RepositoryError> {
+        let query = format!("{} WHERE {} = ?", SELECT_USERS, column);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (value,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        rows.and_then(|rows| rows.into_typed::<UserRowTuple>().next())
+            .and_then(Result::ok)
+            .map(user_from_row)
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl UserRepository for ScyllaUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.users (id, email, password_hash, role, display_name, avatar_url, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &user.id,
+                    &user.email,
+                    &user.password_hash,
+                    user.role.as_str(),
+                    &user.display_name,
+                    &user.avatar_url,
+                    to_timestamp(Some(user.created_at)),
+                    to_timestamp(Some(user.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        self.find_one("email", email).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError> {
+        self.find_one("id", id).await
+    }
+
+    async fn count(&self) -> Result<usize, RepositoryError> {
+        let rows = self
+            .session
+            .query(
+                read_query("SELECT COUNT(*) FROM todo_db.users", &self.consistency),
+                &[],
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<(i64,)>().next())
+            .and_then(Result::ok)
+            .map_or(0, |(count,)| count as usize))
+    }
+
+    async fn update_profile(&self, user: &User) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.users SET email = ?, display_name = ?, avatar_url = ?, updated_at = ? WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &user.email,
+                    &user.display_name,
+                    &user.avatar_url,
+                    to_timestamp(Some(user.updated_at)),
+                    &user.id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        let query = "SELECT provider, subject FROM todo_db.user_identities WHERE user_id = ?";
+        let identities: Vec<(String, String)> = self
+            .session
+            .query(read_query(query, &self.consistency), (id,))
+            .await
+            .map_err(db_error)?
+            .rows
+            .map(|rows| rows.into_typed::<(String, String)>().flatten().collect())
+            .unwrap_or_default();
+
+        let query = "DELETE FROM todo_db.user_identities WHERE provider = ? AND subject = ?";
+        let rows = identities
+            .iter()
+            .map(|(provider, subject)| ((provider, subject), (provider, subject)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await?;
+
+        // The second factor is kept in the user's row, so it goes with it.
+        self.session
+            .query(
+                write_query("DELETE FROM todo_db.users WHERE id = ?", &self.consistency),
+                (id,),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_password(
+        &self,
+        id: &str,
+        password_hash: &str,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.users SET password_hash = ?, updated_at = ? WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (password_hash, to_timestamp(Some(updated_at)), id),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_role(
+        &self,
+        id: &str,
+        role: Role,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.users SET role = ?, updated_at = ? WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (role.as_str(), to_timestamp(Some(updated_at)), id),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn link_identity(&self, identity: &UserIdentity) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.user_identities (provider, subject, user_id, created_at) VALUES (?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &identity.provider,
+                    &identity.subject,
+                    &identity.user_id,
+                    to_timestamp(Some(identity.created_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let query =
+            "SELECT user_id FROM todo_db.user_identities WHERE provider = ? AND subject = ?";
+
+        let user_id = self
+            .session
+            .query(read_query(query, &self.consistency), (provider, subject))
+            .await
+            .map_err(db_error)?
+            .rows
+            .and_then(|rows| rows.into_typed::<(String,)>().next())
+            .and_then(Result::ok);
+
+        match user_id {
+            Some((user_id,)) => self.find_one("id", &user_id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn find_totp(&self, user_id: &str) -> Result<Option<UserTotp>, RepositoryError> {
+        let query = "SELECT totp_secret, totp_enabled_at, totp_last_step, totp_backup_codes FROM todo_db.users WHERE id = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (user_id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<UserTotpRowTuple>().next())
+            .and_then(Result::ok)
+            .and_then(|(secret, enabled_at, last_step, backup_codes)| {
+                Some(UserTotp {
+                    secret: secret?,
+                    enabled_at: enabled_at.and_then(from_timestamp),
+                    last_step: last_step.unwrap_or_default(),
+                    backup_codes: backup_codes
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .map(String::from)
+                        .collect(),
+                })
+            }))
+    }
+
+    async fn save_totp(
+        &self,
+        user_id: &str,
+        totp: Option<&UserTotp>,
+    ) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.users SET totp_secret = ?, totp_enabled_at = ?, totp_last_step = ?, totp_backup_codes = ? WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    totp.map(|totp| totp.secret.as_str()),
+                    totp.and_then(|totp| totp.enabled_at)
+                        .map(|at| to_timestamp(Some(at))),
+                    totp.map(|totp| totp.last_step),
+                    totp.map(|totp| totp.backup_codes.join(" ")),
+                    user_id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct ScyllaWorkspaceRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn workspace_from_row(row: WorkspaceRowTuple) -> Workspace {
+    let (id, name, created_at, updated_at) = row;
+    Workspace {
+        id,
+        name,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    }
+}
+
+fn workspace_member_from_row(
+    row: WorkspaceMemberRowTuple,
+) -> Result<WorkspaceMember, RepositoryError> {
+    let (workspace_id, user_id, role, joined_at) = row;
+    Ok(WorkspaceMember {
+        workspace_id,
+        user_id,
+        role: role.parse().map_err(decode_error)?,
+        joined_at: from_timestamp(joined_at).unwrap_or_default(),
+    })
+}
+
+impl ScyllaWorkspaceRepository {
+    async fn query_members(
+        &self,
+        condition: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+    ) -> Result<Vec<WorkspaceMember>, RepositoryError> {
+        let query = format!("{} WHERE {}", SELECT_WORKSPACE_MEMBERS, condition);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let mut members = rows
+            .map(|rows| {
+                rows.into_typed::<WorkspaceMemberRowTuple>()
+                    .flatten()
+                    .map(workspace_member_from_row)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+        members.sort_by_key(|member| member.joined_at);
+        Ok(members)
+    }
+}
+
+#[async_trait]
+impl WorkspaceRepository for ScyllaWorkspaceRepository {
+    async fn insert(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        let query =
+            "INSERT INTO todo_db.workspaces (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &workspace.id,
+                    &workspace.name,
+                    to_timestamp(Some(workspace.created_at)),
+                    to_timestamp(Some(workspace.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.workspaces SET name = ?, updated_at = ? WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &workspace.name,
+                    to_timestamp(Some(workspace.updated_at)),
+                    &workspace.id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        for query in [
+            "DELETE FROM todo_db.workspace_members WHERE workspace_id = ?",
+            "DELETE FROM todo_db.todos_by_workspace_v2 WHERE workspace_id = ?",
+            "DELETE FROM todo_db.workspaces WHERE id = ?",
+        ] {
+            self.session
+                .query(write_query(query, &self.consistency), (id,))
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Workspace>, RepositoryError> {
+        let query = format!("{} WHERE id = ?", SELECT_WORKSPACES);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<WorkspaceRowTuple>().next())
+            .and_then(Result::ok)
+            .map(workspace_from_row))
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Workspace>, RepositoryError> {
+        let members = self.query_members("user_id = ?", (user_id,)).await?;
+
+        let mut workspaces = Vec::with_capacity(members.len());
+        for member in members {
+            if let Some(workspace) = self.find_by_id(&member.workspace_id).await? {
+                workspaces.push(workspace);
+            }
+        }
+        workspaces.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(workspaces)
+    }
+
+    async fn upsert_member(&self, member: &WorkspaceMember) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.workspace_members (workspace_id, user_id, role, joined_at) VALUES (?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &member.workspace_id,
+                    &member.user_id,
+                    member.role.as_str(),
+                    to_timestamp(Some(member.joined_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.workspace_members WHERE workspace_id = ? AND user_id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (workspace_id, user_id),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceMember>, RepositoryError> {
+        Ok(self
+            .query_members("workspace_id = ? AND user_id = ?", (workspace_id, user_id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn list_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMember>, RepositoryError> {
+        self.query_members("workspace_id = ?", (workspace_id,))
+            .await
+    }
+}
+
+pub struct ScyllaTodoAclRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn todo_share_from_row(row: TodoShareRowTuple) -> Result<TodoShare, RepositoryError> {
+    let (todo_id, user_id, permission, shared_at) = row;
+    Ok(TodoShare {
+        todo_id: TodoId(todo_id),
+        user_id,
+        permission: permission.parse().map_err(decode_error)?,
+        shared_at: from_timestamp(shared_at).unwrap_or_default(),
+    })
+}
+
+impl ScyllaTodoAclRepository {
+    async fn query_shares(
+        &self,
+        condition: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+    ) -> Result<Vec<TodoShare>, RepositoryError> {
+        let query = format!("{} WHERE {}", SELECT_TODO_ACL, condition);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        rows.map(|rows| {
+            rows.into_typed::<TodoShareRowTuple>()
+                .flatten()
+                .map(todo_share_from_row)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map(Option::unwrap_or_default)
+    }
+}
+
+#[async_trait]
+impl TodoAclRepository for ScyllaTodoAclRepository {
+    async fn upsert(&self, share: &TodoShare) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.todo_acl_v2 (todo_id, user_id, permission, shared_at) VALUES (?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    share.todo_id.0,
+                    &share.user_id,
+                    share.permission.as_str(),
+                    to_timestamp(Some(share.shared_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        todo_id: &TodoId,
+        user_id: &str,
+    ) -> Result<Option<TodoShare>, RepositoryError> {
+        Ok(self
+            .query_shares("todo_id = ? AND user_id = ?", (todo_id.0, user_id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<TodoShare>, RepositoryError> {
+        let mut shares = self.query_shares("user_id = ?", (user_id,)).await?;
+        shares.sort_by_key(|share| std::cmp::Reverse(share.shared_at));
+        Ok(shares)
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.todo_acl_v2 WHERE todo_id = ?";
+        let rows = todo_ids.iter().map(|todo_id| (todo_id.0, (todo_id.0,)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await
+    }
+}
+
+/// Templates, partitioned by owner.
+pub struct ScyllaTemplateRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn template_from_row(row: TemplateRowTuple) -> Template {
+    let (id, owner_id, name, title, content, created_at, updated_at) = row;
+    Template {
+        id,
+        owner_id,
+        name,
+        title,
+        content,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    }
+}
+
+impl ScyllaTemplateRepository {
+    async fn query_templates(
+        &self,
+        condition: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+    ) -> Result<Vec<Template>, RepositoryError> {
+        let query = format!("{} WHERE {}", SELECT_TEMPLATES, condition);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<TemplateRowTuple>()
+                    .flatten()
+                    .map(template_from_row)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl TemplateRepository for ScyllaTemplateRepository {
+    async fn insert(&self, template: &Template) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.templates (owner_id, id, name, title, content, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &template.owner_id,
+                    &template.id,
+                    &template.name,
+                    &template.title,
+                    &template.content,
+                    to_timestamp(Some(template.created_at)),
+                    to_timestamp(Some(template.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        owner_id: &str,
+        id: &str,
+    ) -> Result<Option<Template>, RepositoryError> {
+        Ok(self
+            .query_templates("owner_id = ? AND id = ?", (owner_id, id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn list_for_owner(&self, owner_id: &str) -> Result<Vec<Template>, RepositoryError> {
+        let mut templates = self.query_templates("owner_id = ?", (owner_id,)).await?;
+        templates.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(templates)
+    }
+
+    async fn delete(&self, owner_id: &str, id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.templates WHERE owner_id = ? AND id = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (owner_id, id))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+/// Projects, partitioned by workspace; personal projects share the
+/// [`PERSONAL_PROJECTS_KEY`] partition.
+pub struct ScyllaProjectRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn project_from_row(row: ProjectRowTuple) -> Project {
+    let (id, name, workspace_id, owner_id, archived, created_at, updated_at) = row;
+    Project {
+        id,
+        name,
+        workspace_id,
+        owner_id,
+        archived: archived.unwrap_or(false),
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    }
+}
+
+fn project_partition(workspace_id: Option<&str>) -> &str {
+    workspace_id.unwrap_or(PERSONAL_PROJECTS_KEY)
+}
+
+impl ScyllaProjectRepository {
+    async fn query_projects(
+        &self,
+        condition: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+    ) -> Result<Vec<Project>, RepositoryError> {
+        let query = format!("{} WHERE {}", SELECT_PROJECTS, condition);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<ProjectRowTuple>()
+                    .flatten()
+                    .map(project_from_row)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ScyllaProjectRepository {
+    async fn insert(&self, project: &Project) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.projects (workspace_key, id, name, workspace_id, owner_id, archived, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    project_partition(project.workspace_id.as_deref()),
+                    &project.id,
+                    &project.name,
+                    &project.workspace_id,
+                    &project.owner_id,
+                    project.archived,
+                    to_timestamp(Some(project.created_at)),
+                    to_timestamp(Some(project.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, project: &Project) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.projects SET name = ?, archived = ?, updated_at = ? WHERE workspace_key = ? AND id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &project.name,
+                    project.archived,
+                    to_timestamp(Some(project.updated_at)),
+                    project_partition(project.workspace_id.as_deref()),
+                    &project.id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        workspace_id: Option<&str>,
+        id: &str,
+    ) -> Result<Option<Project>, RepositoryError> {
+        Ok(self
+            .query_projects(
+                "workspace_key = ? AND id = ?",
+                (project_partition(workspace_id), id),
+            )
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Project>, RepositoryError> {
+        let mut projects = self
+            .query_projects("workspace_key = ?", (project_partition(workspace_id),))
+            .await?;
+        projects.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(projects)
+    }
+
+    async fn delete(&self, workspace_id: Option<&str>, id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.projects WHERE workspace_key = ? AND id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (project_partition(workspace_id), id),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+/// Comments are partitioned by todo, so a todo's comments are read and
+/// paged together in one partition.
+pub struct ScyllaCommentRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn comment_from_row(row: CommentRowTuple) -> Comment {
+    let (id, todo_id, author_id, body, created_at, updated_at) = row;
+    Comment {
+        id,
+        todo_id: TodoId(todo_id),
+        author_id,
+        body,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        updated_at: from_timestamp(updated_at).unwrap_or_default(),
+    }
+}
+
+impl ScyllaCommentRepository {
+    async fn query_comments(
+        &self,
+        condition: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+    ) -> Result<Vec<Comment>, RepositoryError> {
+        let query = format!("{} WHERE {}", SELECT_COMMENTS, condition);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<CommentRowTuple>()
+                    .flatten()
+                    .map(comment_from_row)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl CommentRepository for ScyllaCommentRepository {
+    async fn insert(&self, comment: &Comment) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.comments_v2 (todo_id, id, author_id, body, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    comment.todo_id.0,
+                    &comment.id,
+                    &comment.author_id,
+                    &comment.body,
+                    to_timestamp(Some(comment.created_at)),
+                    to_timestamp(Some(comment.updated_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Comment>, RepositoryError> {
+        Ok(self
+            .query_comments("todo_id = ? AND id = ?", (todo_id.0, id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.comments_v2 WHERE todo_id = ? AND id = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (todo_id.0, id))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        todo_id: &TodoId,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Comment>, RepositoryError> {
+        let mut comments = self.query_comments("todo_id = ?", (todo_id.0,)).await?;
+        comments.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(comments.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn count_by_todo(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<HashMap<TodoId, usize>, RepositoryError> {
+        let query = "SELECT COUNT(*) FROM todo_db.comments_v2 WHERE todo_id = ?";
+
+        let mut counts = HashMap::new();
+        for todo_id in todo_ids {
+            let rows = self
+                .session
+                .query(read_query(query, &self.consistency), (todo_id.0,))
+                .await
+                .map_err(db_error)?
+                .rows;
+            let count = rows
+                .and_then(|rows| rows.into_typed::<(i64,)>().next())
+                .and_then(Result::ok)
+                .map_or(0, |(count,)| count as usize);
+            if count > 0 {
+                counts.insert(*todo_id, count);
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.comments_v2 WHERE todo_id = ?";
+        let rows = todo_ids.iter().map(|todo_id| (todo_id.0, (todo_id.0,)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await
+    }
+}
+
+/// The activity log, written to one table partitioned by todo and, for
+/// entries with an actor, another partitioned by actor. Both cluster by
+/// time, so a page is read in order and only the kinds are filtered here.
+pub struct ScyllaActivityRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+/// `None` for kinds this version does not know.
+fn activity_from_row(row: ActivityRowTuple) -> Option<Activity> {
+    let (id, todo_id, actor_id, kind, detail, occurred_at) = row;
+    Some(Activity {
+        id,
+        todo_id: TodoId(todo_id),
+        actor_id,
+        kind: kind.parse().ok()?,
+        detail,
+        occurred_at: from_timestamp(occurred_at).unwrap_or_default(),
+    })
+}
+
+impl ScyllaActivityRepository {
+    async fn page(
+        &self,
+        query: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<ActivityRowTuple>()
+                    .flatten()
+                    .filter_map(activity_from_row)
+                    .filter(|activity| kinds.is_empty() || kinds.contains(&activity.kind))
+                    .skip(offset)
+                    .take(limit)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl ActivityRepository for ScyllaActivityRepository {
+    async fn insert(&self, activity: &Activity) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.activity_by_todo (todo_id, occurred_at, id, actor_id, kind, detail) VALUES (?, ?, ?, ?, ?, ?)";
+        let actor_query = "INSERT INTO todo_db.activity_by_actor (actor_id, occurred_at, id, todo_id, kind, detail) VALUES (?, ?, ?, ?, ?, ?)";
+
+        let occurred_at = to_timestamp(Some(activity.occurred_at));
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    activity.todo_id.0,
+                    occurred_at,
+                    &activity.id,
+                    &activity.actor_id,
+                    activity.kind.as_str(),
+                    &activity.detail,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        if let Some(actor_id) = &activity.actor_id {
+            self.session
+                .query(
+                    write_query(actor_query, &self.consistency),
+                    (
+                        actor_id,
+                        occurred_at,
+                        &activity.id,
+                        activity.todo_id.0,
+                        activity.kind.as_str(),
+                        &activity.detail,
+                    ),
+                )
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn list_for_todo(
+        &self,
+        todo_id: &TodoId,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        let query = format!("{} WHERE todo_id = ?", SELECT_ACTIVITY_BY_TODO);
+        self.page(&query, (todo_id.0,), kinds, offset, limit).await
+    }
+
+    async fn list_for_actor(
+        &self,
+        actor_id: &str,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        let query = format!("{} WHERE actor_id = ?", SELECT_ACTIVITY_BY_ACTOR);
+        self.page(&query, (actor_id,), kinds, offset, limit).await
+    }
+
+    /// Scans the whole table; the purge that asks runs in the background.
+    async fn list_before(&self, before: DateTime<Utc>) -> Result<Vec<Activity>, RepositoryError> {
+        let mut query = read_query(SELECT_ACTIVITY_BY_TODO, &self.consistency);
+        query.set_page_size(STREAM_PAGE_SIZE as i32);
+
+        let mut entries: Vec<Activity> = self
+            .session
+            .query_iter(query, &[])
+            .await
+            .map_err(db_error)?
+            .into_typed::<ActivityRowTuple>()
+            .map_err(db_error)
+            .try_filter_map(|row| {
+                future::ready(Ok(
+                    activity_from_row(row).filter(|activity| activity.occurred_at < before)
+                ))
+            })
+            .try_collect()
+            .await?;
+        entries.sort_by(|a, b| {
+            a.occurred_at
+                .cmp(&b.occurred_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(entries)
+    }
+
+    async fn delete(&self, entries: &[Activity]) -> Result<(), RepositoryError> {
+        let query =
+            "DELETE FROM todo_db.activity_by_todo WHERE todo_id = ? AND occurred_at = ? AND id = ?";
+        let rows = entries.iter().map(|activity| {
+            (
+                activity.todo_id.0,
+                (
+                    activity.todo_id.0,
+                    to_timestamp(Some(activity.occurred_at)),
+                    activity.id.as_str(),
+                ),
+            )
+        });
+        write_by_partition(&self.session, &self.consistency, query, rows).await?;
+
+        let query = "DELETE FROM todo_db.activity_by_actor WHERE actor_id = ? AND occurred_at = ? AND id = ?";
+        let rows = entries.iter().filter_map(|activity| {
+            let actor_id = activity.actor_id.as_deref()?;
+            Some((
+                actor_id,
+                (
+                    actor_id,
+                    to_timestamp(Some(activity.occurred_at)),
+                    activity.id.as_str(),
+                ),
+            ))
+        });
+        write_by_partition(&self.session, &self.consistency, query, rows).await
+    }
+}
+
+/// The undo log, partitioned by actor with the newest entry first.
+pub struct ScyllaUndoRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+/// `None` for entries this version cannot read.
+fn undo_entry_from_row(row: UndoRowTuple) -> Option<UndoEntry> {
+    let (id, actor_id, action, todos, created_at) = row;
+    Some(UndoEntry {
+        id,
+        actor_id,
+        action: action.parse().ok()?,
+        todos: serde_json::from_str(&todos).ok()?,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+    })
+}
+
+#[async_trait]
+impl UndoRepository for ScyllaUndoRepository {
+    async fn insert(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.undo_log (actor_id, created_at, id, action, todos) VALUES (?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &entry.actor_id,
+                    to_timestamp(Some(entry.created_at)),
+                    &entry.id,
+                    entry.action.as_str(),
+                    undo_todos_json(&entry.todos)?,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn latest(
+        &self,
+        actor_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<UndoEntry>, RepositoryError> {
+        let query = "SELECT id, actor_id, action, todos, created_at FROM todo_db.undo_log WHERE actor_id = ? AND created_at >= ? LIMIT 1";
+
+        let rows = self
+            .session
+            .query(
+                read_query(query, &self.consistency),
+                (actor_id, to_timestamp(Some(since))),
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<UndoRowTuple>().next())
+            .and_then(Result::ok)
+            .and_then(undo_entry_from_row))
+    }
+
+    async fn delete(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.undo_log WHERE actor_id = ? AND created_at = ? AND id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &entry.actor_id,
+                    to_timestamp(Some(entry.created_at)),
+                    &entry.id,
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete_before(
+        &self,
+        actor_id: &str,
+        before: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.undo_log WHERE actor_id = ? AND created_at < ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (actor_id, to_timestamp(Some(before))),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+/// The change log, one row per todo. Changes since a token are found by
+/// scanning the table, which sync clients only do every so often.
+pub struct ScyllaChangeRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn change_from_row(row: ChangeRowTuple) -> TodoChange {
+    let (todo_id, token, workspace_id, owner_id, deleted, version, changed_at) = row;
+    TodoChange {
+        todo_id: TodoId(todo_id),
+        token,
+        workspace_id,
+        owner_id,
+        deleted,
+        version: serde_json::from_str(&version).unwrap_or_default(),
+        changed_at: from_timestamp(changed_at).unwrap_or_default(),
+    }
+}
+
+#[async_trait]
+impl ChangeRepository for ScyllaChangeRepository {
+    async fn record(&self, change: &TodoChange) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.todo_changes (todo_id, token, workspace_id, owner_id, deleted, version, changed_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    change.todo_id.0,
+                    change.token,
+                    &change.workspace_id,
+                    &change.owner_id,
+                    change.deleted,
+                    version_json(&change.version)?,
+                    to_timestamp(Some(change.changed_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, todo_id: &TodoId) -> Result<Option<TodoChange>, RepositoryError> {
+        let query = format!("{} WHERE todo_id = ?", SELECT_TODO_CHANGES);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (todo_id.0,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<ChangeRowTuple>().next())
+            .and_then(Result::ok)
+            .map(change_from_row))
+    }
+
+    async fn since(&self, token: i64, limit: usize) -> Result<Vec<TodoChange>, RepositoryError> {
+        let mut query = read_query(SELECT_TODO_CHANGES, &self.consistency);
+        query.set_page_size(STREAM_PAGE_SIZE as i32);
+
+        let mut changes: Vec<TodoChange> = self
+            .session
+            .query_iter(query, &[])
+            .await
+            .map_err(db_error)?
+            .into_typed::<ChangeRowTuple>()
+            .map_err(db_error)
+            .map_ok(change_from_row)
+            .try_filter(|change| future::ready(change.token > token))
+            .try_collect()
+            .await?;
+        changes.sort_by_key(|change| change.token);
+        changes.truncate(limit);
+        Ok(changes)
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let ids: Vec<TodoId> = self
+            .list_deleted(before)
+            .await?
+            .into_iter()
+            .map(|change| change.todo_id)
+            .collect();
+        self.delete(&ids).await?;
+
+        Ok(ids.len())
+    }
+
+    async fn list_deleted(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<TodoChange>, RepositoryError> {
+        let mut query = read_query(SELECT_TODO_CHANGES, &self.consistency);
+        query.set_page_size(STREAM_PAGE_SIZE as i32);
+        self.session
+            .query_iter(query, &[])
+            .await
+            .map_err(db_error)?
+            .into_typed::<ChangeRowTuple>()
+            .map_err(db_error)
+            .map_ok(change_from_row)
+            .try_filter(|change| future::ready(change.deleted && change.changed_at < before))
+            .try_collect()
+            .await
+    }
+
+    async fn delete(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.todo_changes WHERE todo_id = ?";
+        let rows = todo_ids.iter().map(|id| (id.0, (id.0,)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await
+    }
+}
+
+/// The event outbox. Entries are found by whether they were delivered,
+/// through a secondary index, as the relay only ever asks for one or the
+/// other.
+pub struct ScyllaOutboxRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn outbox_entry_from_row(row: OutboxRowTuple) -> OutboxEntry {
+    let (id, consumer, event, todo_id, payload, created_at, delivered_at) = row;
+    OutboxEntry {
+        id,
+        // Entries from before consumers were recorded were all for the sink.
+        consumer: consumer.unwrap_or_else(|| "sink".to_string()),
+        event,
+        todo_id: TodoId(todo_id),
+        payload,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        delivered_at: delivered_at.and_then(from_timestamp),
+    }
+}
+
+/// Values for `INSERT_OUTBOX`.
+fn outbox_values(
+    entry: &OutboxEntry,
+) -> (
+    &str,
+    &str,
+    &str,
+    Uuid,
+    &str,
+    bool,
+    CqlTimestamp,
+    Option<CqlTimestamp>,
+) {
+    (
+        &entry.id,
+        &entry.consumer,
+        &entry.event,
+        entry.todo_id.0,
+        &entry.payload,
+        entry.delivered_at.is_some(),
+        to_timestamp(Some(entry.created_at)),
+        entry.delivered_at.map(|at| to_timestamp(Some(at))),
+    )
+}
+
+impl ScyllaOutboxRepository {
+    async fn find_by_delivered(
+        &self,
+        delivered: bool,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        let query = format!("{} WHERE delivered = ?", SELECT_OUTBOX);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (delivered,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .map(|rows| {
+                rows.into_typed::<OutboxRowTuple>()
+                    .flatten()
+                    .map(outbox_entry_from_row)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for ScyllaOutboxRepository {
+    async fn insert(&self, entry: &OutboxEntry) -> Result<(), RepositoryError> {
+        self.session
+            .query(
+                write_query(INSERT_OUTBOX, &self.consistency),
+                outbox_values(entry),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn pending(
+        &self,
+        consumer: &str,
+        limit: usize,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        let mut entries = self.find_by_delivered(false).await?;
+        entries.retain(|entry| entry.consumer == consumer);
+        entries.sort_by_key(|entry| entry.created_at);
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    async fn mark_delivered(&self, id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let query = "UPDATE todo_db.outbox SET delivered = true, delivered_at = ? WHERE id = ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (to_timestamp(Some(at)), id),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn purge_delivered(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let ids: Vec<String> = self
+            .find_by_delivered(true)
+            .await?
+            .into_iter()
+            .filter(|entry| entry.delivered_at.is_some_and(|at| at < before))
+            .map(|entry| entry.id)
+            .collect();
+
+        let query = "DELETE FROM todo_db.outbox WHERE id = ?";
+        let rows = ids.iter().map(|id| (id.as_str(), (id.as_str(),)));
+        write_by_partition(&self.session, &self.consistency, query, rows).await?;
+
+        Ok(ids.len())
+    }
+}
+
+/// Refresh tokens keyed by their hash. Rows carry a TTL, so tokens and
+/// family revocations disappear once they no longer matter.
+pub struct ScyllaRefreshTokenRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl RefreshTokenRepository for ScyllaRefreshTokenRepository {
+    async fn insert(&self, token: &RefreshToken) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.refresh_tokens (token_hash, family_id, user_id, created_at, expires_at, used_at, amr) VALUES (?, ?, ?, ?, ?, ?, ?) USING TTL ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &token.token_hash,
+                    &token.family_id,
+                    &token.user_id,
+                    to_timestamp(Some(token.created_at)),
+                    to_timestamp(Some(token.expires_at)),
+                    token.used_at.map(|at| to_timestamp(Some(at))),
+                    token.amr.join(" "),
+                    ttl_for(Some(token.expires_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        let query = "SELECT token_hash, family_id, user_id, created_at, expires_at, used_at, amr FROM todo_db.refresh_tokens WHERE token_hash = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (token_hash,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<RefreshTokenRowTuple>().next())
+            .and_then(Result::ok)
+            .map(
+                |(token_hash, family_id, user_id, created_at, expires_at, used_at, amr)| {
+                    RefreshToken {
+                        token_hash,
+                        family_id,
+                        user_id,
+                        created_at: from_timestamp(created_at).unwrap_or_default(),
+                        expires_at: from_timestamp(expires_at).unwrap_or_default(),
+                        used_at: used_at.and_then(from_timestamp),
+                        amr: amr
+                            .unwrap_or_default()
+                            .split_whitespace()
+                            .map(String::from)
+                            .collect(),
+                    }
+                },
+            ))
+    }
+
+    /// A lightweight transaction, so of two requests racing to spend the
+    /// same token only one succeeds.
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let query =
+            "UPDATE todo_db.refresh_tokens SET used_at = ? WHERE token_hash = ? IF used_at = null";
+
+        let rows = self
+            .session
+            .query(
+                write_query(query, &self.consistency),
+                (to_timestamp(Some(at)), token_hash),
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(applied(rows))
+    }
+
+    async fn revoke_family(
+        &self,
+        family_id: &str,
+        until: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.revoked_token_families (family_id, revoked_at) VALUES (?, ?) USING TTL ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    family_id,
+                    to_timestamp(Some(Utc::now())),
+                    ttl_for(Some(until)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn is_revoked(&self, family_id: &str) -> Result<bool, RepositoryError> {
+        let query = "SELECT family_id FROM todo_db.revoked_token_families WHERE family_id = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (family_id,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows.is_some_and(|rows| !rows.is_empty()))
+    }
+}
+
+/// Failures and lockouts expire from their tables through TTLs, so only
+/// those that still count are kept.
+fn user_session_from_row(row: UserSessionRowTuple) -> UserSession {
+    let (id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at) = row;
+    UserSession {
+        id,
+        user_id,
+        user_agent,
+        ip_address,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+        last_seen_at: from_timestamp(last_seen_at).unwrap_or_default(),
+        expires_at: from_timestamp(expires_at).unwrap_or_default(),
+    }
+}
+
+/// Sessions are partitioned by user; each write renews the row's TTL to
+/// the session's expiry.
+pub struct ScyllaSessionRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl SessionRepository for ScyllaSessionRepository {
+    async fn save(&self, session: &UserSession) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.sessions (id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?) USING TTL ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &session.id,
+                    &session.user_id,
+                    &session.user_agent,
+                    &session.ip_address,
+                    to_timestamp(Some(session.created_at)),
+                    to_timestamp(Some(session.last_seen_at)),
+                    to_timestamp(Some(session.expires_at)),
+                    ttl_for(Some(session.expires_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, user_id: &str, id: &str) -> Result<Option<UserSession>, RepositoryError> {
+        let query = format!("{} WHERE user_id = ? AND id = ?", SELECT_SESSIONS);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (user_id, id))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<UserSessionRowTuple>().next())
+            .and_then(Result::ok)
+            .map(user_session_from_row))
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, RepositoryError> {
+        let query = format!("{} WHERE user_id = ?", SELECT_SESSIONS);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), (user_id,))
+            .await
+            .map_err(db_error)?
+            .rows
+            .unwrap_or_default();
+
+        let mut sessions: Vec<UserSession> = rows
+            .into_typed::<UserSessionRowTuple>()
+            .filter_map(Result::ok)
+            .map(user_session_from_row)
+            .filter(|session| session.expires_at > now)
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.sessions WHERE user_id = ? AND id = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (user_id, id))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct ScyllaLoginAttemptRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl LoginAttemptRepository for ScyllaLoginAttemptRepository {
+    async fn record_failure(
+        &self,
+        key: &str,
+        at: DateTime<Utc>,
+        since: DateTime<Utc>,
+    ) -> Result<usize, RepositoryError> {
+        let insert = "INSERT INTO todo_db.login_failures (lock_key, failed_at, attempt_id) VALUES (?, ?, ?) USING TTL ?";
+        self.session
+            .query(
+                write_query(insert, &self.consistency),
+                (
+                    key,
+                    to_timestamp(Some(at)),
+                    Uuid::new_v4(),
+                    ttl_for(Some(at + (at - since))),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        let count =
+            "SELECT COUNT(*) FROM todo_db.login_failures WHERE lock_key = ? AND failed_at > ?";
+        let rows = self
+            .session
+            .query(
+                read_query(count, &self.consistency),
+                (key, to_timestamp(Some(since))),
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<(i64,)>().next())
+            .and_then(Result::ok)
+            .map_or(0, |(count,)| count as usize))
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let query =
+            "INSERT INTO todo_db.login_lockouts (lock_key, locked_until) VALUES (?, ?) USING TTL ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (key, to_timestamp(Some(until)), ttl_for(Some(until))),
+            )
+            .await
+            .map_err(db_error)?;
+        self.session
+            .query(
+                write_query(
+                    "DELETE FROM todo_db.login_failures WHERE lock_key = ?",
+                    &self.consistency,
+                ),
+                (key,),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        let query = "SELECT locked_until FROM todo_db.login_lockouts WHERE lock_key = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (key,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<(CqlTimestamp,)>().next())
+            .and_then(Result::ok)
+            .and_then(|(locked_until,)| from_timestamp(locked_until)))
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), RepositoryError> {
+        for query in [
+            "DELETE FROM todo_db.login_failures WHERE lock_key = ?",
+            "DELETE FROM todo_db.login_lockouts WHERE lock_key = ?",
+        ] {
+            self.session
+                .query(write_query(query, &self.consistency), (key,))
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reset tokens expire from the table through their TTL.
+pub struct ScyllaPasswordResetRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+#[async_trait]
+impl PasswordResetRepository for ScyllaPasswordResetRepository {
+    async fn insert(&self, token: &PasswordResetToken) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.password_reset_tokens (token_hash, user_id, created_at, expires_at, used_at) VALUES (?, ?, ?, ?, ?) USING TTL ?";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    &token.token_hash,
+                    &token.user_id,
+                    to_timestamp(Some(token.created_at)),
+                    to_timestamp(Some(token.expires_at)),
+                    token.used_at.map(|at| to_timestamp(Some(at))),
+                    ttl_for(Some(token.expires_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        let query = "SELECT token_hash, user_id, created_at, expires_at, used_at FROM todo_db.password_reset_tokens WHERE token_hash = ?";
+
+        let rows = self
+            .session
+            .query(read_query(query, &self.consistency), (token_hash,))
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_typed::<PasswordResetRowTuple>().next())
+            .and_then(Result::ok)
+            .map(
+                |(token_hash, user_id, created_at, expires_at, used_at)| PasswordResetToken {
+                    token_hash,
+                    user_id,
+                    created_at: from_timestamp(created_at).unwrap_or_default(),
+                    expires_at: from_timestamp(expires_at).unwrap_or_default(),
+                    used_at: used_at.and_then(from_timestamp),
+                },
+            ))
+    }
+
+    /// A lightweight transaction, like spending a refresh token.
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let query = "UPDATE todo_db.password_reset_tokens SET used_at = ? WHERE token_hash = ? IF used_at = null";
+
+        let rows = self
+            .session
+            .query(
+                write_query(query, &self.consistency),
+                (to_timestamp(Some(at)), token_hash),
+            )
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        Ok(rows
+            .and_then(|rows| rows.into_iter().next())
+            .and_then(|row| row.columns.into_iter().next().flatten())
+            .is_some_and(|applied| applied == CqlValue::Boolean(true)))
+    }
+
+    /// Looks the tokens up through the `user_id` index and spends each
+    /// unused one like [`Self::mark_used`].
+    async fn mark_all_used(&self, user_id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        let query =
+            "SELECT token_hash, used_at FROM todo_db.password_reset_tokens WHERE user_id = ?";
+
+        let unused: Vec<String> = self
+            .session
+            .query(read_query(query, &self.consistency), (user_id,))
+            .await
+            .map_err(db_error)?
+            .rows
+            .map(|rows| {
+                rows.into_typed::<(String, Option<CqlTimestamp>)>()
+                    .flatten()
+                    .filter(|(_, used_at)| used_at.is_none())
+                    .map(|(token_hash, _)| token_hash)
+                    .collect()
+            })
+            .unwrap_or_default();
+        for token_hash in unused {
+            self.mark_used(&token_hash, at).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Attachment metadata, partitioned by todo like comments.
+pub struct ScyllaAttachmentRepository {
+    session: Arc<Session>,
+    consistency: ConsistencyConfig,
+}
+
+fn attachment_from_row(row: AttachmentRowTuple) -> Attachment {
+    let (id, todo_id, file_name, content_type, size, uploaded_by, created_at) = row;
+    Attachment {
+        id,
+        todo_id: TodoId(todo_id),
+        file_name,
+        content_type,
+        size: size.max(0) as u64,
+        uploaded_by,
+        created_at: from_timestamp(created_at).unwrap_or_default(),
+    }
+}
+
+impl ScyllaAttachmentRepository {
+    async fn query_attachments(
+        &self,
+        condition: &str,
+        values: impl scylla::serialize::row::SerializeRow + Send,
+    ) -> Result<Vec<Attachment>, RepositoryError> {
+        let query = format!("{} WHERE {}", SELECT_ATTACHMENTS, condition);
+
+        let rows = self
+            .session
+            .query(read_query(&query, &self.consistency), values)
+            .await
+            .map_err(db_error)?
+            .rows;
+
+        let mut attachments: Vec<Attachment> = rows
+            .map(|rows| {
+                rows.into_typed::<AttachmentRowTuple>()
+                    .flatten()
+                    .map(attachment_from_row)
+                    .collect()
+            })
+            .unwrap_or_default();
+        attachments.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(attachments)
+    }
+}
+
+#[async_trait]
+impl AttachmentRepository for ScyllaAttachmentRepository {
+    async fn insert(&self, attachment: &Attachment) -> Result<(), RepositoryError> {
+        let query = "INSERT INTO todo_db.attachments_v2 (todo_id, id, file_name, content_type, size, uploaded_by, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+        self.session
+            .query(
+                write_query(query, &self.consistency),
+                (
+                    attachment.todo_id.0,
+                    &attachment.id,
+                    &attachment.file_name,
+                    &attachment.content_type,
+                    attachment.size as i64,
+                    &attachment.uploaded_by,
+                    to_timestamp(Some(attachment.created_at)),
+                ),
+            )
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Attachment>, RepositoryError> {
+        Ok(self
+            .query_attachments("todo_id = ? AND id = ?", (todo_id.0, id))
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    async fn list(&self, todo_id: &TodoId) -> Result<Vec<Attachment>, RepositoryError> {
+        self.query_attachments("todo_id = ?", (todo_id.0,)).await
+    }
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        let query = "DELETE FROM todo_db.attachments_v2 WHERE todo_id = ? AND id = ?";
+
+        self.session
+            .query(write_query(query, &self.consistency), (todo_id.0, id))
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete_for_todos(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<Vec<Attachment>, RepositoryError> {
+        let query = "DELETE FROM todo_db.attachments_v2 WHERE todo_id = ?";
+
+        let mut removed = Vec::new();
+        for todo_id in todo_ids {
+            removed.extend(self.list(todo_id).await?);
+            self.session
+                .query(write_query(query, &self.consistency), (todo_id.0,))
+                .await
+                .map_err(db_error)?;
+        }
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,3285 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::prelude::*;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{
+    SqliteArgumentValue, SqliteConnectOptions, SqliteExecutor, SqlitePool, SqlitePoolOptions,
+    SqliteTypeInfo, SqliteValueRef,
+};
+use sqlx::{Decode, Encode, QueryBuilder, Sqlite, Type};
+
+use super::{
+    is_pending_recurrence, paged_stream, undo_todos_json, version_json, ActivityRepository,
+    AttachmentRepository, ChangeRepository, CommentRepository, FeatureFlagRepository,
+    JobRepository, LeaseRepository, ListOptions, LoginAttemptRepository, MaintenanceRepository,
+    NotificationSettingsRepository, OutboxRepository, PasswordResetRepository, ProjectRepository,
+    RefreshTokenRepository, RepositoryError, SchemaRepository, SessionRepository,
+    TemplateRepository, TodoAclRepository, TodoFilter, TodoPatch, TodoRepository, TodoScope,
+    TodoSort, TodoStream, TodoVisibility, TodoWrite, UndoRepository, UserRepository,
+    UserSettingsRepository, WebhookRepository, WorkspaceRepository,
+};
+use crate::config::SqliteConfig;
+use crate::migrations::{self, SchemaStatus};
+use crate::model::{
+    Activity, ActivityKind, Attachment, Comment, DeadLetter, JobRecord, Maintenance,
+    NotificationSettings, OutboxEntry, PasswordResetToken, Project, RefreshToken, Role, Template,
+    Todo, TodoChange, TodoId, TodoShare, TodoStatus, UndoEntry, User, UserIdentity, UserSession,
+    UserSettings, UserTotp, Webhook, Workspace, WorkspaceMember,
+};
+use crate::scheduling::Recurrence;
+use crate::titles;
+
+// Todo IDs are kept in text columns; these let them be bound and read
+// without converting by hand.
+impl Type<Sqlite> for TodoId {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for TodoId {
+    fn encode_by_ref(&self, args: &mut Vec<SqliteArgumentValue<'q>>) -> IsNull {
+        <String as Encode<Sqlite>>::encode(self.to_string(), args)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for TodoId {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Sqlite>>::decode(value)?.parse()?)
+    }
+}
+
+/// Rows per multi-row INSERT, keeping the bind parameters (21 per todo)
+/// well under the database's limit.
+const INSERT_CHUNK_SIZE: usize = 50;
+
+/// Reads from the todos that have not expired: the sweep deletes expired
+/// ones only periodically, so they are filtered out until then.
+const SELECT_TODOS: &str =
+    "SELECT id, title, content, completed, archived, due_at, recurrence, series_id, next_occurrence_id, remind_at, reminder_sent_at, owner_id, workspace_id, project_id, position, status, assignee_id, created_at, updated_at, expires_at FROM (SELECT * FROM todos WHERE expires_at IS NULL OR expires_at > strftime('%Y-%m-%dT%H:%M:%f', 'now')) AS todos";
+
+#[derive(sqlx::FromRow)]
+struct TodoRecord {
+    id: TodoId,
+    title: String,
+    content: String,
+    completed: bool,
+    archived: bool,
+    due_at: Option<DateTime<Utc>>,
+    recurrence: Option<String>,
+    series_id: Option<TodoId>,
+    next_occurrence_id: Option<TodoId>,
+    remind_at: Option<DateTime<Utc>>,
+    reminder_sent_at: Option<DateTime<Utc>>,
+    owner_id: Option<String>,
+    workspace_id: Option<String>,
+    project_id: Option<String>,
+    position: Option<i64>,
+    status: Option<String>,
+    assignee_id: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<TodoRecord> for Todo {
+    fn from(record: TodoRecord) -> Self {
+        Todo {
+            id: Some(record.id),
+            title: record.title,
+            content: record.content,
+            completed: Some(record.completed),
+            archived: Some(record.archived),
+            due_at: record.due_at,
+            recurrence: record
+                .recurrence
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            series_id: record.series_id,
+            next_occurrence_id: record.next_occurrence_id,
+            remind_at: record.remind_at,
+            reminder_sent_at: record.reminder_sent_at,
+            owner_id: record.owner_id,
+            workspace_id: record.workspace_id,
+            project_id: record.project_id,
+            position: record.position,
+            status: record.status.and_then(|status| status.parse().ok()),
+            assignee_id: record.assignee_id,
+            created_at: Some(record.created_at),
+            updated_at: Some(record.updated_at),
+            expires_at: record.expires_at,
+            comment_count: None,
+            ttl_seconds: None,
+            content_html: None,
+        }
+    }
+}
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+pub struct SqliteTodoRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteTodoRepository {
+    /// Opens the SQLite database, creating the file if needed, and applies any
+    /// pending migrations from `migrations/sqlite`.
+    pub async fn connect(config: &SqliteConfig) -> Result<Self, RepositoryError> {
+        let repository = Self::open(config).await?;
+        repository.migrate().await?;
+        Ok(repository)
+    }
+
+    /// Opens the SQLite database, creating the file if needed, leaving the
+    /// schema as it is.
+    pub async fn open(config: &SqliteConfig) -> Result<Self, RepositoryError> {
+        let options = SqliteConnectOptions::from_str(&config.url)
+            .map_err(db_error)?
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(options)
+            .await
+            .map_err(db_error)?;
+
+        Ok(SqliteTodoRepository { pool })
+    }
+
+    /// Applies any pending migrations from `migrations/sqlite`.
+    pub async fn migrate(&self) -> Result<(), RepositoryError> {
+        MIGRATOR.run(&self.pool).await.map_err(db_error)
+    }
+
+    /// Compares the migrations recorded in `_sqlx_migrations` with those in
+    /// `migrations/sqlite`, without applying any.
+    pub async fn schema_status(&self) -> Result<SchemaStatus, RepositoryError> {
+        self.schema().status().await
+    }
+
+    /// A workspace repository sharing this repository's connection pool.
+    pub fn workspaces(&self) -> SqliteWorkspaceRepository {
+        SqliteWorkspaceRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// An attachment repository sharing this repository's connection pool.
+    pub fn attachments(&self) -> SqliteAttachmentRepository {
+        SqliteAttachmentRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A template repository sharing this repository's connection pool.
+    pub fn templates(&self) -> SqliteTemplateRepository {
+        SqliteTemplateRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A project repository sharing this repository's connection pool.
+    pub fn projects(&self) -> SqliteProjectRepository {
+        SqliteProjectRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A comment repository sharing this repository's connection pool.
+    pub fn comments(&self) -> SqliteCommentRepository {
+        SqliteCommentRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// An activity log sharing this repository's connection pool.
+    pub fn activity(&self) -> SqliteActivityRepository {
+        SqliteActivityRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// An undo log sharing this repository's connection pool.
+    pub fn undo(&self) -> SqliteUndoRepository {
+        SqliteUndoRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A change log sharing this repository's connection pool.
+    pub fn changes(&self) -> SqliteChangeRepository {
+        SqliteChangeRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// An event outbox sharing this repository's connection pool.
+    pub fn outbox(&self) -> SqliteOutboxRepository {
+        SqliteOutboxRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A refresh token store sharing this repository's connection pool.
+    pub fn refresh_tokens(&self) -> SqliteRefreshTokenRepository {
+        SqliteRefreshTokenRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A session store sharing this repository's connection pool.
+    pub fn sessions(&self) -> SqliteSessionRepository {
+        SqliteSessionRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A failed login store sharing this repository's connection pool.
+    pub fn login_attempts(&self) -> SqliteLoginAttemptRepository {
+        SqliteLoginAttemptRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A password reset token store sharing this repository's connection pool.
+    pub fn password_resets(&self) -> SqlitePasswordResetRepository {
+        SqlitePasswordResetRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A todo ACL repository sharing this repository's connection pool.
+    pub fn acl(&self) -> SqliteTodoAclRepository {
+        SqliteTodoAclRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A user repository sharing this repository's connection pool.
+    pub fn users(&self) -> SqliteUserRepository {
+        SqliteUserRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A job repository sharing this repository's connection pool.
+    pub fn jobs(&self) -> SqliteJobRepository {
+        SqliteJobRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A schema repository sharing this repository's connection pool.
+    pub fn schema(&self) -> SqliteSchemaRepository {
+        SqliteSchemaRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A lease repository sharing this repository's connection pool.
+    pub fn leases(&self) -> SqliteLeaseRepository {
+        SqliteLeaseRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A maintenance repository sharing this repository's connection pool.
+    pub fn maintenance(&self) -> SqliteMaintenanceRepository {
+        SqliteMaintenanceRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A feature flag repository sharing this repository's connection pool.
+    pub fn feature_flags(&self) -> SqliteFeatureFlagRepository {
+        SqliteFeatureFlagRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A notification settings repository sharing this repository's connection pool.
+    pub fn notification_settings(&self) -> SqliteNotificationSettingsRepository {
+        SqliteNotificationSettingsRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A user settings repository sharing this repository's connection pool.
+    pub fn user_settings(&self) -> SqliteUserSettingsRepository {
+        SqliteUserSettingsRepository {
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A webhook repository sharing this repository's connection pool.
+    pub fn webhooks(&self) -> SqliteWebhookRepository {
+        SqliteWebhookRepository {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+fn recurrence_json(recurrence: Option<&Recurrence>) -> Option<String> {
+    recurrence.and_then(|recurrence| serde_json::to_string(recurrence).ok())
+}
+
+/// Appends the `WHERE` conditions that restrict a todo query to `scope`.
+fn push_scope(query: &mut QueryBuilder<'_, Sqlite>, scope: &TodoScope) {
+    match scope {
+        TodoScope::All => {}
+        TodoScope::Personal => {
+            query.push(" AND workspace_id IS NULL");
+        }
+        TodoScope::Workspace(workspace_id) => {
+            query
+                .push(" AND workspace_id = ")
+                .push_bind(workspace_id.clone());
+        }
+    }
+}
+
+/// Appends the `WHERE` condition for the todos `visibility` lets through,
+/// mirroring [`TodoVisibility::allows`].
+fn push_visibility(query: &mut QueryBuilder<'_, Sqlite>, visibility: &TodoVisibility) {
+    let TodoVisibility::Caller { user_id, shared } = visibility else {
+        return;
+    };
+    query.push(" AND (workspace_id IS NOT NULL OR owner_id IS NULL");
+    if let Some(user_id) = user_id {
+        query.push(" OR owner_id = ").push_bind(user_id.clone());
+    }
+    if !shared.is_empty() {
+        query.push(" OR id IN (");
+        let mut ids = query.separated(", ");
+        for id in shared {
+            ids.push_bind(*id);
+        }
+        ids.push_unseparated(")");
+    }
+    query.push(")");
+}
+
+/// Appends the `WHERE` conditions for todos with `status`, mirroring
+/// [`Todo::status`]: `completed` wins over the stored status.
+fn push_status(query: &mut QueryBuilder<'_, Sqlite>, status: TodoStatus) {
+    match status {
+        TodoStatus::Done => {
+            query.push(" AND completed = TRUE");
+        }
+        TodoStatus::Backlog => {
+            query.push(
+                " AND completed = FALSE AND (status IS NULL OR status IN ('backlog', 'done'))",
+            );
+        }
+        TodoStatus::InProgress | TodoStatus::Blocked => {
+            query
+                .push(" AND completed = FALSE AND status = ")
+                .push_bind(status.as_str());
+        }
+    }
+}
+
+fn db_error(e: impl std::fmt::Display) -> RepositoryError {
+    RepositoryError::Database(e.to_string())
+}
+
+async fn insert_todo(
+    executor: impl SqliteExecutor<'_>,
+    todo: &Todo,
+) -> Result<(), RepositoryError> {
+    sqlx::query(
+        "INSERT INTO todos (id, title, content, completed, archived, created_at, updated_at, due_at, recurrence, series_id, next_occurrence_id, remind_at, reminder_sent_at, owner_id, workspace_id, project_id, position, status, assignee_id, expires_at, title_key) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21)",
+    )
+    .bind(todo.id)
+    .bind(&todo.title)
+    .bind(&todo.content)
+    .bind(todo.completed.unwrap_or(false))
+    .bind(todo.archived.unwrap_or(false))
+    .bind(todo.created_at.unwrap_or_else(Utc::now))
+    .bind(todo.updated_at.unwrap_or_else(Utc::now))
+    .bind(todo.due_at)
+    .bind(recurrence_json(todo.recurrence.as_ref()))
+    .bind(todo.series_id)
+    .bind(todo.next_occurrence_id)
+    .bind(todo.remind_at)
+    .bind(todo.reminder_sent_at)
+    .bind(&todo.owner_id)
+    .bind(&todo.workspace_id)
+    .bind(&todo.project_id)
+    .bind(todo.position)
+    .bind(todo.status.map(|status| status.as_str()))
+    .bind(&todo.assignee_id)
+    .bind(todo.expires_at)
+    .bind(titles::key(&todo.title))
+    .execute(executor)
+    .await
+    .map_err(db_error)?;
+
+    Ok(())
+}
+
+async fn update_todo(
+    executor: impl SqliteExecutor<'_>,
+    todo: &Todo,
+) -> Result<(), RepositoryError> {
+    sqlx::query(
+        "UPDATE todos SET title = $1, content = $2, completed = $3, updated_at = $4, archived = $5, due_at = $6, recurrence = $7, series_id = $8, next_occurrence_id = $9, remind_at = $10, reminder_sent_at = $11, project_id = $12, position = $13, status = $14, assignee_id = $15, title_key = $16 WHERE id = $17",
+    )
+    .bind(&todo.title)
+    .bind(&todo.content)
+    .bind(todo.completed.unwrap_or(false))
+    .bind(todo.updated_at.unwrap_or_else(Utc::now))
+    .bind(todo.archived.unwrap_or(false))
+    .bind(todo.due_at)
+    .bind(recurrence_json(todo.recurrence.as_ref()))
+    .bind(todo.series_id)
+    .bind(todo.next_occurrence_id)
+    .bind(todo.remind_at)
+    .bind(todo.reminder_sent_at)
+    .bind(&todo.project_id)
+    .bind(todo.position)
+    .bind(todo.status.map(|status| status.as_str()))
+    .bind(&todo.assignee_id)
+    .bind(titles::key(&todo.title))
+    .bind(todo.id)
+    .execute(executor)
+    .await
+    .map_err(db_error)?;
+
+    Ok(())
+}
+
+async fn patch_todo(
+    executor: impl SqliteExecutor<'_>,
+    id: &TodoId,
+    patch: &TodoPatch,
+) -> Result<(), RepositoryError> {
+    let mut query = QueryBuilder::<Sqlite>::new("UPDATE todos SET updated_at = ");
+    query.push_bind(patch.updated_at);
+    if let Some(title) = &patch.title {
+        query.push(", title = ").push_bind(title.clone());
+        query.push(", title_key = ").push_bind(titles::key(title));
+    }
+    if let Some(content) = &patch.content {
+        query.push(", content = ").push_bind(content.clone());
+    }
+    if let Some(completed) = patch.completed {
+        query.push(", completed = ").push_bind(completed);
+    }
+    if let Some(due_at) = patch.due_at {
+        query.push(", due_at = ").push_bind(due_at);
+    }
+    if let Some(recurrence) = &patch.recurrence {
+        query
+            .push(", recurrence = ")
+            .push_bind(recurrence_json(Some(recurrence)));
+    }
+    if let Some(series_id) = patch.series_id {
+        query.push(", series_id = ").push_bind(series_id);
+    }
+    if let Some(remind_at) = patch.remind_at {
+        query.push(", remind_at = ").push_bind(remind_at);
+    }
+    if patch.rearm_reminder {
+        query.push(", reminder_sent_at = NULL");
+    }
+    if let Some(project_id) = &patch.project_id {
+        query.push(", project_id = ").push_bind(project_id.clone());
+    }
+    if let Some(position) = patch.position {
+        query.push(", position = ").push_bind(position);
+    }
+    if let Some(status) = patch.status {
+        query.push(", status = ").push_bind(status.as_str());
+    }
+    if let Some(assignee_id) = &patch.assignee_id {
+        query
+            .push(", assignee_id = ")
+            .push_bind(assignee_id.clone());
+    }
+    query.push(" WHERE id = ").push_bind(*id);
+
+    query.build().execute(executor).await.map_err(db_error)?;
+
+    Ok(())
+}
+
+async fn delete_todo(
+    executor: impl SqliteExecutor<'_>,
+    id: &TodoId,
+) -> Result<(), RepositoryError> {
+    sqlx::query("DELETE FROM todos WHERE id = $1")
+        .bind(id)
+        .execute(executor)
+        .await
+        .map_err(db_error)?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl TodoRepository for SqliteTodoRepository {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        let mut query = QueryBuilder::<Sqlite>::new(SELECT_TODOS);
+        query
+            .push(" WHERE (archived = FALSE OR ")
+            .push_bind(options.include_archived)
+            .push(")");
+        push_scope(&mut query, &options.scope);
+        push_visibility(&mut query, &options.visibility);
+        if let Some(owner_id) = &options.owner_id {
+            query.push(" AND owner_id = ").push_bind(owner_id.clone());
+        }
+        if let Some(created_after) = options.created_after {
+            query.push(" AND created_at >= ").push_bind(created_after);
+        }
+        if let Some(created_before) = options.created_before {
+            query.push(" AND created_at < ").push_bind(created_before);
+        }
+        if let Some(project_id) = &options.project_id {
+            query
+                .push(" AND project_id = ")
+                .push_bind(project_id.clone());
+        }
+        if let Some(status) = options.status {
+            push_status(&mut query, status);
+        }
+        if let Some(assignee_id) = &options.assignee_id {
+            query
+                .push(" AND assignee_id = ")
+                .push_bind(assignee_id.clone());
+        }
+        if let Some(completed) = options.completed {
+            query.push(" AND completed = ").push_bind(completed);
+        }
+        if let Some(due_after) = options.due_after {
+            query.push(" AND due_at >= ").push_bind(due_after);
+        }
+        if let Some(due_before) = options.due_before {
+            query.push(" AND due_at < ").push_bind(due_before);
+        }
+        query
+            .push(match options.sort {
+                TodoSort::CreatedAt => " ORDER BY created_at, id",
+                // SQLite sorts NULLs first, so order on whether there is one.
+                TodoSort::Position => " ORDER BY position IS NULL, position, created_at, id",
+            })
+            .push(" LIMIT ")
+            .push_bind(options.limit as i64)
+            .push(" OFFSET ")
+            .push_bind(options.offset as i64);
+
+        let records = query
+            .build_query_as::<TodoRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Todo::from).collect())
+    }
+
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        let repository = SqliteTodoRepository {
+            pool: self.pool.clone(),
+        };
+        Ok(paged_stream(repository, options.clone()))
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        let query = format!("{} WHERE id = $1", SELECT_TODOS);
+        let record = sqlx::query_as::<_, TodoRecord>(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Todo::from))
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM todos WHERE title_key = $1 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%dT%H:%M:%f', 'now')))",
+        )
+            .bind(titles::key(title))
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        let query = format!("{} WHERE title = $1 ORDER BY created_at, id", SELECT_TODOS);
+        let records = sqlx::query_as::<_, TodoRecord>(&query)
+            .bind(title)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Todo::from).collect())
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        insert_todo(&self.pool, todo).await
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for chunk in todos.chunks(INSERT_CHUNK_SIZE) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO todos (id, title, content, completed, archived, created_at, updated_at, due_at, recurrence, series_id, next_occurrence_id, remind_at, reminder_sent_at, owner_id, workspace_id, project_id, position, status, assignee_id, expires_at, title_key) ",
+            );
+            query.push_values(chunk, |mut row, todo| {
+                row.push_bind(todo.id)
+                    .push_bind(&todo.title)
+                    .push_bind(&todo.content)
+                    .push_bind(todo.completed.unwrap_or(false))
+                    .push_bind(todo.archived.unwrap_or(false))
+                    .push_bind(todo.created_at.unwrap_or_else(Utc::now))
+                    .push_bind(todo.updated_at.unwrap_or_else(Utc::now))
+                    .push_bind(todo.due_at)
+                    .push_bind(recurrence_json(todo.recurrence.as_ref()))
+                    .push_bind(todo.series_id)
+                    .push_bind(todo.next_occurrence_id)
+                    .push_bind(todo.remind_at)
+                    .push_bind(todo.reminder_sent_at)
+                    .push_bind(&todo.owner_id)
+                    .push_bind(&todo.workspace_id)
+                    .push_bind(&todo.project_id)
+                    .push_bind(todo.position)
+                    .push_bind(todo.status.map(|status| status.as_str()))
+                    .push_bind(&todo.assignee_id)
+                    .push_bind(todo.expires_at)
+                    .push_bind(titles::key(&todo.title));
+            });
+            query.build().execute(&mut *tx).await.map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        update_todo(&self.pool, todo).await
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        patch_todo(&self.pool, id, patch).await
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        delete_todo(&self.pool, id).await
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        match write {
+            TodoWrite::Insert(todo) => insert_todo(&mut *tx, todo).await?,
+            TodoWrite::Update(todo) => update_todo(&mut *tx, todo).await?,
+            TodoWrite::Patch(id, patch) => patch_todo(&mut *tx, id, patch).await?,
+            TodoWrite::Delete(id) => delete_todo(&mut *tx, id).await?,
+        }
+        for entry in entries {
+            insert_outbox_entry(&mut *tx, entry).await?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut updated = Vec::new();
+        for id in ids {
+            let result =
+                sqlx::query("UPDATE todos SET completed = $1, updated_at = $2 WHERE id = $3")
+                    .bind(completed)
+                    .bind(updated_at)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_error)?;
+            if result.rows_affected() > 0 {
+                updated.push(*id);
+            }
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(updated)
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT id FROM todos WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        query.push(")");
+        push_scope(&mut query, scope);
+
+        let found: Vec<TodoId> = query
+            .build_query_scalar()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let mut existing: Vec<TodoId> = Vec::new();
+        for id in ids {
+            if found.contains(id) && !existing.contains(id) {
+                existing.push(*id);
+            }
+        }
+        Ok(existing)
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT id FROM todos WHERE 1 = 1");
+        push_scope(&mut query, &filter.scope);
+        push_visibility(&mut query, &filter.visibility);
+        if let Some(completed) = filter.completed {
+            query.push(" AND completed = ").push_bind(completed);
+        }
+        if let Some(created_before) = filter.created_before {
+            query.push(" AND created_at < ").push_bind(created_before);
+        }
+        query.push(" ORDER BY created_at, id");
+
+        query
+            .build_query_scalar()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for id in ids {
+            sqlx::query("DELETE FROM todos WHERE id = $1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        let query = format!(
+            "{} WHERE series_id = $1 ORDER BY due_at, created_at",
+            SELECT_TODOS
+        );
+        let records = sqlx::query_as::<_, TodoRecord>(&query)
+            .bind(series_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Todo::from).collect())
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        let query = format!(
+            "{} WHERE completed = TRUE AND recurrence IS NOT NULL AND next_occurrence_id IS NULL",
+            SELECT_TODOS
+        );
+        let records = sqlx::query_as::<_, TodoRecord>(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records
+            .into_iter()
+            .map(Todo::from)
+            .filter(is_pending_recurrence)
+            .collect())
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "{} WHERE remind_at IS NOT NULL AND reminder_sent_at IS NULL",
+            SELECT_TODOS
+        ));
+        if let Some(due_before) = due_before {
+            query.push(" AND remind_at <= ").push_bind(due_before);
+        }
+        query.push(" ORDER BY remind_at, id");
+
+        let records = query
+            .build_query_as::<TodoRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Todo::from).collect())
+    }
+
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        sqlx::query_scalar("DELETE FROM todos WHERE expires_at <= $1 RETURNING id")
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM todos WHERE owner_id IS $1 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%dT%H:%M:%f', 'now'))",
+        )
+        .bind(owner_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(count as u64)
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        let rows: Vec<(Option<String>, i64)> = sqlx::query_as(
+            "SELECT owner_id, COUNT(*) FROM todos WHERE expires_at IS NULL OR expires_at > strftime('%Y-%m-%dT%H:%M:%f', 'now') GROUP BY owner_id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(owner_id, count)| (owner_id, count as u64))
+            .collect())
+    }
+
+    /// Counts are computed by the queries above, so there is nothing to repair.
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        Ok(0)
+    }
+
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        // The migration adding title keys could only approximate them in
+        // SQL, so any that differ from this build's are rewritten.
+        let stored: Vec<(TodoId, String, Option<String>)> =
+            sqlx::query_as("SELECT id, title, title_key FROM todos")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut corrected = 0;
+        for (id, title, stored_key) in stored {
+            let key = titles::key(&title);
+            if stored_key.as_deref() != Some(key.as_str()) {
+                sqlx::query("UPDATE todos SET title_key = $1 WHERE id = $2")
+                    .bind(key)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_error)?;
+                corrected += 1;
+            }
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        sqlx::query("REINDEX todos")
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(corrected)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WebhookRecord {
+    id: String,
+    url: String,
+    secret: String,
+    events: String,
+    active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<WebhookRecord> for Webhook {
+    fn from(record: WebhookRecord) -> Self {
+        Webhook {
+            id: record.id,
+            url: record.url,
+            secret: record.secret,
+            events: serde_json::from_str(&record.events).unwrap_or_default(),
+            active: record.active,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct DeadLetterRecord {
+    id: String,
+    webhook_id: String,
+    event: String,
+    payload: String,
+    attempts: i32,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+impl TryFrom<DeadLetterRecord> for DeadLetter {
+    type Error = RepositoryError;
+
+    fn try_from(record: DeadLetterRecord) -> Result<Self, Self::Error> {
+        Ok(DeadLetter {
+            id: record.id,
+            webhook_id: record.webhook_id,
+            event: record.event.parse().map_err(db_error)?,
+            payload: serde_json::from_str(&record.payload).map_err(db_error)?,
+            attempts: record.attempts as u32,
+            last_error: record.last_error,
+            failed_at: record.failed_at,
+        })
+    }
+}
+
+const SELECT_WEBHOOKS: &str =
+    "SELECT id, url, secret, events, active, created_at, updated_at FROM webhooks";
+
+pub struct SqliteWebhookRepository {
+    pool: SqlitePool,
+}
+
+fn events_json(webhook: &Webhook) -> String {
+    serde_json::to_string(&webhook.events).unwrap_or_else(|_| "[]".to_string())
+}
+
+#[async_trait]
+impl WebhookRepository for SqliteWebhookRepository {
+    async fn list(&self) -> Result<Vec<Webhook>, RepositoryError> {
+        let query = format!("{} ORDER BY created_at, id", SELECT_WEBHOOKS);
+        let records = sqlx::query_as::<_, WebhookRecord>(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Webhook::from).collect())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Webhook>, RepositoryError> {
+        let query = format!("{} WHERE id = $1", SELECT_WEBHOOKS);
+        let record = sqlx::query_as::<_, WebhookRecord>(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Webhook::from))
+    }
+
+    async fn insert(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO webhooks (id, url, secret, events, active, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&webhook.id)
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(events_json(webhook))
+        .bind(webhook.active)
+        .bind(webhook.created_at)
+        .bind(webhook.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, webhook: &Webhook) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE webhooks SET url = $1, secret = $2, events = $3, active = $4, updated_at = $5 WHERE id = $6",
+        )
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(events_json(webhook))
+        .bind(webhook.active)
+        .bind(webhook.updated_at)
+        .bind(&webhook.id)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn add_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO webhook_dead_letters (id, webhook_id, event, payload, attempts, last_error, failed_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&dead_letter.id)
+        .bind(&dead_letter.webhook_id)
+        .bind(dead_letter.event.as_str())
+        .bind(dead_letter.payload.to_string())
+        .bind(dead_letter.attempts as i32)
+        .bind(&dead_letter.last_error)
+        .bind(dead_letter.failed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list_dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter>, RepositoryError> {
+        let records = sqlx::query_as::<_, DeadLetterRecord>(
+            "SELECT id, webhook_id, event, payload, attempts, last_error, failed_at FROM webhook_dead_letters ORDER BY failed_at DESC LIMIT $1",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        records.into_iter().map(DeadLetter::try_from).collect()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct NotificationSettingsRecord {
+    email: String,
+    reminders: bool,
+    digest: bool,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<NotificationSettingsRecord> for NotificationSettings {
+    fn from(record: NotificationSettingsRecord) -> Self {
+        NotificationSettings {
+            email: record.email,
+            reminders: record.reminders,
+            digest: record.digest,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+const SELECT_NOTIFICATION_SETTINGS: &str =
+    "SELECT email, reminders, digest, updated_at FROM notification_settings";
+
+pub struct SqliteNotificationSettingsRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl NotificationSettingsRepository for SqliteNotificationSettingsRepository {
+    async fn list(&self) -> Result<Vec<NotificationSettings>, RepositoryError> {
+        let query = format!("{} ORDER BY email", SELECT_NOTIFICATION_SETTINGS);
+        let records = sqlx::query_as::<_, NotificationSettingsRecord>(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records
+            .into_iter()
+            .map(NotificationSettings::from)
+            .collect())
+    }
+
+    async fn find(&self, email: &str) -> Result<Option<NotificationSettings>, RepositoryError> {
+        let query = format!("{} WHERE email = $1", SELECT_NOTIFICATION_SETTINGS);
+        let record = sqlx::query_as::<_, NotificationSettingsRecord>(&query)
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(NotificationSettings::from))
+    }
+
+    async fn upsert(&self, settings: &NotificationSettings) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO notification_settings (email, reminders, digest, updated_at) VALUES ($1, $2, $3, $4) ON CONFLICT (email) DO UPDATE SET reminders = excluded.reminders, digest = excluded.digest, updated_at = excluded.updated_at",
+        )
+        .bind(&settings.email)
+        .bind(settings.reminders)
+        .bind(settings.digest)
+        .bind(settings.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, email: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM notification_settings WHERE email = $1")
+            .bind(email)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UserSettingsRecord {
+    user_id: String,
+    timezone: String,
+    page_size: Option<i32>,
+    default_sort: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<UserSettingsRecord> for UserSettings {
+    type Error = RepositoryError;
+
+    fn try_from(record: UserSettingsRecord) -> Result<Self, Self::Error> {
+        Ok(UserSettings {
+            user_id: record.user_id,
+            timezone: record.timezone.parse().map_err(db_error)?,
+            page_size: record.page_size.map(|size| size as usize),
+            default_sort: record
+                .default_sort
+                .map(|sort| sort.parse())
+                .transpose()
+                .map_err(db_error)?,
+            updated_at: Some(record.updated_at),
+        })
+    }
+}
+
+pub struct SqliteUserSettingsRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl UserSettingsRepository for SqliteUserSettingsRepository {
+    async fn find(&self, user_id: &str) -> Result<Option<UserSettings>, RepositoryError> {
+        let record = sqlx::query_as::<_, UserSettingsRecord>(
+            "SELECT user_id, timezone, page_size, default_sort, updated_at FROM user_settings WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        record.map(UserSettings::try_from).transpose()
+    }
+
+    async fn upsert(&self, settings: &UserSettings) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO user_settings (user_id, timezone, page_size, default_sort, updated_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (user_id) DO UPDATE SET timezone = excluded.timezone, page_size = excluded.page_size, default_sort = excluded.default_sort, updated_at = excluded.updated_at",
+        )
+        .bind(&settings.user_id)
+        .bind(settings.timezone.name())
+        .bind(settings.page_size.map(|size| size as i32))
+        .bind(settings.default_sort.map(|sort| sort.as_str()))
+        .bind(settings.updated_at.unwrap_or_else(Utc::now))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM user_settings WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct JobRow {
+    id: String,
+    kind: String,
+    payload: String,
+    status: String,
+    attempts: i32,
+    max_attempts: i32,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<JobRow> for JobRecord {
+    type Error = RepositoryError;
+
+    fn try_from(row: JobRow) -> Result<Self, Self::Error> {
+        Ok(JobRecord {
+            id: row.id,
+            kind: row.kind,
+            payload: serde_json::from_str(&row.payload).map_err(db_error)?,
+            status: row.status.parse().map_err(db_error)?,
+            attempts: row.attempts as u32,
+            max_attempts: row.max_attempts as u32,
+            last_error: row.last_error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+const SELECT_JOBS: &str = "SELECT id, kind, payload, status, attempts, max_attempts, last_error, created_at, updated_at FROM jobs";
+
+pub struct SqliteJobRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl JobRepository for SqliteJobRepository {
+    async fn insert(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO jobs (id, kind, payload, status, attempts, max_attempts, last_error, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(&job.id)
+        .bind(&job.kind)
+        .bind(job.payload.to_string())
+        .bind(job.status.as_str())
+        .bind(job.attempts as i32)
+        .bind(job.max_attempts as i32)
+        .bind(&job.last_error)
+        .bind(job.created_at)
+        .bind(job.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, job: &JobRecord) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE jobs SET status = $1, attempts = $2, last_error = $3, updated_at = $4 WHERE id = $5",
+        )
+        .bind(job.status.as_str())
+        .bind(job.attempts as i32)
+        .bind(&job.last_error)
+        .bind(job.updated_at)
+        .bind(&job.id)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<JobRecord>, RepositoryError> {
+        let query = format!("{} WHERE id = $1", SELECT_JOBS);
+        let row = sqlx::query_as::<_, JobRow>(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.map(JobRecord::try_from).transpose()
+    }
+
+    async fn list_unfinished(&self) -> Result<Vec<JobRecord>, RepositoryError> {
+        let query = format!(
+            "{} WHERE status IN ('queued', 'running') ORDER BY created_at",
+            SELECT_JOBS
+        );
+        let rows = sqlx::query_as::<_, JobRow>(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        rows.into_iter().map(JobRecord::try_from).collect()
+    }
+
+    async fn purge_finished(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let result = sqlx::query(
+            "DELETE FROM jobs WHERE status IN ('succeeded', 'failed') AND updated_at < $1",
+        )
+        .bind(before)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected() as usize)
+    }
+}
+
+pub struct SqliteSchemaRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl SchemaRepository for SqliteSchemaRepository {
+    async fn status(&self) -> Result<SchemaStatus, RepositoryError> {
+        let tracked: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+        let applied: Vec<(i64, Vec<u8>)> = match tracked {
+            true => sqlx::query_as("SELECT version, checksum FROM _sqlx_migrations WHERE success")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(db_error)?,
+            false => Vec::new(),
+        };
+
+        Ok(migrations::compare(&MIGRATOR, &applied))
+    }
+}
+
+/// The key of the single row holding the maintenance under way.
+const CURRENT_MAINTENANCE: &str = "current";
+
+#[derive(sqlx::FromRow)]
+struct MaintenanceRecord {
+    message: String,
+    retry_after_secs: i32,
+    allow_reads: bool,
+    started_by: String,
+    started_at: DateTime<Utc>,
+}
+
+impl From<MaintenanceRecord> for Maintenance {
+    fn from(record: MaintenanceRecord) -> Self {
+        Maintenance {
+            message: record.message,
+            retry_after_secs: record.retry_after_secs.max(0) as u32,
+            allow_reads: record.allow_reads,
+            started_by: record.started_by,
+            started_at: record.started_at,
+        }
+    }
+}
+
+pub struct SqliteMaintenanceRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl MaintenanceRepository for SqliteMaintenanceRepository {
+    async fn current(&self) -> Result<Option<Maintenance>, RepositoryError> {
+        let record = sqlx::query_as::<_, MaintenanceRecord>(
+            "SELECT message, retry_after_secs, allow_reads, started_by, started_at FROM maintenance WHERE id = $1",
+        )
+        .bind(CURRENT_MAINTENANCE)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(record.map(Maintenance::from))
+    }
+
+    async fn start(&self, maintenance: &Maintenance) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO maintenance (id, message, retry_after_secs, allow_reads, started_by, started_at) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (id) DO UPDATE SET message = excluded.message, retry_after_secs = excluded.retry_after_secs, allow_reads = excluded.allow_reads, started_by = excluded.started_by, started_at = excluded.started_at",
+        )
+        .bind(CURRENT_MAINTENANCE)
+        .bind(&maintenance.message)
+        .bind(i32::try_from(maintenance.retry_after_secs).unwrap_or(i32::MAX))
+        .bind(maintenance.allow_reads)
+        .bind(&maintenance.started_by)
+        .bind(maintenance.started_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn end(&self) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM maintenance WHERE id = $1")
+            .bind(CURRENT_MAINTENANCE)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct SqliteFeatureFlagRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl FeatureFlagRepository for SqliteFeatureFlagRepository {
+    async fn list(&self) -> Result<HashMap<String, bool>, RepositoryError> {
+        let rows = sqlx::query_as::<_, (String, bool)>("SELECT name, enabled FROM feature_flags")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn set(&self, name: &str, enabled: bool) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO feature_flags (name, enabled) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET enabled = excluded.enabled",
+        )
+        .bind(name)
+        .bind(enabled)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, name: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM feature_flags WHERE name = $1")
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct SqliteLeaseRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl LeaseRepository for SqliteLeaseRepository {
+    /// One upsert, so of two instances racing for a free lease only one
+    /// gets it.
+    async fn acquire(
+        &self,
+        name: &str,
+        holder: &str,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "INSERT INTO leases (name, holder, expires_at) VALUES ($1, $2, $3) ON CONFLICT (name) DO UPDATE SET holder = excluded.holder, expires_at = excluded.expires_at WHERE leases.holder = excluded.holder OR leases.expires_at <= $4",
+        )
+        .bind(name)
+        .bind(holder)
+        .bind(until)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn release(&self, name: &str, holder: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM leases WHERE name = $1 AND holder = $2")
+            .bind(name)
+            .bind(holder)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UserRow {
+    id: String,
+    email: String,
+    password_hash: String,
+    role: String,
+    display_name: Option<String>,
+    avatar_url: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = RepositoryError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: row.id,
+            email: row.email,
+            password_hash: row.password_hash,
+            role: row.role.parse().map_err(db_error)?,
+            display_name: row.display_name,
+            avatar_url: row.avatar_url,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+const SELECT_USERS: &str = "SELECT id, email, password_hash, role, display_name, avatar_url, created_at, updated_at FROM users";
+
+pub struct SqliteUserRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteUserRepository {
+    async fn find_one(&self, column: &str, value: &str) -> Result<Option<User>, RepositoryError> {
+        let query = format!("{} WHERE {} = $1", SELECT_USERS, column);
+        let row = sqlx::query_as::<_, UserRow>(&query)
+            .bind(value)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.map(User::try_from).transpose()
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteUserRepository {
+    async fn insert(&self, user: &User) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO users (id, email, password_hash, role, display_name, avatar_url, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(&user.id)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(user.role.as_str())
+        .bind(&user.display_name)
+        .bind(&user.avatar_url)
+        .bind(user.created_at)
+        .bind(user.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        self.find_one("email", email).await
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError> {
+        self.find_one("id", id).await
+    }
+
+    async fn count(&self) -> Result<usize, RepositoryError> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(count as usize)
+    }
+
+    async fn update_profile(&self, user: &User) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE users SET email = $1, display_name = $2, avatar_url = $3, updated_at = $4 WHERE id = $5",
+        )
+        .bind(&user.email)
+        .bind(&user.display_name)
+        .bind(&user.avatar_url)
+        .bind(user.updated_at)
+        .bind(&user.id)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlx::query("DELETE FROM user_identities WHERE user_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        // The second factor is kept in the user's row, so it goes with it.
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_password(
+        &self,
+        id: &str,
+        password_hash: &str,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE users SET password_hash = $1, updated_at = $2 WHERE id = $3")
+            .bind(password_hash)
+            .bind(updated_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update_role(
+        &self,
+        id: &str,
+        role: Role,
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE users SET role = $1, updated_at = $2 WHERE id = $3")
+            .bind(role.as_str())
+            .bind(updated_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn link_identity(&self, identity: &UserIdentity) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO user_identities (provider, subject, user_id, created_at) VALUES ($1, $2, $3, $4) ON CONFLICT (provider, subject) DO UPDATE SET user_id = excluded.user_id",
+        )
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .bind(&identity.user_id)
+        .bind(identity.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let query = format!(
+            "{} WHERE id = (SELECT user_id FROM user_identities WHERE provider = $1 AND subject = $2)",
+            SELECT_USERS
+        );
+        let row = sqlx::query_as::<_, UserRow>(&query)
+            .bind(provider)
+            .bind(subject)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        row.map(User::try_from).transpose()
+    }
+
+    async fn find_totp(&self, user_id: &str) -> Result<Option<UserTotp>, RepositoryError> {
+        let row: Option<(Option<String>, Option<DateTime<Utc>>, i64, String)> = sqlx::query_as(
+            "SELECT totp_secret, totp_enabled_at, totp_last_step, totp_backup_codes FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(
+            row.and_then(|(secret, enabled_at, last_step, backup_codes)| {
+                Some(UserTotp {
+                    secret: secret?,
+                    enabled_at,
+                    last_step,
+                    backup_codes: backup_codes.split_whitespace().map(String::from).collect(),
+                })
+            }),
+        )
+    }
+
+    async fn save_totp(
+        &self,
+        user_id: &str,
+        totp: Option<&UserTotp>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE users SET totp_secret = $1, totp_enabled_at = $2, totp_last_step = $3, totp_backup_codes = $4 WHERE id = $5",
+        )
+        .bind(totp.map(|totp| &totp.secret))
+        .bind(totp.and_then(|totp| totp.enabled_at))
+        .bind(totp.map_or(0, |totp| totp.last_step))
+        .bind(totp.map(|totp| totp.backup_codes.join(" ")).unwrap_or_default())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WorkspaceRecord {
+    id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<WorkspaceRecord> for Workspace {
+    fn from(record: WorkspaceRecord) -> Self {
+        Workspace {
+            id: record.id,
+            name: record.name,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct WorkspaceMemberRecord {
+    workspace_id: String,
+    user_id: String,
+    role: String,
+    joined_at: DateTime<Utc>,
+}
+
+impl TryFrom<WorkspaceMemberRecord> for WorkspaceMember {
+    type Error = RepositoryError;
+
+    fn try_from(record: WorkspaceMemberRecord) -> Result<Self, Self::Error> {
+        Ok(WorkspaceMember {
+            workspace_id: record.workspace_id,
+            user_id: record.user_id,
+            role: record.role.parse().map_err(db_error)?,
+            joined_at: record.joined_at,
+        })
+    }
+}
+
+const SELECT_WORKSPACES: &str = "SELECT id, name, created_at, updated_at FROM workspaces";
+
+const SELECT_WORKSPACE_MEMBERS: &str =
+    "SELECT workspace_id, user_id, role, joined_at FROM workspace_members";
+
+pub struct SqliteWorkspaceRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl WorkspaceRepository for SqliteWorkspaceRepository {
+    async fn insert(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO workspaces (id, name, created_at, updated_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&workspace.id)
+        .bind(&workspace.name)
+        .bind(workspace.created_at)
+        .bind(workspace.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, workspace: &Workspace) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE workspaces SET name = $1, updated_at = $2 WHERE id = $3")
+            .bind(&workspace.name)
+            .bind(workspace.updated_at)
+            .bind(&workspace.id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlx::query("DELETE FROM workspace_members WHERE workspace_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        sqlx::query("DELETE FROM workspaces WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: &str) -> Result<Option<Workspace>, RepositoryError> {
+        let query = format!("{} WHERE id = $1", SELECT_WORKSPACES);
+        let record = sqlx::query_as::<_, WorkspaceRecord>(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Workspace::from))
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Workspace>, RepositoryError> {
+        let query = format!(
+            "{} WHERE id IN (SELECT workspace_id FROM workspace_members WHERE user_id = $1) ORDER BY created_at, id",
+            SELECT_WORKSPACES
+        );
+        let records = sqlx::query_as::<_, WorkspaceRecord>(&query)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Workspace::from).collect())
+    }
+
+    async fn upsert_member(&self, member: &WorkspaceMember) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO workspace_members (workspace_id, user_id, role, joined_at) VALUES ($1, $2, $3, $4) ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = excluded.role",
+        )
+        .bind(&member.workspace_id)
+        .bind(&member.user_id)
+        .bind(member.role.as_str())
+        .bind(member.joined_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn remove_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM workspace_members WHERE workspace_id = $1 AND user_id = $2")
+            .bind(workspace_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_member(
+        &self,
+        workspace_id: &str,
+        user_id: &str,
+    ) -> Result<Option<WorkspaceMember>, RepositoryError> {
+        let query = format!(
+            "{} WHERE workspace_id = $1 AND user_id = $2",
+            SELECT_WORKSPACE_MEMBERS
+        );
+        let record = sqlx::query_as::<_, WorkspaceMemberRecord>(&query)
+            .bind(workspace_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        record.map(WorkspaceMember::try_from).transpose()
+    }
+
+    async fn list_members(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceMember>, RepositoryError> {
+        let query = format!(
+            "{} WHERE workspace_id = $1 ORDER BY joined_at",
+            SELECT_WORKSPACE_MEMBERS
+        );
+        let records = sqlx::query_as::<_, WorkspaceMemberRecord>(&query)
+            .bind(workspace_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        records.into_iter().map(WorkspaceMember::try_from).collect()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TodoShareRecord {
+    todo_id: TodoId,
+    user_id: String,
+    permission: String,
+    shared_at: DateTime<Utc>,
+}
+
+impl TryFrom<TodoShareRecord> for TodoShare {
+    type Error = RepositoryError;
+
+    fn try_from(record: TodoShareRecord) -> Result<Self, Self::Error> {
+        Ok(TodoShare {
+            todo_id: record.todo_id,
+            user_id: record.user_id,
+            permission: record.permission.parse().map_err(db_error)?,
+            shared_at: record.shared_at,
+        })
+    }
+}
+
+const SELECT_TODO_ACL: &str = "SELECT todo_id, user_id, permission, shared_at FROM todo_acl";
+
+pub struct SqliteTodoAclRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl TodoAclRepository for SqliteTodoAclRepository {
+    async fn upsert(&self, share: &TodoShare) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO todo_acl (todo_id, user_id, permission, shared_at) VALUES ($1, $2, $3, $4) ON CONFLICT (todo_id, user_id) DO UPDATE SET permission = excluded.permission",
+        )
+        .bind(share.todo_id)
+        .bind(&share.user_id)
+        .bind(share.permission.as_str())
+        .bind(share.shared_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(
+        &self,
+        todo_id: &TodoId,
+        user_id: &str,
+    ) -> Result<Option<TodoShare>, RepositoryError> {
+        let query = format!("{} WHERE todo_id = $1 AND user_id = $2", SELECT_TODO_ACL);
+        let record = sqlx::query_as::<_, TodoShareRecord>(&query)
+            .bind(todo_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        record.map(TodoShare::try_from).transpose()
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<TodoShare>, RepositoryError> {
+        let query = format!(
+            "{} WHERE user_id = $1 ORDER BY shared_at DESC",
+            SELECT_TODO_ACL
+        );
+        let records = sqlx::query_as::<_, TodoShareRecord>(&query)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        records.into_iter().map(TodoShare::try_from).collect()
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for todo_id in todo_ids {
+            sqlx::query("DELETE FROM todo_acl WHERE todo_id = $1")
+                .bind(todo_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TemplateRecord {
+    id: String,
+    owner_id: String,
+    name: String,
+    title: String,
+    content: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<TemplateRecord> for Template {
+    fn from(record: TemplateRecord) -> Self {
+        Template {
+            id: record.id,
+            owner_id: record.owner_id,
+            name: record.name,
+            title: record.title,
+            content: record.content,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+const SELECT_TEMPLATES: &str =
+    "SELECT id, owner_id, name, title, content, created_at, updated_at FROM templates";
+
+pub struct SqliteTemplateRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl TemplateRepository for SqliteTemplateRepository {
+    async fn insert(&self, template: &Template) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO templates (id, owner_id, name, title, content, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&template.id)
+        .bind(&template.owner_id)
+        .bind(&template.name)
+        .bind(&template.title)
+        .bind(&template.content)
+        .bind(template.created_at)
+        .bind(template.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        owner_id: &str,
+        id: &str,
+    ) -> Result<Option<Template>, RepositoryError> {
+        let query = format!("{} WHERE owner_id = $1 AND id = $2", SELECT_TEMPLATES);
+        let record = sqlx::query_as::<_, TemplateRecord>(&query)
+            .bind(owner_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Template::from))
+    }
+
+    async fn list_for_owner(&self, owner_id: &str) -> Result<Vec<Template>, RepositoryError> {
+        let query = format!(
+            "{} WHERE owner_id = $1 ORDER BY created_at, id",
+            SELECT_TEMPLATES
+        );
+        let records = sqlx::query_as::<_, TemplateRecord>(&query)
+            .bind(owner_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Template::from).collect())
+    }
+
+    async fn delete(&self, owner_id: &str, id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM templates WHERE owner_id = $1 AND id = $2")
+            .bind(owner_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ProjectRecord {
+    id: String,
+    name: String,
+    workspace_id: Option<String>,
+    owner_id: Option<String>,
+    archived: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<ProjectRecord> for Project {
+    fn from(record: ProjectRecord) -> Self {
+        Project {
+            id: record.id,
+            name: record.name,
+            workspace_id: record.workspace_id,
+            owner_id: record.owner_id,
+            archived: record.archived,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+const SELECT_PROJECTS: &str =
+    "SELECT id, name, workspace_id, owner_id, archived, created_at, updated_at FROM projects";
+
+pub struct SqliteProjectRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl ProjectRepository for SqliteProjectRepository {
+    async fn insert(&self, project: &Project) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO projects (id, name, workspace_id, owner_id, archived, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&project.id)
+        .bind(&project.name)
+        .bind(&project.workspace_id)
+        .bind(&project.owner_id)
+        .bind(project.archived)
+        .bind(project.created_at)
+        .bind(project.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn update(&self, project: &Project) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE projects SET name = $1, archived = $2, updated_at = $3 WHERE id = $4")
+            .bind(&project.name)
+            .bind(project.archived)
+            .bind(project.updated_at)
+            .bind(&project.id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        workspace_id: Option<&str>,
+        id: &str,
+    ) -> Result<Option<Project>, RepositoryError> {
+        let query = format!("{} WHERE workspace_id IS $1 AND id = $2", SELECT_PROJECTS);
+        let record = sqlx::query_as::<_, ProjectRecord>(&query)
+            .bind(workspace_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Project::from))
+    }
+
+    async fn list(&self, workspace_id: Option<&str>) -> Result<Vec<Project>, RepositoryError> {
+        let query = format!(
+            "{} WHERE workspace_id IS $1 ORDER BY created_at, id",
+            SELECT_PROJECTS
+        );
+        let records = sqlx::query_as::<_, ProjectRecord>(&query)
+            .bind(workspace_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Project::from).collect())
+    }
+
+    async fn delete(&self, workspace_id: Option<&str>, id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM projects WHERE workspace_id IS $1 AND id = $2")
+            .bind(workspace_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CommentRecord {
+    id: String,
+    todo_id: TodoId,
+    author_id: String,
+    body: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<CommentRecord> for Comment {
+    fn from(record: CommentRecord) -> Self {
+        Comment {
+            id: record.id,
+            todo_id: record.todo_id,
+            author_id: record.author_id,
+            body: record.body,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+const SELECT_COMMENTS: &str =
+    "SELECT id, todo_id, author_id, body, created_at, updated_at FROM comments";
+
+pub struct SqliteCommentRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl CommentRepository for SqliteCommentRepository {
+    async fn insert(&self, comment: &Comment) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO comments (id, todo_id, author_id, body, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&comment.id)
+        .bind(comment.todo_id)
+        .bind(&comment.author_id)
+        .bind(&comment.body)
+        .bind(comment.created_at)
+        .bind(comment.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Comment>, RepositoryError> {
+        let query = format!("{} WHERE todo_id = $1 AND id = $2", SELECT_COMMENTS);
+        let record = sqlx::query_as::<_, CommentRecord>(&query)
+            .bind(todo_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Comment::from))
+    }
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM comments WHERE todo_id = $1 AND id = $2")
+            .bind(todo_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        todo_id: &TodoId,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Comment>, RepositoryError> {
+        let query = format!(
+            "{} WHERE todo_id = $1 ORDER BY created_at, id LIMIT $3 OFFSET $2",
+            SELECT_COMMENTS
+        );
+        let records = sqlx::query_as::<_, CommentRecord>(&query)
+            .bind(todo_id)
+            .bind(offset as i64)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Comment::from).collect())
+    }
+
+    async fn count_by_todo(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<HashMap<TodoId, usize>, RepositoryError> {
+        if todo_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT todo_id, COUNT(*) FROM comments WHERE todo_id IN (",
+        );
+        let mut separated = query.separated(", ");
+        for todo_id in todo_ids {
+            separated.push_bind(*todo_id);
+        }
+        query.push(") GROUP BY todo_id");
+
+        let rows: Vec<(TodoId, i64)> = query
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(todo_id, count)| (todo_id, count as usize))
+            .collect())
+    }
+
+    async fn delete_for_todos(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for todo_id in todo_ids {
+            sqlx::query("DELETE FROM comments WHERE todo_id = $1")
+                .bind(todo_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ActivityRecord {
+    id: String,
+    todo_id: TodoId,
+    actor_id: Option<String>,
+    kind: String,
+    detail: Option<String>,
+    occurred_at: DateTime<Utc>,
+}
+
+impl ActivityRecord {
+    /// `None` for kinds this version does not know.
+    fn into_activity(self) -> Option<Activity> {
+        Some(Activity {
+            id: self.id,
+            todo_id: self.todo_id,
+            actor_id: self.actor_id,
+            kind: self.kind.parse().ok()?,
+            detail: self.detail,
+            occurred_at: self.occurred_at,
+        })
+    }
+}
+
+const SELECT_ACTIVITY: &str =
+    "SELECT id, todo_id, actor_id, kind, detail, occurred_at FROM activity";
+
+pub struct SqliteActivityRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteActivityRepository {
+    async fn page(
+        &self,
+        column: &str,
+        value: String,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        let mut query = QueryBuilder::<Sqlite>::new(SELECT_ACTIVITY);
+        query
+            .push(" WHERE ")
+            .push(column)
+            .push(" = ")
+            .push_bind(value);
+        if !kinds.is_empty() {
+            query.push(" AND kind IN (");
+            let mut separated = query.separated(", ");
+            for kind in kinds {
+                separated.push_bind(kind.as_str());
+            }
+            separated.push_unseparated(")");
+        }
+        query
+            .push(" ORDER BY occurred_at, id LIMIT ")
+            .push_bind(limit as i64)
+            .push(" OFFSET ")
+            .push_bind(offset as i64);
+
+        let records = query
+            .build_query_as::<ActivityRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(ActivityRecord::into_activity)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ActivityRepository for SqliteActivityRepository {
+    async fn insert(&self, activity: &Activity) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO activity (id, todo_id, actor_id, kind, detail, occurred_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&activity.id)
+        .bind(activity.todo_id)
+        .bind(&activity.actor_id)
+        .bind(activity.kind.as_str())
+        .bind(&activity.detail)
+        .bind(activity.occurred_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn list_for_todo(
+        &self,
+        todo_id: &TodoId,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        self.page("todo_id", todo_id.to_string(), kinds, offset, limit)
+            .await
+    }
+
+    async fn list_for_actor(
+        &self,
+        actor_id: &str,
+        kinds: &[ActivityKind],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Activity>, RepositoryError> {
+        self.page("actor_id", actor_id.to_string(), kinds, offset, limit)
+            .await
+    }
+
+    async fn list_before(&self, before: DateTime<Utc>) -> Result<Vec<Activity>, RepositoryError> {
+        let records = sqlx::query_as::<_, ActivityRecord>(&format!(
+            "{} WHERE occurred_at < $1 ORDER BY occurred_at, id",
+            SELECT_ACTIVITY
+        ))
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(records
+            .into_iter()
+            .filter_map(ActivityRecord::into_activity)
+            .collect())
+    }
+
+    async fn delete(&self, entries: &[Activity]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for activity in entries {
+            sqlx::query("DELETE FROM activity WHERE id = $1")
+                .bind(&activity.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UndoRecord {
+    id: String,
+    actor_id: String,
+    action: String,
+    todos: String,
+    created_at: DateTime<Utc>,
+}
+
+impl UndoRecord {
+    /// `None` for entries this version cannot read.
+    fn into_entry(self) -> Option<UndoEntry> {
+        Some(UndoEntry {
+            id: self.id,
+            actor_id: self.actor_id,
+            action: self.action.parse().ok()?,
+            todos: serde_json::from_str(&self.todos).ok()?,
+            created_at: self.created_at,
+        })
+    }
+}
+
+pub struct SqliteUndoRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl UndoRepository for SqliteUndoRepository {
+    async fn insert(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO undo_log (id, actor_id, action, todos, created_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(&entry.id)
+        .bind(&entry.actor_id)
+        .bind(entry.action.as_str())
+        .bind(undo_todos_json(&entry.todos)?)
+        .bind(entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn latest(
+        &self,
+        actor_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<UndoEntry>, RepositoryError> {
+        let record = sqlx::query_as::<_, UndoRecord>(
+            "SELECT id, actor_id, action, todos, created_at FROM undo_log WHERE actor_id = $1 AND created_at >= $2 ORDER BY created_at DESC, id DESC LIMIT 1",
+        )
+        .bind(actor_id)
+        .bind(since)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(record.and_then(UndoRecord::into_entry))
+    }
+
+    async fn delete(&self, entry: &UndoEntry) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM undo_log WHERE id = $1")
+            .bind(&entry.id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete_before(
+        &self,
+        actor_id: &str,
+        before: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM undo_log WHERE actor_id = $1 AND created_at < $2")
+            .bind(actor_id)
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ChangeRecord {
+    todo_id: TodoId,
+    token: i64,
+    workspace_id: Option<String>,
+    owner_id: Option<String>,
+    deleted: bool,
+    version: String,
+    changed_at: DateTime<Utc>,
+}
+
+impl From<ChangeRecord> for TodoChange {
+    fn from(record: ChangeRecord) -> Self {
+        TodoChange {
+            todo_id: record.todo_id,
+            token: record.token,
+            workspace_id: record.workspace_id,
+            owner_id: record.owner_id,
+            deleted: record.deleted,
+            version: serde_json::from_str(&record.version).unwrap_or_default(),
+            changed_at: record.changed_at,
+        }
+    }
+}
+
+const SELECT_CHANGES: &str =
+    "SELECT todo_id, token, workspace_id, owner_id, deleted, version, changed_at FROM todo_changes";
+
+pub struct SqliteChangeRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl ChangeRepository for SqliteChangeRepository {
+    async fn record(&self, change: &TodoChange) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO todo_changes (todo_id, token, workspace_id, owner_id, deleted, version, changed_at) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (todo_id) DO UPDATE SET token = excluded.token, workspace_id = excluded.workspace_id, owner_id = excluded.owner_id, deleted = excluded.deleted, version = excluded.version, changed_at = excluded.changed_at",
+        )
+        .bind(change.todo_id)
+        .bind(change.token)
+        .bind(&change.workspace_id)
+        .bind(&change.owner_id)
+        .bind(change.deleted)
+        .bind(version_json(&change.version)?)
+        .bind(change.changed_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, todo_id: &TodoId) -> Result<Option<TodoChange>, RepositoryError> {
+        let record =
+            sqlx::query_as::<_, ChangeRecord>(&format!("{} WHERE todo_id = $1", SELECT_CHANGES))
+                .bind(*todo_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(db_error)?;
+
+        Ok(record.map(TodoChange::from))
+    }
+
+    async fn since(&self, token: i64, limit: usize) -> Result<Vec<TodoChange>, RepositoryError> {
+        let records = sqlx::query_as::<_, ChangeRecord>(&format!(
+            "{} WHERE token > $1 ORDER BY token LIMIT $2",
+            SELECT_CHANGES
+        ))
+        .bind(token)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(records.into_iter().map(TodoChange::from).collect())
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let result = sqlx::query("DELETE FROM todo_changes WHERE deleted AND changed_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn list_deleted(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<TodoChange>, RepositoryError> {
+        let records = sqlx::query_as::<_, ChangeRecord>(&format!(
+            "{} WHERE deleted AND changed_at < $1",
+            SELECT_CHANGES
+        ))
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(records.into_iter().map(TodoChange::from).collect())
+    }
+
+    async fn delete(&self, todo_ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        for todo_id in todo_ids {
+            sqlx::query("DELETE FROM todo_changes WHERE todo_id = $1")
+                .bind(todo_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct OutboxRecord {
+    id: String,
+    consumer: String,
+    event: String,
+    todo_id: TodoId,
+    payload: String,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl From<OutboxRecord> for OutboxEntry {
+    fn from(record: OutboxRecord) -> Self {
+        OutboxEntry {
+            id: record.id,
+            consumer: record.consumer,
+            event: record.event,
+            todo_id: record.todo_id,
+            payload: record.payload,
+            created_at: record.created_at,
+            delivered_at: record.delivered_at,
+        }
+    }
+}
+
+pub struct SqliteOutboxRepository {
+    pool: SqlitePool,
+}
+
+async fn insert_outbox_entry(
+    executor: impl SqliteExecutor<'_>,
+    entry: &OutboxEntry,
+) -> Result<(), RepositoryError> {
+    sqlx::query(
+        "INSERT INTO outbox (id, consumer, event, todo_id, payload, created_at, delivered_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(&entry.id)
+    .bind(&entry.consumer)
+    .bind(&entry.event)
+    .bind(entry.todo_id)
+    .bind(&entry.payload)
+    .bind(entry.created_at)
+    .bind(entry.delivered_at)
+    .execute(executor)
+    .await
+    .map_err(db_error)?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl OutboxRepository for SqliteOutboxRepository {
+    async fn insert(&self, entry: &OutboxEntry) -> Result<(), RepositoryError> {
+        insert_outbox_entry(&self.pool, entry).await
+    }
+
+    async fn pending(
+        &self,
+        consumer: &str,
+        limit: usize,
+    ) -> Result<Vec<OutboxEntry>, RepositoryError> {
+        let records = sqlx::query_as::<_, OutboxRecord>(
+            "SELECT id, consumer, event, todo_id, payload, created_at, delivered_at FROM outbox WHERE consumer = $1 AND delivered_at IS NULL ORDER BY created_at LIMIT $2",
+        )
+        .bind(consumer)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(records.into_iter().map(OutboxEntry::from).collect())
+    }
+
+    async fn mark_delivered(&self, id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        sqlx::query("UPDATE outbox SET delivered_at = $1 WHERE id = $2")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn purge_delivered(&self, before: DateTime<Utc>) -> Result<usize, RepositoryError> {
+        let result = sqlx::query("DELETE FROM outbox WHERE delivered_at < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(result.rows_affected() as usize)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RefreshTokenRecord {
+    token_hash: String,
+    family_id: String,
+    user_id: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+    amr: String,
+}
+
+impl From<RefreshTokenRecord> for RefreshToken {
+    fn from(record: RefreshTokenRecord) -> Self {
+        RefreshToken {
+            token_hash: record.token_hash,
+            family_id: record.family_id,
+            user_id: record.user_id,
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+            used_at: record.used_at,
+            amr: record.amr.split_whitespace().map(String::from).collect(),
+        }
+    }
+}
+
+pub struct SqliteRefreshTokenRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl RefreshTokenRepository for SqliteRefreshTokenRepository {
+    async fn insert(&self, token: &RefreshToken) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, family_id, user_id, created_at, expires_at, used_at, amr) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&token.token_hash)
+        .bind(&token.family_id)
+        .bind(&token.user_id)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .bind(token.amr.join(" "))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<RefreshToken>, RepositoryError> {
+        let record = sqlx::query_as::<_, RefreshTokenRecord>(
+            "SELECT token_hash, family_id, user_id, created_at, expires_at, used_at, amr FROM refresh_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(record.map(RefreshToken::from))
+    }
+
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = $1 WHERE token_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_family(
+        &self,
+        family_id: &str,
+        until: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO revoked_token_families (family_id, revoked_at, expires_at) VALUES ($1, $2, $3) ON CONFLICT (family_id) DO UPDATE SET expires_at = excluded.expires_at",
+        )
+        .bind(family_id)
+        .bind(Utc::now())
+        .bind(until)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn is_revoked(&self, family_id: &str) -> Result<bool, RepositoryError> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM revoked_token_families WHERE family_id = $1)",
+        )
+        .bind(family_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionRecord {
+    id: String,
+    user_id: String,
+    user_agent: Option<String>,
+    ip_address: String,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+impl From<SessionRecord> for UserSession {
+    fn from(record: SessionRecord) -> Self {
+        UserSession {
+            id: record.id,
+            user_id: record.user_id,
+            user_agent: record.user_agent,
+            ip_address: record.ip_address,
+            created_at: record.created_at,
+            last_seen_at: record.last_seen_at,
+            expires_at: record.expires_at,
+        }
+    }
+}
+
+const SELECT_SESSIONS: &str = "SELECT id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at FROM sessions";
+
+pub struct SqliteSessionRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl SessionRepository for SqliteSessionRepository {
+    async fn save(&self, session: &UserSession) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO sessions (id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (id) DO UPDATE SET user_agent = excluded.user_agent, ip_address = excluded.ip_address, last_seen_at = excluded.last_seen_at, expires_at = excluded.expires_at",
+        )
+        .bind(&session.id)
+        .bind(&session.user_id)
+        .bind(&session.user_agent)
+        .bind(&session.ip_address)
+        .bind(session.created_at)
+        .bind(session.last_seen_at)
+        .bind(session.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, user_id: &str, id: &str) -> Result<Option<UserSession>, RepositoryError> {
+        let query = format!("{} WHERE user_id = $1 AND id = $2", SELECT_SESSIONS);
+        let record = sqlx::query_as::<_, SessionRecord>(&query)
+            .bind(user_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(UserSession::from))
+    }
+
+    async fn find_by_user(
+        &self,
+        user_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<UserSession>, RepositoryError> {
+        let query = format!(
+            "{} WHERE user_id = $1 AND expires_at > $2 ORDER BY last_seen_at DESC",
+            SELECT_SESSIONS
+        );
+        let records = sqlx::query_as::<_, SessionRecord>(&query)
+            .bind(user_id)
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(UserSession::from).collect())
+    }
+
+    async fn delete(&self, user_id: &str, id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND id = $2")
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+pub struct SqliteLoginAttemptRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl LoginAttemptRepository for SqliteLoginAttemptRepository {
+    async fn record_failure(
+        &self,
+        key: &str,
+        at: DateTime<Utc>,
+        since: DateTime<Utc>,
+    ) -> Result<usize, RepositoryError> {
+        sqlx::query("DELETE FROM login_failures WHERE lock_key = $1 AND failed_at <= $2")
+            .bind(key)
+            .bind(since)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        sqlx::query("INSERT INTO login_failures (lock_key, failed_at) VALUES ($1, $2)")
+            .bind(key)
+            .bind(at)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM login_failures WHERE lock_key = $1 AND failed_at > $2",
+        )
+        .bind(key)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(count as usize)
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM login_failures WHERE lock_key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        sqlx::query(
+            "INSERT INTO login_lockouts (lock_key, locked_until) VALUES ($1, $2) ON CONFLICT (lock_key) DO UPDATE SET locked_until = excluded.locked_until",
+        )
+        .bind(key)
+        .bind(until)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn locked_until(&self, key: &str) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        sqlx::query_scalar("SELECT locked_until FROM login_lockouts WHERE lock_key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM login_failures WHERE lock_key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        sqlx::query("DELETE FROM login_lockouts WHERE lock_key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PasswordResetRecord {
+    token_hash: String,
+    user_id: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+}
+
+impl From<PasswordResetRecord> for PasswordResetToken {
+    fn from(record: PasswordResetRecord) -> Self {
+        PasswordResetToken {
+            token_hash: record.token_hash,
+            user_id: record.user_id,
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+            used_at: record.used_at,
+        }
+    }
+}
+
+pub struct SqlitePasswordResetRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl PasswordResetRepository for SqlitePasswordResetRepository {
+    async fn insert(&self, token: &PasswordResetToken) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at, used_at) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(&token.token_hash)
+        .bind(&token.user_id)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find(&self, token_hash: &str) -> Result<Option<PasswordResetToken>, RepositoryError> {
+        let record = sqlx::query_as::<_, PasswordResetRecord>(
+            "SELECT token_hash, user_id, created_at, expires_at, used_at FROM password_reset_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(record.map(PasswordResetToken::from))
+    }
+
+    async fn mark_used(
+        &self,
+        token_hash: &str,
+        at: DateTime<Utc>,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = $1 WHERE token_hash = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn mark_all_used(&self, user_id: &str, at: DateTime<Utc>) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = $1 WHERE user_id = $2 AND used_at IS NULL",
+        )
+        .bind(at)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AttachmentRecord {
+    id: String,
+    todo_id: TodoId,
+    file_name: String,
+    content_type: String,
+    size: i64,
+    uploaded_by: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AttachmentRecord> for Attachment {
+    fn from(record: AttachmentRecord) -> Self {
+        Attachment {
+            id: record.id,
+            todo_id: record.todo_id,
+            file_name: record.file_name,
+            content_type: record.content_type,
+            size: record.size.max(0) as u64,
+            uploaded_by: record.uploaded_by,
+            created_at: record.created_at,
+        }
+    }
+}
+
+const SELECT_ATTACHMENTS: &str =
+    "SELECT id, todo_id, file_name, content_type, size, uploaded_by, created_at FROM attachments";
+
+pub struct SqliteAttachmentRepository {
+    pool: SqlitePool,
+}
+
+#[async_trait]
+impl AttachmentRepository for SqliteAttachmentRepository {
+    async fn insert(&self, attachment: &Attachment) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO attachments (id, todo_id, file_name, content_type, size, uploaded_by, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&attachment.id)
+        .bind(attachment.todo_id)
+        .bind(&attachment.file_name)
+        .bind(&attachment.content_type)
+        .bind(attachment.size as i64)
+        .bind(&attachment.uploaded_by)
+        .bind(attachment.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn find_by_id(
+        &self,
+        todo_id: &TodoId,
+        id: &str,
+    ) -> Result<Option<Attachment>, RepositoryError> {
+        let query = format!("{} WHERE todo_id = $1 AND id = $2", SELECT_ATTACHMENTS);
+        let record = sqlx::query_as::<_, AttachmentRecord>(&query)
+            .bind(todo_id)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(record.map(Attachment::from))
+    }
+
+    async fn list(&self, todo_id: &TodoId) -> Result<Vec<Attachment>, RepositoryError> {
+        let query = format!(
+            "{} WHERE todo_id = $1 ORDER BY created_at, id",
+            SELECT_ATTACHMENTS
+        );
+        let records = sqlx::query_as::<_, AttachmentRecord>(&query)
+            .bind(todo_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(records.into_iter().map(Attachment::from).collect())
+    }
+
+    async fn delete(&self, todo_id: &TodoId, id: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM attachments WHERE todo_id = $1 AND id = $2")
+            .bind(todo_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        Ok(())
+    }
+
+    async fn delete_for_todos(
+        &self,
+        todo_ids: &[TodoId],
+    ) -> Result<Vec<Attachment>, RepositoryError> {
+        let query = "DELETE FROM attachments WHERE todo_id = $1 RETURNING id, todo_id, file_name, content_type, size, uploaded_by, created_at";
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut removed = Vec::new();
+        for todo_id in todo_ids {
+            let records = sqlx::query_as::<_, AttachmentRecord>(query)
+                .bind(todo_id)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(db_error)?;
+            removed.extend(records.into_iter().map(Attachment::from));
+        }
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{
+    ChangeRepository, ListOptions, RepositoryError, TodoFilter, TodoPatch, TodoRepository,
+    TodoScope, TodoStream, TodoWrite,
+};
+use crate::model::{OutboxEntry, Todo, TodoChange, TodoId, VersionVector};
+use crate::sync;
+
+/// Decorates another repository so every write lands in the change log
+/// read by `GET /sync`, counted as a change by the server replica.
+/// Recording is best effort: a failure is logged and the write stands, so
+/// sync clients only miss the change until the todo next changes.
+pub struct SyncedTodoRepository<R> {
+    inner: R,
+    changes: Arc<dyn ChangeRepository>,
+}
+
+impl<R: TodoRepository> SyncedTodoRepository<R> {
+    pub fn new(inner: R, changes: Arc<dyn ChangeRepository>) -> Self {
+        SyncedTodoRepository { inner, changes }
+    }
+
+    /// The last change to `id`, or one describing the todo as it is when
+    /// none was recorded yet. `None` when the todo is unknown.
+    async fn base(&self, id: &TodoId) -> Result<Option<TodoChange>, RepositoryError> {
+        if let Some(change) = self.changes.find(id).await? {
+            return Ok(Some(change));
+        }
+        Ok(self.inner.find_by_id(id).await?.map(|todo| TodoChange {
+            todo_id: *id,
+            token: 0,
+            workspace_id: todo.workspace_id,
+            owner_id: todo.owner_id,
+            deleted: false,
+            version: VersionVector::default(),
+            changed_at: Utc::now(),
+        }))
+    }
+
+    async fn record_change(
+        &self,
+        mut change: TodoChange,
+        deleted: bool,
+    ) -> Result<(), RepositoryError> {
+        change.token = sync::next_token();
+        change.deleted = deleted;
+        change.version.increment(VersionVector::SERVER);
+        change.changed_at = Utc::now();
+        self.changes.record(&change).await
+    }
+
+    async fn record_todo(&self, todo: &Todo) {
+        let Some(id) = todo.id else {
+            return;
+        };
+        let result = async {
+            let version = self
+                .changes
+                .find(&id)
+                .await?
+                .map(|change| change.version)
+                .unwrap_or_default();
+            let change = TodoChange {
+                todo_id: id,
+                token: 0,
+                workspace_id: todo.workspace_id.clone(),
+                owner_id: todo.owner_id.clone(),
+                deleted: false,
+                version,
+                changed_at: Utc::now(),
+            };
+            self.record_change(change, false).await
+        }
+        .await;
+        log_failure(&id, result);
+    }
+
+    /// Records changes to todos that still exist.
+    async fn record_ids(&self, ids: &[TodoId]) {
+        for id in ids {
+            let result = async {
+                match self.base(id).await? {
+                    Some(change) => self.record_change(change, false).await,
+                    None => Ok(()),
+                }
+            }
+            .await;
+            log_failure(id, result);
+        }
+    }
+
+    /// Looks up what deleting `ids` will need for their tombstones, while
+    /// the todos can still be read.
+    async fn bases(&self, ids: &[TodoId]) -> Vec<TodoChange> {
+        let mut bases = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.base(id).await {
+                Ok(Some(change)) => bases.push(change),
+                Ok(None) => {}
+                Err(e) => log_failure(id, Err(e)),
+            }
+        }
+        bases
+    }
+
+    async fn record_tombstones(&self, bases: Vec<TodoChange>) {
+        for change in bases {
+            let id = change.todo_id;
+            log_failure(&id, self.record_change(change, true).await);
+        }
+    }
+}
+
+fn log_failure(id: &TodoId, result: Result<(), RepositoryError>) {
+    if let Err(e) = result {
+        log::warn!(
+            "event=todo_change_record_failed todo_id={} error=\"{}\"",
+            id,
+            e
+        );
+    }
+}
+
+#[async_trait]
+impl<R: TodoRepository> TodoRepository for SyncedTodoRepository<R> {
+    async fn list(&self, options: &ListOptions) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.list(options).await
+    }
+
+    async fn stream(&self, options: &ListOptions) -> Result<TodoStream, RepositoryError> {
+        self.inner.stream(options).await
+    }
+
+    async fn find_by_id(&self, id: &TodoId) -> Result<Option<Todo>, RepositoryError> {
+        self.inner.find_by_id(id).await
+    }
+
+    async fn exists_with_title(&self, title: &str) -> Result<bool, RepositoryError> {
+        self.inner.exists_with_title(title).await
+    }
+
+    async fn find_by_title(&self, title: &str) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.find_by_title(title).await
+    }
+
+    async fn insert(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.inner.insert(todo).await?;
+        self.record_todo(todo).await;
+        Ok(())
+    }
+
+    async fn insert_many(&self, todos: &[Todo]) -> Result<(), RepositoryError> {
+        self.inner.insert_many(todos).await?;
+        for todo in todos {
+            self.record_todo(todo).await;
+        }
+        Ok(())
+    }
+
+    async fn update(&self, todo: &Todo) -> Result<(), RepositoryError> {
+        self.inner.update(todo).await?;
+        self.record_todo(todo).await;
+        Ok(())
+    }
+
+    async fn update_fields(&self, id: &TodoId, patch: &TodoPatch) -> Result<(), RepositoryError> {
+        self.inner.update_fields(id, patch).await?;
+        self.record_ids(std::slice::from_ref(id)).await;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &TodoId) -> Result<(), RepositoryError> {
+        let bases = self.bases(std::slice::from_ref(id)).await;
+        self.inner.delete(id).await?;
+        self.record_tombstones(bases).await;
+        Ok(())
+    }
+
+    async fn commit(
+        &self,
+        write: &TodoWrite,
+        entries: &[OutboxEntry],
+    ) -> Result<(), RepositoryError> {
+        match write {
+            TodoWrite::Insert(todo) | TodoWrite::Update(todo) => {
+                self.inner.commit(write, entries).await?;
+                self.record_todo(todo).await;
+            }
+            TodoWrite::Patch(id, _) => {
+                self.inner.commit(write, entries).await?;
+                self.record_ids(std::slice::from_ref(id)).await;
+            }
+            TodoWrite::Delete(id) => {
+                let bases = self.bases(std::slice::from_ref(id)).await;
+                self.inner.commit(write, entries).await?;
+                self.record_tombstones(bases).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn set_completed(
+        &self,
+        ids: &[TodoId],
+        completed: bool,
+        updated_at: DateTime<Utc>,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        let updated = self.inner.set_completed(ids, completed, updated_at).await?;
+        self.record_ids(&updated).await;
+        Ok(updated)
+    }
+
+    async fn existing_ids(
+        &self,
+        ids: &[TodoId],
+        scope: &TodoScope,
+    ) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.existing_ids(ids, scope).await
+    }
+
+    async fn find_ids(&self, filter: &TodoFilter) -> Result<Vec<TodoId>, RepositoryError> {
+        self.inner.find_ids(filter).await
+    }
+
+    async fn delete_many(&self, ids: &[TodoId]) -> Result<(), RepositoryError> {
+        let bases = self.bases(ids).await;
+        self.inner.delete_many(ids).await?;
+        self.record_tombstones(bases).await;
+        Ok(())
+    }
+
+    async fn list_series(&self, series_id: &TodoId) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.list_series(series_id).await
+    }
+
+    async fn pending_recurrences(&self) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.pending_recurrences().await
+    }
+
+    async fn pending_reminders(
+        &self,
+        due_before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Todo>, RepositoryError> {
+        self.inner.pending_reminders(due_before).await
+    }
+
+    /// Expired todos are gone by the time their IDs are known, so only
+    /// those with a recorded change get a tombstone.
+    async fn delete_expired(&self, now: DateTime<Utc>) -> Result<Vec<TodoId>, RepositoryError> {
+        let deleted = self.inner.delete_expired(now).await?;
+        let bases = self.bases(&deleted).await;
+        self.record_tombstones(bases).await;
+        Ok(deleted)
+    }
+
+    async fn count_for_owner<'a>(&self, owner_id: Option<&'a str>) -> Result<u64, RepositoryError> {
+        self.inner.count_for_owner(owner_id).await
+    }
+
+    async fn owner_counts(&self) -> Result<HashMap<Option<String>, u64>, RepositoryError> {
+        self.inner.owner_counts().await
+    }
+
+    async fn reconcile_counts(&self) -> Result<usize, RepositoryError> {
+        self.inner.reconcile_counts().await
+    }
+
+    async fn rebuild_indexes(&self) -> Result<usize, RepositoryError> {
+        self.inner.rebuild_indexes().await
+    }
+}
@@ -1,6 +1,20 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
 
-use crate::model::Todo;
+use crate::blobs::PresignedRequest;
+use crate::features::FeatureFlag;
+use crate::model::{
+    Activity, Attachment, Comment, DeadLetter, JobStatus, Maintenance, NotificationSettings, Project, SharePermission,
+    SyncOutcome, Template, Todo, TodoId, TodoShare, TodoStatus, UndoAction, User, UserSession,
+    UserSettings, VersionVector, Webhook, Workspace, WorkspaceMember,
+};
+use crate::retention::PurgeReport;
+use crate::scheduler::ScheduleStatus;
+use crate::sync::SyncedTodo;
+use crate::timezones;
+use crate::versioning::ApiVersion;
 
 #[derive(Serialize)]
 pub struct GenericResponse {
@@ -8,20 +22,967 @@ pub struct GenericResponse {
     pub message: String,
 }
 
+/// The body of a 423: a `GenericResponse` plus when the lockout ends.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedResponse {
+    pub status: String,
+    pub message: String,
+    pub unlock_at: DateTime<Utc>,
+}
+
 #[derive(Serialize, Debug)]
 pub struct TodoData {
-    pub todo: Todo,
+    pub todo: TodoRepresentation,
+}
+
+/// A todo as rendered for the API version serving the request.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum TodoRepresentation {
+    V1(Todo),
+    V2(TodoV2),
+}
+
+impl TodoRepresentation {
+    pub fn new(version: ApiVersion, mut todo: Todo) -> Self {
+        // Clients see the status `completed` implies, never a stale one.
+        todo.status = Some(todo.status());
+        match version {
+            ApiVersion::V1 => TodoRepresentation::V1(todo),
+            ApiVersion::V2 => TodoRepresentation::V2(TodoV2::from(todo)),
+        }
+    }
+}
+
+/// Version 2 of the todo representation: flags are never null, unset
+/// fields are left out and recurrence is an ISO 8601 duration.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TodoV2 {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<TodoId>,
+    pub title: String,
+    pub content: String,
+    pub completed: bool,
+    pub archived: bool,
+    #[serde(skip_serializing_if = "Option::is_none", with = "timezones::local")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<RecurrenceV2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series_id: Option<TodoId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_occurrence_id: Option<TodoId>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "timezones::local")]
+    pub remind_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reminder_sent_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<i64>,
+    pub status: TodoStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+}
+
+/// E.g. `{"every": "P2W"}` for every other week.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurrenceV2 {
+    pub every: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl From<Todo> for TodoV2 {
+    fn from(todo: Todo) -> Self {
+        let status = todo.status();
+        TodoV2 {
+            id: todo.id,
+            title: todo.title,
+            content: todo.content,
+            completed: todo.completed.unwrap_or(false),
+            archived: todo.archived.unwrap_or(false),
+            due_at: todo.due_at,
+            recurrence: todo.recurrence.map(|recurrence| RecurrenceV2 {
+                every: recurrence.iso_duration(),
+                until: recurrence.until,
+            }),
+            series_id: todo.series_id,
+            next_occurrence_id: todo.next_occurrence_id,
+            remind_at: todo.remind_at,
+            reminder_sent_at: todo.reminder_sent_at,
+            owner_id: todo.owner_id,
+            workspace_id: todo.workspace_id,
+            project_id: todo.project_id,
+            position: todo.position,
+            status,
+            created_at: todo.created_at,
+            updated_at: todo.updated_at,
+            expires_at: todo.expires_at,
+            comment_count: todo.comment_count,
+            ttl_seconds: todo.ttl_seconds,
+            content_html: todo.content_html,
+        }
+    }
+}
+
+/// A link to a related resource or action; `method` is left out for GET.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub href: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<&'static str>,
+}
+
+impl Link {
+    pub fn get(href: String) -> Self {
+        Link { href, method: None }
+    }
+
+    pub fn with_method(href: String, method: &'static str) -> Self {
+        Link {
+            href,
+            method: Some(method),
+        }
+    }
+}
+
+/// What a client can do next with a single todo.
+#[derive(Serialize, Debug)]
+pub struct TodoLinks {
+    #[serde(rename = "self")]
+    pub self_link: Link,
+    pub update: Link,
+    pub delete: Link,
+    /// Moves the todo to `done` through its status.
+    pub complete: Link,
+}
+
+/// The page of a list that was returned and its neighbours. `next` is
+/// left out once a page comes back short, `prev` on the first page.
+#[derive(Serialize, Debug)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub self_link: Link,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<Link>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<Link>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct SingleTodoResponse {
     pub status: String,
     pub data: TodoData,
+    #[serde(rename = "_links")]
+    pub links: TodoLinks,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SuggestedTodoResponse {
+    pub status: String,
+    pub data: SuggestedTodoData,
+    #[serde(rename = "_links")]
+    pub links: TodoLinks,
+}
+
+/// `score` is the weighted sum the todo won with; comparable only between
+/// suggestions made with the same weights.
+#[derive(Serialize, Debug)]
+pub struct SuggestedTodoData {
+    pub todo: TodoRepresentation,
+    pub score: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PatchTodoResponse {
+    pub status: String,
+    /// `false` when the body matched the stored todo and nothing was written.
+    pub modified: bool,
+    pub data: TodoData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UndoData {
+    pub action: UndoAction,
+    /// The todos put back, as they are now.
+    pub todos: Vec<TodoRepresentation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UndoResponse {
+    pub status: String,
+    pub data: UndoData,
+}
+
+/// A todo as a sync client should now have it; `todo` is left out once it
+/// is deleted.
+#[derive(Serialize, Debug)]
+pub struct SyncedTodoRepresentation {
+    pub id: TodoId,
+    pub deleted: bool,
+    pub version: VersionVector,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub todo: Option<TodoRepresentation>,
+}
+
+impl SyncedTodoRepresentation {
+    pub fn new(version: ApiVersion, synced: SyncedTodo) -> Self {
+        SyncedTodoRepresentation {
+            id: synced.id,
+            deleted: synced.deleted,
+            version: synced.version,
+            todo: synced
+                .todo
+                .map(|todo| TodoRepresentation::new(version, todo)),
+        }
+    }
+}
+
+/// Tokens are strings so clients need not handle 64-bit integers.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPullResponse {
+    pub status: String,
+    pub results: usize,
+    pub changes: Vec<SyncedTodoRepresentation>,
+    pub next_token: String,
+    pub has_more: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncPushResult {
+    pub id: TodoId,
+    pub outcome: SyncOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The todo as the server has it after the change.
+    pub current: SyncedTodoRepresentation,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncPushResponse {
+    pub status: String,
+    pub results: Vec<SyncPushResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchItemResult {
+    pub id: TodoId,
+    /// `"updated"`, `"conflict"` or `"not_found"`.
+    pub status: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchResponse {
+    pub status: String,
+    pub results: Vec<BatchItemResult>,
+}
+
+#[cfg(feature = "seed")]
+#[derive(Serialize, Debug)]
+pub struct SeedResponse {
+    pub status: String,
+    pub created: usize,
+}
+
+#[cfg(feature = "chaos")]
+#[derive(Serialize, Debug)]
+pub struct FaultRulesData {
+    pub rules: Vec<crate::chaos::FaultRule>,
+}
+
+#[cfg(feature = "chaos")]
+#[derive(Serialize, Debug)]
+pub struct FaultRulesResponse {
+    pub status: String,
+    pub data: FaultRulesData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BulkDeleteResponse {
+    pub status: String,
+    pub dry_run: bool,
+    pub deleted: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OccurrencesResponse {
+    pub status: String,
+    /// Occurrences that already exist, oldest first.
+    pub occurrences: Vec<TodoRepresentation>,
+    /// Projected due dates after the latest occurrence.
+    pub upcoming: Vec<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Reminder {
+    pub todo_id: TodoId,
+    pub title: String,
+    pub remind_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReminderListResponse {
+    pub status: String,
+    pub results: usize,
+    pub reminders: Vec<Reminder>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookData {
+    pub webhook: Webhook,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleWebhookResponse {
+    pub status: String,
+    pub data: WebhookData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookListResponse {
+    pub status: String,
+    pub results: usize,
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeadLetterListResponse {
+    pub status: String,
+    pub results: usize,
+    pub dead_letters: Vec<DeadLetter>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NotificationSettingsResponse {
+    pub status: String,
+    pub settings: NotificationSettings,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProfileData {
+    pub user: User,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProfileResponse {
+    pub status: String,
+    pub data: ProfileData,
+}
+
+/// Progress of an account export.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportData {
+    pub job_id: String,
+    pub status: JobStatus,
+    /// Why the latest attempt failed.
+    pub error: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExportResponse {
+    pub status: String,
+    pub data: ExportData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserSettingsResponse {
+    pub status: String,
+    pub settings: UserSettingsData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserSettingsData {
+    #[serde(flatten)]
+    pub settings: UserSettings,
+    pub notifications: NotificationOptIns,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NotificationOptIns {
+    pub reminders: bool,
+    pub digest: bool,
+}
+
+/// Body of `POST /todos/batch-get`: the todos found, in request order,
+/// and the IDs that do not exist or are not visible to the caller.
+#[derive(Serialize, Debug)]
+pub struct BatchGetResponse {
+    pub status: String,
+    pub results: usize,
+    pub todos: Vec<TodoRepresentation>,
+    pub missing: Vec<TodoId>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct TodoListResponse {
     pub status: String,
     pub results: usize,
-    pub todos: Vec<Todo>,
+    pub todos: Vec<TodoRepresentation>,
+    /// Why stored todos were left out of the list; see [`crate::decoding`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(rename = "_links")]
+    pub links: PageLinks,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatsTotals {
+    pub open: usize,
+    pub completed: usize,
+    pub overdue: usize,
+    pub archived: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CompletionRate {
+    pub days: u64,
+    pub created: usize,
+    pub completed: usize,
+    pub rate: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub created: usize,
+    pub completed: usize,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsData {
+    pub generated_at: DateTime<Utc>,
+    pub totals: StatsTotals,
+    pub completion_rates: Vec<CompletionRate>,
+    pub trend: Vec<DailyCount>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatsResponse {
+    pub status: String,
+    pub data: StatsData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TodoCountResponse {
+    pub status: String,
+    pub data: TodoCountData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TodoCountData {
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthData {
+    /// Short-lived bearer token for API requests.
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    /// Single-use token for `POST /auth/refresh`, which returns a new pair.
+    pub refresh_token: String,
+    pub refresh_expires_at: DateTime<Utc>,
+    pub user: User,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AuthResponse {
+    pub status: String,
+    pub data: AuthData,
+}
+
+/// A session as listed to its user, marking the one making the request.
+#[derive(Serialize, Debug)]
+pub struct SessionSummary {
+    #[serde(flatten)]
+    pub session: UserSession,
+    pub current: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SessionListResponse {
+    pub status: String,
+    pub results: usize,
+    pub sessions: Vec<SessionSummary>,
+}
+
+/// Returned by a login instead of tokens while the account's second
+/// factor is still to be given, with `mfaToken` to `POST /auth/login/totp`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaChallengeData {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MfaChallengeResponse {
+    pub status: String,
+    pub data: MfaChallengeData,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CsrfTokenData {
+    pub csrf_token: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CsrfTokenResponse {
+    pub status: String,
+    pub data: CsrfTokenData,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrollmentData {
+    /// Base32, for typing into the app by hand.
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TotpEnrollmentResponse {
+    pub status: String,
+    pub data: TotpEnrollmentData,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupCodesData {
+    /// Each works once in place of a code from the app.
+    pub backup_codes: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BackupCodesResponse {
+    pub status: String,
+    pub data: BackupCodesData,
+}
+
+/// A queued re-encryption of todo content.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReencryptionData {
+    pub job_id: String,
+    /// The key content is being sealed with.
+    pub key_id: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReencryptionResponse {
+    pub status: String,
+    pub data: ReencryptionData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RetentionPreviewResponse {
+    pub status: String,
+    pub data: PurgeReport,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduleListResponse {
+    pub status: String,
+    pub results: usize,
+    pub schedules: Vec<ScheduleStatus>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerTodoCount {
+    pub user_id: String,
+    pub todos: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminTodoStats {
+    pub total: usize,
+    pub open: usize,
+    pub completed: usize,
+    pub overdue: usize,
+    pub archived: usize,
+    /// Owners with the most todos first.
+    pub per_user: Vec<OwnerTodoCount>,
+    pub unowned: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdminUserStats {
+    pub total: usize,
+}
+
+/// Latencies are in milliseconds.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryLatency {
+    pub operation: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// `cluster` is null unless the backend is Scylla.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminDatabaseStats {
+    pub queries: Vec<QueryLatency>,
+    /// Repository calls running when the stats were taken.
+    pub in_flight: usize,
+    pub lookups: LookupStats,
+    pub cluster: Option<ClusterStats>,
+}
+
+/// Lookups of a todo by ID since startup. `shared` ones got the result of
+/// a concurrent lookup of the same todo rather than querying themselves.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LookupStats {
+    pub queried: u64,
+    pub shared: u64,
+}
+
+/// Driver counters cover every statement sent, retries included.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterStats {
+    pub nodes: usize,
+    pub nodes_up: usize,
+    pub queries: u64,
+    pub errors: u64,
+    pub retries: u64,
+}
+
+/// `limit` is null when the limit is disabled.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcurrencyStats {
+    pub group: String,
+    pub limit: Option<usize>,
+    pub in_flight: usize,
+    pub peak: usize,
+    pub admitted: u64,
+    pub rejected: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminStatsData {
+    pub generated_at: DateTime<Utc>,
+    pub todos: AdminTodoStats,
+    pub users: AdminUserStats,
+    pub database: AdminDatabaseStats,
+    /// The global request limit first, then each route group.
+    pub concurrency: Vec<ConcurrencyStats>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdminStatsResponse {
+    pub status: String,
+    pub data: AdminStatsData,
+}
+
+/// A node of the Scylla cluster as the driver last saw it.
+#[derive(Serialize, Debug)]
+pub struct ClusterNode {
+    pub address: String,
+    pub datacenter: Option<String>,
+    pub rack: Option<String>,
+    pub up: bool,
+}
+
+/// `latest` is the highest migration version applied.
+#[derive(Serialize, Debug)]
+pub struct MigrationLevel {
+    pub latest: Option<i64>,
+    pub pending: Vec<i64>,
+    pub unknown: Vec<i64>,
+}
+
+/// The instance serving the request. `config` holds settings by the
+/// variable that sets them, without secrets; `migrations` is null when the
+/// database could not be asked, and `cluster` for the SQL and memory
+/// backends.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugInfoData {
+    pub version: String,
+    pub commit: Option<String>,
+    pub features: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub uptime_seconds: u64,
+    pub config: BTreeMap<String, String>,
+    pub migrations: Option<MigrationLevel>,
+    pub cluster: Option<Vec<ClusterNode>>,
+}
+
+/// `build` is the version with the commit as build metadata, as sent in
+/// `X-Api-Version`.
+#[derive(Serialize, Debug)]
+pub struct VersionData {
+    pub version: String,
+    pub commit: Option<String>,
+    pub build: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VersionResponse {
+    pub status: String,
+    pub data: VersionData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DebugInfoResponse {
+    pub status: String,
+    pub data: DebugInfoData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MaintenanceData {
+    /// `None` while the API is not in maintenance.
+    pub maintenance: Option<Maintenance>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MaintenanceResponse {
+    pub status: String,
+    pub data: MaintenanceData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FeatureFlagListResponse {
+    pub status: String,
+    pub results: usize,
+    pub flags: Vec<FeatureFlag>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FeatureFlagData {
+    pub flag: FeatureFlag,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleFeatureFlagResponse {
+    pub status: String,
+    pub data: FeatureFlagData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkspaceData {
+    pub workspace: Workspace,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleWorkspaceResponse {
+    pub status: String,
+    pub data: WorkspaceData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkspaceListResponse {
+    pub status: String,
+    pub results: usize,
+    pub workspaces: Vec<Workspace>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleWorkspaceMemberResponse {
+    pub status: String,
+    pub member: WorkspaceMember,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkspaceMemberListResponse {
+    pub status: String,
+    pub results: usize,
+    pub members: Vec<WorkspaceMember>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleTodoShareResponse {
+    pub status: String,
+    pub share: TodoShare,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SharedTodo {
+    #[serde(flatten)]
+    pub todo: TodoRepresentation,
+    pub permission: SharePermission,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SharedTodoListResponse {
+    pub status: String,
+    pub results: usize,
+    pub todos: Vec<SharedTodo>,
+}
+
+/// A template with the variables its placeholders use.
+#[derive(Serialize, Debug)]
+pub struct TemplateSummary {
+    #[serde(flatten)]
+    pub template: Template,
+    pub variables: Vec<String>,
+}
+
+impl TemplateSummary {
+    pub fn new(template: Template) -> Self {
+        let variables = crate::templates::variables(&template)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        TemplateSummary {
+            template,
+            variables,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TemplateData {
+    pub template: TemplateSummary,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleTemplateResponse {
+    pub status: String,
+    pub data: TemplateData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TemplateListResponse {
+    pub status: String,
+    pub results: usize,
+    pub templates: Vec<TemplateSummary>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProjectData {
+    pub project: Project,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleProjectResponse {
+    pub status: String,
+    pub data: ProjectData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProjectListResponse {
+    pub status: String,
+    pub results: usize,
+    pub projects: Vec<Project>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommentData {
+    pub comment: Comment,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleCommentResponse {
+    pub status: String,
+    pub data: CommentData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommentListResponse {
+    pub status: String,
+    pub results: usize,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ActivityListResponse {
+    pub status: String,
+    pub results: usize,
+    pub activity: Vec<Activity>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AttachmentData {
+    pub attachment: Attachment,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SingleAttachmentResponse {
+    pub status: String,
+    pub data: AttachmentData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AttachmentListResponse {
+    pub status: String,
+    pub results: usize,
+    pub attachments: Vec<Attachment>,
+}
+
+/// A request the client sends straight to the blob store.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUrlData {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<PresignedRequest> for PresignedUrlData {
+    fn from(request: PresignedRequest) -> Self {
+        PresignedUrlData {
+            method: request.method,
+            url: request.url,
+            headers: request.headers.into_iter().collect(),
+            expires_at: request.expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUploadData {
+    pub attachment_id: String,
+    pub upload: PresignedUrlData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PresignedUploadResponse {
+    pub status: String,
+    pub data: PresignedUploadData,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PresignedDownloadResponse {
+    pub status: String,
+    pub data: PresignedUrlData,
 }
@@ -0,0 +1,256 @@
+//! Retention of what is kept about the past: the tombstones deleted todos
+//! leave in the change log for sync clients, and the activity log. Both
+//! are purged by a scheduled job once older than their retention, which a
+//! workspace can set differently, e.g. to keep its audit trail longer.
+//! Personal todos go by the defaults. Activity goes by the workspace its
+//! todo was last seen in by the change log.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::RetentionConfig;
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::model::{Activity, TodoChange, TodoId};
+use crate::repository::{ActivityRepository, ChangeRepository, RepositoryError};
+use crate::scheduler::Scheduler;
+
+/// What a purge removes, or would remove, in one workspace; personal
+/// todos have no workspace ID.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspacePurge {
+    pub workspace_id: Option<String>,
+    pub deleted_todos: usize,
+    pub activity: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeReport {
+    pub deleted_todos: usize,
+    pub activity: usize,
+    /// Workspaces with anything to purge.
+    pub workspaces: Vec<WorkspacePurge>,
+}
+
+/// The entries past their retention.
+struct Expired {
+    tombstones: Vec<TodoChange>,
+    activity: Vec<Activity>,
+    /// The workspace of each todo either belongs to.
+    workspaces: HashMap<TodoId, Option<String>>,
+}
+
+impl Expired {
+    fn report(&self) -> PurgeReport {
+        let mut workspaces: BTreeMap<Option<&str>, WorkspacePurge> = BTreeMap::new();
+        let tombstones = self
+            .tombstones
+            .iter()
+            .map(|tombstone| (tombstone.todo_id, true));
+        let activity = self
+            .activity
+            .iter()
+            .map(|activity| (activity.todo_id, false));
+        for (todo_id, tombstone) in tombstones.chain(activity) {
+            let workspace_id = self.workspaces.get(&todo_id).and_then(Option::as_deref);
+            let purge = workspaces
+                .entry(workspace_id)
+                .or_insert_with(|| WorkspacePurge {
+                    workspace_id: workspace_id.map(str::to_string),
+                    ..WorkspacePurge::default()
+                });
+            if tombstone {
+                purge.deleted_todos += 1;
+            } else {
+                purge.activity += 1;
+            }
+        }
+
+        PurgeReport {
+            deleted_todos: self.tombstones.len(),
+            activity: self.activity.len(),
+            workspaces: workspaces.into_values().collect(),
+        }
+    }
+}
+
+/// The retention of entries in `workspace_id`.
+fn retention_for(
+    default: Option<Duration>,
+    overrides: &HashMap<String, Option<Duration>>,
+    workspace_id: Option<&str>,
+) -> Option<Duration> {
+    workspace_id
+        .and_then(|id| overrides.get(id))
+        .copied()
+        .unwrap_or(default)
+}
+
+/// The time before which entries may be past their retention, under the
+/// shortest one; `None` when everything is kept for good.
+fn earliest_cutoff(
+    now: DateTime<Utc>,
+    default: Option<Duration>,
+    overrides: &HashMap<String, Option<Duration>>,
+) -> Option<DateTime<Utc>> {
+    let shortest = default
+        .into_iter()
+        .chain(overrides.values().flatten().copied())
+        .min()?;
+    Some(now - chrono::Duration::from_std(shortest).unwrap_or_default())
+}
+
+/// Whether an entry from `at` in `workspace_id` is past its retention.
+fn expired(
+    now: DateTime<Utc>,
+    at: DateTime<Utc>,
+    default: Option<Duration>,
+    overrides: &HashMap<String, Option<Duration>>,
+    workspace_id: Option<&str>,
+) -> bool {
+    retention_for(default, overrides, workspace_id).is_some_and(|retention| {
+        at < now - chrono::Duration::from_std(retention).unwrap_or_default()
+    })
+}
+
+/// Applies the configured retention, on schedule or as a dry run.
+#[derive(Clone)]
+pub struct Retention {
+    config: Arc<RetentionConfig>,
+    changes: Arc<dyn ChangeRepository>,
+    activity: Arc<dyn ActivityRepository>,
+}
+
+impl Retention {
+    /// Registers the purge on `jobs`, to run on the configured schedule.
+    pub fn new(
+        jobs: &JobQueue,
+        scheduler: &Scheduler,
+        config: &RetentionConfig,
+        changes: Arc<dyn ChangeRepository>,
+        activity: Arc<dyn ActivityRepository>,
+    ) -> Self {
+        let retention = Retention {
+            config: Arc::new(config.clone()),
+            changes,
+            activity,
+        };
+        jobs.register(Arc::new(RetentionPurgeJob {
+            retention: retention.clone(),
+        }));
+        scheduler.add(RetentionPurgeJob::KIND, config.schedule.clone());
+        retention
+    }
+
+    async fn find_expired(&self, now: DateTime<Utc>) -> Result<Expired, RepositoryError> {
+        let config = &self.config;
+        let mut workspaces = HashMap::new();
+
+        let mut tombstones = Vec::new();
+        if let Some(before) =
+            earliest_cutoff(now, config.deleted_todos, &config.workspace_deleted_todos)
+        {
+            for tombstone in self.changes.list_deleted(before).await? {
+                let workspace_id = tombstone.workspace_id.as_deref();
+                if expired(
+                    now,
+                    tombstone.changed_at,
+                    config.deleted_todos,
+                    &config.workspace_deleted_todos,
+                    workspace_id,
+                ) {
+                    workspaces.insert(tombstone.todo_id, tombstone.workspace_id.clone());
+                    tombstones.push(tombstone);
+                }
+            }
+        }
+
+        let mut activity = Vec::new();
+        if let Some(before) = earliest_cutoff(now, config.activity, &config.workspace_activity) {
+            for entry in self.activity.list_before(before).await? {
+                let workspace_id = match workspaces.entry(entry.todo_id) {
+                    Entry::Occupied(known) => known.into_mut(),
+                    Entry::Vacant(unknown) => {
+                        let change = self.changes.find(&entry.todo_id).await?;
+                        unknown.insert(change.and_then(|change| change.workspace_id))
+                    }
+                };
+                if expired(
+                    now,
+                    entry.occurred_at,
+                    config.activity,
+                    &config.workspace_activity,
+                    workspace_id.as_deref(),
+                ) {
+                    activity.push(entry);
+                }
+            }
+        }
+
+        Ok(Expired {
+            tombstones,
+            activity,
+            workspaces,
+        })
+    }
+
+    /// What a purge would remove now, without removing it.
+    pub async fn preview(&self) -> Result<PurgeReport, RepositoryError> {
+        Ok(self.find_expired(Utc::now()).await?.report())
+    }
+
+    /// Removes everything past its retention.
+    pub async fn purge(&self) -> Result<PurgeReport, RepositoryError> {
+        let expired = self.find_expired(Utc::now()).await?;
+        // Activity first: should deleting it fail, the next purge still
+        // finds its workspaces through the tombstones.
+        self.activity.delete(&expired.activity).await?;
+        let ids: Vec<TodoId> = expired
+            .tombstones
+            .iter()
+            .map(|tombstone| tombstone.todo_id)
+            .collect();
+        self.changes.delete(&ids).await?;
+        Ok(expired.report())
+    }
+}
+
+/// Job running [`Retention::purge`] on the purge schedule.
+pub struct RetentionPurgeJob {
+    pub retention: Retention,
+}
+
+impl RetentionPurgeJob {
+    pub const KIND: &'static str = "retention.purge";
+}
+
+#[async_trait]
+impl Job for RetentionPurgeJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let report = self.retention.purge().await?;
+        if report.deleted_todos > 0 || report.activity > 0 {
+            log::info!(
+                "event=retention_purged deleted_todos={} activity={}",
+                report.deleted_todos,
+                report.activity
+            );
+        }
+        Ok(())
+    }
+
+    /// The next scheduled purge is the retry.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+}
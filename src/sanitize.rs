@@ -0,0 +1,55 @@
+//! Cleaning of todo titles and content before they are stored, as several
+//! clients put them straight into web pages. Markup outside the configured
+//! allowlist is removed, along with scripts, styles, event handlers and
+//! `javascript:` links, which are never allowed.
+//!
+//! Only values containing `<` can hold markup, so only those are cleaned;
+//! the rest are stored as sent. A cleaned value is HTML, so characters
+//! such as `&` in it come back escaped.
+
+use std::collections::HashSet;
+
+use crate::config::SanitizeConfig;
+
+/// Tags dropped with their contents whatever the allowlist says.
+const NEVER_ALLOWED: [&str; 2] = ["script", "style"];
+
+pub struct Sanitizer {
+    enabled: bool,
+    title_tags: HashSet<String>,
+    content_tags: HashSet<String>,
+}
+
+impl Sanitizer {
+    pub fn new(config: &SanitizeConfig) -> Self {
+        let allowed = |tags: &[String]| {
+            tags.iter()
+                .filter(|tag| !NEVER_ALLOWED.contains(&tag.as_str()))
+                .cloned()
+                .collect()
+        };
+        Sanitizer {
+            enabled: config.enabled,
+            title_tags: allowed(&config.title_tags),
+            content_tags: allowed(&config.content_tags),
+        }
+    }
+
+    pub fn title(&self, title: String) -> String {
+        self.clean(title, &self.title_tags)
+    }
+
+    pub fn content(&self, content: String) -> String {
+        self.clean(content, &self.content_tags)
+    }
+
+    fn clean(&self, value: String, tags: &HashSet<String>) -> String {
+        if !self.enabled || !value.contains('<') {
+            return value;
+        }
+        ammonia::Builder::default()
+            .tags(tags.iter().map(String::as_str).collect())
+            .clean(&value)
+            .to_string()
+    }
+}
@@ -0,0 +1,150 @@
+//! Periodic jobs run on cron schedules from the config, such as
+//! `0 30 3 * * *` for 03:30 UTC every day; seconds come first, though
+//! config also takes the usual five fields. A run is skipped while the job
+//! the previous run queued has not finished, so a slow job never runs
+//! twice at once. Each schedule's last run and skipped runs are kept for
+//! operators.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::Serialize;
+
+use crate::jobs::JobQueue;
+use crate::model::JobRecord;
+use crate::repository::RepositoryError;
+
+/// A schedule and how its runs went.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleStatus {
+    pub kind: String,
+    pub schedule: String,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// The job the last run queued, as last saved; `None` before the first
+    /// run and once the job has been purged.
+    pub last_job: Option<JobRecord>,
+    /// Runs skipped because the previous job was still going.
+    pub skipped_runs: u64,
+}
+
+struct Entry {
+    kind: &'static str,
+    schedule: Schedule,
+    runs: Mutex<Runs>,
+}
+
+#[derive(Default)]
+struct Runs {
+    last_run_at: Option<DateTime<Utc>>,
+    last_job_id: Option<String>,
+    skipped: u64,
+}
+
+/// Queues jobs on their cron schedules.
+#[derive(Clone)]
+pub struct Scheduler {
+    queue: JobQueue,
+    entries: Arc<RwLock<Vec<Arc<Entry>>>>,
+}
+
+impl Scheduler {
+    pub fn new(queue: JobQueue) -> Self {
+        Scheduler {
+            queue,
+            entries: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Enqueues a `kind` job with an empty payload at every time of
+    /// `schedule`, in UTC. Register the job on the queue as well.
+    pub fn add(&self, kind: &'static str, schedule: Schedule) {
+        let entry = Arc::new(Entry {
+            kind,
+            schedule,
+            runs: Mutex::new(Runs::default()),
+        });
+        self.entries.write().unwrap().push(entry.clone());
+
+        let queue = self.queue.clone();
+        tokio::spawn(async move {
+            let mut after = Utc::now();
+            while let Some(next) = entry.schedule.after(&after).next() {
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(wait).await;
+                run(&queue, &entry).await;
+                after = next.max(Utc::now());
+            }
+        });
+    }
+
+    /// Every schedule, in the order they were added.
+    pub async fn statuses(&self) -> Result<Vec<ScheduleStatus>, RepositoryError> {
+        let entries = self.entries.read().unwrap().clone();
+        let mut statuses = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (last_run_at, last_job_id, skipped_runs) = {
+                let runs = entry.runs.lock().unwrap();
+                (runs.last_run_at, runs.last_job_id.clone(), runs.skipped)
+            };
+            let last_job = match last_job_id {
+                Some(id) => self.queue.find(&id).await?,
+                None => None,
+            };
+            statuses.push(ScheduleStatus {
+                kind: entry.kind.to_string(),
+                schedule: entry.schedule.to_string(),
+                next_run_at: entry.schedule.upcoming(Utc).next(),
+                last_run_at,
+                last_job,
+                skipped_runs,
+            });
+        }
+        Ok(statuses)
+    }
+}
+
+/// Queues `entry`'s job unless the one queued last time is unfinished.
+async fn run(queue: &JobQueue, entry: &Entry) {
+    if !queue.runs_here(entry.kind) {
+        return;
+    }
+    let previous = entry.runs.lock().unwrap().last_job_id.clone();
+    if let Some(id) = previous {
+        match queue.find(&id).await {
+            Ok(Some(record)) if !record.status.is_finished() => {
+                entry.runs.lock().unwrap().skipped += 1;
+                log::warn!(
+                    "event=scheduled_run_skipped kind={} running_job_id={}",
+                    entry.kind,
+                    id
+                );
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!(
+                    "event=job_schedule_failed kind={} error=\"{}\"",
+                    entry.kind,
+                    e
+                );
+                return;
+            }
+        }
+    }
+
+    match queue.enqueue(entry.kind, serde_json::Value::Null).await {
+        Ok(id) => {
+            let mut runs = entry.runs.lock().unwrap();
+            runs.last_run_at = Some(Utc::now());
+            runs.last_job_id = Some(id);
+        }
+        Err(e) => log::warn!(
+            "event=job_schedule_failed kind={} error=\"{}\"",
+            entry.kind,
+            e
+        ),
+    }
+}
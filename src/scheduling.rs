@@ -0,0 +1,286 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Months, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::model::{Todo, TodoId};
+use crate::repository::TodoRepository;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// How often a todo repeats, e.g. `{"frequency": "weekly", "interval": 2}`
+/// for every other week. Also accepted as `{"every": "P2W"}`, the form
+/// version 2 of the API uses.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "RecurrenceInput")]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    #[serde(default = "default_interval")]
+    pub interval: u32,
+    /// No occurrences are generated after this instant.
+    pub until: Option<DateTime<Utc>>,
+}
+
+fn default_interval() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecurrenceInput {
+    Fields {
+        frequency: Frequency,
+        #[serde(default = "default_interval")]
+        interval: u32,
+        until: Option<DateTime<Utc>>,
+    },
+    Duration {
+        every: String,
+        until: Option<DateTime<Utc>>,
+    },
+}
+
+impl TryFrom<RecurrenceInput> for Recurrence {
+    type Error = String;
+
+    fn try_from(input: RecurrenceInput) -> Result<Self, Self::Error> {
+        match input {
+            RecurrenceInput::Fields {
+                frequency,
+                interval,
+                until,
+            } => Ok(Recurrence {
+                frequency,
+                interval,
+                until,
+            }),
+            RecurrenceInput::Duration { every, until } => {
+                let (frequency, interval) = parse_iso_duration(&every)?;
+                Ok(Recurrence {
+                    frequency,
+                    interval,
+                    until,
+                })
+            }
+        }
+    }
+}
+
+/// Parses the single-unit ISO 8601 durations a recurrence can express:
+/// `P<n>D`, `P<n>W` and `P<n>M`.
+fn parse_iso_duration(value: &str) -> Result<(Frequency, u32), String> {
+    let invalid = || {
+        format!(
+            "recurrence.every must be an ISO 8601 duration such as P1D, P2W or P1M, got '{}'",
+            value
+        )
+    };
+    let rest = value
+        .strip_prefix('P')
+        .or_else(|| value.strip_prefix('p'))
+        .ok_or_else(invalid)?;
+    let unit = rest.chars().last().ok_or_else(invalid)?;
+    let interval: u32 = rest[..rest.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let frequency = match unit.to_ascii_uppercase() {
+        'D' => Frequency::Daily,
+        'W' => Frequency::Weekly,
+        'M' => Frequency::Monthly,
+        _ => return Err(invalid()),
+    };
+    Ok((frequency, interval))
+}
+
+impl Recurrence {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err("recurrence.interval must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// The interval as an ISO 8601 duration, e.g. `P2W`.
+    pub fn iso_duration(&self) -> String {
+        let unit = match self.frequency {
+            Frequency::Daily => 'D',
+            Frequency::Weekly => 'W',
+            Frequency::Monthly => 'M',
+        };
+        format!("P{}{}", self.interval, unit)
+    }
+
+    /// The occurrence following `from`, or `None` once the series has ended.
+    pub fn next_after(&self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = match self.frequency {
+            Frequency::Daily => {
+                from.checked_add_signed(chrono::Duration::days(self.interval as i64))
+            }
+            Frequency::Weekly => {
+                from.checked_add_signed(chrono::Duration::weeks(self.interval as i64))
+            }
+            Frequency::Monthly => from.checked_add_months(Months::new(self.interval)),
+        }?;
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+
+    /// Up to `count` occurrences after `from`.
+    pub fn upcoming(&self, from: DateTime<Utc>, count: usize) -> Vec<DateTime<Utc>> {
+        let mut dates = Vec::with_capacity(count);
+        let mut current = from;
+        while dates.len() < count {
+            match self.next_after(current) {
+                Some(next) => {
+                    dates.push(next);
+                    current = next;
+                }
+                None => break,
+            }
+        }
+        dates
+    }
+}
+
+/// The point a todo's next occurrence is computed from.
+pub fn anchor(todo: &Todo) -> Option<DateTime<Utc>> {
+    todo.due_at.or(todo.created_at)
+}
+
+/// Handle used by handlers to run the recurrence sweep as soon as a
+/// recurring todo is completed, instead of waiting for the next scheduled one.
+#[derive(Clone)]
+pub struct RecurrenceScheduler {
+    jobs: JobQueue,
+}
+
+impl RecurrenceScheduler {
+    /// Registers the sweep job and schedules it every `interval`, so
+    /// completions missed by a restart are still picked up.
+    pub fn new(jobs: JobQueue, repository: Arc<dyn TodoRepository>, interval: Duration) -> Self {
+        jobs.register(Arc::new(RecurrenceSweepJob {
+            repository,
+            running: Mutex::new(()),
+        }));
+        jobs.every(RecurrenceSweepJob::KIND, interval);
+        RecurrenceScheduler { jobs }
+    }
+
+    /// Has the sweep run now rather than at its next interval, on the
+    /// leader only so sweeps of different instances never overlap.
+    pub async fn wake(&self) {
+        if !self.jobs.runs_here(RecurrenceSweepJob::KIND) {
+            return;
+        }
+        if let Err(e) = self
+            .jobs
+            .enqueue(RecurrenceSweepJob::KIND, serde_json::Value::Null)
+            .await
+        {
+            log::warn!("event=recurrence_wake_failed error=\"{}\"", e);
+        }
+    }
+}
+
+/// Job that materializes the next occurrence of every completed recurring todo.
+struct RecurrenceSweepJob {
+    repository: Arc<dyn TodoRepository>,
+    /// Sweeps must not overlap, or both would create the same occurrence.
+    running: Mutex<()>,
+}
+
+impl RecurrenceSweepJob {
+    const KIND: &'static str = "recurrence.sweep";
+}
+
+#[async_trait]
+impl Job for RecurrenceSweepJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let _running = self.running.lock().await;
+        materialize_pending(self.repository.as_ref()).await?;
+        Ok(())
+    }
+
+    /// The next scheduled sweep is the retry.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+}
+
+/// Creates the next occurrence for each completed recurring todo that does
+/// not have one yet. Returns how many occurrences were created.
+pub async fn materialize_pending(
+    repository: &dyn TodoRepository,
+) -> Result<usize, crate::repository::RepositoryError> {
+    let mut created = 0;
+
+    for completed in repository.pending_recurrences().await? {
+        let (Some(recurrence), Some(from)) = (completed.recurrence.clone(), anchor(&completed))
+        else {
+            continue;
+        };
+        let Some(due_at) = recurrence.next_after(from) else {
+            continue;
+        };
+
+        let id = TodoId::generate();
+        let series_id = completed.series_id.or(completed.id).unwrap_or(id);
+        let now = Utc::now();
+        let next = Todo {
+            id: Some(id),
+            title: completed.title.clone(),
+            content: completed.content.clone(),
+            completed: Some(false),
+            archived: Some(false),
+            due_at: Some(due_at),
+            recurrence: Some(recurrence),
+            series_id: Some(series_id),
+            next_occurrence_id: None,
+            remind_at: None,
+            reminder_sent_at: None,
+            owner_id: completed.owner_id.clone(),
+            workspace_id: completed.workspace_id.clone(),
+            assignee_id: completed.assignee_id.clone(),
+            project_id: completed.project_id.clone(),
+            position: completed.position,
+            status: None,
+            created_at: Some(now),
+            updated_at: Some(now),
+            expires_at: None,
+            comment_count: None,
+            ttl_seconds: None,
+            content_html: None,
+        };
+        repository.insert(&next).await?;
+
+        let mut completed = completed;
+        completed.next_occurrence_id = Some(id);
+        repository.update(&completed).await?;
+
+        log::info!(
+            "event=recurrence_materialized series_id={} occurrence_id={} due_at={}",
+            series_id,
+            id,
+            due_at.to_rfc3339()
+        );
+        created += 1;
+    }
+
+    Ok(created)
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{SecretsError, SecretsProvider};
+use crate::config::AwsSecretsConfig;
+
+const SERVICE: &str = "secretsmanager";
+const TARGET: &str = "secretsmanager.GetSecretValue";
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetSecretValueResponse {
+    secret_string: Option<String>,
+}
+
+/// Reads the secrets from one AWS Secrets Manager secret holding a JSON
+/// object keyed by secret name. Requests are signed with the credentials
+/// in `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and, for temporary
+/// ones, `AWS_SESSION_TOKEN`.
+pub struct AwsSecretsManager {
+    http: reqwest::Client,
+    region: String,
+    secret_id: String,
+}
+
+impl AwsSecretsManager {
+    pub fn new(config: &AwsSecretsConfig) -> Result<Self, SecretsError> {
+        let region = config
+            .region
+            .clone()
+            .ok_or_else(|| SecretsError("AWS_REGION must be set".to_string()))?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| SecretsError(e.to_string()))?;
+
+        Ok(AwsSecretsManager {
+            http,
+            region,
+            secret_id: config.secret_id.clone(),
+        })
+    }
+
+    /// The `Authorization` header of a request, per AWS Signature Version 4.
+    /// `headers` must be lowercase and sorted, and include `host`.
+    fn authorization(
+        &self,
+        credentials: &Credentials,
+        amz_date: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> String {
+        let date = &amz_date[..8];
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            headers
+                .iter()
+                .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+                .collect::<String>(),
+            signed_headers,
+            hex::encode(Sha256::digest(body.as_bytes()))
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, SERVICE);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [self.region.as_str(), SERVICE, "aws4_request"].iter().fold(
+            hmac(
+                format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+                date,
+            ),
+            |key, part| hmac(&key, part),
+        );
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key_id,
+            scope,
+            signed_headers,
+            hex::encode(hmac(&key, &string_to_sign))
+        )
+    }
+}
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Read on every request, so refreshed credentials are picked up.
+    fn from_env() -> Result<Self, SecretsError> {
+        let var = |key: &str| {
+            crate::config::env_opt(key).ok_or_else(|| SecretsError(format!("{} must be set", key)))
+        };
+        Ok(Credentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: crate::config::env_opt("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[async_trait]
+impl SecretsProvider for AwsSecretsManager {
+    fn name(&self) -> &'static str {
+        "aws"
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, String>, SecretsError> {
+        let credentials = Credentials::from_env()?;
+        let host = format!("{}.{}.amazonaws.com", SERVICE, self.region);
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let body = serde_json::json!({ "SecretId": self.secret_id }).to_string();
+
+        let mut headers = vec![
+            ("content-type", CONTENT_TYPE),
+            ("host", host.as_str()),
+            ("x-amz-date", amz_date.as_str()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.as_str()));
+        }
+        headers.push(("x-amz-target", TARGET));
+        let authorization = self.authorization(&credentials, &amz_date, &headers, &body);
+
+        let mut request = self
+            .http
+            .post(format!("https://{}/", host))
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, *value);
+        }
+        let failed =
+            |e: reqwest::Error| SecretsError(format!("Secrets Manager request failed: {}", e));
+        let response: GetSecretValueResponse = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(failed)?
+            .json()
+            .await
+            .map_err(failed)?;
+
+        let secret = response.secret_string.ok_or_else(|| {
+            SecretsError(format!("Secret {} has no string value", self.secret_id))
+        })?;
+        let values: HashMap<String, serde_json::Value> = serde_json::from_str(&secret)
+            .map_err(|_| SecretsError(format!("Secret {} is not a JSON object", self.secret_id)))?;
+        Ok(values
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                serde_json::Value::String(value) => Some((name, value)),
+                _ => None,
+            })
+            .collect())
+    }
+}
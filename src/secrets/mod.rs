@@ -0,0 +1,169 @@
+//! Credentials read from a secrets manager instead of plain env vars: the
+//! Scylla login, the JWT signing secret and the SMTP password. Providers
+//! are asked again periodically, and whoever holds a secret is told when
+//! it changes, so rotated secrets apply without a restart.
+
+mod aws;
+mod vault;
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+
+use crate::config::{Config, SecretsBackend, SecretsConfig};
+
+pub use self::aws::AwsSecretsManager;
+pub use self::vault::VaultSecrets;
+
+/// Name of the Scylla user to log in as; without it, no login is sent.
+pub const SCYLLA_USERNAME: &str = "scylla_username";
+pub const SCYLLA_PASSWORD: &str = "scylla_password";
+/// Key signing access tokens.
+pub const JWT_SECRET: &str = "jwt_secret";
+pub const SMTP_PASSWORD: &str = "smtp_password";
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct SecretsError(pub String);
+
+/// Somewhere secrets are kept, by name.
+#[async_trait]
+pub trait SecretsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The current secrets. Names the provider does not hold are left out.
+    async fn fetch(&self) -> Result<HashMap<String, String>, SecretsError>;
+}
+
+/// Reads the secrets from env vars, as before secrets managers were
+/// supported.
+pub struct EnvSecrets;
+
+#[async_trait]
+impl SecretsProvider for EnvSecrets {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, String>, SecretsError> {
+        Ok([
+            (SCYLLA_USERNAME, "SCYLLA_USERNAME"),
+            (SCYLLA_PASSWORD, "SCYLLA_PASSWORD"),
+            (JWT_SECRET, "AUTH_JWT_SECRET"),
+            (SMTP_PASSWORD, "SMTP_PASSWORD"),
+        ]
+        .into_iter()
+        .filter_map(|(name, key)| Some((name.to_string(), crate::config::env_opt(key)?)))
+        .collect())
+    }
+}
+
+type Watcher = Box<dyn Fn(&str) + Send + Sync>;
+
+/// The latest secrets, shared by everything that uses one.
+#[derive(Clone, Default)]
+pub struct Secrets {
+    values: Arc<RwLock<HashMap<String, String>>>,
+    watchers: Arc<RwLock<Vec<(String, Watcher)>>>,
+}
+
+impl Secrets {
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Secrets {
+            values: Arc::new(RwLock::new(values)),
+            watchers: Arc::default(),
+        }
+    }
+
+    /// Reads the secrets from the configured provider and, unless the
+    /// interval is zero or they come from env vars, reads them again every
+    /// `refresh_interval`.
+    pub async fn load(config: &SecretsConfig) -> Result<Self, SecretsError> {
+        let provider: Arc<dyn SecretsProvider> = match config.backend {
+            SecretsBackend::Env => Arc::new(EnvSecrets),
+            SecretsBackend::Vault => Arc::new(VaultSecrets::new(&config.vault)?),
+            SecretsBackend::Aws => Arc::new(AwsSecretsManager::new(&config.aws)?),
+        };
+        let values = provider.fetch().await.map_err(|e| {
+            SecretsError(format!(
+                "Failed to read secrets from {}: {}",
+                provider.name(),
+                e
+            ))
+        })?;
+        log::info!(
+            "event=secrets_loaded provider={} count={}",
+            provider.name(),
+            values.len()
+        );
+        let secrets = Secrets::new(values);
+
+        if config.backend != SecretsBackend::Env && !config.refresh_interval.is_zero() {
+            let secrets = secrets.clone();
+            let interval = config.refresh_interval;
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    match provider.fetch().await {
+                        Ok(values) => secrets.update(values),
+                        // The secrets in use stay valid until the provider answers again.
+                        Err(e) => log::warn!(
+                            "event=secrets_refresh_failed provider={} error=\"{}\"",
+                            provider.name(),
+                            e
+                        ),
+                    }
+                }
+            });
+        }
+
+        Ok(secrets)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values.read().unwrap().get(name).cloned()
+    }
+
+    /// Calls `watcher` with the new value each time secret `name` changes.
+    /// A secret that disappears keeps its last value.
+    pub fn watch(&self, name: &str, watcher: impl Fn(&str) + Send + Sync + 'static) {
+        self.watchers
+            .write()
+            .unwrap()
+            .push((name.to_string(), Box::new(watcher)));
+    }
+
+    /// Takes freshly read `values`, telling the watchers of those that
+    /// changed.
+    pub fn update(&self, values: HashMap<String, String>) {
+        let mut changed = Vec::new();
+        {
+            let mut current = self.values.write().unwrap();
+            for (name, value) in values {
+                if current.get(&name) != Some(&value) {
+                    current.insert(name.clone(), value.clone());
+                    changed.push((name, value));
+                }
+            }
+        }
+
+        let watchers = self.watchers.read().unwrap();
+        for (name, value) in changed {
+            log::info!("event=secret_rotated name={}", name);
+            for (_, watcher) in watchers.iter().filter(|(watched, _)| *watched == name) {
+                watcher(&value);
+            }
+        }
+    }
+
+    /// Puts the secrets into `config`, over what env vars set.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(secret) = self.get(JWT_SECRET) {
+            config.auth.jwt_secret = Some(secret);
+        }
+        if let (Some(smtp), Some(password)) = (&mut config.notifier.smtp, self.get(SMTP_PASSWORD)) {
+            smtp.password = Some(password);
+        }
+    }
+}
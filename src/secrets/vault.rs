@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{SecretsError, SecretsProvider};
+use crate::config::VaultConfig;
+
+#[derive(Deserialize)]
+struct KvResponse {
+    data: KvData,
+}
+
+#[derive(Deserialize)]
+struct KvData {
+    data: HashMap<String, serde_json::Value>,
+}
+
+/// Reads the secrets from one entry of a HashiCorp Vault KV v2 engine,
+/// whose keys are the secret names.
+pub struct VaultSecrets {
+    http: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl VaultSecrets {
+    pub fn new(config: &VaultConfig) -> Result<Self, SecretsError> {
+        let token = config
+            .token
+            .clone()
+            .ok_or_else(|| SecretsError("VAULT_TOKEN must be set".to_string()))?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| SecretsError(e.to_string()))?;
+
+        Ok(VaultSecrets {
+            http,
+            url: format!(
+                "{}/v1/{}/data/{}",
+                config.address.trim_end_matches('/'),
+                config.mount.trim_matches('/'),
+                config.path.trim_matches('/')
+            ),
+            token,
+        })
+    }
+}
+
+#[async_trait]
+impl SecretsProvider for VaultSecrets {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, String>, SecretsError> {
+        let failed = |e: reqwest::Error| SecretsError(format!("Vault request failed: {}", e));
+        let response: KvResponse = self
+            .http
+            .get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(failed)?
+            .json()
+            .await
+            .map_err(failed)?;
+
+        Ok(response
+            .data
+            .data
+            .into_iter()
+            .filter_map(|(name, value)| match value {
+                serde_json::Value::String(value) => Some((name, value)),
+                _ => None,
+            })
+            .collect())
+    }
+}
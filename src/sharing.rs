@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+use crate::auth::AuthUser;
+use crate::error::AppError;
+use crate::model::{AppState, SharePermission, Todo, TodoId};
+use crate::repository::{TodoScope, TodoVisibility};
+
+/// What a request wants to do with a single todo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoAccess {
+    Read,
+    Write,
+    /// Deleting; never granted through a share.
+    Owner,
+}
+
+impl TodoAccess {
+    fn granted_by(self, permission: SharePermission) -> bool {
+        match self {
+            TodoAccess::Read => true,
+            TodoAccess::Write => permission == SharePermission::Write,
+            TodoAccess::Owner => false,
+        }
+    }
+}
+
+/// Loads a todo the caller may access in the requested way.
+///
+/// Todos in the request's scope are fully accessible when they belong to
+/// the workspace (membership was checked when the scope was extracted),
+/// have no owner, or are owned by the caller. Anything else needs a share
+/// with the caller. Callers with no access at all get a 404; collaborators
+/// whose permission is too weak get a 403.
+pub async fn authorize(
+    data: &AppState,
+    id: &TodoId,
+    scope: &TodoScope,
+    user: Option<&AuthUser>,
+    access: TodoAccess,
+) -> Result<Todo, AppError> {
+    let not_found = || AppError::NotFound(format!("Todo with ID: {} not found", id));
+
+    let todo = data.todos.find_by_id(id).await?.ok_or_else(not_found)?;
+
+    let owns = match &todo.owner_id {
+        None => true,
+        Some(owner_id) => user.is_some_and(|user| &user.id == owner_id),
+    };
+    if scope.contains(&todo) && (todo.workspace_id.is_some() || owns) {
+        return Ok(todo);
+    }
+
+    let Some(user) = user else {
+        return Err(not_found());
+    };
+    match data.acl.find(id, &user.id).await? {
+        Some(share) if access.granted_by(share.permission) => Ok(todo),
+        Some(share) => Err(AppError::Forbidden(format!(
+            "Todo with ID: {} is shared with you as {} only",
+            id,
+            share.permission.as_str()
+        ))),
+        None => Err(not_found()),
+    }
+}
+
+/// The todos among `ids` the caller may access in the requested way, each
+/// once and in the order given. Those [`authorize`] refuses are left out,
+/// so callers report them like todos that do not exist.
+pub async fn authorize_all(
+    data: &AppState,
+    ids: &[TodoId],
+    scope: &TodoScope,
+    user: Option<&AuthUser>,
+    access: TodoAccess,
+) -> Result<Vec<Todo>, AppError> {
+    let mut seen = HashSet::with_capacity(ids.len());
+    let unique: Vec<TodoId> = ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+
+    let checks = unique
+        .iter()
+        .map(|id| authorize(data, id, scope, user, access));
+    let mut todos = Vec::with_capacity(unique.len());
+    for result in futures_util::future::join_all(checks).await {
+        match result {
+            Ok(todo) => todos.push(todo),
+            Err(AppError::NotFound(_) | AppError::Forbidden(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(todos)
+}
+
+/// What listings show the caller: the todos [`authorize`] would let them
+/// read, shares included.
+pub async fn visibility(
+    data: &AppState,
+    user: Option<&AuthUser>,
+) -> Result<TodoVisibility, AppError> {
+    let shared = match user {
+        Some(user) => data
+            .acl
+            .list_for_user(&user.id)
+            .await?
+            .into_iter()
+            .map(|share| share.todo_id)
+            .collect(),
+        None => Vec::new(),
+    };
+    Ok(TodoVisibility::Caller {
+        user_id: user.map(|user| user.id.clone()),
+        shared,
+    })
+}
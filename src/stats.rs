@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::model::Todo;
+use crate::repository::{ListOptions, TodoRepository, TodoScope, TodoSort, TodoVisibility};
+
+/// Days of per-day history kept in a snapshot; the longest window or trend
+/// that can be requested.
+pub const MAX_STATS_DAYS: u64 = 365;
+
+/// Todo counts from one aggregation pass.
+#[derive(Debug, Clone, Default)]
+pub struct StatsSnapshot {
+    pub generated_at: DateTime<Utc>,
+    /// Counts exclude archived todos, which are only reported in `archived`.
+    pub open: usize,
+    pub completed: usize,
+    /// Open todos whose due date had passed when the snapshot was taken.
+    pub overdue: usize,
+    pub archived: usize,
+    days: BTreeMap<NaiveDate, DayCounts>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct DayCounts {
+    /// Todos created on this day.
+    created: usize,
+    /// Of the todos created on this day, how many are completed now.
+    created_completed: usize,
+    /// Todos completed on this day, going by their last update.
+    completed: usize,
+}
+
+/// Completion rate of the todos created in the last `days` days.
+#[derive(Debug, Clone, Copy)]
+pub struct CompletionWindow {
+    pub days: u64,
+    pub created: usize,
+    pub completed: usize,
+    /// `None` when nothing was created in the window.
+    pub rate: Option<f64>,
+}
+
+/// Created and completed counts for one day.
+#[derive(Debug, Clone, Copy)]
+pub struct DailyTrend {
+    pub date: NaiveDate,
+    pub created: usize,
+    pub completed: usize,
+}
+
+impl StatsSnapshot {
+    /// Aggregates `todos` as of `now`.
+    pub fn compute(todos: &[Todo], now: DateTime<Utc>) -> StatsSnapshot {
+        let oldest = now.date_naive() - Days::new(MAX_STATS_DAYS);
+        let mut snapshot = StatsSnapshot {
+            generated_at: now,
+            ..StatsSnapshot::default()
+        };
+
+        for todo in todos {
+            let completed = todo.completed.unwrap_or(false);
+            if todo.archived.unwrap_or(false) {
+                snapshot.archived += 1;
+            } else if completed {
+                snapshot.completed += 1;
+            } else {
+                snapshot.open += 1;
+                if todo.due_at.is_some_and(|due_at| due_at < now) {
+                    snapshot.overdue += 1;
+                }
+            }
+
+            if let Some(created_at) = todo.created_at.map(|at| at.date_naive()) {
+                if created_at > oldest {
+                    let day = snapshot.days.entry(created_at).or_default();
+                    day.created += 1;
+                    if completed {
+                        day.created_completed += 1;
+                    }
+                }
+            }
+            if completed {
+                if let Some(completed_at) = todo.updated_at.map(|at| at.date_naive()) {
+                    if completed_at > oldest {
+                        snapshot.days.entry(completed_at).or_default().completed += 1;
+                    }
+                }
+            }
+        }
+
+        snapshot
+    }
+
+    /// Completion rate over the last `days` days, counting today.
+    pub fn total(&self) -> usize {
+        self.open + self.completed + self.archived
+    }
+
+    pub fn completion_window(&self, days: u64) -> CompletionWindow {
+        let (created, completed) =
+            self.recent_days(days)
+                .fold((0, 0), |(created, completed), (_, counts)| {
+                    (
+                        created + counts.created,
+                        completed + counts.created_completed,
+                    )
+                });
+
+        CompletionWindow {
+            days,
+            created,
+            completed,
+            rate: (created > 0).then(|| completed as f64 / created as f64),
+        }
+    }
+
+    /// One entry per day for the last `days` days, oldest first, including
+    /// days with no activity.
+    pub fn trend(&self, days: u64) -> Vec<DailyTrend> {
+        let today = self.generated_at.date_naive();
+        (0..days.min(MAX_STATS_DAYS))
+            .rev()
+            .map(|offset| {
+                let date = today - Days::new(offset);
+                let counts = self.days.get(&date).copied().unwrap_or_default();
+                DailyTrend {
+                    date,
+                    created: counts.created,
+                    completed: counts.completed,
+                }
+            })
+            .collect()
+    }
+
+    fn recent_days(&self, days: u64) -> impl Iterator<Item = (&NaiveDate, &DayCounts)> {
+        let today = self.generated_at.date_naive();
+        let first = today - Days::new(days.clamp(1, MAX_STATS_DAYS) - 1);
+        self.days.range(first..=today)
+    }
+}
+
+/// Serves todo statistics from a snapshot that a background job refreshes,
+/// so requests never scan the todo table themselves.
+#[derive(Clone)]
+pub struct TodoStats {
+    jobs: JobQueue,
+    snapshot: Arc<RwLock<Option<Arc<StatsSnapshot>>>>,
+}
+
+impl TodoStats {
+    /// Registers the refresh job and schedules it every `interval`.
+    pub fn new(jobs: JobQueue, repository: Arc<dyn TodoRepository>, interval: Duration) -> Self {
+        let snapshot = Arc::new(RwLock::new(None));
+        jobs.register(Arc::new(StatsRefreshJob {
+            repository,
+            snapshot: snapshot.clone(),
+        }));
+        jobs.every(StatsRefreshJob::KIND, interval);
+        TodoStats { jobs, snapshot }
+    }
+
+    /// The latest snapshot, or `None` until the first refresh has finished.
+    pub fn snapshot(&self) -> Option<Arc<StatsSnapshot>> {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    /// Queues a refresh ahead of the schedule.
+    pub async fn refresh(&self) {
+        if let Err(e) = self
+            .jobs
+            .enqueue(StatsRefreshJob::KIND, serde_json::Value::Null)
+            .await
+        {
+            log::warn!("event=stats_refresh_enqueue_failed error=\"{}\"", e);
+        }
+    }
+}
+
+/// Job that recomputes the statistics snapshot from every stored todo.
+struct StatsRefreshJob {
+    repository: Arc<dyn TodoRepository>,
+    snapshot: Arc<RwLock<Option<Arc<StatsSnapshot>>>>,
+}
+
+impl StatsRefreshJob {
+    const KIND: &'static str = "stats.refresh";
+}
+
+#[async_trait]
+impl Job for StatsRefreshJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let todos = self
+            .repository
+            .list(&ListOptions {
+                offset: 0,
+                // Every todo; SQL backends bind the limit as an i64.
+                limit: i64::MAX as usize,
+                include_archived: true,
+                scope: TodoScope::All,
+                visibility: TodoVisibility::All,
+                owner_id: None,
+                created_after: None,
+                created_before: None,
+                project_id: None,
+                status: None,
+                assignee_id: None,
+                completed: None,
+                due_after: None,
+                due_before: None,
+                sort: TodoSort::CreatedAt,
+            })
+            .await?;
+
+        let snapshot = StatsSnapshot::compute(&todos, Utc::now());
+        *self.snapshot.write().unwrap() = Some(Arc::new(snapshot));
+        Ok(())
+    }
+
+    /// The next scheduled refresh is the retry.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+
+    /// Every instance serves statistics from its own snapshot.
+    fn per_instance(&self) -> bool {
+        true
+    }
+}
+
+/// Job that rebuilds the per-owner todo counters from the todos, repairing
+/// any drift from failed counter updates or todos expiring through a TTL.
+pub struct CountReconcileJob {
+    pub repository: Arc<dyn TodoRepository>,
+}
+
+impl CountReconcileJob {
+    pub const KIND: &'static str = "stats.reconcile_counts";
+}
+
+#[async_trait]
+impl Job for CountReconcileJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        let corrected = self.repository.reconcile_counts().await?;
+        if corrected > 0 {
+            log::warn!("event=todo_counts_reconciled corrected={}", corrected);
+        }
+        Ok(())
+    }
+
+    /// The next scheduled run is the retry.
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+}
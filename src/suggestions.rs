@@ -0,0 +1,93 @@
+//! Picks the todo to work on next for `GET /todos/next`. Every open todo
+//! gets a score from three signals, each between 0 and 1, weighted by
+//! [`SuggestionWeights`]:
+//!
+//! - priority: 1 for todos already in progress, as finishing beats
+//!   starting; otherwise from the manual order, just under 1 for the first
+//!   positioned todo down to 0 for unpositioned ones;
+//! - due date: 1 once due, falling off as the due date gets further away,
+//!   0 without one;
+//! - age: rising towards 1 the longer the todo has been waiting.
+//!
+//! Blocked todos are never suggested.
+
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+
+use crate::config::SuggestionWeights;
+use crate::error::AppError;
+use crate::model::{AppState, Todo, TodoStatus};
+use crate::repository::ListOptions;
+
+/// Age at which a todo's age signal reaches one half.
+const HALF_AGE_DAYS: f64 = 7.0;
+
+/// The highest scoring open todo of those `options` list, with its score;
+/// the oldest wins a tie. `None` when there is nothing to do.
+pub async fn next(
+    data: &AppState,
+    options: &ListOptions,
+    weights: &SuggestionWeights,
+) -> Result<Option<(Todo, f64)>, AppError> {
+    let mut candidates: Vec<Todo> = data
+        .todos
+        .stream(options)
+        .await?
+        .try_filter(|todo| {
+            let open = !todo.completed.unwrap_or(false) && todo.status() != TodoStatus::Blocked;
+            async move { open }
+        })
+        .try_collect()
+        .await?;
+    candidates.sort_by_key(|todo| todo.created_at);
+
+    let mut positions: Vec<i64> = candidates.iter().filter_map(|todo| todo.position).collect();
+    positions.sort_unstable();
+
+    let now = Utc::now();
+    let best = candidates
+        .into_iter()
+        .map(|todo| {
+            let priority = priority(&todo, &positions);
+            let score = score(&todo, priority, now, weights);
+            (todo, score)
+        })
+        .fold(
+            None,
+            |best: Option<(Todo, f64)>, (todo, score)| match best {
+                Some(best) if best.1 >= score => Some(best),
+                _ => Some((todo, score)),
+            },
+        );
+    Ok(best)
+}
+
+/// The priority signal of `todo`, given the sorted positions of every
+/// candidate.
+fn priority(todo: &Todo, positions: &[i64]) -> f64 {
+    if todo.status() == TodoStatus::InProgress {
+        return 1.0;
+    }
+    match todo.position {
+        Some(position) => {
+            let rank = positions.partition_point(|other| *other < position);
+            1.0 - (rank + 1) as f64 / (positions.len() + 1) as f64
+        }
+        None => 0.0,
+    }
+}
+
+/// The weighted sum of `todo`'s signals at `now`.
+fn score(todo: &Todo, priority: f64, now: DateTime<Utc>, weights: &SuggestionWeights) -> f64 {
+    let days = |from: DateTime<Utc>, to: DateTime<Utc>| (to - from).num_minutes() as f64 / 1440.0;
+    let due = todo.due_at.map_or(0.0, |due_at| {
+        let days_left = days(now, due_at).max(0.0);
+        1.0 / (1.0 + days_left)
+    });
+    let age = todo.created_at.map_or(0.0, |created_at| {
+        let waited = days(created_at, now).max(0.0);
+        waited / (waited + HALF_AGE_DAYS)
+    });
+
+    weights.priority * priority + weights.due * due + weights.age * age
+}
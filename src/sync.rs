@@ -0,0 +1,472 @@
+//! Incremental sync for offline clients. Every write to a todo is logged
+//! with a change token (see [`crate::repository::SyncedTodoRepository`]);
+//! `GET /sync` returns what changed after the token a client last saw,
+//! deletions included, and `POST /sync` takes the changes the client made
+//! meanwhile.
+//!
+//! Each todo carries a version vector counting the changes every replica
+//! made to it. A pushed change is applied when its version has seen all the
+//! server's; when each side has changes the other has not seen, the later
+//! of the two wins, the server on a tie.
+
+use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicI64};
+
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+
+use crate::auth::AuthUser;
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{
+    AppState, SyncChangeSchema, SyncOutcome, SyncTodoSchema, Todo, TodoChange, TodoId, UndoAction,
+    VersionVector,
+};
+use crate::outbox;
+use crate::repository::{ListOptions, TodoScope, TodoSort, TodoVisibility, TodoWrite};
+use crate::titles;
+use crate::todos;
+
+/// Pushes with more changes than this are rejected.
+pub const MAX_PUSH_CHANGES: usize = 500;
+
+static LAST_TOKEN: AtomicI64 = AtomicI64::new(0);
+
+/// A change token later than any handed out before by this process: the
+/// time in microseconds, moved on by one when the clock has not advanced.
+pub fn next_token() -> i64 {
+    let now = Utc::now().timestamp_micros();
+    let previous = LAST_TOKEN
+        .fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap_or_else(|last| last);
+    now.max(previous + 1)
+}
+
+/// A todo as a sync client should now have it.
+#[derive(Debug, Clone)]
+pub struct SyncedTodo {
+    pub id: TodoId,
+    pub deleted: bool,
+    pub version: VersionVector,
+    /// `None` once deleted.
+    pub todo: Option<Todo>,
+}
+
+#[derive(Debug)]
+pub struct Pull {
+    pub changes: Vec<SyncedTodo>,
+    /// Where the next pull should continue from.
+    pub next_token: i64,
+    pub has_more: bool,
+}
+
+#[derive(Debug)]
+pub struct PushResult {
+    pub outcome: SyncOutcome,
+    /// Why a change was rejected.
+    pub reason: Option<String>,
+    /// The todo as the server has it after the change.
+    pub current: SyncedTodo,
+}
+
+/// Parses a token from `nextToken`.
+pub fn parse_token(token: &str) -> Result<i64, AppError> {
+    token
+        .parse()
+        .ok()
+        .filter(|token| *token >= 0)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid sync token: {}", token)))
+}
+
+/// Whether `user` syncs a todo in `workspace_id` owned by `owner_id` when
+/// syncing `scope`: the todos they could list there. Todos other users
+/// shared with them are not synced.
+fn visible(
+    scope: &TodoScope,
+    user: &AuthUser,
+    workspace_id: Option<&str>,
+    owner_id: Option<&str>,
+) -> bool {
+    let in_scope = match scope {
+        TodoScope::All => true,
+        TodoScope::Personal => workspace_id.is_none(),
+        TodoScope::Workspace(id) => workspace_id == Some(id.as_str()),
+    };
+    in_scope && (workspace_id.is_some() || owner_id.is_none_or(|owner_id| owner_id == user.id))
+}
+
+/// Changes to the todos `user` sees in `scope` after `since`, up to `limit`
+/// of them; without `since`, every such todo, for a client syncing for the
+/// first time.
+pub async fn pull(
+    data: &AppState,
+    scope: &TodoScope,
+    user: &AuthUser,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Pull, AppError> {
+    let Some(since) = since else {
+        return snapshot(data, scope, user).await;
+    };
+
+    let changes = data.changes.since(since, limit).await?;
+    let has_more = changes.len() == limit;
+    let next_token = changes.last().map_or(since, |change| change.token);
+
+    let mut synced = Vec::with_capacity(changes.len());
+    for change in changes {
+        if !visible(
+            scope,
+            user,
+            change.workspace_id.as_deref(),
+            change.owner_id.as_deref(),
+        ) {
+            continue;
+        }
+        let todo = match change.deleted {
+            true => None,
+            false => data.todos.find_by_id(&change.todo_id).await?,
+        };
+        synced.push(SyncedTodo {
+            id: change.todo_id,
+            // Deleted since the change was logged, or its tombstone failed
+            // to be written.
+            deleted: todo.is_none(),
+            version: change.version,
+            todo,
+        });
+    }
+
+    Ok(Pull {
+        changes: synced,
+        next_token,
+        has_more,
+    })
+}
+
+async fn snapshot(data: &AppState, scope: &TodoScope, user: &AuthUser) -> Result<Pull, AppError> {
+    // Taken first, so changes made while reading are pulled again next time.
+    let next_token = next_token();
+    let options = ListOptions {
+        offset: 0,
+        limit: usize::MAX,
+        include_archived: true,
+        scope: scope.clone(),
+        // Todos other users shared with them are not synced.
+        visibility: TodoVisibility::Caller {
+            user_id: Some(user.id.clone()),
+            shared: Vec::new(),
+        },
+        owner_id: None,
+        created_after: None,
+        created_before: None,
+        project_id: None,
+        status: None,
+        assignee_id: None,
+        completed: None,
+        due_after: None,
+        due_before: None,
+        sort: TodoSort::CreatedAt,
+    };
+    let todos: Vec<Todo> = data.todos.stream(&options).await?.try_collect().await?;
+
+    let mut synced = Vec::with_capacity(todos.len());
+    for todo in todos {
+        let Some(id) = todo.id else {
+            continue;
+        };
+        let version = data
+            .changes
+            .find(&id)
+            .await?
+            .map(|change| change.version)
+            .unwrap_or_default();
+        synced.push(SyncedTodo {
+            id,
+            deleted: false,
+            version,
+            todo: Some(todo),
+        });
+    }
+
+    Ok(Pull {
+        changes: synced,
+        next_token,
+        has_more: false,
+    })
+}
+
+/// Applies the changes `replica_id` made on behalf of `user`, in order.
+pub async fn push(
+    data: &AppState,
+    scope: &TodoScope,
+    user: &AuthUser,
+    replica_id: &str,
+    changes: &[SyncChangeSchema],
+) -> Result<Vec<PushResult>, AppError> {
+    if replica_id.trim().is_empty() || replica_id == VersionVector::SERVER {
+        return Err(AppError::BadRequest(format!(
+            "replicaId must be set and must not be '{}'",
+            VersionVector::SERVER
+        )));
+    }
+    if changes.len() > MAX_PUSH_CHANGES {
+        return Err(AppError::BadRequest(format!(
+            "A push can hold at most {} changes",
+            MAX_PUSH_CHANGES
+        )));
+    }
+
+    let mut results = Vec::with_capacity(changes.len());
+    for change in changes {
+        results.push(push_one(data, scope, user, change).await?);
+    }
+
+    log::info!(
+        "event=sync_push user_id={} replica_id={} changes={} applied={}",
+        user.id,
+        replica_id,
+        results.len(),
+        results
+            .iter()
+            .filter(|result| result.outcome == SyncOutcome::Applied)
+            .count()
+    );
+    Ok(results)
+}
+
+async fn push_one(
+    data: &AppState,
+    scope: &TodoScope,
+    user: &AuthUser,
+    change: &SyncChangeSchema,
+) -> Result<PushResult, AppError> {
+    let id = change.id;
+    let stored = data.changes.find(&id).await?;
+    let existing = data.todos.find_by_id(&id).await?;
+    let server_version = stored
+        .as_ref()
+        .map(|stored| stored.version.clone())
+        .unwrap_or_default();
+    let current = SyncedTodo {
+        id,
+        deleted: existing.is_none(),
+        version: server_version.clone(),
+        todo: existing.clone(),
+    };
+
+    let owner = match (&existing, &stored) {
+        (Some(todo), _) => Some((todo.workspace_id.as_deref(), todo.owner_id.as_deref())),
+        (None, Some(stored)) => Some((stored.workspace_id.as_deref(), stored.owner_id.as_deref())),
+        (None, None) => None,
+    };
+    if owner.is_some_and(|(workspace_id, owner_id)| !visible(scope, user, workspace_id, owner_id)) {
+        return Ok(PushResult {
+            outcome: SyncOutcome::Rejected,
+            reason: Some(format!("Todo with ID: {} not found", id)),
+            current: SyncedTodo {
+                id,
+                deleted: true,
+                version: VersionVector::default(),
+                todo: None,
+            },
+        });
+    }
+
+    let client_wins = match change.version.compare(&server_version) {
+        Some(Ordering::Greater | Ordering::Equal) => true,
+        Some(Ordering::Less) => false,
+        None => stored
+            .as_ref()
+            .is_none_or(|stored| change.modified_at > stored.changed_at),
+    };
+    if !client_wins {
+        return Ok(PushResult {
+            outcome: SyncOutcome::Conflict,
+            reason: None,
+            current,
+        });
+    }
+
+    let now = Utc::now();
+    let applied = match (&change.todo, existing) {
+        _ if change.deleted => delete(data, user, current.todo.clone()).await.map(|_| None),
+        (None, _) => Err(AppError::BadRequest(
+            "A change needs a todo unless it deletes one".to_string(),
+        )),
+        (Some(fields), Some(existing)) => update(data, user, existing, fields, now).await.map(Some),
+        (Some(fields), None) => create(data, scope, user, &id, fields, now).await.map(Some),
+    };
+    let todo = match applied {
+        Ok(todo) => todo,
+        Err(AppError::BadRequest(reason) | AppError::Conflict(reason)) => {
+            return Ok(PushResult {
+                outcome: SyncOutcome::Rejected,
+                reason: Some(reason),
+                current,
+            });
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Replaces what the write itself logged: the client's version already
+    // counts this change. Deleting a todo the server never had logs nothing.
+    let version = change.version.merge(&server_version);
+    let known = todo.as_ref().or(current.todo.as_ref());
+    let owner = match (known, &stored) {
+        (Some(todo), _) => Some((todo.workspace_id.clone(), todo.owner_id.clone())),
+        (None, Some(stored)) => Some((stored.workspace_id.clone(), stored.owner_id.clone())),
+        (None, None) => None,
+    };
+    if let Some((workspace_id, owner_id)) = owner {
+        data.changes
+            .record(&TodoChange {
+                todo_id: id,
+                token: next_token(),
+                workspace_id,
+                owner_id,
+                deleted: todo.is_none(),
+                version: version.clone(),
+                changed_at: now,
+            })
+            .await?;
+    }
+
+    Ok(PushResult {
+        outcome: SyncOutcome::Applied,
+        reason: None,
+        current: SyncedTodo {
+            id,
+            deleted: todo.is_none(),
+            version,
+            todo,
+        },
+    })
+}
+
+fn check_title(data: &AppState, fields: &SyncTodoSchema) -> Result<String, AppError> {
+    let title = titles::normalize(&data.sanitizer.title(fields.title.clone()));
+    if title.is_empty() {
+        return Err(AppError::BadRequest("title must not be empty".to_string()));
+    }
+    Ok(title)
+}
+
+async fn create(
+    data: &AppState,
+    scope: &TodoScope,
+    user: &AuthUser,
+    id: &TodoId,
+    fields: &SyncTodoSchema,
+    now: DateTime<Utc>,
+) -> Result<Todo, AppError> {
+    let todo = Todo {
+        id: Some(*id),
+        title: check_title(data, fields)?,
+        content: data.sanitizer.content(fields.content.clone()),
+        completed: Some(fields.completed),
+        archived: Some(fields.archived),
+        due_at: fields.due_at,
+        recurrence: None,
+        series_id: None,
+        next_occurrence_id: None,
+        remind_at: fields.remind_at,
+        reminder_sent_at: None,
+        owner_id: Some(user.id.clone()),
+        workspace_id: scope.workspace_id().map(str::to_string),
+        assignee_id: None,
+        project_id: None,
+        position: None,
+        status: None,
+        created_at: Some(now),
+        updated_at: Some(now),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    };
+    todos::create(data, &todo).await?;
+    Ok(todo)
+}
+
+async fn update(
+    data: &AppState,
+    user: &AuthUser,
+    existing: Todo,
+    fields: &SyncTodoSchema,
+    now: DateTime<Utc>,
+) -> Result<Todo, AppError> {
+    let id = existing.id.expect("stored todos have an ID");
+    let title = check_title(data, fields)?;
+    if titles::key(&title) != titles::key(&existing.title)
+        && data.todos.exists_with_title(&title).await?
+    {
+        return Err(AppError::Conflict(format!(
+            "Todo with title: '{}' already exists",
+            title
+        )));
+    }
+
+    let title_edited = title != existing.title;
+    let was_completed = existing.completed == Some(true);
+    let previous_status = existing.status();
+    let todo = Todo {
+        title,
+        content: data.sanitizer.content(fields.content.clone()),
+        completed: Some(fields.completed),
+        archived: Some(fields.archived),
+        due_at: fields.due_at,
+        // A changed reminder time re-arms the reminder.
+        reminder_sent_at: if fields.remind_at == existing.remind_at {
+            existing.reminder_sent_at
+        } else {
+            None
+        },
+        remind_at: fields.remind_at,
+        updated_at: Some(now),
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+        ..existing
+    };
+    data.workflow.check(previous_status, todo.status())?;
+
+    let completes = todo.completed == Some(true) && !was_completed;
+    let mut events = vec![DomainEvent::TodoUpdated {
+        id,
+        todo: Some(todo.clone()),
+        title: title_edited.then(|| todo.title.clone()),
+        actor_id: Some(user.id.clone()),
+    }];
+    if completes {
+        events.push(DomainEvent::TodoCompleted {
+            id,
+            actor_id: Some(user.id.clone()),
+        });
+    }
+    outbox::commit(data, TodoWrite::Update(todo.clone()), events).await?;
+    if completes && todo.recurrence.is_some() {
+        data.recurrence.wake().await;
+    }
+    Ok(todo)
+}
+
+/// Deletes `todo` unless it is gone already.
+async fn delete(data: &AppState, user: &AuthUser, todo: Option<Todo>) -> Result<(), AppError> {
+    let Some(todo) = todo else {
+        return Ok(());
+    };
+    let id = todo.id.expect("stored todos have an ID");
+    let event = DomainEvent::TodoDeleted {
+        id,
+        actor_id: Some(user.id.clone()),
+    };
+    outbox::commit(data, TodoWrite::Delete(id), vec![event]).await?;
+    todos::delete_related(data, std::slice::from_ref(&id)).await?;
+    data.undo
+        .record(Some(&user.id), UndoAction::Delete, vec![todo])
+        .await;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Todo templates. Titles and contents may hold `{{variable}}`
+//! placeholders, filled in from the values given when a template is
+//! instantiated.
+
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+use crate::model::{InstantiateTemplateSchema, Template, Todo, TodoId};
+use crate::titles;
+
+/// One `{{name}}` placeholder found in a template text.
+struct Placeholder<'a> {
+    /// Byte range of the whole placeholder, braces included.
+    start: usize,
+    end: usize,
+    name: &'a str,
+}
+
+/// The placeholders of `text` in order. Names are trimmed and made of
+/// letters, digits and underscores; anything else between braces, or an
+/// unclosed `{{`, is left as literal text.
+fn placeholders(text: &str) -> Vec<Placeholder<'_>> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(open) = text[from..].find("{{").map(|i| from + i) {
+        let Some(close) = text[open + 2..].find("}}").map(|i| open + 2 + i) else {
+            break;
+        };
+        let name = text[open + 2..close].trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            found.push(Placeholder {
+                start: open,
+                end: close + 2,
+                name,
+            });
+            from = close + 2;
+        } else {
+            from = open + 2;
+        }
+    }
+    found
+}
+
+/// The distinct variable names used by a template, sorted.
+pub fn variables(template: &Template) -> BTreeSet<&str> {
+    placeholders(&template.title)
+        .into_iter()
+        .chain(placeholders(&template.content))
+        .map(|placeholder| placeholder.name)
+        .collect()
+}
+
+/// Replaces every placeholder of `text` with its value. Values are
+/// inserted as-is, so braces in them are never expanded again.
+fn render(text: &str, values: &HashMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut copied = 0;
+    for placeholder in placeholders(text) {
+        rendered.push_str(&text[copied..placeholder.start]);
+        match values.get(placeholder.name) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&text[placeholder.start..placeholder.end]),
+        }
+        copied = placeholder.end;
+    }
+    rendered.push_str(&text[copied..]);
+    rendered
+}
+
+/// A new todo from `template`, owned by `owner_id` in `workspace_id`.
+/// Every variable the template uses must have a value.
+pub fn instantiate(
+    template: &Template,
+    body: &InstantiateTemplateSchema,
+    owner_id: String,
+    workspace_id: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<Todo, AppError> {
+    let missing: Vec<&str> = variables(template)
+        .into_iter()
+        .filter(|name| !body.variables.contains_key(*name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "Missing values for template variables: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let title = titles::normalize(&render(&template.title, &body.variables));
+    if title.is_empty() {
+        return Err(AppError::BadRequest(
+            "The instantiated title must not be empty".to_string(),
+        ));
+    }
+
+    Ok(Todo {
+        id: Some(TodoId::generate()),
+        title,
+        content: render(&template.content, &body.variables),
+        completed: Some(false),
+        archived: Some(false),
+        due_at: body.due_at,
+        recurrence: None,
+        series_id: None,
+        next_occurrence_id: None,
+        remind_at: body.remind_at,
+        reminder_sent_at: None,
+        owner_id: Some(owner_id),
+        workspace_id,
+        assignee_id: None,
+        project_id: None,
+        position: None,
+        status: None,
+        created_at: Some(now),
+        updated_at: Some(now),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    })
+}
@@ -0,0 +1,97 @@
+//! Time zones of due dates. Due and reminder times are stored in UTC, but
+//! each request runs in the time zone of the signed-in user's
+//! [`preferences`](crate::preferences): times in responses carry that zone's offset, times sent without an
+//! offset are read as local times there, and the `overdue` and `today`
+//! filters go by its days. Anonymous requests, and users who never chose
+//! a zone, get UTC, so responses look as they always have.
+
+use std::future::Future;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+tokio::task_local! {
+    static ZONE: Tz;
+}
+
+/// Runs `f` in `zone`.
+pub async fn scope<F: Future>(zone: Tz, f: F) -> F::Output {
+    ZONE.scope(zone, f).await
+}
+
+/// The current request's time zone; UTC outside a request.
+pub fn current() -> Tz {
+    ZONE.try_with(|zone| *zone).unwrap_or(Tz::UTC)
+}
+
+/// Runs `f` in `zone`, for work outside a request, such as writing a
+/// streamed response after its handler has returned.
+pub fn with_zone<R>(zone: Tz, f: impl FnOnce() -> R) -> R {
+    ZONE.sync_scope(zone, f)
+}
+
+/// The first instant of `date` in `zone`. Usually midnight, but where a
+/// clock change skips midnight, the first hour that exists.
+fn start_of(date: NaiveDate, zone: Tz) -> DateTime<Utc> {
+    (0..24)
+        .find_map(|hour| {
+            let time = NaiveTime::from_hms_opt(hour, 0, 0)?;
+            zone.from_local_datetime(&date.and_time(time)).earliest()
+        })
+        .map_or_else(
+            || Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)),
+            |start| start.with_timezone(&Utc),
+        )
+}
+
+/// The start and end of the day `now` falls on in `zone`.
+pub fn day_bounds(now: DateTime<Utc>, zone: Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = now.with_timezone(&zone).date_naive();
+    let tomorrow = today + Days::new(1);
+    (start_of(today, zone), start_of(tomorrow, zone))
+}
+
+/// Serde format for optional times given and shown in the current
+/// request's zone. Accepts RFC 3339 times, which fix the instant whatever
+/// the zone, and times without an offset, taken as local times; a local
+/// time repeated by a clock change is the earlier of the two.
+pub mod local {
+    use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+    use chrono_tz::Tz;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match (value, super::current()) {
+            (Some(value), Tz::UTC) | (Some(value), Tz::Etc__UTC) => value.serialize(serializer),
+            (Some(value), zone) => value
+                .with_timezone(&zone)
+                .fixed_offset()
+                .serialize(serializer),
+            (None, _) => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        let Some(value) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        if let Ok(time) = value.parse::<DateTime<FixedOffset>>() {
+            return Ok(Some(time.with_timezone(&Utc)));
+        }
+        let local = value
+            .parse::<NaiveDateTime>()
+            .map_err(|_| serde::de::Error::custom(format!("Invalid date and time '{}'", value)))?;
+        let zone = super::current();
+        zone.from_local_datetime(&local)
+            .earliest()
+            .map(|time| Some(time.with_timezone(&Utc)))
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!("{} does not exist in {}", value, zone.name()))
+            })
+    }
+}
@@ -0,0 +1,18 @@
+//! Normalization of todo titles. Titles are unique, and two that differ
+//! only in Unicode composition, whitespace or case are the same title:
+//! "Buy Milk " and "buy milk" clash. Each todo is stored with its title's
+//! [`key`], which the uniqueness check and lookups by title compare.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// `title` in NFC, trimmed, with each run of whitespace collapsed into a
+/// single space. Titles are stored this way.
+pub fn normalize(title: &str) -> String {
+    let title: String = title.nfc().collect();
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The form titles are compared in: normalized and lowercased.
+pub fn key(title: &str) -> String {
+    normalize(title).to_lowercase()
+}
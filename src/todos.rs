@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{AppState, Todo, TodoId};
+use crate::outbox;
+use crate::repository::TodoWrite;
+
+/// Appended to the title of a duplicate when the request names no suffix.
+pub const DEFAULT_COPY_SUFFIX: &str = " (copy)";
+
+/// Stores a new todo and announces it, with its owner as its creator.
+/// Titles are unique, so a todo whose
+/// title is taken is rejected with a 409.
+pub async fn create(data: &AppState, todo: &Todo) -> Result<(), AppError> {
+    if data.todos.exists_with_title(&todo.title).await? {
+        return Err(AppError::Conflict(format!(
+            "Todo with title: '{}' already exists",
+            todo.title
+        )));
+    }
+
+    let id = todo.id.expect("new todos are given an ID");
+    let event = DomainEvent::TodoCreated {
+        todo: todo.clone(),
+        actor_id: todo.owner_id.clone(),
+    };
+    outbox::commit(data, TodoWrite::Insert(todo.clone()), vec![event]).await?;
+
+    log::info!("event=todo_created todo_id={}", id);
+    Ok(())
+}
+
+/// A new todo copying the title, content, schedule and reminder of
+/// `source`, owned by `owner_id` in `workspace_id`. A recurring copy starts
+/// a series of its own and a copy within the same workspace stays in the
+/// source's project; the expiry, archive flag, assignee and sent reminder
+/// are not carried over, and completion and status only when `keep_completion` is
+/// set.
+pub fn duplicate(
+    source: &Todo,
+    title_suffix: &str,
+    keep_completion: bool,
+    owner_id: Option<String>,
+    workspace_id: Option<String>,
+    now: DateTime<Utc>,
+) -> Todo {
+    let id = TodoId::generate();
+    Todo {
+        id: Some(id),
+        title: format!("{}{}", source.title, title_suffix),
+        content: source.content.clone(),
+        completed: Some(keep_completion && source.completed.unwrap_or(false)),
+        archived: Some(false),
+        due_at: source.due_at,
+        recurrence: source.recurrence.clone(),
+        series_id: source.recurrence.as_ref().map(|_| id),
+        next_occurrence_id: None,
+        remind_at: source.remind_at,
+        reminder_sent_at: None,
+        owner_id,
+        // A project only groups todos of its own workspace.
+        project_id: source
+            .project_id
+            .clone()
+            .filter(|_| source.workspace_id == workspace_id),
+        workspace_id,
+        assignee_id: None,
+        position: None,
+        status: keep_completion.then(|| source.status()),
+        created_at: Some(now),
+        updated_at: Some(now),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    }
+}
+
+/// Deletes an attachment's contents. Failures only leave an orphaned blob
+/// behind, so they are logged rather than returned.
+pub async fn delete_blob(data: &AppState, key: &str) {
+    if let Err(e) = data.blobs.delete(key).await {
+        log::warn!(
+            "event=blob_delete_failed attachment_id={} error=\"{}\"",
+            key,
+            e
+        );
+    }
+}
+
+/// Deletes `ids` on behalf of `actor_id`, announcing each deletion, along
+/// with what hangs off them.
+pub async fn delete_all(
+    data: &AppState,
+    ids: &[TodoId],
+    actor_id: Option<String>,
+) -> Result<(), AppError> {
+    let writes = ids
+        .iter()
+        .map(|id| {
+            let event = DomainEvent::TodoDeleted {
+                id: *id,
+                actor_id: actor_id.clone(),
+            };
+            (TodoWrite::Delete(*id), vec![event])
+        })
+        .collect();
+    outbox::commit_all(data, writes).await?;
+    delete_related(data, ids).await
+}
+
+/// Removes what hangs off deleted todos: shares, comments and attachments.
+pub async fn delete_related(data: &AppState, ids: &[TodoId]) -> Result<(), AppError> {
+    data.acl.delete_for_todos(ids).await?;
+    data.comments.delete_for_todos(ids).await?;
+    for attachment in data.attachments.delete_for_todos(ids).await? {
+        delete_blob(data, &attachment.id).await;
+    }
+    Ok(())
+}
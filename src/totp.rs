@@ -0,0 +1,353 @@
+//! TOTP (RFC 6238) second factors: enrolled through an `otpauth://` URI
+//! that authenticator apps scan as a QR code, asked for after the
+//! password at login, with single-use backup codes for a lost device.
+
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::auth::hash_token;
+use crate::config::AuthConfig;
+use crate::error::AppError;
+use crate::model::{User, UserTotp};
+use crate::repository::UserRepository;
+
+/// Seconds each code is valid for.
+const PERIOD: i64 = 30;
+const DIGITS: usize = 6;
+/// Steps either side of the current one still accepted, for clocks that
+/// drift.
+const SKEW: i64 = 1;
+const BACKUP_CODES: usize = 10;
+const NONCE_LEN: usize = 12;
+
+/// The code an authenticator app shows for `secret` at `at`. SHA-1, as
+/// most apps ignore any other algorithm.
+pub fn code(secret: &[u8], at: DateTime<Utc>) -> String {
+    code_for_step(secret, at.timestamp().div_euclid(PERIOD))
+}
+
+fn code_for_step(secret: &[u8], step: i64) -> String {
+    let mut mac =
+        <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // Dynamic truncation (RFC 4226, section 5.3).
+    let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        value % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    )
+}
+
+/// Backup codes are accepted with any case, spaces or dashes.
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Percent-encodes everything but unreserved characters, as `otpauth://`
+/// labels and parameters need.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// A new enrollment: the secret for typing in by hand, and the URI to
+/// show as a QR code.
+pub struct Enrollment {
+    pub secret: String,
+    pub uri: String,
+}
+
+#[derive(Clone)]
+pub struct TotpService {
+    users: Arc<dyn UserRepository>,
+    /// Seals new secrets. Unset while only the legacy key is available, as
+    /// secrets sealed with that would not survive a rotation of the JWT
+    /// secret.
+    cipher: Option<Arc<Aes256Gcm>>,
+    /// Derived from the JWT secret, which sealed secrets before
+    /// `TOTP_ENCRYPTION_KEY` existed; only used to open those.
+    legacy: Option<Arc<Aes256Gcm>>,
+    issuer: String,
+}
+
+impl TotpService {
+    pub fn new(users: Arc<dyn UserRepository>, config: &AuthConfig) -> Result<Self, String> {
+        let legacy = config.jwt_secret.as_ref().map(|secret| {
+            let key: [u8; 32] = Sha256::new()
+                .chain_update(b"totp-secrets:")
+                .chain_update(secret.as_bytes())
+                .finalize()
+                .into();
+            Arc::new(Aes256Gcm::new(&key.into()))
+        });
+        let key: Option<[u8; 32]> = match &config.totp.encryption_key {
+            Some(key) => Some(
+                hex::decode(key.trim())
+                    .ok()
+                    .and_then(|key| key.try_into().ok())
+                    .ok_or("TOTP_ENCRYPTION_KEY must be 64 hex digits")?,
+            ),
+            None if legacy.is_some() => {
+                log::warn!("event=totp_key_missing message=\"TOTP_ENCRYPTION_KEY is not set; new two-factor enrollments are refused\"");
+                None
+            }
+            None => {
+                log::warn!("event=totp_ephemeral_key message=\"TOTP_ENCRYPTION_KEY and AUTH_JWT_SECRET are not set; two-factor enrollments will not survive a restart\"");
+                Some(rand::thread_rng().gen())
+            }
+        };
+
+        Ok(TotpService {
+            users,
+            cipher: key.map(|key| Arc::new(Aes256Gcm::new(&key.into()))),
+            legacy,
+            issuer: config.totp.issuer.clone(),
+        })
+    }
+
+    fn encrypt(&self, secret: &[u8]) -> Result<String, AppError> {
+        let cipher = self.cipher.as_ref().ok_or_else(|| {
+            AppError::ServiceUnavailable(
+                "Two-factor authentication needs TOTP_ENCRYPTION_KEY to be set".to_string(),
+            )
+        })?;
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            cipher
+                .encrypt(Nonce::from_slice(&nonce), secret)
+                .map_err(|_| AppError::Internal("Failed to encrypt TOTP secret".to_string()))?,
+        );
+        Ok(STANDARD.encode(sealed))
+    }
+
+    /// The secret, and whether it was sealed with the legacy key.
+    fn decrypt(&self, stored: &str) -> Result<(Vec<u8>, bool), AppError> {
+        let unreadable = || AppError::Internal("Stored TOTP secret is unreadable".to_string());
+        let sealed = STANDARD.decode(stored).map_err(|_| unreadable())?;
+        if sealed.len() < NONCE_LEN {
+            return Err(unreadable());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let open = |cipher: &Option<Arc<Aes256Gcm>>| {
+            cipher
+                .as_ref()
+                .and_then(|cipher| cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok())
+        };
+        open(&self.cipher)
+            .map(|secret| (secret, false))
+            .or_else(|| open(&self.legacy).map(|secret| (secret, true)))
+            .ok_or_else(unreadable)
+    }
+
+    /// The user's second factor, if they have confirmed one.
+    async fn enabled(&self, user_id: &str) -> Result<Option<UserTotp>, AppError> {
+        Ok(self
+            .users
+            .find_totp(user_id)
+            .await?
+            .filter(|totp| totp.enabled_at.is_some()))
+    }
+
+    /// Whether logins to the account need a code after the password.
+    pub async fn is_enabled(&self, user_id: &str) -> Result<bool, AppError> {
+        Ok(self.enabled(user_id).await?.is_some())
+    }
+
+    /// Starts enrolling `user` with a new secret, replacing any earlier
+    /// enrollment that was never confirmed.
+    pub async fn enroll(&self, user: &User) -> Result<Enrollment, AppError> {
+        if self.is_enabled(&user.id).await? {
+            return Err(AppError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret: [u8; 20] = rand::thread_rng().gen();
+        self.users
+            .save_totp(
+                &user.id,
+                Some(&UserTotp {
+                    secret: self.encrypt(&secret)?,
+                    enabled_at: None,
+                    last_step: 0,
+                    backup_codes: Vec::new(),
+                }),
+            )
+            .await?;
+
+        let secret = BASE32_NOPAD.encode(&secret);
+        let issuer = percent_encode(&self.issuer);
+        let uri = format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            issuer,
+            percent_encode(&user.email),
+            secret,
+            issuer,
+            DIGITS,
+            PERIOD
+        );
+        Ok(Enrollment { secret, uri })
+    }
+
+    /// Turns the second factor on once the user proves their app has the
+    /// secret, and returns their backup codes.
+    pub async fn confirm(&self, user_id: &str, code: &str) -> Result<Vec<String>, AppError> {
+        let mut totp = match self.users.find_totp(user_id).await? {
+            Some(totp) if totp.enabled_at.is_some() => {
+                return Err(AppError::Conflict(
+                    "Two-factor authentication is already enabled".to_string(),
+                ))
+            }
+            Some(totp) => totp,
+            None => {
+                return Err(AppError::BadRequest(
+                    "Start enrolling two-factor authentication first".to_string(),
+                ))
+            }
+        };
+        // Only a code from the app proves it; there are no backup codes yet.
+        if !self.check_code(&mut totp, code)? {
+            return Err(invalid_code());
+        }
+
+        totp.enabled_at = Some(Utc::now());
+        let codes = self.save_with_backup_codes(user_id, totp).await?;
+        log::info!("event=totp_enabled user_id={}", user_id);
+        Ok(codes)
+    }
+
+    /// Replaces the backup codes with new ones, after checking a code.
+    pub async fn regenerate_backup_codes(
+        &self,
+        user_id: &str,
+        code: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let mut totp = self.enabled(user_id).await?.ok_or_else(not_enabled)?;
+        if !self.check_code(&mut totp, code)? {
+            return Err(invalid_code());
+        }
+
+        let codes = self.save_with_backup_codes(user_id, totp).await?;
+        log::info!("event=totp_backup_codes_regenerated user_id={}", user_id);
+        Ok(codes)
+    }
+
+    /// Turns the second factor off, after checking a code.
+    pub async fn disable(&self, user_id: &str, code: &str) -> Result<(), AppError> {
+        let mut totp = self.enabled(user_id).await?.ok_or_else(not_enabled)?;
+        if !self.check_code(&mut totp, code)? {
+            return Err(invalid_code());
+        }
+
+        self.users.save_totp(user_id, None).await?;
+        log::info!("event=totp_disabled user_id={}", user_id);
+        Ok(())
+    }
+
+    /// Turns the second factor off without a code, when whoever set it up
+    /// may not be the account's owner.
+    pub async fn remove(&self, user_id: &str) -> Result<(), AppError> {
+        if self.users.find_totp(user_id).await?.is_some() {
+            self.users.save_totp(user_id, None).await?;
+            log::info!("event=totp_removed user_id={}", user_id);
+        }
+        Ok(())
+    }
+
+    /// Checks a code given at login. A backup code is used up by it.
+    pub async fn verify(&self, user_id: &str, code: &str) -> Result<bool, AppError> {
+        let Some(mut totp) = self.enabled(user_id).await? else {
+            return Ok(false);
+        };
+        if !self.check_code(&mut totp, code)? {
+            return Ok(false);
+        }
+
+        self.users.save_totp(user_id, Some(&totp)).await?;
+        Ok(true)
+    }
+
+    /// Whether `code` is a current code from the app or one of the backup
+    /// codes, recording it in `totp` so it cannot be used again.
+    fn check_code(&self, totp: &mut UserTotp, code: &str) -> Result<bool, AppError> {
+        let code = normalize(code);
+        if code.len() == DIGITS && code.bytes().all(|b| b.is_ascii_digit()) {
+            let (secret, legacy) = self.decrypt(&totp.secret)?;
+            let current = Utc::now().timestamp().div_euclid(PERIOD);
+            let step = (current - SKEW..=current + SKEW)
+                .filter(|step| *step > totp.last_step)
+                .find(|step| code_for_step(&secret, *step) == code);
+            if let Some(step) = step {
+                totp.last_step = step;
+                // Callers save `totp`, moving it off the legacy key.
+                if legacy && self.cipher.is_some() {
+                    totp.secret = self.encrypt(&secret)?;
+                }
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+
+        let hash = hash_token(&code);
+        let unused = totp.backup_codes.len();
+        totp.backup_codes.retain(|backup_code| *backup_code != hash);
+        Ok(totp.backup_codes.len() < unused)
+    }
+
+    /// Stores `totp` with a new set of backup codes, which are returned.
+    async fn save_with_backup_codes(
+        &self,
+        user_id: &str,
+        mut totp: UserTotp,
+    ) -> Result<Vec<String>, AppError> {
+        let codes: Vec<String> = (0..BACKUP_CODES)
+            .map(|_| {
+                let code = hex::encode(rand::thread_rng().gen::<[u8; 5]>());
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect();
+        totp.backup_codes = codes
+            .iter()
+            .map(|code| hash_token(&normalize(code)))
+            .collect();
+        self.users.save_totp(user_id, Some(&totp)).await?;
+        Ok(codes)
+    }
+}
+
+fn invalid_code() -> AppError {
+    AppError::BadRequest("Invalid two-factor code".to_string())
+}
+
+fn not_enabled() -> AppError {
+    AppError::BadRequest("Two-factor authentication is not enabled".to_string())
+}
@@ -0,0 +1,41 @@
+//! A small single-page UI over the API, served at `/` so people running the
+//! server can list, create, complete and delete todos without curl. Its
+//! files under `ui/` are compiled into the binary; API-only deployments
+//! build without the `ui` feature to leave it out.
+
+use actix_web::http::header::{self, CacheDirective};
+use actix_web::{get, web, HttpResponse};
+
+const INDEX_HTML: &str = include_str!("../ui/index.html");
+const APP_JS: &str = include_str!("../ui/app.js");
+const STYLE_CSS: &str = include_str!("../ui/style.css");
+
+/// Serves `body`, asking browsers to revalidate so an upgraded server's UI
+/// is picked up straight away.
+fn asset(content_type: &'static str, body: &'static str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::CacheControl(vec![CacheDirective::NoCache]))
+        .body(body)
+}
+
+#[get("/")]
+async fn index_handler() -> HttpResponse {
+    asset("text/html; charset=utf-8", INDEX_HTML)
+}
+
+#[get("/ui/app.js")]
+async fn app_js_handler() -> HttpResponse {
+    asset("text/javascript; charset=utf-8", APP_JS)
+}
+
+#[get("/ui/style.css")]
+async fn style_css_handler() -> HttpResponse {
+    asset("text/css; charset=utf-8", STYLE_CSS)
+}
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(index_handler)
+        .service(app_js_handler)
+        .service(style_css_handler);
+}
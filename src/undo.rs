@@ -0,0 +1,181 @@
+//! Undoing the caller's recent deletes and completions. Each such change
+//! logs the todos as they were before it; `POST /undo` puts back the most
+//! recent one still inside the configured window and drops it from the log,
+//! so repeated undos walk further back.
+//!
+//! Only the todos themselves are restored: the comments, attachments and
+//! shares of a deleted todo are gone, and an occurrence already created by
+//! completing a recurring todo stays.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{AppState, Todo, UndoAction, UndoEntry};
+use crate::outbox;
+use crate::repository::{TodoPatch, TodoWrite, UndoRepository};
+
+/// Changes touching more todos than this are not logged for undo.
+pub const MAX_UNDO_TODOS: usize = 1_000;
+
+/// Handle on the undo log and how long its entries can be undone.
+pub struct UndoLog {
+    entries: Arc<dyn UndoRepository>,
+    window: Duration,
+}
+
+impl UndoLog {
+    pub fn new(entries: Arc<dyn UndoRepository>, window: Duration) -> Self {
+        UndoLog { entries, window }
+    }
+
+    fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - chrono::Duration::from_std(self.window).unwrap_or_default()
+    }
+
+    /// Logs that `actor_id` did `action` to `todos`, given as they were
+    /// before. Anonymous changes cannot be undone and are not logged; a
+    /// failed write is logged but does not fail the change.
+    pub async fn record(&self, actor_id: Option<&str>, action: UndoAction, todos: Vec<Todo>) {
+        let Some(actor_id) = actor_id else {
+            return;
+        };
+        if todos.is_empty() || self.window.is_zero() {
+            return;
+        }
+        if todos.len() > MAX_UNDO_TODOS {
+            log::info!(
+                "event=undo_skipped actor_id={} action={} todos={}",
+                actor_id,
+                action.as_str(),
+                todos.len()
+            );
+            return;
+        }
+
+        let now = Utc::now();
+        let entry = UndoEntry {
+            id: Uuid::new_v4().to_string(),
+            actor_id: actor_id.to_string(),
+            action,
+            todos,
+            created_at: now,
+        };
+        let result = match self.entries.delete_before(actor_id, self.cutoff(now)).await {
+            Ok(()) => self.entries.insert(&entry).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            log::warn!(
+                "event=undo_record_failed actor_id={} action={} error=\"{}\"",
+                actor_id,
+                action.as_str(),
+                e
+            );
+        }
+    }
+}
+
+/// Reverses `actor_id`'s most recent change still inside the window and
+/// returns it with the todos as they are now, or `None` when there is
+/// nothing to undo.
+pub async fn undo(
+    data: &AppState,
+    actor_id: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<(UndoAction, Vec<Todo>)>, AppError> {
+    let log = &data.undo;
+    let Some(entry) = log.entries.latest(actor_id, log.cutoff(now)).await? else {
+        return Ok(None);
+    };
+
+    let todos = match entry.action {
+        UndoAction::Delete => restore(data, actor_id, &entry.todos).await?,
+        UndoAction::Complete | UndoAction::Incomplete => {
+            revert_completion(data, actor_id, &entry.todos, now).await?
+        }
+    };
+    log.entries.delete(&entry).await?;
+
+    log::info!(
+        "event=undo actor_id={} action={} todos={}",
+        actor_id,
+        entry.action.as_str(),
+        todos.len()
+    );
+    Ok(Some((entry.action, todos)))
+}
+
+/// Inserts the deleted `todos` again, skipping any whose ID has been taken
+/// since. A todo whose title has been reused since blocks the whole undo.
+async fn restore(data: &AppState, actor_id: &str, todos: &[Todo]) -> Result<Vec<Todo>, AppError> {
+    let mut restored = Vec::with_capacity(todos.len());
+    for todo in todos {
+        let Some(id) = todo.id else {
+            continue;
+        };
+        if data.todos.find_by_id(&id).await?.is_some() {
+            continue;
+        }
+        if data.todos.exists_with_title(&todo.title).await? {
+            return Err(AppError::Conflict(format!(
+                "Todo with title: '{}' already exists",
+                todo.title
+            )));
+        }
+        restored.push(todo.clone());
+    }
+
+    let changes = restored
+        .iter()
+        .map(|todo| {
+            let event = DomainEvent::TodoCreated {
+                todo: todo.clone(),
+                actor_id: Some(actor_id.to_string()),
+            };
+            (TodoWrite::Insert(todo.clone()), vec![event])
+        })
+        .collect();
+    outbox::commit_all(data, changes).await?;
+    Ok(restored)
+}
+
+/// Puts `completed` and the status of `todos` back as they were; todos
+/// deleted since are skipped.
+async fn revert_completion(
+    data: &AppState,
+    actor_id: &str,
+    todos: &[Todo],
+    now: DateTime<Utc>,
+) -> Result<Vec<Todo>, AppError> {
+    let mut reverted = Vec::with_capacity(todos.len());
+    for before in todos {
+        let Some(id) = before.id else {
+            continue;
+        };
+        let Some(mut todo) = data.todos.find_by_id(&id).await? else {
+            continue;
+        };
+        let patch = TodoPatch {
+            completed: Some(before.completed.unwrap_or(false)),
+            status: Some(before.status()),
+            updated_at: now,
+            expires_at: todo.expires_at,
+            ..TodoPatch::default()
+        };
+        patch.apply(&mut todo);
+        let event = DomainEvent::TodoUpdated {
+            id,
+            todo: Some(todo.clone()),
+            title: None,
+            actor_id: Some(actor_id.to_string()),
+        };
+        outbox::commit(data, TodoWrite::Patch(id, patch), vec![event]).await?;
+        reverted.push(todo);
+    }
+    Ok(reverted)
+}
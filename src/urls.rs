@@ -0,0 +1,117 @@
+use actix_web::HttpRequest;
+
+use crate::error::AppError;
+use crate::model::TodoId;
+use crate::pagination::QueryOptions;
+use crate::response::{Link, PageLinks, TodoLinks};
+use crate::versioning::{ApiMount, ApiVersion};
+
+/// Prefix every API route is mounted under.
+pub const API_BASE_PATH: &str = "/api";
+
+/// Base path of the scope that routed `req`: `/api`, `/api/v1` or `/api/v2`,
+/// so links stay within the version the client is using.
+pub fn base_path(req: &HttpRequest) -> String {
+    match req.app_data::<ApiMount>() {
+        Some(ApiMount(version)) => version.base_path(),
+        None => API_BASE_PATH.to_string(),
+    }
+}
+
+/// Path of the route registered under `name`, with `elements` filling its
+/// dynamic segments, within the version `req` was routed through. Each
+/// version mounts the same named routes and the router resolves a name to
+/// one of them, so its base path is swapped for the request's.
+pub fn route(req: &HttpRequest, name: &str, elements: &[&str]) -> Result<String, AppError> {
+    let url = req
+        .url_for(name, elements)
+        .map_err(|e| AppError::Internal(format!("No URL for route '{}': {}", name, e)))?;
+    let path = url.path();
+    let relative = ApiVersion::ALL
+        .iter()
+        .map(ApiVersion::base_path)
+        .chain([API_BASE_PATH.to_string()])
+        .find_map(|base| {
+            path.strip_prefix(base.as_str())
+                .filter(|rest| rest.starts_with('/'))
+        })
+        .unwrap_or(path);
+    Ok(format!("{}{}", base_path(req), relative))
+}
+
+pub fn todo_links(req: &HttpRequest, id: &TodoId) -> Result<TodoLinks, AppError> {
+    let id = id.to_string();
+    let todo = route(req, "todo", &[&id])?;
+    Ok(TodoLinks {
+        self_link: Link::get(todo.clone()),
+        update: Link::with_method(todo.clone(), "PATCH"),
+        delete: Link::with_method(todo, "DELETE"),
+        complete: Link::with_method(route(req, "todo_status", &[&id])?, "PATCH"),
+    })
+}
+
+/// Links of a list response holding `results` todos. Lists paged with
+/// `options` also link to the pages either side of this one.
+pub fn page_links(req: &HttpRequest, options: Option<&QueryOptions>, results: usize) -> PageLinks {
+    let self_link = match req.query_string() {
+        "" => req.path().to_string(),
+        query => format!("{}?{}", req.path(), query),
+    };
+    let Some(options) = options else {
+        return PageLinks {
+            self_link: Link::get(self_link),
+            next: None,
+            prev: None,
+        };
+    };
+    let page = options.offset / options.limit + 1;
+    PageLinks {
+        self_link: Link::get(self_link),
+        next: (results == options.limit).then(|| Link::get(with_page(req, page + 1))),
+        prev: (page > 1).then(|| Link::get(with_page(req, page - 1))),
+    }
+}
+
+/// The request's path and query with `page` set to `page`.
+fn with_page(req: &HttpRequest, page: usize) -> String {
+    let mut query: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some("page"))
+        .map(str::to_string)
+        .collect();
+    query.push(format!("page={}", page));
+    format!("{}?{}", req.path(), query.join("&"))
+}
+
+pub fn todo(req: &HttpRequest, id: &TodoId) -> String {
+    format!("{}/todos/{}", base_path(req), id)
+}
+
+pub fn comment(req: &HttpRequest, todo_id: &TodoId, id: &str) -> String {
+    format!("{}/comments/{}", todo(req, todo_id), id)
+}
+
+pub fn attachment(req: &HttpRequest, todo_id: &TodoId, id: &str) -> String {
+    format!("{}/attachments/{}", todo(req, todo_id), id)
+}
+
+pub fn project(req: &HttpRequest, id: &str) -> String {
+    format!("{}/projects/{}", base_path(req), id)
+}
+
+pub fn template(req: &HttpRequest, id: &str) -> String {
+    format!("{}/templates/{}", base_path(req), id)
+}
+
+pub fn webhook(req: &HttpRequest, id: &str) -> String {
+    format!("{}/webhooks/{}", base_path(req), id)
+}
+
+pub fn workspace(req: &HttpRequest, id: &str) -> String {
+    format!("{}/workspaces/{}", base_path(req), id)
+}
+
+pub fn workspace_member(req: &HttpRequest, workspace_id: &str, user_id: &str) -> String {
+    format!("{}/members/{}", workspace(req, workspace_id), user_id)
+}
@@ -0,0 +1,79 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{FromRequest, HttpRequest};
+
+use crate::error::AppError;
+use crate::urls::API_BASE_PATH;
+
+/// Media types clients can put in `Accept` to pick a version on the
+/// unversioned `/api` routes.
+pub const V1_MEDIA_TYPE: &str = "application/vnd.todos.v1+json";
+pub const V2_MEDIA_TYPE: &str = "application/vnd.todos.v2+json";
+
+/// The API version a request is served with. `/api/v1` and `/api/v2` fix
+/// the version; plain `/api` negotiates it from `Accept` and falls back to
+/// v1 so existing clients keep the representation they were written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+/// App data of the `/api/v1` and `/api/v2` scopes naming their version.
+/// Absent on the unversioned `/api` scope.
+#[derive(Debug, Clone, Copy)]
+pub struct ApiMount(pub ApiVersion);
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+
+    /// Where this version's routes are mounted, e.g. `/api/v2`.
+    pub fn base_path(&self) -> String {
+        format!("{}/{}", API_BASE_PATH, self.as_str())
+    }
+
+    /// The version named in the `Accept` header, if any.
+    fn negotiate(req: &HttpRequest) -> Option<ApiVersion> {
+        let accept = req
+            .headers()
+            .get_all(header::ACCEPT)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|media_type| media_type.split(';').next())
+            .map(str::trim);
+        for media_type in accept {
+            if media_type.eq_ignore_ascii_case(V2_MEDIA_TYPE) {
+                return Some(ApiVersion::V2);
+            }
+            if media_type.eq_ignore_ascii_case(V1_MEDIA_TYPE) {
+                return Some(ApiVersion::V1);
+            }
+        }
+        None
+    }
+
+    pub fn of(req: &HttpRequest) -> ApiVersion {
+        match req.app_data::<ApiMount>() {
+            Some(ApiMount(version)) => *version,
+            None => ApiVersion::negotiate(req).unwrap_or(ApiVersion::V1),
+        }
+    }
+}
+
+impl FromRequest for ApiVersion {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(ApiVersion::of(req)))
+    }
+}
@@ -0,0 +1,373 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::config::WebhookConfig;
+use crate::events::{DomainEvent, EventHandler, HandlerError};
+use crate::jobs::{Job, JobError, JobQueue};
+use crate::model::{DeadLetter, Todo, TodoId};
+use crate::repository::WebhookRepository;
+
+/// Header carrying `sha256=<hex HMAC of the body>`, keyed by the webhook's secret.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TodoEvent {
+    #[serde(rename = "todo.created")]
+    Created,
+    #[serde(rename = "todo.updated")]
+    Updated,
+    #[serde(rename = "todo.deleted")]
+    Deleted,
+}
+
+impl TodoEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoEvent::Created => "todo.created",
+            TodoEvent::Updated => "todo.updated",
+            TodoEvent::Deleted => "todo.deleted",
+        }
+    }
+}
+
+impl FromStr for TodoEvent {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "todo.created" => Ok(TodoEvent::Created),
+            "todo.updated" => Ok(TodoEvent::Updated),
+            "todo.deleted" => Ok(TodoEvent::Deleted),
+            other => Err(format!("unknown todo event: {}", other)),
+        }
+    }
+}
+
+/// JSON body POSTed to subscribers. `todo` is left out for deletions and
+/// for batch updates that do not load the full todo.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub id: String,
+    pub event: TodoEvent,
+    pub occurred_at: DateTime<Utc>,
+    pub todo_id: TodoId,
+    pub todo: Option<Todo>,
+}
+
+/// Computes the value of [`SIGNATURE_HEADER`] for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Checks that `url` is an http(s) URL whose host resolves only to public
+/// addresses, so webhooks cannot be pointed at this host or the network
+/// it sits in.
+pub async fn check_destination(url: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(url).map_err(|_| "url is not a valid URL".to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("url must be an http:// or https:// URL".to_string());
+    }
+    let host = url.host_str().ok_or("url has no host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    // IPv6 hosts come bracketed.
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<IpAddr> = match literal.parse() {
+        Ok(ip) => vec![ip],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| format!("url host '{}' does not resolve", host))?
+            .map(|address| address.ip())
+            .collect(),
+    };
+    if addresses.is_empty() {
+        return Err(format!("url host '{}' does not resolve", host));
+    }
+    if addresses.into_iter().any(is_internal) {
+        return Err(format!("url host '{}' is not a public address", host));
+    }
+    Ok(())
+}
+
+/// Loopback, private, link-local and other addresses that are not reachable
+/// on the public internet.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // "This network", 0.0.0.0/8.
+                || a == 0
+                // Carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => {
+            // NAT64 translates 64:ff9b::/96 to the IPv4 address in its
+            // last 32 bits.
+            let [s0, s1, s2, s3, s4, s5, ..] = ip.segments();
+            let nat64 = [s0, s1, s2, s3, s4, s5] == [0x64, 0xff9b, 0, 0, 0, 0];
+            match ip.to_ipv4_mapped() {
+                Some(ip) => is_internal(ip.into()),
+                None if nat64 => {
+                    let [.., a, b, c, d] = ip.octets();
+                    is_internal(Ipv4Addr::new(a, b, c, d).into())
+                }
+                None => {
+                    ip.is_loopback()
+                        || ip.is_unspecified()
+                        || ip.is_unique_local()
+                        || ip.is_unicast_link_local()
+                        || ip.is_multicast()
+                }
+            }
+        }
+    }
+}
+
+/// Resolves webhook hosts when a delivery connects, refusing internal
+/// addresses. [`check_destination`] resolves the host separately, so
+/// without this a host could pass the check and then resolve elsewhere.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            if addresses.iter().any(|address| is_internal(address.ip())) {
+                return Err(format!("host '{}' is not a public address", host).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Queues todo events for delivery to every matching webhook, as they
+/// come in from the domain events. Publishing only persists a job;
+/// delivery happens on the job queue. An event whose job could not be
+/// persisted is handed over again by the outbox.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    jobs: JobQueue,
+}
+
+impl WebhookDispatcher {
+    /// Registers the fan-out and delivery jobs on `jobs`.
+    pub fn new(
+        jobs: JobQueue,
+        repository: Arc<dyn WebhookRepository>,
+        config: &WebhookConfig,
+    ) -> Result<Self, reqwest::Error> {
+        // Redirects are not followed: the target was never checked.
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver))
+            .build()?;
+        jobs.register(Arc::new(FanoutJob {
+            jobs: jobs.clone(),
+            repository: repository.clone(),
+        }));
+        jobs.register(Arc::new(DeliveryJob {
+            client,
+            repository,
+            max_attempts: config.max_attempts.max(1),
+        }));
+
+        Ok(WebhookDispatcher { jobs })
+    }
+
+    pub async fn publish(
+        &self,
+        event: TodoEvent,
+        todo_id: &TodoId,
+        todo: Option<&Todo>,
+    ) -> Result<(), HandlerError> {
+        let payload = WebhookPayload {
+            id: Uuid::new_v4().to_string(),
+            event,
+            occurred_at: Utc::now(),
+            todo_id: *todo_id,
+            todo: todo.cloned(),
+        };
+        let payload = serde_json::to_value(&payload).map_err(|e| HandlerError(e.to_string()))?;
+        self.jobs
+            .enqueue(FanoutJob::KIND, payload)
+            .await
+            .map_err(|e| HandlerError(format!("could not queue the fan-out: {}", e)))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for WebhookDispatcher {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), HandlerError> {
+        match event {
+            DomainEvent::TodoCreated { todo, .. } => {
+                self.publish(TodoEvent::Created, &event.todo_id(), Some(todo))
+                    .await
+            }
+            DomainEvent::TodoUpdated { id, todo, .. } => {
+                self.publish(TodoEvent::Updated, id, todo.as_ref()).await
+            }
+            DomainEvent::TodoDeleted { id, .. } => self.publish(TodoEvent::Deleted, id, None).await,
+            // Subscribers hear of completions as the update they come with.
+            DomainEvent::TodoCompleted { .. } => Ok(()),
+        }
+    }
+}
+
+/// Looks up the webhooks subscribed to an event and queues one delivery each.
+struct FanoutJob {
+    jobs: JobQueue,
+    repository: Arc<dyn WebhookRepository>,
+}
+
+impl FanoutJob {
+    const KIND: &'static str = "webhook.fanout";
+}
+
+#[async_trait]
+impl Job for FanoutJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    async fn run(&self, payload: &serde_json::Value) -> Result<(), JobError> {
+        let event: WebhookPayload =
+            serde_json::from_value(payload.clone()).map_err(|e| JobError(e.to_string()))?;
+
+        for webhook in self.repository.list().await? {
+            if !webhook.active || !webhook.subscribes_to(event.event) {
+                continue;
+            }
+            let delivery = DeliveryPayload {
+                webhook_id: webhook.id,
+                payload: event.clone(),
+            };
+            let delivery = serde_json::to_value(&delivery).map_err(|e| JobError(e.to_string()))?;
+            self.jobs.enqueue(DeliveryJob::KIND, delivery).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeliveryPayload {
+    webhook_id: String,
+    payload: WebhookPayload,
+}
+
+/// POSTs one signed payload to one webhook. Non-2xx responses and transport
+/// errors are retried by the queue; the final failure becomes a dead letter.
+struct DeliveryJob {
+    client: reqwest::Client,
+    repository: Arc<dyn WebhookRepository>,
+    max_attempts: u32,
+}
+
+impl DeliveryJob {
+    const KIND: &'static str = "webhook.delivery";
+}
+
+#[async_trait]
+impl Job for DeliveryJob {
+    fn kind(&self) -> &'static str {
+        Self::KIND
+    }
+
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.max_attempts)
+    }
+
+    async fn run(&self, payload: &serde_json::Value) -> Result<(), JobError> {
+        let delivery: DeliveryPayload =
+            serde_json::from_value(payload.clone()).map_err(|e| JobError(e.to_string()))?;
+
+        // Deleted or deactivated since the event was published.
+        let Some(webhook) = self.repository.find_by_id(&delivery.webhook_id).await? else {
+            return Ok(());
+        };
+        if !webhook.active {
+            return Ok(());
+        }
+        // Checked again here: the host may resolve elsewhere by now. The
+        // client's resolver holds the connection itself to the same rule.
+        check_destination(&webhook.url).await.map_err(JobError)?;
+
+        let body = serde_json::to_vec(&delivery.payload).map_err(|e| JobError(e.to_string()))?;
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, &body))
+            .header(EVENT_HEADER, delivery.payload.event.as_str())
+            .header(DELIVERY_HEADER, &delivery.payload.id)
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| JobError(e.to_string()))?;
+        if response.status().is_redirection() {
+            return Err(JobError(format!(
+                "webhook answered with a redirect ({})",
+                response.status()
+            )));
+        }
+
+        log::info!(
+            "event=webhook_delivered webhook_id={} delivery_id={}",
+            webhook.id,
+            delivery.payload.id
+        );
+        Ok(())
+    }
+
+    async fn on_failure(&self, payload: &serde_json::Value, attempts: u32, error: &JobError) {
+        let Ok(delivery) = serde_json::from_value::<DeliveryPayload>(payload.clone()) else {
+            return;
+        };
+
+        let dead_letter = DeadLetter {
+            id: Uuid::new_v4().to_string(),
+            webhook_id: delivery.webhook_id.clone(),
+            event: delivery.payload.event,
+            payload: serde_json::to_value(&delivery.payload).unwrap_or_default(),
+            attempts,
+            last_error: error.to_string(),
+            failed_at: Utc::now(),
+        };
+        if let Err(e) = self.repository.add_dead_letter(&dead_letter).await {
+            log::error!(
+                "event=webhook_dead_letter_failed webhook_id={} delivery_id={} error=\"{}\"",
+                delivery.webhook_id,
+                delivery.payload.id,
+                e
+            );
+        }
+    }
+}
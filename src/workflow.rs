@@ -0,0 +1,146 @@
+//! The status workflow: which moves between statuses a todo may make.
+//! Setting the older `completed` flag moves a todo to or from done, so
+//! every write to it, not only `PATCH /todos/{id}/status`, is checked
+//! against the workflow.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::error::AppError;
+use crate::events::DomainEvent;
+use crate::model::{AppState, Todo, TodoId, TodoStatus, UndoAction};
+use crate::outbox;
+use crate::repository::{TodoPatch, TodoWrite};
+
+/// The allowed moves between statuses.
+#[derive(Debug, Clone)]
+pub struct StatusWorkflow {
+    allowed: HashMap<TodoStatus, Vec<TodoStatus>>,
+}
+
+impl Default for StatusWorkflow {
+    /// Todos can be done straight from the backlog, as completing them
+    /// always could, but not while blocked.
+    fn default() -> Self {
+        StatusWorkflow::new([
+            (TodoStatus::Backlog, TodoStatus::InProgress),
+            (TodoStatus::Backlog, TodoStatus::Blocked),
+            (TodoStatus::Backlog, TodoStatus::Done),
+            (TodoStatus::InProgress, TodoStatus::Backlog),
+            (TodoStatus::InProgress, TodoStatus::Blocked),
+            (TodoStatus::InProgress, TodoStatus::Done),
+            (TodoStatus::Blocked, TodoStatus::Backlog),
+            (TodoStatus::Blocked, TodoStatus::InProgress),
+            (TodoStatus::Done, TodoStatus::InProgress),
+            (TodoStatus::Done, TodoStatus::Backlog),
+        ])
+    }
+}
+
+impl StatusWorkflow {
+    /// A workflow allowing exactly the given `(from, to)` moves.
+    pub fn new(transitions: impl IntoIterator<Item = (TodoStatus, TodoStatus)>) -> Self {
+        let mut allowed: HashMap<TodoStatus, Vec<TodoStatus>> = HashMap::new();
+        for (from, to) in transitions {
+            let targets = allowed.entry(from).or_default();
+            if from != to && !targets.contains(&to) {
+                targets.push(to);
+            }
+        }
+        StatusWorkflow { allowed }
+    }
+
+    /// Parses moves such as `backlog->in_progress,in_progress->done`;
+    /// `None` when any of them is malformed.
+    pub fn parse(value: &str) -> Option<Self> {
+        let transitions = value
+            .split(',')
+            .map(str::trim)
+            .filter(|transition| !transition.is_empty())
+            .map(|transition| {
+                let (from, to) = transition.split_once("->")?;
+                Some((from.trim().parse().ok()?, to.trim().parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(StatusWorkflow::new(transitions))
+    }
+
+    /// The statuses a todo in `from` may move to.
+    pub fn targets(&self, from: TodoStatus) -> &[TodoStatus] {
+        self.allowed
+            .get(&from)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Checks a move from `from` to `to`; staying put is always allowed.
+    pub fn check(&self, from: TodoStatus, to: TodoStatus) -> Result<(), AppError> {
+        if from == to || self.targets(from).contains(&to) {
+            return Ok(());
+        }
+        let targets: Vec<&str> = self.targets(from).iter().map(TodoStatus::as_str).collect();
+        Err(AppError::Conflict(format!(
+            "A todo cannot move from {} to {}; allowed: {}",
+            from.as_str(),
+            to.as_str(),
+            if targets.is_empty() {
+                "none".to_string()
+            } else {
+                targets.join(", ")
+            }
+        )))
+    }
+}
+
+/// Moves `todo` to `status` on behalf of `actor_id` if the workflow allows
+/// it, keeping `completed` in step, and returns whether anything changed.
+pub async fn set_status(
+    data: &AppState,
+    id: &TodoId,
+    todo: &mut Todo,
+    status: TodoStatus,
+    actor_id: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<bool, AppError> {
+    let current = todo.status();
+    data.workflow.check(current, status)?;
+    if current == status {
+        return Ok(false);
+    }
+
+    let completed = status == TodoStatus::Done;
+    let patch = TodoPatch {
+        status: Some(status),
+        completed: Some(completed).filter(|completed| Some(*completed) != todo.completed),
+        updated_at: now,
+        expires_at: todo.expires_at,
+        ..TodoPatch::default()
+    };
+    let before = todo.clone();
+    patch.apply(todo);
+    let completes = patch.completed == Some(true);
+    let mut events = vec![DomainEvent::TodoUpdated {
+        id: *id,
+        todo: Some(todo.clone()),
+        title: None,
+        actor_id: actor_id.map(str::to_string),
+    }];
+    if completes {
+        events.push(DomainEvent::TodoCompleted {
+            id: *id,
+            actor_id: actor_id.map(str::to_string),
+        });
+    }
+    outbox::commit(data, TodoWrite::Patch(*id, patch), events).await?;
+    if completes {
+        data.undo
+            .record(actor_id, UndoAction::Complete, vec![before])
+            .await;
+    }
+
+    if completed && todo.recurrence.is_some() {
+        data.recurrence.wake().await;
+    }
+    Ok(true)
+}
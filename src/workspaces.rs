@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+
+use crate::auth::AuthUser;
+use crate::error::AppError;
+use crate::model::{AppState, WorkspaceMember, WorkspaceRole};
+use crate::repository::{TodoScope, WorkspaceRepository};
+
+/// Header selecting the workspace a todo request operates in.
+pub const WORKSPACE_HEADER: &str = "X-Workspace-Id";
+
+/// The todos a request may see: the workspace named by the
+/// `X-Workspace-Id` header, or the caller's personal todos without it.
+/// Naming a workspace requires a bearer token for one of its members.
+#[derive(Debug, Clone)]
+pub struct RequestScope(pub TodoScope);
+
+impl FromRequest for RequestScope {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let workspace_id = match req.headers().get(WORKSPACE_HEADER) {
+            None => return Box::pin(async { Ok(RequestScope(TodoScope::Personal)) }),
+            Some(value) => value.to_str().map(|value| value.trim().to_string()),
+        };
+        let user = AuthUser::from_request(req, payload).into_inner();
+        let state = req.app_data::<web::Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let workspace_id = workspace_id
+                .ok()
+                .filter(|id| !id.is_empty())
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!("{} must not be empty", WORKSPACE_HEADER))
+                })?;
+            let user = user?;
+            let state = state
+                .ok_or_else(|| AppError::Internal("Application state is missing".to_string()))?;
+
+            require_member(state.workspaces.as_ref(), &workspace_id, &user).await?;
+            Ok(RequestScope(TodoScope::Workspace(workspace_id)))
+        })
+    }
+}
+
+/// The caller's membership of `workspace_id`. Non-members get the same
+/// 404 as for a missing workspace so workspace IDs cannot be probed.
+pub async fn require_member(
+    workspaces: &dyn WorkspaceRepository,
+    workspace_id: &str,
+    user: &AuthUser,
+) -> Result<WorkspaceMember, AppError> {
+    workspaces
+        .find_member(workspace_id, &user.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Workspace with ID: {} not found", workspace_id)))
+}
+
+/// Like [`require_member`], but only owners pass.
+pub async fn require_owner(
+    workspaces: &dyn WorkspaceRepository,
+    workspace_id: &str,
+    user: &AuthUser,
+) -> Result<WorkspaceMember, AppError> {
+    let member = require_member(workspaces, workspace_id, user).await?;
+    if member.role != WorkspaceRole::Owner {
+        return Err(AppError::Forbidden(
+            "Only workspace owners can do this".to_string(),
+        ));
+    }
+    Ok(member)
+}
@@ -0,0 +1,188 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use serde_json::json;
+use simple_api_actix_web::error::AppError;
+use simple_api_actix_web::handler;
+use simple_api_actix_web::model::{AppState, Role, User};
+use simple_api_actix_web::oauth::{self, ExternalAccount, OAuthProvider};
+
+const ADMIN: &str = "root@example.com";
+
+/// In-memory state with `ADMIN` listed in `ADMIN_EMAILS`.
+async fn state() -> AppState {
+    common::memory_state(|config| {
+        config.auth.admin_emails = vec![ADMIN.to_string()];
+        config.auth.password_reset.url = Some("https://todos.example.com/reset".to_string());
+    })
+    .await
+}
+
+/// Emails `user` a reset link and returns the token in it.
+async fn emailed_reset_token(state: &AppState, user: &User) -> String {
+    let email = state.password_resets.issue(user).await.unwrap();
+    let (_, link) = email.message.split_once("token=").unwrap();
+    link.chars().take_while(char::is_ascii_hexdigit).collect()
+}
+
+#[actix_web::test]
+async fn registering_an_admin_email_gives_a_plain_account_until_it_is_verified() {
+    let state = state().await;
+    let state = web::Data::new(state);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": ADMIN, "password": "correct horse battery" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let user = state.users.find_by_email(ADMIN).await.unwrap().unwrap();
+    assert_eq!(user.role, Role::User);
+
+    // Redeeming a reset sent to the address shows the owner controls it.
+    let token = emailed_reset_token(&state, &user).await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/reset-password")
+        .set_json(json!({ "token": token, "password": "battery staple horse" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let user = state.users.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(user.role, Role::Admin);
+}
+
+#[actix_web::test]
+async fn other_addresses_stay_plain_accounts_after_a_reset() {
+    let state = state().await;
+    let state = web::Data::new(state);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": "someone@example.com", "password": "correct horse battery" }));
+    test::call_service(&app, req.to_request()).await;
+    let user = state
+        .users
+        .find_by_email("someone@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let token = emailed_reset_token(&state, &user).await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/reset-password")
+        .set_json(json!({ "token": token, "password": "battery staple horse" }));
+    test::call_service(&app, req.to_request()).await;
+    let user = state.users.find_by_id(&user.id).await.unwrap().unwrap();
+    assert_eq!(user.role, Role::User);
+}
+
+#[actix_web::test]
+async fn a_provider_verified_admin_email_signs_in_as_admin() {
+    let state = state().await;
+    let account = |subject: &str, email: &str| ExternalAccount {
+        provider: OAuthProvider::GitHub,
+        subject: subject.to_string(),
+        email: Some(email.to_string()),
+    };
+
+    let admin = oauth::resolve_user(&state, &account("1", ADMIN))
+        .await
+        .unwrap();
+    assert_eq!(admin.role, Role::Admin);
+    let user = oauth::resolve_user(&state, &account("2", "someone@example.com"))
+        .await
+        .unwrap();
+    assert_eq!(user.role, Role::User);
+}
+
+#[actix_web::test]
+async fn promotion_ends_earlier_sessions_and_second_factor() {
+    let state = state().await;
+    let state = web::Data::new(state);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+
+    let credentials = json!({ "email": ADMIN, "password": "correct horse battery" });
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(&credentials);
+    test::call_service(&app, req.to_request()).await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(&credentials);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let refresh_token = body["data"]["refreshToken"].as_str().unwrap().to_string();
+    let user = state.users.find_by_email(ADMIN).await.unwrap().unwrap();
+    state.totp.enroll(&user).await.unwrap();
+
+    let token = emailed_reset_token(&state, &user).await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/reset-password")
+        .set_json(json!({ "token": token, "password": "battery staple horse" }));
+    test::call_service(&app, req.to_request()).await;
+
+    assert!(state.users.find_totp(&user.id).await.unwrap().is_none());
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": refresh_token }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn a_provider_cannot_take_over_an_address_registered_with_a_password() {
+    let state = state().await;
+    let state = web::Data::new(state);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+
+    let credentials =
+        json!({ "email": "someone@example.com", "password": "correct horse battery" });
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(&credentials);
+    test::call_service(&app, req.to_request()).await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(&credentials);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let refresh_token = body["data"]["refreshToken"].as_str().unwrap().to_string();
+
+    let account = ExternalAccount {
+        provider: OAuthProvider::Google,
+        subject: "1".to_string(),
+        email: Some("someone@example.com".to_string()),
+    };
+    let err = oauth::resolve_user(&state, &account).await.unwrap_err();
+    assert!(matches!(err, AppError::Conflict(_)), "{:?}", err);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(&credentials);
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": refresh_token }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
@@ -0,0 +1,67 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use simple_api_actix_web::bridge::{self, BridgeError, Transport};
+use simple_api_actix_web::events::{DomainEvent, EventBus};
+use simple_api_actix_web::model::TodoId;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// A broker in memory, shared by the instances of a test.
+struct Loopback {
+    messages: broadcast::Sender<Vec<u8>>,
+}
+
+#[async_trait]
+impl Transport for Loopback {
+    fn name(&self) -> &'static str {
+        "loopback"
+    }
+
+    async fn publish(&self, message: &[u8]) -> Result<(), BridgeError> {
+        let _ = self.messages.send(message.to_vec());
+        Ok(())
+    }
+
+    async fn subscribe(&self, deliver: &(dyn Fn(Vec<u8>) + Send + Sync)) -> BridgeError {
+        let mut messages = self.messages.subscribe();
+        while let Ok(message) = messages.recv().await {
+            deliver(message);
+        }
+        BridgeError("closed".to_string())
+    }
+}
+
+#[tokio::test]
+async fn events_reach_subscribers_of_every_instance_once() {
+    let transport = Arc::new(Loopback {
+        messages: broadcast::channel(16).0,
+    });
+    let (here, there) = (EventBus::new(), EventBus::new());
+    bridge::start(&here, transport.clone());
+    bridge::start(&there, transport);
+    // Let both subscribe to the broker.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let mut seen_here = here.subscribe();
+    let mut seen_there = there.subscribe();
+    let id = TodoId(Uuid::new_v4());
+    here.publish(DomainEvent::TodoDeleted {
+        id,
+        actor_id: Some("user-1".to_string()),
+    });
+
+    for seen in [&mut seen_here, &mut seen_there] {
+        let event = tokio::time::timeout(Duration::from_secs(1), seen.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name(), "todo_deleted");
+        assert_eq!(event.todo_id(), id);
+        assert_eq!(event.actor_id(), Some("user-1"));
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(seen_here.try_recv().is_err());
+    assert!(seen_there.try_recv().is_err());
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use actix_web::{middleware, test, web, App, HttpResponse};
+use serde_json::json;
+use simple_api_actix_web::chaos::{self, FaultInjector, FaultRule};
+
+fn rules(rules: serde_json::Value) -> Vec<FaultRule> {
+    serde_json::from_value(rules).unwrap()
+}
+
+#[actix_web::test]
+async fn faults_are_injected_on_matching_routes() {
+    let injector = web::Data::new(FaultInjector::new());
+    let app = test::init_service(
+        App::new()
+            .app_data(injector.clone())
+            .route("/api/todos", web::get().to(HttpResponse::Ok))
+            .route("/api/todos", web::post().to(HttpResponse::Created))
+            .route("/api/todos/{id}", web::get().to(HttpResponse::Ok))
+            .wrap(middleware::from_fn(chaos::inject_faults)),
+    )
+    .await;
+
+    injector
+        .set(rules(json!([
+            { "route": "/todos", "method": "GET", "errorPercent": 100, "errorStatus": 502 },
+            { "route": "/todos/{id}", "latencyPercent": 100, "latencyMs": 50 },
+        ])))
+        .unwrap();
+
+    let status = |req: test::TestRequest| {
+        let app = &app;
+        async move {
+            match test::try_call_service(app, req.to_request()).await {
+                Ok(res) => res.status(),
+                Err(e) => e.error_response().status(),
+            }
+        }
+    };
+    assert_eq!(
+        status(test::TestRequest::get().uri("/api/todos")).await,
+        StatusCode::BAD_GATEWAY
+    );
+    assert_eq!(
+        status(test::TestRequest::post().uri("/api/todos")).await,
+        StatusCode::CREATED
+    );
+    let started = Instant::now();
+    assert_eq!(
+        status(test::TestRequest::get().uri("/api/todos/1")).await,
+        StatusCode::OK
+    );
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    injector.set(Vec::new()).unwrap();
+    assert_eq!(
+        status(test::TestRequest::get().uri("/api/todos")).await,
+        StatusCode::OK
+    );
+}
+
+#[actix_web::test]
+async fn invalid_rules_are_refused() {
+    let injector = FaultInjector::new();
+    for rule in [
+        json!({ "route": "*", "errorPercent": 120 }),
+        json!({ "route": "*", "errorPercent": 60, "dropPercent": 60 }),
+        json!({ "route": "*", "errorPercent": 10, "errorStatus": 404 }),
+        json!({ "route": "", "latencyPercent": 10 }),
+    ] {
+        assert!(injector.set(rules(json!([rule]))).is_err());
+    }
+    assert!(injector.rules().is_empty());
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use simple_api_actix_web::circuit_breaker::CircuitBreaker;
+use simple_api_actix_web::config::CircuitBreakerConfig;
+
+fn breaker(open_duration: Duration) -> CircuitBreaker {
+    CircuitBreaker::new(&CircuitBreakerConfig {
+        failure_threshold: 1,
+        open_duration,
+    })
+}
+
+#[test]
+fn late_successes_leave_an_open_circuit_open() {
+    let breaker = breaker(Duration::from_secs(60));
+    let first = breaker.try_acquire().unwrap();
+    let second = breaker.try_acquire().unwrap();
+
+    breaker.on_failure(first);
+    breaker.on_success(second);
+    assert!(breaker.try_acquire().is_none());
+}
+
+#[test]
+fn a_successful_probe_closes_the_circuit() {
+    let breaker = breaker(Duration::from_millis(20));
+    breaker.on_failure(breaker.try_acquire().unwrap());
+    assert!(breaker.try_acquire().is_none());
+
+    std::thread::sleep(Duration::from_millis(30));
+    let probe = breaker.try_acquire().unwrap();
+    assert!(breaker.try_acquire().is_none());
+    breaker.on_success(probe);
+    assert!(breaker.try_acquire().is_some());
+    assert!(breaker.try_acquire().is_some());
+}
+
+#[test]
+fn only_the_probe_decides_a_half_open_circuit() {
+    let breaker = breaker(Duration::from_millis(20));
+    let early = breaker.try_acquire().unwrap();
+    let late = breaker.try_acquire().unwrap();
+    breaker.on_failure(early);
+
+    std::thread::sleep(Duration::from_millis(30));
+    let probe = breaker.try_acquire().unwrap();
+    breaker.on_success(late);
+    assert!(breaker.try_acquire().is_none());
+    breaker.on_failure(late);
+    assert!(breaker.try_acquire().is_none());
+
+    breaker.on_success(probe);
+    assert!(breaker.try_acquire().is_some());
+}
+
+#[test]
+fn a_probe_given_up_on_no_longer_decides() {
+    let breaker = breaker(Duration::from_millis(20));
+    breaker.on_failure(breaker.try_acquire().unwrap());
+
+    std::thread::sleep(Duration::from_millis(30));
+    let stale = breaker.try_acquire().unwrap();
+    std::thread::sleep(Duration::from_millis(30));
+    let probe = breaker.try_acquire().unwrap();
+
+    breaker.on_success(stale);
+    assert!(breaker.try_acquire().is_none());
+    breaker.on_failure(probe);
+    assert!(breaker.try_acquire().is_none());
+}
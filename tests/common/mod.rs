@@ -0,0 +1,171 @@
+//! Shared setup for the integration tests: an ephemeral Scylla container
+//! per test and the application wired exactly as the server binary does,
+//! which requires a running Docker daemon, or state kept in memory.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{middleware, test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::app::{assemble_state, build_state, create_repositories};
+use simple_api_actix_web::config::{BlobBackend, Config, Replication, StorageBackend};
+use simple_api_actix_web::metrics::QueryMetrics;
+use simple_api_actix_web::model::AppState;
+use simple_api_actix_web::secrets::Secrets;
+use simple_api_actix_web::{
+    casing, consistency, csrf, formats, handler, i18n, maintenance, preferences, signing,
+};
+use testcontainers::core::IntoContainerPort;
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+
+const SCYLLA_IMAGE: &str = "scylladb/scylla";
+const SCYLLA_TAG: &str = "5.4";
+const CQL_PORT: u16 = 9042;
+
+/// A migrated Scylla instance and the state built on top of it. The
+/// container is removed when this is dropped.
+pub struct TestContext {
+    pub config: Config,
+    pub state: web::Data<AppState>,
+    _scylla: ContainerAsync<GenericImage>,
+}
+
+impl TestContext {
+    pub async fn start() -> TestContext {
+        let scylla = GenericImage::new(SCYLLA_IMAGE, SCYLLA_TAG)
+            .with_exposed_port(CQL_PORT.tcp())
+            .with_cmd([
+                "--smp",
+                "1",
+                "--memory",
+                "512M",
+                "--overprovisioned",
+                "1",
+                "--developer-mode",
+                "1",
+            ])
+            .start()
+            .await
+            .expect("failed to start the Scylla container");
+        let host = scylla.get_host().await.expect("container host");
+        let port = scylla
+            .get_host_port_ipv4(CQL_PORT)
+            .await
+            .expect("mapped CQL port");
+
+        // Scylla takes a while to accept CQL connections after the container
+        // is up; the connection retry in `build_state` covers that.
+        let mut config = Config::from_env();
+        config.storage.backend = StorageBackend::Scylla;
+        config.database.contact_points = vec![format!("{}:{}", host, port)];
+        config.database.bootstrap = Some(Replication::Simple { factor: 1 });
+        config.database.migrate_on_start = true;
+        config.attachments.backend = BlobBackend::Local;
+        config.attachments.local_dir =
+            std::env::temp_dir().join(format!("todo-attachments-{}", uuid::Uuid::new_v4()));
+
+        let state = build_state(&config)
+            .await
+            .expect("failed to build application state");
+
+        TestContext {
+            config,
+            state: web::Data::new(state),
+            _scylla: scylla,
+        }
+    }
+
+    /// The application as `main` assembles it, minus CORS and logging.
+    pub fn app(
+        &self,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(self.state.clone())
+            .app_data(self.config.server.field_case)
+            .app_data(self.config.auth.cookies)
+            .configure(handler::config)
+            .wrap(middleware::from_fn(maintenance::refuse_during_maintenance))
+            .wrap(middleware::from_fn(preferences::load_user_settings))
+            .wrap(middleware::from_fn(consistency::read_your_writes))
+            .wrap(middleware::from_fn(csrf::protect))
+            .wrap(middleware::from_fn(casing::apply_field_case))
+            .wrap(middleware::from_fn(formats::negotiate_format))
+            .wrap(middleware::from_fn(signing::verify_signatures))
+            .wrap(middleware::from_fn(i18n::negotiate_language))
+    }
+}
+
+/// State backed by the in-memory repositories, for tests that need no
+/// database. `configure` adjusts the configuration before it is built.
+pub async fn memory_state(configure: impl FnOnce(&mut Config)) -> AppState {
+    let mut config = Config::from_env();
+    config.storage.backend = StorageBackend::Memory;
+    config.attachments.backend = BlobBackend::Local;
+    config.attachments.local_dir =
+        std::env::temp_dir().join(format!("todo-attachments-{}", uuid::Uuid::new_v4()));
+    configure(&mut config);
+
+    let query_metrics = Arc::new(QueryMetrics::new());
+    let secrets = Secrets::default();
+    let repositories = create_repositories(&config, query_metrics.clone(), &secrets)
+        .await
+        .unwrap();
+    let (state, _queue) = assemble_state(&config, repositories, query_metrics, &secrets)
+        .await
+        .unwrap();
+    state
+}
+
+/// Registers a user and returns a bearer token for them.
+pub async fn register<S, B>(app: &S, email: &str) -> String
+where
+    S: actix_web::dev::Service<
+        actix_http::Request,
+        Response = ServiceResponse<B>,
+        Error = actix_web::Error,
+    >,
+    B: MessageBody,
+{
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": email, "password": "correct horse battery" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(app, req).await;
+    body["data"]["token"]
+        .as_str()
+        .expect("registration returns a token")
+        .to_string()
+}
+
+/// Creates a todo and returns its ID.
+pub async fn create_todo<S, B>(app: &S, title: &str) -> String
+where
+    S: actix_web::dev::Service<
+        actix_http::Request,
+        Response = ServiceResponse<B>,
+        Error = actix_web::Error,
+    >,
+    B: MessageBody,
+{
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .set_json(json!({ "title": title, "content": "integration test" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(app, req).await;
+    body["data"]["todo"]["id"]
+        .as_str()
+        .expect("created todo has an ID")
+        .to_string()
+}
@@ -0,0 +1,118 @@
+use std::io::Read;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{middleware, test, web, App, HttpResponse};
+use serde_json::{json, Value};
+use simple_api_actix_web::compression;
+use simple_api_actix_web::config::CompressionConfig;
+
+const CONFIG: CompressionConfig = CompressionConfig {
+    enabled: true,
+    gzip_level: 6,
+    brotli_quality: 4,
+    min_size: 1024,
+};
+
+/// A JSON list of `count` todos, well over the minimum size for large counts.
+fn todos(count: usize) -> Value {
+    let todos: Vec<Value> = (0..count)
+        .map(|i| json!({ "id": i, "title": format!("Todo {}", i), "completed": false }))
+        .collect();
+    json!({ "status": "success", "results": count, "todos": todos })
+}
+
+async fn get(
+    config: CompressionConfig,
+    count: usize,
+    accept_encoding: Option<&str>,
+) -> (StatusCode, header::HeaderMap, Vec<u8>) {
+    let app = test::init_service(
+        App::new()
+            .app_data(config)
+            .route(
+                "/todos",
+                web::get().to(move || async move { HttpResponse::Ok().json(todos(count)) }),
+            )
+            .wrap(middleware::from_fn(compression::compress_responses)),
+    )
+    .await;
+
+    let mut req = test::TestRequest::get().uri("/todos");
+    if let Some(accept_encoding) = accept_encoding {
+        req = req.insert_header((header::ACCEPT_ENCODING, accept_encoding));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = test::read_body(res).await.to_vec();
+    (status, headers, body)
+}
+
+fn content_encoding(headers: &header::HeaderMap) -> Option<&str> {
+    headers
+        .get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[actix_web::test]
+async fn gzip_round_trips() {
+    let (status, headers, body) = get(CONFIG, 200, Some("gzip, deflate")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_encoding(&headers), Some("gzip"));
+
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert!(body.len() < decoded.len());
+    assert_eq!(
+        serde_json::from_slice::<Value>(&decoded).unwrap(),
+        todos(200)
+    );
+}
+
+#[actix_web::test]
+async fn brotli_round_trips_and_is_preferred() {
+    let (_, headers, body) = get(CONFIG, 200, Some("gzip, br")).await;
+    assert_eq!(content_encoding(&headers), Some("br"));
+
+    let mut decoded = Vec::new();
+    brotli::Decompressor::new(&body[..], 4096)
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&decoded).unwrap(),
+        todos(200)
+    );
+}
+
+#[actix_web::test]
+async fn honours_quality_values() {
+    let (_, headers, _) = get(CONFIG, 200, Some("br;q=0.5, gzip")).await;
+    assert_eq!(content_encoding(&headers), Some("gzip"));
+
+    let (_, headers, _) = get(CONFIG, 200, Some("br;q=0, gzip;q=0")).await;
+    assert_eq!(content_encoding(&headers), None);
+}
+
+#[actix_web::test]
+async fn small_bodies_are_sent_as_is() {
+    let (_, headers, body) = get(CONFIG, 1, Some("gzip, br")).await;
+    assert_eq!(content_encoding(&headers), None);
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), todos(1));
+}
+
+#[actix_web::test]
+async fn uncompressed_without_accept_encoding_or_when_disabled() {
+    let (_, headers, body) = get(CONFIG, 200, None).await;
+    assert_eq!(content_encoding(&headers), None);
+    assert_eq!(headers.get(header::VARY).unwrap(), "accept-encoding");
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), todos(200));
+
+    let disabled = CompressionConfig {
+        enabled: false,
+        ..CONFIG
+    };
+    let (_, headers, _) = get(disabled, 200, Some("gzip")).await;
+    assert_eq!(content_encoding(&headers), None);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::handler;
+
+/// Fetches `uri` as `bearer`, revalidating against `etag` when given, and
+/// returns the status and the `ETag` of the response.
+async fn fetch<S>(app: &S, uri: &str, bearer: &str, etag: Option<&str>) -> (StatusCode, String)
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let mut req = test::TestRequest::get()
+        .uri(uri)
+        .insert_header((header::AUTHORIZATION, bearer));
+    if let Some(etag) = etag {
+        req = req.insert_header((header::IF_NONE_MATCH, etag));
+    }
+    let res = test::call_service(app, req.to_request()).await;
+    let etag = res
+        .headers()
+        .get(header::ETAG)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    (res.status(), etag)
+}
+
+#[actix_web::test]
+async fn revalidation_notices_deleted_todos_and_new_comments() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let bearer = format!("Bearer {}", common::register(&app, "ada@example.com").await);
+
+    let mut ids = Vec::new();
+    for title in ["Older", "Newer"] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .set_json(json!({ "title": title, "content": "" }));
+        let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        ids.push(body["data"]["todo"]["id"].as_str().unwrap().to_string());
+    }
+
+    let (status, etag) = fetch(&app, "/api/todos", &bearer, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = fetch(&app, "/api/todos", &bearer, Some(&etag)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+    // Deleting the most recently updated todo leaves no newer timestamp.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", ids[1]))
+        .insert_header((header::AUTHORIZATION, bearer.as_str()));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    let (status, _) = fetch(&app, "/api/todos", &bearer, Some(&etag)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Commenting changes the todo's comment count, not its `updatedAt`.
+    let todo = format!("/api/todos/{}", ids[0]);
+    let (_, etag) = fetch(&app, &todo, &bearer, None).await;
+    let (status, _) = fetch(&app, &todo, &bearer, Some(&etag)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/comments", todo))
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "body": "Soon" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let (status, _) = fetch(&app, &todo, &bearer, Some(&etag)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn if_modified_since_applies_without_if_none_match() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let bearer = format!("Bearer {}", common::register(&app, "ada@example.com").await);
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "title": "Water plants", "content": "" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let todo = format!(
+        "/api/todos/{}",
+        body["data"]["todo"]["id"].as_str().unwrap()
+    );
+
+    let get = |headers: &[(header::HeaderName, &str)]| {
+        let mut req = test::TestRequest::get()
+            .uri(&todo)
+            .insert_header((header::AUTHORIZATION, bearer.as_str()));
+        for (name, value) in headers {
+            req = req.insert_header((name.clone(), *value));
+        }
+        test::call_service(&app, req.to_request())
+    };
+    let res = get(&[]).await;
+    let etag = res.headers().get(header::ETAG).unwrap().to_str().unwrap();
+    // Hashed before re-encoding, so shared by every representation.
+    assert!(etag.starts_with("W/"), "{}", etag);
+    let last_modified = res
+        .headers()
+        .get(header::LAST_MODIFIED)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let res = get(&[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    let res = get(&[(header::IF_MODIFIED_SINCE, "Sat, 01 Jan 2000 00:00:00 GMT")]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    // If-None-Match decides when both are sent.
+    let res = get(&[
+        (header::IF_MODIFIED_SINCE, &last_modified),
+        (header::IF_NONE_MATCH, "W/\"other\""),
+    ])
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
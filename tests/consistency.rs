@@ -0,0 +1,53 @@
+use actix_web::http::StatusCode;
+use actix_web::{middleware, test, web, App, HttpResponse};
+use simple_api_actix_web::consistency::{self, READ_YOUR_WRITES_HEADER};
+
+/// Responds with whether the handler saw a read-your-writes request.
+async fn get(header: Option<&str>) -> (StatusCode, String) {
+    let app = test::init_service(
+        App::new()
+            .route(
+                "/todos",
+                web::get().to(|| async {
+                    HttpResponse::Ok().body(consistency::reads_own_writes().to_string())
+                }),
+            )
+            .wrap(middleware::from_fn(consistency::read_your_writes)),
+    )
+    .await;
+
+    let mut req = test::TestRequest::get().uri("/todos");
+    if let Some(header) = header {
+        req = req.insert_header((READ_YOUR_WRITES_HEADER, header));
+    }
+    let res = match test::try_call_service(&app, req.to_request()).await {
+        Ok(res) => res,
+        Err(e) => return (e.as_response_error().status_code(), String::new()),
+    };
+    let status = res.status();
+    let body = test::read_body(res).await;
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[actix_web::test]
+async fn reads_are_ordinary_by_default() {
+    assert_eq!(get(None).await, (StatusCode::OK, "false".to_string()));
+    assert_eq!(
+        get(Some("false")).await,
+        (StatusCode::OK, "false".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn the_header_applies_to_the_whole_request() {
+    assert_eq!(
+        get(Some("true")).await,
+        (StatusCode::OK, "true".to_string())
+    );
+}
+
+#[actix_web::test]
+async fn other_values_are_rejected() {
+    let (status, _) = get(Some("yes")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,85 @@
+use actix_web::cookie::Cookie;
+use actix_web::http::{header, StatusCode};
+use actix_web::{middleware, test, web, App, HttpResponse};
+use simple_api_actix_web::auth::ACCESS_COOKIE;
+use simple_api_actix_web::config::CookieAuthConfig;
+use simple_api_actix_web::csrf::{self, CSRF_COOKIE, CSRF_HEADER};
+
+const CONFIG: CookieAuthConfig = CookieAuthConfig {
+    enabled: true,
+    secure: false,
+};
+
+/// The status of `req` sent to an app with one readable and one mutating
+/// route behind the CSRF check.
+async fn status(config: CookieAuthConfig, req: test::TestRequest) -> StatusCode {
+    let app = test::init_service(
+        App::new()
+            .app_data(config)
+            .route("/todos", web::get().to(HttpResponse::Ok))
+            .route("/todos", web::post().to(HttpResponse::Created))
+            .wrap(middleware::from_fn(csrf::protect)),
+    )
+    .await;
+    match test::try_call_service(&app, req.to_request()).await {
+        Ok(res) => res.status(),
+        Err(e) => e.error_response().status(),
+    }
+}
+
+/// A mutating request from a browser in a cookie session.
+fn signed_in_post() -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/todos")
+        .cookie(Cookie::new(ACCESS_COOKIE, "access"))
+        .cookie(Cookie::new(CSRF_COOKIE, "token"))
+}
+
+#[actix_web::test]
+async fn cookie_mutations_require_the_header() {
+    assert_eq!(
+        status(CONFIG, signed_in_post()).await,
+        StatusCode::FORBIDDEN
+    );
+
+    let req = signed_in_post().insert_header((CSRF_HEADER, "token"));
+    assert_eq!(status(CONFIG, req).await, StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn mismatched_tokens_are_rejected() {
+    let req = signed_in_post().insert_header((CSRF_HEADER, "guess"));
+    assert_eq!(status(CONFIG, req).await, StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri("/todos")
+        .cookie(Cookie::new(ACCESS_COOKIE, "access"))
+        .insert_header((CSRF_HEADER, "token"));
+    assert_eq!(status(CONFIG, req).await, StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn reads_and_bearer_requests_are_not_checked() {
+    let req = test::TestRequest::get()
+        .uri("/todos")
+        .cookie(Cookie::new(ACCESS_COOKIE, "access"));
+    assert_eq!(status(CONFIG, req).await, StatusCode::OK);
+
+    let req = signed_in_post().insert_header((header::AUTHORIZATION, "Bearer access"));
+    assert_eq!(status(CONFIG, req).await, StatusCode::CREATED);
+
+    let req = test::TestRequest::post().uri("/todos");
+    assert_eq!(status(CONFIG, req).await, StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn nothing_is_checked_without_cookie_sessions() {
+    let disabled = CookieAuthConfig {
+        enabled: false,
+        ..CONFIG
+    };
+    assert_eq!(
+        status(disabled, signed_in_post()).await,
+        StatusCode::CREATED
+    );
+}
@@ -0,0 +1,78 @@
+use scylla::frame::response::result::{CqlValue, Row};
+use scylla::frame::value::CqlTimestamp;
+use simple_api_actix_web::decoding::RowDecoding;
+use simple_api_actix_web::repository::{decode_todos, RepositoryError};
+use uuid::Uuid;
+
+const CREATED_AT: usize = 4;
+const RECURRENCE: usize = 8;
+const STATUS: usize = 17;
+
+/// A `SELECT_TODOS` row of a todo with every column set.
+fn row() -> Row {
+    let now = CqlValue::Timestamp(CqlTimestamp(1_700_000_000_000));
+    Row {
+        columns: vec![
+            Some(CqlValue::Uuid(Uuid::new_v4())),
+            Some(CqlValue::Text("Water the plants".to_string())),
+            Some(CqlValue::Text("The ferns too".to_string())),
+            Some(CqlValue::Boolean(false)),
+            Some(now.clone()),
+            Some(now.clone()),
+            Some(CqlValue::Boolean(false)),
+            Some(now.clone()),
+            Some(CqlValue::Text(r#"{"frequency":"weekly"}"#.to_string())),
+            None,
+            None,
+            Some(now),
+            None,
+            Some(CqlValue::Text("owner".to_string())),
+            None,
+            None,
+            Some(CqlValue::BigInt(1)),
+            Some(CqlValue::Text("in_progress".to_string())),
+            None,
+            Some(CqlValue::Text("water the plants".to_string())),
+            Some(CqlValue::Int(0)),
+        ],
+    }
+}
+
+/// [`row`] with `column` set to `value`.
+fn row_with(column: usize, value: CqlValue) -> Row {
+    let mut row = row();
+    row.columns[column] = Some(value);
+    row
+}
+
+fn decode_error(row: Row) -> String {
+    match decode_todos(vec![row], RowDecoding::Strict) {
+        Err(RepositoryError::Decode(message)) => message,
+        other => panic!("expected a decode error, got {:?}", other),
+    }
+}
+
+#[test]
+fn a_complete_row_decodes() {
+    let todos = decode_todos(vec![row()], RowDecoding::Strict).unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Water the plants");
+}
+
+#[test]
+fn a_timestamp_out_of_range_is_a_decode_error() {
+    let row = row_with(CREATED_AT, CqlValue::Timestamp(CqlTimestamp(i64::MAX)));
+    assert!(decode_error(row).contains("invalid created_at"));
+}
+
+#[test]
+fn recurrence_that_is_not_json_is_a_decode_error() {
+    let row = row_with(RECURRENCE, CqlValue::Text("{weekly".to_string()));
+    assert!(decode_error(row).contains("invalid recurrence"));
+}
+
+#[test]
+fn an_unknown_status_is_a_decode_error() {
+    let row = row_with(STATUS, CqlValue::Text("someday".to_string()));
+    assert!(decode_error(row).contains("invalid status"));
+}
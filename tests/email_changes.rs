@@ -0,0 +1,69 @@
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::handler;
+
+#[actix_web::test]
+async fn a_new_address_is_used_once_its_owner_confirms_it() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let bearer = format!("Bearer {}", common::register(&app, "ada@example.com").await);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/me")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "email": "victim" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/me")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "email": "Victim@Example.com", "displayName": "Ada" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["data"]["user"]["email"], "ada@example.com");
+    assert_eq!(body["data"]["user"]["displayName"], "Ada");
+    assert!(state
+        .users
+        .find_by_email("victim@example.com")
+        .await
+        .unwrap()
+        .is_none());
+
+    let user = state
+        .users
+        .find_by_email("ada@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    let token = state
+        .tokens
+        .issue_email_change(&user, "ada@new.example.com")
+        .unwrap();
+    let stale = state
+        .tokens
+        .issue_email_change(&user, "ada@other.example.com")
+        .unwrap();
+
+    let confirm = |token: &str| {
+        test::TestRequest::post()
+            .uri("/api/auth/confirm-email")
+            .set_json(json!({ "token": token }))
+            .to_request()
+    };
+    let res = test::call_service(&app, confirm("forged")).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::call_and_read_body_json(&app, confirm(&token)).await;
+    assert_eq!(body["data"]["user"]["email"], "ada@new.example.com");
+
+    // Asked for from the old address, which the account no longer uses.
+    let res = test::call_service(&app, confirm(&stale)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
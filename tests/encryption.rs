@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use futures_util::TryStreamExt;
+use simple_api_actix_web::config::EncryptionConfig;
+use simple_api_actix_web::decoding::RowDecoding;
+use simple_api_actix_web::encryption::ContentCipher;
+use simple_api_actix_web::model::{Todo, TodoId};
+use simple_api_actix_web::repository::{
+    EncryptedTodoRepository, InMemoryTodoRepository, ListOptions, RepositoryError, TodoRepository,
+    TodoScope, TodoSort, TodoVisibility,
+};
+
+fn cipher(keys: &[(&str, &str)], active_key: Option<&str>) -> ContentCipher {
+    ContentCipher::new(&EncryptionConfig {
+        keys: keys
+            .iter()
+            .map(|(id, key)| (id.to_string(), key.to_string()))
+            .collect(),
+        active_key: active_key.map(str::to_string),
+    })
+    .unwrap()
+}
+
+const OLD: (&str, &str) = (
+    "2024",
+    "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff",
+);
+const NEW: (&str, &str) = (
+    "2025",
+    "ffeeddccbbaa99887766554433221100ffeeddccbbaa99887766554433221100",
+);
+
+fn todo(title: &str, content: &str) -> Todo {
+    let now = Utc::now();
+    Todo {
+        id: Some(TodoId::generate()),
+        title: title.to_string(),
+        content: content.to_string(),
+        completed: Some(false),
+        archived: Some(false),
+        due_at: None,
+        recurrence: None,
+        series_id: None,
+        next_occurrence_id: None,
+        remind_at: None,
+        reminder_sent_at: None,
+        owner_id: None,
+        workspace_id: None,
+        assignee_id: None,
+        project_id: None,
+        position: None,
+        status: None,
+        created_at: Some(now),
+        updated_at: Some(now),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    }
+}
+
+fn everything() -> ListOptions {
+    ListOptions {
+        offset: 0,
+        limit: 100,
+        include_archived: true,
+        scope: TodoScope::All,
+        visibility: TodoVisibility::All,
+        owner_id: None,
+        created_after: None,
+        created_before: None,
+        project_id: None,
+        status: None,
+        assignee_id: None,
+        completed: None,
+        due_after: None,
+        due_before: None,
+        sort: TodoSort::CreatedAt,
+    }
+}
+
+#[test]
+fn content_round_trips_sealed_with_the_key_id() {
+    let cipher = cipher(&[OLD], None);
+    let sealed = cipher.seal("Buy milk").unwrap();
+    assert!(sealed.starts_with("enc:v1:2024:"));
+    assert!(!sealed.contains("milk"));
+    assert_ne!(sealed, cipher.seal("Buy milk").unwrap());
+    assert_eq!(cipher.open(&sealed).unwrap(), "Buy milk");
+    assert!(cipher.is_current(&sealed));
+}
+
+#[test]
+fn plain_content_passes_through() {
+    let disabled = cipher(&[], None);
+    assert_eq!(disabled.seal("Buy milk").unwrap(), "Buy milk");
+    assert!(disabled.is_current("Buy milk"));
+
+    let enabled = cipher(&[OLD], None);
+    assert_eq!(enabled.open("Buy milk").unwrap(), "Buy milk");
+    assert!(!enabled.is_current("Buy milk"));
+}
+
+#[test]
+fn plain_content_that_looks_sealed_round_trips() {
+    let disabled = cipher(&[], None);
+    let stored = disabled.seal("enc:v1:2024:not a secret").unwrap();
+    assert_ne!(stored, "enc:v1:2024:not a secret");
+    assert_eq!(disabled.open(&stored).unwrap(), "enc:v1:2024:not a secret");
+    assert!(disabled.is_current(&stored));
+
+    let enabled = cipher(&[OLD], None);
+    assert_eq!(enabled.open(&stored).unwrap(), "enc:v1:2024:not a secret");
+    assert!(!enabled.is_current(&stored));
+}
+
+#[test]
+fn rotated_keys_still_open_old_content() {
+    let sealed = cipher(&[OLD], None).seal("Buy milk").unwrap();
+    let rotated = cipher(&[NEW, OLD], Some("2025"));
+    assert_eq!(rotated.open(&sealed).unwrap(), "Buy milk");
+    assert!(!rotated.is_current(&sealed));
+    assert!(rotated
+        .seal("Buy milk")
+        .unwrap()
+        .starts_with("enc:v1:2025:"));
+
+    assert!(matches!(
+        cipher(&[NEW], None).open(&sealed),
+        Err(RepositoryError::Decode(_))
+    ));
+}
+
+#[test]
+fn tampered_content_is_rejected() {
+    let cipher = cipher(&[OLD], None);
+    let mut sealed = cipher.seal("Buy milk").unwrap();
+    let last = sealed.pop().unwrap();
+    sealed.push(if last == 'A' { 'B' } else { 'A' });
+    assert!(cipher.open(&sealed).is_err());
+}
+
+#[test]
+fn malformed_keys_are_refused() {
+    for (keys, active_key) in [
+        (vec![("2024", "abcd")], None),
+        (vec![("", OLD.1)], None),
+        (vec![("20:24", OLD.1)], None),
+        (vec![OLD, OLD], None),
+        (vec![OLD], Some("2025")),
+    ] {
+        let config = EncryptionConfig {
+            keys: keys
+                .iter()
+                .map(|(id, key)| (id.to_string(), key.to_string()))
+                .collect(),
+            active_key: active_key.map(str::to_string),
+        };
+        assert!(ContentCipher::new(&config).is_err(), "{:?}", config.keys);
+    }
+}
+
+#[tokio::test]
+async fn todos_that_cannot_be_opened_are_left_out_of_lists() {
+    let inner = Arc::new(InMemoryTodoRepository::new());
+    let sealed = cipher(&[NEW], None).seal("Buy milk").unwrap();
+    inner.insert(&todo("Lost key", &sealed)).await.unwrap();
+    inner.insert(&todo("Plain", "Water plants")).await.unwrap();
+
+    let lenient =
+        EncryptedTodoRepository::new(inner.clone(), cipher(&[OLD], None), RowDecoding::Lenient);
+    let todos = lenient.list(&everything()).await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Plain");
+    let streamed: Vec<Todo> = lenient
+        .stream(&everything())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(streamed.len(), 1);
+
+    let strict = EncryptedTodoRepository::new(inner, cipher(&[OLD], None), RowDecoding::Strict);
+    assert!(matches!(
+        strict.list(&everything()).await,
+        Err(RepositoryError::Decode(_))
+    ));
+}
@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use simple_api_actix_web::activity::ActivityRecorder;
+use simple_api_actix_web::events::{DomainEvent, EventBus, EventHandler, HandlerError};
+use simple_api_actix_web::model::{ActivityKind, Todo, TodoId};
+use simple_api_actix_web::repository::{ActivityRepository, InMemoryActivityRepository};
+
+fn todo(id: TodoId, title: &str) -> Todo {
+    let now = Utc::now();
+    Todo {
+        id: Some(id),
+        title: title.to_string(),
+        content: String::new(),
+        completed: Some(false),
+        archived: Some(false),
+        due_at: None,
+        recurrence: None,
+        series_id: None,
+        next_occurrence_id: None,
+        remind_at: None,
+        reminder_sent_at: None,
+        owner_id: None,
+        workspace_id: None,
+        assignee_id: None,
+        project_id: None,
+        position: None,
+        status: None,
+        created_at: Some(now),
+        updated_at: Some(now),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    }
+}
+
+/// Keeps the names of the events it is handed.
+#[derive(Default)]
+struct Recorder {
+    seen: Mutex<Vec<&'static str>>,
+}
+
+#[async_trait]
+impl EventHandler for Recorder {
+    fn name(&self) -> &'static str {
+        "recorder"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), HandlerError> {
+        self.seen.lock().unwrap().push(event.name());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn every_subscriber_gets_every_event_in_order() {
+    let bus = EventBus::new();
+    let (first, second) = (Arc::new(Recorder::default()), Arc::new(Recorder::default()));
+    bus.spawn(first.clone());
+    bus.spawn(second.clone());
+
+    let id = TodoId::generate();
+    bus.publish(DomainEvent::TodoCreated {
+        todo: todo(id, "Water plants"),
+        actor_id: None,
+    });
+    bus.publish(DomainEvent::TodoDeleted { id, actor_id: None });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for recorder in [first, second] {
+        assert_eq!(
+            *recorder.seen.lock().unwrap(),
+            ["todo_created", "todo_deleted"]
+        );
+    }
+}
+
+#[tokio::test]
+async fn activity_is_recorded_for_lifecycle_events() {
+    let repository = Arc::new(InMemoryActivityRepository::new());
+    let recorder = ActivityRecorder {
+        repository: repository.clone(),
+    };
+    let id = TodoId::generate();
+    let actor_id = Some("user-1".to_string());
+
+    for event in [
+        DomainEvent::TodoCreated {
+            todo: todo(id, "Water plants"),
+            actor_id: actor_id.clone(),
+        },
+        DomainEvent::TodoUpdated {
+            id,
+            todo: None,
+            title: None,
+            actor_id: actor_id.clone(),
+        },
+        DomainEvent::TodoUpdated {
+            id,
+            todo: None,
+            title: Some("Water the plants".to_string()),
+            actor_id: actor_id.clone(),
+        },
+        DomainEvent::TodoCompleted {
+            id,
+            actor_id: actor_id.clone(),
+        },
+    ] {
+        recorder.handle(&event).await.unwrap();
+    }
+
+    let activity = repository.list_for_todo(&id, &[], 0, 10).await.unwrap();
+    let kinds: Vec<ActivityKind> = activity.iter().map(|activity| activity.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ActivityKind::Created,
+            ActivityKind::TitleEdited,
+            ActivityKind::Completed
+        ]
+    );
+    assert_eq!(activity[1].detail.as_deref(), Some("Water the plants"));
+    assert!(activity
+        .iter()
+        .all(|activity| activity.actor_id.as_deref() == Some("user-1")));
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App};
+use simple_api_actix_web::config::FeaturesConfig;
+use simple_api_actix_web::features::{self, FeatureFlags};
+use simple_api_actix_web::handler;
+use simple_api_actix_web::repository::InMemoryFeatureFlagRepository;
+
+fn config() -> FeaturesConfig {
+    FeaturesConfig {
+        defaults: HashMap::from([
+            ("graphql".to_string(), false),
+            ("cursor_pagination".to_string(), true),
+        ]),
+        refresh_interval: Duration::from_secs(30),
+    }
+}
+
+#[tokio::test]
+async fn stored_flags_override_the_defaults_until_cleared() {
+    let flags = FeatureFlags::new(&config(), Arc::new(InMemoryFeatureFlagRepository::new()));
+    assert!(flags.is_enabled("cursor_pagination"));
+    assert!(!flags.is_enabled("graphql"));
+    assert!(!flags.is_enabled("unknown"));
+    assert!(flags.require("graphql").is_err());
+
+    let flag = flags.set("graphql", true).await.unwrap();
+    assert!(flag.enabled);
+    assert_eq!((flag.default, flag.stored), (Some(false), Some(true)));
+    assert!(flags.require("graphql").is_ok());
+
+    let flag = flags.clear("graphql").await.unwrap();
+    assert!(!flag.enabled);
+    assert_eq!(flag.stored, None);
+}
+
+#[tokio::test]
+async fn other_instances_follow_on_their_next_refresh() {
+    let repository = Arc::new(InMemoryFeatureFlagRepository::new());
+    let first = FeatureFlags::new(&config(), repository.clone());
+    let second = FeatureFlags::new(&config(), repository);
+
+    first.set("cursor_pagination", false).await.unwrap();
+    assert!(second.is_enabled("cursor_pagination"));
+    second.refresh().await;
+    assert!(!second.is_enabled("cursor_pagination"));
+
+    first.clear("cursor_pagination").await.unwrap();
+    second.refresh().await;
+    assert!(second.is_enabled("cursor_pagination"));
+    let names: Vec<_> = second.list().into_iter().map(|flag| flag.name).collect();
+    assert_eq!(names, ["cursor_pagination", "graphql"]);
+}
+
+#[actix_web::test]
+async fn offline_sync_is_not_found_while_turned_off() {
+    let state = web::Data::new(
+        common::memory_state(|config| {
+            config
+                .features
+                .defaults
+                .insert(features::OFFLINE_SYNC.to_string(), false);
+        })
+        .await,
+    );
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let bearer = format!("Bearer {}", common::register(&app, "ada@example.com").await);
+    let pull = || {
+        test::TestRequest::get()
+            .uri("/api/sync")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request()
+    };
+
+    let res = test::call_service(&app, pull()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    state
+        .features
+        .set(features::OFFLINE_SYNC, true)
+        .await
+        .unwrap();
+    let res = test::call_service(&app, pull()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
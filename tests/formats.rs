@@ -0,0 +1,182 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{middleware, test, web, App, HttpResponse};
+use serde_json::{json, Value};
+use simple_api_actix_web::formats::{self, Format};
+
+fn todos() -> Value {
+    json!({
+        "status": "success",
+        "results": 2,
+        "todos": [
+            { "id": 1, "title": "Fish & <chips>", "completed": false, "dueAt": null },
+            { "id": -2, "title": "", "completed": true, "position": 1.5, "tags": [] }
+        ],
+        "counts": { "2024-01-01": 3 },
+        "meta": {}
+    })
+}
+
+async fn call(req: test::TestRequest) -> (StatusCode, header::HeaderMap, Vec<u8>) {
+    let app = test::init_service(app()).await;
+    let res = test::call_service(&app, req.to_request()).await;
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = test::read_body(res).await.to_vec();
+    (status, headers, body)
+}
+
+fn app() -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .route(
+            "/todos",
+            web::get().to(|| async { HttpResponse::Ok().json(todos()) }),
+        )
+        .route(
+            "/todos",
+            web::post().to(|body: web::Json<Value>| async move {
+                HttpResponse::Created().json(body.into_inner())
+            }),
+        )
+        .route(
+            "/export",
+            web::get().to(|| async { HttpResponse::Ok().content_type("text/csv").body("a,b") }),
+        )
+        .wrap(middleware::from_fn(formats::negotiate_format))
+}
+
+fn content_type(headers: &header::HeaderMap) -> &str {
+    headers.get(header::CONTENT_TYPE).unwrap().to_str().unwrap()
+}
+
+#[actix_web::test]
+async fn json_is_the_default() {
+    for accept in [None, Some("*/*"), Some("application/json, application/xml")] {
+        let mut req = test::TestRequest::get().uri("/todos");
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        let (status, headers, body) = call(req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type(&headers), "application/json");
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), todos());
+    }
+}
+
+#[actix_web::test]
+async fn xml_responses_round_trip() {
+    let req = test::TestRequest::get()
+        .uri("/todos")
+        .insert_header((header::ACCEPT, "application/xml"));
+    let (_, headers, body) = call(req).await;
+    assert_eq!(content_type(&headers), "application/xml; charset=utf-8");
+    assert_eq!(headers.get(header::VARY).unwrap(), "accept");
+
+    let xml = String::from_utf8(body.clone()).unwrap();
+    assert!(xml.contains("<title>Fish &amp; &lt;chips&gt;</title>"));
+    assert!(xml.contains(r#"<completed type="boolean">false</completed>"#));
+    assert!(xml.contains(r#"<dueAt nil="true"/>"#));
+    assert!(xml.contains(r#"<entry key="2024-01-01" type="number">3</entry>"#));
+    assert_eq!(Format::Xml.decode(&body).unwrap(), todos());
+}
+
+#[actix_web::test]
+async fn msgpack_responses_round_trip() {
+    let req = test::TestRequest::get().uri("/todos").insert_header((
+        header::ACCEPT,
+        "application/json;q=0.5, application/msgpack",
+    ));
+    let (_, headers, body) = call(req).await;
+    assert_eq!(content_type(&headers), "application/msgpack");
+    assert!(body.len() < serde_json::to_vec(&todos()).unwrap().len());
+    assert_eq!(Format::MessagePack.decode(&body).unwrap(), todos());
+}
+
+#[actix_web::test]
+async fn msgpack_covers_wide_values() {
+    let value = json!({
+        "big": u64::MAX,
+        "small": i64::MIN,
+        "text": "x".repeat(70_000),
+        "items": (0..300).collect::<Vec<_>>(),
+    });
+    let bytes = Format::MessagePack.encode(&value);
+    assert_eq!(Format::MessagePack.decode(&bytes).unwrap(), value);
+}
+
+#[actix_web::test]
+async fn request_bodies_are_decoded() {
+    let todo = json!({ "title": "Buy milk", "completed": true, "position": 3 });
+    for format in [Format::Xml, Format::MessagePack] {
+        let req = test::TestRequest::post()
+            .uri("/todos")
+            .insert_header((header::CONTENT_TYPE, format.content_type()))
+            .insert_header((header::ACCEPT, format.content_type()))
+            .set_payload(format.encode(&todo));
+        let (status, headers, body) = call(req).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(content_type(&headers), format.content_type());
+        assert_eq!(format.decode(&body).unwrap(), todo);
+    }
+}
+
+#[actix_web::test]
+async fn hand_written_xml_is_accepted() {
+    let req = test::TestRequest::post()
+        .uri("/todos")
+        .insert_header((header::CONTENT_TYPE, "text/xml"))
+        .set_payload(
+            "<?xml version=\"1.0\"?>\n<todo>\n  <title><![CDATA[Fish & chips]]></title>\n  \
+             <completed type=\"boolean\">true</completed>\n</todo>",
+        );
+    let (status, _, body) = call(req).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({ "title": "Fish & chips", "completed": true })
+    );
+}
+
+#[actix_web::test]
+async fn malformed_bodies_are_rejected() {
+    for (content_type, payload) in [
+        ("application/msgpack", vec![0x92, 0x01]),
+        ("application/msgpack", vec![0xc4, 0x01, 0x00]),
+        ("application/xml", b"<todo><title>open</todo>".to_vec()),
+        ("application/xml", b"<!DOCTYPE todo []><todo/>".to_vec()),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/todos")
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .set_payload(payload);
+        let app = test::init_service(app()).await;
+        let Err(err) = test::try_call_service(&app, req.to_request()).await else {
+            panic!("{} body was accepted", content_type);
+        };
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST,
+            "{}",
+            content_type
+        );
+    }
+}
+
+#[actix_web::test]
+async fn other_responses_pass_through() {
+    let req = test::TestRequest::get()
+        .uri("/export")
+        .insert_header((header::ACCEPT, "application/msgpack"));
+    let (_, headers, body) = call(req).await;
+    assert_eq!(content_type(&headers), "text/csv");
+    assert_eq!(body, b"a,b");
+}
@@ -0,0 +1,96 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{middleware, test, web, App};
+use serde_json::Value;
+use simple_api_actix_web::error::AppError;
+use simple_api_actix_web::i18n::{self, DEFAULT_LANGUAGE};
+
+/// Fails every request with a not-found error, answering in the language
+/// `accept_language` prefers.
+async fn not_found(accept_language: Option<&str>) -> (StatusCode, String, String) {
+    let app = test::init_service(
+        App::new()
+            .route(
+                "/todos/{id}",
+                web::get().to(|path: web::Path<String>| async move {
+                    Err::<String, _>(AppError::NotFound(format!(
+                        "Todo with ID: {} not found",
+                        path.into_inner()
+                    )))
+                }),
+            )
+            .wrap(middleware::from_fn(i18n::negotiate_language)),
+    )
+    .await;
+
+    let mut req = test::TestRequest::get().uri("/todos/42");
+    if let Some(accept_language) = accept_language {
+        req = req.insert_header((header::ACCEPT_LANGUAGE, accept_language));
+    }
+    let res = test::call_service(&app, req.to_request()).await;
+    let status = res.status();
+    let language = res
+        .headers()
+        .get(header::CONTENT_LANGUAGE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: Value = test::read_body_json(res).await;
+    (
+        status,
+        language,
+        body["message"].as_str().unwrap().to_string(),
+    )
+}
+
+#[actix_web::test]
+async fn errors_are_in_english_by_default() {
+    let (status, language, message) = not_found(None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(language, "en");
+    assert_eq!(message, "Todo with ID: 42 not found");
+}
+
+#[actix_web::test]
+async fn errors_follow_accept_language() {
+    let (status, language, message) = not_found(Some("fr-CA, en;q=0.8")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(language, "fr");
+    assert_eq!(message, "Tâche avec l'ID 42 introuvable");
+}
+
+#[actix_web::test]
+async fn unsupported_languages_fall_back_to_english() {
+    let (_, language, message) = not_found(Some("ja, ko;q=0.9")).await;
+    assert_eq!(language, "en");
+    assert_eq!(message, "Todo with ID: 42 not found");
+}
+
+#[actix_web::test]
+async fn the_highest_weighted_supported_language_wins() {
+    assert_eq!(i18n::preferred_language("de;q=0.5, es;q=0.9"), "es");
+    assert_eq!(i18n::preferred_language("ja, de-CH, fr"), "de");
+    assert_eq!(i18n::preferred_language("en-GB, de"), DEFAULT_LANGUAGE);
+    assert_eq!(i18n::preferred_language("de;q=0, *"), DEFAULT_LANGUAGE);
+}
+
+#[actix_web::test]
+async fn specific_messages_win_over_general_ones() {
+    assert_eq!(
+        i18n::translate("title must not be empty", "de").as_deref(),
+        Some("Der Titel darf nicht leer sein")
+    );
+    assert_eq!(
+        i18n::translate("X-Workspace-Id must not be empty", "de").as_deref(),
+        Some("X-Workspace-Id darf nicht leer sein")
+    );
+    assert_eq!(
+        i18n::translate(
+            "A todo cannot move from done to blocked; allowed: backlog",
+            "es"
+        )
+        .as_deref(),
+        Some("Una tarea no puede pasar de done a blocked; permitido: backlog")
+    );
+    assert_eq!(i18n::translate("Something else entirely", "fr"), None);
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use simple_api_actix_web::leader::{LeaderElector, WORKERS};
+use simple_api_actix_web::repository::InMemoryLeaseRepository;
+
+#[tokio::test]
+async fn only_one_instance_leads_until_it_resigns() {
+    let leases = Arc::new(InMemoryLeaseRepository::new());
+    let first = LeaderElector::new(leases.clone(), WORKERS, Duration::from_secs(30));
+    let second = LeaderElector::new(leases, WORKERS, Duration::from_secs(30));
+
+    assert!(first.try_lead().await);
+    assert!(!second.try_lead().await);
+    // Renewing keeps the lead.
+    assert!(first.try_lead().await);
+    assert!(first.is_leader() && !second.is_leader());
+
+    first.resign().await;
+    assert!(!first.is_leader());
+    assert!(second.try_lead().await);
+    assert!(!first.try_lead().await);
+}
+
+#[tokio::test]
+async fn another_instance_takes_over_once_the_lease_runs_out() {
+    let leases = Arc::new(InMemoryLeaseRepository::new());
+    let first = LeaderElector::new(leases.clone(), WORKERS, Duration::from_millis(100));
+    let second = LeaderElector::new(leases, WORKERS, Duration::from_millis(100));
+
+    assert!(first.try_lead().await);
+    assert!(!second.try_lead().await);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(second.try_lead().await);
+    assert!(!first.try_lead().await);
+}
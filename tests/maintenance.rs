@@ -0,0 +1,175 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::{middleware, test, web, App, ResponseError};
+use serde_json::{json, Value};
+use simple_api_actix_web::config::MaintenanceConfig;
+use simple_api_actix_web::handler;
+use simple_api_actix_web::maintenance::{self, MaintenanceMode};
+use simple_api_actix_web::model::StartMaintenanceSchema;
+use simple_api_actix_web::repository::InMemoryMaintenanceRepository;
+
+fn config() -> MaintenanceConfig {
+    MaintenanceConfig {
+        message: "Down for maintenance".to_string(),
+        retry_after: Duration::from_secs(120),
+        refresh_interval: Duration::from_secs(5),
+        read_cache_size: 16,
+    }
+}
+
+/// The status of `req`'s response, including one the middleware refused.
+async fn status<S, B>(app: &S, req: actix_http::Request) -> StatusCode
+where
+    S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    match test::try_call_service(app, req).await {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    }
+}
+
+fn request(allow_reads: Option<bool>) -> StartMaintenanceSchema {
+    StartMaintenanceSchema {
+        message: None,
+        retry_after_secs: None,
+        allow_reads,
+    }
+}
+
+#[tokio::test]
+async fn writes_are_refused_with_a_retry_after() {
+    let mode = MaintenanceMode::new(&config(), Arc::new(InMemoryMaintenanceRepository::new()));
+    assert!(mode.check(&Method::POST, "/api/todos").is_ok());
+
+    mode.enable("admin-1", request(None)).await.unwrap();
+    assert!(mode.check(&Method::OPTIONS, "/api/todos").is_ok());
+    assert!(mode.check(&Method::PUT, "/api/admin/maintenance").is_ok());
+    assert!(mode.check(&Method::POST, "/api/auth/login").is_ok());
+
+    let error = mode.check(&Method::POST, "/api/todos").unwrap_err();
+    let response = error.error_response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "120");
+    assert_eq!(error.to_string(), "Down for maintenance");
+
+    mode.disable().await.unwrap();
+    assert!(mode.check(&Method::POST, "/api/todos").is_ok());
+}
+
+#[actix_web::test]
+async fn reads_are_served_from_the_cache_while_allowed() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config)
+            .wrap(middleware::from_fn(maintenance::refuse_during_maintenance)),
+    )
+    .await;
+    let bearer = format!("Bearer {}", common::register(&app, "ada@example.com").await);
+    let list = || {
+        test::TestRequest::get()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request()
+    };
+    let create = |title: &str| {
+        test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .set_json(json!({ "title": title, "content": "" }))
+            .to_request()
+    };
+
+    assert_eq!(status(&app, create("Read me")).await, StatusCode::CREATED);
+    let before: Value = test::call_and_read_body_json(&app, list()).await;
+
+    state
+        .maintenance
+        .enable("admin-1", request(None))
+        .await
+        .unwrap();
+    let during: Value = test::call_and_read_body_json(&app, list()).await;
+    assert_eq!(during, before);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?limit=1")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    assert_eq!(status(&app, req).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        status(&app, create("Refused")).await,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+
+    state
+        .maintenance
+        .enable("admin-1", request(Some(false)))
+        .await
+        .unwrap();
+    assert_eq!(status(&app, list()).await, StatusCode::SERVICE_UNAVAILABLE);
+
+    // A change drops its caller's cached reads, which would be out of date.
+    state.maintenance.disable().await.unwrap();
+    assert_eq!(status(&app, create("Changed")).await, StatusCode::CREATED);
+    state
+        .maintenance
+        .enable("admin-1", request(None))
+        .await
+        .unwrap();
+    assert_eq!(status(&app, list()).await, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_web::test]
+async fn reads_are_refused_without_the_cache() {
+    let state =
+        web::Data::new(common::memory_state(|config| config.maintenance.read_cache_size = 0).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config)
+            .wrap(middleware::from_fn(maintenance::refuse_during_maintenance)),
+    )
+    .await;
+    let bearer = format!("Bearer {}", common::register(&app, "ada@example.com").await);
+    let list = || {
+        test::TestRequest::get()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request()
+    };
+    assert_eq!(status(&app, list()).await, StatusCode::OK);
+
+    state
+        .maintenance
+        .enable("admin-1", request(None))
+        .await
+        .unwrap();
+    assert_eq!(status(&app, list()).await, StatusCode::SERVICE_UNAVAILABLE);
+    let req = test::TestRequest::get()
+        .uri("/api/healthchecker")
+        .to_request();
+    assert_eq!(status(&app, req).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn other_instances_follow_on_their_next_refresh() {
+    let repository = Arc::new(InMemoryMaintenanceRepository::new());
+    let first = MaintenanceMode::new(&config(), repository.clone());
+    let second = MaintenanceMode::new(&config(), repository);
+
+    first.enable("admin-1", request(None)).await.unwrap();
+    assert!(second.check(&Method::DELETE, "/api/todos/{id}").is_ok());
+    second.refresh().await;
+    assert!(second.check(&Method::DELETE, "/api/todos/{id}").is_err());
+    assert_eq!(second.current().unwrap().started_by, "admin-1");
+
+    first.disable().await.unwrap();
+    second.refresh().await;
+    assert!(second.current().is_none());
+}
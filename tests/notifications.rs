@@ -0,0 +1,51 @@
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::handler;
+
+#[actix_web::test]
+async fn callers_manage_the_opt_ins_of_their_own_address() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/api/notifications/settings");
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let req = test::TestRequest::post().uri("/api/notifications/test");
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": "someone@example.com", "password": "correct horse battery" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let bearer = format!("Bearer {}", body["data"]["token"].as_str().unwrap());
+
+    let req = test::TestRequest::put()
+        .uri("/api/notifications/settings")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "reminders": true }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["settings"]["email"], "someone@example.com");
+    assert_eq!(body["settings"]["reminders"], true);
+
+    let req = test::TestRequest::get()
+        .uri("/api/notifications/settings")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["settings"]["reminders"], true);
+    assert_eq!(body["settings"]["digest"], false);
+
+    let req = test::TestRequest::get()
+        .uri("/api/notifications/settings/other@example.com")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,233 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value;
+use simple_api_actix_web::events::{DomainEvent, EventHandler, HandlerError};
+use simple_api_actix_web::jobs::Job;
+use simple_api_actix_web::model::{OutboxEntry, Todo, TodoId};
+use simple_api_actix_web::outbox::{
+    self, Consumer, EventSink, OutboxPurgeJob, OutboxRelayJob, SinkError, SINK_CONSUMER,
+};
+use simple_api_actix_web::repository::{
+    InMemoryOutboxRepository, InMemoryTodoRepository, OutboxRepository, TodoRepository, TodoWrite,
+};
+
+fn todo(id: TodoId) -> Todo {
+    let now = Utc::now();
+    Todo {
+        id: Some(id),
+        title: "Water plants".to_string(),
+        content: String::new(),
+        completed: Some(false),
+        archived: Some(false),
+        due_at: None,
+        recurrence: None,
+        series_id: None,
+        next_occurrence_id: None,
+        remind_at: None,
+        reminder_sent_at: None,
+        owner_id: None,
+        workspace_id: None,
+        assignee_id: None,
+        project_id: None,
+        position: None,
+        status: None,
+        created_at: Some(now),
+        updated_at: Some(now),
+        expires_at: None,
+        comment_count: None,
+        ttl_seconds: None,
+        content_html: None,
+    }
+}
+
+/// Keeps the payloads it is handed, failing once `fail_after` were taken.
+#[derive(Default)]
+struct Sink {
+    published: Mutex<Vec<Value>>,
+    fail_after: Option<usize>,
+}
+
+#[async_trait]
+impl EventSink for Sink {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    async fn publish(&self, entry: &OutboxEntry) -> Result<(), SinkError> {
+        let mut published = self.published.lock().unwrap();
+        if Some(published.len()) == self.fail_after {
+            return Err(SinkError("broker unavailable".to_string()));
+        }
+        published.push(serde_json::from_str(&entry.payload).unwrap());
+        Ok(())
+    }
+}
+
+/// Keeps the names of the events it is handed.
+#[derive(Default)]
+struct Recorder {
+    seen: Mutex<Vec<&'static str>>,
+}
+
+#[async_trait]
+impl EventHandler for Recorder {
+    fn name(&self) -> &'static str {
+        "recorder"
+    }
+
+    async fn handle(&self, event: &DomainEvent) -> Result<(), HandlerError> {
+        self.seen.lock().unwrap().push(event.name());
+        Ok(())
+    }
+}
+
+const CONSUMERS: [&str; 2] = [SINK_CONSUMER, "recorder"];
+
+/// Completes and then deletes `id`, committing the events for
+/// [`CONSUMERS`] to `outbox` along with the changes.
+async fn commit_events(outbox: &Arc<InMemoryOutboxRepository>, id: TodoId) {
+    let todos = InMemoryTodoRepository::with_outbox(outbox.clone());
+    let completed = DomainEvent::TodoCompleted {
+        id,
+        actor_id: Some("user-1".to_string()),
+    };
+    let entries = outbox::entries(&[completed], &CONSUMERS, Utc::now());
+    let mut todo = todo(id);
+    todo.completed = Some(true);
+    todos
+        .commit(&TodoWrite::Update(todo), &entries)
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    let deleted = DomainEvent::TodoDeleted { id, actor_id: None };
+    let entries = outbox::entries(&[deleted], &CONSUMERS, Utc::now());
+    todos
+        .commit(&TodoWrite::Delete(id), &entries)
+        .await
+        .unwrap();
+}
+
+fn relay(
+    outbox: &Arc<InMemoryOutboxRepository>,
+    sink: &Arc<Sink>,
+    recorder: &Arc<Recorder>,
+) -> OutboxRelayJob {
+    OutboxRelayJob::new(
+        outbox.clone(),
+        vec![
+            Consumer::Sink(sink.clone()),
+            Consumer::Handler(recorder.clone()),
+        ],
+        100,
+    )
+}
+
+#[tokio::test]
+async fn a_change_is_committed_with_an_entry_per_event_and_consumer() {
+    let outbox = Arc::new(InMemoryOutboxRepository::new());
+    let todos = InMemoryTodoRepository::with_outbox(outbox.clone());
+    let id = TodoId::generate();
+    let events = [
+        DomainEvent::TodoCreated {
+            todo: todo(id),
+            actor_id: None,
+        },
+        DomainEvent::TodoCompleted { id, actor_id: None },
+    ];
+    let entries = outbox::entries(&events, &CONSUMERS, Utc::now());
+
+    todos
+        .commit(&TodoWrite::Insert(todo(id)), &entries)
+        .await
+        .unwrap();
+
+    assert!(todos.find_by_id(&id).await.unwrap().is_some());
+    for consumer in CONSUMERS {
+        let pending = outbox.pending(consumer, 10).await.unwrap();
+        let events: Vec<&str> = pending.iter().map(|entry| entry.event.as_str()).collect();
+        assert_eq!(events, ["todo_created", "todo_completed"]);
+    }
+}
+
+#[tokio::test]
+async fn each_consumer_gets_its_events_in_order_once() {
+    let outbox = Arc::new(InMemoryOutboxRepository::new());
+    let (sink, recorder) = (Arc::new(Sink::default()), Arc::new(Recorder::default()));
+    let id = TodoId::generate();
+    commit_events(&outbox, id).await;
+
+    let relay = relay(&outbox, &sink, &recorder);
+    relay.run(&Value::Null).await.unwrap();
+    relay.run(&Value::Null).await.unwrap();
+
+    for consumer in CONSUMERS {
+        assert!(outbox.pending(consumer, 10).await.unwrap().is_empty());
+    }
+    assert_eq!(
+        *recorder.seen.lock().unwrap(),
+        ["todo_completed", "todo_deleted"]
+    );
+    let published = sink.published.lock().unwrap();
+    let events: Vec<&str> = published
+        .iter()
+        .map(|message| message["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(events, ["todo_completed", "todo_deleted"]);
+    assert_eq!(published[0]["todoId"], id.to_string());
+    assert_eq!(published[0]["actorId"], "user-1");
+}
+
+#[tokio::test]
+async fn a_failing_consumer_holds_up_only_itself() {
+    let outbox = Arc::new(InMemoryOutboxRepository::new());
+    let recorder = Arc::new(Recorder::default());
+    commit_events(&outbox, TodoId::generate()).await;
+
+    let failing = Arc::new(Sink {
+        fail_after: Some(1),
+        ..Sink::default()
+    });
+    assert!(relay(&outbox, &failing, &recorder)
+        .run(&Value::Null)
+        .await
+        .is_err());
+    assert_eq!(recorder.seen.lock().unwrap().len(), 2);
+    let pending = outbox.pending(SINK_CONSUMER, 10).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].event, "todo_deleted");
+
+    let sink = Arc::new(Sink::default());
+    relay(&outbox, &sink, &recorder)
+        .run(&Value::Null)
+        .await
+        .unwrap();
+    assert_eq!(sink.published.lock().unwrap()[0]["event"], "todo_deleted");
+    assert_eq!(recorder.seen.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn only_delivered_events_are_purged() {
+    let outbox = Arc::new(InMemoryOutboxRepository::new());
+    commit_events(&outbox, TodoId::generate()).await;
+    let pending = outbox.pending(SINK_CONSUMER, 10).await.unwrap();
+    outbox
+        .mark_delivered(&pending[0].id, Utc::now() - chrono::Duration::hours(2))
+        .await
+        .unwrap();
+
+    OutboxPurgeJob {
+        repository: outbox.clone(),
+        retention: Duration::from_secs(60 * 60),
+    }
+    .run(&Value::Null)
+    .await
+    .unwrap();
+
+    assert_eq!(outbox.purge_delivered(Utc::now()).await.unwrap(), 0);
+    assert_eq!(outbox.pending(SINK_CONSUMER, 10).await.unwrap().len(), 1);
+    assert_eq!(outbox.pending("recorder", 10).await.unwrap().len(), 2);
+}
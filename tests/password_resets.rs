@@ -0,0 +1,91 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::handler;
+use simple_api_actix_web::model::AppState;
+
+const EMAIL: &str = "someone@example.com";
+
+async fn state() -> AppState {
+    common::memory_state(|config| {
+        config.auth.password_reset.url = Some("https://todos.example.com/reset".to_string());
+    })
+    .await
+}
+
+#[actix_web::test]
+async fn a_reset_ends_every_earlier_login() {
+    let state = web::Data::new(state().await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+
+    let credentials = json!({ "email": EMAIL, "password": "correct horse battery" });
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(&credentials);
+    test::call_service(&app, req.to_request()).await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(&credentials);
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let old_refresh_token = body["data"]["refreshToken"].as_str().unwrap().to_string();
+
+    let user = state.users.find_by_email(EMAIL).await.unwrap().unwrap();
+    let email = state.password_resets.issue(&user).await.unwrap();
+    let (_, link) = email.message.split_once("token=").unwrap();
+    let token: String = link.chars().take_while(char::is_ascii_hexdigit).collect();
+    let req = test::TestRequest::post()
+        .uri("/api/auth/reset-password")
+        .set_json(json!({ "token": token, "password": "battery staple horse" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": old_refresh_token }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(state
+        .refresh_tokens
+        .sessions(&user.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[actix_web::test]
+async fn redeeming_a_token_spends_the_older_ones() {
+    let state = web::Data::new(state().await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": EMAIL, "password": "correct horse battery" }));
+    test::call_service(&app, req.to_request()).await;
+    let user = state.users.find_by_email(EMAIL).await.unwrap().unwrap();
+
+    let mut tokens = Vec::new();
+    for _ in 0..2 {
+        let email = state.password_resets.issue(&user).await.unwrap();
+        let (_, link) = email.message.split_once("token=").unwrap();
+        tokens.push(
+            link.chars()
+                .take_while(char::is_ascii_hexdigit)
+                .collect::<String>(),
+        );
+    }
+
+    let redeemed = state.password_resets.redeem(&tokens[1]).await.unwrap();
+    assert_eq!(redeemed, user.id);
+    assert!(state.password_resets.redeem(&tokens[0]).await.is_err());
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use simple_api_actix_web::config::{JobConfig, RetentionConfig};
+use simple_api_actix_web::jobs::JobQueue;
+use simple_api_actix_web::model::{Activity, ActivityKind, TodoChange, TodoId};
+use simple_api_actix_web::repository::{
+    ActivityRepository, ChangeRepository, InMemoryActivityRepository, InMemoryChangeRepository,
+    InMemoryJobRepository,
+};
+use simple_api_actix_web::retention::Retention;
+use simple_api_actix_web::scheduler::Scheduler;
+use uuid::Uuid;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn days_ago(days: i64) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(days)
+}
+
+fn tombstone(workspace_id: Option<&str>, deleted_at: DateTime<Utc>) -> TodoChange {
+    TodoChange {
+        todo_id: TodoId(Uuid::new_v4()),
+        token: 1,
+        workspace_id: workspace_id.map(str::to_string),
+        owner_id: None,
+        deleted: true,
+        version: Default::default(),
+        changed_at: deleted_at,
+    }
+}
+
+fn activity(todo_id: TodoId, occurred_at: DateTime<Utc>) -> Activity {
+    Activity {
+        id: Uuid::new_v4().to_string(),
+        todo_id,
+        actor_id: Some("user-1".to_string()),
+        kind: ActivityKind::Created,
+        detail: None,
+        occurred_at,
+    }
+}
+
+struct Fixture {
+    retention: Retention,
+    changes: Arc<InMemoryChangeRepository>,
+    activity: Arc<InMemoryActivityRepository>,
+}
+
+/// Deleted todos are kept 30 days and activity 180, except in `audited`,
+/// which keeps activity for good and deleted todos a year.
+fn fixture() -> Fixture {
+    let jobs = JobQueue::new(
+        Arc::new(InMemoryJobRepository::new()),
+        &JobConfig {
+            concurrency: 1,
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(1),
+            retention: DAY,
+            leader_lease: Duration::from_secs(30),
+        },
+    );
+    let config = RetentionConfig {
+        schedule: "0 0 3 * * *".parse().unwrap(),
+        deleted_todos: Some(30 * DAY),
+        activity: Some(180 * DAY),
+        workspace_deleted_todos: HashMap::from([("audited".to_string(), Some(365 * DAY))]),
+        workspace_activity: HashMap::from([("audited".to_string(), None)]),
+    };
+    let changes = Arc::new(InMemoryChangeRepository::new());
+    let activity = Arc::new(InMemoryActivityRepository::new());
+    Fixture {
+        retention: Retention::new(
+            &jobs,
+            &Scheduler::new(jobs.clone()),
+            &config,
+            changes.clone(),
+            activity.clone(),
+        ),
+        changes,
+        activity,
+    }
+}
+
+#[tokio::test]
+async fn deleted_todos_are_purged_after_their_workspaces_retention() {
+    let fixture = fixture();
+    let recent = tombstone(None, days_ago(10));
+    let old = tombstone(None, days_ago(40));
+    let audited = tombstone(Some("audited"), days_ago(40));
+    let ancient = tombstone(Some("audited"), days_ago(400));
+    for change in [&recent, &old, &audited, &ancient] {
+        fixture.changes.record(change).await.unwrap();
+    }
+
+    let preview = fixture.retention.preview().await.unwrap();
+    assert_eq!(preview.deleted_todos, 2);
+    // A dry run leaves everything in place.
+    assert!(fixture.changes.find(&old.todo_id).await.unwrap().is_some());
+
+    let report = fixture.retention.purge().await.unwrap();
+    assert_eq!(report.deleted_todos, 2);
+    let workspaces: Vec<_> = report
+        .workspaces
+        .iter()
+        .map(|purge| (purge.workspace_id.as_deref(), purge.deleted_todos))
+        .collect();
+    assert_eq!(workspaces, [(None, 1), (Some("audited"), 1)]);
+
+    let kept = fixture.changes.list_deleted(Utc::now()).await.unwrap();
+    let mut kept: Vec<TodoId> = kept.into_iter().map(|change| change.todo_id).collect();
+    kept.sort_by_key(|id| id.0);
+    let mut expected = vec![recent.todo_id, audited.todo_id];
+    expected.sort_by_key(|id| id.0);
+    assert_eq!(kept, expected);
+}
+
+#[tokio::test]
+async fn activity_goes_by_the_workspace_of_its_todo() {
+    let fixture = fixture();
+    let personal = tombstone(None, days_ago(1));
+    let audited = tombstone(Some("audited"), days_ago(1));
+    fixture.changes.record(&personal).await.unwrap();
+    fixture.changes.record(&audited).await.unwrap();
+
+    let old = activity(personal.todo_id, days_ago(200));
+    let recent = activity(personal.todo_id, days_ago(20));
+    let kept_for_good = activity(audited.todo_id, days_ago(1000));
+    for entry in [&old, &recent, &kept_for_good] {
+        fixture.activity.insert(entry).await.unwrap();
+    }
+
+    let report = fixture.retention.purge().await.unwrap();
+    assert_eq!(report.activity, 1);
+    assert_eq!(report.deleted_todos, 0);
+
+    let left: Vec<String> = fixture
+        .activity
+        .list_before(Utc::now())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.id)
+        .collect();
+    assert_eq!(left, [kept_for_good.id, recent.id]);
+}
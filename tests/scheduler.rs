@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use simple_api_actix_web::config::{parse_cron, JobConfig};
+use simple_api_actix_web::jobs::{Job, JobError, JobQueue};
+use simple_api_actix_web::model::JobStatus;
+use simple_api_actix_web::repository::InMemoryJobRepository;
+use simple_api_actix_web::scheduler::Scheduler;
+
+/// A job that takes longer than the second between its runs.
+struct SlowJob {
+    runs: AtomicUsize,
+}
+
+#[async_trait]
+impl Job for SlowJob {
+    fn kind(&self) -> &'static str {
+        "test.slow"
+    }
+
+    async fn run(&self, _payload: &serde_json::Value) -> Result<(), JobError> {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        Ok(())
+    }
+}
+
+#[test]
+fn schedules_may_leave_out_seconds() {
+    assert_eq!(
+        parse_cron("30 3 * * *").unwrap(),
+        "0 30 3 * * *".parse().unwrap()
+    );
+    assert!(parse_cron("0 30 3 * * *").is_some());
+    assert!(parse_cron("every day").is_none());
+}
+
+#[tokio::test]
+async fn runs_are_skipped_while_the_last_one_is_going() {
+    let queue = JobQueue::new(
+        Arc::new(InMemoryJobRepository::new()),
+        &JobConfig {
+            concurrency: 4,
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(1),
+            retention: Duration::from_secs(60),
+            leader_lease: Duration::from_secs(30),
+        },
+    );
+    let job = Arc::new(SlowJob {
+        runs: AtomicUsize::new(0),
+    });
+    queue.register(job.clone());
+    queue.start().await.unwrap();
+
+    let scheduler = Scheduler::new(queue);
+    scheduler.add("test.slow", "* * * * * *".parse().unwrap());
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+
+    assert_eq!(job.runs.load(Ordering::SeqCst), 1);
+    let statuses = scheduler.statuses().await.unwrap();
+    assert_eq!(statuses.len(), 1);
+    let status = &statuses[0];
+    assert_eq!(status.kind, "test.slow");
+    assert!(status.skipped_runs >= 1);
+    assert_eq!(status.last_job.as_ref().unwrap().status, JobStatus::Running);
+    assert!(status.next_run_at > status.last_run_at);
+}
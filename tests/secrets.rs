@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use simple_api_actix_web::config::Config;
+use simple_api_actix_web::secrets::{self, Secrets};
+
+fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn watchers_hear_about_changed_secrets_only() {
+    let secrets = Secrets::new(values(&[
+        (secrets::JWT_SECRET, "one"),
+        (secrets::SMTP_PASSWORD, "mail"),
+    ]));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorded = seen.clone();
+    secrets.watch(secrets::JWT_SECRET, move |value| {
+        recorded.lock().unwrap().push(value.to_string())
+    });
+
+    secrets.update(values(&[
+        (secrets::JWT_SECRET, "one"),
+        (secrets::SMTP_PASSWORD, "post"),
+    ]));
+    assert!(seen.lock().unwrap().is_empty());
+    assert_eq!(secrets.get(secrets::SMTP_PASSWORD).as_deref(), Some("post"));
+
+    secrets.update(values(&[(secrets::JWT_SECRET, "two")]));
+    assert_eq!(*seen.lock().unwrap(), vec!["two".to_string()]);
+    // Left out of a refresh, a secret keeps its last value.
+    assert_eq!(secrets.get(secrets::SMTP_PASSWORD).as_deref(), Some("post"));
+}
+
+#[test]
+fn loaded_secrets_override_the_config() {
+    let mut config = Config::from_env();
+    config.auth.jwt_secret = Some("from env".to_string());
+    Secrets::new(values(&[(secrets::JWT_SECRET, "from vault")])).apply(&mut config);
+    assert_eq!(config.auth.jwt_secret.as_deref(), Some("from vault"));
+
+    Secrets::default().apply(&mut config);
+    assert_eq!(config.auth.jwt_secret.as_deref(), Some("from vault"));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::config::TrustedProxies;
+use simple_api_actix_web::handler;
+
+/// The address noted on the session of a registration from `peer` with
+/// `forwarded_for` as its `X-Forwarded-For` header.
+async fn noted_address(proxies: TrustedProxies, peer: &str, forwarded_for: &str) -> String {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .app_data(proxies)
+            .configure(handler::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .peer_addr(peer.parse().unwrap())
+        .insert_header(("X-Forwarded-For", forwarded_for))
+        .set_json(json!({ "email": "someone@example.com", "password": "correct horse battery" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let req = test::TestRequest::get()
+        .uri("/api/auth/sessions")
+        .insert_header((
+            "Authorization",
+            format!("Bearer {}", body["data"]["token"].as_str().unwrap()),
+        ));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    body["sessions"][0]["ipAddress"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn forwarded_addresses_are_ignored_from_untrusted_peers() {
+    let address = noted_address(
+        TrustedProxies::default(),
+        "198.51.100.1:4000",
+        "203.0.113.7",
+    )
+    .await;
+    assert_eq!(address, "198.51.100.1");
+}
+
+#[actix_web::test]
+async fn trusted_proxies_name_the_client() {
+    let proxies: TrustedProxies = "10.0.0.1, 10.0.0.2".parse().unwrap();
+    assert_eq!(
+        noted_address(proxies.clone(), "10.0.0.2:4000", "203.0.113.7").await,
+        "203.0.113.7"
+    );
+    assert_eq!(
+        noted_address(proxies.clone(), "10.0.0.3:4000", "203.0.113.7").await,
+        "10.0.0.3"
+    );
+    assert!("10.0.0.1,proxy".parse::<TrustedProxies>().is_err());
+}
+
+#[actix_web::test]
+async fn addresses_sent_by_the_client_are_not_believed() {
+    let proxies: TrustedProxies = "10.0.0.1, 10.0.0.2".parse().unwrap();
+    // The client claimed 192.0.2.1; the proxies appended what they saw.
+    let chain = "192.0.2.1, 203.0.113.7, 10.0.0.1";
+    assert_eq!(
+        noted_address(proxies.clone(), "10.0.0.2:4000", chain).await,
+        "203.0.113.7"
+    );
+    assert_eq!(
+        noted_address(proxies, "10.0.0.2:4000", "192.0.2.1, nonsense").await,
+        "10.0.0.2"
+    );
+}
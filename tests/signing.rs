@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use simple_api_actix_web::config::SigningConfig;
+use simple_api_actix_web::error::AppError;
+use simple_api_actix_web::signing::{self, SignatureVerifier, SignedRequest};
+
+const NOW: i64 = 1_700_000_000;
+const BODY: &[u8] = br#"{"title":"Nightly export"}"#;
+
+fn verifier() -> SignatureVerifier {
+    SignatureVerifier::new(&SigningConfig {
+        clients: vec![("exporter".to_string(), "s3cret".to_string())],
+        window: Duration::from_secs(300),
+    })
+}
+
+/// Verifies a request to `POST /api/todos` signed with `secret`, sent at
+/// `sent_at` with `body` but hashing `BODY`.
+fn verify(
+    verifier: &SignatureVerifier,
+    secret: &str,
+    sent_at: i64,
+    body: &[u8],
+) -> Result<String, String> {
+    let timestamp = sent_at.to_string();
+    let content_hash = signing::content_hash(BODY);
+    let signature = signing::sign(
+        secret,
+        &signing::canonical_request("POST", "/api/todos", &timestamp, &content_hash),
+    );
+    let request = SignedRequest {
+        client_id: "exporter",
+        method: "POST",
+        path: "/api/todos",
+        timestamp: &timestamp,
+        content_hash: &content_hash,
+        signature: &signature,
+    };
+    match verifier.verify(&request, body, NOW) {
+        Ok(client) => Ok(client.id),
+        Err(AppError::Unauthorized(message)) => Err(message),
+        Err(e) => panic!("expected 401, got {:?}", e),
+    }
+}
+
+#[test]
+fn signed_requests_identify_the_client() {
+    assert_eq!(
+        verify(&verifier(), "s3cret", NOW, BODY).unwrap(),
+        "exporter"
+    );
+}
+
+#[test]
+fn requests_outside_the_window_are_rejected() {
+    let verifier = verifier();
+    assert!(verify(&verifier, "s3cret", NOW - 299, BODY).is_ok());
+    let message = verify(&verifier, "s3cret", NOW - 301, BODY).unwrap_err();
+    assert!(message.contains("301s off"), "{}", message);
+    assert!(verify(&verifier, "s3cret", NOW + 301, BODY).is_err());
+}
+
+#[test]
+fn extreme_timestamps_are_rejected() {
+    let verifier = verifier();
+    for sent_at in [i64::MIN, i64::MIN + 1, i64::MAX] {
+        let message = verify(&verifier, "s3cret", sent_at, BODY).unwrap_err();
+        assert!(message.contains("off the server clock"), "{}", message);
+    }
+}
+
+#[test]
+fn tampered_bodies_are_rejected() {
+    let message = verify(&verifier(), "s3cret", NOW, b"{}").unwrap_err();
+    assert!(message.contains("X-Content-SHA256"), "{}", message);
+}
+
+#[test]
+fn wrong_secrets_are_rejected_with_the_expected_input() {
+    let message = verify(&verifier(), "guess", NOW, BODY).unwrap_err();
+    assert!(
+        message.contains("X-Signature does not match"),
+        "{}",
+        message
+    );
+    assert!(message.contains("POST\\n/api/todos\\n"), "{}", message);
+}
+
+#[test]
+fn signatures_work_once() {
+    let verifier = verifier();
+    assert!(verify(&verifier, "s3cret", NOW, BODY).is_ok());
+    let message = verify(&verifier, "s3cret", NOW, BODY).unwrap_err();
+    assert!(message.contains("already used"), "{}", message);
+}
+
+#[test]
+fn unknown_clients_are_rejected() {
+    let verifier = SignatureVerifier::new(&SigningConfig {
+        clients: Vec::new(),
+        window: Duration::from_secs(300),
+    });
+    let message = verify(&verifier, "s3cret", NOW, BODY).unwrap_err();
+    assert_eq!(message, "Unknown client 'exporter'");
+}
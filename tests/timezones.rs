@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use simple_api_actix_web::timezones::{self, day_bounds};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Due {
+    #[serde(default, with = "timezones::local")]
+    at: Option<DateTime<Utc>>,
+}
+
+fn utc(value: &str) -> DateTime<Utc> {
+    value.parse().unwrap()
+}
+
+fn zone(name: &str) -> Tz {
+    name.parse().unwrap()
+}
+
+#[test]
+fn days_follow_the_zone() {
+    // Still the evening of the 7th in New York.
+    let (start, end) = day_bounds(utc("2026-03-08T02:00:00Z"), zone("America/New_York"));
+    assert_eq!(start, utc("2026-03-07T05:00:00Z"));
+    assert_eq!(end, utc("2026-03-08T05:00:00Z"));
+
+    // Clocks go forward early on the 8th, so that day is 23 hours long.
+    let (start, end) = day_bounds(utc("2026-03-08T12:00:00Z"), zone("America/New_York"));
+    assert_eq!(start, utc("2026-03-08T05:00:00Z"));
+    assert_eq!(end, utc("2026-03-09T04:00:00Z"));
+}
+
+#[test]
+fn a_day_whose_midnight_is_skipped_starts_at_one() {
+    // Chile moves its clocks forward at midnight.
+    let (start, _) = day_bounds(utc("2026-09-06T12:00:00Z"), zone("America/Santiago"));
+    assert_eq!(start, utc("2026-09-06T04:00:00Z"));
+}
+
+#[test]
+fn times_are_shown_in_the_zone() {
+    let due = Due {
+        at: Some(utc("2026-07-01T08:00:00Z")),
+    };
+    let json = timezones::with_zone(zone("Europe/Berlin"), || serde_json::to_string(&due));
+    assert_eq!(json.unwrap(), r#"{"at":"2026-07-01T10:00:00+02:00"}"#);
+    // Outside a request, as in UTC.
+    assert_eq!(
+        serde_json::to_string(&due).unwrap(),
+        r#"{"at":"2026-07-01T08:00:00Z"}"#
+    );
+}
+
+#[test]
+fn times_without_an_offset_are_local() {
+    let berlin = zone("Europe/Berlin");
+    let parse = |json: &str| timezones::with_zone(berlin, || serde_json::from_str::<Due>(json));
+
+    assert_eq!(
+        parse(r#"{"at":"2026-07-01T10:00:00"}"#).unwrap().at,
+        Some(utc("2026-07-01T08:00:00Z"))
+    );
+    assert_eq!(
+        parse(r#"{"at":"2026-07-01T10:00:00-04:00"}"#).unwrap().at,
+        Some(utc("2026-07-01T14:00:00Z"))
+    );
+    assert_eq!(parse("{}").unwrap().at, None);
+    // Clocks skip from 02:00 to 03:00 that night.
+    assert!(parse(r#"{"at":"2026-03-29T02:30:00"}"#).is_err());
+}
@@ -0,0 +1,1089 @@
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use chrono::{Duration, Utc};
+use common::{create_todo, register, TestContext};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+async fn create_then_get_todo() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .set_json(json!({ "title": "Buy milk", "content": "Two litres" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let location = res
+        .headers()
+        .get(header::LOCATION)
+        .expect("Location header")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body: Value = test::read_body_json(res).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap();
+    assert_eq!(location, format!("/api/todos/{}", id));
+    assert_eq!(body["data"]["todo"]["completed"], false);
+
+    let req = test::TestRequest::get().uri(&location).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["status"], "success");
+    assert_eq!(body["data"]["todo"]["title"], "Buy milk");
+    assert_eq!(body["data"]["todo"]["content"], "Two litres");
+}
+
+#[actix_web::test]
+async fn create_rejects_duplicate_title() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    create_todo(&app, "Water plants").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .set_json(json!({ "title": "Water plants", "content": "Again" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["status"], "fail");
+}
+
+#[actix_web::test]
+async fn create_rejects_title_differing_in_case_and_spacing() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    create_todo(&app, "Buy Milk").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .set_json(json!({ "title": " buy  milk ", "content": "Again" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn list_pages_through_todos() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    for title in ["First", "Second", "Third"] {
+        create_todo(&app, title).await;
+    }
+
+    let req = test::TestRequest::get().uri("/api/todos").to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 3);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?page=2&limit=2")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?limit=0")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn patch_updates_only_given_fields() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let id = create_todo(&app, "Call plumber").await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", id))
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["todo"]["completed"], true);
+    assert_eq!(body["data"]["todo"]["title"], "Call plumber");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", id))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["todo"]["completed"], true);
+}
+
+#[actix_web::test]
+async fn put_replaces_and_upserts() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let id = create_todo(&app, "Draft report").await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{}", id))
+        .set_json(json!({ "title": "Final report", "content": "Send it", "completed": true }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["todo"]["title"], "Final report");
+    assert_eq!(body["data"]["todo"]["completed"], true);
+
+    let missing = uuid::Uuid::new_v4();
+    let replacement = json!({ "title": "Fresh", "content": "New", "completed": false });
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{}", missing))
+        .set_json(&replacement)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{}?upsert=true", missing))
+        .set_json(&replacement)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn delete_removes_todo() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let id = create_todo(&app, "Take out bins").await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", id))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", id))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", id))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn unknown_and_malformed_ids() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let missing = uuid::Uuid::new_v4();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", missing))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", missing))
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/not-a-uuid")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn comments_require_an_existing_todo() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "commenter@example.com").await;
+    let id = create_todo(&app, "Plan trip").await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/comments", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "body": "Book the train" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}/comments", id))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+    assert_eq!(body["comments"][0]["body"], "Book the train");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/comments", uuid::Uuid::new_v4()))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "body": "Nobody home" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn template_instantiates_todo_with_variables() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "planner@example.com").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/templates")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({
+            "name": "Weekly report",
+            "title": "Report for {{week}}",
+            "content": "Send to {{ manager }}",
+        }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    let id = body["data"]["template"]["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        body["data"]["template"]["variables"],
+        json!(["manager", "week"])
+    );
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/templates/{}/instantiate", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "variables": { "week": "W12" } }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // A body that does not parse is refused rather than taken as empty.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/templates/{}/instantiate", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "variables": { "week": "W12", "manager": "Ada" }, "dueAt": "soon" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/templates/{}/instantiate", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "variables": { "week": "W12", "manager": "Ada" } }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["data"]["todo"]["title"], "Report for W12");
+    assert_eq!(body["data"]["todo"]["content"], "Send to Ada");
+}
+
+#[actix_web::test]
+async fn list_sorts_by_moved_position() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let first = create_todo(&app, "First in line").await;
+    let second = create_todo(&app, "Second in line").await;
+
+    let req = test::TestRequest::patch()
+        .uri("/api/todos/reorder")
+        .set_json(json!({ "ids": [first, second] }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/todos/reorder")
+        .set_json(json!({ "id": second, "before": first }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?sort_by=position&limit=2")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["todos"][0]["id"], second.as_str());
+    assert_eq!(body["todos"][1]["id"], first.as_str());
+}
+
+#[actix_web::test]
+async fn status_moves_show_in_filtered_lists() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let id = create_todo(&app, "Paint the fence").await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}/status", id))
+        .set_json(json!({ "status": "in_progress" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?status=in_progress")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+    assert_eq!(body["todos"][0]["id"], id.as_str());
+    assert_eq!(body["todos"][0]["completed"], false);
+
+    // Completing through the older flag still moves the todo to done.
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", id))
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["todo"]["status"], "done");
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?status=in_progress")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 0);
+}
+
+#[actix_web::test]
+async fn archiving_project_archives_its_todos() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    // Personal projects belong to the user who created them.
+    let bearer = format!("Bearer {}", register(&app, "gardener@example.com").await);
+
+    let req = test::TestRequest::post()
+        .uri("/api/projects")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "name": "Garden" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: Value = test::read_body_json(res).await;
+    let project_id = body["data"]["project"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "title": "Water the plants", "content": "", "projectId": project_id }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/projects/{}/todos", project_id))
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/projects/{}", project_id))
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "archived": true }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", id))
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["todo"]["archived"], true);
+}
+
+#[actix_web::test]
+async fn assignee_sees_todo_assigned_to_them() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let owner = register(&app, "lead@example.com").await;
+    let member = register(&app, "builder@example.com").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/workspaces")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", owner)))
+        .set_json(json!({ "name": "Renovation" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let workspace_id = body["data"]["workspace"]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/workspaces/{}/members", workspace_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", owner)))
+        .set_json(json!({ "email": "builder@example.com" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let member_id = body["member"]["userId"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", owner)))
+        .insert_header(("X-Workspace-Id", workspace_id.as_str()))
+        .set_json(json!({ "title": "Tile the bathroom", "content": "Grey tiles" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/assign", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", owner)))
+        .insert_header(("X-Workspace-Id", workspace_id.as_str()))
+        .set_json(json!({ "assigneeId": member_id }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?assigned_to=me")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", member)))
+        .insert_header(("X-Workspace-Id", workspace_id.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+    assert_eq!(body["todos"][0]["id"], id.as_str());
+    assert_eq!(body["todos"][0]["assigneeId"], member_id.as_str());
+}
+
+#[actix_web::test]
+async fn activity_feed_follows_a_todo() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "diarist@example.com").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "title": "Write journal", "content": "Daily entry" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap().to_string();
+
+    for change in [
+        json!({ "title": "Write the journal" }),
+        json!({ "completed": true }),
+    ] {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{}", id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(change)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    // Entries are written from the domain events, shortly after the change.
+    let mut kinds = Vec::new();
+    for _ in 0..50 {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/todos/{}/activity", id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        kinds = body["activity"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|activity| activity["type"].as_str().unwrap().to_string())
+            .collect();
+        if kinds.len() == 3 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(kinds, ["created", "title_edited", "completed"]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/activity?type=completed")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+    assert_eq!(body["activity"][0]["todoId"], id.as_str());
+}
+
+#[actix_web::test]
+async fn undo_restores_deleted_todo() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "second-thoughts@example.com").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "title": "Keep receipts", "content": "For taxes" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::post()
+        .uri("/api/undo")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["action"], "delete");
+    assert_eq!(body["data"]["todos"][0]["id"], id.as_str());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // The delete has been undone, so there is nothing left to undo.
+    let req = test::TestRequest::post()
+        .uri("/api/undo")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn sync_resolves_conflicts_and_reports_deletes() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "offline@example.com").await;
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", token));
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth.clone())
+        .set_json(json!({ "title": "Pack for the trip", "content": "" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::get()
+        .uri("/api/sync")
+        .insert_header(auth.clone())
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["changes"][0]["id"], id.as_str());
+    assert_eq!(body["changes"][0]["version"], json!({ "server": 1 }));
+    let since = body["nextToken"].as_str().unwrap().to_string();
+
+    // An edit made on top of the server's version is applied.
+    let req = test::TestRequest::post()
+        .uri("/api/sync")
+        .insert_header(auth.clone())
+        .set_json(json!({
+            "replicaId": "phone",
+            "changes": [{
+                "id": id,
+                "todo": { "title": "Pack for the trip", "content": "Passport", "completed": false },
+                "version": { "server": 1, "phone": 1 },
+                "modifiedAt": "2030-01-01T00:00:00Z"
+            }]
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"][0]["outcome"], "applied");
+    assert_eq!(body["results"][0]["current"]["todo"]["content"], "Passport");
+
+    // One made on an older version loses to it.
+    let req = test::TestRequest::post()
+        .uri("/api/sync")
+        .insert_header(auth.clone())
+        .set_json(json!({
+            "replicaId": "tablet",
+            "changes": [{
+                "id": id,
+                "todo": { "title": "Pack for the trip", "content": "Snacks", "completed": false },
+                "version": { "server": 1 },
+                "modifiedAt": "2030-01-02T00:00:00Z"
+            }]
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"][0]["outcome"], "conflict");
+    assert_eq!(body["results"][0]["current"]["todo"]["content"], "Passport");
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", id))
+        .insert_header(auth.clone())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/sync?since={}", since))
+        .insert_header(auth)
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 1);
+    assert_eq!(body["changes"][0]["id"], id.as_str());
+    assert_eq!(body["changes"][0]["deleted"], true);
+    assert!(body["changes"][0].get("todo").is_none());
+}
+
+#[actix_web::test]
+async fn refresh_tokens_rotate_and_detect_reuse() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": "rotate@example.com", "password": "correct horse battery" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let first = body["data"]["refreshToken"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": first }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["data"]["token"].is_string());
+    let second = body["data"]["refreshToken"].as_str().unwrap().to_string();
+    assert_ne!(first, second);
+
+    // Replaying the spent token revokes everything issued from that login.
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": first }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": second }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // Logging out ends a fresh login the same way.
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "email": "rotate@example.com", "password": "correct horse battery" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let third = body["data"]["refreshToken"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/logout")
+        .set_json(json!({ "refreshToken": third }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": third }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn forgot_password_is_rate_limited() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    register(&app, "forgetful@example.com").await;
+
+    for _ in 0..5 {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/forgot-password")
+            .set_json(json!({ "email": "forgetful@example.com" }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/forgot-password")
+        .set_json(json!({ "email": "forgetful@example.com" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(res.headers().contains_key(header::RETRY_AFTER));
+}
+
+#[actix_web::test]
+async fn repeated_failed_logins_lock_the_account() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    register(&app, "guessed@example.com").await;
+
+    let login = |password: &str| {
+        test::TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "email": "guessed@example.com", "password": password }))
+            .to_request()
+    };
+    for _ in 0..5 {
+        let res = test::call_service(&app, login("hunter2 hunter2")).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // Locked even with the right password.
+    let res = test::call_service(&app, login("correct horse battery")).await;
+    assert_eq!(res.status(), StatusCode::LOCKED);
+    assert!(res.headers().contains_key(header::RETRY_AFTER));
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["unlockAt"].is_string());
+}
+
+#[actix_web::test]
+async fn totp_is_asked_for_after_the_password() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "careful@example.com").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/totp/enroll")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["data"]["otpauthUri"]
+        .as_str()
+        .unwrap()
+        .starts_with("otpauth://totp/"));
+    let secret = data_encoding::BASE32_NOPAD
+        .decode(body["data"]["secret"].as_str().unwrap().as_bytes())
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/totp/confirm")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .set_json(json!({ "code": simple_api_actix_web::totp::code(&secret, chrono::Utc::now()) }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let backup_code = body["data"]["backupCodes"][0].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "email": "careful@example.com", "password": "correct horse battery" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["mfaRequired"], true);
+    assert!(body["data"]["token"].is_null());
+    let mfa_token = body["data"]["mfaToken"].as_str().unwrap().to_string();
+
+    let login = |code: &str| {
+        test::TestRequest::post()
+            .uri("/api/auth/login/totp")
+            .set_json(json!({ "mfaToken": mfa_token, "code": code }))
+            .to_request()
+    };
+    let res = test::call_service(&app, login("000000")).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = test::call_service(&app, login(&backup_code)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(res).await;
+    let payload = body["data"]["token"]
+        .as_str()
+        .unwrap()
+        .split('.')
+        .nth(1)
+        .unwrap();
+    let claims: Value = serde_json::from_slice(
+        &base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, payload)
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(claims["amr"], json!(["pwd", "otp"]));
+
+    // Backup codes work once.
+    let res = test::call_service(&app, login(&backup_code)).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn sessions_can_be_listed_and_revoked() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    register(&app, "roaming@example.com").await;
+
+    let login = |user_agent: &str| {
+        test::TestRequest::post()
+            .uri("/api/auth/login")
+            .insert_header((header::USER_AGENT, user_agent))
+            .set_json(
+                json!({ "email": "roaming@example.com", "password": "correct horse battery" }),
+            )
+            .to_request()
+    };
+    let phone: Value = test::call_and_read_body_json(&app, login("Phone/1.0")).await;
+    let laptop: Value = test::call_and_read_body_json(&app, login("Laptop/2.0")).await;
+    let token = laptop["data"]["token"].as_str().unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/auth/sessions")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 3);
+    let sessions = body["sessions"].as_array().unwrap();
+    let current: Vec<&Value> = sessions.iter().filter(|s| s["current"] == true).collect();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0]["userAgent"], "Laptop/2.0");
+    let phone_session = sessions
+        .iter()
+        .find(|s| s["userAgent"] == "Phone/1.0")
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/auth/sessions/{}", phone_session))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/refresh")
+        .set_json(json!({ "refreshToken": phone["data"]["refreshToken"] }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/auth/sessions/{}", phone_session))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn due_dates_follow_the_users_time_zone() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "islander@example.com").await;
+    let bearer = format!("Bearer {}", token);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/me/settings")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "timezone": "Pacific/Kiritimati" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["settings"]["timezone"], "Pacific/Kiritimati");
+
+    // Fourteen hours ahead of UTC, so its days rarely line up with UTC's.
+    let today = (Utc::now() + Duration::hours(14)).date_naive();
+    let yesterday = today - Duration::days(1);
+    for (title, due_at) in [
+        ("Feed the fish", format!("{}T23:30:00", today)),
+        ("Post the letter", format!("{}T09:00:00", yesterday)),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .set_json(json!({ "title": title, "content": "", "dueAt": due_at }))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            body["data"]["todo"]["dueAt"],
+            format!("{}+14:00", due_at).as_str()
+        );
+    }
+
+    for (due, title) in [("today", "Feed the fish"), ("overdue", "Post the letter")] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/todos?mine=true&due={}", due))
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["results"], 1, "due={}", due);
+        assert_eq!(body["todos"][0]["title"], title);
+    }
+}
+
+#[actix_web::test]
+async fn lists_follow_the_users_settings() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "tidy@example.com").await;
+    let bearer = format!("Bearer {}", token);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/me/settings")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({
+            "pageSize": 2,
+            "defaultSort": "position",
+            "notifications": { "reminders": true },
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["settings"]["pageSize"], 2);
+    assert_eq!(body["settings"]["defaultSort"], "position");
+    assert_eq!(body["settings"]["timezone"], "UTC");
+    assert_eq!(body["settings"]["notifications"]["reminders"], true);
+    assert_eq!(body["settings"]["notifications"]["digest"], false);
+
+    for (title, position) in [("Third", 3), ("First", 1), ("Second", 2)] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .set_json(json!({ "title": title, "content": "", "position": position }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?mine=true")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["results"], 2);
+    assert_eq!(body["todos"][0]["title"], "First");
+    assert_eq!(body["todos"][1]["title"], "Second");
+
+    let req = test::TestRequest::get()
+        .uri("/api/me/settings")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["settings"]["pageSize"], 2);
+    assert_eq!(body["settings"]["notifications"]["reminders"], true);
+}
+
+#[actix_web::test]
+async fn deleting_the_account_erases_its_todos() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "leaving@example.com").await;
+    let bearer = format!("Bearer {}", token);
+
+    let req = test::TestRequest::patch()
+        .uri("/api/me")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({
+            "email": "Gone@Example.com",
+            "displayName": " Gone ",
+            "avatarUrl": "https://example.com/gone.png",
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    // A new address waits for the confirmation emailed to it.
+    assert_eq!(body["data"]["user"]["email"], "leaving@example.com");
+    assert_eq!(body["data"]["user"]["displayName"], "Gone");
+    assert_eq!(
+        body["data"]["user"]["avatarUrl"],
+        "https://example.com/gone.png"
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "title": "Private thoughts", "content": "" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["data"]["todo"]["id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::delete()
+        .uri("/api/me")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", id))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/api/me")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "email": "leaving@example.com", "password": "correct horse battery" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn exports_hold_the_users_todos() {
+    let ctx = TestContext::start().await;
+    let app = test::init_service(ctx.app()).await;
+    let token = register(&app, "curious@example.com").await;
+    let bearer = format!("Bearer {}", token);
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .set_json(json!({ "title": "Mine to keep", "content": "" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::get()
+        .uri("/api/me/export")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri("/api/me/export")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    let location = res
+        .headers()
+        .get(header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let mut status = Value::Null;
+    for _ in 0..50 {
+        let req = test::TestRequest::get()
+            .uri(&location)
+            .insert_header((header::AUTHORIZATION, bearer.as_str()))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        status = body["data"]["status"].clone();
+        if status == "succeeded" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(status, "succeeded");
+
+    let req = test::TestRequest::get()
+        .uri("/api/me/export")
+        .insert_header((header::AUTHORIZATION, bearer.as_str()))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["user"]["email"], "curious@example.com");
+    assert_eq!(body["todos"][0]["title"], "Mine to keep");
+    assert!(body["user"]["passwordHash"].is_null());
+
+    // Other users cannot follow the job.
+    let other = register(&app, "nosy@example.com").await;
+    let req = test::TestRequest::get()
+        .uri(&location)
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", other)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use data_encoding::BASE32_NOPAD;
+use simple_api_actix_web::config::{AuthConfig, Config};
+use simple_api_actix_web::model::{Role, User};
+use simple_api_actix_web::repository::{InMemoryUserRepository, UserRepository};
+use simple_api_actix_web::totp::{self, TotpService};
+
+const KEY: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+fn auth_config(jwt_secret: Option<&str>, encryption_key: Option<&str>) -> AuthConfig {
+    let mut config = Config::from_env().auth;
+    config.jwt_secret = jwt_secret.map(str::to_string);
+    config.totp.encryption_key = encryption_key.map(str::to_string);
+    config
+}
+
+async fn user(users: &InMemoryUserRepository) -> User {
+    let now = Utc::now();
+    let user = User {
+        id: uuid::Uuid::new_v4().to_string(),
+        email: "someone@example.com".to_string(),
+        password_hash: String::new(),
+        display_name: None,
+        avatar_url: None,
+        role: Role::User,
+        created_at: now,
+        updated_at: now,
+    };
+    users.insert(&user).await.unwrap();
+    user
+}
+
+#[test]
+fn codes_match_the_rfc_6238_vectors() {
+    // The SHA-1 vectors of RFC 6238 appendix B, cut to six digits.
+    let secret = b"12345678901234567890";
+    for (time, expected) in [
+        (59, "287082"),
+        (1_111_111_109, "081804"),
+        (1_111_111_111, "050471"),
+        (1_234_567_890, "005924"),
+        (2_000_000_000, "279037"),
+        (20_000_000_000, "353130"),
+    ] {
+        let at = Utc.timestamp_opt(time, 0).unwrap();
+        assert_eq!(totp::code(secret, at), expected, "at {}", time);
+    }
+}
+
+#[test]
+fn codes_change_every_thirty_seconds() {
+    let secret = b"12345678901234567890";
+    let at = |time| Utc.timestamp_opt(time, 0).unwrap();
+    assert_eq!(totp::code(secret, at(60)), totp::code(secret, at(89)));
+    assert_ne!(totp::code(secret, at(89)), totp::code(secret, at(90)));
+}
+
+#[tokio::test]
+async fn enrollments_survive_a_rotated_jwt_secret() {
+    let users = Arc::new(InMemoryUserRepository::new());
+    let user = user(&users).await;
+    let service = TotpService::new(users.clone(), &auth_config(Some("first"), Some(KEY))).unwrap();
+    let enrollment = service.enroll(&user).await.unwrap();
+    let secret = BASE32_NOPAD.decode(enrollment.secret.as_bytes()).unwrap();
+    service
+        .confirm(&user.id, &totp::code(&secret, Utc::now()))
+        .await
+        .unwrap();
+
+    // The step after the one just used, which the skew still accepts.
+    let next = totp::code(&secret, Utc::now() + chrono::Duration::seconds(30));
+    let rotated = TotpService::new(users, &auth_config(Some("second"), Some(KEY))).unwrap();
+    assert!(rotated.verify(&user.id, &next).await.unwrap());
+}
+
+#[tokio::test]
+async fn enrolling_without_a_dedicated_key_is_refused() {
+    let users = Arc::new(InMemoryUserRepository::new());
+    let user = user(&users).await;
+    let service = TotpService::new(users, &auth_config(Some("secret"), None)).unwrap();
+    assert!(service.enroll(&user).await.is_err());
+}
@@ -0,0 +1,37 @@
+use actix_web::http::StatusCode;
+use actix_web::{middleware, test, web, App, HttpResponse};
+use simple_api_actix_web::build_info::{self, VERSION_HEADER};
+use simple_api_actix_web::casing::{self, FIELD_CASE_HEADER};
+
+#[actix_web::test]
+async fn every_response_names_the_version() {
+    let app = test::init_service(
+        App::new()
+            .route("/todos", web::get().to(HttpResponse::Ok))
+            .wrap(middleware::from_fn(casing::apply_field_case))
+            .wrap(middleware::from_fn(build_info::add_version_header)),
+    )
+    .await;
+
+    let served = test::TestRequest::get().uri("/todos");
+    let rejected = test::TestRequest::get()
+        .uri("/todos")
+        .insert_header((FIELD_CASE_HEADER, "kebab"));
+    let missing = test::TestRequest::get().uri("/missing");
+    for (req, status) in [
+        (served, StatusCode::OK),
+        (rejected, StatusCode::BAD_REQUEST),
+        (missing, StatusCode::NOT_FOUND),
+    ] {
+        let res = match test::try_call_service(&app, req.to_request()).await {
+            Ok(res) => res.into_parts().1.map_into_boxed_body(),
+            // Rejected by the casing middleware; the server makes the
+            // response from the error.
+            Err(e) => e.error_response(),
+        };
+        assert_eq!(res.status(), status);
+        let version = res.headers().get(VERSION_HEADER).unwrap().to_str().unwrap();
+        assert_eq!(version, build_info::version());
+        assert!(version.starts_with(build_info::VERSION));
+    }
+}
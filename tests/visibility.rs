@@ -0,0 +1,297 @@
+mod common;
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App};
+use serde_json::{json, Value};
+use simple_api_actix_web::handler;
+use simple_api_actix_web::repository::{TodoFilter, TodoScope, TodoVisibility};
+
+/// Registers `email` and returns the `Authorization` header value for it.
+async fn register<S>(app: &S, email: &str) -> String
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "email": email, "password": "correct horse battery" }));
+    let body: Value = test::call_and_read_body_json(app, req.to_request()).await;
+    format!("Bearer {}", body["data"]["token"].as_str().unwrap())
+}
+
+/// Titles of the todos `uri` lists for `bearer`, or for an anonymous caller.
+async fn titles<S>(app: &S, uri: &str, bearer: Option<&str>) -> Vec<String>
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let mut req = test::TestRequest::get().uri(uri);
+    if let Some(bearer) = bearer {
+        req = req.insert_header((header::AUTHORIZATION, bearer));
+    }
+    let body: Value = test::call_and_read_body_json(app, req.to_request()).await;
+    body["todos"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|todo| todo["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[actix_web::test]
+async fn listings_leave_out_other_users_private_todos() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let alice = register(&app, "alice@example.com").await;
+    let bob = register(&app, "bob@example.com").await;
+
+    let mut ids = Vec::new();
+    for title in ["Diary", "Groceries"] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header((header::AUTHORIZATION, alice.as_str()))
+            .set_json(json!({ "title": title, "content": "" }));
+        let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        ids.push(body["data"]["todo"]["id"].as_str().unwrap().to_string());
+    }
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/share", ids[1]))
+        .insert_header((header::AUTHORIZATION, alice.as_str()))
+        .set_json(json!({ "email": "bob@example.com" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert!(res.status().is_success());
+
+    assert_eq!(
+        titles(&app, "/api/todos", Some(&alice)).await,
+        ["Diary", "Groceries"]
+    );
+    assert_eq!(titles(&app, "/api/todos", Some(&bob)).await, ["Groceries"]);
+    assert!(titles(&app, "/api/todos", None).await.is_empty());
+    assert!(titles(&app, "/api/todos/by-title/Diary", Some(&bob))
+        .await
+        .is_empty());
+    assert!(titles(&app, "/api/todos/by-title/Diary", None)
+        .await
+        .is_empty());
+    assert_eq!(
+        titles(&app, "/api/todos/by-title/Diary", Some(&alice)).await,
+        ["Diary"]
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?stream=true")
+        .insert_header((header::AUTHORIZATION, bob.as_str()));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = test::read_body(res).await;
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert_eq!(body.lines().count(), 1);
+    assert!(body.contains("Groceries"));
+}
+
+/// Creates a todo for `bearer` and returns its ID.
+async fn create<S>(app: &S, bearer: &str, todo: Value) -> String
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, bearer))
+        .set_json(todo);
+    let body: Value = test::call_and_read_body_json(app, req.to_request()).await;
+    body["data"]["todo"]["id"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn bulk_changes_leave_other_users_private_todos_alone() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let alice = register(&app, "alice@example.com").await;
+    let bob = register(&app, "bob@example.com").await;
+
+    let diary = create(
+        &app,
+        &alice,
+        json!({ "title": "Diary", "content": "", "status": "done" }),
+    )
+    .await;
+    let groceries = create(&app, &alice, json!({ "title": "Groceries", "content": "" })).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/share", groceries))
+        .insert_header((header::AUTHORIZATION, alice.as_str()))
+        .set_json(json!({ "email": "bob@example.com", "permission": "write" }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert!(res.status().is_success());
+
+    // Completing needs write access, which the share grants for Groceries only.
+    let req = test::TestRequest::patch()
+        .uri("/api/todos/complete")
+        .insert_header((header::AUTHORIZATION, bob.as_str()))
+        .set_json(json!({ "ids": [diary, groceries] }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["results"][0]["status"], "not_found");
+    assert_eq!(body["results"][1]["status"], "updated");
+    let req = test::TestRequest::patch()
+        .uri("/api/todos/incomplete")
+        .set_json(json!({ "ids": [diary] }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["results"][0]["status"], "not_found");
+
+    // Deleting needs ownership, which no share grants.
+    for bearer in [Some(bob.as_str()), None] {
+        for (uri, ids) in [
+            ("/api/todos", Some(json!([diary, groceries]))),
+            ("/api/todos?completed=true", None),
+        ] {
+            let mut req = test::TestRequest::delete().uri(uri);
+            if let Some(ids) = ids {
+                req = req.set_json(ids);
+            }
+            if let Some(bearer) = bearer {
+                req = req.insert_header((header::AUTHORIZATION, bearer));
+            }
+            let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+            assert_eq!(body["deleted"], 0, "{} as {:?}", uri, bearer);
+        }
+    }
+    assert_eq!(
+        titles(&app, "/api/todos", Some(&alice)).await,
+        ["Diary", "Groceries"]
+    );
+
+    // The filter itself only picks todos the caller may delete.
+    let bob_id = state
+        .users
+        .find_by_email("bob@example.com")
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let picked = state
+        .todos
+        .find_ids(&TodoFilter {
+            completed: Some(true),
+            scope: TodoScope::Personal,
+            visibility: TodoVisibility::Caller {
+                user_id: Some(bob_id),
+                shared: Vec::new(),
+            },
+            ..TodoFilter::default()
+        })
+        .await
+        .unwrap();
+    assert!(picked.is_empty());
+
+    let req = test::TestRequest::delete()
+        .uri("/api/todos?completed=true")
+        .insert_header((header::AUTHORIZATION, alice.as_str()));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["deleted"], 2);
+    let req = test::TestRequest::post()
+        .uri("/api/undo")
+        .insert_header((header::AUTHORIZATION, alice.as_str()));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        titles(&app, "/api/todos", Some(&alice)).await,
+        ["Diary", "Groceries"]
+    );
+}
+
+#[actix_web::test]
+async fn reminders_leave_out_other_users_private_todos() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let alice = register(&app, "alice@example.com").await;
+    let bob = register(&app, "bob@example.com").await;
+    create(
+        &app,
+        &alice,
+        json!({ "title": "Diary", "content": "", "remindAt": "2099-01-01T09:00:00Z" }),
+    )
+    .await;
+
+    for (bearer, expected) in [(Some(&alice), 1), (Some(&bob), 0), (None, 0)] {
+        let mut req = test::TestRequest::get().uri("/api/reminders");
+        if let Some(bearer) = bearer {
+            req = req.insert_header((header::AUTHORIZATION, bearer.as_str()));
+        }
+        let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        assert_eq!(body["results"], expected);
+    }
+}
+
+#[actix_web::test]
+async fn personal_projects_belong_to_their_creator() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let alice = register(&app, "alice@example.com").await;
+    let bob = register(&app, "bob@example.com").await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/projects")
+        .insert_header((header::AUTHORIZATION, alice.as_str()))
+        .set_json(json!({ "name": "Garden" }));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    let project_id = body["data"]["project"]["id"].as_str().unwrap().to_string();
+    let project = format!("/api/projects/{}", project_id);
+
+    let req = test::TestRequest::get()
+        .uri("/api/projects")
+        .insert_header((header::AUTHORIZATION, bob.as_str()));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["results"], 0);
+    for req in [
+        test::TestRequest::get().uri(&project),
+        test::TestRequest::patch()
+            .uri(&project)
+            .set_json(json!({ "archived": true })),
+        test::TestRequest::delete().uri(&project),
+    ] {
+        let req = req.insert_header((header::AUTHORIZATION, bob.as_str()));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header((header::AUTHORIZATION, bob.as_str()))
+        .set_json(json!({ "title": "Weed the beds", "content": "", "projectId": project_id }));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    for req in [
+        test::TestRequest::get().uri("/api/projects"),
+        test::TestRequest::get().uri(&project),
+        test::TestRequest::post()
+            .uri("/api/projects")
+            .set_json(json!({ "name": "Shed" })),
+    ] {
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&project)
+        .insert_header((header::AUTHORIZATION, alice.as_str()));
+    let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+    assert_eq!(body["data"]["project"]["archived"], false);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use chrono::Utc;
+use serde_json::{json, Value};
+use simple_api_actix_web::handler;
+use simple_api_actix_web::model::{AppState, Role};
+use simple_api_actix_web::webhooks::check_destination;
+
+/// Registers `email`, gives it `role` and returns a bearer token for it.
+async fn sign_in<S>(app: &S, state: &AppState, email: &str, role: Role) -> String
+where
+    S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let credentials = json!({ "email": email, "password": "correct horse battery" });
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(&credentials);
+    test::call_service(app, req.to_request()).await;
+    let user = state.users.find_by_email(email).await.unwrap().unwrap();
+    state
+        .users
+        .update_role(&user.id, role, Utc::now())
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(&credentials);
+    let body: Value = test::call_and_read_body_json(app, req.to_request()).await;
+    body["data"]["token"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn only_admins_manage_webhooks() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let token = sign_in(&app, &state, "someone@example.com", Role::User).await;
+
+    let req = test::TestRequest::get().uri("/api/webhooks");
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    for req in [
+        test::TestRequest::get().uri("/api/webhooks"),
+        test::TestRequest::get().uri("/api/webhooks/dead-letters"),
+        test::TestRequest::post()
+            .uri("/api/webhooks")
+            .set_json(json!({ "url": "https://93.184.216.34/hook", "secret": "s3cret" })),
+    ] {
+        let req = req.insert_header(("Authorization", format!("Bearer {}", token)));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+#[actix_web::test]
+async fn webhooks_cannot_target_internal_addresses() {
+    let state = web::Data::new(common::memory_state(|_| {}).await);
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(handler::config),
+    )
+    .await;
+    let token = sign_in(&app, &state, "root@example.com", Role::Admin).await;
+    let create = |url: &str| {
+        test::TestRequest::post()
+            .uri("/api/webhooks")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "url": url, "secret": "s3cret" }))
+            .to_request()
+    };
+
+    for url in [
+        "http://127.0.0.1:8000/hook",
+        "http://10.1.2.3/hook",
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::1]/hook",
+        "http://[::ffff:192.168.0.1]/hook",
+        "ftp://93.184.216.34/hook",
+    ] {
+        let res = test::call_service(&app, create(url)).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", url);
+    }
+
+    let res = test::call_service(&app, create("https://93.184.216.34/hook")).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn internal_address_ranges_are_refused() {
+    for url in [
+        "http://0.0.0.0/",
+        "http://0.1.2.3/",
+        "http://224.0.0.1/",
+        "http://100.64.0.1/",
+        "http://172.16.0.1/",
+        "http://192.168.1.1/",
+        "http://255.255.255.255/",
+        "http://[fd00::1]/",
+        "http://[fe80::1]/",
+        "http://[::]/",
+        "http://[ff02::1]/",
+        "http://[64:ff9b::7f00:1]/",
+        "http://[64:ff9b::a9fe:a9fe]/",
+    ] {
+        assert!(check_destination(url).await.is_err(), "{}", url);
+    }
+    assert!(check_destination("http://8.8.8.8/").await.is_ok());
+    assert!(check_destination("http://[2606:4700::1111]/").await.is_ok());
+    assert!(check_destination("http://[64:ff9b::808:808]/")
+        .await
+        .is_ok());
+    assert!(check_destination("not a url").await.is_err());
+}
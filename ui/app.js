@@ -0,0 +1,100 @@
+// Lists, creates, completes and deletes todos through the v1 API.
+const API = "/api/v1/todos";
+const LIMIT = 20;
+let page = 1;
+
+const list = document.getElementById("todos");
+const error = document.getElementById("error");
+
+// With a cookie session, writes must repeat the CSRF cookie in a header.
+function csrfToken() {
+  const cookie = document.cookie.split("; ").find((c) => c.startsWith("csrf_token="));
+  return cookie && cookie.slice("csrf_token=".length);
+}
+
+async function request(method, url, body) {
+  const headers = body ? { "Content-Type": "application/json" } : {};
+  const token = csrfToken();
+  if (token && method !== "GET") {
+    headers["X-CSRF-Token"] = token;
+  }
+  const response = await fetch(url, {
+    method,
+    headers,
+    body: body ? JSON.stringify(body) : undefined,
+  });
+  if (!response.ok) {
+    const problem = await response.json().catch(() => ({}));
+    throw new Error(problem.message || `${response.status} ${response.statusText}`);
+  }
+  return response.status === 204 ? null : response.json();
+}
+
+function showError(e) {
+  error.textContent = e.message;
+  error.hidden = false;
+}
+
+function render(todo) {
+  const item = document.createElement("li");
+  item.classList.toggle("completed", Boolean(todo.completed));
+
+  const completed = document.createElement("input");
+  completed.type = "checkbox";
+  completed.checked = Boolean(todo.completed);
+  completed.addEventListener("change", () =>
+    update(request("PATCH", `${API}/${todo.id}`, { completed: completed.checked })));
+
+  const title = document.createElement("span");
+  title.className = "title";
+  title.textContent = todo.title;
+  title.title = todo.content;
+
+  const remove = document.createElement("button");
+  remove.type = "button";
+  remove.textContent = "Delete";
+  remove.addEventListener("click", () => update(request("DELETE", `${API}/${todo.id}`)));
+
+  item.append(completed, title, remove);
+  return item;
+}
+
+async function load() {
+  const body = await request("GET", `${API}?page=${page}&limit=${LIMIT}`);
+  list.replaceChildren(...body.todos.map(render));
+  document.getElementById("empty").hidden = body.todos.length > 0;
+  document.getElementById("page").textContent = `Page ${page}`;
+  document.getElementById("previous").disabled = page === 1;
+  document.getElementById("next").disabled = body.todos.length < LIMIT;
+}
+
+// Reloads the list once `change` is done, showing any error either hits.
+async function update(change) {
+  try {
+    await change;
+    error.hidden = true;
+    await load();
+  } catch (e) {
+    showError(e);
+  }
+}
+
+document.getElementById("create").addEventListener("submit", (event) => {
+  event.preventDefault();
+  const form = event.target;
+  const { title, content } = form.elements;
+  const todo = { title: title.value, content: content.value };
+  update(request("POST", API, todo).then(() => form.reset()));
+});
+
+document.getElementById("previous").addEventListener("click", () => {
+  page -= 1;
+  update(Promise.resolve());
+});
+
+document.getElementById("next").addEventListener("click", () => {
+  page += 1;
+  update(Promise.resolve());
+});
+
+update(Promise.resolve());